@@ -0,0 +1,7 @@
+fn main() {
+    // The gRPC server is generated from its proto file only when the feature is enabled,
+    // so default builds don't need protoc.
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/prompt_store.proto")
+        .unwrap_or_else(|e| panic!("Failed to compile proto/prompt_store.proto: {}", e));
+}
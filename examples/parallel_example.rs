@@ -51,7 +51,7 @@ async fn main() -> Result<(), RunError> {
         // 4. This step runs only if the summary contains the word "safety"
         .step_if("tweet", "Generate Tweet", |ctx| {
             ctx.get("summary")
-                .map_or(false, |s| s.to_lowercase().contains("safety"))
+                .is_some_and(|s| s.to_lowercase().contains("safety"))
         })
         .with_provider("openai")
         .vars([("query", user_query)])
@@ -0,0 +1,43 @@
+/*
+ * C interface of prompt-store, built with `cargo build --release --features ffi`
+ * (target/release/libprompt_store.so, .dylib or prompt_store.dll).
+ *
+ * Strings are NUL-terminated UTF-8. Strings returned are owned by the caller and freed
+ * with ps_string_free. A function that fails returns NULL; ps_last_error tells why.
+ */
+#ifndef PROMPT_STORE_H
+#define PROMPT_STORE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct StoreHandle PsStore;
+
+/* Unlocks the store; password may be NULL when the key isn't protected by one. */
+PsStore *ps_store_open(const char *password);
+
+/* Locks and frees a store. */
+void ps_store_free(PsStore *store);
+
+/* Returns a prompt, by ID or title, as a JSON object. */
+char *ps_prompt_find(const PsStore *store, const char *id);
+
+/* Renders a prompt; vars_json is a JSON object of strings, or NULL. */
+char *ps_prompt_render(const PsStore *store, const char *id, const char *vars_json);
+
+/* Runs a prompt on backend, a provider name from config.toml, and returns the answer. */
+char *ps_prompt_run(const PsStore *store, const char *id, const char *vars_json,
+                    const char *backend);
+
+/* Why the last call failed on this thread, or NULL. Do not free. */
+const char *ps_last_error(void);
+
+/* Frees a string returned by a ps_prompt_* function. */
+void ps_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PROMPT_STORE_H */
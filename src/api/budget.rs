@@ -0,0 +1,151 @@
+//! Input budgets keeping the rendered prompt of a chain step within a context window.
+
+use llm::chain::LLMRegistry;
+use llm::chat::ChatMessage;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::core::template::render;
+
+use super::error::{RunError, StoreError};
+
+/// Maximum size of the rendered prompt of a step, and how to shrink the variables it
+/// uses when they make it larger.
+///
+/// In YAML chain files, it is the `context:` block of a step:
+/// `context: { max_chars: 8000, strategy: truncate_head }`, or with
+/// `strategy: summarize` and `provider: <cheap model>`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContextBudget {
+    /// Maximum number of characters of the rendered prompt.
+    pub max_chars: usize,
+    /// How variables are shrunk, largest first, until the prompt fits.
+    #[serde(flatten)]
+    pub strategy: BudgetStrategy,
+}
+
+/// How a variable making a prompt exceed its budget is shrunk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum BudgetStrategy {
+    /// Drops the beginning of the variable, keeping its end (e.g., the latest log lines).
+    TruncateHead,
+    /// Drops the end of the variable, keeping its beginning.
+    TruncateTail,
+    /// Asks a provider, usually a cheap model, to summarize the variable to the size
+    /// left for it. The summary is truncated if it is still too long.
+    Summarize {
+        /// ID of the provider in the registry.
+        provider: String,
+    },
+}
+
+impl ContextBudget {
+    /// A budget keeping the end of oversized variables.
+    pub fn truncate_head(max_chars: usize) -> Self {
+        Self {
+            max_chars,
+            strategy: BudgetStrategy::TruncateHead,
+        }
+    }
+
+    /// A budget keeping the beginning of oversized variables.
+    pub fn truncate_tail(max_chars: usize) -> Self {
+        Self {
+            max_chars,
+            strategy: BudgetStrategy::TruncateTail,
+        }
+    }
+
+    /// A budget summarizing oversized variables with the given provider.
+    pub fn summarize(max_chars: usize, provider: &str) -> Self {
+        Self {
+            max_chars,
+            strategy: BudgetStrategy::Summarize {
+                provider: provider.to_string(),
+            },
+        }
+    }
+
+    /// Shrinks the variables used by `template`, largest first, until it renders within
+    /// the budget. Variables are left untouched if the template alone is too large.
+    pub(crate) async fn fit(
+        &self,
+        template: &str,
+        mut vars: HashMap<String, String>,
+        reg: &LLMRegistry,
+    ) -> Result<HashMap<String, String>, RunError> {
+        let used = template_vars(template);
+        let mut shrunk: Vec<&str> = Vec::new();
+        loop {
+            let size = render(template, &vars).chars().count();
+            if size <= self.max_chars {
+                return Ok(vars);
+            }
+            let Some((name, occurrences)) = used
+                .iter()
+                .filter(|(name, _)| !shrunk.contains(&name.as_str()))
+                .max_by_key(|(name, _)| vars.get(name).map_or(0, |v| v.chars().count()))
+            else {
+                return Ok(vars);
+            };
+            shrunk.push(name);
+            let Some(value) = vars.get(name) else {
+                continue;
+            };
+            let len = value.chars().count();
+            let excess = (size - self.max_chars).div_ceil(*occurrences);
+            let target = len.saturating_sub(excess);
+            let value = match &self.strategy {
+                BudgetStrategy::TruncateHead => keep_end(value, target),
+                BudgetStrategy::TruncateTail => keep_start(value, target),
+                BudgetStrategy::Summarize { provider } => {
+                    keep_start(&summarize(reg, provider, value, target).await?, target)
+                }
+            };
+            vars.insert(name.clone(), value);
+        }
+    }
+}
+
+/// The variables referenced by a template, with their number of occurrences.
+fn template_vars(template: &str) -> Vec<(String, usize)> {
+    let re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
+    let mut found: Vec<(String, usize)> = Vec::new();
+    for caps in re.captures_iter(template) {
+        match found.iter_mut().find(|(name, _)| *name == caps[1]) {
+            Some((_, count)) => *count += 1,
+            None => found.push((caps[1].to_string(), 1)),
+        }
+    }
+    found
+}
+
+fn keep_start(text: &str, chars: usize) -> String {
+    text.chars().take(chars).collect()
+}
+
+fn keep_end(text: &str, chars: usize) -> String {
+    let skip = text.chars().count().saturating_sub(chars);
+    text.chars().skip(skip).collect()
+}
+
+async fn summarize(
+    reg: &LLMRegistry,
+    provider_id: &str,
+    text: &str,
+    max_chars: usize,
+) -> Result<String, RunError> {
+    let provider = reg.get(provider_id).ok_or_else(|| {
+        StoreError::Configuration(format!("Provider '{}' not found in registry", provider_id))
+    })?;
+    let request = ChatMessage::user()
+        .content(format!(
+            "Summarize the following text in at most {} characters. Keep the facts, names and figures. Answer with the summary only.\n\n{}",
+            max_chars, text
+        ))
+        .build();
+    let response = provider.chat(&[request]).await?;
+    Ok(response.text().unwrap_or_default())
+}
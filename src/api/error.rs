@@ -1,6 +1,7 @@
 //! Error types for the library API.
 
 use llm::error::LLMError;
+use std::collections::HashMap;
 use thiserror::Error;
 
 /// Errors related to the prompt store (file access, crypto, etc.).
@@ -18,14 +19,19 @@ pub enum StoreError {
     #[error("ID '{0}' is ambiguous (found both a prompt and a chain)")]
     AmbiguousId(String),
 
-    /// A given title matches multiple prompts or chains.
-    #[error("Title '{0}' is ambiguous (multiple matches found)")]
-    AmbiguousTitle(String),
+    /// A given title matches multiple prompts or chains; holds the full IDs of the candidates.
+    #[error("Title '{0}' is ambiguous, use one of these IDs instead:\n  - {}", .1.join("\n  - "))]
+    AmbiguousTitle(String, Vec<String>),
 
     /// The API was used with an invalid configuration.
     #[error("Configuration error: {0}")]
     Configuration(String),
 
+    /// The operation breaks an organization policy (see `core::policy`), or the providers
+    /// a workspace allows its prompts to run on.
+    #[error("{0}")]
+    Policy(String),
+
     /// An underlying file I/O error occurred.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -34,6 +40,14 @@ pub enum StoreError {
     #[error("Crypto error: {0}")]
     Crypto(String),
 
+    /// Reading or writing the prompt files failed.
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    /// The document index used by retrieval steps could not be read.
+    #[error("Document index error: {0}")]
+    DocIndex(String),
+
     /// Failed to serialize or deserialize data.
     #[error("JSON parsing error: {0}")]
     Json(#[from] serde_json::Error),
@@ -49,4 +63,87 @@ pub enum RunError {
     /// An error originating from the underlying LLM backend.
     #[error("LLM backend error: {0}")]
     LLM(#[from] LLMError),
+
+    /// A fetch step failed to retrieve its URL.
+    #[error("Fetch error: {0}")]
+    Fetch(String),
+
+    /// A shell step failed to run or exited with a non-zero status.
+    #[error("Shell step error: {0}")]
+    Shell(String),
+
+    /// Delivering chain outputs to a sink failed.
+    #[error("Sink error: {0}")]
+    Sink(String),
+
+    /// The output of a chain step broke a rule of its guard.
+    #[error("Guard violation: {0}")]
+    Guard(String),
+
+    /// A chain step failed, after its fallback if it has one.
+    ///
+    /// Values of environment profile variables, and of variables whose name contains
+    /// `key`, `token`, `secret` or `password`, are replaced with `[REDACTED]` in the
+    /// details and in fetch and shell error messages.
+    #[error("Step '{key}' failed: {source}")]
+    Step {
+        /// Output key of the failed step.
+        key: String,
+        /// The rendered prompt, query, URL or program of the last attempt, if rendering
+        /// got that far (the prompt may not have been found). `None` when a guard
+        /// rejected the output.
+        rendered: Option<String>,
+        /// Chain variables and step outputs when the step failed.
+        partial_context: HashMap<String, String>,
+        /// The underlying error.
+        source: Box<RunError>,
+    },
+}
+
+impl RunError {
+    /// Replaces the values of the given `(name, value)` secrets in the details of a
+    /// failed step.
+    pub(crate) fn redact(self, secrets: &[(String, String)]) -> Self {
+        match self {
+            RunError::Step {
+                key,
+                rendered,
+                mut partial_context,
+                source,
+            } => {
+                let hide = |text: String| hide_secrets(text, secrets);
+                for (name, _) in secrets {
+                    if let Some(value) = partial_context.get_mut(name) {
+                        *value = REDACTED.to_string();
+                    }
+                }
+                let partial_context = partial_context
+                    .into_iter()
+                    .map(|(name, value)| (name, hide(value)))
+                    .collect();
+                let source = match *source {
+                    RunError::Fetch(message) => RunError::Fetch(hide(message)),
+                    RunError::Shell(message) => RunError::Shell(hide(message)),
+                    source => source,
+                };
+                RunError::Step {
+                    key,
+                    rendered: rendered.map(hide),
+                    partial_context,
+                    source: Box::new(source),
+                }
+            }
+            e => e,
+        }
+    }
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Replaces the values of the given `(name, value)` secrets in a text.
+pub(crate) fn hide_secrets(text: String, secrets: &[(String, String)]) -> String {
+    secrets
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .fold(text, |text, (_, value)| text.replace(value, REDACTED))
 }
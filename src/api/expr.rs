@@ -0,0 +1,364 @@
+//! Condition expressions for chain steps, e.g. `sentiment == 'negative' && length(feedback) > 100`.
+//!
+//! Expressions combine variables of the chain context, string and number literals,
+//! comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`), `&&`/`and`, `||`/`or`, `!`/`not`,
+//! parentheses and a few functions: `length`, `lower`, `upper`, `trim`, `contains`,
+//! `starts_with` and `ends_with`. Missing variables are empty strings. Values that both
+//! read as numbers compare as numbers, others as strings.
+
+use std::collections::HashMap;
+
+/// A parsed condition expression.
+#[derive(Debug, Clone)]
+pub(crate) struct Expr(Node);
+
+impl Expr {
+    /// Parses an expression, returning a description of the first syntax error.
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let node = parser.or()?;
+        match parser.peek() {
+            None => Ok(Expr(node)),
+            Some(token) => Err(format!("Unexpected {} in '{}'", token, source)),
+        }
+    }
+
+    /// Evaluates the expression against the chain context.
+    pub(crate) fn matches(&self, ctx: &HashMap<String, String>) -> bool {
+        self.0.eval(ctx).truthy()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Str(s) => write!(f, "string '{}'", s),
+            Token::Num(n) => write!(f, "number {}", n),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+const OPERATORS: [&str; 12] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "-", "=", "&",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("Unterminated string in '{}'", source)),
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        value.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text
+                .parse()
+                .map_err(|_| format!("Invalid number '{}' in '{}'", text, source))?;
+            tokens.push(Token::Num(number));
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c == ',' {
+            tokens.push(Token::Comma);
+            i += 1;
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("Unexpected character '{}' in '{}'", c, source))?;
+            if *op == "=" || *op == "&" {
+                return Err(format!(
+                    "Unexpected '{}' in '{}', use '{}{}'",
+                    op, source, op, op
+                ));
+            }
+            tokens.push(Token::Op(op));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Length,
+    Lower,
+    Upper,
+    Trim,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "length" => Func::Length,
+            "lower" => Func::Lower,
+            "upper" => Func::Upper,
+            "trim" => Func::Trim,
+            "contains" => Func::Contains,
+            "starts_with" => Func::StartsWith,
+            "ends_with" => Func::EndsWith,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Func::Length | Func::Lower | Func::Upper | Func::Trim => 1,
+            Func::Contains | Func::StartsWith | Func::EndsWith => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Value(Value),
+    Var(String),
+    Call(Func, Vec<Node>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Box<Node>, &'static str, Box<Node>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "Unexpected end of expression".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consumes the next token if it is one of the operators or keywords.
+    fn eat(&mut self, symbols: &[&str]) -> Option<&'static str> {
+        let found = match self.peek()? {
+            Token::Op(op) => symbols.contains(op).then_some(*op),
+            Token::Ident(word) => ["and", "or", "not"]
+                .into_iter()
+                .find(|keyword| keyword == word && symbols.contains(keyword)),
+            _ => None,
+        }?;
+        self.pos += 1;
+        Some(found)
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.eat(&["||", "or"]).is_some() {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.not()?;
+        while self.eat(&["&&", "and"]).is_some() {
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Result<Node, String> {
+        if self.eat(&["!", "not"]).is_some() {
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        let left = self.primary()?;
+        match self.eat(&["==", "!=", "<=", ">=", "<", ">"]) {
+            Some(op) => Ok(Node::Compare(Box::new(left), op, Box::new(self.primary()?))),
+            None => Ok(left),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Str(s) => Ok(Node::Value(Value::Str(s))),
+            Token::Num(n) => Ok(Node::Value(Value::Num(n))),
+            Token::Op("-") => match self.next()? {
+                Token::Num(n) => Ok(Node::Value(Value::Num(-n))),
+                token => Err(format!("Expected a number after '-', found {}", token)),
+            },
+            Token::LParen => {
+                let node = self.or()?;
+                match self.next()? {
+                    Token::RParen => Ok(node),
+                    token => Err(format!("Expected ')', found {}", token)),
+                }
+            }
+            Token::Ident(name) if name == "true" => Ok(Node::Value(Value::Bool(true))),
+            Token::Ident(name) if name == "false" => Ok(Node::Value(Value::Bool(false))),
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                let func =
+                    Func::from_name(&name).ok_or_else(|| format!("Unknown function '{}'", name))?;
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        args.push(self.or()?);
+                        match self.next()? {
+                            Token::Comma => {}
+                            Token::RParen => break,
+                            token => return Err(format!("Expected ',' or ')', found {}", token)),
+                        }
+                    }
+                }
+                if args.len() != func.arity() {
+                    return Err(format!(
+                        "'{}' takes {} argument(s), got {}",
+                        name,
+                        func.arity(),
+                        args.len()
+                    ));
+                }
+                Ok(Node::Call(func, args))
+            }
+            Token::Ident(name) => Ok(Node::Var(name)),
+            token => Err(format!("Unexpected {}", token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty() && s != "false",
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(s) => s.trim().parse().ok(),
+            Value::Bool(_) => None,
+        }
+    }
+
+    fn into_string(self) -> String {
+        match self {
+            Value::Str(s) => s,
+            Value::Num(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+impl Node {
+    fn eval(&self, ctx: &HashMap<String, String>) -> Value {
+        match self {
+            Node::Value(value) => value.clone(),
+            Node::Var(name) => Value::Str(ctx.get(name).cloned().unwrap_or_default()),
+            Node::Not(node) => Value::Bool(!node.eval(ctx).truthy()),
+            Node::And(a, b) => Value::Bool(a.eval(ctx).truthy() && b.eval(ctx).truthy()),
+            Node::Or(a, b) => Value::Bool(a.eval(ctx).truthy() || b.eval(ctx).truthy()),
+            Node::Compare(a, op, b) => {
+                let (a, b) = (a.eval(ctx), b.eval(ctx));
+                let ordering = match (a.as_number(), b.as_number()) {
+                    (Some(x), Some(y)) => x.partial_cmp(&y),
+                    _ => Some(a.into_string().cmp(&b.into_string())),
+                };
+                Value::Bool(ordering.is_some_and(|ordering| match *op {
+                    "==" => ordering.is_eq(),
+                    "!=" => ordering.is_ne(),
+                    "<" => ordering.is_lt(),
+                    "<=" => ordering.is_le(),
+                    ">" => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+            Node::Call(func, args) => {
+                let mut args = args.iter().map(|arg| arg.eval(ctx).into_string());
+                let mut arg = || args.next().unwrap_or_default();
+                match func {
+                    Func::Length => Value::Num(arg().chars().count() as f64),
+                    Func::Lower => Value::Str(arg().to_lowercase()),
+                    Func::Upper => Value::Str(arg().to_uppercase()),
+                    Func::Trim => Value::Str(arg().trim().to_string()),
+                    Func::Contains => Value::Bool(arg().contains(&arg())),
+                    Func::StartsWith => Value::Bool(arg().starts_with(&arg())),
+                    Func::EndsWith => Value::Bool(arg().ends_with(&arg())),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! gRPC service over the store, described by `proto/prompt_store.proto` and served by
+//! `prompt-store serve`. Available with the `grpc` feature.
+//!
+//! ```rust,no_run
+//! # use std::sync::Arc;
+//! # use prompt_store::{api::grpc::GrpcService, PromptStore};
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let store = Arc::new(PromptStore::with_password("my-secret")?);
+//! tonic::transport::Server::builder()
+//!     .add_service(GrpcService::new(store).into_server())
+//!     .serve("127.0.0.1:50051".parse()?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+// Checks fail with the `Status` returned to the client, as tonic handlers do.
+#![allow(clippy::result_large_err)]
+
+use super::{ChainRunner, PromptStore, RunError, RunOutput, StoreError};
+use crate::core::audit;
+use crate::core::config::load_input_redaction;
+use crate::core::metrics;
+use crate::core::storage::parse_id;
+use crate::core::tokens::{find_token, ApiToken, Operation};
+use futures::stream::{self, Stream, StreamExt};
+use llm::chat::ChatMessage;
+use log::warn;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tonic::{Request, Response, Status};
+
+/// Types generated from `proto/prompt_store.proto`.
+pub mod proto {
+    tonic::include_proto!("prompt_store.v1");
+}
+
+use proto::prompt_store_server::{PromptStore as PromptStoreRpc, PromptStoreServer};
+use proto::{
+    chain_event::Event, ChainCompleted, ChainEvent, RenderRequest, RenderResponse, RunChainRequest,
+    RunChunk, RunRequest, StepCompleted,
+};
+
+/// Window of the rate limits of tokens.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// The `PromptStore` gRPC service, running prompts on the providers of `config.toml`.
+pub struct GrpcService {
+    store: Arc<PromptStore>,
+    tokens: Vec<ApiToken>,
+    /// Start and request count of the current rate limit window of each token.
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl GrpcService {
+    pub fn new(store: Arc<PromptStore>) -> Self {
+        Self {
+            store,
+            tokens: Vec::new(),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Requires requests to carry one of `tokens` as `authorization: Bearer <token>`, and
+    /// limits them to its scope, workspaces and rate limit. Without tokens every request
+    /// is served.
+    pub fn with_tokens(mut self, tokens: Vec<ApiToken>) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// Wraps the service to be added to a `tonic::transport::Server`.
+    pub fn into_server(self) -> PromptStoreServer<Self> {
+        PromptStoreServer::new(self)
+    }
+
+    /// Renders a prompt like `PromptRunner::run` without a backend, redacting the
+    /// variables when `config.toml` asks for it.
+    async fn render_prompt(
+        &self,
+        id: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+        lang: &str,
+        variant: &str,
+    ) -> Result<String, Status> {
+        let mut runner = self.store.prompt(id).vars(vars);
+        if !lang.is_empty() {
+            runner = runner.lang(lang);
+        }
+        if !variant.is_empty() {
+            runner = runner.variant(variant);
+        }
+        if let Some(policy) = load_input_redaction().map_err(Status::internal)? {
+            runner = runner.redact_inputs(policy);
+        }
+        match runner.run().await.map_err(run_status)? {
+            RunOutput::Prompt(text) => Ok(text),
+            RunOutput::Chain(_) => Err(Status::internal("Prompt run returned chain outputs")),
+        }
+    }
+
+    /// Admits a request for `action` on the prompt or chain `id`: checks its token, when
+    /// the server has tokens, and records the access, granted or not, in the audit log.
+    fn admit<T>(
+        &self,
+        request: &Request<T>,
+        action: &str,
+        id: &str,
+        operation: Operation,
+        workspaces: impl FnOnce() -> Result<Vec<String>, Status>,
+    ) -> Result<(), Status> {
+        let (token, result) = match self.identify(request) {
+            Ok(token) => (
+                token,
+                token.map_or(Ok(()), |token| self.authorize(token, operation, workspaces)),
+            ),
+            Err(status) => (None, Err(status)),
+        };
+        let details = serde_json::json!({
+            "id": id,
+            "peer": request.remote_addr().map(|addr| addr.ip().to_string()),
+            "allowed": result.is_ok(),
+            "reason": result.as_ref().err().map(|status| status.message()),
+        });
+        let user = token.map(|token| token.name.as_str());
+        if let Err(e) = audit::record_as(&self.store.ctx.base_dir, user, action, details) {
+            // An access that cannot be recorded is refused.
+            warn!("{}", e);
+            return result.and(Err(Status::internal("Audit log error")));
+        }
+        result
+    }
+
+    /// Returns the token of a request, `None` when the server has no tokens.
+    fn identify<T>(&self, request: &Request<T>) -> Result<Option<&ApiToken>, Status> {
+        if self.tokens.is_empty() {
+            return Ok(None);
+        }
+        let secret = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing 'authorization: Bearer' token"))?;
+        find_token(&self.tokens, secret.trim())
+            .map(Some)
+            .ok_or_else(|| Status::unauthenticated("Invalid token"))
+    }
+
+    /// Checks that a token allows an operation on every workspace given by `workspaces`,
+    /// which are only resolved when the token is restricted, then counts the request
+    /// against its rate limit.
+    fn authorize(
+        &self,
+        token: &ApiToken,
+        operation: Operation,
+        workspaces: impl FnOnce() -> Result<Vec<String>, Status>,
+    ) -> Result<(), Status> {
+        if !token.scope.allows(operation) {
+            let action = match operation {
+                Operation::Read => "read prompts",
+                Operation::Run => "run prompts",
+            };
+            return Err(Status::permission_denied(format!(
+                "Token '{}' cannot {}",
+                token.name, action
+            )));
+        }
+        if !token.workspaces.is_empty() {
+            let workspaces = workspaces()?;
+            if let Some(workspace) = workspaces.iter().find(|w| !token.allows_workspace(w)) {
+                return Err(Status::permission_denied(format!(
+                    "Token '{}' cannot use workspace '{}'",
+                    token.name, workspace
+                )));
+            }
+        }
+        let Some(limit) = token.rate_limit else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let (started, count) = windows.entry(token.name.clone()).or_insert((now, 0));
+        if now.duration_since(*started) >= RATE_WINDOW {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= limit {
+            return Err(Status::resource_exhausted(format!(
+                "Token '{}' is over its limit of {} requests per minute",
+                token.name, limit
+            )));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Returns the workspace of the prompt an ID or title resolves to.
+    fn prompt_workspace(&self, id_or_title: &str) -> Result<String, Status> {
+        let (full_id, _) = self
+            .store
+            .find_prompt_variant(None, id_or_title, None, None)
+            .map_err(store_status)?;
+        Ok(parse_id(&full_id).0)
+    }
+
+    /// Loads a stored chain for a request.
+    fn load_chain(&self, req: &RunChainRequest) -> Result<ChainRunner<'static>, Status> {
+        let mut runner = self
+            .store
+            .stored_chain_owned(&req.id)
+            .map_err(store_status)?;
+        if !req.env.is_empty() {
+            runner = runner.env_profile(&req.env);
+        }
+        if !req.lang.is_empty() {
+            runner = runner.lang(&req.lang);
+        }
+        Ok(runner)
+    }
+}
+
+#[tonic::async_trait]
+impl PromptStoreRpc for GrpcService {
+    async fn render(
+        &self,
+        request: Request<RenderRequest>,
+    ) -> Result<Response<RenderResponse>, Status> {
+        let id = &request.get_ref().id;
+        self.admit(&request, "serve.render", id, Operation::Read, || {
+            self.prompt_workspace(id).map(|workspace| vec![workspace])
+        })?;
+        let req = request.into_inner();
+        let text = self
+            .render_prompt(&req.id, req.vars, &req.lang, &req.variant)
+            .await?;
+        Ok(Response::new(RenderResponse { text }))
+    }
+
+    type RunStream = ResponseStream<RunChunk>;
+
+    async fn run(&self, request: Request<RunRequest>) -> Result<Response<Self::RunStream>, Status> {
+        let id = &request.get_ref().id;
+        self.admit(&request, "serve.run", id, Operation::Run, || {
+            self.prompt_workspace(id).map(|workspace| vec![workspace])
+        })?;
+        let req = request.into_inner();
+        let registry = Arc::clone(self.store.config_registry().map_err(store_status)?);
+        if !registry.backends.contains_key(&req.backend) {
+            return Err(Status::invalid_argument(format!(
+                "Unknown backend '{}': use a provider name from config.toml",
+                req.backend
+            )));
+        }
+        let workspace = self.prompt_workspace(&req.id)?;
+        self.store
+            .ctx
+            .prompts()
+            .check_provider(&workspace, &req.backend)
+            .map_err(Status::permission_denied)?;
+        let prompt = self
+            .render_prompt(&req.id, req.vars, &req.lang, &req.variant)
+            .await?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let _in_flight = metrics::start_run();
+            let llm = &registry.backends[&req.backend];
+            let messages = vec![ChatMessage::user().content(prompt).build()];
+            let ok = match llm.chat_stream(&messages).await {
+                Ok(mut chunks) => {
+                    let mut ok = true;
+                    while let Some(chunk) = chunks.next().await {
+                        let item = chunk
+                            .map(|text| RunChunk { text })
+                            .map_err(|e| Status::unavailable(e.to_string()));
+                        ok = item.is_ok();
+                        if tx.send(item).is_err() || !ok {
+                            break;
+                        }
+                    }
+                    ok
+                }
+                // Providers without streaming answer in a single chunk.
+                Err(_) => {
+                    let item = llm
+                        .chat(&messages)
+                        .await
+                        .map(|resp| RunChunk {
+                            text: resp.text().unwrap_or_default(),
+                        })
+                        .map_err(|e| Status::unavailable(e.to_string()));
+                    let ok = item.is_ok();
+                    let _ = tx.send(item);
+                    ok
+                }
+            };
+            metrics::record_run("prompt", ok);
+        });
+        Ok(Response::new(Box::pin(receiver_stream(rx))))
+    }
+
+    type RunChainStream = ResponseStream<ChainEvent>;
+
+    async fn run_chain(
+        &self,
+        request: Request<RunChainRequest>,
+    ) -> Result<Response<Self::RunChainStream>, Status> {
+        let id = &request.get_ref().id;
+        // A step can run a prompt of another workspace, so every step is authorized.
+        let mut loaded = None;
+        self.admit(&request, "serve.run_chain", id, Operation::Run, || {
+            let runner = self.load_chain(request.get_ref())?;
+            let mut workspaces = vec![parse_id(id).0];
+            workspaces.extend(runner.prompt_workspaces().map_err(store_status)?);
+            loaded = Some(runner);
+            Ok(workspaces)
+        })?;
+        let req = request.into_inner();
+        let runner = match loaded {
+            Some(runner) => runner,
+            None => self.load_chain(&req)?,
+        };
+        let (step_tx, mut step_rx) = mpsc::unbounded_channel();
+        let runner = runner.vars(req.vars).on_step(step_tx);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let steps_tx = tx.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(step) = step_rx.recv().await {
+                let event = Event::Step(StepCompleted {
+                    key: step.key,
+                    provider: step.provider.unwrap_or_default(),
+                    latency_ms: step.latency.as_millis() as u64,
+                    output: step.output,
+                });
+                let _ = steps_tx.send(Ok(ChainEvent { event: Some(event) }));
+            }
+        });
+        tokio::spawn(async move {
+            let _in_flight = metrics::start_run();
+            let result = runner.run_with_metadata().await;
+            // The runner is gone, so every step has been forwarded once this returns.
+            let _ = forward.await;
+            let item = match result {
+                Ok((RunOutput::Chain(outputs), metadata)) => Ok(ChainEvent {
+                    event: Some(Event::Completed(ChainCompleted {
+                        outputs,
+                        finished_by: metadata.finished_by.unwrap_or_default(),
+                    })),
+                }),
+                Ok((RunOutput::Prompt(_), _)) => {
+                    Err(Status::internal("Chain run returned a prompt output"))
+                }
+                Err(e) => Err(run_status(e)),
+            };
+            metrics::record_run("chain", item.is_ok());
+            let _ = tx.send(item);
+        });
+        Ok(Response::new(Box::pin(receiver_stream(rx))))
+    }
+}
+
+fn receiver_stream<T>(rx: UnboundedReceiver<T>) -> impl Stream<Item = T> {
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+fn store_status(e: StoreError) -> Status {
+    match e {
+        StoreError::NotFound(_) => Status::not_found(e.to_string()),
+        StoreError::AmbiguousId(_) | StoreError::AmbiguousTitle(..) => {
+            Status::invalid_argument(e.to_string())
+        }
+        StoreError::Policy(_) => Status::permission_denied(e.to_string()),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+fn run_status(e: RunError) -> Status {
+    match e {
+        RunError::Store(e) => store_status(e),
+        e => Status::unavailable(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::AppCtx;
+    use crate::core::tokens::Scope;
+    use tonic::Code;
+
+    fn service() -> (tempfile::TempDir, GrpcService) {
+        let (dir, ctx) = AppCtx::temp();
+        (dir, GrpcService::new(Arc::new(PromptStore::from_ctx(ctx))))
+    }
+
+    fn token(scope: Scope, workspaces: &[&str]) -> ApiToken {
+        let workspaces = workspaces.iter().map(|w| w.to_string()).collect();
+        ApiToken::generate("ci", scope, workspaces).0
+    }
+
+    fn workspace(name: &str) -> impl FnOnce() -> Result<Vec<String>, Status> + '_ {
+        move || Ok(vec![name.to_string()])
+    }
+
+    #[test]
+    fn scopes_limit_the_operations() {
+        let (_dir, service) = service();
+        let read_only = token(Scope::ReadOnly, &[]);
+        let run_only = token(Scope::RunOnly, &[]);
+        let admin = token(Scope::Admin, &[]);
+        let authorize =
+            |token: &ApiToken, operation| service.authorize(token, operation, workspace("default"));
+
+        assert!(authorize(&read_only, Operation::Read).is_ok());
+        let status = authorize(&read_only, Operation::Run).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message(), "Token 'ci' cannot run prompts");
+        assert!(authorize(&run_only, Operation::Run).is_ok());
+        let status = authorize(&run_only, Operation::Read).unwrap_err();
+        assert_eq!(status.message(), "Token 'ci' cannot read prompts");
+        assert!(authorize(&admin, Operation::Read).is_ok());
+        assert!(authorize(&admin, Operation::Run).is_ok());
+    }
+
+    #[test]
+    fn restricted_tokens_only_use_their_workspaces() {
+        let (_dir, service) = service();
+        let token = token(Scope::Admin, &["team"]);
+        assert!(service
+            .authorize(&token, Operation::Run, workspace("team"))
+            .is_ok());
+        let status = service
+            .authorize(&token, Operation::Run, workspace("default"))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(
+            status.message(),
+            "Token 'ci' cannot use workspace 'default'"
+        );
+        // A prompt that cannot be found is not served to a restricted token either.
+        let status = service
+            .authorize(&token, Operation::Run, || Err(Status::not_found("missing")))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    #[test]
+    fn workspaces_are_only_resolved_when_needed() {
+        let (_dir, service) = service();
+        let unresolved = || -> Result<Vec<String>, Status> { panic!("workspace resolved") };
+        assert!(service
+            .authorize(&token(Scope::Admin, &[]), Operation::Run, unresolved)
+            .is_ok());
+        // The scope is checked first, so a token without it learns nothing of the prompt.
+        let status = service
+            .authorize(
+                &token(Scope::ReadOnly, &["team"]),
+                Operation::Run,
+                unresolved,
+            )
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn chains_are_refused_when_a_step_uses_another_workspace() {
+        let (_dir, service) = service();
+        let repo = service.store.ctx.prompts();
+        for full_id in ["team::summary", "other::secret"] {
+            let prompt = serde_json::from_value(serde_json::json!({
+                "id": full_id, "title": full_id, "content": "Hello", "tags": [],
+            }));
+            repo.save(full_id, &prompt.unwrap()).unwrap();
+        }
+        let yaml = "steps:\n  - id: summary\n    prompt: team::summary\n    if: \"lang == 'en'\"\n    else:\n      prompt: other::secret\n";
+        repo.save_chain_definition("team::digest", yaml).unwrap();
+
+        let (token, secret) = ApiToken::generate("ci", Scope::Admin, vec!["team".to_string()]);
+        let service = service.with_tokens(vec![token]);
+        let mut request = Request::new(RunChainRequest {
+            id: "team::digest".to_string(),
+            ..Default::default()
+        });
+        let bearer = format!("Bearer {}", secret).parse().unwrap();
+        request.metadata_mut().insert("authorization", bearer);
+        let status = service.run_chain(request).await.err().unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message(), "Token 'ci' cannot use workspace 'other'");
+    }
+
+    #[test]
+    fn rate_limits_count_requests_per_token() {
+        let (_dir, service) = service();
+        let mut limited = token(Scope::Admin, &[]);
+        limited.rate_limit = Some(2);
+        let mut other = limited.clone();
+        other.name = "other".to_string();
+
+        for _ in 0..2 {
+            assert!(service
+                .authorize(&limited, Operation::Run, workspace("default"))
+                .is_ok());
+        }
+        let status = service
+            .authorize(&limited, Operation::Run, workspace("default"))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert!(service
+            .authorize(&other, Operation::Run, workspace("default"))
+            .is_ok());
+    }
+}
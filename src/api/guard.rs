@@ -0,0 +1,184 @@
+//! Guards validating the output of a chain step against policy rules.
+
+use llm::chain::LLMRegistry;
+use llm::chat::ChatMessage;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::error::{RunError, StoreError};
+
+/// Rules the output of a step must follow, and what happens when it breaks one.
+///
+/// In YAML chain files, it is the `guard:` block of a step, e.g.
+/// `guard: { deny: ["(?i)internal only"], max_chars: 2000, on_violation: redact }`, or
+/// `on_violation: { fallback: <prompt> }`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Guard {
+    /// Regular expressions the output must not match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+    /// Maximum number of characters of the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<usize>,
+    /// Keys the output must have, as a JSON object (optionally in a code fence).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_keys: Vec<String>,
+    /// ID of a provider asked whether the output is safe, checked after the other rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<String>,
+    /// What to do with an output breaking a rule.
+    #[serde(
+        rename = "on_violation",
+        default,
+        with = "serde_yaml::with::singleton_map"
+    )]
+    pub action: GuardAction,
+}
+
+/// What a guard does with an output breaking one of its rules.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardAction {
+    /// Fails the step.
+    #[default]
+    Block,
+    /// Replaces the matches of the denylist with `[REDACTED]` and truncates the output
+    /// to `max_chars`. The step fails if the result still breaks a rule.
+    Redact,
+    /// Runs a prompt from the store, given by ID or title, instead, with the same step
+    /// settings. The step fails if its output also breaks a rule.
+    Fallback(String),
+}
+
+impl Guard {
+    /// A guard blocking outputs that break the rules added to it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects outputs matching a regular expression.
+    pub fn deny(mut self, pattern: &str) -> Self {
+        self.deny.push(pattern.to_string());
+        self
+    }
+
+    /// Rejects outputs longer than `max_chars` characters.
+    pub fn max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+
+    /// Rejects outputs that are not a JSON object with these keys.
+    pub fn require_keys(mut self, keys: &[&str]) -> Self {
+        self.required_keys
+            .extend(keys.iter().map(|key| key.to_string()));
+        self
+    }
+
+    /// Asks a provider of the registry, e.g. a moderation model, whether outputs are safe.
+    pub fn moderate_with(mut self, provider_id: &str) -> Self {
+        self.moderation = Some(provider_id.to_string());
+        self
+    }
+
+    /// Sets what happens to outputs breaking a rule. Defaults to `GuardAction::Block`.
+    pub fn on_violation(mut self, action: GuardAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Describes the first rule the output breaks, if any.
+    pub(crate) async fn violation(
+        &self,
+        output: &str,
+        reg: &LLMRegistry,
+    ) -> Result<Option<String>, RunError> {
+        for (pattern, re) in self.deny.iter().zip(self.denylist()?) {
+            if re.is_match(output) {
+                return Ok(Some(format!("output matches denied pattern '{}'", pattern)));
+            }
+        }
+        if let Some(max_chars) = self.max_chars {
+            let len = output.chars().count();
+            if len > max_chars {
+                return Ok(Some(format!(
+                    "output is {} characters long, over the limit of {}",
+                    len, max_chars
+                )));
+            }
+        }
+        if !self.required_keys.is_empty() {
+            let object = serde_json::from_str::<serde_json::Value>(strip_code_fence(output))
+                .ok()
+                .and_then(|value| value.as_object().cloned());
+            let Some(object) = object else {
+                return Ok(Some("output is not a JSON object".to_string()));
+            };
+            if let Some(key) = self.required_keys.iter().find(|k| !object.contains_key(*k)) {
+                return Ok(Some(format!("output is missing the JSON key '{}'", key)));
+            }
+        }
+        if let Some(provider_id) = &self.moderation {
+            if !moderate(reg, provider_id, output).await? {
+                return Ok(Some(format!(
+                    "moderation by '{}' flagged the output",
+                    provider_id
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Redacts the denylist matches of the output and truncates it to `max_chars`.
+    pub(crate) fn redact(&self, output: &str) -> Result<String, RunError> {
+        let redacted = self
+            .denylist()?
+            .iter()
+            .fold(output.to_string(), |text, re| {
+                re.replace_all(&text, "[REDACTED]").into_owned()
+            });
+        Ok(match self.max_chars {
+            Some(max_chars) => redacted.chars().take(max_chars).collect(),
+            None => redacted,
+        })
+    }
+
+    fn denylist(&self) -> Result<Vec<Regex>, StoreError> {
+        self.deny
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    StoreError::Configuration(format!("Invalid guard pattern '{}': {}", pattern, e))
+                })
+            })
+            .collect()
+    }
+}
+
+/// The content of a ```json code fence, or the trimmed text.
+fn strip_code_fence(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|rest| rest.trim_start_matches("json").trim())
+        .unwrap_or(text)
+}
+
+/// Asks the provider whether the text is safe.
+async fn moderate(reg: &LLMRegistry, provider_id: &str, text: &str) -> Result<bool, RunError> {
+    let provider = reg.get(provider_id).ok_or_else(|| {
+        StoreError::Configuration(format!("Provider '{}' not found in registry", provider_id))
+    })?;
+    let request = ChatMessage::user()
+        .content(format!(
+            "Does the following text contain harmful, hateful, sexual, violent or otherwise unsafe content? Answer with SAFE or UNSAFE only.\n\n{}",
+            text
+        ))
+        .build();
+    let response = provider.chat(&[request]).await?;
+    Ok(!response
+        .text()
+        .unwrap_or_default()
+        .to_uppercase()
+        .contains("UNSAFE"))
+}
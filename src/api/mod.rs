@@ -1,13 +1,30 @@
 //! High-level fluent API for running prompts and chains.
 
+mod budget;
 mod error;
+mod expr;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod guard;
 mod llm_bridge;
+mod redact;
 mod runner;
+mod sampling;
+mod sink;
 mod store;
+mod stored_chain;
 
+pub use budget::{BudgetStrategy, ContextBudget};
 pub use error::{RunError, StoreError};
+pub use guard::{Guard, GuardAction};
+pub use crate::core::pii::RedactionPolicy;
+pub use crate::core::scan::{Risk, RiskKind};
+pub use crate::core::vars::VarSources;
 pub use llm_bridge::LLMBackendRef;
+pub use redact::redact;
 pub use runner::{ChainRunner, PromptRunner};
+pub use sampling::AggregateStrategy;
+pub use sink::Sink;
 pub use store::PromptStore;
 
 /// Result of running a prompt or chain.
@@ -18,3 +35,41 @@ pub enum RunOutput {
     /// Outputs of a multi-step chain run (map of step IDs to generated text).
     Chain(std::collections::HashMap<String, String>),
 }
+
+/// Details about how a chain run was executed.
+#[derive(Debug, Clone, Default)]
+pub struct RunMetadata {
+    /// Provider that actually served each LLM or retrieval step, keyed by output key.
+    /// Differs from the configured provider when a failover group fell back.
+    pub served_by: std::collections::HashMap<String, String>,
+    /// Output key of the step whose exit condition ended the chain early, if any.
+    pub finished_by: Option<String>,
+    /// Iterations of each refinement loop, keyed by the output key of its drafts.
+    pub refinements: std::collections::HashMap<String, Vec<RefineIteration>>,
+    /// Steps that ran, in the order they completed, with secrets redacted.
+    pub steps: Vec<StepTrace>,
+}
+
+/// One step of a chain run, as recorded for transcripts.
+#[derive(Debug, Clone)]
+pub struct StepTrace {
+    /// Output key of the step.
+    pub key: String,
+    /// Rendered prompt, query, URL or program of the step.
+    pub rendered: String,
+    /// Provider that served the step, for LLM and retrieval steps.
+    pub provider: Option<String>,
+    /// Time the step took, including fallbacks and guard checks.
+    pub latency: std::time::Duration,
+    /// Output stored under the step's key.
+    pub output: String,
+}
+
+/// One round of a refinement loop.
+#[derive(Debug, Clone)]
+pub struct RefineIteration {
+    /// Draft written by the generator step.
+    pub draft: String,
+    /// Review of the draft by the critic step.
+    pub critique: String,
+}
@@ -0,0 +1,18 @@
+//! Redaction of personal data before text reaches external providers.
+
+use crate::core::pii::{self, RedactionPolicy};
+
+use super::error::StoreError;
+
+/// Replaces the email addresses, phone numbers, card numbers and custom patterns selected
+/// by the policy with placeholders such as `[EMAIL]`. Detection is best effort.
+///
+/// ```
+/// use prompt_store::{redact, RedactionPolicy};
+///
+/// let text = redact("Contact jane@example.com", &RedactionPolicy::new()).unwrap();
+/// assert_eq!(text, "Contact [EMAIL]");
+/// ```
+pub fn redact(text: &str, policy: &RedactionPolicy) -> Result<String, StoreError> {
+    pii::redact(text, policy).map_err(StoreError::Configuration)
+}
@@ -1,16 +1,41 @@
 //! Fluent runners for executing single prompts or complex chains.
 
 use futures::future;
-use llm::{chain::MultiChainStepMode, LLMProvider};
-use regex::Regex;
-use std::collections::HashMap;
+use llm::{
+    chain::{LLMRegistry, MultiChainStepMode},
+    chat::ChatMessage,
+    error::LLMError,
+    LLMProvider,
+};
+use log::{debug, info, warn};
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::core::config::{load_required_redaction, load_step_timeout, provider_backend};
+use crate::core::docs::{self, DocIndex};
+use crate::core::pii::{self, RedactionPolicy};
+use crate::core::storage::parse_id;
+use crate::core::vars::VarSources;
+use crate::core::{logging, retry, shell, template, usage, web};
 
 use super::{
-    error::{RunError, StoreError},
+    budget::{BudgetStrategy, ContextBudget},
+    error::{hide_secrets, RunError, StoreError},
+    guard::{Guard, GuardAction},
     llm_bridge::LLMBackendRef,
+    sampling::{self, AggregateStrategy},
+    sink::Sink,
     store::PromptStore,
-    RunOutput,
+    stored_chain::{
+        ChainFile, Condition, CriticStep, FallbackStep, FetchStep, LoopStep, RetrieveStep,
+        SamplesSpec, ShellStep, Step, StepDefinition, SwitchStep, VarSpec,
+    },
+    RefineIteration, RunMetadata, RunOutput, StepTrace,
 };
 
 /// Represents the source of a prompt for a chain step.
@@ -29,7 +54,14 @@ pub struct PromptRunner<'a> {
     store: &'a PromptStore,
     id_or_title: &'a str,
     vars: HashMap<String, String>,
+    env_profile: Option<String>,
+    workspace: Option<String>,
+    lang: Option<String>,
+    variant: Option<String>,
+    experiment: Vec<(String, u32)>,
     backend: Option<&'a dyn LLMProvider>,
+    provider: Option<String>,
+    redaction: Option<RedactionPolicy>,
 }
 
 impl<'a> PromptRunner<'a> {
@@ -39,7 +71,14 @@ impl<'a> PromptRunner<'a> {
             store,
             id_or_title,
             vars: HashMap::new(),
+            env_profile: None,
+            workspace: None,
+            lang: None,
+            variant: None,
+            experiment: Vec::new(),
             backend: None,
+            provider: None,
+            redaction: None,
         }
     }
 
@@ -55,20 +94,137 @@ impl<'a> PromptRunner<'a> {
         self
     }
 
+    /// Uses the variables of a stored environment profile as defaults.
+    /// Variables set with `vars()` take precedence over the profile.
+    pub fn env_profile(mut self, name: &str) -> Self {
+        self.env_profile = Some(name.to_string());
+        self
+    }
+
+    /// Resolves the prompt ID or title inside the given workspace only.
+    pub fn workspace(mut self, name: &str) -> Self {
+        self.workspace = Some(name.to_string());
+        self
+    }
+
+    /// Runs the translation of the prompt in a language (e.g., `fr`), falling back to
+    /// its default content when it has none.
+    pub fn lang(mut self, code: &str) -> Self {
+        self.lang = Some(code.to_string());
+        self
+    }
+
+    /// Runs a named variant of the prompt instead of its content (the `default` variant).
+    /// Variants have no translations, so `lang()` is ignored.
+    pub fn variant(mut self, name: &str) -> Self {
+        self.variant = Some(name.to_string());
+        self
+    }
+
+    /// Runs a variant picked at random with relative weights, e.g.
+    /// `&[("default", 90), ("b", 10)]`, unless `variant()` is set.
+    ///
+    /// Runs served by a variant are counted per variant in the usage history, and
+    /// `run_with_variant()` tells which one served a run, to relate outcomes to it.
+    pub fn experiment(mut self, weights: &[(&str, u32)]) -> Self {
+        self.experiment = weights
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(variant, weight)| (variant.to_string(), *weight))
+            .collect();
+        self
+    }
+
     /// Sets the LLM backend to execute the prompt with.
     /// If not set, `run()` will only perform template substitution and return the result.
+    ///
+    /// Prompts of a workspace limiting its providers (see `describe`) can't be run on an
+    /// unnamed backend: use `provider()` for them.
     pub fn backend(mut self, llm: &'a dyn LLMProvider) -> Self {
         self.backend = Some(llm);
         self
     }
 
+    /// Executes the prompt on a provider of `config.toml`, by name, instead of
+    /// `backend()`, once checked against the providers the workspace of the prompt allows.
+    pub fn provider(mut self, name: &str) -> Self {
+        self.provider = Some(name.to_string());
+        self
+    }
+
+    /// Redacts personal data (see `redact`) from the variables before rendering, so it
+    /// doesn't reach the backend.
+    pub fn redact_inputs(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
     /// Finds, decrypts, renders, and executes the prompt.
     pub async fn run(self) -> Result<RunOutput, RunError> {
-        let pd = self.store.find_prompt(self.id_or_title)?;
-        let rendered = render_template(&pd.content, &self.vars);
+        Ok(self.run_with_variant().await?.0)
+    }
+
+    /// Runs the prompt like `run()`, also returning the variant that served it when
+    /// `variant()` or `experiment()` is set.
+    pub async fn run_with_variant(self) -> Result<(RunOutput, Option<String>), RunError> {
+        let variant = match (&self.variant, self.experiment.is_empty()) {
+            (Some(name), _) => Some(name.clone()),
+            (None, false) => Some(pick_variant(&self.experiment).to_string()),
+            (None, true) => None,
+        };
+        let (full_id, pd) = self.store.find_prompt_variant(
+            self.workspace.as_deref(),
+            self.id_or_title,
+            self.lang.as_deref(),
+            variant.as_deref(),
+        )?;
+        let mut vars = match &self.env_profile {
+            Some(profile) => self.store.profile_vars(profile)?,
+            None => HashMap::new(),
+        };
+        vars.extend(self.vars);
+        // An organization policy may require the redaction the caller didn't ask for.
+        let redaction = match self.redaction {
+            Some(policy) => Some(policy),
+            None => load_required_redaction().map_err(StoreError::Configuration)?,
+        };
+        if let Some(policy) = &redaction {
+            pii::redact_values(&mut vars, policy).map_err(StoreError::Configuration)?;
+        }
+        add_examples(&mut vars, pd.few_shot_examples());
+        let rendered = template::render(&pd.content, &vars);
+
+        let workspace = parse_id(&full_id).0;
+        let repo = self.store.ctx.prompts();
+        let llm = match (&self.provider, self.backend) {
+            (Some(name), _) => {
+                repo.check_provider(&workspace, name)
+                    .map_err(StoreError::Policy)?;
+                let registry = self.store.config_registry()?;
+                Some(registry.get(name).ok_or_else(|| {
+                    StoreError::Configuration(format!(
+                        "Provider '{}' not found in config.toml",
+                        name
+                    ))
+                })?)
+            }
+            (None, Some(llm)) => {
+                let info = repo
+                    .load_workspace_info(&workspace)
+                    .map_err(StoreError::Storage)?;
+                if info.is_some_and(|info| info.restricts_providers()) {
+                    return Err(StoreError::Policy(format!(
+                        "Workspace '{}' limits the providers its prompts run on: name the provider with `provider()` instead of `backend()`.",
+                        workspace
+                    ))
+                    .into());
+                }
+                Some(llm)
+            }
+            (None, None) => None,
+        };
 
-        let result = if let Some(llm) = self.backend {
-            use llm::chat::ChatMessage;
+        let result = if let Some(llm) = llm {
             let req = ChatMessage::user().content(&rendered).build();
             let resp = llm.chat(&[req]).await?;
             resp.text().unwrap_or_default()
@@ -76,28 +232,326 @@ impl<'a> PromptRunner<'a> {
             rendered
         };
 
-        Ok(RunOutput::Prompt(result))
+        if let Some(name) = &variant {
+            usage::record_variant(&self.store.ctx.base_dir, &full_id, name);
+        }
+        Ok((RunOutput::Prompt(result), variant))
+    }
+}
+
+/// Picks a variant at random, with a probability proportional to its weight.
+fn pick_variant(weights: &[(String, u32)]) -> &str {
+    let total: u64 = weights.iter().map(|(_, weight)| u64::from(*weight)).sum();
+    let mut roll = rand::thread_rng().gen_range(0..total);
+    for (name, weight) in weights {
+        match roll.checked_sub(u64::from(*weight)) {
+            Some(rest) => roll = rest,
+            None => return name,
+        }
     }
+    unreachable!("the roll is below the total weight")
 }
 
 // --- ChainRunner for multi-step chains ---
 
+/// Text in the critic output that ends a refinement loop.
+const APPROVAL_MARKER: &str = "APPROVED";
+
+/// Conversation joined by steps set to `MultiChainStepMode::Chat`.
+const DEFAULT_CONVERSATION: &str = "chat";
+
+/// A predicate evaluated against the chain context to decide whether a step runs.
+pub(super) type StepCondition<'a> =
+    Box<dyn Fn(&HashMap<String, String>) -> bool + Send + Sync + 'a>;
+
+/// What a chain step does with its rendered source.
+#[derive(Clone)]
+enum StepKind {
+    /// Send the rendered prompt to the step's LLM provider.
+    Llm,
+    /// Use the rendered source as a query against the document index and return the top-k chunks.
+    Retrieve { top_k: usize },
+    /// Fetch the rendered source as a URL and return the response body as text.
+    Fetch { max_bytes: usize, readable: bool },
+    /// Run the source as a local program with rendered arguments and return its stdout.
+    Shell {
+        args: Vec<String>,
+        stdin: Option<String>,
+    },
+}
+
+/// Routes a step to the prompt of the case matching the value of a variable.
+struct Switch {
+    variable: String,
+    cases: Vec<(String, PromptSource)>,
+}
+
+impl Switch {
+    /// The prompt of the case equal to the variable, ignoring surrounding whitespace.
+    fn select(&self, ctx: &HashMap<String, String>) -> Option<&PromptSource> {
+        let value = ctx.get(&self.variable)?.trim();
+        self.cases
+            .iter()
+            .find(|(case, _)| case == value)
+            .map(|(_, source)| source)
+    }
+}
+
 /// Defines a single step in a chain.
 struct ChainStepDefinition<'a> {
     pub output_key: String,
     pub source: PromptSource,
+    pub kind: StepKind,
     pub provider_id: Option<String>,
-    pub mode: MultiChainStepMode,
-    pub condition: Option<Box<dyn Fn(&HashMap<String, String>) -> bool + Send + Sync + 'a>>,
+    pub pool: Option<String>,
+    /// Name of the conversation whose message history the step shares.
+    pub conversation: Option<String>,
+    pub condition: Option<StepCondition<'a>>,
+    /// The `if:` of a stored chain or expression the condition was built from, kept to
+    /// save it back.
+    pub condition_spec: Option<Condition>,
+    /// Why a condition expression of the step could not be parsed, reported when the
+    /// chain runs.
+    pub condition_error: Option<String>,
+    /// Ends the chain once the step has run and this is true.
+    pub exit_condition: Option<StepCondition<'a>>,
+    /// The `exit_when:` the exit condition was built from, kept to save it back.
+    pub exit_spec: Option<Condition>,
+    /// Prompt run instead when the condition is not met or no switch case matches.
+    pub else_source: Option<PromptSource>,
+    /// Set for switch steps, whose `source` is unused.
+    pub switch: Option<Switch>,
     pub fallback_source: Option<PromptSource>,
+    pub context_budget: Option<ContextBudget>,
+    /// Number of times the prompt is sent, and how the answers are aggregated.
+    pub samples: Option<(usize, AggregateStrategy)>,
+    pub guard: Option<Guard>,
+}
+
+impl<'a> ChainStepDefinition<'a> {
+    /// Creates an unconditional LLM step without provider or fallback.
+    fn new(output_key: &str, source: PromptSource) -> Self {
+        Self {
+            output_key: output_key.to_string(),
+            source,
+            kind: StepKind::Llm,
+            provider_id: None,
+            pool: None,
+            conversation: None,
+            condition: None,
+            condition_spec: None,
+            condition_error: None,
+            exit_condition: None,
+            exit_spec: None,
+            else_source: None,
+            switch: None,
+            fallback_source: None,
+            context_budget: None,
+            samples: None,
+            guard: None,
+        }
+    }
+
+    /// Creates a stored prompt step conditioned by an `if:` or expression.
+    fn conditional(output_key: &str, prompt_id_or_title: &str, cond: Condition) -> Self {
+        let step = Self::new(
+            output_key,
+            PromptSource::Stored(prompt_id_or_title.to_string()),
+        );
+        let (condition, condition_error) = match cond.predicate() {
+            Ok(predicate) => (Some(predicate), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            condition,
+            condition_spec: Some(cond),
+            condition_error,
+            ..step
+        }
+    }
+
+    /// Converts the step to the YAML chain format, which only references stored prompts
+    /// and describes conditions with `if:`.
+    fn to_definition(&self, workspace: Option<&str>) -> Result<StepDefinition, StoreError> {
+        if self.guard.is_some() && (self.switch.is_some() || !matches!(self.kind, StepKind::Llm)) {
+            return Err(self.unsupported("has a guard, which YAML only supports on prompt steps"));
+        }
+        if self.exit_condition.is_some() && self.exit_spec.is_none() {
+            return Err(self.unsupported("has a closure exit condition"));
+        }
+        if let Some(switch) = &self.switch {
+            return self.to_switch_step(switch, workspace);
+        }
+        let template = match &self.source {
+            PromptSource::Stored(s) | PromptSource::Raw(s) => s.clone(),
+        };
+        Ok(match &self.kind {
+            StepKind::Llm => StepDefinition::Sequential(self.to_llm_step(workspace)?),
+            StepKind::Retrieve { top_k } => StepDefinition::Retrieve {
+                retrieve: RetrieveStep {
+                    id: self.output_key.clone(),
+                    query: template,
+                    provider: self.provider_id.clone(),
+                    top_k: *top_k,
+                    exit_when: self.exit_spec.clone(),
+                },
+            },
+            StepKind::Fetch {
+                max_bytes,
+                readable,
+            } => StepDefinition::Fetch {
+                fetch: FetchStep {
+                    id: self.output_key.clone(),
+                    url: template,
+                    max_bytes: (*max_bytes != web::DEFAULT_MAX_BYTES).then_some(*max_bytes),
+                    readable: *readable,
+                    exit_when: self.exit_spec.clone(),
+                },
+            },
+            StepKind::Shell { args, stdin } => StepDefinition::Shell {
+                shell: ShellStep {
+                    id: self.output_key.clone(),
+                    command: template,
+                    args: args.clone(),
+                    stdin: stdin.clone(),
+                    exit_when: self.exit_spec.clone(),
+                },
+            },
+        })
+    }
+
+    fn to_llm_step(&self, workspace: Option<&str>) -> Result<Step, StoreError> {
+        if self.condition.is_some() && self.condition_spec.is_none() {
+            return Err(self.unsupported("has a closure condition"));
+        }
+        Ok(Step {
+            id: self.output_key.clone(),
+            prompt: self.stored(&self.source, workspace, "uses a raw prompt")?,
+            provider: self.provider_id.clone(),
+            pool: self.pool.clone(),
+            condition: self.condition_spec.clone(),
+            otherwise: self.else_step(workspace)?,
+            exit_when: self.exit_spec.clone(),
+            context: self.context_budget.clone(),
+            samples: self
+                .samples
+                .clone()
+                .map(|(count, aggregate)| SamplesSpec { count, aggregate }),
+            guard: self.guard.clone(),
+            conversation: self.conversation.clone(),
+            on_error: self.fallback_step(workspace)?,
+        })
+    }
+
+    fn to_switch_step(
+        &self,
+        switch: &Switch,
+        workspace: Option<&str>,
+    ) -> Result<StepDefinition, StoreError> {
+        Ok(StepDefinition::Switch {
+            switch: SwitchStep {
+                id: self.output_key.clone(),
+                on: switch.variable.clone(),
+                cases: switch
+                    .cases
+                    .iter()
+                    .map(|(value, source)| {
+                        Ok((
+                            value.clone(),
+                            self.stored(source, workspace, "has a raw case prompt")?,
+                        ))
+                    })
+                    .collect::<Result<_, StoreError>>()?,
+                default: self.else_step(workspace)?.map(|step| step.prompt),
+                exit_when: self.exit_spec.clone(),
+                provider: self.provider_id.clone(),
+                pool: self.pool.clone(),
+                on_error: self.fallback_step(workspace)?,
+                context: self.context_budget.clone(),
+                conversation: self.conversation.clone(),
+            },
+        })
+    }
+
+    fn else_step(&self, workspace: Option<&str>) -> Result<Option<FallbackStep>, StoreError> {
+        self.else_source
+            .as_ref()
+            .map(|source| self.stored(source, workspace, "has a raw else prompt"))
+            .transpose()
+            .map(|prompt| prompt.map(|prompt| FallbackStep { prompt }))
+    }
+
+    fn fallback_step(&self, workspace: Option<&str>) -> Result<Option<FallbackStep>, StoreError> {
+        self.fallback_source
+            .as_ref()
+            .map(|source| self.stored(source, workspace, "has a raw fallback prompt"))
+            .transpose()
+            .map(|prompt| prompt.map(|prompt| FallbackStep { prompt }))
+    }
+
+    /// The reference of a stored prompt, qualified with the workspace of the chain.
+    fn stored(
+        &self,
+        source: &PromptSource,
+        workspace: Option<&str>,
+        what: &str,
+    ) -> Result<String, StoreError> {
+        match source {
+            PromptSource::Stored(reference) => Ok(match workspace {
+                Some(ws) if !reference.contains("::") => format!("{}::{}", ws, reference),
+                _ => reference.clone(),
+            }),
+            PromptSource::Raw(_) => Err(self.unsupported(what)),
+        }
+    }
+
+    fn unsupported(&self, what: &str) -> StoreError {
+        StoreError::Configuration(format!(
+            "Step '{}' {} and cannot be saved as a chain definition",
+            self.output_key, what
+        ))
+    }
+}
+
+/// Converts a refinement loop to the `loop:` YAML step.
+fn to_loop_step(
+    steps: &[ChainStepDefinition],
+    max_iterations: usize,
+    workspace: Option<&str>,
+) -> Result<StepDefinition, StoreError> {
+    let [generator, critic] = steps else {
+        return Err(StoreError::Configuration(
+            "A refinement loop needs a generator and a critic step".to_string(),
+        ));
+    };
+    Ok(StepDefinition::Loop {
+        refine: LoopStep {
+            id: generator.output_key.clone(),
+            prompt: generator.stored(&generator.source, workspace, "uses a raw prompt")?,
+            critic: CriticStep {
+                id: critic.output_key.clone(),
+                prompt: critic.stored(&critic.source, workspace, "uses a raw prompt")?,
+            },
+            max_iterations,
+            provider: generator.provider_id.clone(),
+            pool: generator.pool.clone(),
+            context: generator.context_budget.clone(),
+            conversation: generator.conversation.clone(),
+        },
+    })
 }
 
 /// Represents a node in the execution graph of a chain.
 enum ExecutionNode<'a> {
     /// A single, sequential step.
-    Step(ChainStepDefinition<'a>),
+    Step(Box<ChainStepDefinition<'a>>),
     /// A group of steps to be executed in parallel.
     Parallel(Vec<ChainStepDefinition<'a>>),
+    /// A generator step and a critic step, repeated until the critic approves.
+    Refine {
+        steps: Vec<ChainStepDefinition<'a>>,
+        max_iterations: usize,
+    },
 }
 
 /// A builder for defining a group of parallel steps.
@@ -112,51 +566,77 @@ impl<'a> ParallelGroupBuilder<'a> {
 
     /// Adds a step from the store to the parallel group.
     pub fn step(mut self, output_key: &str, prompt_id_or_title: &str) -> Self {
-        self.steps.push(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Stored(prompt_id_or_title.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
-            condition: None,
-            fallback_source: None,
-        });
+        self.steps.push(ChainStepDefinition::new(
+            output_key,
+            PromptSource::Stored(prompt_id_or_title.to_string()),
+        ));
         self
     }
-    
+
     /// Adds a conditional step from the store to the parallel group.
     pub fn step_if<F>(mut self, output_key: &str, prompt_id_or_title: &str, condition: F) -> Self
     where
         F: Fn(&HashMap<String, String>) -> bool + Send + Sync + 'a,
     {
         self.steps.push(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Stored(prompt_id_or_title.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
             condition: Some(Box::new(condition)),
-            fallback_source: None,
+            ..ChainStepDefinition::new(
+                output_key,
+                PromptSource::Stored(prompt_id_or_title.to_string()),
+            )
         });
         self
     }
 
+    /// Adds a conditional step from the store to the parallel group, run only if the
+    /// expression is true. See `ChainRunner::step_if_expr`.
+    pub fn step_if_expr(self, output_key: &str, prompt_id_or_title: &str, expr: &str) -> Self {
+        self.step_when(
+            output_key,
+            prompt_id_or_title,
+            Condition::Expr(expr.to_string()),
+        )
+    }
+
+    /// Adds a step of a stored chain, conditioned by its `if:`.
+    pub(super) fn step_when(
+        mut self,
+        output_key: &str,
+        prompt_id_or_title: &str,
+        cond: Condition,
+    ) -> Self {
+        self.steps.push(ChainStepDefinition::conditional(
+            output_key,
+            prompt_id_or_title,
+            cond,
+        ));
+        self
+    }
+
     /// Sets a fallback prompt from the store for the last added step in the group.
     pub fn on_error_stored(mut self, fallback_id_or_title: &str) -> Self {
         if let Some(last_step) = self.steps.last_mut() {
-            last_step.fallback_source = Some(PromptSource::Stored(fallback_id_or_title.to_string()));
+            last_step.fallback_source =
+                Some(PromptSource::Stored(fallback_id_or_title.to_string()));
+        }
+        self
+    }
+
+    /// Sets the prompt from the store run instead of the last added conditional step in
+    /// the group when its condition is not met.
+    pub fn else_stored(mut self, prompt_id_or_title: &str) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.else_source = Some(PromptSource::Stored(prompt_id_or_title.to_string()));
         }
         self
     }
 
     /// Adds a raw prompt step to the parallel group.
     pub fn step_raw(mut self, output_key: &str, prompt_content: &str) -> Self {
-        self.steps.push(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Raw(prompt_content.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
-            condition: None,
-            fallback_source: None,
-        });
+        self.steps.push(ChainStepDefinition::new(
+            output_key,
+            PromptSource::Raw(prompt_content.to_string()),
+        ));
         self
     }
 
@@ -167,50 +647,219 @@ impl<'a> ParallelGroupBuilder<'a> {
         }
         self
     }
+
+    /// Sets the provider pool for the last added step in the parallel group.
+    pub fn with_provider_pool(mut self, pool: &str) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.pool = Some(pool.to_string());
+        }
+        self
+    }
+
+    /// Adds the last added step in the parallel group to a named conversation.
+    pub fn in_conversation(mut self, name: &str) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.conversation = Some(name.to_string());
+        }
+        self
+    }
+
+    /// Sets the input budget of the last added step in the parallel group.
+    pub fn with_context_budget(mut self, budget: ContextBudget) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.context_budget = Some(budget);
+        }
+        self
+    }
+
+    /// Guards the output of the last added step in the parallel group. See
+    /// `ChainRunner::with_guard`.
+    pub fn with_guard(mut self, guard: Guard) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.guard = Some(guard);
+        }
+        self
+    }
+
+    /// Samples the last added step in the parallel group. See `ChainRunner::with_samples`.
+    pub fn with_samples(mut self, count: usize, aggregate: AggregateStrategy) -> Self {
+        if let Some(last_step) = self.steps.last_mut() {
+            last_step.samples = Some((count, aggregate));
+        }
+        self
+    }
+}
+
+/// The store a chain runs against: borrowed, or shared so the runner is `'static`.
+enum StoreRef<'a> {
+    Borrowed(&'a PromptStore),
+    Shared(Arc<PromptStore>),
+}
+
+impl Deref for StoreRef<'_> {
+    type Target = PromptStore;
+
+    fn deref(&self) -> &PromptStore {
+        match self {
+            StoreRef::Borrowed(store) => store,
+            StoreRef::Shared(store) => store,
+        }
+    }
+}
+
+/// The providers of a chain: borrowed, or a shared registry so the runner is `'static`.
+enum ChainBackend<'a> {
+    Borrowed(LLMBackendRef<'a>),
+    Shared(Arc<LLMRegistry>),
 }
 
 /// A fluent builder to define and execute a multi-step prompt chain.
+///
+/// Created with `PromptStore::chain`, it borrows the store and the registry. Runners
+/// created with `PromptStore::chain_owned` own them and are `ChainRunner<'static>`, to
+/// be kept in a struct or moved into a spawned task.
 pub struct ChainRunner<'a> {
-    store: &'a PromptStore,
-    backend: LLMBackendRef<'a>,
+    store: StoreRef<'a>,
+    backend: ChainBackend<'a>,
     nodes: Vec<ExecutionNode<'a>>,
+    defaults: HashMap<String, String>,
+    required: Vec<String>,
+    presets: BTreeMap<String, BTreeMap<String, String>>,
+    preset: Option<String>,
+    resolved: HashMap<String, String>,
     vars: HashMap<String, String>,
+    env_profile: Option<String>,
+    workspace: Option<String>,
+    lang: Option<String>,
+    allowed_commands: Vec<String>,
+    sinks: Vec<Sink>,
+    failover_groups: HashMap<String, Vec<String>>,
+    pools: HashMap<String, Vec<(String, u32)>>,
+    pool_weights: Mutex<HashMap<String, Vec<i64>>>,
+    served_by: Mutex<HashMap<String, String>>,
+    steps: Mutex<Vec<StepTrace>>,
+    step_listener: Option<UnboundedSender<StepTrace>>,
+    conversations: Mutex<HashMap<String, Vec<ChatMessage>>>,
+    redaction: Option<RedactionPolicy>,
 }
 
 impl<'a> ChainRunner<'a> {
     /// Creates a new `ChainRunner`.
     pub(crate) fn new(store: &'a PromptStore, backend: LLMBackendRef<'a>) -> Self {
+        Self::from_parts(StoreRef::Borrowed(store), ChainBackend::Borrowed(backend))
+    }
+
+    fn from_parts(store: StoreRef<'a>, backend: ChainBackend<'a>) -> Self {
         Self {
             store,
             backend,
             nodes: Vec::new(),
+            defaults: HashMap::new(),
+            required: Vec::new(),
+            presets: BTreeMap::new(),
+            preset: None,
+            resolved: HashMap::new(),
             vars: HashMap::new(),
+            env_profile: None,
+            workspace: None,
+            lang: None,
+            allowed_commands: Vec::new(),
+            sinks: Vec::new(),
+            failover_groups: HashMap::new(),
+            pools: HashMap::new(),
+            pool_weights: Mutex::new(HashMap::new()),
+            served_by: Mutex::new(HashMap::new()),
+            steps: Mutex::new(Vec::new()),
+            step_listener: None,
+            conversations: Mutex::new(HashMap::new()),
+            redaction: None,
         }
     }
 
+    /// Creates a `ChainRunner` owning a share of the store and of the registry.
+    pub(crate) fn new_owned(store: Arc<PromptStore>, registry: Arc<LLMRegistry>) -> Self {
+        Self::from_parts(StoreRef::Shared(store), ChainBackend::Shared(registry))
+    }
+
     /// Adds a sequential step from the store.
     pub fn step(mut self, output_key: &str, prompt_id_or_title: &str) -> Self {
-        self.nodes.push(ExecutionNode::Step(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Stored(prompt_id_or_title.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
-            condition: None,
-            fallback_source: None,
-        }));
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition::new(
+                output_key,
+                PromptSource::Stored(prompt_id_or_title.to_string()),
+            ))));
         self
     }
 
     /// Adds a sequential step with a raw prompt.
     pub fn step_raw(mut self, output_key: &str, prompt_content: &str) -> Self {
-        self.nodes.push(ExecutionNode::Step(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Raw(prompt_content.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
-            condition: None,
-            fallback_source: None,
-        }));
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition::new(
+                output_key,
+                PromptSource::Raw(prompt_content.to_string()),
+            ))));
+        self
+    }
+
+    /// Adds a retrieval step that embeds the rendered query and stores the `top_k` most
+    /// similar chunks from the document index under `output_key`. The query is embedded
+    /// with the provider that built the index; a step provider must be that one.
+    pub fn step_retrieve(mut self, output_key: &str, query_template: &str, top_k: usize) -> Self {
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition {
+                kind: StepKind::Retrieve { top_k },
+                ..ChainStepDefinition::new(
+                    output_key,
+                    PromptSource::Raw(query_template.to_string()),
+                )
+            })));
+        self
+    }
+
+    /// Adds a step that fetches the rendered URL and stores the response text under `output_key`.
+    /// No LLM provider is needed. Use `with_max_bytes` and `with_readability` to tune it.
+    pub fn step_fetch(mut self, output_key: &str, url_template: &str) -> Self {
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition {
+                kind: StepKind::Fetch {
+                    max_bytes: web::DEFAULT_MAX_BYTES,
+                    readable: false,
+                },
+                ..ChainStepDefinition::new(output_key, PromptSource::Raw(url_template.to_string()))
+            })));
+        self
+    }
+
+    /// Adds a step that runs a local program and stores its stdout under `output_key`.
+    /// Arguments are rendered with the chain context and passed without a shell.
+    /// The program must be permitted with `allow_commands`, otherwise the step fails, and
+    /// is killed once it runs past the `[defaults]` timeout of `config.toml` (60s if unset).
+    pub fn step_shell(mut self, output_key: &str, program: &str, args: &[&str]) -> Self {
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition {
+                kind: StepKind::Shell {
+                    args: args.iter().map(|a| a.to_string()).collect(),
+                    stdin: None,
+                },
+                ..ChainStepDefinition::new(output_key, PromptSource::Raw(program.to_string()))
+            })));
+        self
+    }
+
+    /// Sets a template rendered and piped to the stdin of the last added shell step.
+    pub fn with_stdin(mut self, stdin_template: &str) -> Self {
+        if let Some(ExecutionNode::Step(step)) = self.nodes.last_mut() {
+            if let StepKind::Shell { stdin, .. } = &mut step.kind {
+                *stdin = Some(stdin_template.to_string());
+            }
+        }
+        self
+    }
+
+    /// Permits shell steps to run the given programs. Shell steps are disabled by default.
+    pub fn allow_commands(mut self, commands: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_commands
+            .extend(commands.into_iter().map(Into::into));
         self
     }
 
@@ -219,14 +868,158 @@ impl<'a> ChainRunner<'a> {
     where
         F: Fn(&HashMap<String, String>) -> bool + Send + Sync + 'a,
     {
-        self.nodes.push(ExecutionNode::Step(ChainStepDefinition {
-            output_key: output_key.to_string(),
-            source: PromptSource::Stored(prompt_id_or_title.to_string()),
-            provider_id: None,
-            mode: MultiChainStepMode::Completion,
-            condition: Some(Box::new(condition)),
-            fallback_source: None,
-        }));
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition {
+                condition: Some(Box::new(condition)),
+                ..ChainStepDefinition::new(
+                    output_key,
+                    PromptSource::Stored(prompt_id_or_title.to_string()),
+                )
+            })));
+        self
+    }
+
+    /// Adds a conditional step from the store, run only if the expression is true, e.g.
+    /// `sentiment == 'negative' && length(feedback) > 100`.
+    ///
+    /// Expressions compare chain variables and literals with `==`, `!=`, `<`, `<=`, `>`
+    /// and `>=`, combine them with `&&`/`and`, `||`/`or` and `!`/`not`, and can call
+    /// `length`, `lower`, `upper`, `trim`, `contains`, `starts_with` and `ends_with`.
+    /// Unlike closures, expressions are saved with `save_as`. An invalid expression
+    /// makes `run` fail before any step executes.
+    pub fn step_if_expr(self, output_key: &str, prompt_id_or_title: &str, expr: &str) -> Self {
+        self.step_when(
+            output_key,
+            prompt_id_or_title,
+            Condition::Expr(expr.to_string()),
+        )
+    }
+
+    /// Adds a step of a stored chain, conditioned by its `if:`.
+    pub(super) fn step_when(
+        mut self,
+        output_key: &str,
+        prompt_id_or_title: &str,
+        cond: Condition,
+    ) -> Self {
+        self.nodes.push(ExecutionNode::Step(Box::new(
+            ChainStepDefinition::conditional(output_key, prompt_id_or_title, cond),
+        )));
+        self
+    }
+
+    /// Adds a step that runs the prompt from the store of the case matching the value of
+    /// `variable`, e.g. the output of a classifier step. Values are compared after
+    /// trimming surrounding whitespace. The step is skipped when no case matches, unless
+    /// a default prompt is set with `else_stored` or `else_raw`.
+    ///
+    /// ```no_run
+    /// # use prompt_store::ChainRunner;
+    /// # fn build(chain: ChainRunner<'_>) -> ChainRunner<'_> {
+    /// chain
+    ///     .step("route", "Classify Ticket")
+    ///     .branch("answer", "route", [("billing", "Billing Reply"), ("bug", "Bug Triage")])
+    ///     .else_stored("General Reply")
+    /// # }
+    /// ```
+    pub fn branch<'c>(
+        mut self,
+        output_key: &str,
+        variable: &str,
+        cases: impl IntoIterator<Item = (&'c str, &'c str)>,
+    ) -> Self {
+        let cases = cases
+            .into_iter()
+            .map(|(value, prompt)| (value.to_string(), PromptSource::Stored(prompt.to_string())))
+            .collect();
+        self.nodes
+            .push(ExecutionNode::Step(Box::new(ChainStepDefinition {
+                switch: Some(Switch {
+                    variable: variable.to_string(),
+                    cases,
+                }),
+                ..ChainStepDefinition::new(output_key, PromptSource::Raw(String::new()))
+            })));
+        self
+    }
+
+    /// Sets the prompt from the store run instead of the last added step when its
+    /// condition is not met, or when no case of a `branch` matches. Its output is stored
+    /// under the key of the step.
+    pub fn else_stored(mut self, prompt_id_or_title: &str) -> Self {
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            step_def.else_source = Some(PromptSource::Stored(prompt_id_or_title.to_string()));
+        }
+        self
+    }
+
+    /// Sets a raw prompt run instead of the last added step when its condition is not
+    /// met, or when no case of a `branch` matches.
+    pub fn else_raw(mut self, prompt_content: &str) -> Self {
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            step_def.else_source = Some(PromptSource::Raw(prompt_content.to_string()));
+        }
+        self
+    }
+
+    /// Ends the chain successfully after the last added step when the condition is met,
+    /// skipping the remaining steps, e.g. once a validator step approves a draft. The
+    /// condition sees the output of the step, and is not checked when the step is
+    /// skipped. Sinks still receive the outputs, and `RunMetadata::finished_by` names
+    /// the step.
+    pub fn finish_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn(&HashMap<String, String>) -> bool + Send + Sync + 'a,
+    {
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            step_def.exit_condition = Some(Box::new(condition));
+            step_def.exit_spec = None;
+        }
+        self
+    }
+
+    /// Like `finish_if`, with an expression (see `step_if_expr`) that is saved with
+    /// `save_as`, e.g. `contains(lower(review), 'approved')`.
+    pub fn finish_if_expr(self, expr: &str) -> Self {
+        self.finish_when(Condition::Expr(expr.to_string()))
+    }
+
+    /// Sets the `exit_when:` of the last added step of a stored chain.
+    pub(super) fn finish_when(mut self, cond: Condition) -> Self {
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            match cond.predicate() {
+                Ok(predicate) => step_def.exit_condition = Some(predicate),
+                Err(e) => step_def.condition_error = Some(e),
+            }
+            step_def.exit_spec = Some(cond);
+        }
+        self
+    }
+
+    /// Adds a bounded refinement loop: the `generator` prompt writes a draft stored under
+    /// `draft_key`, then the `critic` prompt reviews it into `critique_key`. Both run
+    /// again, seeing the previous draft and critique, until the critique contains
+    /// `APPROVED` or `max_iterations` is reached. The chain then goes on with the last
+    /// draft either way; `RunMetadata::refinements` holds every iteration.
+    ///
+    /// `with_provider`, `with_provider_pool`, `with_context_budget` and `in_conversation`
+    /// apply to both steps of the loop.
+    pub fn refine_loop(
+        mut self,
+        draft_key: &str,
+        generator: &str,
+        critique_key: &str,
+        critic: &str,
+        max_iterations: usize,
+    ) -> Self {
+        let steps = vec![
+            ChainStepDefinition::new(draft_key, PromptSource::Stored(generator.to_string())),
+            ChainStepDefinition::new(critique_key, PromptSource::Stored(critic.to_string())),
+        ];
+        self.nodes.push(ExecutionNode::Refine {
+            steps,
+            max_iterations,
+        });
         self
     }
 
@@ -245,21 +1038,16 @@ impl<'a> ChainRunner<'a> {
     /// Sets a fallback prompt from the store for the last added step.
     /// This is executed if the primary prompt execution fails.
     pub fn on_error_stored(mut self, fallback_id_or_title: &str) -> Self {
-        if let Some(node) = self.nodes.last_mut() {
-            if let ExecutionNode::Step(step_def) = node {
-                step_def.fallback_source =
-                    Some(PromptSource::Stored(fallback_id_or_title.to_string()));
-            }
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            step_def.fallback_source = Some(PromptSource::Stored(fallback_id_or_title.to_string()));
         }
         self
     }
 
     /// Sets a raw fallback prompt for the last added step.
     pub fn on_error_raw(mut self, fallback_content: &str) -> Self {
-        if let Some(node) = self.nodes.last_mut() {
-            if let ExecutionNode::Step(step_def) = node {
-                step_def.fallback_source = Some(PromptSource::Raw(fallback_content.to_string()));
-            }
+        if let Some(ExecutionNode::Step(step_def)) = self.nodes.last_mut() {
+            step_def.fallback_source = Some(PromptSource::Raw(fallback_content.to_string()));
         }
         self
     }
@@ -271,9 +1059,9 @@ impl<'a> ChainRunner<'a> {
                 ExecutionNode::Step(step) => {
                     step.provider_id = Some(provider_id.to_string());
                 }
-                ExecutionNode::Parallel(steps) => {
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
                     for step in steps {
-                        if step.provider_id.is_none() {
+                        if step.provider_id.is_none() && step.pool.is_none() {
                             step.provider_id = Some(provider_id.to_string());
                         }
                     }
@@ -283,10 +1071,153 @@ impl<'a> ChainRunner<'a> {
         self
     }
 
-    /// Sets the execution mode for the last added step.
-    pub fn with_mode(mut self, mode: MultiChainStepMode) -> Self {
-        if let Some(ExecutionNode::Step(step)) = self.nodes.last_mut() {
-            step.mode = mode;
+    /// Spreads the last added step, or all steps in the last parallel group, across the
+    /// members of a provider pool defined with `provider_pool`.
+    pub fn with_provider_pool(mut self, pool: &str) -> Self {
+        if let Some(node) = self.nodes.last_mut() {
+            match node {
+                ExecutionNode::Step(step) => {
+                    step.pool = Some(pool.to_string());
+                }
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    for step in steps {
+                        if step.provider_id.is_none() && step.pool.is_none() {
+                            step.pool = Some(pool.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Sets the input budget of the last added step, or of all steps in the last parallel
+    /// group without one. When the rendered prompt is larger, the variables it uses are
+    /// truncated or summarized, largest first, for this step only; the chain context
+    /// keeps their full values. Applies to LLM steps.
+    pub fn with_context_budget(mut self, budget: ContextBudget) -> Self {
+        if let Some(node) = self.nodes.last_mut() {
+            match node {
+                ExecutionNode::Step(step) => {
+                    step.context_budget = Some(budget);
+                }
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    for step in steps {
+                        if step.context_budget.is_none() {
+                            step.context_budget = Some(budget.clone());
+                        }
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Sends the prompt of the last added step, or of all steps in the last parallel
+    /// group without sampling, `count` times concurrently, and aggregates the answers
+    /// into the step output, e.g. to make classification steps more reliable. With a
+    /// provider pool, the samples are spread across its members.
+    ///
+    /// Sampled prompts are sent alone, outside of any conversation. Applies to LLM steps.
+    pub fn with_samples(mut self, count: usize, aggregate: AggregateStrategy) -> Self {
+        if let Some(node) = self.nodes.last_mut() {
+            match node {
+                ExecutionNode::Step(step) => {
+                    step.samples = Some((count, aggregate));
+                }
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    for step in steps {
+                        if step.samples.is_none() {
+                            step.samples = Some((count, aggregate.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Checks the output of the last added step, or of all steps in the last parallel
+    /// group without a guard, against policy rules (denied patterns, maximum length,
+    /// required JSON keys, moderation model) before it is stored. Depending on the
+    /// guard's `GuardAction`, an output breaking a rule fails the step with
+    /// `RunError::Guard`, is redacted, or is replaced by the output of a fallback prompt.
+    pub fn with_guard(mut self, guard: Guard) -> Self {
+        if let Some(node) = self.nodes.last_mut() {
+            match node {
+                ExecutionNode::Step(step) => {
+                    step.guard = Some(guard);
+                }
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    for step in steps {
+                        if step.guard.is_none() {
+                            step.guard = Some(guard.clone());
+                        }
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Sets the execution mode for the last added step.
+    ///
+    /// `MultiChainStepMode::Chat` adds the step to the default conversation of the chain
+    /// (see `in_conversation`). Other modes send the prompt as a single fresh message.
+    pub fn with_mode(mut self, mode: MultiChainStepMode) -> Self {
+        if let Some(ExecutionNode::Step(step)) = self.nodes.last_mut() {
+            step.conversation = match mode {
+                MultiChainStepMode::Chat => step
+                    .conversation
+                    .take()
+                    .or_else(|| Some(DEFAULT_CONVERSATION.to_string())),
+                _ => None,
+            };
+        }
+        self
+    }
+
+    /// Adds the last added step, or all steps in the last parallel group, to a named
+    /// conversation.
+    ///
+    /// Steps of a conversation send the prompts and replies of the previous steps of that
+    /// conversation before their own prompt, so later steps can refine earlier answers.
+    /// Parallel steps of a conversation see the history as it was before their group.
+    pub fn in_conversation(mut self, name: &str) -> Self {
+        if let Some(node) = self.nodes.last_mut() {
+            match node {
+                ExecutionNode::Step(step) => {
+                    step.conversation = Some(name.to_string());
+                }
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    for step in steps {
+                        step.conversation = Some(name.to_string());
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Sets the maximum number of bytes read by the last added fetch step.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        if let Some(ExecutionNode::Step(step)) = self.nodes.last_mut() {
+            if let StepKind::Fetch {
+                max_bytes: limit, ..
+            } = &mut step.kind
+            {
+                *limit = max_bytes;
+            }
+        }
+        self
+    }
+
+    /// Strips HTML markup from the response of the last added fetch step.
+    pub fn with_readability(mut self) -> Self {
+        if let Some(ExecutionNode::Step(step)) = self.nodes.last_mut() {
+            if let StepKind::Fetch { readable, .. } = &mut step.kind {
+                *readable = true;
+            }
         }
         self
     }
@@ -303,10 +1234,230 @@ impl<'a> ChainRunner<'a> {
         self
     }
 
+    /// Adds the variables of the vars file named by `PS_VARS_FILE` and of the `PS_VAR_*`
+    /// environment variables, as `prompt-store chain run` does. They override the
+    /// preset and the environment profile, and are overridden by `vars()`.
+    pub fn vars_auto(self) -> Result<Self, StoreError> {
+        self.vars_from(&VarSources::auto())
+    }
+
+    /// Adds the variables of vars files, the environment and optionally stdin, with the
+    /// precedence of `vars_auto()` (see [`VarSources`]).
+    pub fn vars_from(mut self, sources: &VarSources) -> Result<Self, StoreError> {
+        self.resolved = sources.resolve().map_err(StoreError::Configuration)?;
+        Ok(self)
+    }
+
+    /// Sets the defaults of a stored chain definition, overridden by the profile and `vars()`.
+    pub(crate) fn default_vars(mut self, defaults: HashMap<String, String>) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Sets the variables a stored chain definition requires, checked before running.
+    pub(crate) fn required_vars(mut self, names: Vec<String>) -> Self {
+        self.required = names;
+        self
+    }
+
+    /// Sets the variable presets saved with a stored chain, selected with `preset()`.
+    pub(crate) fn presets(mut self, presets: BTreeMap<String, BTreeMap<String, String>>) -> Self {
+        self.presets = presets;
+        self
+    }
+
+    /// Uses a named set of variables saved with a stored chain (`prompt-store chain run
+    /// --save-preset`). They override the environment profile and are overridden by
+    /// `vars()`.
+    pub fn preset(mut self, name: &str) -> Self {
+        self.preset = Some(name.to_string());
+        self
+    }
+
+    /// Uses the variables of a stored environment profile as defaults.
+    /// Variables set with `vars()` take precedence over the profile.
+    pub fn env_profile(mut self, name: &str) -> Self {
+        self.env_profile = Some(name.to_string());
+        self
+    }
+
+    /// Resolves stored step prompts inside the given workspace only.
+    pub fn workspace(mut self, name: &str) -> Self {
+        self.workspace = Some(name.to_string());
+        self
+    }
+
+    /// Runs the translations of stored step prompts in a language (e.g., `fr`); steps
+    /// whose prompt has none use its default content.
+    pub fn lang(mut self, code: &str) -> Self {
+        self.lang = Some(code.to_string());
+        self
+    }
+
+    /// Redacts personal data (see `redact`) from the variables of prompts and retrieval
+    /// queries before they are sent to providers. Shell and fetch steps, sinks and the
+    /// chain output see the original values.
+    pub fn redact_inputs(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
+    /// Sends each step to a channel as soon as it completes, with secrets redacted as in
+    /// `RunMetadata::steps`. Steps of a parallel group are sent when the whole group is done.
+    pub fn on_step(mut self, listener: UnboundedSender<StepTrace>) -> Self {
+        self.step_listener = Some(listener);
+        self
+    }
+
+    /// Adds a sink that receives the chain outputs once every step has completed.
+    pub fn on_complete(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Defines a failover group usable as a provider ID in `with_provider`.
+    ///
+    /// Steps using the group call `primary` first and move on to each fallback in order
+    /// when a provider fails with a transient error (HTTP 429/5xx or timeout).
+    pub fn failover_group(mut self, name: &str, primary: &str, fallbacks: &[&str]) -> Self {
+        let members = std::iter::once(primary)
+            .chain(fallbacks.iter().copied())
+            .map(str::to_string)
+            .collect();
+        self.failover_groups.insert(name.to_string(), members);
+        self
+    }
+
+    /// Defines a pool of providers with relative weights, selected with `with_provider_pool`.
+    ///
+    /// Each step using the pool is sent to the next member in weighted round-robin order,
+    /// so parallel steps spread across API keys or deployments. The other members are
+    /// tried in turn if the selected one fails with a transient error.
+    pub fn provider_pool(mut self, name: &str, members: &[(&str, u32)]) -> Self {
+        let members = members
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(provider, weight)| (provider.to_string(), *weight))
+            .collect();
+        self.pools.insert(name.to_string(), members);
+        self
+    }
+
+    /// Serializes the chain to the YAML format of `prompt-store chain import`.
+    ///
+    /// Variables set with `vars()` become the defaults of the definition. Failover
+    /// groups, provider pools and allowed commands are not part of it: the CLI reads
+    /// them from `config.toml`. Steps with raw prompts or closure conditions cannot be
+    /// saved, as the format only references stored prompts.
+    pub fn to_yaml(&self) -> Result<String, StoreError> {
+        let workspace = self.workspace.as_deref();
+        let steps = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                ExecutionNode::Step(step) => step.to_definition(workspace),
+                ExecutionNode::Parallel(steps) => Ok(StepDefinition::Parallel {
+                    parallel: steps
+                        .iter()
+                        .map(|step| step.to_llm_step(workspace))
+                        .collect::<Result<_, _>>()?,
+                }),
+                ExecutionNode::Refine {
+                    steps,
+                    max_iterations,
+                } => to_loop_step(steps, *max_iterations, workspace),
+            })
+            .collect::<Result<_, StoreError>>()?;
+        let mut vars: BTreeMap<String, VarSpec> = self
+            .defaults
+            .iter()
+            .chain(&self.vars)
+            .map(|(name, value)| (name.clone(), VarSpec::Default(value.clone())))
+            .collect();
+        for name in &self.required {
+            vars.entry(name.clone()).or_insert(VarSpec::Spec {
+                required: true,
+                default: None,
+                description: None,
+            });
+        }
+        let chain = ChainFile {
+            vars,
+            steps,
+            sinks: self.sinks.clone(),
+        };
+        serde_yaml::to_string(&chain)
+            .map_err(|e| StoreError::Configuration(format!("Failed to serialize chain: {}", e)))
+    }
+
+    /// Saves the chain as a YAML definition in the store, replacing any chain definition
+    /// with this ID, so it can be run with `prompt-store chain run <id>` or `stored_chain()`.
+    pub fn save_as(&self, id: &str) -> Result<(), StoreError> {
+        let yaml = self.to_yaml()?;
+        self.store
+            .ctx
+            .prompts()
+            .save_chain_definition(id, &yaml)
+            .map_err(StoreError::Storage)
+    }
+
+    /// Returns the workspaces of the stored prompts the steps of the chain can run,
+    /// including `else`, switch, fallback and guard fallback prompts.
+    #[cfg(feature = "grpc")]
+    pub(crate) fn prompt_workspaces(&self) -> Result<Vec<String>, StoreError> {
+        let mut workspaces = Vec::new();
+        for node in &self.nodes {
+            let steps = match node {
+                ExecutionNode::Step(step) => std::slice::from_ref(step.as_ref()),
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => steps,
+            };
+            for step in steps {
+                let guard_fallback = step.guard.as_ref().and_then(|guard| match &guard.action {
+                    GuardAction::Fallback(prompt) => Some(prompt.as_str()),
+                    _ => None,
+                });
+                let cases = step.switch.iter().flat_map(|switch| &switch.cases);
+                let sources = std::iter::once(&step.source)
+                    .chain(&step.else_source)
+                    .chain(cases.map(|(_, source)| source))
+                    .chain(&step.fallback_source)
+                    .filter_map(|source| match source {
+                        PromptSource::Stored(id) => Some(id.as_str()),
+                        PromptSource::Raw(_) => None,
+                    })
+                    .chain(guard_fallback);
+                for id in sources {
+                    let (full_id, _) = self.store.find_prompt_variant(
+                        self.workspace.as_deref(),
+                        id,
+                        self.lang.as_deref(),
+                        None,
+                    )?;
+                    let workspace = parse_id(&full_id).0;
+                    if !workspaces.contains(&workspace) {
+                        workspaces.push(workspace);
+                    }
+                }
+            }
+        }
+        Ok(workspaces)
+    }
+
     /// Executes the chain.
     pub async fn run(self) -> Result<RunOutput, RunError> {
-        let reg = match self.backend {
-            LLMBackendRef::Registry(reg) => reg,
+        self.run_with_metadata().await.map(|(output, _)| output)
+    }
+
+    /// Executes the chain and also returns details about the run, such as the provider
+    /// that actually served each step.
+    pub async fn run_with_metadata(mut self) -> Result<(RunOutput, RunMetadata), RunError> {
+        // An organization policy may require the redaction the caller didn't ask for.
+        if self.redaction.is_none() {
+            self.redaction = load_required_redaction().map_err(StoreError::Configuration)?;
+        }
+        let reg: &LLMRegistry = match &self.backend {
+            ChainBackend::Borrowed(LLMBackendRef::Registry(reg)) => reg,
+            ChainBackend::Shared(reg) => reg,
             _ => {
                 return Err(StoreError::Configuration(
                     "ChainRunner requires a LLMRegistry".to_string(),
@@ -315,14 +1466,81 @@ impl<'a> ChainRunner<'a> {
             }
         };
 
-        let context = Arc::new(Mutex::new(self.vars.clone()));
+        let invalid = self
+            .nodes
+            .iter()
+            .flat_map(|node| match node {
+                ExecutionNode::Step(step) => std::slice::from_ref(&**step),
+                ExecutionNode::Parallel(steps) | ExecutionNode::Refine { steps, .. } => {
+                    steps.as_slice()
+                }
+            })
+            .find_map(|step| Some((&step.output_key, step.condition_error.as_ref()?)));
+        if let Some((key, e)) = invalid {
+            return Err(StoreError::Configuration(format!(
+                "Invalid condition of step '{}': {}",
+                key, e
+            ))
+            .into());
+        }
+
+        let mut initial_vars = self.defaults.clone();
+        let mut secret_keys = Vec::new();
+        if let Some(profile) = &self.env_profile {
+            let profile_vars = self.store.profile_vars(profile)?;
+            secret_keys.extend(profile_vars.keys().cloned());
+            initial_vars.extend(profile_vars);
+        }
+        if let Some(name) = &self.preset {
+            let preset = self.presets.get(name).ok_or_else(|| {
+                StoreError::Configuration(format!("The chain has no preset '{}'", name))
+            })?;
+            initial_vars.extend(preset.clone());
+        }
+        initial_vars.extend(self.resolved.clone());
+        initial_vars.extend(self.vars.clone());
+        let missing: Vec<&str> = self
+            .required
+            .iter()
+            .filter(|name| !initial_vars.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(StoreError::Configuration(format!(
+                "Missing required chain variables: {}",
+                missing.join(", ")
+            ))
+            .into());
+        }
+        secret_keys.extend(
+            initial_vars
+                .keys()
+                .filter(|key| is_secret_name(key))
+                .cloned(),
+        );
+        let secrets: Vec<(String, String)> = secret_keys
+            .into_iter()
+            .filter_map(|key| initial_vars.get(&key).map(|value| (key, value.clone())))
+            .collect();
+        let context = Arc::new(Mutex::new(initial_vars));
 
+        let mut finished_by = None;
+        let mut refinements = HashMap::new();
+        let mut reported = 0;
         for node in &self.nodes {
-            match node {
-                ExecutionNode::Step(step_def) => {
-                    self.execute_step(step_def, Arc::clone(&context), reg)
-                        .await?;
-                }
+            let result = match node {
+                ExecutionNode::Step(step_def) => self
+                    .execute_step(step_def, Arc::clone(&context), reg)
+                    .await
+                    .map(|ran| {
+                        let ctx = context.lock().unwrap();
+                        let exit = ran
+                            && step_def
+                                .exit_condition
+                                .as_ref()
+                                .is_some_and(|cond| cond(&ctx));
+                        exit.then(|| step_def.output_key.clone())
+                    }),
                 ExecutionNode::Parallel(steps) => {
                     let tasks = steps
                         .iter()
@@ -332,99 +1550,636 @@ impl<'a> ChainRunner<'a> {
                         })
                         .collect::<Vec<_>>();
 
-                    future::try_join_all(tasks).await?;
+                    future::try_join_all(tasks).await.map(|_| None)
                 }
+                ExecutionNode::Refine {
+                    steps,
+                    max_iterations,
+                } => self
+                    .execute_refine_loop(steps, *max_iterations, &context, reg)
+                    .await
+                    .map(|(draft_key, iterations)| {
+                        refinements.insert(draft_key, iterations);
+                        None
+                    }),
+            };
+            if let Some(listener) = &self.step_listener {
+                let steps = self.steps.lock().unwrap();
+                for step in &steps[reported..] {
+                    // A listener that went away only stops receiving.
+                    let _ = listener.send(StepTrace {
+                        rendered: hide_secrets(step.rendered.clone(), &secrets),
+                        output: hide_secrets(step.output.clone(), &secrets),
+                        ..step.clone()
+                    });
+                }
+                reported = steps.len();
+            }
+            finished_by = result.map_err(|e| e.redact(&secrets))?;
+            if finished_by.is_some() {
+                break;
             }
         }
 
         let final_context = Arc::try_unwrap(context).ok().unwrap().into_inner().unwrap();
-        Ok(RunOutput::Chain(final_context))
+        for sink in &self.sinks {
+            sink.deliver(&final_context).await.map_err(RunError::Sink)?;
+        }
+        let steps = self
+            .steps
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|step| StepTrace {
+                rendered: hide_secrets(step.rendered, &secrets),
+                output: hide_secrets(step.output, &secrets),
+                ..step
+            })
+            .collect();
+        let metadata = RunMetadata {
+            served_by: self.served_by.into_inner().unwrap(),
+            finished_by,
+            refinements,
+            steps,
+        };
+        Ok((RunOutput::Chain(final_context), metadata))
+    }
+
+    /// Runs the generator and critic steps of a refinement loop until the critic output
+    /// contains `APPROVAL_MARKER`, returning the draft key and the iterations.
+    async fn execute_refine_loop(
+        &self,
+        steps: &[ChainStepDefinition<'a>],
+        max_iterations: usize,
+        context: &Arc<Mutex<HashMap<String, String>>>,
+        reg: &LLMRegistry,
+    ) -> Result<(String, Vec<RefineIteration>), RunError> {
+        let [generator, critic] = steps else {
+            return Err(StoreError::Configuration(
+                "A refinement loop needs a generator and a critic step".to_string(),
+            )
+            .into());
+        };
+        let mut iterations = Vec::new();
+        for _ in 0..max_iterations {
+            self.execute_step(generator, Arc::clone(context), reg)
+                .await?;
+            self.execute_step(critic, Arc::clone(context), reg).await?;
+            let ctx = context.lock().unwrap();
+            let output = |key: &str| ctx.get(key).cloned().unwrap_or_default();
+            let iteration = RefineIteration {
+                draft: output(&generator.output_key),
+                critique: output(&critic.output_key),
+            };
+            let approved = iteration.critique.contains(APPROVAL_MARKER);
+            iterations.push(iteration);
+            if approved {
+                break;
+            }
+        }
+        Ok((generator.output_key.clone(), iterations))
     }
 
     async fn execute_step(
         &self,
         step_def: &ChainStepDefinition<'a>,
         context: Arc<Mutex<HashMap<String, String>>>,
-        reg: &'a llm::chain::LLMRegistry,
-    ) -> Result<(), RunError> {
-        let should_run = {
+        reg: &LLMRegistry,
+    ) -> Result<bool, RunError> {
+        let source = {
             let ctx = context.lock().unwrap();
-            step_def.condition.as_ref().map_or(true, |cond| cond(&ctx))
+            match &step_def.switch {
+                Some(switch) => switch.select(&ctx),
+                None if step_def.condition.as_ref().is_none_or(|cond| cond(&ctx)) => {
+                    Some(&step_def.source)
+                }
+                None => None,
+            }
+            .or(step_def.else_source.as_ref())
+        };
+        let Some(source) = source else {
+            debug!("step '{}' skipped by its condition", step_def.output_key);
+            return Ok(false);
         };
-        if !should_run {
-            return Ok(());
-        }
 
+        info!("running step '{}'", step_def.output_key);
+        let started = Instant::now();
         let result = self
-            .try_execute_source(&step_def.source, &context, step_def, reg)
+            .try_execute_source(source, &context, step_def, reg)
             .await;
 
         let final_output = match (result, &step_def.fallback_source) {
             (Ok(output), _) => Ok(output),
-            (Err(_), Some(fallback)) => {
+            (Err((e, _)), Some(fallback)) => {
+                warn!(
+                    "step '{}' failed, running its fallback: {}",
+                    step_def.output_key,
+                    logged_error(&e)
+                );
                 self.try_execute_source(fallback, &context, step_def, reg)
                     .await
             }
             (Err(e), None) => Err(e),
-        }?;
+        };
+        let final_output = match (final_output, &step_def.guard) {
+            (Ok((output, rendered)), Some(guard)) => self
+                .apply_guard(guard, output, &context, step_def, reg)
+                .await
+                .map(|output| (output, rendered)),
+            (output, _) => output,
+        }
+        .map_err(|(source, rendered)| {
+            warn!(
+                "step '{}' failed after {} ms: {}",
+                step_def.output_key,
+                started.elapsed().as_millis(),
+                logged_error(&source)
+            );
+            RunError::Step {
+                key: step_def.output_key.clone(),
+                rendered,
+                partial_context: context.lock().unwrap().clone(),
+                source: Box::new(source),
+            }
+        })?;
 
+        let (final_output, rendered) = final_output;
+        info!(
+            "step '{}' done in {} ms: {} chars",
+            step_def.output_key,
+            started.elapsed().as_millis(),
+            final_output.chars().count()
+        );
+        self.steps.lock().unwrap().push(StepTrace {
+            key: step_def.output_key.clone(),
+            rendered,
+            provider: self
+                .served_by
+                .lock()
+                .unwrap()
+                .get(&step_def.output_key)
+                .cloned(),
+            latency: started.elapsed(),
+            output: final_output.clone(),
+        });
         let mut ctx = context.lock().unwrap();
         ctx.insert(step_def.output_key.clone(), final_output);
-        Ok(())
+        Ok(true)
+    }
+
+    /// Checks a step output against its guard, then blocks, redacts or replaces it.
+    async fn apply_guard(
+        &self,
+        guard: &Guard,
+        output: String,
+        context: &Arc<Mutex<HashMap<String, String>>>,
+        step_def: &ChainStepDefinition<'a>,
+        reg: &LLMRegistry,
+    ) -> Result<String, (RunError, Option<String>)> {
+        let Some(violation) = guard.violation(&output, reg).await.map_err(|e| (e, None))? else {
+            return Ok(output);
+        };
+        let output = match &guard.action {
+            GuardAction::Block => return Err((RunError::Guard(violation), None)),
+            GuardAction::Redact => guard.redact(&output).map_err(|e| (e, None))?,
+            GuardAction::Fallback(prompt) => {
+                let source = PromptSource::Stored(prompt.clone());
+                self.try_execute_source(&source, context, step_def, reg)
+                    .await?
+                    .0
+            }
+        };
+        match guard.violation(&output, reg).await.map_err(|e| (e, None))? {
+            Some(violation) => Err((RunError::Guard(violation), None)),
+            None => Ok(output),
+        }
     }
 
+    /// Runs a step on a prompt source and returns the output with the rendered prompt.
     async fn try_execute_source(
         &self,
         source: &PromptSource,
         context: &Arc<Mutex<HashMap<String, String>>>,
         step_def: &ChainStepDefinition<'a>,
-        reg: &'a llm::chain::LLMRegistry,
+        reg: &LLMRegistry,
+    ) -> Result<(String, String), (RunError, Option<String>)> {
+        let (template, examples, workspace) = match source {
+            PromptSource::Stored(id) => {
+                let (full_id, pd) = self
+                    .store
+                    .find_prompt_variant(self.workspace.as_deref(), id, self.lang.as_deref(), None)
+                    .map_err(|e| (e.into(), None))?;
+                let examples = pd.few_shot_examples();
+                (pd.content, examples, Some(parse_id(&full_id).0))
+            }
+            PromptSource::Raw(content) => (content.clone(), None, self.workspace.clone()),
+        };
+        if let Some(workspace) = &workspace {
+            self.check_step_providers(workspace, step_def)
+                .map_err(|e| (e, None))?;
+        }
+
+        let mut vars = match &step_def.kind {
+            StepKind::Llm | StepKind::Retrieve { .. } => {
+                self.provider_vars(context).map_err(|e| (e, None))?
+            }
+            _ => context.lock().unwrap().clone(),
+        };
+        add_examples(&mut vars, examples);
+        let vars = match (&step_def.kind, &step_def.context_budget) {
+            (StepKind::Llm, Some(budget)) => budget
+                .fit(&template, vars, reg)
+                .await
+                .map_err(|e| (e, None))?,
+            _ => vars,
+        };
+        let rendered = template::render(&template, &vars);
+
+        let output = match (&step_def.kind, &step_def.samples) {
+            (StepKind::Llm, Some((count, aggregate))) => {
+                self.execute_sampled(&rendered, *count, aggregate, context, step_def, reg)
+                    .await
+            }
+            _ => {
+                self.execute_rendered(&rendered, context, step_def, reg)
+                    .await
+            }
+        };
+        match output {
+            Ok(output) => Ok((output, rendered)),
+            Err(e) => Err((e, Some(rendered))),
+        }
+    }
+
+    /// Sends a rendered prompt `count` times concurrently and aggregates the answers.
+    async fn execute_sampled(
+        &self,
+        rendered: &str,
+        count: usize,
+        aggregate: &AggregateStrategy,
+        context: &Arc<Mutex<HashMap<String, String>>>,
+        step_def: &ChainStepDefinition<'a>,
+        reg: &LLMRegistry,
+    ) -> Result<String, RunError> {
+        let ask = |prompt: String| {
+            let req = ChatMessage::user().content(prompt).build();
+            self.with_failover(step_def, reg, move |provider| {
+                let messages = [req.clone()];
+                async move {
+                    let resp = provider.chat(&messages).await?;
+                    Ok(resp.text().unwrap_or_default())
+                }
+            })
+        };
+        let answers =
+            future::try_join_all((0..count.max(1)).map(|_| ask(rendered.to_string()))).await?;
+        match aggregate {
+            AggregateStrategy::MajorityVote => Ok(sampling::majority_vote(&answers)),
+            AggregateStrategy::Judge { prompt } => {
+                let template = self
+                    .store
+                    .find_expanded_prompt(self.workspace.as_deref(), prompt, self.lang.as_deref())?
+                    .content;
+                let mut vars = self.provider_vars(context)?;
+                vars.insert("samples".to_string(), sampling::numbered(&answers));
+                ask(template::render(&template, &vars)).await
+            }
+        }
+    }
+
+    /// The chain variables, redacted if `redact_inputs` is set, for a prompt sent to a
+    /// provider.
+    fn provider_vars(
+        &self,
+        context: &Arc<Mutex<HashMap<String, String>>>,
+    ) -> Result<HashMap<String, String>, RunError> {
+        let mut vars = context.lock().unwrap().clone();
+        if let Some(policy) = &self.redaction {
+            pii::redact_values(&mut vars, policy).map_err(StoreError::Configuration)?;
+        }
+        Ok(vars)
+    }
+
+    /// Runs a step on its rendered prompt, query, URL or program.
+    async fn execute_rendered(
+        &self,
+        rendered: &str,
+        context: &Arc<Mutex<HashMap<String, String>>>,
+        step_def: &ChainStepDefinition<'a>,
+        reg: &LLMRegistry,
     ) -> Result<String, RunError> {
+        match &step_def.kind {
+            StepKind::Llm => {
+                let req = ChatMessage::user().content(rendered).build();
+                let mut messages = match &step_def.conversation {
+                    Some(name) => self
+                        .conversations
+                        .lock()
+                        .unwrap()
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_default(),
+                    None => Vec::new(),
+                };
+                messages.push(req.clone());
+                let output = self
+                    .with_failover(step_def, reg, |provider| {
+                        let messages = messages.clone();
+                        async move {
+                            let resp = provider.chat(&messages).await?;
+                            Ok(resp.text().unwrap_or_default())
+                        }
+                    })
+                    .await?;
+                if let Some(name) = &step_def.conversation {
+                    let reply = ChatMessage::assistant().content(&output).build();
+                    let mut conversations = self.conversations.lock().unwrap();
+                    let history = conversations.entry(name.clone()).or_default();
+                    history.push(req);
+                    history.push(reply);
+                }
+                Ok(output)
+            }
+            StepKind::Retrieve { top_k } => {
+                let index = DocIndex::load(
+                    &docs::index_path(&self.store.ctx.base_dir),
+                    &self.store.ctx.cipher,
+                )
+                .map_err(StoreError::DocIndex)?;
+                let query_embedding = self.embed_query(&index, rendered, step_def, reg).await?;
+                if let Some(chunk) = index.chunks.first() {
+                    if chunk.embedding.len() != query_embedding.len() {
+                        return Err(StoreError::Configuration(format!(
+                            "Step '{}': the query embedding has {} dimensions but the document index has {}; rebuild the index with `docs add`.",
+                            step_def.output_key,
+                            query_embedding.len(),
+                            chunk.embedding.len()
+                        ))
+                        .into());
+                    }
+                }
+                Ok(docs::format_chunks(&index.search(&query_embedding, *top_k)))
+            }
+            StepKind::Fetch {
+                max_bytes,
+                readable,
+            } => web::fetch_text(rendered, *max_bytes, *readable)
+                .await
+                .map_err(RunError::Fetch),
+            StepKind::Shell { args, stdin } => {
+                if !shell::is_allowed(rendered, &self.allowed_commands) {
+                    return Err(StoreError::Configuration(format!(
+                        "Command '{}' is not in the shell allowlist",
+                        rendered
+                    ))
+                    .into());
+                }
+                let (args, stdin) = {
+                    let ctx = context.lock().unwrap();
+                    let args: Vec<String> =
+                        args.iter().map(|a| template::render(a, &ctx)).collect();
+                    let stdin = stdin.as_ref().map(|s| template::render(s, &ctx));
+                    (args, stdin)
+                };
+                let timeout = load_step_timeout().map_err(StoreError::Configuration)?;
+                shell::run_command(rendered, &args, stdin.as_deref(), timeout)
+                    .await
+                    .map_err(RunError::Shell)
+            }
+        }
+    }
+
+    /// Embeds the query of a retrieval step with the provider that built the index, which
+    /// the providers of the step, if any, must include. Indexes that don't record their
+    /// provider are queried with the step's providers.
+    async fn embed_query(
+        &self,
+        index: &DocIndex,
+        query: &str,
+        step_def: &ChainStepDefinition<'a>,
+        reg: &LLMRegistry,
+    ) -> Result<Vec<f32>, RunError> {
+        let query = vec![query.to_string()];
+        let embeddings = match &index.provider {
+            Some(indexed) => {
+                if step_def.pool.is_some() || step_def.provider_id.is_some() {
+                    let providers = self.step_providers(step_def)?;
+                    if !providers.contains(indexed) {
+                        return Err(StoreError::Configuration(format!(
+                            "Step '{}' embeds its query with {} but the document index was built with '{}'.",
+                            step_def.output_key,
+                            providers.join(", "),
+                            indexed
+                        ))
+                        .into());
+                    }
+                }
+                let provider = reg.get(indexed).ok_or_else(|| {
+                    StoreError::Configuration(format!(
+                        "Provider '{}', which built the document index, not found in registry",
+                        indexed
+                    ))
+                })?;
+                let embeddings = provider.embed(query).await?;
+                self.served_by
+                    .lock()
+                    .unwrap()
+                    .insert(step_def.output_key.clone(), indexed.clone());
+                embeddings
+            }
+            None => {
+                self.with_failover(step_def, reg, |provider| provider.embed(query.clone()))
+                    .await?
+            }
+        };
+        Ok(embeddings.into_iter().next().unwrap_or_default())
+    }
+
+    /// Calls the step's provider, moving on to the next member of its failover group
+    /// on transient errors, and records which provider served the step.
+    async fn with_failover<'r, T, F, Fut>(
+        &self,
+        step_def: &ChainStepDefinition<'a>,
+        reg: &'r LLMRegistry,
+        call: F,
+    ) -> Result<T, RunError>
+    where
+        F: Fn(&'r dyn LLMProvider) -> Fut,
+        Fut: Future<Output = Result<T, LLMError>>,
+    {
+        let mut last_err = None;
+        for provider_id in self.step_providers(step_def)? {
+            let provider = reg.get(&provider_id).ok_or_else(|| {
+                StoreError::Configuration(format!(
+                    "Provider '{}' not found in registry",
+                    provider_id
+                ))
+            })?;
+            match call(provider).await {
+                Ok(output) => {
+                    self.served_by
+                        .lock()
+                        .unwrap()
+                        .insert(step_def.output_key.clone(), provider_id);
+                    return Ok(output);
+                }
+                Err(e) if retry::is_transient(&e) => {
+                    warn!("provider '{}' failed, failing over: {}", provider_id, e);
+                    last_err = Some(e);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(last_err.map_or_else(
+            || {
+                StoreError::Configuration(format!(
+                    "Step '{}' has no provider.",
+                    step_def.output_key
+                ))
+                .into()
+            },
+            RunError::from,
+        ))
+    }
+
+    /// Checks that every provider a step may send data of its prompt to is allowed by the
+    /// workspace of the prompt: the members of its pool or failover group, the provider
+    /// that built the document index for retrieval, the provider summarizing for its
+    /// context budget and the one moderating its guard.
+    fn check_step_providers(
+        &self,
+        workspace: &str,
+        step_def: &ChainStepDefinition<'a>,
+    ) -> Result<(), RunError> {
+        let repo = self.store.ctx.prompts();
+        let Some(info) = repo
+            .load_workspace_info(workspace)
+            .map_err(StoreError::Storage)?
+            .filter(|info| info.restricts_providers())
+        else {
+            return Ok(());
+        };
+        // Retrieval steps embed their query with the provider that built the index.
+        let indexed = match step_def.kind {
+            StepKind::Retrieve { .. } => {
+                DocIndex::load(
+                    &docs::index_path(&self.store.ctx.base_dir),
+                    &self.store.ctx.cipher,
+                )
+                .map_err(StoreError::DocIndex)?
+                .provider
+            }
+            _ => None,
+        };
+        let mut providers: Vec<&String> = match (&step_def.pool, &step_def.provider_id) {
+            _ if !matches!(step_def.kind, StepKind::Llm | StepKind::Retrieve { .. }) => Vec::new(),
+            (Some(pool), _) => self
+                .pools
+                .get(pool)
+                .map(|members| members.iter().map(|(provider, _)| provider).collect())
+                .unwrap_or_default(),
+            (None, Some(id)) => match self.failover_groups.get(id) {
+                Some(group) => group.iter().collect(),
+                None => vec![id],
+            },
+            (None, None) => Vec::new(),
+        };
+        providers.extend(indexed.as_ref());
+        if let (StepKind::Llm, Some(budget)) = (&step_def.kind, &step_def.context_budget) {
+            if let BudgetStrategy::Summarize { provider } = &budget.strategy {
+                providers.push(provider);
+            }
+        }
+        if let Some(moderation) = step_def.guard.as_ref().and_then(|g| g.moderation.as_ref()) {
+            providers.push(moderation);
+        }
+        for provider in providers {
+            let backend = provider_backend(provider).map_err(StoreError::Configuration)?;
+            info.check_provider(workspace, provider, backend.as_deref())
+                .map_err(|e| {
+                    StoreError::Policy(format!("Step '{}': {}", step_def.output_key, e))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Resolves the provider IDs to try for a step, in order: the pool members starting
+    /// with the selected one, the members of its failover group, or the provider alone.
+    fn step_providers(&self, step_def: &ChainStepDefinition<'a>) -> Result<Vec<String>, RunError> {
+        if let Some(pool) = &step_def.pool {
+            let members = self
+                .pools
+                .get(pool)
+                .filter(|members| !members.is_empty())
+                .ok_or_else(|| {
+                    StoreError::Configuration(format!("Provider pool '{}' is not defined", pool))
+                })?;
+            let first = self.next_pool_member(pool, members);
+            return Ok(members[first..]
+                .iter()
+                .chain(&members[..first])
+                .map(|(provider, _)| provider.clone())
+                .collect());
+        }
+
         let provider_id = step_def.provider_id.as_deref().ok_or_else(|| {
             StoreError::Configuration(format!(
                 "Step '{}' is missing a provider ID.",
                 step_def.output_key
             ))
         })?;
-        let provider = reg.get(provider_id).ok_or_else(|| {
-            StoreError::Configuration(format!("Provider '{}' not found in registry", provider_id))
-        })?;
+        Ok(self
+            .failover_groups
+            .get(provider_id)
+            .cloned()
+            .unwrap_or_else(|| vec![provider_id.to_string()]))
+    }
 
-        let prompt_data = match source {
-            PromptSource::Stored(id) => self.store.find_prompt(id)?,
-            PromptSource::Raw(content) => {
-                // For raw prompts, we don't have stored schema, so validation is simpler
-                crate::core::storage::PromptData {
-                    id: "raw".to_string(),
-                    title: "Raw Prompt".to_string(),
-                    content: content.clone(),
-                    tags: vec![],
-                    schema: None,
-                }
+    /// Picks the index of the next pool member using smooth weighted round-robin,
+    /// which interleaves members instead of sending bursts to the heaviest one.
+    fn next_pool_member(&self, pool: &str, members: &[(String, u32)]) -> usize {
+        let mut weights = self.pool_weights.lock().unwrap();
+        let current = weights
+            .entry(pool.to_string())
+            .or_insert_with(|| vec![0; members.len()]);
+        let total: i64 = members.iter().map(|(_, w)| i64::from(*w)).sum();
+        for (c, (_, w)) in current.iter_mut().zip(members) {
+            *c += i64::from(*w);
+        }
+        let mut best = 0;
+        for (i, c) in current.iter().enumerate() {
+            if *c > current[best] {
+                best = i;
             }
-        };
+        }
+        current[best] -= total;
+        best
+    }
+}
 
-        let rendered = {
-            let ctx = context.lock().unwrap();
-            render_template(&prompt_data.content, &ctx)
-        };
+/// Whether a variable name suggests a credential, whose value is redacted from errors.
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["key", "token", "secret", "password"]
+        .iter()
+        .any(|word| name.contains(word))
+}
 
-        use llm::chat::ChatMessage;
-        let req = ChatMessage::user().content(&rendered).build();
-        let resp = provider.chat(&[req]).await?;
-        Ok(resp.text().unwrap_or_default())
+/// Describes a step error for the logs. Fetch, shell and guard messages may quote
+/// variables or outputs, so they are redacted unless content logging is allowed.
+fn logged_error(e: &RunError) -> String {
+    match e {
+        RunError::Fetch(_) | RunError::Shell(_) | RunError::Guard(_) => {
+            logging::content(&e.to_string()).into_owned()
+        }
+        _ => e.to_string(),
     }
 }
 
-/// Renders a template string with the given variables.
-fn render_template(template: &str, vars: &HashMap<String, String>) -> String {
-    let re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
-    re.replace_all(template, |caps: &regex::Captures| {
-        let key = &caps[1];
-        vars.get(key)
-            .map(|s| s.as_str())
-            .unwrap_or("")
-            .to_string()
-    })
-    .into_owned()
-}
\ No newline at end of file
+/// Sets `{{examples}}` to the few-shot text of a stored prompt's examples, unless the
+/// variable is already given.
+fn add_examples(vars: &mut HashMap<String, String>, examples: Option<String>) {
+    if let Some(examples) = examples {
+        vars.entry("examples".to_string()).or_insert(examples);
+    }
+}
@@ -0,0 +1,52 @@
+//! Self-consistency sampling: running a chain step several times and aggregating the answers.
+
+use serde::{Deserialize, Serialize};
+
+/// How the answers of a step sampled with `with_samples` are combined into its output.
+///
+/// In YAML chain files, it is the `aggregate:` of the `samples:` block of a step:
+/// `samples: { count: 5, aggregate: majority_vote }`, or with `aggregate: judge` and
+/// `prompt: <judge prompt>`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "aggregate", rename_all = "snake_case")]
+pub enum AggregateStrategy {
+    /// Keeps the most frequent answer, compared ignoring case and surrounding whitespace.
+    /// Ties go to the answer seen first.
+    MajorityVote,
+    /// Asks a prompt from the store to pick or merge the answers, which it receives as
+    /// a numbered list in the `{{samples}}` variable, next to the chain variables.
+    Judge {
+        /// ID or title of the judge prompt.
+        prompt: String,
+    },
+}
+
+/// The most frequent answer, trimmed.
+pub(crate) fn majority_vote(answers: &[String]) -> String {
+    let mut votes: Vec<(String, &str, usize)> = Vec::new();
+    for answer in answers {
+        let answer = answer.trim();
+        let key = answer.to_lowercase();
+        match votes.iter_mut().find(|(k, _, _)| *k == key) {
+            Some((_, _, count)) => *count += 1,
+            None => votes.push((key, answer, 1)),
+        }
+    }
+    // `max_by_key` returns the last maximum, so scan in reverse to favour the first.
+    votes
+        .iter()
+        .rev()
+        .max_by_key(|(_, _, count)| *count)
+        .map(|(_, answer, _)| answer.to_string())
+        .unwrap_or_default()
+}
+
+/// The answers as a numbered list, for the judge prompt.
+pub(crate) fn numbered(answers: &[String]) -> String {
+    answers
+        .iter()
+        .enumerate()
+        .map(|(i, answer)| format!("{}. {}", i + 1, answer.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
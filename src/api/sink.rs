@@ -0,0 +1,69 @@
+//! Destinations that receive chain outputs once a run completes.
+
+use crate::core::config::http_client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// A destination for the outputs of a completed chain run.
+///
+/// In YAML chain files sinks are declared under `sinks:` as `- webhook: <url>`,
+/// `- file: <path>` or `- slack: <webhook url>`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Sink {
+    /// POST the outputs as a JSON object to a URL.
+    Webhook(String),
+    /// Write the outputs as pretty-printed JSON to a file.
+    File(PathBuf),
+    /// Post the outputs as a message to a Slack incoming webhook.
+    Slack(String),
+}
+
+impl Sink {
+    /// Sends the chain outputs to this sink.
+    pub(crate) async fn deliver(&self, outputs: &HashMap<String, String>) -> Result<(), String> {
+        match self {
+            Sink::Webhook(url) => {
+                let body = serde_json::to_string(outputs).map_err(|e| e.to_string())?;
+                post_json(url, body).await
+            }
+            Sink::File(path) => {
+                let body = serde_json::to_string_pretty(outputs).map_err(|e| e.to_string())?;
+                fs::write(path, body)
+                    .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+            }
+            Sink::Slack(url) => {
+                let mut keys: Vec<_> = outputs.keys().collect();
+                keys.sort();
+                let text = keys
+                    .into_iter()
+                    .map(|k| format!("*{}*\n{}", k, outputs[k]))
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                let body = serde_json::to_string(&serde_json::json!({ "text": text }))
+                    .map_err(|e| e.to_string())?;
+                post_json(url, body).await
+            }
+        }
+    }
+}
+
+async fn post_json(url: &str, body: String) -> Result<(), String> {
+    let resp = http_client()?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to post to '{}': {}", url, e))?;
+    if !resp.status().is_success() {
+        return Err(format!(
+            "Posting to '{}' failed: HTTP {}",
+            url,
+            resp.status()
+        ));
+    }
+    Ok(())
+}
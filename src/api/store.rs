@@ -1,14 +1,21 @@
 //! The main entry point for interacting with the prompt store.
 
-use crate::core::crypto::decrypt_key_with_password;
-use crate::core::storage::{AppCtx, PromptData};
+use crate::core::cipher::{CipherBackend, StoreCipher};
+use crate::core::config::{
+    load_cipher_algorithm, load_llm_registry, load_resolution_order, ResolutionOrder,
+};
+use crate::core::crypto::{decrypt_key_with_password, finish_rotation};
+use crate::core::paths::store_dir;
+use crate::core::policy::load_policies;
+use crate::core::profiles::load_profile;
+use crate::core::releases::{release_dir, validate_release_name};
+use crate::core::scan::{scan, Risk};
+use crate::core::storage::{parse_id, AppCtx, Canary, PromptData};
 use crate::core::utils::ensure_dir;
-use aes_gcm::aead::{Aead, KeyInit};
-use aes_gcm::{Aes256Gcm, Key, Nonce};
-use base64::{engine::general_purpose, Engine as _};
-use std::env;
+use llm::chain::LLMRegistry;
+use std::collections::HashMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 use super::error::StoreError;
 use super::llm_bridge::LLMBackendRef;
@@ -17,15 +24,18 @@ use super::runner::{ChainRunner, PromptRunner};
 /// The main entry point for interacting with the prompt store.
 ///
 /// This structure is designed to be created once and shared throughout your application.
-/// It holds the necessary context, including the encryption cipher.
+/// It holds the necessary context, including the encryption cipher. The cipher's key
+/// material is wiped from memory when the store is dropped, as are the decrypted buffers
+/// it hands out internally.
 pub struct PromptStore {
     pub(crate) ctx: AppCtx,
+    /// Providers of `config.toml`, loaded on first use by `stored_chain()`.
+    registry: OnceLock<Arc<LLMRegistry>>,
 }
 
 impl PromptStore {
-    fn new_from_key(key_bytes: Vec<u8>) -> Result<Self, StoreError> {
-        let home = env::var("HOME").map_err(|e| StoreError::Init(e.to_string()))?;
-        let base_dir = PathBuf::from(home).join(".prompt-store");
+    fn new_from_key(key_bytes: &[u8]) -> Result<Self, StoreError> {
+        let base_dir = store_dir().map_err(StoreError::Init)?;
         let key_path = base_dir.join("keys").join("key.bin");
         let workspaces_dir = base_dir.join("workspaces");
         let registries_dir = base_dir.join("registries");
@@ -35,7 +45,8 @@ impl PromptStore {
         ensure_dir(&registries_dir).map_err(StoreError::Init)?;
         ensure_dir(&workspaces_dir.join("default")).map_err(StoreError::Init)?;
 
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes));
+        let algorithm = load_cipher_algorithm().map_err(StoreError::Configuration)?;
+        let cipher = StoreCipher::from_key(key_bytes, &algorithm).map_err(StoreError::Init)?;
 
         let ctx = AppCtx {
             base_dir,
@@ -45,16 +56,31 @@ impl PromptStore {
             cipher,
         };
 
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            registry: OnceLock::new(),
+        })
+    }
+
+    /// Opens the store of a context, e.g. one from [`AppCtx::temp`].
+    #[cfg(all(test, feature = "grpc"))]
+    pub(crate) fn from_ctx(ctx: AppCtx) -> Self {
+        Self {
+            ctx,
+            registry: OnceLock::new(),
+        }
     }
 
     /// Initializes the PromptStore by prompting for a password if the key is encrypted.
     ///
-    /// This function will locate `~/.prompt-store`, load the encryption key,
+    /// This function will locate the store directory, load the encryption key,
     /// and interactively prompt for a password if required.
     pub fn init() -> Result<Self, StoreError> {
         let ctx = AppCtx::init().map_err(StoreError::Init)?;
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            registry: OnceLock::new(),
+        })
     }
 
     /// Initializes the PromptStore non-interactively with a password.
@@ -66,9 +92,8 @@ impl PromptStore {
     ///
     /// * `password` - The password to decrypt the master key.
     pub fn with_password(password: &str) -> Result<Self, StoreError> {
-        let home = env::var("HOME").map_err(|e| StoreError::Init(e.to_string()))?;
-        let key_path = PathBuf::from(home)
-            .join(".prompt-store")
+        let key_path = store_dir()
+            .map_err(StoreError::Init)?
             .join("keys")
             .join("key.bin");
 
@@ -78,11 +103,72 @@ impl PromptStore {
             ));
         }
 
+        finish_rotation(&key_path).map_err(StoreError::Init)?;
         let key_data = fs::read(&key_path)?;
         let decrypted_key =
             decrypt_key_with_password(&key_data, password).map_err(StoreError::Init)?;
 
-        Self::new_from_key(decrypted_key)
+        Self::new_from_key(&decrypted_key)
+    }
+
+    /// Encrypts the files written from now on with a custom cipher backend, such as
+    /// envelope encryption with a KMS or operations on an HSM.
+    ///
+    /// The algorithm of each file is recorded in its header, so files written before
+    /// still decrypt with the built-in ciphers, and files written by `backend` can only
+    /// be read by stores it is installed on.
+    ///
+    /// ```rust,no_run
+    /// # use std::sync::Arc;
+    /// # use prompt_store::{CipherBackend, PromptStore};
+    /// # fn demo(kms: Arc<dyn CipherBackend>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let store = PromptStore::init()?.with_cipher_backend(kms);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_cipher_backend(mut self, backend: Arc<dyn CipherBackend>) -> Self {
+        self.ctx.cipher = self.ctx.cipher.clone().with_backend(backend);
+        self
+    }
+
+    /// Returns the store as it was in a release (see `prompt-store release create`).
+    ///
+    /// Prompts and chains run from the returned store resolve to their released
+    /// versions, so an application pinned to a release is unaffected by later edits.
+    ///
+    /// ```rust,no_run
+    /// # use prompt_store::PromptStore;
+    /// # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let store = PromptStore::init()?;
+    /// let output = store
+    ///     .at_release("v1")?
+    ///     .prompt("summarizer")
+    ///     .vars([("text", "...")])
+    ///     .run()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn at_release(&self, name: &str) -> Result<PromptStore, StoreError> {
+        validate_release_name(name).map_err(StoreError::Configuration)?;
+        let workspaces_dir = release_dir(&self.ctx.base_dir, name);
+        if !workspaces_dir.is_dir() {
+            return Err(StoreError::Configuration(format!(
+                "Release '{}' not found",
+                name
+            )));
+        }
+        let ctx = AppCtx {
+            base_dir: self.ctx.base_dir.clone(),
+            workspaces_dir,
+            registries_dir: self.ctx.registries_dir.clone(),
+            key_path: self.ctx.key_path.clone(),
+            cipher: self.ctx.cipher.clone(),
+        };
+        Ok(Self {
+            ctx,
+            registry: OnceLock::new(),
+        })
     }
 
     /// Creates a runner for executing a single prompt.
@@ -104,79 +190,231 @@ impl PromptStore {
         ChainRunner::new(self, backend.into())
     }
 
-    /// Internal logic for finding and decrypting a prompt by its ID or title.
-    /// Searches local prompts, chain prompts, and cached prompts from deployed packs.
-    pub(crate) fn find_prompt(&self, id_or_title: &str) -> Result<PromptData, StoreError> {
-        // First, try to load by full ID directly (e.g., "abcdef12", "chain/1", or "pack::abc").
-        let prompt_path = self.ctx.prompt_path(id_or_title);
-        if prompt_path.exists() {
-            return self.decrypt_prompt_file(&prompt_path);
+    /// Returns the providers configured in `config.toml`, loading them once.
+    pub(crate) fn config_registry(&self) -> Result<&Arc<LLMRegistry>, StoreError> {
+        if let Some(registry) = self.registry.get() {
+            return Ok(registry);
         }
+        let registry = load_llm_registry(&self.ctx).map_err(StoreError::Configuration)?;
+        Ok(self.registry.get_or_init(|| Arc::new(registry)))
+    }
+
+    /// Creates a chain runner that owns a share of the store and of the registry.
+    ///
+    /// Unlike `chain()`, the returned `ChainRunner<'static>` borrows nothing, so it can be
+    /// built in one function and run in a spawned task, or kept in a struct.
+    ///
+    /// ```rust,no_run
+    /// # use std::sync::Arc;
+    /// # use llm::chain::LLMRegistry;
+    /// # use prompt_store::PromptStore;
+    /// # async fn demo(registry: LLMRegistry) -> Result<(), Box<dyn std::error::Error>> {
+    /// let store = Arc::new(PromptStore::init()?);
+    /// let runner = store
+    ///     .chain_owned(Arc::new(registry))
+    ///     .step("summary", "summarizer")
+    ///     .with_provider("openai");
+    /// let output = tokio::spawn(runner.run()).await??;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chain_owned(self: &Arc<Self>, registry: Arc<LLMRegistry>) -> ChainRunner<'static> {
+        ChainRunner::new_owned(Arc::clone(self), registry)
+    }
 
-        // If not found, search all prompts by title. This is more expensive.
-        let mut found_prompts = vec![];
-        if self.ctx.workspaces_dir.exists() {
-            self.find_prompts_by_title_recursive(
-                &self.ctx.workspaces_dir,
-                id_or_title,
-                &mut found_prompts,
-            )?;
+    /// Loads the variables of a named environment profile.
+    pub(crate) fn profile_vars(&self, name: &str) -> Result<HashMap<String, String>, StoreError> {
+        load_profile(&self.ctx.base_dir, &self.ctx.cipher, name)
+            .map_err(StoreError::Crypto)?
+            .map(|vars| vars.into_iter().collect())
+            .ok_or_else(|| {
+                StoreError::Configuration(format!("Environment profile '{}' not found", name))
+            })
+    }
+
+    /// Lists the names of all workspaces (`default` and one per deployed pack), sorted.
+    pub fn workspaces(&self) -> Result<Vec<String>, StoreError> {
+        self.ctx.prompts().workspaces().map_err(StoreError::Storage)
+    }
+
+    /// Lists and decrypts every prompt in a workspace, including chain step prompts.
+    ///
+    /// The `id` of each returned prompt is its full ID (e.g., `abc123`, `chain/1`
+    /// or `pack::abc123`), usable directly with `prompt()`.
+    pub fn list_prompts_in(&self, workspace: &str) -> Result<Vec<PromptData>, StoreError> {
+        if !self.ctx.workspaces_dir.join(workspace).is_dir() {
+            return Err(StoreError::NotFound(format!("workspace '{}'", workspace)));
         }
+        let prompts = self
+            .ctx
+            .prompts()
+            .list(workspace)
+            .map_err(StoreError::Storage)?;
+        Ok(prompts
+            .into_iter()
+            .map(|p| PromptData {
+                id: p.full_id,
+                ..p.data
+            })
+            .collect())
+    }
+
+    /// Internal logic for finding and decrypting a prompt by its ID or title.
+    /// Searches local prompts, chain prompts, and cached prompts from deployed packs.
+    ///
+    /// When `workspace` is set, unqualified IDs and titles are resolved inside it only.
+    /// A `workspace::` prefix in `id_or_title` also scopes the title search to that
+    /// workspace, which avoids collisions between packs using the same titles.
+    pub(crate) fn find_prompt(
+        &self,
+        workspace: Option<&str>,
+        id_or_title: &str,
+    ) -> Result<PromptData, StoreError> {
+        Ok(self.resolve_prompt(workspace, id_or_title)?.1)
+    }
+
+    /// Finds a prompt like [`find_prompt`](Self::find_prompt), with its content in
+    /// `lang` (see [`PromptData::content_for`]) and the `{{attachment:<name>}}`
+    /// placeholders replaced by its attachments.
+    pub(crate) fn find_expanded_prompt(
+        &self,
+        workspace: Option<&str>,
+        id_or_title: &str,
+        lang: Option<&str>,
+    ) -> Result<PromptData, StoreError> {
+        Ok(self
+            .find_prompt_variant(workspace, id_or_title, lang, None)?
+            .1)
+    }
 
-        if found_prompts.len() == 1 {
-            Ok(found_prompts.remove(0))
-        } else if found_prompts.is_empty() {
-            Err(StoreError::NotFound(id_or_title.to_string()))
-        } else {
-            Err(StoreError::AmbiguousTitle(id_or_title.to_string()))
+    /// Finds a prompt like [`find_expanded_prompt`](Self::find_expanded_prompt), with
+    /// the content of a variant instead when `variant` is set, and returns it with its
+    /// full ID.
+    ///
+    /// Without a variant, the content is the canary of the prompt for its share of the
+    /// calls (see [`serve_canary`]).
+    pub(crate) fn find_prompt_variant(
+        &self,
+        workspace: Option<&str>,
+        id_or_title: &str,
+        lang: Option<&str>,
+        variant: Option<&str>,
+    ) -> Result<(String, PromptData), StoreError> {
+        let (full_id, mut pd) = self.resolve_prompt(workspace, id_or_title)?;
+        if variant.is_none() {
+            serve_canary(&mut pd);
         }
+        let content = match variant {
+            Some(name) => pd
+                .variant_content(name)
+                .ok_or_else(|| StoreError::NotFound(format!("{} variant {}", full_id, name)))?,
+            None => pd.content_for(lang),
+        };
+        pd.content = self
+            .ctx
+            .prompts()
+            .expand_attachments(&full_id, content)
+            .map_err(StoreError::Storage)?;
+        load_policies()
+            .map_err(StoreError::Configuration)?
+            .check_content(&full_id, &pd.content)
+            .map_err(StoreError::Policy)?;
+        Ok((full_id, pd))
     }
 
-    /// Recursive helper to find prompts by title.
-    fn find_prompts_by_title_recursive(
+    /// Finds a prompt and returns it with its full ID.
+    fn resolve_prompt(
         &self,
-        dir: &Path,
-        title_query: &str,
-        found: &mut Vec<PromptData>,
-    ) -> Result<(), StoreError> {
-        for entry in fs::read_dir(dir)? {
-            let path = entry?.path();
-            if path.is_dir() {
-                self.find_prompts_by_title_recursive(&path, title_query, found)?;
-            } else if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("prompt")
-            {
-                if let Ok(pd) = self.decrypt_prompt_file(&path) {
-                    if pd.title.eq_ignore_ascii_case(title_query) {
-                        found.push(pd);
-                    }
-                }
+        workspace: Option<&str>,
+        id_or_title: &str,
+    ) -> Result<(String, PromptData), StoreError> {
+        let repo = self.ctx.prompts();
+        let (workspace, query) = match (workspace, id_or_title.contains("::")) {
+            (Some(ws), false) => (Some(ws.to_string()), id_or_title.to_string()),
+            (_, true) => {
+                let (ws, query) = parse_id(id_or_title);
+                (Some(ws), query)
             }
+            (None, false) => (None, id_or_title.to_string()),
+        };
+        let full_id = match &workspace {
+            Some(ws) => format!("{}::{}", ws, query),
+            None => query.clone(),
+        };
+
+        // First, try to load by full ID directly (e.g., "abcdef12", "chain/1", or "pack::abc").
+        if repo.exists(&full_id) {
+            let pd = repo.load(&full_id).map_err(StoreError::Storage)?;
+            return Ok((full_id, pd));
         }
-        Ok(())
-    }
 
-    /// Helper to decrypt a single prompt file.
-    fn decrypt_prompt_file(&self, path: &Path) -> Result<PromptData, StoreError> {
-        let encoded = fs::read_to_string(path)?;
-        let decoded = general_purpose::STANDARD
-            .decode(encoded.trim_end())
-            .map_err(|_| StoreError::Crypto("Invalid Base64 data.".to_string()))?;
+        // If not found, search prompts by title. This is more expensive.
+        let candidates = match &workspace {
+            Some(ws) => repo.list(ws),
+            None => repo.list_all(),
+        }
+        .map_err(StoreError::Storage)?;
+        let mut found_prompts: Vec<(String, PromptData)> = candidates
+            .into_iter()
+            .filter(|p| p.data.title.eq_ignore_ascii_case(&query))
+            .map(|p| (p.full_id, p.data))
+            .collect();
 
-        if decoded.len() < 12 {
-            return Err(StoreError::Crypto(
-                "Data is too short to be valid.".to_string(),
-            ));
+        if found_prompts.len() > 1 {
+            let order = load_resolution_order().map_err(StoreError::Configuration)?;
+            let preferred: Vec<usize> = found_prompts
+                .iter()
+                .enumerate()
+                .filter(|(_, (id, _))| match order {
+                    ResolutionOrder::LocalFirst => !id.contains("::"),
+                    ResolutionOrder::PacksFirst => id.contains("::"),
+                    ResolutionOrder::Strict => false,
+                })
+                .map(|(i, _)| i)
+                .collect();
+            if let [only] = preferred[..] {
+                return Ok(found_prompts.swap_remove(only));
+            }
         }
 
-        let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-        let plaintext = self
-            .ctx
-            .cipher
-            .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-            .map_err(|_| {
-                StoreError::Crypto("Decryption failed. Check key or password.".to_string())
-            })?;
+        match found_prompts.len() {
+            0 => Err(StoreError::NotFound(id_or_title.to_string())),
+            1 => Ok(found_prompts.remove(0)),
+            _ => {
+                let mut candidates: Vec<String> =
+                    found_prompts.into_iter().map(|(id, _)| id).collect();
+                candidates.sort();
+                Err(StoreError::AmbiguousTitle(
+                    id_or_title.to_string(),
+                    candidates,
+                ))
+            }
+        }
+    }
 
-        Ok(serde_json::from_slice(&plaintext)?)
+    /// Finds a prompt by ID or title inside a single workspace, ignoring prompts with
+    /// the same title in other workspaces.
+    ///
+    /// When the prompt has a canary (see `prompt-store canary`), its content is the
+    /// canary version for the canary's share of the calls and the stable one otherwise.
+    pub fn find_prompt_in(&self, workspace: &str, name: &str) -> Result<PromptData, StoreError> {
+        let mut pd = self.find_prompt(Some(workspace), name)?;
+        serve_canary(&mut pd);
+        Ok(pd)
+    }
+
+    /// Scans a prompt, by ID or title, for prompt-injection patterns: exfiltration
+    /// instructions, attempts to override the system prompt, and hidden unicode.
+    /// Detection is heuristic, so an empty list does not prove the prompt is safe.
+    pub fn scan_prompt(&self, id_or_title: &str) -> Result<Vec<Risk>, StoreError> {
+        Ok(scan(&self.find_prompt(None, id_or_title)?.content))
     }
-}
\ No newline at end of file
+}
+
+/// Replaces the content of a prompt with its canary when a draw picks it, and drops the
+/// canary, so the prompt holds the version that serves this call.
+fn serve_canary(pd: &mut PromptData) {
+    if let Some(canary) = pd.canary.take().filter(Canary::picked) {
+        pd.content = canary.content;
+    }
+}
@@ -0,0 +1,571 @@
+//! Chains stored as YAML definitions (`chain import`), turned into a [`ChainRunner`].
+
+use crate::core::config::{
+    load_failover_groups, load_input_redaction, load_provider_pools, load_shell_allowlist,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use super::budget::ContextBudget;
+use super::error::StoreError;
+use super::expr::Expr;
+use super::guard::Guard;
+use super::runner::{ChainRunner, StepCondition};
+use super::sampling::AggregateStrategy;
+use super::sink::Sink;
+use super::store::PromptStore;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub(super) enum StepDefinition {
+    Sequential(Step),
+    Parallel {
+        parallel: Vec<Step>,
+    },
+    Switch {
+        switch: SwitchStep,
+    },
+    Loop {
+        #[serde(rename = "loop")]
+        refine: LoopStep,
+    },
+    Retrieve {
+        retrieve: RetrieveStep,
+    },
+    Fetch {
+        fetch: FetchStep,
+    },
+    Shell {
+        shell: ShellStep,
+    },
+}
+
+impl StepDefinition {
+    /// The ID and `exit_when:` of a sequential step that has one.
+    fn exit_when(&self) -> Option<(&str, &Condition)> {
+        let (id, exit_when) = match self {
+            StepDefinition::Sequential(step) => (&step.id, &step.exit_when),
+            StepDefinition::Switch { switch } => (&switch.id, &switch.exit_when),
+            StepDefinition::Retrieve { retrieve } => (&retrieve.id, &retrieve.exit_when),
+            StepDefinition::Fetch { fetch } => (&fetch.id, &fetch.exit_when),
+            StepDefinition::Shell { shell } => (&shell.id, &shell.exit_when),
+            StepDefinition::Parallel { .. } | StepDefinition::Loop { .. } => return None,
+        };
+        Some((id, exit_when.as_ref()?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct Step {
+    pub(super) id: String,
+    pub(super) prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) provider: Option<String>,
+    /// Provider pool to spread this step across, instead of a single provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) pool: Option<String>,
+    #[serde(rename = "if", default, skip_serializing_if = "Option::is_none")]
+    pub(super) condition: Option<Condition>,
+    /// Prompt run instead when the `if:` is not met.
+    #[serde(rename = "else", default, skip_serializing_if = "Option::is_none")]
+    pub(super) otherwise: Option<FallbackStep>,
+    /// Ends the chain once the step has run and this is met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_when: Option<Condition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) on_error: Option<FallbackStep>,
+    /// Input budget of the step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) context: Option<ContextBudget>,
+    /// Self-consistency sampling of the step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) samples: Option<SamplesSpec>,
+    /// Policy rules the output of the step must follow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) guard: Option<Guard>,
+    /// Conversation whose message history the step shares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) conversation: Option<String>,
+}
+
+/// The `samples:` of a step: how many times its prompt is sent, and how the answers
+/// are aggregated.
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct SamplesSpec {
+    pub(super) count: usize,
+    #[serde(flatten)]
+    pub(super) aggregate: AggregateStrategy,
+}
+
+/// A step running the prompt of the case matching the value of the `on:` variable, or
+/// the `default:` prompt when none matches.
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct SwitchStep {
+    pub(super) id: String,
+    pub(super) on: String,
+    pub(super) cases: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) default: Option<String>,
+    /// Ends the chain once the step has run and this is met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_when: Option<Condition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) pool: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) on_error: Option<FallbackStep>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) context: Option<ContextBudget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) conversation: Option<String>,
+}
+
+/// A refinement loop: the `prompt` writes a draft under `id`, then the critic reviews
+/// it, until the review contains `APPROVED` or `max_iterations` is reached.
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct LoopStep {
+    pub(super) id: String,
+    pub(super) prompt: String,
+    pub(super) critic: CriticStep,
+    #[serde(default = "default_max_iterations")]
+    pub(super) max_iterations: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) pool: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) context: Option<ContextBudget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) conversation: Option<String>,
+}
+
+fn default_max_iterations() -> usize {
+    3
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct CriticStep {
+    pub(super) id: String,
+    pub(super) prompt: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct RetrieveStep {
+    pub(super) id: String,
+    pub(super) query: String,
+    /// Provider embedding the query, the one that built the document index by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) provider: Option<String>,
+    #[serde(default = "default_top_k")]
+    pub(super) top_k: usize,
+    /// Ends the chain once the step has run and this is met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_when: Option<Condition>,
+}
+
+fn default_top_k() -> usize {
+    3
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct FetchStep {
+    pub(super) id: String,
+    pub(super) url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) max_bytes: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) readable: bool,
+    /// Ends the chain once the step has run and this is met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_when: Option<Condition>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct ShellStep {
+    pub(super) id: String,
+    pub(super) command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) stdin: Option<String>,
+    /// Ends the chain once the step has run and this is met.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_when: Option<Condition>,
+}
+
+/// The `if:` of a step: an expression (see `step_if_expr`), or a variable that must
+/// equal or contain a value.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(super) enum Condition {
+    Expr(String),
+    Match {
+        variable: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        contains: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        equals: Option<String>,
+    },
+}
+
+impl Condition {
+    /// Builds the predicate deciding whether the step runs, or describes why the
+    /// expression is invalid.
+    pub(super) fn predicate(&self) -> Result<StepCondition<'static>, String> {
+        match self.clone() {
+            Condition::Expr(source) => {
+                let expr = Expr::parse(&source)?;
+                Ok(Box::new(move |ctx| expr.matches(ctx)))
+            }
+            Condition::Match {
+                variable,
+                contains,
+                equals,
+            } => Ok(Box::new(move |ctx| match ctx.get(&variable) {
+                Some(val) => match (&equals, &contains) {
+                    (Some(expected), _) => val == expected,
+                    (None, Some(substring)) => val.contains(substring),
+                    (None, None) => false,
+                },
+                None => false,
+            })),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct FallbackStep {
+    pub(super) prompt: String,
+    // provider field can be added here if needed, for now assumes same provider
+}
+
+/// A variable of a chain definition: its default value, or whether it is required,
+/// its default and a description.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(super) enum VarSpec {
+    Default(String),
+    Spec {
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        required: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct ChainFile {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) vars: BTreeMap<String, VarSpec>,
+    pub(super) steps: Vec<StepDefinition>,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "serde_yaml::with::singleton_map_recursive"
+    )]
+    pub(super) sinks: Vec<Sink>,
+}
+
+impl PromptStore {
+    /// Creates a runner for a chain stored as a YAML definition, by its ID
+    /// (e.g., `my-chain` or `my-pack::my-chain`).
+    ///
+    /// The chain runs like `prompt-store chain run`: on the providers, failover groups,
+    /// provider pools and shell allowlist of `config.toml`, with the variables and sinks
+    /// of the definition. Variables set with `vars()`, a `preset()` saved with the chain
+    /// or an environment profile take precedence over the defaults of the definition,
+    /// and the run fails if a variable the definition marks `required` is left unset.
+    pub fn stored_chain(&self, id: &str) -> Result<ChainRunner<'_>, StoreError> {
+        let runner = self.chain(self.config_registry()?.as_ref());
+        self.load_stored_chain(runner, id)
+    }
+
+    /// Like `stored_chain()`, but the runner owns a share of the store, as with
+    /// `chain_owned()`, so it can be run in a spawned task.
+    pub fn stored_chain_owned(
+        self: &Arc<Self>,
+        id: &str,
+    ) -> Result<ChainRunner<'static>, StoreError> {
+        let runner = self.chain_owned(Arc::clone(self.config_registry()?));
+        self.load_stored_chain(runner, id)
+    }
+
+    /// Adds the steps, variables and sinks of a stored chain and the settings of
+    /// `config.toml` to a runner.
+    fn load_stored_chain<'a>(
+        &self,
+        runner: ChainRunner<'a>,
+        id: &str,
+    ) -> Result<ChainRunner<'a>, StoreError> {
+        let repo = self.ctx.prompts();
+        if !repo.chain_definition_path(id).exists() {
+            return Err(StoreError::NotFound(id.to_string()));
+        }
+        let yaml = repo
+            .load_chain_definition(id)
+            .map_err(StoreError::Storage)?;
+        let chain_def: ChainFile = serde_yaml::from_str(&yaml)
+            .map_err(|e| StoreError::Configuration(format!("Failed to parse chain file: {}", e)))?;
+
+        for step_def in &chain_def.steps {
+            if let Some((id, cond)) = step_def.exit_when() {
+                if let Err(e) = cond.predicate() {
+                    return Err(StoreError::Configuration(format!(
+                        "Invalid exit_when of step '{}': {}",
+                        id, e
+                    )));
+                }
+            }
+            let steps = match step_def {
+                StepDefinition::Sequential(step) => std::slice::from_ref(step),
+                StepDefinition::Parallel { parallel } => parallel.as_slice(),
+                _ => &[],
+            };
+            for step in steps {
+                if let Some(Err(e)) = step.condition.as_ref().map(Condition::predicate) {
+                    return Err(StoreError::Configuration(format!(
+                        "Invalid condition of step '{}': {}",
+                        step.id, e
+                    )));
+                }
+                if step.exit_when.is_some() && matches!(step_def, StepDefinition::Parallel { .. }) {
+                    return Err(StoreError::Configuration(format!(
+                        "Step '{}' has an 'exit_when', which is not supported in parallel groups",
+                        step.id
+                    )));
+                }
+                if step.otherwise.is_some() && step.condition.is_none() {
+                    return Err(StoreError::Configuration(format!(
+                        "Step '{}' has an 'else' without an 'if'",
+                        step.id
+                    )));
+                }
+            }
+        }
+
+        let mut defaults = HashMap::new();
+        let mut required = Vec::new();
+        for (name, spec) in chain_def.vars {
+            match spec {
+                VarSpec::Default(value)
+                | VarSpec::Spec {
+                    required: false,
+                    default: Some(value),
+                    ..
+                } => {
+                    defaults.insert(name, value);
+                }
+                VarSpec::Spec {
+                    required: true,
+                    default: Some(_),
+                    ..
+                } => {
+                    return Err(StoreError::Configuration(format!(
+                        "Variable '{}' is both required and given a default",
+                        name
+                    )))
+                }
+                VarSpec::Spec { required: true, .. } => required.push(name),
+                VarSpec::Spec { .. } => {}
+            }
+        }
+        let presets = repo.load_chain_presets(id).map_err(StoreError::Storage)?;
+
+        let mut runner = runner
+            .default_vars(defaults)
+            .required_vars(required)
+            .presets(presets)
+            .allow_commands(load_shell_allowlist().map_err(StoreError::Configuration)?);
+        if let Some(policy) = load_input_redaction().map_err(StoreError::Configuration)? {
+            runner = runner.redact_inputs(policy);
+        }
+        for sink in chain_def.sinks {
+            runner = runner.on_complete(sink);
+        }
+        for (name, members) in load_failover_groups().map_err(StoreError::Configuration)? {
+            if let Some((primary, fallbacks)) = members.split_first() {
+                let fallbacks: Vec<&str> = fallbacks.iter().map(String::as_str).collect();
+                runner = runner.failover_group(&name, primary, &fallbacks);
+            }
+        }
+        for (name, members) in load_provider_pools().map_err(StoreError::Configuration)? {
+            let members: Vec<(&str, u32)> = members.iter().map(|(p, w)| (p.as_str(), *w)).collect();
+            runner = runner.provider_pool(&name, &members);
+        }
+
+        for step_def in chain_def.steps {
+            let exit_when = step_def.exit_when().map(|(_, cond)| cond.clone());
+            runner = match step_def {
+                StepDefinition::Sequential(step) => {
+                    let runner_with_step = if let Some(cond) = step.condition {
+                        runner.step_when(&step.id, &step.prompt, cond)
+                    } else {
+                        runner.step(&step.id, &step.prompt)
+                    };
+
+                    let runner_with_step = match &step.otherwise {
+                        Some(otherwise) => runner_with_step.else_stored(&otherwise.prompt),
+                        None => runner_with_step,
+                    };
+
+                    let runner_with_fallback = if let Some(fallback) = step.on_error {
+                        runner_with_step.on_error_stored(&fallback.prompt)
+                    } else {
+                        runner_with_step
+                    };
+
+                    let runner_with_fallback = match step.context {
+                        Some(budget) => runner_with_fallback.with_context_budget(budget),
+                        None => runner_with_fallback,
+                    };
+                    let runner_with_fallback = match step.samples {
+                        Some(spec) => runner_with_fallback.with_samples(spec.count, spec.aggregate),
+                        None => runner_with_fallback,
+                    };
+                    let runner_with_fallback = match step.guard {
+                        Some(guard) => runner_with_fallback.with_guard(guard),
+                        None => runner_with_fallback,
+                    };
+                    let runner_with_fallback = match &step.conversation {
+                        Some(name) => runner_with_fallback.in_conversation(name),
+                        None => runner_with_fallback,
+                    };
+
+                    match (&step.pool, &step.provider) {
+                        (Some(pool), _) => runner_with_fallback.with_provider_pool(pool),
+                        (None, Some(provider)) => runner_with_fallback.with_provider(provider),
+                        (None, None) => runner_with_fallback,
+                    }
+                }
+                StepDefinition::Parallel { parallel } => runner.parallel(|group| {
+                    let mut current_group = group;
+                    for step in parallel {
+                        let step_id = step.id.clone();
+                        let prompt = step.prompt.clone();
+
+                        let group_with_step = if let Some(cond) = step.condition {
+                            current_group.step_when(&step_id, &prompt, cond)
+                        } else {
+                            current_group.step(&step_id, &prompt)
+                        };
+
+                        let group_with_step = match &step.otherwise {
+                            Some(otherwise) => group_with_step.else_stored(&otherwise.prompt),
+                            None => group_with_step,
+                        };
+
+                        let group_with_fallback = if let Some(fallback) = step.on_error {
+                            group_with_step.on_error_stored(&fallback.prompt)
+                        } else {
+                            group_with_step
+                        };
+
+                        let group_with_fallback = match step.context {
+                            Some(budget) => group_with_fallback.with_context_budget(budget),
+                            None => group_with_fallback,
+                        };
+                        let group_with_fallback = match step.samples {
+                            Some(spec) => {
+                                group_with_fallback.with_samples(spec.count, spec.aggregate)
+                            }
+                            None => group_with_fallback,
+                        };
+                        let group_with_fallback = match step.guard {
+                            Some(guard) => group_with_fallback.with_guard(guard),
+                            None => group_with_fallback,
+                        };
+                        let group_with_fallback = match &step.conversation {
+                            Some(name) => group_with_fallback.in_conversation(name),
+                            None => group_with_fallback,
+                        };
+
+                        current_group = match (&step.pool, &step.provider) {
+                            (Some(pool), _) => group_with_fallback.with_provider_pool(pool),
+                            (None, Some(provider)) => group_with_fallback.with_provider(provider),
+                            (None, None) => group_with_fallback,
+                        };
+                    }
+                    current_group
+                }),
+                StepDefinition::Switch { switch } => {
+                    let cases = switch
+                        .cases
+                        .iter()
+                        .map(|(value, prompt)| (value.as_str(), prompt.as_str()));
+                    let mut runner = runner.branch(&switch.id, &switch.on, cases);
+                    if let Some(default) = &switch.default {
+                        runner = runner.else_stored(default);
+                    }
+                    if let Some(fallback) = &switch.on_error {
+                        runner = runner.on_error_stored(&fallback.prompt);
+                    }
+                    if let Some(budget) = switch.context {
+                        runner = runner.with_context_budget(budget);
+                    }
+                    if let Some(name) = &switch.conversation {
+                        runner = runner.in_conversation(name);
+                    }
+                    match (&switch.pool, &switch.provider) {
+                        (Some(pool), _) => runner.with_provider_pool(pool),
+                        (None, Some(provider)) => runner.with_provider(provider),
+                        (None, None) => runner,
+                    }
+                }
+                StepDefinition::Loop { refine } => {
+                    let mut runner = runner.refine_loop(
+                        &refine.id,
+                        &refine.prompt,
+                        &refine.critic.id,
+                        &refine.critic.prompt,
+                        refine.max_iterations,
+                    );
+                    if let Some(budget) = refine.context {
+                        runner = runner.with_context_budget(budget);
+                    }
+                    if let Some(name) = &refine.conversation {
+                        runner = runner.in_conversation(name);
+                    }
+                    match (&refine.pool, &refine.provider) {
+                        (Some(pool), _) => runner.with_provider_pool(pool),
+                        (None, Some(provider)) => runner.with_provider(provider),
+                        (None, None) => runner,
+                    }
+                }
+                StepDefinition::Retrieve { retrieve } => {
+                    let runner =
+                        runner.step_retrieve(&retrieve.id, &retrieve.query, retrieve.top_k);
+                    match &retrieve.provider {
+                        Some(provider) => runner.with_provider(provider),
+                        None => runner,
+                    }
+                }
+                StepDefinition::Fetch { fetch } => {
+                    let mut runner = runner.step_fetch(&fetch.id, &fetch.url);
+                    if let Some(max_bytes) = fetch.max_bytes {
+                        runner = runner.with_max_bytes(max_bytes);
+                    }
+                    if fetch.readable {
+                        runner = runner.with_readability();
+                    }
+                    runner
+                }
+                StepDefinition::Shell { shell } => {
+                    let args: Vec<&str> = shell.args.iter().map(String::as_str).collect();
+                    let runner = runner.step_shell(&shell.id, &shell.command, &args);
+                    match &shell.stdin {
+                        Some(stdin) => runner.with_stdin(stdin),
+                        None => runner,
+                    }
+                }
+            };
+            if let Some(cond) = exit_when {
+                runner = runner.finish_when(cond);
+            }
+        }
+        Ok(runner)
+    }
+}
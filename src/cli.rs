@@ -1,35 +1,146 @@
 //! Defines the command-line interface structure using clap.
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "prompt-store", version, about = "Encrypted prompts manager")]
 pub struct Cli {
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub log: LogArgs,
+    #[cfg(feature = "grpc")]
+    #[command(flatten)]
+    pub remote: RemoteArgs,
     #[command(subcommand)]
     pub command: Cmd,
 }
 
+/// Flags of every command controlling how the output is shown.
+#[derive(Args, Clone, Copy, Default)]
+pub struct OutputArgs {
+    /// Disable colors (also disabled by a non-empty NO_COLOR)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Print long listings at once instead of through a pager
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+    /// Show long lines in full instead of truncating them to the terminal width
+    #[arg(long, global = true)]
+    pub full: bool,
+    /// Hide the progress bars of bulk operations, for scripts
+    #[arg(long, short = 'q', global = true)]
+    pub quiet: bool,
+}
+
+/// Flags of every command controlling the diagnostic logs.
+#[derive(Args, Clone, Copy, Default)]
+pub struct LogArgs {
+    /// Log to stderr: -v for operations, -vv for requests and file access, -vvv for everything
+    #[arg(long, short = 'v', action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Also log to logs/prompt-store.log in the store (always on with [logging] file = true)
+    #[arg(long, global = true)]
+    pub log_file: bool,
+    /// With -vvv, log prompt and response text instead of its length
+    #[arg(long, global = true)]
+    pub unsafe_log: bool,
+}
+
+/// Flags of every command sending it to a store served by `prompt-store serve`.
+#[cfg(feature = "grpc")]
+#[derive(Args, Clone, Default)]
+pub struct RemoteArgs {
+    /// Run render, run and chain run on the store served at this URL (e.g., 'https://host:50051') instead of the local one
+    #[arg(long, global = true, env = "PROMPT_STORE_REMOTE")]
+    pub remote: Option<String>,
+    /// API token of the remote store (see 'prompt-store tokens add')
+    #[arg(long, global = true, env = "PROMPT_STORE_TOKEN", hide_env_values = true)]
+    pub remote_token: Option<String>,
+}
+
+/// Flags of the commands removing data.
+#[derive(Args, Clone, Copy, Default)]
+pub struct RemoveArgs {
+    /// Remove without asking for confirmation
+    #[arg(long)]
+    pub force: bool,
+    /// Show what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
 #[derive(Subcommand)]
 pub enum Cmd {
+    /// Set up the store step by step: password, a provider, a starter pack and a sample prompt
+    Init,
     /// List all stored prompts and chains
     List {
         #[arg(long, help = "Filter prompts by tag(s)")]
         tag: Vec<String>,
+        /// Only list this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+        /// Order of the prompts in each workspace
+        #[arg(long, value_enum, default_value_t = ListSort::Id)]
+        sort: ListSort,
+        /// Columns to show, comma-separated
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [ListColumn::Id, ListColumn::Title])]
+        columns: Vec<ListColumn>,
     },
     /// Create a new prompt
     New,
     /// Get a specific prompt by ID (e.g., `my-prompt` or `my-pack::my-prompt`)
-    Get { id: String },
+    Get {
+        id: String,
+        /// Also show the example inputs and expected outputs of the prompt
+        #[arg(long)]
+        examples: bool,
+        /// Show the translation in this language (e.g., 'fr') instead of the default content
+        #[arg(long)]
+        lang: Option<String>,
+        /// Show this variant (e.g., 'b') instead of the default content
+        #[arg(long, conflicts_with = "lang")]
+        variant: Option<String>,
+    },
     /// Edit an existing prompt
-    Edit { id: String },
-    /// Delete a prompt or chain by ID
-    Delete { id: String },
+    Edit {
+        id: String,
+        /// Edit the translation in this language (e.g., 'fr') instead of the default content
+        #[arg(long)]
+        lang: Option<String>,
+        /// Edit or create this variant (e.g., 'b') instead of the default content
+        #[arg(long, conflicts_with = "lang")]
+        variant: Option<String>,
+    },
+    /// Delete a prompt or chain by ID, with its attachments and backups
+    Delete {
+        id: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+    /// Undo the last delete, step or tag removal, rename or import
+    Undo {
+        /// Undo without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    /// List the chains referencing a prompt by ID or title
+    Uses { id: String },
     /// Rename a prompt's title
     Rename {
         id: String,
         #[arg(long, help = "New title for the prompt")]
-        title: String,
+        title: Option<String>,
+        /// New ID for the prompt, in the same workspace
+        #[arg(long = "id")]
+        new_id: Option<String>,
+        /// Rewrite the chain references to the prompt instead of letting them break
+        #[arg(long)]
+        update_references: bool,
     },
+    /// Report chain references to prompts that no longer exist
+    CheckLinks,
     /// Search prompts by query, optionally filtering by tag or content
     Search {
         query: String,
@@ -40,35 +151,220 @@ pub enum Cmd {
     },
     /// Tag a prompt with one or more tags
     #[command(about = "Tag a prompt with one or more tags")]
-    Tag { id: String, changes: Vec<String> },
+    Tag {
+        id: String,
+        #[arg(allow_hyphen_values = true, help = "Tags to add (+tag) or remove (-tag)")]
+        changes: Vec<String>,
+    },
     /// Copy a prompt to clipboard
     Copy { id: String },
     /// Generate a response by executing a prompt with an LLM
     Run {
         /// ID of the prompt to execute (e.g., `my-prompt` or `pack::my-prompt`)
         id: String,
-        /// LLM backend to use, e.g., 'openai:gpt-4o-mini'
+        /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
         #[arg(long)]
         backend: String,
         /// Variable assignments in key=value format
         #[arg(long = "var")]
         vars: Vec<String>,
+        /// Environment profile providing default variables
+        #[arg(long = "env")]
+        env_profile: Option<String>,
+        /// Execute the generated shell command (prompt must be tagged 'command')
+        #[arg(long)]
+        exec: bool,
+        /// Skip the confirmation prompt when using --exec
+        #[arg(long, requires = "exec")]
+        yes: bool,
+        /// Follow-up prompt ID receiving {{command}}, {{exit_status}}, {{stdout}} and {{stderr}}
+        #[arg(long, requires = "exec")]
+        then: Option<String>,
+        /// Language of the translation to use (e.g., 'fr'), falling back to the default content
+        #[arg(long)]
+        lang: Option<String>,
+        /// Variant to run (e.g., 'b') instead of the default content; runs are counted per variant
+        #[arg(long, conflicts_with = "lang")]
+        variant: Option<String>,
+    },
+    /// Run prompts in sequence, passing each output to the next as {{input}}
+    Pipe {
+        /// IDs or titles of the prompts to run, in order
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Provider name from config.toml (defaults to [defaults] provider)
+        #[arg(long)]
+        provider: Option<String>,
+        /// Variable assignments in key=value format
+        #[arg(long = "var")]
+        vars: Vec<String>,
+        /// Language of the translation to use (e.g., 'fr'), falling back to the default content
+        #[arg(long)]
+        lang: Option<String>,
     },
     /// Render a prompt with variable substitution (local only)
     Render {
         id: String,
         #[arg(long = "var", help = "Variable assignments in key=value format")]
         vars: Vec<String>,
+        /// Language of the translation to use (e.g., 'fr'), falling back to the default content
+        #[arg(long)]
+        lang: Option<String>,
+    },
+    /// Write a new version of a prompt and serve it to a share of the library runs
+    Canary {
+        /// ID of the prompt
+        id: String,
+        /// Percentage of the runs served by the canary (0 to 100)
+        #[arg(long)]
+        percent: Option<u8>,
+        /// Drop the canary, keeping the current content
+        #[arg(long, conflicts_with = "percent")]
+        abort: bool,
+    },
+    /// Make the canary of a prompt its content, served to every run
+    Promote {
+        /// ID of the prompt
+        id: String,
+    },
+    /// Draft a translation of a prompt with an LLM, review it in the editor and save it
+    Translate {
+        /// ID of the prompt
+        id: String,
+        /// Language code of the translation, e.g., 'fr' or 'pt-br'
+        #[arg(long)]
+        to: String,
+        /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+        #[arg(long)]
+        backend: String,
+    },
+    /// Run a prompt on each of its examples and compare the outputs with the expected ones
+    Eval {
+        /// ID or title of the prompt
+        id: String,
+        /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+        #[arg(long)]
+        backend: String,
+    },
+    /// Run a prompt once per record of a JSONL dataset, or manage provider batch jobs
+    #[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+    Batch {
+        #[command(subcommand)]
+        job: Option<BatchCmd>,
+        #[command(flatten)]
+        run: Option<BatchRunArgs>,
+    },
+    /// Run two versions of a prompt on the same inputs and report the outputs side by side
+    CompareVersions {
+        /// ID of the prompt
+        id: String,
+        /// Older version: 'v<n>' as listed by `history`, 'current' or a backup timestamp
+        #[arg(long)]
+        from: String,
+        /// Newer version, the current content by default
+        #[arg(long, default_value = "current")]
+        to: String,
+        /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+        #[arg(long)]
+        backend: String,
+        /// YAML list of variable maps, one per case (defaults to the prompt's examples)
+        #[arg(long)]
+        vars_file: Option<String>,
+        /// Backend judging which output is better for each case
+        #[arg(long)]
+        judge: Option<String>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Md)]
+        format: ReportFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Iterate on a prompt: edit its variables and content, re-run it, then save it
+    Play {
+        /// ID of the prompt
+        id: String,
+        /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+        #[arg(long)]
+        backend: String,
+        /// Initial variable assignments in key=value format
+        #[arg(long = "var")]
+        vars: Vec<String>,
+    },
+    /// Print a file, or stdin, with emails, phone numbers, card numbers and `[redaction]` patterns masked
+    Redact {
+        /// File to redact (reads stdin if omitted or `-`)
+        file: Option<String>,
     },
-    /// Export prompts to a file for personal backup
+    /// Export prompts and chains to a file for personal backup
     Export {
-        #[arg(long, help = "Comma-separated list of prompt IDs to export from the default workspace")]
+        #[arg(
+            long,
+            help = "Comma-separated list of prompt or chain IDs to export from the default workspace"
+        )]
         ids: Option<String>,
-        #[arg(long, help = "Output file path")]
+        #[arg(long, help = "Output file path (an empty directory with --plaintext)")]
         out: String,
+        /// Format of the output file
+        #[arg(long, value_enum, default_value_t = ExportFormat::Bundle)]
+        format: ExportFormat,
+        /// LLM backend used by the run actions of launcher exports (raycast, quick-actions)
+        #[arg(long)]
+        backend: Option<String>,
+        /// Include the attachments of the prompts (bundle format only)
+        #[arg(long)]
+        attachments: bool,
+        /// Write an unencrypted, canonical dump with a manifest of SHA-256 hashes (recorded in the audit log)
+        #[arg(long)]
+        plaintext: bool,
+        /// Sign the manifest of a plaintext dump with the Ed25519 key of the store
+        #[arg(long, requires = "plaintext")]
+        sign: bool,
+        /// Confirm the plaintext dump without prompting
+        #[arg(long, requires = "plaintext")]
+        yes: bool,
+    },
+    /// Import prompts and chains from a personal backup file, or prompts from a CSV file
+    Import {
+        file: String,
+        /// Format of the file
+        #[arg(long, value_enum, default_value_t = ImportFormat::Bundle)]
+        format: ImportFormat,
+        /// Map a CSV column to a prompt field (title, content, tags, description), e.g. "Prompt text=content"
+        #[arg(long = "map")]
+        mappings: Vec<String>,
+        /// Tag marking the notes to import as prompts, besides `prompt: true` (markdown only)
+        #[arg(long, default_value = "prompt")]
+        prompt_tag: String,
+        /// What to do when an imported ID already exists [default: new-id, overwrite for markdown]
+        #[arg(long, value_enum)]
+        on_conflict: Option<OnConflict>,
+        /// What to do with prompts identical to one stored under another ID
+        #[arg(long, value_enum, default_value_t = OnDuplicate::Ask)]
+        on_duplicate: OnDuplicate,
+        /// Show what would be created, updated or skipped without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Only import matching items: tag:<tag>, id:<id>, title:<title>, title~<text> or content~<text> (repeatable, all must match)
+        #[arg(long = "only")]
+        only: Vec<String>,
+        /// List the (matching) contents of the file without importing anything
+        #[arg(long)]
+        list: bool,
+    },
+    /// Share a prompt encrypted with a one-time passphrase, as a paste link or `.pshare` file
+    Share {
+        id: String,
+        /// Write the share to this file instead of uploading it to the `[share]` endpoint
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Import a prompt shared with `share`, from a `.pshare` file or a paste URL
+    Receive {
+        source: String,
+        /// Passphrase of the share (prompted if omitted, can also be set via PROMPT_SHARE_PASSPHRASE)
+        #[arg(long, env = "PROMPT_SHARE_PASSPHRASE")]
+        passphrase: Option<String>,
     },
-    /// Import prompts from a personal backup file
-    Import { file: String },
     /// Show prompt revision history
     History { id: String },
     /// Revert a prompt to a previous version
@@ -82,15 +378,67 @@ pub enum Cmd {
         #[arg(long, help = "Protect the new key with a password")]
         password: bool,
     },
+    /// Change the password protecting the master key, without re-encrypting the store
+    Passwd {
+        /// Argon2 memory cost, in KiB
+        #[arg(long, default_value_t = argon2::Params::DEFAULT_M_COST)]
+        memory_kib: u32,
+        /// Argon2 number of iterations
+        #[arg(long, default_value_t = argon2::Params::DEFAULT_T_COST)]
+        iterations: u32,
+        /// Argon2 degree of parallelism
+        #[arg(long, default_value_t = argon2::Params::DEFAULT_P_COST)]
+        parallelism: u32,
+    },
+    /// Archive the whole store (workspaces, chains, history, config) to an encrypted file
+    Backup {
+        #[arg(long, help = "Output file path")]
+        out: String,
+        #[arg(long, help = "Include the master key, protected by a password")]
+        with_key: bool,
+    },
+    /// Restore the whole store from a backup, after verifying its integrity
+    Restore {
+        file: String,
+        #[arg(long, help = "Skip the confirmation prompt")]
+        yes: bool,
+    },
+    /// Move the store between machines without exporting the master key
+    #[command(subcommand)]
+    Transfer(TransferCmd),
     /// Manage prompt chains
     #[command(subcommand)]
     Chain(ChainCmd),
+    /// List the recorded chain runs and export them as reports
+    #[command(subcommand)]
+    Runs(RunsCmd),
+    /// Freeze a workspace into immutable named releases that applications can pin
+    #[command(subcommand)]
+    Release(ReleaseCmd),
     /// Manage prompt packs for sharing and deployment
     #[command(subcommand)]
     Pack(PackCmd),
-    /// Deploy a prompt pack from a git repository
+    /// Manage LLM providers and their stored API keys
+    #[command(subcommand)]
+    Providers(ProvidersCmd),
+    /// Manage environment profiles of variables (e.g., dev/staging/prod)
+    #[command(subcommand)]
+    Env(EnvCmd),
+    /// Manage the API tokens `serve` requires, with their scope and workspaces
+    #[command(subcommand)]
+    Tokens(TokensCmd),
+    /// Manage the local document index used by retrieval steps
+    #[command(subcommand)]
+    Docs(DocsCmd),
+    /// Manage encrypted files attached to a prompt (reference docs, example outputs)
+    #[command(subcommand)]
+    Attach(AttachCmd),
+    /// Escrow the master key as Shamir shares, and recover it from them
+    #[command(subcommand)]
+    Key(KeyCmd),
+    /// Deploy a prompt pack from a git repository or a pack registry
     Deploy {
-        /// URL of the git repository to deploy
+        /// URL of the git repository, or of a registry pack (`https://registry.internal/packs/legal`)
         repo_url: String,
         /// Optional local alias for the pack (defaults to repo name)
         #[arg(long)]
@@ -98,22 +446,120 @@ pub enum Cmd {
         /// Password for private/encrypted packs (can also be set via PROMPT_PACK_PASSWORD env var)
         #[arg(long, env = "PROMPT_PACK_PASSWORD")]
         password: Option<String>,
+        /// Pin the pack to a tag, branch or commit; `update` then leaves it alone
+        #[arg(long)]
+        rev: Option<String>,
+        /// Install without asking for confirmation when prompts look like prompt injections
+        #[arg(long, short = 'y')]
+        yes: bool,
+        /// What to do with prompts identical to one already stored
+        #[arg(long, value_enum, default_value_t = OnDuplicate::Ask)]
+        on_duplicate: OnDuplicate,
     },
     /// Update deployed prompt pack(s)
     Update {
         /// The alias of a specific pack to update. If omitted, all packs are updated.
         alias: Option<String>,
+        /// Only report which packs have a newer version, without updating them
+        #[arg(long)]
+        check: bool,
+        /// Also update pinned packs to the latest version, removing their pin
+        #[arg(long)]
+        latest: bool,
+        /// Apply updates without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    /// Show what a workspace or deployed pack is for: its purpose, owner, conventions and providers
+    Describe {
+        /// Workspace or pack alias
+        name: String,
+        /// Edit the description of the workspace as YAML
+        #[arg(long)]
+        edit: bool,
+        /// Set the purpose of the workspace ("" clears it)
+        #[arg(long)]
+        purpose: Option<String>,
+        /// Set who maintains the workspace ("" clears it)
+        #[arg(long)]
+        owner: Option<String>,
+        /// Set the conventions the prompts of the workspace follow ("" clears it)
+        #[arg(long)]
+        conventions: Option<String>,
+        /// Only run the prompts of the workspace on these providers, e.g. 'local-ollama' ("" clears it)
+        #[arg(long, value_delimiter = ',')]
+        allow_providers: Option<Vec<String>>,
+        /// Never run the prompts of the workspace on these providers, e.g. 'openai' ("" clears it)
+        #[arg(long, value_delimiter = ',')]
+        deny_providers: Option<Vec<String>>,
     },
     /// Show store statistics
-    Stats,
+    Stats {
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Prune old backups, orphaned chain steps and unused registry clones
+    Gc {
+        #[arg(
+            long,
+            default_value_t = 5,
+            help = "Number of backups to keep per prompt"
+        )]
+        keep: usize,
+        #[command(flatten)]
+        remove: RemoveArgs,
+        #[arg(
+            long,
+            help = "Also rewrite encrypted files that shrink once compressed in the current format"
+        )]
+        recompress: bool,
+    },
+    /// Upgrade store files written in older formats to the current one
+    Migrate {
+        #[arg(long, help = "List the files to upgrade without writing anything")]
+        dry_run: bool,
+    },
     /// Start an interactive session (REPL)
     Interactive,
+    /// Run prompts requested through a queue (a directory or a Redis URL) and write the results back
+    Worker {
+        /// Directory of request files, or a Redis URL such as 'redis://localhost:6379'
+        #[arg(long)]
+        queue: String,
+        /// Backend used for requests that don't name one, e.g., 'openai:gpt-4o-mini'
+        #[arg(long)]
+        backend: Option<String>,
+        /// Stop once the queue is empty instead of waiting for new requests
+        #[arg(long)]
+        once: bool,
+        /// Serve Prometheus metrics (/metrics) and health checks (/healthz, /readyz) on this address, e.g. '127.0.0.1:9464'
+        #[arg(long)]
+        metrics_addr: Option<String>,
+    },
+    /// Serve render, run and chain operations over gRPC (see proto/prompt_store.proto)
+    #[cfg(feature = "grpc")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:50051")]
+        addr: String,
+        /// Serve Prometheus metrics (/metrics) and health checks (/healthz, /readyz) on this address, e.g. '127.0.0.1:9464'
+        #[arg(long)]
+        metrics_addr: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum ChainCmd {
-    /// Create a new multi-step prompt chain interactively
-    New,
+    /// Create a new multi-step prompt chain interactively, or from a built-in template
+    New {
+        /// Create a runnable chain from a built-in template instead
+        #[arg(long, value_enum)]
+        template: Option<ChainTemplate>,
+        /// ID of the chain created from the template (generated from its title by default)
+        #[arg(long, requires = "template")]
+        id: Option<String>,
+    },
     /// Import a YAML chain definition into the default workspace
     Import {
         /// Path to the YAML file defining the chain
@@ -128,6 +574,31 @@ pub enum ChainCmd {
         id: String,
         #[arg(long = "var", help = "Variable assignments in key=value format")]
         vars: Vec<String>,
+        /// Environment profile providing default variables
+        #[arg(long = "env")]
+        env_profile: Option<String>,
+        /// Language of the step prompt translations to use (e.g., 'fr')
+        #[arg(long)]
+        lang: Option<String>,
+        /// Use a set of variables saved with the chain
+        #[arg(long)]
+        preset: Option<String>,
+        /// Read variables from a JSON or YAML object (defaults to $PS_VARS_FILE)
+        #[arg(long)]
+        vars_file: Option<String>,
+        /// Read variables from a JSON object on stdin
+        #[arg(long)]
+        vars_stdin: bool,
+        /// Save the `--var` variables as a preset of the chain before running it
+        #[arg(long, value_name = "NAME")]
+        save_preset: Option<String>,
+    },
+    /// List the variable presets of a chain
+    Presets {
+        id: String,
+        /// Delete this preset
+        #[arg(long, value_name = "NAME")]
+        delete: Option<String>,
     },
     /// Edit a chain's metadata (e.g., title)
     Edit { id: String },
@@ -135,8 +606,21 @@ pub enum ChainCmd {
     AddStep { id: String },
     /// Remove a step from a chain
     RmStep {
-        #[arg(help = "The ID of the step to remove (e.g., mychain/1)")]
+        #[arg(help = "The ID of the step to remove (e.g., mychain/1 or mychain/\"Extract Topic\")")]
         step_id: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+    /// List the steps of a chain with their titles
+    Steps {
+        /// The ID of the chain (e.g., `my-chain` or `my-pack::my-chain`)
+        id: String,
+    },
+    /// Add (+tag) or remove (-tag) tags on a chain
+    Tag {
+        id: String,
+        #[arg(allow_hyphen_values = true, help = "Tags to add (+tag) or remove (-tag)")]
+        changes: Vec<String>,
     },
 }
 
@@ -147,5 +631,379 @@ pub enum PackCmd {
         /// Workspace to export (defaults to 'default')
         #[arg(long)]
         workspace: Option<String>,
+        /// Include the attachments of the prompts in the pack
+        #[arg(long)]
+        attachments: bool,
+        /// Ship a policy.toml with the pack, enforced where the pack is deployed
+        #[arg(long)]
+        policy: Option<String>,
+    },
+    /// Remove a deployed pack: its workspace, its git clone and its deployment record
+    Remove {
+        /// Alias of the pack
+        alias: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+    /// Re-install the version of a pack deployed before its last update
+    Rollback {
+        /// Alias of the pack
+        alias: String,
+    },
+    /// List the packs available on a pack registry server
+    ListRemote {
+        /// Base URL of the registry (e.g., `https://registry.internal`)
+        registry: String,
+    },
+    /// Show how often the prompts of a pack were run or copied
+    Stats {
+        /// Alias of the pack
+        alias: String,
+        /// Also write anonymized counts to this file, to share with the pack maintainers
+        #[arg(long)]
+        export: Option<String>,
+    },
+}
+
+/// Orders accepted by `list --sort`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListSort {
+    Id,
+    Title,
+    /// Most recently updated first
+    Updated,
+    /// Most run first
+    Runs,
+}
+
+/// Columns accepted by `list --columns`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListColumn {
+    Id,
+    Title,
+    Tags,
+    Updated,
+}
+
+/// Formats written by `export`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Encrypted bundle, read back by `import`
+    Bundle,
+    /// VS Code snippets (plain text), with `{{var}}` placeholders as tab stops
+    VscodeSnippets,
+    /// Directory of Raycast script commands copying or running each prompt
+    Raycast,
+    /// JSON list of titles with the commands copying or running each prompt
+    QuickActions,
+}
+
+/// Formats accepted by `import`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// Encrypted bundle written by `export`
+    Bundle,
+    /// CSV with title, content, tags and description columns
+    Csv,
+    /// Folder of markdown notes (Obsidian vault, Notion export)
+    Markdown,
+}
+
+/// How `import` handles items whose ID already exists.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnConflict {
+    /// Keep the existing item
+    Skip,
+    /// Replace the existing item
+    Overwrite,
+    /// Import under a new ID
+    NewId,
+    /// Keep whichever was updated most recently
+    Merge,
+}
+
+/// Built-in chains of `chain new --template`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChainTemplate {
+    /// Summarize a text, then review the summary against it
+    SummarizeAndCritique,
+    /// Extract fields of a document as JSON, then check them against it
+    ExtractStructuredData,
+    /// Translate a text, then enforce the translations of a glossary
+    TranslateAndGlossaryCheck,
+}
+
+/// How `import` and `deploy` handle prompts identical to one stored under another ID.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnDuplicate {
+    /// Ask for each prompt
+    Ask,
+    /// Store a link to the existing prompt
+    Link,
+    /// Store a copy
+    Copy,
+}
+
+#[derive(Subcommand)]
+pub enum TransferCmd {
+    /// Export the store re-encrypted under a transfer password
+    Export {
+        #[arg(long, help = "Output file path")]
+        out: String,
+        /// Transfer password (prompted if omitted, can also be set via PROMPT_TRANSFER_PASSWORD)
+        #[arg(long, env = "PROMPT_TRANSFER_PASSWORD")]
+        password: Option<String>,
+    },
+    /// Import a transfer file, re-encrypting it with this machine's master key
+    Import {
+        file: String,
+        /// Transfer password (prompted if omitted, can also be set via PROMPT_TRANSFER_PASSWORD)
+        #[arg(long, env = "PROMPT_TRANSFER_PASSWORD")]
+        password: Option<String>,
+        #[arg(long, help = "Skip the confirmation prompt")]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AttachCmd {
+    /// Attach a file to a prompt, replacing an attachment of the same name
+    Add {
+        /// The prompt ID
+        id: String,
+        /// Path to the file to attach
+        file: String,
+        /// Name referenced as {{attachment:<name>}} (defaults to the file name without extension)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List the attachments of a prompt
+    List { id: String },
+    /// Print an attachment, or write it to a file
+    Get {
+        id: String,
+        name: String,
+        /// Write the attachment to this file instead of stdout
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Remove an attachment from a prompt
+    Remove {
+        id: String,
+        name: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RunsCmd {
+    /// List the recorded chain runs, most recent first
+    List,
+    /// Write a readable report of a chain run: each step's prompt, provider, latency and output
+    Export {
+        /// ID of the run, as printed by `chain run` or `runs list`
+        run_id: String,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Md)]
+        format: ReportFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
+
+/// Arguments of `batch <id>`, which runs the records itself.
+#[derive(Args)]
+pub struct BatchRunArgs {
+    /// ID of the prompt
+    pub id: String,
+    /// JSONL file with one object of variables per line
+    #[arg(long)]
+    pub input: String,
+    /// LLM backend to use, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+    #[arg(long)]
+    pub backend: String,
+    /// JSONL file the results are appended to; records already answered there are skipped
+    #[arg(long)]
+    pub out: String,
+    /// Number of records sent at the same time
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+    /// Maximum requests per minute
+    #[arg(long)]
+    pub rate: Option<u32>,
+    /// Retries of a record failing with a transient error (network, 429, 5xx)
+    #[arg(long, default_value_t = 2)]
+    pub retries: u32,
+}
+
+#[derive(Subcommand)]
+pub enum BatchCmd {
+    /// Submit the records of a JSONL dataset as a provider batch job, billed at batch rates
+    Submit {
+        /// ID of the prompt
+        id: String,
+        /// JSONL file with one object of variables per line
+        #[arg(long)]
+        input: String,
+        /// OpenAI-compatible backend, e.g., 'openai:gpt-4o-mini', or a provider name from config.toml
+        #[arg(long)]
+        backend: String,
+    },
+    /// Show the status of a batch job, or list the submitted jobs
+    Status {
+        /// ID of the job, as printed by `batch submit`
+        job: Option<String>,
+    },
+    /// Download the results of a completed batch job
+    Fetch {
+        /// ID of the job, as printed by `batch submit`
+        job: String,
+        /// JSONL file to write the results to, in the format of `batch <id>`
+        #[arg(long)]
+        out: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReleaseCmd {
+    /// Snapshot the prompts and chains of a workspace into a new release
+    Create {
+        /// Name of the release, e.g., 'v1'
+        name: String,
+        /// Workspace to snapshot
+        #[arg(long, default_value = "default")]
+        workspace: String,
+    },
+    /// List the releases
+    List,
+}
+
+/// Formats written by `runs export`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Markdown
+    Md,
+    /// Standalone HTML page
+    Html,
+}
+
+#[derive(Subcommand)]
+pub enum DocsCmd {
+    /// Chunk and embed a file or folder into the encrypted document index
+    Add {
+        /// Path to a file or folder of text documents
+        path: String,
+        /// Embedding provider name from config.toml (defaults to [defaults] provider)
+        #[arg(long)]
+        provider: Option<String>,
+        /// Approximate number of characters per chunk
+        #[arg(long, default_value_t = 1000)]
+        chunk_size: usize,
+    },
+    /// List indexed documents
+    List,
+    /// Remove all documents from the index
+    Clear {
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyCmd {
+    /// Split the master key into shares for offline escrow
+    Backup {
+        /// Number of shares to create
+        #[arg(long, default_value_t = 5)]
+        shares: u8,
+        /// Number of shares needed to recover the key
+        #[arg(long, default_value_t = 3)]
+        threshold: u8,
+    },
+    /// Wrap the master key with the hardware device set by [key] hardware in config.toml
+    Protect,
+    /// Rebuild the master key from shares (asked for if not given) and restore key.bin
+    Recover {
+        shares: Vec<String>,
+        /// Replace the current key.bin without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EnvCmd {
+    /// Set variables in a profile, creating it if needed
+    Set {
+        profile: String,
+        /// Variable assignments in key=value format
+        #[arg(required = true)]
+        vars: Vec<String>,
     },
-}
\ No newline at end of file
+    /// Remove variables from a profile, or the whole profile if no keys are given
+    Unset {
+        profile: String,
+        keys: Vec<String>,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+    /// Show the variables of a profile
+    Show { profile: String },
+    /// List all profiles
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum TokensCmd {
+    /// Create a token and print it, once
+    Add {
+        name: String,
+        /// What the token gives access to
+        #[arg(long, value_enum)]
+        scope: TokenScope,
+        /// Restrict the token to this workspace (repeatable; every workspace by default)
+        #[arg(long = "workspace")]
+        workspaces: Vec<String>,
+        /// Maximum requests per minute (unlimited by default)
+        #[arg(long)]
+        rate_limit: Option<u32>,
+    },
+    /// List the tokens with their scope and workspaces
+    List,
+    /// Revoke a token
+    Remove {
+        name: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+}
+
+/// Scopes of the API tokens of `serve`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TokenScope {
+    /// Render prompts, without running them (e.g., CI)
+    ReadOnly,
+    /// Run prompts and chains, without reading the prompts (e.g., a frontend)
+    RunOnly,
+    /// Every operation
+    Admin,
+}
+
+#[derive(Subcommand)]
+pub enum ProvidersCmd {
+    /// List providers configured in config.toml
+    List,
+    /// Store a provider API key encrypted with the master key
+    SetKey { provider: String },
+    /// Remove a stored provider API key
+    RemoveKey {
+        provider: String,
+        #[command(flatten)]
+        remove: RemoveArgs,
+    },
+    /// Detect local models (Ollama, llama.cpp) and register them as providers
+    Discover,
+    /// List the models available from a configured provider
+    Models { name: String },
+}
@@ -0,0 +1,28 @@
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Attach a file to a prompt, encrypted with the master key. The attachment is named
+/// after the file, without its extension, unless `name` is given.
+pub fn run(ctx: &AppCtx, id: &str, file: &str, name: Option<&str>) -> Result<(), String> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => Path::new(file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("Cannot name an attachment after '{}', use --name", file))?,
+    };
+    let data = Zeroizing::new(fs::read(file).map_err(|e| format!("Read error: {}", e))?);
+    ctx.prompts().save_attachment(id, &name, &data)?;
+    println!(
+        "{} {} attached to prompt {}, use it as {}",
+        style(BULLET).green().bold(),
+        file,
+        id,
+        style(format!("{{{{attachment:{}}}}}", name)).yellow()
+    );
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::fs;
+use std::io::{self, Write};
+
+/// Print an attachment of a prompt, or decrypt it to `out`.
+pub fn run(ctx: &AppCtx, id: &str, name: &str, out: Option<&str>) -> Result<(), String> {
+    let data = ctx.prompts().load_attachment(id, name)?;
+    match out {
+        Some(path) => {
+            fs::write(path, &*data).map_err(|e| format!("Write error: {}", e))?;
+            eprintln!(
+                "{} attachment {} written to {}",
+                style(BULLET).green().bold(),
+                name,
+                path
+            );
+        }
+        None => io::stdout()
+            .write_all(&data)
+            .map_err(|e| format!("Write error: {}", e))?,
+    }
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the attachments of a prompt with their sizes.
+pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    if !prompts.exists(id) {
+        return Err(format!("No prompt with ID '{}'", id));
+    }
+    let names = prompts.list_attachments(id)?;
+    if names.is_empty() {
+        println!("{}", style("No attachments.").yellow());
+        return Ok(());
+    }
+
+    println!("{}", style("Attachments:").green().bold());
+    for name in names {
+        let size = prompts.load_attachment(id, &name)?.len();
+        println!("  {} {} ({} bytes)", style(BULLET).green(), name, size);
+    }
+    Ok(())
+}
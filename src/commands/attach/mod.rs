@@ -0,0 +1,4 @@
+pub mod add;
+pub mod get;
+pub mod list;
+pub mod remove;
@@ -0,0 +1,24 @@
+use crate::cli::RemoveArgs;
+use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Remove an attachment from a prompt.
+pub fn run(ctx: &AppCtx, id: &str, name: &str, args: RemoveArgs) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    if !prompts.list_attachments(id)?.iter().any(|n| n == name) {
+        return Err(format!("Prompt '{}' has no attachment '{}'", id, name));
+    }
+    if !confirm_removal(args, &format!("attachment {} of prompt {}", name, id))? {
+        return Ok(());
+    }
+    prompts.delete_attachment(id, name)?;
+    println!(
+        "{} attachment {} removed from prompt {}",
+        style(BULLET).green().bold(),
+        name,
+        id
+    );
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use crate::core::backup::create_backup;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+use dialoguer::Password;
+use std::fs;
+
+/// Archive the whole store to an encrypted file.
+pub fn run(ctx: &AppCtx, out: &str, with_key: bool) -> Result<(), String> {
+    let password = if with_key {
+        Some(
+            Password::new()
+                .with_prompt("Enter a password to protect the backup and its key")
+                .with_confirmation("Confirm password", "Passwords do not match.")
+                .interact()
+                .map_err(|e| format!("Password input error: {}", e))?,
+        )
+    } else {
+        None
+    };
+
+    let (archive, count) = create_backup(ctx, password.as_deref())?;
+    fs::write(out, archive).map_err(|e| format!("Failed to write backup: {}", e))?;
+
+    println!(
+        "{} Backed up {} files to {}",
+        style(OK).green(),
+        count,
+        style(out).yellow()
+    );
+    if !with_key {
+        println!(
+            "{}",
+            style("The master key is not included; restoring requires the same key (or use --with-key).")
+                .yellow()
+        );
+    }
+    Ok(())
+}
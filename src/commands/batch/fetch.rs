@@ -0,0 +1,87 @@
+use super::run::{count_records, BatchResult};
+use crate::core::batch_jobs::{download_answers, load_job, remote_status, save_job};
+use crate::core::config::load_openai_endpoint;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use std::fs;
+
+/// Download the answers of a completed batch job into a results file, one line per record
+/// in the format of `batch <id>`.
+pub async fn run(ctx: &AppCtx, id: &str, out: &str) -> Result<(), String> {
+    let mut job = load_job(&ctx.base_dir, &ctx.cipher, id)?
+        .ok_or_else(|| format!("Batch job '{}' not found.", id))?;
+    let endpoint = load_openai_endpoint(ctx, &job.backend)?;
+    let remote = remote_status(&endpoint, &job.id).await?;
+    if remote.status != job.status {
+        job.status = remote.status.clone();
+        save_job(&ctx.base_dir, &ctx.cipher, &job)?;
+    }
+    // An expired job keeps the answers given before its window ran out.
+    if !matches!(remote.status.as_str(), "completed" | "expired") {
+        return Err(format!(
+            "Job {} is {}; results can be fetched once it is completed.",
+            job.id, remote.status
+        ));
+    }
+
+    let answers = download_answers(&endpoint, &remote).await?;
+    let mut results = Vec::new();
+    for answer in answers {
+        let Some(input) = job.records.remove(&answer.line) else {
+            continue;
+        };
+        let (output, error) = match answer.result {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(e)),
+        };
+        results.push(BatchResult {
+            line: answer.line,
+            input,
+            output,
+            error,
+            latency_ms: 0,
+        });
+    }
+    // Records the provider didn't answer, e.g. when the job expired midway.
+    results.extend(
+        std::mem::take(&mut job.records)
+            .into_iter()
+            .map(|(line, input)| BatchResult {
+                line,
+                input,
+                output: None,
+                error: Some("No answer in the batch job".to_string()),
+                latency_ms: 0,
+            }),
+    );
+    results.sort_by_key(|result| result.line);
+
+    let mut lines = String::new();
+    for result in &results {
+        let json = serde_json::to_string(result).map_err(|e| format!("Serialize error: {}", e))?;
+        lines.push_str(&json);
+        lines.push('\n');
+    }
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    fs::write(out, lines).map_err(|e| format!("Unable to write {}: {}", out, e))?;
+
+    if failed > 0 {
+        println!(
+            "{} {} failed; 'prompt-store batch {} --input {} --backend {} --out {}' runs them again.",
+            style(BULLET).yellow().bold(),
+            count_records(failed),
+            job.prompt,
+            job.input,
+            job.backend,
+            out
+        );
+    }
+    println!(
+        "{} Results of job {} in {}",
+        style(OK).green().bold(),
+        job.id,
+        out
+    );
+    Ok(())
+}
@@ -0,0 +1,4 @@
+pub mod fetch;
+pub mod run;
+pub mod status;
+pub mod submit;
@@ -0,0 +1,257 @@
+use crate::commands::run::{build_llm, with_examples};
+use crate::core::config::load_input_redaction;
+use crate::core::pii::{redact_values, RedactionPolicy};
+use crate::core::policy::load_policies;
+use crate::core::retry::{RetryPolicy, RetryingProvider};
+use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::core::template::render;
+use crate::ui::progress::bar;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use futures::stream::{self, StreamExt};
+use llm::{chat::ChatMessage, LLMProvider};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+/// Delay before the first retry of a failed record, doubled after each attempt.
+const RETRY_BACKOFF_MS: u64 = 1000;
+
+/// A record of the input file: its line number and its variables.
+pub(super) type Record = (usize, BTreeMap<String, String>);
+
+/// Options of `batch`.
+pub struct BatchOptions<'a> {
+    pub backend: &'a str,
+    /// JSONL file with one object of variables per line.
+    pub input: &'a str,
+    /// JSONL file the results are appended to.
+    pub out: &'a str,
+    /// Records sent at the same time.
+    pub concurrency: usize,
+    /// Maximum requests per minute.
+    pub rate: Option<u32>,
+    /// Retries of a record failing with a transient error.
+    pub retries: u32,
+}
+
+/// One line of the results file.
+#[derive(Serialize, Deserialize)]
+pub(super) struct BatchResult {
+    /// Line of the record in the input file, starting at 1.
+    pub line: usize,
+    pub input: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub latency_ms: u64,
+}
+
+/// Run a prompt once per record of a JSONL file and append the results to another.
+///
+/// Records already answered in the results file are skipped, so an interrupted batch
+/// resumes where it stopped and a finished one only retries the records that failed.
+pub async fn run(ctx: &AppCtx, id: &str, opts: &BatchOptions<'_>) -> Result<(), String> {
+    if opts.concurrency == 0 {
+        return Err("--concurrency must be at least 1.".to_string());
+    }
+    if opts.rate == Some(0) {
+        return Err("--rate must be at least 1 request per minute.".to_string());
+    }
+    let pd = ctx.prompts().load(id)?;
+    let template = ctx.prompts().expand_attachments(id, &pd.content)?;
+    load_policies()?.check_content(id, &template)?;
+    ctx.prompts()
+        .check_provider(&parse_id(id).0, opts.backend)?;
+    let redaction = load_input_redaction()?;
+
+    let records = read_records(opts.input)?;
+    let done = answered_lines(opts.out)?;
+    let pending: Vec<Record> = records
+        .into_iter()
+        .filter(|(line, _)| !done.contains(line))
+        .collect();
+    if pending.is_empty() {
+        println!(
+            "{} Every record of {} already has a result in {}.",
+            style(OK).green().bold(),
+            opts.input,
+            opts.out
+        );
+        return Ok(());
+    }
+    if !done.is_empty() {
+        println!(
+            "{} Resuming: {} already done, {} to run.",
+            style(BULLET).green().bold(),
+            count_records(done.len()),
+            pending.len()
+        );
+    }
+
+    let llm = RetryingProvider::new(
+        build_llm(ctx, opts.backend)?,
+        RetryPolicy {
+            max_attempts: opts.retries + 1,
+            backoff_ms: RETRY_BACKOFF_MS,
+        },
+    );
+    let limiter = opts.rate.map(|rate| {
+        let mut ticks = interval(Duration::from_secs(60) / rate);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Mutex::new(ticks)
+    });
+
+    let mut out = open_results(opts.out)?;
+    let total = pending.len();
+    let progress = bar(total, "Running");
+    let mut failed = 0;
+    let mut results = stream::iter(pending)
+        .map(|(line, input)| {
+            let (llm, limiter) = (&llm, &limiter);
+            let (template, pd, redaction) = (&template, &pd, &redaction);
+            async move {
+                let prompt = render_record(template, pd, redaction.as_ref(), &input);
+                let start = Instant::now();
+                let answer = match prompt {
+                    Ok(prompt) => answer(llm, limiter, &prompt).await,
+                    Err(e) => Err(e),
+                };
+                let (output, error) = match answer {
+                    Ok(output) => (Some(output), None),
+                    Err(e) => (None, Some(e)),
+                };
+                BatchResult {
+                    line,
+                    input,
+                    output,
+                    error,
+                    latency_ms: start.elapsed().as_millis() as u64,
+                }
+            }
+        })
+        .buffer_unordered(opts.concurrency);
+
+    while let Some(result) = results.next().await {
+        if let Some(error) = &result.error {
+            failed += 1;
+            progress.suspend(|| eprintln!("{} line {}: {}", BULLET, result.line, error));
+            progress.set_message(format!("Running ({} failed)", failed));
+        }
+        let json = serde_json::to_string(&result).map_err(|e| format!("Serialize error: {}", e))?;
+        writeln!(out, "{}", json)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("Write error: {}", e))?;
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} failed. Run the same command again to retry them.",
+            failed,
+            count_records(total)
+        ));
+    }
+    println!(
+        "{} Ran {}, results in {}",
+        style(OK).green().bold(),
+        count_records(total),
+        opts.out
+    );
+    Ok(())
+}
+
+/// Renders the prompt of a record, redacting its values first when inputs are redacted.
+pub(super) fn render_record(
+    template: &str,
+    pd: &PromptData,
+    redaction: Option<&RedactionPolicy>,
+    input: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    let mut vars: HashMap<String, String> = input.clone().into_iter().collect();
+    if let Some(policy) = redaction {
+        redact_values(&mut vars, policy)?;
+    }
+    Ok(render(template, &with_examples(&vars, pd)))
+}
+
+/// "1 record", "2 records".
+pub(super) fn count_records(count: usize) -> String {
+    format!("{} record{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// Waits for the rate limiter, if any, then sends the prompt.
+async fn answer(
+    llm: &dyn LLMProvider,
+    limiter: &Option<Mutex<Interval>>,
+    prompt: &str,
+) -> Result<String, String> {
+    if let Some(limiter) = limiter {
+        limiter.lock().await.tick().await;
+    }
+    let messages = vec![ChatMessage::user().content(prompt).build()];
+    let response = llm.chat(&messages).await.map_err(|e| e.to_string())?;
+    Ok(response.text().unwrap_or_default())
+}
+
+/// Reads the records of a JSONL file with their line numbers. Values that aren't
+/// strings are kept as JSON text; blank lines are skipped.
+pub(super) fn read_records(path: &str) -> Result<Vec<Record>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut records = Vec::new();
+    for (i, text) in content.lines().enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let object: serde_json::Map<String, Value> = serde_json::from_str(text)
+            .map_err(|e| format!("{} line {}: expected a JSON object ({})", path, i + 1, e))?;
+        let vars = object
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => (key, text),
+                other => (key, other.to_string()),
+            })
+            .collect();
+        records.push((i + 1, vars));
+    }
+    if records.is_empty() {
+        return Err(format!("No records in {}.", path));
+    }
+    Ok(records)
+}
+
+/// Input lines that already have a successful result in the results file.
+fn answered_lines(path: &str) -> Result<HashSet<usize>, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(HashSet::new());
+    };
+    Ok(content
+        .lines()
+        .filter_map(|text| serde_json::from_str::<BatchResult>(text).ok())
+        .filter(|result| result.error.is_none())
+        .map(|result| result.line)
+        .collect())
+}
+
+/// Opens the results file for appending, ending a line cut short by an interruption.
+fn open_results(path: &str) -> Result<fs::File, String> {
+    let cut_short = fs::read(path).is_ok_and(|data| data.last().is_some_and(|b| *b != b'\n'));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Unable to open {}: {}", path, e))?;
+    if cut_short {
+        writeln!(file).map_err(|e| format!("Write error: {}", e))?;
+    }
+    Ok(file)
+}
@@ -0,0 +1,74 @@
+use crate::core::batch_jobs::{list_jobs, load_job, remote_status, save_job};
+use crate::core::config::load_openai_endpoint;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use chrono::Local;
+use console::style;
+
+/// Show the progress of a batch job, or list the submitted jobs with their last known status.
+pub async fn run(ctx: &AppCtx, job: Option<&str>) -> Result<(), String> {
+    let Some(id) = job else {
+        return list(ctx);
+    };
+    let mut job = load_job(&ctx.base_dir, &ctx.cipher, id)?
+        .ok_or_else(|| format!("Batch job '{}' not found.", id))?;
+    let endpoint = load_openai_endpoint(ctx, &job.backend)?;
+    let remote = remote_status(&endpoint, &job.id).await?;
+    if remote.status != job.status {
+        job.status = remote.status.clone();
+        save_job(&ctx.base_dir, &ctx.cipher, &job)?;
+    }
+
+    println!(
+        "{} {}",
+        style("Job").green().bold(),
+        style(&job.id).yellow()
+    );
+    println!("  Prompt:  {}", job.prompt);
+    println!("  Backend: {}", job.backend);
+    println!("  Input:   {}", job.input);
+    println!("  Status:  {}", remote.status);
+    let counts = &remote.request_counts;
+    if counts.total > 0 {
+        println!(
+            "  Records: {}/{} done, {} failed",
+            counts.completed + counts.failed,
+            counts.total,
+            counts.failed
+        );
+    }
+    if remote.status == "completed" {
+        println!(
+            "  Download the results with 'prompt-store batch fetch {} --out <file>'.",
+            job.id
+        );
+    }
+    Ok(())
+}
+
+fn list(ctx: &AppCtx) -> Result<(), String> {
+    let jobs = list_jobs(&ctx.base_dir, &ctx.cipher)?;
+    if jobs.is_empty() {
+        println!(
+            "{}",
+            style("No batch jobs yet. Submit one with 'prompt-store batch submit'.").yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", style("Batch jobs:").green().bold());
+    for job in jobs {
+        println!(
+            "  {} {}  {} ({} records, {}) at {}",
+            style(BULLET).green(),
+            style(&job.id).yellow(),
+            job.prompt,
+            job.records.len(),
+            job.status,
+            job.created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use super::run::{count_records, read_records, render_record};
+use crate::core::batch_jobs::{save_job, submit, BatchJob};
+use crate::core::config::{load_input_redaction, load_openai_endpoint};
+use crate::core::policy::load_policies;
+use crate::core::storage::{parse_id, AppCtx};
+use crate::ui::symbols::OK;
+use chrono::Utc;
+use console::style;
+
+/// Render a prompt for every record of a JSONL file and submit them as a provider batch job.
+pub async fn run(ctx: &AppCtx, id: &str, input: &str, backend: &str) -> Result<(), String> {
+    let endpoint = load_openai_endpoint(ctx, backend)?;
+    let pd = ctx.prompts().load(id)?;
+    let template = ctx.prompts().expand_attachments(id, &pd.content)?;
+    load_policies()?.check_content(id, &template)?;
+    ctx.prompts().check_provider(&parse_id(id).0, backend)?;
+    let redaction = load_input_redaction()?;
+
+    let records = read_records(input)?;
+    let prompts = records
+        .iter()
+        .map(|(line, vars)| {
+            render_record(&template, &pd, redaction.as_ref(), vars).map(|prompt| (*line, prompt))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let job_id = submit(&endpoint, &prompts).await?;
+    let job = BatchJob {
+        id: job_id,
+        prompt: id.to_string(),
+        backend: backend.to_string(),
+        input: input.to_string(),
+        created_at: Utc::now(),
+        records: records.into_iter().collect(),
+        status: "validating".to_string(),
+    };
+    save_job(&ctx.base_dir, &ctx.cipher, &job)?;
+
+    println!(
+        "{} Submitted {} as job {}",
+        style(OK).green().bold(),
+        count_records(job.records.len()),
+        style(&job.id).yellow()
+    );
+    println!(
+        "  Check it with 'prompt-store batch status {}', then download the results with 'prompt-store batch fetch {} --out <file>'.",
+        job.id, job.id
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use crate::commands::edit::save_with_backup;
+use crate::core::storage::{AppCtx, Canary};
+use crate::core::utils::editor;
+use crate::ui::symbols::OK;
+use chrono::Utc;
+use console::style;
+
+/// Write a new version of a prompt in the editor and roll it out as a canary to `percent`
+/// of the library runs, or change the share of an existing canary. With `abort`, drop
+/// the canary instead.
+pub fn run(ctx: &AppCtx, id: &str, percent: Option<u8>, abort: bool) -> Result<(), String> {
+    let mut pd = ctx.prompts().load(id)?;
+
+    if abort {
+        if pd.canary.take().is_none() {
+            return Err(format!("Prompt '{}' has no canary.", id));
+        }
+        save_with_backup(ctx, id, &mut pd)?;
+        println!(
+            "{} Dropped the canary of prompt '{}'.",
+            style(OK).green().bold(),
+            id
+        );
+        return Ok(());
+    }
+
+    let percent = percent.ok_or("Use --percent to set the share of runs served by the canary.")?;
+    if percent > 100 {
+        return Err(format!("Invalid percentage {}: use 0 to 100.", percent));
+    }
+
+    let current = pd
+        .canary
+        .as_ref()
+        .map_or(&pd.content, |canary| &canary.content);
+    let content = match editor()
+        .edit(current)
+        .map_err(|e| format!("Editor error: {}", e))?
+    {
+        Some(edited) if edited.trim().is_empty() => {
+            println!("{}", style("Empty canary discarded.").yellow());
+            return Ok(());
+        }
+        Some(edited) => edited,
+        // Closing the editor without saving keeps the current canary, if any.
+        None => match &pd.canary {
+            Some(canary) => canary.content.clone(),
+            None => {
+                println!("{}", style("No canary written. Nothing to save.").yellow());
+                return Ok(());
+            }
+        },
+    };
+    if content == pd.content {
+        return Err("The canary is identical to the current content.".to_string());
+    }
+
+    let created_at = pd.canary.as_ref().map_or_else(Utc::now, |c| c.created_at);
+    pd.canary = Some(Canary {
+        content,
+        percent,
+        created_at,
+    });
+    save_with_backup(ctx, id, &mut pd)?;
+    println!(
+        "{} The canary of prompt '{}' now serves {}% of library runs.",
+        style(OK).green().bold(),
+        id,
+        percent
+    );
+    println!(
+        "Promote it with `prompt-store promote {}` once it proves itself.",
+        id
+    );
+    Ok(())
+}
@@ -1,29 +1,32 @@
 use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::core::utils::editor;
+use crate::ui::symbols::BULLET;
 use crate::ui::theme;
-use aes_gcm::aead::{Aead, AeadCore, OsRng};
-use aes_gcm::Aes256Gcm;
-use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
 use console::style;
-use dialoguer::{Editor, Input};
+use dialoguer::Input;
+use std::collections::BTreeMap;
 use std::fs;
 
 /// Add a new prompt step to an existing chain.
 pub fn run(ctx: &AppCtx, chain_id: &str) -> Result<(), String> {
-    let (workspace, local_id) = parse_id(chain_id);
-    let chain_dir = ctx.workspaces_dir.join(workspace).join(&local_id);
+    let prompts = ctx.prompts();
+    let (_workspace, local_id) = parse_id(chain_id);
+    let chain_dir = prompts.chain_dir(chain_id);
 
     if !chain_dir.is_dir() {
         return Err(format!("Chain with ID '{}' not found.", chain_id));
     }
 
     let mut max_step = 0;
-    for entry in fs::read_dir(&chain_dir).map_err(|e| format!("Read error: {}", e))? {
-        if let Ok(entry) = entry {
-            if let Some(stem) = entry.path().file_stem().and_then(|s| s.to_str()) {
-                if let Ok(num) = stem.parse::<u32>() {
-                    if num > max_step {
-                        max_step = num;
-                    }
+    for entry in fs::read_dir(&chain_dir)
+        .map_err(|e| format!("Read error: {}", e))?
+        .flatten()
+    {
+        if let Some(stem) = entry.path().file_stem().and_then(|s| s.to_str()) {
+            if let Ok(num) = stem.parse::<u32>() {
+                if num > max_step {
+                    max_step = num;
                 }
             }
         }
@@ -51,7 +54,7 @@ pub fn run(ctx: &AppCtx, chain_id: &str) -> Result<(), String> {
         .filter(|s| !s.is_empty())
         .collect();
 
-    let content = Editor::new()
+    let content = editor()
         .edit("Enter prompt content. Use {{var}} for variables.")
         .map_err(|e| format!("Editor error: {}", e))?
         .unwrap_or_default();
@@ -61,28 +64,22 @@ pub fn run(ctx: &AppCtx, chain_id: &str) -> Result<(), String> {
         id: prompt_id,
         title: prompt_title.clone(),
         content,
+        translations: BTreeMap::new(),
+        variants: BTreeMap::new(),
+        canary: None,
         tags,
+        description: None,
         schema: None, // Schemas are not defined for chain sub-prompts in this flow
+        examples: Vec::new(),
+        updated_at: Some(Utc::now()),
+        attachments: Vec::new(),
     };
 
-    let prompt_path = chain_dir.join(format!("{}.prompt", next_step));
-    let json = serde_json::to_vec(&pd).map_err(|e| format!("Serialize error: {}", e))?;
-
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = ctx
-        .cipher
-        .encrypt(&nonce, json.as_ref())
-        .map_err(|_| "Encrypt error")?;
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded = general_purpose::STANDARD.encode(&out);
-
-    fs::write(prompt_path, encoded).map_err(|e| format!("Write error: {}", e))?;
+    prompts.save(&format!("{}/{}", chain_id, next_step), &pd)?;
 
     println!(
         "{} Added prompt '{}' to chain '{}'.",
-        style("•").green().bold(),
+        style(BULLET).green().bold(),
         style(prompt_title).cyan(),
         style(chain_id).yellow()
     );
@@ -1,6 +1,10 @@
 pub mod add_step;
 pub mod edit;
 pub mod new;
+pub mod presets;
 pub mod rm_step;
+pub mod steps;
+pub mod tag;
+pub mod templates;
 pub mod run;
 pub mod import;
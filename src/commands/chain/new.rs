@@ -1,13 +1,11 @@
 use crate::core::storage::{AppCtx, ChainData, PromptData};
-use crate::core::utils::{ensure_dir, new_id};
+use crate::core::utils::{editor, new_id};
+use crate::ui::symbols::{BRANCH, BULLET, OK};
 use crate::ui::theme;
-use aes_gcm::aead::{Aead, AeadCore, OsRng};
-use aes_gcm::Aes256Gcm;
-use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
 use console::style;
-use dialoguer::{Confirm, Editor, Input};
-use std::fs;
-use std::path::Path;
+use dialoguer::{Confirm, Input};
+use std::collections::BTreeMap;
 
 /// Creates a new prompt chain interactively in the default workspace.
 pub fn run(ctx: &AppCtx) -> Result<(), String> {
@@ -22,22 +20,20 @@ pub fn run(ctx: &AppCtx) -> Result<(), String> {
         return Err("Title cannot be empty".to_string());
     }
 
-    let chain_id = new_id(&default_workspace);
-    let chain_dir = default_workspace.join(&chain_id);
-    ensure_dir(&chain_dir)?;
+    let chain_id = new_id(&default_workspace, &title)?;
+    let prompts = ctx.prompts();
 
     let chain_data = ChainData {
         id: chain_id.clone(),
         title: title.clone(),
+        tags: Vec::new(),
     };
 
-    let chain_meta_path = chain_dir.join("chain.meta");
-    let json = serde_json::to_vec(&chain_data).map_err(|e| format!("Serialize error: {}", e))?;
-    encrypt_and_write(&ctx.cipher, &chain_meta_path, &json)?;
+    prompts.save_chain(&chain_id, &chain_data)?;
 
     println!(
         "\n{} Chain '{}' created with ID {}.",
-        style("•").green().bold(),
+        style(BULLET).green().bold(),
         style(&title).cyan(),
         style(&chain_id).yellow()
     );
@@ -70,51 +66,37 @@ pub fn run(ctx: &AppCtx) -> Result<(), String> {
             .filter(|s| !s.is_empty())
             .collect();
 
-        let content = Editor::new()
+        let content = editor()
             .edit("Enter prompt content. Use {{var}} for variables.")
             .map_err(|e| format!("Editor error: {}", e))?
             .unwrap_or_default();
 
         let prompt_id = format!("{}/{}", &chain_id, step_counter);
         let pd = PromptData {
-            id: prompt_id,
+            id: prompt_id.clone(),
             title: prompt_title.clone(),
             content,
+            translations: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            canary: None,
             tags,
+            description: None,
             schema: None, // Schemas are not defined for chain sub-prompts in this flow
+            examples: Vec::new(),
+            updated_at: Some(Utc::now()),
+            attachments: Vec::new(),
         };
 
-        let prompt_path = chain_dir.join(format!("{}.prompt", step_counter));
-        let json = serde_json::to_vec(&pd).map_err(|e| format!("Serialize error: {}", e))?;
-        encrypt_and_write(&ctx.cipher, &prompt_path, &json)?;
+        prompts.save(&prompt_id, &pd)?;
 
         println!(
             "  {} Added prompt '{}'",
-            style("└─").green(),
+            style(BRANCH).green(),
             style(prompt_title).cyan()
         );
         step_counter += 1;
     }
 
-    println!("\n{} Chain '{}' saved.", style("✔").green().bold(), title);
+    println!("\n{} Chain '{}' saved.", style(OK).green().bold(), title);
     Ok(())
 }
-
-fn encrypt_and_write(
-    cipher: &Aes256Gcm,
-    path: &Path,
-    data: &[u8],
-) -> Result<(), String> {
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = cipher
-        .encrypt(&nonce, data)
-        .map_err(|_| "Encrypt error".to_string())?;
-
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded = general_purpose::STANDARD.encode(&out);
-
-    fs::write(path, encoded).map_err(|e| format!("Write error: {}", e))?;
-    Ok(())
-}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the variable presets saved with a YAML chain (`chain run --save-preset`), or
+/// delete one.
+pub fn run(ctx: &AppCtx, id: &str, delete: Option<&str>) -> Result<(), String> {
+    let repo = ctx.prompts();
+    if !repo.chain_definition_path(id).exists() {
+        return Err(format!("Chain with ID '{}' not found.", id));
+    }
+    let mut presets = repo.load_chain_presets(id)?;
+    if let Some(name) = delete {
+        if presets.remove(name).is_none() {
+            return Err(format!("Chain '{}' has no preset '{}'", id, name));
+        }
+        repo.save_chain_presets(id, &presets)?;
+        println!(
+            "{} Preset '{}' deleted.",
+            style(BULLET).green().bold(),
+            name
+        );
+        return Ok(());
+    }
+    if presets.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "No presets. Save one with 'prompt-store chain run {} --var key=value --save-preset <name>'.",
+                id
+            ))
+            .yellow()
+        );
+        return Ok(());
+    }
+    for (name, vars) in &presets {
+        println!("  {} {}", style(BULLET).green(), style(name).yellow());
+        for (key, value) in vars {
+            println!("      {}={}", key, value);
+        }
+    }
+    Ok(())
+}
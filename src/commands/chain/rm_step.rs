@@ -1,10 +1,14 @@
+use crate::cli::RemoveArgs;
+use crate::core::journal::JournalEntry;
 use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
 use console::style;
-use dialoguer::Confirm;
 use std::fs;
 
-/// Remove a step from a chain.
-pub fn run(ctx: &AppCtx, step_id: &str) -> Result<(), String> {
+/// Remove a step from a chain, addressed by number or title.
+pub fn run(ctx: &AppCtx, step_id: &str, args: RemoveArgs) -> Result<(), String> {
+    let step_id = &ctx.prompts().resolve_step(step_id)?;
     let Some((chain_id, step_num_str)) = step_id.split_once('/') else {
         return Err("Invalid step ID format. Use 'chain_id/step_number'.".to_string());
     };
@@ -16,17 +20,20 @@ pub fn run(ctx: &AppCtx, step_id: &str) -> Result<(), String> {
             step_num_str, chain_id
         ));
     }
+    let step = ctx.prompts().load(step_id)?;
+    println!("Step {}: {}", style(step_id).yellow(), step.title);
 
-    if Confirm::new()
-        .with_prompt(format!("Are you sure you want to delete step {}?", step_id))
-        .default(false)
-        .interact()
-        .unwrap_or(false)
-    {
-        fs::remove_file(path).map_err(|e| format!("Failed to delete step: {}", e))?;
-        println!("{} Step '{}' removed.", style("•").green().bold(), step_id);
-    } else {
-        println!("Deletion cancelled.");
+    if !confirm_removal(args, &format!("step {}", step_id))? {
+        return Ok(());
     }
+    let mut journal = JournalEntry::new(format!("chain rm-step {}", step_id));
+    journal.save(ctx, &path)?;
+    fs::remove_file(path).map_err(|e| format!("Failed to delete step: {}", e))?;
+    journal.commit(ctx)?;
+    println!(
+        "{} Step '{}' removed.",
+        style(BULLET).green().bold(),
+        step_id
+    );
     Ok(())
 }
@@ -1,143 +1,149 @@
-use crate::api::PromptStore;
-use crate::core::config::load_llm_registry;
-use crate::core::storage::{parse_id, AppCtx};
-use aes_gcm::aead::Aead;
-use aes_gcm::Nonce;
-use base64::{engine::general_purpose, Engine as _};
+use crate::api::{PromptStore, StoreError};
+use crate::core::paths::config_file;
+use crate::core::runs::{save_run, RunRecord, RunStep};
+use crate::core::vars::VarSources;
+use crate::ui::symbols::{BULLET, OK};
+use chrono::Utc;
 use console::style;
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::fs;
-
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-enum StepDefinition {
-    Sequential(Step),
-    Parallel { parallel: Vec<Step> },
-}
-
-#[derive(Deserialize, Debug)]
-struct Step {
-    id: String,
-    prompt: String,
-    provider: String,
-    #[serde(rename = "if", default)]
-    condition: Option<Condition>,
-    #[serde(default)]
-    on_error: Option<FallbackStep>,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-struct Condition {
-    variable: String,
-    contains: Option<String>,
-    equals: Option<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct FallbackStep {
-    prompt: String,
-    // provider field can be added here if needed, for now assumes same provider
-}
-
-#[derive(Deserialize, Debug)]
-struct ChainFile {
-    #[serde(default)]
-    vars: HashMap<String, String>,
-    steps: Vec<StepDefinition>,
+use log::info;
+use std::path::PathBuf;
+
+/// Options of `chain run`.
+pub struct ChainRunOptions<'a> {
+    /// Environment profile providing default variables.
+    pub env_profile: Option<&'a str>,
+    /// Language of the step prompt translations to use.
+    pub lang: Option<&'a str>,
+    /// Preset of variables saved with the chain.
+    pub preset: Option<&'a str>,
+    /// JSON or YAML file of variables, instead of `$PS_VARS_FILE`.
+    pub vars_file: Option<&'a str>,
+    /// Read a JSON object of variables from stdin.
+    pub vars_stdin: bool,
+    /// Saves the variables given on the command line as a preset of this name.
+    pub save_preset: Option<&'a str>,
 }
 
-/// Run a stored prompt chain.
-pub async fn run(ctx: &AppCtx, id: &str, vars_override: &[String]) -> Result<(), String> {
-    let (workspace, local_id) = parse_id(id);
-    let chain_path = ctx
-        .workspaces_dir
-        .join(workspace)
-        .join("chains")
-        .join(format!("{}.chain", local_id));
-
-    if !chain_path.exists() {
-        return Err(format!("Chain with ID '{}' not found.", id));
+/// Run a stored prompt chain and record its transcript.
+///
+/// Variables are resolved like `ChainRunner::vars_auto()` does (see `core::vars`), from
+/// lowest to highest precedence: the defaults of the chain, the environment profile, the
+/// preset, the vars file, `PS_VAR_*` environment variables, stdin and `--var`.
+pub async fn run(
+    id: &str,
+    vars_override: &[String],
+    opts: &ChainRunOptions<'_>,
+) -> Result<(), String> {
+    let store = PromptStore::init().map_err(|e| e.to_string())?;
+    if store
+        .config_registry()
+        .map_err(|e| e.to_string())?
+        .backends
+        .is_empty()
+    {
+        println!(
+            "{}",
+            style(format!(
+                "Warning: No LLM providers configured in {}. Chain execution may fail.",
+                config_file()?.display()
+            ))
+            .yellow()
+        );
     }
 
-    let encrypted_b64 = fs::read_to_string(&chain_path).map_err(|e| e.to_string())?;
-    let encrypted_bytes = general_purpose::STANDARD
-        .decode(encrypted_b64.trim())
-        .map_err(|e| e.to_string())?;
-    let (nonce, ciphertext) = encrypted_bytes.split_at(12);
-    let yaml_bytes = ctx
-        .cipher
-        .decrypt(Nonce::from_slice(nonce), ciphertext)
-        .map_err(|_| "Failed to decrypt chain file. Check master password.".to_string())?;
-
-    let mut chain_def: ChainFile =
-        serde_yaml::from_slice(&yaml_bytes).map_err(|e| format!("Failed to parse chain file: {}", e))?;
+    let mut runner = store.stored_chain(id).map_err(|e| match e {
+        StoreError::NotFound(_) => format!("Chain with ID '{}' not found.", id),
+        e => e.to_string(),
+    })?;
 
-    // Override variables from CLI
-    for var_pair in vars_override {
-        if let Some((key, value)) = var_pair.split_once('=') {
-            chain_def.vars.insert(key.to_string(), value.to_string());
-        }
+    if let Some(profile) = opts.env_profile {
+        runner = runner.env_profile(profile);
     }
-
-    let registry = load_llm_registry()?;
-    if registry.backends.is_empty() {
-        println!("{}", style("Warning: No LLM providers configured in ~/.prompt-store/config.toml. Chain execution may fail.").yellow());
+    if let Some(preset) = opts.preset {
+        runner = runner.preset(preset);
+    }
+    let mut sources = VarSources::auto();
+    if let Some(path) = opts.vars_file {
+        sources.files = vec![PathBuf::from(path)];
+    }
+    sources.stdin = opts.vars_stdin;
+    runner = runner.vars_from(&sources).map_err(|e| e.to_string())?;
+    if let Some(lang) = opts.lang {
+        runner = runner.lang(lang);
     }
-    
-    let store = PromptStore::init().map_err(|e| e.to_string())?;
-    let mut runner = store.chain(&registry).vars(chain_def.vars);
-
-    for step_def in chain_def.steps {
-        runner = match step_def {
-            StepDefinition::Sequential(step) => {
-                let runner_with_step = if let Some(cond) = step.condition {
-                    runner.step_if(&step.id, &step.prompt, move |ctx| check_condition(ctx, &cond))
-                } else {
-                    runner.step(&step.id, &step.prompt)
-                };
-
-                let runner_with_fallback = if let Some(fallback) = step.on_error {
-                    runner_with_step.on_error_stored(&fallback.prompt)
-                } else {
-                    runner_with_step
-                };
-                
-                runner_with_fallback.with_provider(&step.provider)
-            }
-            StepDefinition::Parallel { parallel } => {
-                runner.parallel(|group| {
-                    let mut current_group = group;
-                    for step in parallel {
-                        let step_id = step.id.clone();
-                        let prompt = step.prompt.clone();
-                        let provider = step.provider.clone();
-
-                        let group_with_step = if let Some(cond) = step.condition {
-                            current_group.step_if(&step_id, &prompt, move |ctx| check_condition(ctx, &cond))
-                        } else {
-                            current_group.step(&step_id, &prompt)
-                        };
-
-                        let group_with_fallback = if let Some(fallback) = step.on_error {
-                             group_with_step.on_error_stored(&fallback.prompt)
-                        } else {
-                            group_with_step
-                        };
 
-                        current_group = group_with_fallback.with_provider(&provider);
-                    }
-                    current_group
-                })
-            }
-        };
+    // Override variables from CLI
+    let vars: Vec<(&str, &str)> = vars_override
+        .iter()
+        .filter_map(|var_pair| var_pair.split_once('='))
+        .collect();
+    if let Some(name) = opts.save_preset {
+        save_preset(&store, id, name, &vars)?;
     }
+    let names: Vec<&str> = vars.iter().map(|(name, _)| *name).collect();
+    info!("running chain '{}' (--var {})", id, names.join(", "));
+    runner = runner.vars(vars);
 
     println!("Executing chain '{}'...", style(id).yellow());
-    match runner.run().await {
-        Ok(output) => {
-            println!("{}", style("✔ Chain execution complete.").green());
+    let started_at = Utc::now();
+    match runner.run_with_metadata().await {
+        Ok((output, metadata)) => {
+            println!(
+                "{} {}",
+                style(OK).green(),
+                style("Chain execution complete.").green()
+            );
             println!("{:#?}", output);
+            if let Some(step) = &metadata.finished_by {
+                println!(
+                    "  {} finished early after step {}",
+                    style(BULLET).green(),
+                    style(step).yellow()
+                );
+            }
+            let mut refinements: Vec<_> = metadata.refinements.into_iter().collect();
+            refinements.sort_by(|a, b| a.0.cmp(&b.0));
+            for (draft, iterations) in refinements {
+                println!(
+                    "  {} {} refined in {} iteration(s)",
+                    style(BULLET).green(),
+                    draft,
+                    iterations.len()
+                );
+            }
+            let mut served_by: Vec<_> = metadata.served_by.into_iter().collect();
+            served_by.sort();
+            for (step, provider) in served_by {
+                println!(
+                    "  {} {} served by {}",
+                    style(BULLET).green(),
+                    step,
+                    style(provider).yellow()
+                );
+            }
+            let record = RunRecord {
+                chain: id.to_string(),
+                started_at,
+                steps: metadata
+                    .steps
+                    .into_iter()
+                    .map(|step| RunStep {
+                        key: step.key,
+                        prompt: step.rendered,
+                        provider: step.provider,
+                        latency_ms: step.latency.as_millis() as u64,
+                        output: step.output,
+                    })
+                    .collect(),
+            };
+            let run_id = save_run(&store.ctx.base_dir, &store.ctx.cipher, &record)?;
+            info!("chain '{}' complete, saved as run {}", id, run_id);
+            println!(
+                "  {} Transcript saved as run {} (see 'prompt-store runs export {}')",
+                style(BULLET).green(),
+                style(&run_id).yellow(),
+                run_id
+            );
         }
         Err(e) => return Err(format!("Chain execution failed: {}", e)),
     }
@@ -145,14 +151,30 @@ pub async fn run(ctx: &AppCtx, id: &str, vars_override: &[String]) -> Result<(),
     Ok(())
 }
 
-fn check_condition(ctx: &HashMap<String, String>, cond: &Condition) -> bool {
-    if let Some(val) = ctx.get(&cond.variable) {
-        if let Some(expected) = &cond.equals {
-            return val == expected;
-        }
-        if let Some(substring) = &cond.contains {
-            return val.contains(substring);
-        }
+/// Saves variables as a preset of a chain, replacing a preset of the same name.
+fn save_preset(
+    store: &PromptStore,
+    id: &str,
+    name: &str,
+    vars: &[(&str, &str)],
+) -> Result<(), String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid preset name '{}'", name));
     }
-    false
-}
\ No newline at end of file
+    let repo = store.ctx.prompts();
+    let mut presets = repo.load_chain_presets(id)?;
+    presets.insert(
+        name.to_string(),
+        vars.iter()
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect(),
+    );
+    repo.save_chain_presets(id, &presets)?;
+    println!(
+        "{} Saved {} variables as preset '{}'.",
+        style(BULLET).green(),
+        vars.len(),
+        style(name).yellow()
+    );
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use crate::commands::uses::chain_references;
+use crate::core::storage::{full_id, parse_id, AppCtx};
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the steps of a chain. Steps of an interactive chain are shown with the ID and
+/// title they can be addressed by (`chain/2` or `chain/"Extract Topic"`); steps of a
+/// YAML chain with the prompt they run.
+pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
+    let repo = ctx.prompts();
+    if repo.chain_dir(id).join("chain.meta").exists() {
+        let chain = repo.load_chain(id)?;
+        let mut steps = repo.chain_steps(id)?;
+        // Numbered files sort as text, so `10` would come before `2`.
+        steps.sort_by_key(|s| {
+            let number = s.full_id.rsplit('/').next().unwrap_or_default();
+            number.parse::<usize>().unwrap_or(usize::MAX)
+        });
+        println!("{} {}", style("Chain:").green().bold(), chain.title);
+        if steps.is_empty() {
+            println!("{}", style("No steps.").yellow());
+        }
+        for step in &steps {
+            println!(
+                "  {} {} - {}",
+                style(BULLET).green(),
+                style(&step.full_id).yellow(),
+                step.data.title
+            );
+        }
+        return Ok(());
+    }
+    if repo.chain_definition_path(id).exists() {
+        let (workspace, local_id) = parse_id(id);
+        let chain = full_id(&workspace, &local_id);
+        let steps: Vec<_> = chain_references(&repo)?
+            .into_iter()
+            .filter(|r| r.chain == chain)
+            .collect();
+        println!("{} {} (YAML chain)", style("Chain:").green().bold(), id);
+        if steps.is_empty() {
+            println!("{}", style("No steps referencing prompts.").yellow());
+        }
+        for r in &steps {
+            println!(
+                "  {} step {}: {} - prompt {}",
+                style(BULLET).green(),
+                r.step,
+                style(&r.step_id).yellow(),
+                r.prompt
+            );
+        }
+        return Ok(());
+    }
+    Err(format!("Chain with ID '{}' not found.", id))
+}
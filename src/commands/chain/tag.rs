@@ -0,0 +1,29 @@
+use crate::commands::tag::{apply_changes, removes_tags};
+use crate::core::journal::JournalEntry;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Modify tags (+/-) on a chain.
+pub fn run(ctx: &AppCtx, chain_id: &str, changes: &[String]) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    if !prompts.chain_dir(chain_id).is_dir() {
+        return Err(format!("Chain with ID '{}' not found.", chain_id));
+    }
+    let mut chain_data = prompts.load_chain(chain_id).map_err(|_| {
+        format!(
+            "Chain metadata for '{}' is missing or unreadable.",
+            chain_id
+        )
+    })?;
+
+    apply_changes(&mut chain_data.tags, changes);
+    let mut journal = JournalEntry::new(format!("chain tag {} {}", chain_id, changes.join(" ")));
+    if removes_tags(changes) {
+        journal.save(ctx, &prompts.chain_dir(chain_id).join("chain.meta"))?;
+    }
+    prompts.save_chain(chain_id, &chain_data)?;
+    journal.commit(ctx)?;
+    println!("{} tags updated", style(BULLET).green().bold());
+    Ok(())
+}
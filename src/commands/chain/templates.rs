@@ -0,0 +1,167 @@
+//! Built-in chain templates of `chain new --template`, shipped with the binary so they
+//! work offline.
+//!
+//! A template creates a YAML chain definition and one prompt per step, under
+//! `<chain id>/<step id>`. The prompts hold placeholder instructions to adapt with
+//! `prompt-store edit`; the output of each step is available to the next ones as
+//! `{{<step id>}}`.
+
+use crate::cli::ChainTemplate;
+use crate::core::storage::{AppCtx, PromptData};
+use crate::core::utils::new_id;
+use crate::ui::symbols::{BRANCH, OK};
+use chrono::Utc;
+use console::style;
+use std::collections::BTreeMap;
+
+struct Template {
+    title: &'static str,
+    /// `(name, description)` of the variables the chain requires.
+    vars: &'static [(&'static str, &'static str)],
+    steps: &'static [TemplateStep],
+}
+
+struct TemplateStep {
+    id: &'static str,
+    title: &'static str,
+    content: &'static str,
+}
+
+fn template(kind: ChainTemplate) -> Template {
+    match kind {
+        ChainTemplate::SummarizeAndCritique => Template {
+            title: "Summarize and critique",
+            vars: &[("text", "The text to summarize")],
+            steps: &[
+                TemplateStep {
+                    id: "summary",
+                    title: "Summarize",
+                    content: "Summarize the following text in [5 bullet points] for [an audience of engineers].\n\n{{text}}",
+                },
+                TemplateStep {
+                    id: "critique",
+                    title: "Critique the summary",
+                    content: "Compare this summary with the original text. List anything missing, inaccurate or misleading, then give a corrected summary.\n\nSummary:\n{{summary}}\n\nOriginal text:\n{{text}}",
+                },
+            ],
+        },
+        ChainTemplate::ExtractStructuredData => Template {
+            title: "Extract structured data",
+            vars: &[("document", "The document to extract the data from")],
+            steps: &[
+                TemplateStep {
+                    id: "extraction",
+                    title: "Extract fields",
+                    content: "Extract the following fields from the document and answer with a JSON object only:\n- [name]: [description]\n- [date]: [format, e.g. YYYY-MM-DD]\n\nUse null for fields that are not in the document.\n\n{{document}}",
+                },
+                TemplateStep {
+                    id: "validation",
+                    title: "Check the extraction",
+                    content: "Check this JSON against the document: fix values that don't match it and set to null those it doesn't state. Answer with the corrected JSON object only.\n\nJSON:\n{{extraction}}\n\nDocument:\n{{document}}",
+                },
+            ],
+        },
+        ChainTemplate::TranslateAndGlossaryCheck => Template {
+            title: "Translate and glossary check",
+            vars: &[
+                ("text", "The text to translate"),
+                ("language", "The language to translate into"),
+                ("glossary", "Terms and their required translations, one per line"),
+            ],
+            steps: &[
+                TemplateStep {
+                    id: "translation",
+                    title: "Translate",
+                    content: "Translate the following text into {{language}}, keeping [a formal tone] and the formatting of the original.\n\n{{text}}",
+                },
+                TemplateStep {
+                    id: "review",
+                    title: "Check the glossary",
+                    content: "Check that this translation into {{language}} uses the required translation of every term of the glossary. Answer with the corrected translation only.\n\nGlossary:\n{{glossary}}\n\nTranslation:\n{{translation}}",
+                },
+            ],
+        },
+    }
+}
+
+/// Creates a chain from a built-in template in the default workspace, as `id` or an ID
+/// generated from the title of the template.
+pub fn run(ctx: &AppCtx, kind: ChainTemplate, id: Option<&str>) -> Result<(), String> {
+    let template = template(kind);
+    let repo = ctx.prompts();
+    let chain_id = match id {
+        Some(id) => {
+            if id.contains(['/', ':']) {
+                return Err(format!("'{}' is not a valid chain ID.", id));
+            }
+            if repo.chain_definition_path(id).exists() || repo.chain_dir(id).exists() {
+                return Err(format!(
+                    "A chain with ID '{}' already exists in the default workspace.",
+                    id
+                ));
+            }
+            id.to_string()
+        }
+        None => new_id(&ctx.workspaces_dir.join("default"), template.title)?,
+    };
+
+    let mut yaml = String::new();
+    if !template.vars.is_empty() {
+        yaml.push_str("vars:\n");
+        for (name, description) in template.vars {
+            yaml.push_str(&format!(
+                "  {}:\n    required: true\n    description: {}\n",
+                name, description
+            ));
+        }
+    }
+    yaml.push_str("steps:\n");
+    for step in template.steps {
+        let prompt_id = format!("{}/{}", chain_id, step.id);
+        let pd = PromptData {
+            id: prompt_id.clone(),
+            title: step.title.to_string(),
+            content: step.content.to_string(),
+            translations: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            canary: None,
+            tags: Vec::new(),
+            description: None,
+            schema: None,
+            examples: Vec::new(),
+            updated_at: Some(Utc::now()),
+            attachments: Vec::new(),
+        };
+        repo.save(&prompt_id, &pd)?;
+        yaml.push_str(&format!("  - id: {}\n    prompt: {}\n", step.id, prompt_id));
+    }
+    repo.save_chain_definition(&chain_id, &yaml)
+        .map_err(|e| format!("Failed to write encrypted chain file: {}", e))?;
+
+    println!(
+        "{} Chain '{}' created with ID {}.",
+        style(OK).green().bold(),
+        style(template.title).cyan(),
+        style(&chain_id).yellow()
+    );
+    println!("Edit the placeholders in [brackets] of its prompts:");
+    for step in template.steps {
+        println!(
+            "  {} prompt-store edit {}/{}",
+            style(BRANCH).green(),
+            chain_id,
+            step.id
+        );
+    }
+    let vars: Vec<String> = template
+        .vars
+        .iter()
+        .map(|(name, _)| format!("--var {}=...", name))
+        .collect();
+    println!(
+        "Then run it with: prompt-store chain run {} {}",
+        chain_id,
+        vars.join(" ")
+    );
+    Ok(())
+}
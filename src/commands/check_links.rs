@@ -0,0 +1,88 @@
+use crate::commands::uses::chain_references;
+use crate::core::storage::{full_id, parse_id, AppCtx};
+use crate::ui::symbols::{BULLET, ERR, OK};
+use console::style;
+
+/// Report the chain references that no longer resolve to a prompt, and those whose
+/// title matches several prompts. Fails when a reference is dangling, for use in scripts.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let repo = ctx.prompts();
+    let refs = chain_references(&repo)?;
+    let prompts: Vec<(String, String)> = repo
+        .list_all()?
+        .into_iter()
+        .map(|p| (p.full_id, p.data.title.to_lowercase()))
+        .collect();
+
+    let mut dangling = 0;
+    let mut ambiguous = 0;
+    for r in &refs {
+        // Resolved like `chain run` does: by ID first, then by title.
+        let (scope, query) = match r.prompt.split_once("::") {
+            Some(_) => {
+                let (workspace, query) = parse_id(&r.prompt);
+                (Some(workspace), query)
+            }
+            None => (None, r.prompt.clone()),
+        };
+        let id = match &scope {
+            Some(workspace) => full_id(workspace, &query),
+            None => query.clone(),
+        };
+        if repo.exists(&id) {
+            continue;
+        }
+        let query = query.to_lowercase();
+        let matches: Vec<&str> = prompts
+            .iter()
+            .filter(|(id, title)| {
+                *title == query && scope.as_ref().is_none_or(|ws| parse_id(id).0 == *ws)
+            })
+            .map(|(id, _)| id.as_str())
+            .collect();
+        let location = format!("{} (step {}: {})", r.chain, r.step, r.step_id);
+        match matches.len() {
+            0 => {
+                dangling += 1;
+                println!(
+                    "  {} {} references '{}', which does not exist",
+                    style(ERR).red(),
+                    style(location).yellow(),
+                    r.prompt
+                );
+            }
+            1 => {}
+            _ => {
+                ambiguous += 1;
+                println!(
+                    "  {} {} references '{}', a title shared by {}",
+                    style(BULLET).yellow(),
+                    style(location).yellow(),
+                    r.prompt,
+                    matches.join(", ")
+                );
+            }
+        }
+    }
+
+    if dangling > 0 {
+        return Err(format!(
+            "{} of {} chain references are dangling.",
+            dangling,
+            refs.len()
+        ));
+    }
+    if ambiguous > 0 {
+        println!(
+            "{} ambiguous reference(s); they resolve through `[lookup] resolution` in config.toml, or fail. Reference those prompts by ID to be sure.",
+            ambiguous
+        );
+    } else {
+        println!(
+            "{} All {} chain references resolve.",
+            style(OK).green(),
+            refs.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,383 @@
+use crate::cli::ReportFormat;
+use crate::commands::run::{build_llm, with_examples};
+use crate::commands::runs::export::escape;
+use crate::core::config::load_input_redaction;
+use crate::core::pii::redact_values;
+use crate::core::policy::load_policies;
+use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::core::template::render;
+use crate::ui::symbols::BULLET;
+use console::style;
+use llm::{chat::ChatMessage, LLMProvider};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// Which version a judge preferred for a case.
+#[derive(Clone, Copy, PartialEq)]
+enum Verdict {
+    From,
+    To,
+    Tie,
+    /// The judge's answer did not start with A, B or TIE.
+    Unclear,
+}
+
+/// The outputs of both versions for one set of variables.
+struct CaseResult {
+    vars: BTreeMap<String, String>,
+    from_output: String,
+    to_output: String,
+    judgement: Option<(Verdict, String)>,
+}
+
+/// What the report is about.
+struct Comparison<'a> {
+    id: &'a str,
+    title: &'a str,
+    from: &'a str,
+    to: &'a str,
+    backend: &'a str,
+    judge: Option<&'a str>,
+    cases: Vec<CaseResult>,
+}
+
+/// Options of `compare-versions`.
+pub struct CompareOptions<'a> {
+    pub backend: &'a str,
+    /// YAML list of variable maps, one per case.
+    pub vars_file: Option<&'a str>,
+    /// Backend asked which output is better for each case.
+    pub judge: Option<&'a str>,
+    pub format: ReportFormat,
+    pub out: Option<&'a str>,
+}
+
+/// Run two versions of a prompt on the same inputs and report their outputs side by
+/// side, with the verdict of a judge model when one is given.
+///
+/// Versions are named as in `history`: `v1` is the oldest backup and the current content
+/// is the last version, also named `current`. A backup timestamp works too.
+pub async fn run(
+    ctx: &AppCtx,
+    id: &str,
+    from: &str,
+    to: &str,
+    opts: &CompareOptions<'_>,
+) -> Result<(), String> {
+    let backups = ctx.prompts().list_backups(id)?;
+    let from_pd = load_version(ctx, id, from, &backups)?;
+    let to_pd = load_version(ctx, id, to, &backups)?;
+    let from_template = ctx.prompts().expand_attachments(id, &from_pd.content)?;
+    let to_template = ctx.prompts().expand_attachments(id, &to_pd.content)?;
+    let policies = load_policies()?;
+    policies.check_content(id, &from_template)?;
+    policies.check_content(id, &to_template)?;
+
+    let cases = match opts.vars_file {
+        Some(path) => load_cases(path)?,
+        // Without a file, the examples of the newer version serve as inputs.
+        None if !to_pd.examples.is_empty() => {
+            to_pd.examples.iter().map(|e| e.vars.clone()).collect()
+        }
+        None => vec![BTreeMap::new()],
+    };
+    let redaction = load_input_redaction()?;
+    // The judge sees the prompts too.
+    let workspace = parse_id(id).0;
+    for backend in std::iter::once(opts.backend).chain(opts.judge) {
+        ctx.prompts().check_provider(&workspace, backend)?;
+    }
+    let llm = build_llm(ctx, opts.backend)?;
+    let judge = opts
+        .judge
+        .map(|backend| build_llm(ctx, backend))
+        .transpose()?;
+
+    let mut results = Vec::new();
+    for (i, case) in cases.into_iter().enumerate() {
+        eprintln!(
+            "{} Case {}: running {} and {}",
+            style(BULLET).green().bold(),
+            i + 1,
+            from,
+            to
+        );
+        let mut vars: HashMap<String, String> = case.clone().into_iter().collect();
+        if let Some(policy) = &redaction {
+            redact_values(&mut vars, policy)?;
+        }
+        let from_prompt = render(&from_template, &with_examples(&vars, &from_pd));
+        let to_prompt = render(&to_template, &with_examples(&vars, &to_pd));
+        let from_output = answer(llm.as_ref(), &from_prompt).await?;
+        let to_output = answer(llm.as_ref(), &to_prompt).await?;
+
+        let judgement = match &judge {
+            Some(judge) => {
+                let request = judge_request(&from_prompt, &from_output, &to_prompt, &to_output);
+                Some(parse_judgement(&answer(judge.as_ref(), &request).await?))
+            }
+            None => None,
+        };
+        results.push(CaseResult {
+            vars: case,
+            from_output,
+            to_output,
+            judgement,
+        });
+    }
+
+    let comparison = Comparison {
+        id,
+        title: &to_pd.title,
+        from,
+        to,
+        backend: opts.backend,
+        judge: opts.judge,
+        cases: results,
+    };
+    let report = match opts.format {
+        ReportFormat::Md => markdown_report(&comparison),
+        ReportFormat::Html => html_report(&comparison),
+    };
+    match opts.out {
+        Some(path) => {
+            fs::write(path, report).map_err(|e| format!("Write error: {}", e))?;
+            eprintln!(
+                "{} Comparison of {} and {} written to {}",
+                style(BULLET).green().bold(),
+                from,
+                to,
+                path
+            );
+        }
+        None => print!("{}", report),
+    }
+    if comparison.judge.is_some() {
+        eprintln!("{} {}", style(BULLET).green().bold(), summary(&comparison));
+    }
+    Ok(())
+}
+
+/// Loads a version of a prompt by number (`v3`), `current` or backup timestamp.
+fn load_version(
+    ctx: &AppCtx,
+    id: &str,
+    version: &str,
+    backups: &[String],
+) -> Result<PromptData, String> {
+    let number = version
+        .strip_prefix('v')
+        .and_then(|n| n.parse::<usize>().ok());
+    match number {
+        _ if version == "current" => ctx.prompts().load(id),
+        Some(n) if (1..=backups.len()).contains(&n) => {
+            ctx.prompts().load_backup(id, &backups[n - 1])
+        }
+        Some(n) if n == backups.len() + 1 => ctx.prompts().load(id),
+        Some(_) => Err(format!(
+            "Prompt '{}' has {} versions. See 'prompt-store history {}'.",
+            id,
+            backups.len() + 1,
+            id
+        )),
+        None => ctx.prompts().load_backup(id, version),
+    }
+}
+
+/// Reads the cases of a YAML file: a list of variable maps, e.g. `- text: Hello`.
+fn load_cases(path: &str) -> Result<Vec<BTreeMap<String, String>>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let cases: Vec<BTreeMap<String, String>> = serde_yaml::from_str(&content)
+        .map_err(|e| format!("Invalid cases file {}: {}", path, e))?;
+    if cases.is_empty() {
+        return Err(format!("No cases in {}.", path));
+    }
+    Ok(cases)
+}
+
+/// Sends a single user message and returns the text of the answer.
+async fn answer(llm: &dyn LLMProvider, prompt: &str) -> Result<String, String> {
+    let messages = vec![ChatMessage::user().content(prompt).build()];
+    let response = llm.chat(&messages).await.map_err(|e| e.to_string())?;
+    Ok(response.text().unwrap_or_default())
+}
+
+/// Asks which of two versions of a prompt, run on the same input, answered better.
+fn judge_request(prompt_a: &str, answer_a: &str, prompt_b: &str, answer_b: &str) -> String {
+    format!(
+        "Two versions of a prompt were run on the same input. Judge which answer better \
+         serves what the prompts ask for.\n\n\
+         Prompt A:\n{}\n\nAnswer A:\n{}\n\nPrompt B:\n{}\n\nAnswer B:\n{}\n\n\
+         Reply with A, B or TIE on the first line, then one sentence explaining why.",
+        prompt_a, answer_a, prompt_b, answer_b
+    )
+}
+
+/// Reads the verdict from the first line of the judge's answer and keeps the rest as
+/// its reason.
+fn parse_judgement(text: &str) -> (Verdict, String) {
+    let text = text.trim();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let word = first
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_uppercase();
+    let verdict = match word.split_whitespace().next().unwrap_or_default() {
+        "A" => Verdict::From,
+        "B" => Verdict::To,
+        "TIE" => Verdict::Tie,
+        _ => Verdict::Unclear,
+    };
+    let reason = match verdict {
+        Verdict::Unclear => text,
+        _ => rest.trim(),
+    };
+    (verdict, reason.to_string())
+}
+
+fn verdict_label(comparison: &Comparison, verdict: Verdict) -> String {
+    match verdict {
+        Verdict::From => format!("{} is better", comparison.from),
+        Verdict::To => format!("{} is better", comparison.to),
+        Verdict::Tie => "tie".to_string(),
+        Verdict::Unclear => "unclear".to_string(),
+    }
+}
+
+/// Counts the verdicts, e.g. "v5 better in 3 cases, v3 in 1, 1 tie".
+fn summary(comparison: &Comparison) -> String {
+    let count = |verdict: Verdict| {
+        comparison
+            .cases
+            .iter()
+            .filter(|c| matches!(&c.judgement, Some((v, _)) if *v == verdict))
+            .count()
+    };
+    let mut text = format!(
+        "{} better in {} cases, {} in {}, {} ties",
+        comparison.to,
+        count(Verdict::To),
+        comparison.from,
+        count(Verdict::From),
+        count(Verdict::Tie)
+    );
+    let unclear = count(Verdict::Unclear);
+    if unclear > 0 {
+        text.push_str(&format!(", {} unclear", unclear));
+    }
+    text
+}
+
+fn vars_line(vars: &BTreeMap<String, String>) -> String {
+    if vars.is_empty() {
+        return "no variables".to_string();
+    }
+    vars.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn markdown_report(comparison: &Comparison) -> String {
+    let mut md = format!(
+        "# `{}` ({}): {} vs {}\n\n",
+        comparison.title, comparison.id, comparison.from, comparison.to
+    );
+    md.push_str(&format!("- **Backend:** {}\n", comparison.backend));
+    md.push_str(&format!("- **Cases:** {}\n", comparison.cases.len()));
+    if let Some(judge) = comparison.judge {
+        md.push_str(&format!(
+            "- **Judge:** {} ({})\n",
+            judge,
+            summary(comparison)
+        ));
+    }
+    for (i, case) in comparison.cases.iter().enumerate() {
+        md.push_str(&format!("\n## Case {}\n\n", i + 1));
+        md.push_str(&format!("- **Variables:** {}\n", vars_line(&case.vars)));
+        if let Some((verdict, reason)) = &case.judgement {
+            md.push_str(&format!(
+                "- **Judge:** {}. {}\n",
+                verdict_label(comparison, *verdict),
+                reason
+            ));
+        }
+        md.push_str(&format!(
+            "\n| {} | {} |\n| --- | --- |\n| {} | {} |\n",
+            comparison.from,
+            comparison.to,
+            table_cell(&case.from_output),
+            table_cell(&case.to_output)
+        ));
+    }
+    md
+}
+
+/// Fits text in a Markdown table cell.
+fn table_cell(text: &str) -> String {
+    text.trim()
+        .replace('|', "\\|")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+fn html_report(comparison: &Comparison) -> String {
+    let heading = format!(
+        "<code>{}</code> ({}): {} vs {}",
+        escape(comparison.title),
+        escape(comparison.id),
+        escape(comparison.from),
+        escape(comparison.to)
+    );
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{} vs {}</title>\n\
+         <style>\nbody {{ font-family: sans-serif; max-width: 80rem; margin: 2rem auto; }}\n\
+         table {{ width: 100%; table-layout: fixed; border-collapse: collapse; }}\n\
+         td {{ vertical-align: top; width: 50%; }}\n\
+         pre {{ background: #f4f4f4; padding: 1rem; white-space: pre-wrap; }}\n</style>\n\
+         </head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        escape(comparison.from),
+        escape(comparison.to),
+        heading
+    );
+    html.push_str(&format!(
+        "<li><b>Backend:</b> {}</li>\n",
+        escape(comparison.backend)
+    ));
+    html.push_str(&format!(
+        "<li><b>Cases:</b> {}</li>\n",
+        comparison.cases.len()
+    ));
+    if let Some(judge) = comparison.judge {
+        html.push_str(&format!(
+            "<li><b>Judge:</b> {} ({})</li>\n",
+            escape(judge),
+            escape(&summary(comparison))
+        ));
+    }
+    html.push_str("</ul>\n");
+    for (i, case) in comparison.cases.iter().enumerate() {
+        html.push_str(&format!("<h2>Case {}</h2>\n<ul>\n", i + 1));
+        html.push_str(&format!(
+            "<li><b>Variables:</b> {}</li>\n",
+            escape(&vars_line(&case.vars))
+        ));
+        if let Some((verdict, reason)) = &case.judgement {
+            html.push_str(&format!(
+                "<li><b>Judge:</b> {}. {}</li>\n",
+                escape(&verdict_label(comparison, *verdict)),
+                escape(reason)
+            ));
+        }
+        html.push_str(&format!(
+            "</ul>\n<table>\n<tr><th>{}</th><th>{}</th></tr>\n\
+             <tr><td><pre>{}</pre></td><td><pre>{}</pre></td></tr>\n</table>\n",
+            escape(comparison.from),
+            escape(comparison.to),
+            escape(&case.from_output),
+            escape(&case.to_output)
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
@@ -1,15 +1,154 @@
-use crate::core::storage::AppCtx;
+use crate::cli::RemoveArgs;
+use crate::commands::uses::references;
+use crate::core::journal::JournalEntry;
+use crate::core::storage::{AppCtx, PromptRepository};
+use crate::ui::confirm_removal;
+use crate::ui::symbols::{BULLET, WARN};
 use console::style;
 use std::fs;
+use std::path::PathBuf;
 
-/// Delete a prompt.
-pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
-    let path = ctx.prompt_path(id);
-    if path.exists() {
-        fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
-        println!("{} prompt {} deleted", style("•").green().bold(), id);
-        Ok(())
+/// Delete a prompt, an interactive chain or a YAML chain definition, after showing
+/// what goes with it and asking for confirmation (or `force`).
+///
+/// A prompt is deleted with its attachments and edit backups. Chains still referencing
+/// it are listed first, as they would fail at runtime. Prompts linking to it get a copy
+/// of its content, so they keep working.
+pub fn run(ctx: &AppCtx, id: &str, args: RemoveArgs) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    let mut links = Vec::new();
+    let (kind, paths) = if prompts.exists(id) {
+        links = prompts.links_to(id)?;
+        ("prompt", describe_prompt(ctx, &prompts, id, &links)?)
+    } else if prompts.chain_dir(id).join("chain.meta").is_file() {
+        ("chain", describe_chain(&prompts, id)?)
+    } else if prompts.chain_definition_path(id).is_file() {
+        ("chain", describe_chain_definition(&prompts, id)?)
     } else {
-        Err(format!("No prompt with ID {}", id))
+        return Err(format!("No prompt or chain with ID '{}'", id));
+    };
+
+    if !confirm_removal(args, &format!("{} {}", kind, id))? {
+        return Ok(());
+    }
+    let mut journal = JournalEntry::new(format!("delete {}", id));
+    for path in &paths {
+        journal.save(ctx, path)?;
+    }
+    for link in &links {
+        journal.save(ctx, &ctx.prompt_path(link))?;
+        prompts.unlink(link)?;
+    }
+    for path in &paths {
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        removed.map_err(|e| format!("Delete error: {}", e))?;
+    }
+    journal.commit(ctx)?;
+    println!(
+        "{} {} {} deleted (restore it with 'prompt-store undo')",
+        style(BULLET).green().bold(),
+        kind,
+        id
+    );
+    Ok(())
+}
+
+/// Prints what deleting a prompt removes, and returns the paths to remove.
+fn describe_prompt(
+    ctx: &AppCtx,
+    prompts: &PromptRepository,
+    id: &str,
+    links: &[String],
+) -> Result<Vec<PathBuf>, String> {
+    let pd = prompts.load(id)?;
+    let path = ctx.prompt_path(id);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let backups: Vec<PathBuf> = prompts
+        .list_backups(id)?
+        .iter()
+        .map(|ts| path.with_file_name(format!("{}.{}.bak", stem, ts)))
+        .collect();
+    let attachments = prompts.list_attachments(id)?;
+
+    println!("Prompt {}: {}", style(id).yellow(), pd.title);
+    print_tags(&pd.tags);
+    println!(
+        "  {} attachments, {} edit backups",
+        attachments.len(),
+        backups.len()
+    );
+
+    let refs = references(prompts, id)?;
+    if !refs.is_empty() {
+        println!(
+            "{} {}",
+            style(WARN).yellow(),
+            style("It is used by these chains, which will fail when run:").yellow()
+        );
+        for (r, _) in &refs {
+            println!(
+                "  {} {} (step {}: {})",
+                style(BULLET).yellow(),
+                r.chain,
+                r.step,
+                r.step_id
+            );
+        }
+    }
+
+    if !links.is_empty() {
+        println!(
+            "{} {}",
+            style(WARN).yellow(),
+            style("These prompts link to it and will get a copy of its content:").yellow()
+        );
+        for link in links {
+            println!("  {} {}", style(BULLET).yellow(), link);
+        }
+    }
+
+    let mut paths = vec![path];
+    if !attachments.is_empty() {
+        paths.push(prompts.attachments_dir(id));
+    }
+    paths.extend(backups);
+    Ok(paths)
+}
+
+/// Prints what deleting an interactive chain removes: its folder, with its steps.
+fn describe_chain(prompts: &PromptRepository, id: &str) -> Result<Vec<PathBuf>, String> {
+    let chain = prompts.load_chain(id)?;
+    println!("Chain {}: {}", style(id).yellow(), chain.title);
+    print_tags(&chain.tags);
+    println!("  {} steps", prompts.chain_steps(id)?.len());
+    Ok(vec![prompts.chain_dir(id)])
+}
+
+/// Prints what deleting a YAML chain definition removes: it and its variable presets.
+fn describe_chain_definition(prompts: &PromptRepository, id: &str) -> Result<Vec<PathBuf>, String> {
+    let yaml = prompts.load_chain_definition(id)?;
+    let steps = serde_yaml::from_str::<serde_yaml::Value>(&yaml)
+        .ok()
+        .and_then(|doc| doc.get("steps")?.as_sequence().map(Vec::len))
+        .unwrap_or(0);
+    let presets = prompts.load_chain_presets(id)?;
+    println!("YAML chain {}", style(id).yellow());
+    println!("  {} steps, {} variable presets", steps, presets.len());
+
+    let mut paths = vec![prompts.chain_definition_path(id)];
+    let presets_path = prompts.chain_presets_path(id);
+    if presets_path.is_file() {
+        paths.push(presets_path);
+    }
+    Ok(paths)
+}
+
+fn print_tags(tags: &[String]) {
+    if !tags.is_empty() {
+        println!("  tags: {}", tags.join(", "));
     }
 }
@@ -1,80 +1,225 @@
-use crate::commands::pack_logic::{install_pack_from_local_repo, DeployedInfo};
+use crate::cli::OnDuplicate;
+use crate::commands::duplicates::should_link;
+use crate::commands::pack_logic::{
+    checkout_rev, confirm_pack_risks, duplicate_links, fetch_progress,
+    install_prompts_to_workspace, load_manifest, read_local_pack_file, read_local_policy,
+    read_pack_file, save_manifest, DeployedInfo, PackSource,
+};
+use crate::core::logging;
+use crate::core::policy::POLICY_FILE;
+use crate::core::registry::{fetch_bundle, fetch_pack_info};
 use crate::core::storage::AppCtx;
+use crate::ui::progress::bar;
+use crate::ui::symbols::{BULLET, OK, WARN};
 use console::style;
-use git2::Repository;
-use std::collections::HashMap;
+use git2::build::RepoBuilder;
+use log::{debug, info, warn};
 use std::fs;
 
-/// Deploy a prompt pack from a git repository.
+/// Deploy a prompt pack from a git repository or a pack registry.
+///
+/// HTTP(S) URLs not ending in `.git` are first tried as a registry pack
+/// (`{registry}/packs/{name}`), then cloned with git. A git pack can be pinned to a
+/// tag, branch or commit with `rev`.
+///
+/// Prompts that look like prompt injections are reported before installing, which then
+/// needs confirmation unless `yes` is set. Prompts identical to one already stored
+/// are linked to it or copied according to `on_duplicate`.
 pub async fn run(
     ctx: &AppCtx,
     repo_url: &str,
     alias: Option<&str>,
     password: Option<&str>,
+    rev: Option<&str>,
+    yes: bool,
+    on_duplicate: OnDuplicate,
 ) -> Result<(), String> {
     let pack_alias = alias.map(String::from).unwrap_or_else(|| {
         repo_url
             .split('/')
-            .last()
+            .next_back()
             .unwrap_or("default-pack")
             .trim_end_matches(".git")
             .to_string()
     });
 
     let registry_path = ctx.registries_dir.join(&pack_alias);
-    if registry_path.exists() {
+    // Registry packs have no clone, only their workspace.
+    if registry_path.exists() || ctx.workspaces_dir.join(&pack_alias).exists() {
         return Err(format!(
             "A pack with alias '{}' already exists. Use 'update' to get the latest version.",
             pack_alias
         ));
     }
 
+    info!("deploying {} as '{}'", logging::url(repo_url), pack_alias);
+    let is_http = repo_url.starts_with("http://") || repo_url.starts_with("https://");
+    if is_http && !repo_url.ends_with(".git") {
+        if let Some(info) = fetch_pack_info(repo_url).await? {
+            if rev.is_some() {
+                return Err("Registry packs cannot be pinned with --rev.".to_string());
+            }
+            println!("Downloading {} {}...", info.name, info.version);
+            let content = fetch_bundle(repo_url).await?;
+            debug!("downloaded {} bytes of pack {}", content.len(), info.name);
+            let Some(num_prompts) = install_checked(
+                ctx,
+                &content,
+                None,
+                &pack_alias,
+                password,
+                yes,
+                on_duplicate,
+            )?
+            else {
+                return Ok(());
+            };
+            update_deployment_manifest(
+                ctx,
+                &pack_alias,
+                repo_url,
+                &info.version,
+                PackSource::Registry,
+                None,
+            )?;
+            println!(
+                "{} Successfully deployed {} prompts from pack '{}' (version {}).",
+                style(OK).green(),
+                num_prompts,
+                style(&pack_alias).yellow(),
+                info.version
+            );
+            print_policy_notice(ctx, &pack_alias);
+            return Ok(());
+        }
+    }
+
     println!("Cloning {}...", repo_url);
-    let repo = Repository::clone(repo_url, &registry_path)
-        .map_err(|e| format!("Failed to clone repository: {}", e))?;
+    let progress = bar(0, "Receiving objects");
+    let repo = RepoBuilder::new()
+        .fetch_options(fetch_progress(progress.clone()))
+        .clone(repo_url, &registry_path)
+        .map_err(|e| {
+            warn!("cloning {} failed: {:?}", logging::url(repo_url), e);
+            format!("Failed to clone repository: {}", e)
+        });
+    progress.finish_and_clear();
+    let repo = repo?;
 
-    let head = repo
-        .head()
-        .map_err(|e| format!("Failed to get HEAD for repo: {}", e))?;
-    let commit_hash = head
-        .target()
-        .ok_or_else(|| "Invalid HEAD commit".to_string())?
-        .to_string();
+    let commit_hash = match rev {
+        Some(rev) => {
+            drop(repo);
+            checkout_rev(&registry_path, rev)?
+        }
+        None => repo
+            .head()
+            .map_err(|e| format!("Failed to get HEAD for repo: {}", e))?
+            .target()
+            .ok_or_else(|| "Invalid HEAD commit".to_string())?
+            .to_string(),
+    };
 
-    let num_prompts = install_pack_from_local_repo(ctx, &registry_path, &pack_alias, password)?;
-    update_deployment_manifest(ctx, &pack_alias, repo_url, &commit_hash)?;
+    debug!("cloned {} at {}", logging::url(repo_url), commit_hash);
+    let content = read_local_pack_file(&registry_path)?;
+    let policy = read_local_policy(&registry_path)?;
+    let installed = install_checked(
+        ctx,
+        &content,
+        policy,
+        &pack_alias,
+        password,
+        yes,
+        on_duplicate,
+    );
+    if !matches!(installed, Ok(Some(_))) {
+        // Without its workspace, the clone would block deploying the pack again.
+        fs::remove_dir_all(&registry_path).ok();
+    }
+    let Some(num_prompts) = installed? else {
+        return Ok(());
+    };
+    update_deployment_manifest(
+        ctx,
+        &pack_alias,
+        repo_url,
+        &commit_hash,
+        PackSource::Git,
+        rev,
+    )?;
 
     println!(
-        "{} Successfully deployed {} prompts from pack '{}'.",
-        style("✔").green(),
+        "{} Successfully deployed {} prompts from pack '{}'{}.",
+        style(OK).green(),
         num_prompts,
-        style(pack_alias).yellow()
+        style(&pack_alias).yellow(),
+        rev.map(|rev| format!(" pinned to {}", rev))
+            .unwrap_or_default()
     );
+    print_policy_notice(ctx, &pack_alias);
     Ok(())
 }
 
+/// Tells that a deployed pack came with a policy.
+fn print_policy_notice(ctx: &AppCtx, alias: &str) {
+    if ctx.workspaces_dir.join(alias).join(POLICY_FILE).is_file() {
+        println!(
+            "{} The pack ships a {}, enforced while it is deployed.",
+            style(WARN).yellow(),
+            POLICY_FILE
+        );
+    }
+}
+
+/// Installs the prompts of a pack file once its risk report is confirmed, with `policy`
+/// in place of the one of the pack file when given. Returns the number of prompts
+/// installed, or `None` if the installation was declined.
+fn install_checked(
+    ctx: &AppCtx,
+    content: &str,
+    policy: Option<String>,
+    alias: &str,
+    password: Option<&str>,
+    yes: bool,
+    on_duplicate: OnDuplicate,
+) -> Result<Option<usize>, String> {
+    let mut pack = read_pack_file(content, alias, password)?;
+    pack.policy = policy.or(pack.policy);
+    if !confirm_pack_risks(alias, &pack.prompts, yes)? {
+        println!("{}", style(format!("Did not deploy '{}'.", alias)).yellow());
+        return Ok(None);
+    }
+    let links = duplicate_links(ctx, alias, &pack.prompts, |id, existing| {
+        should_link(on_duplicate, id, existing, false)
+    })?;
+    if !links.is_empty() {
+        println!(
+            "{} {} prompts are identical to existing ones and are stored as links.",
+            style(BULLET).green(),
+            links.len()
+        );
+    }
+    let num_prompts = pack.prompts.len();
+    install_prompts_to_workspace(ctx, alias, pack, &links)?;
+    Ok(Some(num_prompts))
+}
+
 fn update_deployment_manifest(
     ctx: &AppCtx,
     alias: &str,
     url: &str,
     commit_hash: &str,
+    source: PackSource,
+    pin: Option<&str>,
 ) -> Result<(), String> {
-    let manifest_path = ctx.base_dir.join("deployed.json");
-    let mut manifest: HashMap<String, DeployedInfo> = if manifest_path.exists() {
-        let content = fs::read_to_string(&manifest_path).map_err(|e| e.to_string())?;
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        HashMap::new()
-    };
-
+    let mut manifest = load_manifest(ctx)?;
     let info = DeployedInfo {
         alias: alias.to_string(),
         url: url.to_string(),
         commit_hash: commit_hash.to_string(),
+        source,
+        pin: pin.map(String::from),
+        previous: None,
     };
     manifest.insert(alias.to_string(), info);
-
-    let content = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
-    fs::write(manifest_path, content).map_err(|e| e.to_string())?;
-    Ok(())
+    save_manifest(ctx, &manifest)
 }
@@ -0,0 +1,135 @@
+use crate::commands::pack_logic::load_manifest;
+use crate::core::storage::{AppCtx, WorkspaceInfo};
+use crate::core::utils::editor;
+use crate::ui::symbols::OK;
+use console::style;
+
+/// Fields of a description left empty in the editor, as a starting point.
+const EMPTY_DESCRIPTION: &str =
+    "purpose:\nowner:\nconventions:\nallowed_providers: []\ndenied_providers: []\n";
+
+/// Updates of `describe` to the description of a workspace.
+pub struct DescribeEdit {
+    /// Opens the description in the editor.
+    pub editor: bool,
+    /// Fields to set, an empty value clearing the field.
+    pub fields: WorkspaceInfo,
+    /// Providers to allow, replacing the current ones; an empty list clears them.
+    pub allowed_providers: Option<Vec<String>>,
+    /// Providers to deny, replacing the current ones; an empty list clears them.
+    pub denied_providers: Option<Vec<String>>,
+}
+
+/// Shows what a workspace or deployed pack is for, after applying `edit` to the
+/// description of a workspace.
+pub fn run(ctx: &AppCtx, name: &str, edit: DescribeEdit) -> Result<(), String> {
+    if !ctx.workspaces_dir.join(name).is_dir() {
+        return Err(format!("Workspace '{}' not found.", name));
+    }
+    let repo = ctx.prompts();
+    let pack = load_manifest(ctx)?.remove(name);
+    let mut info = repo.load_workspace_info(name)?;
+
+    let edits_providers = edit.allowed_providers.is_some() || edit.denied_providers.is_some();
+    if edit.editor || !edit.fields.is_empty() || edits_providers {
+        if pack.is_some() {
+            return Err(format!(
+                "'{}' is a pack: its description comes with the pack and is replaced by updates.",
+                name
+            ));
+        }
+        let mut updated = info.clone().unwrap_or_default();
+        if edit.editor {
+            let current = match &info {
+                Some(info) => {
+                    serde_yaml::to_string(info).map_err(|e| format!("Serialize error: {}", e))?
+                }
+                None => EMPTY_DESCRIPTION.to_string(),
+            };
+            let Some(edited) = editor()
+                .extension(".yaml")
+                .edit(&current)
+                .map_err(|e| format!("Editor error: {}", e))?
+            else {
+                println!("{}", style("Description unchanged.").yellow());
+                return Ok(());
+            };
+            updated = if edited.trim().is_empty() {
+                WorkspaceInfo::default()
+            } else {
+                serde_yaml::from_str(&edited).map_err(|e| format!("Invalid description: {}", e))?
+            };
+        }
+        for (field, value) in [
+            (&mut updated.purpose, edit.fields.purpose),
+            (&mut updated.owner, edit.fields.owner),
+            (&mut updated.conventions, edit.fields.conventions),
+        ] {
+            if let Some(value) = value {
+                *field = Some(value).filter(|v| !v.trim().is_empty());
+            }
+        }
+        for (field, value) in [
+            (&mut updated.allowed_providers, edit.allowed_providers),
+            (&mut updated.denied_providers, edit.denied_providers),
+        ] {
+            if let Some(value) = value {
+                *field = value
+                    .into_iter()
+                    .map(|provider| provider.trim().to_string())
+                    .filter(|provider| !provider.is_empty())
+                    .collect();
+            }
+        }
+        repo.save_workspace_info(name, &updated)?;
+        println!(
+            "{} Description of '{}' saved.\n",
+            style(OK).green().bold(),
+            name
+        );
+        info = Some(updated).filter(|info| !info.is_empty());
+    }
+
+    let prompts = repo.list(name)?.len();
+    let chains = repo.list_chains(name)?.len() + repo.list_chain_definitions(name)?.len();
+    println!("{}", style(name).cyan().bold());
+    match &pack {
+        Some(pack) => println!(
+            "Pack deployed from {} at {}",
+            pack.url,
+            pack.commit_hash.get(..7).unwrap_or(&pack.commit_hash)
+        ),
+        None => println!("Workspace"),
+    }
+    println!("{} prompts, {} chains", prompts, chains);
+
+    let Some(info) = info else {
+        if pack.is_none() {
+            println!(
+                "\nNo description. Add one with `prompt-store describe {} --edit`.",
+                name
+            );
+        }
+        return Ok(());
+    };
+    for (label, value) in [
+        ("Purpose", &info.purpose),
+        ("Owner", &info.owner),
+        ("Conventions", &info.conventions),
+    ] {
+        if let Some(value) = value {
+            println!("\n{}", style(label).bold());
+            println!("{}", value.trim_end());
+        }
+    }
+    if info.restricts_providers() {
+        println!("\n{}", style("Providers").bold());
+        if !info.allowed_providers.is_empty() {
+            println!("Only {}", info.allowed_providers.join(", "));
+        }
+        if !info.denied_providers.is_empty() {
+            println!("Never {}", info.denied_providers.join(", "));
+        }
+    }
+    Ok(())
+}
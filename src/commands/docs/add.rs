@@ -0,0 +1,115 @@
+use crate::core::config::{load_llm_registry, resolve_provider_name};
+use crate::core::docs::{chunk_text, index_path, DocChunk, DocIndex};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of chunks sent to the embedding provider per request.
+const EMBED_BATCH_SIZE: usize = 64;
+
+/// Chunk and embed a file or folder of documents into the encrypted document index.
+pub async fn run(
+    ctx: &AppCtx,
+    path: &str,
+    provider: Option<&str>,
+    chunk_size: usize,
+) -> Result<(), String> {
+    let root = Path::new(path);
+    if !root.exists() {
+        return Err(format!("Path '{}' not found.", path));
+    }
+
+    let provider_name = resolve_provider_name(provider)?;
+    let registry = load_llm_registry(ctx)?;
+    let llm = registry
+        .get(&provider_name)
+        .ok_or_else(|| format!("Provider '{}' not found in config.toml", provider_name))?;
+
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    if files.is_empty() {
+        return Err(format!("No documents found in '{}'.", path));
+    }
+
+    let idx_path = index_path(&ctx.base_dir);
+    let mut index = DocIndex::load(&idx_path, &ctx.cipher)?;
+    if let Some(existing) = &index.provider {
+        if existing != &provider_name {
+            println!(
+                "{}",
+                style(format!(
+                    "Warning: index was built with provider '{}', embeddings may not be comparable.",
+                    existing
+                ))
+                .yellow()
+            );
+        }
+    }
+
+    let mut total_chunks = 0;
+    for file in &files {
+        // Non-UTF-8 files are skipped rather than aborting the whole import.
+        let Ok(text) = fs::read_to_string(file) else {
+            continue;
+        };
+        let texts = chunk_text(&text, chunk_size);
+        if texts.is_empty() {
+            continue;
+        }
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBED_BATCH_SIZE) {
+            let mut batch_embeddings = llm
+                .embed(batch.to_vec())
+                .await
+                .map_err(|e| format!("Embedding failed for '{}': {}", file.display(), e))?;
+            embeddings.append(&mut batch_embeddings);
+        }
+
+        let source = file.display().to_string();
+        let chunks: Vec<DocChunk> = texts
+            .into_iter()
+            .zip(embeddings)
+            .map(|(text, embedding)| DocChunk {
+                source: source.clone(),
+                text,
+                embedding,
+            })
+            .collect();
+        total_chunks += chunks.len();
+        index.replace_source(&source, chunks);
+        println!("  {} {}", style(BULLET).green(), source);
+    }
+
+    index.provider = Some(provider_name);
+    index.save(&idx_path, &ctx.cipher)?;
+
+    println!(
+        "{} Indexed {} chunks from {} files.",
+        style(OK).green().bold(),
+        total_chunks,
+        files.len()
+    );
+    Ok(())
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path).map_err(|e| format!("Read dir error: {}", e))? {
+        let entry_path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        let hidden = entry_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        collect_files(&entry_path, files)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use crate::cli::RemoveArgs;
+use crate::core::docs::{index_path, DocIndex};
+use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::collections::HashSet;
+use std::fs;
+
+/// Remove every document from the document index.
+pub fn run(ctx: &AppCtx, args: RemoveArgs) -> Result<(), String> {
+    let path = index_path(&ctx.base_dir);
+    if !path.exists() {
+        println!("{}", style("No documents indexed.").yellow());
+        return Ok(());
+    }
+    let index = DocIndex::load(&path, &ctx.cipher)?;
+    let sources: HashSet<&str> = index.chunks.iter().map(|c| c.source.as_str()).collect();
+    println!(
+        "Document index: {} documents, {} chunks",
+        sources.len(),
+        index.chunks.len()
+    );
+
+    if confirm_removal(args, "every document of the index")? {
+        fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+        println!("{} document index cleared", style(BULLET).green().bold());
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use crate::core::docs::{index_path, DocIndex};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::collections::BTreeMap;
+
+/// List the documents held in the document index.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let index = DocIndex::load(&index_path(&ctx.base_dir), &ctx.cipher)?;
+    if index.chunks.is_empty() {
+        println!("{}", style("No documents indexed.").yellow());
+        return Ok(());
+    }
+
+    let mut sources: BTreeMap<&str, usize> = BTreeMap::new();
+    for chunk in &index.chunks {
+        *sources.entry(chunk.source.as_str()).or_insert(0) += 1;
+    }
+
+    println!(
+        "{} ({})",
+        style("Indexed documents:").green().bold(),
+        index.provider.as_deref().unwrap_or("unknown provider")
+    );
+    for (source, count) in sources {
+        println!("  {} {} ({} chunks)", style(BULLET).green(), source, count);
+    }
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod add;
+pub mod clear;
+pub mod list;
@@ -0,0 +1,28 @@
+//! Handling of incoming prompts identical to one already stored under another ID,
+//! found through the content index.
+
+use crate::cli::OnDuplicate;
+use dialoguer::Confirm;
+
+/// Whether an incoming prompt identical to `existing` is stored as a link to it rather
+/// than as a copy. `preview` never asks, and assumes linking.
+pub fn should_link(
+    on_duplicate: OnDuplicate,
+    id: &str,
+    existing: &str,
+    preview: bool,
+) -> Result<bool, String> {
+    match on_duplicate {
+        OnDuplicate::Link => Ok(true),
+        OnDuplicate::Copy => Ok(false),
+        OnDuplicate::Ask if preview => Ok(true),
+        OnDuplicate::Ask => Confirm::new()
+            .with_prompt(format!(
+                "'{}' is identical to '{}'. Link to it instead of storing a copy?",
+                id, existing
+            ))
+            .default(true)
+            .interact()
+            .map_err(|e| format!("{} (use --on-duplicate to choose without a prompt)", e)),
+    }
+}
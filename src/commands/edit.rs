@@ -1,29 +1,81 @@
-use crate::core::storage::{decrypt_full_prompt, parse_id, AppCtx, PromptSchema};
-use aes_gcm::{
-    aead::{Aead, AeadCore, OsRng},
-    Aes256Gcm,
+use crate::core::storage::{
+    normalize_lang, parse_id, validate_variant_name, AppCtx, PromptData, PromptSchema,
+    DEFAULT_VARIANT,
 };
-use base64::{engine::general_purpose, Engine as _};
-use chrono::Local;
+use crate::core::utils::editor;
+use crate::ui::symbols::OK;
+use chrono::{Local, Utc};
 use console::style;
-use dialoguer::{theme::ColorfulTheme, Editor, Select};
+use dialoguer::{theme::ColorfulTheme, Select};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
-/// Edit a prompt's content or schema and create a timestamped backup.
-pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID '{}'", id));
+/// Text of a prompt edited instead of its content.
+enum Alternative {
+    Translation(String),
+    Variant(String),
+}
+
+impl Alternative {
+    fn describe(&self) -> String {
+        match self {
+            Alternative::Translation(lang) => format!("'{}' translation", lang),
+            Alternative::Variant(name) => format!("'{}' variant", name),
+        }
     }
 
-    let mut pd = decrypt_full_prompt(&path, &ctx.cipher)?;
+    fn texts<'p>(&self, pd: &'p mut PromptData) -> (&'p mut BTreeMap<String, String>, &str) {
+        match self {
+            Alternative::Translation(lang) => (&mut pd.translations, lang),
+            Alternative::Variant(name) => (&mut pd.variants, name),
+        }
+    }
+}
+
+/// Edit a prompt's content (or its translation in `lang`, or its variant `variant`),
+/// schema or examples and create a timestamped backup. Chain steps can be addressed by
+/// title.
+pub fn run(
+    ctx: &AppCtx,
+    id: &str,
+    lang: Option<&str>,
+    variant: Option<&str>,
+) -> Result<(), String> {
+    let alternative = match (lang, variant) {
+        (Some(lang), _) => Some(Alternative::Translation(normalize_lang(lang)?)),
+        (None, Some(DEFAULT_VARIANT)) | (None, None) => None,
+        (None, Some(name)) => {
+            validate_variant_name(name)?;
+            Some(Alternative::Variant(name.to_string()))
+        }
+    };
+    let prompts = ctx.prompts();
+    let id = &prompts.resolve_step(id)?;
+    let mut pd = prompts.load(id)?;
     let original_pd = pd.clone();
     let theme = ColorfulTheme::default();
+    if let Some(alternative) = &alternative {
+        let (texts, name) = alternative.texts(&mut pd);
+        let message = if texts.contains_key(name) {
+            format!("Editing the {}.", alternative.describe())
+        } else {
+            format!(
+                "Editing a new {}, starting from the default content.",
+                alternative.describe()
+            )
+        };
+        println!("{}", style(message).yellow());
+    }
 
     loop {
-        let selections = &["Edit Content", "Edit Schema", "Finish Editing"];
+        let selections = &[
+            "Edit Content",
+            "Edit Schema",
+            "Edit Examples",
+            "Finish Editing",
+        ];
         let selection = Select::with_theme(&theme)
             .with_prompt("What would you like to do?")
             .default(0)
@@ -34,12 +86,32 @@ pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
         match selection {
             0 => {
                 // Edit Content
-                let edited = Editor::new()
-                    .edit(&pd.content)
+                let Some(alternative) = &alternative else {
+                    let edited = editor()
+                        .edit(&pd.content)
+                        .map_err(|e| format!("Editor error: {}", e))?
+                        .unwrap_or_default();
+                    pd.content = edited;
+                    println!("{}", style("Content updated.").green());
+                    continue;
+                };
+                let content = pd.content.clone();
+                let (texts, name) = alternative.texts(&mut pd);
+                let current = texts.get(name).unwrap_or(&content);
+                let Some(edited) = editor()
+                    .edit(current)
                     .map_err(|e| format!("Editor error: {}", e))?
-                    .unwrap_or_default();
-                pd.content = edited;
-                println!("{}", style("Content updated.").green());
+                else {
+                    continue;
+                };
+                let label = alternative.describe();
+                if edited.trim().is_empty() {
+                    texts.remove(name);
+                    println!("{}", style(format!("Removed the {}.", label)).yellow());
+                } else {
+                    texts.insert(name.to_string(), edited);
+                    println!("{}", style(format!("Updated the {}.", label)).green());
+                }
             }
             1 => {
                 // Edit Schema
@@ -48,7 +120,7 @@ pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
                     |s| serde_json::to_string_pretty(s).unwrap_or_else(|_| "{}".to_string()),
                 );
 
-                let new_schema_str = Editor::new()
+                let new_schema_str = editor()
                     .edit(&current_schema_str)
                     .map_err(|e| format!("Editor error: {}", e))?
                     .unwrap_or_default();
@@ -66,6 +138,25 @@ pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
                     println!("{}", style("Schema updated.").green());
                 }
             }
+            2 => {
+                // Edit Examples, as a JSON array of {"vars": {...}, "output": "..."}
+                let current_examples_str =
+                    serde_json::to_string_pretty(&pd.examples).unwrap_or_else(|_| "[]".to_string());
+
+                let new_examples_str = editor()
+                    .edit(&current_examples_str)
+                    .map_err(|e| format!("Editor error: {}", e))?
+                    .unwrap_or_default();
+
+                if new_examples_str.trim().is_empty() || new_examples_str.trim() == "[]" {
+                    pd.examples.clear();
+                    println!("{}", style("Examples removed.").yellow());
+                } else {
+                    pd.examples = serde_json::from_str(&new_examples_str)
+                        .map_err(|e| format!("Invalid JSON in examples: {}", e))?;
+                    println!("{}", style("Examples updated.").green());
+                }
+            }
             _ => break, // Finish Editing
         }
     }
@@ -81,7 +172,19 @@ pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
         return Ok(());
     }
 
+    save_with_backup(ctx, id, &mut pd)?;
+    println!(
+        "{} Prompt '{}' updated successfully.",
+        style(OK).green().bold(),
+        id
+    );
+    Ok(())
+}
+
+/// Saves a new version of a prompt, keeping the current one as a timestamped backup.
+pub(crate) fn save_with_backup(ctx: &AppCtx, id: &str, pd: &mut PromptData) -> Result<(), String> {
     // Create backup
+    let path = ctx.prompt_path(id);
     let ts = Local::now().format("%Y%m%d%H%M%S").to_string();
     let (_workspace, local_id) = parse_id(id);
     let mut bak_path = PathBuf::from(&path);
@@ -89,22 +192,6 @@ pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
     fs::copy(&path, &bak_path).map_err(|e| format!("Backup error: {}", e))?;
 
     // Save new version
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = ctx
-        .cipher
-        .encrypt(&nonce, new_json.as_ref())
-        .map_err(|_| "Encrypt error".to_string())?;
-
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded_out = general_purpose::STANDARD.encode(&out);
-
-    fs::write(&path, encoded_out).map_err(|e| format!("Write error: {}", e))?;
-    println!(
-        "{} Prompt '{}' updated successfully.",
-        style("✔").green().bold(),
-        id
-    );
-    Ok(())
+    pd.updated_at = Some(Utc::now());
+    ctx.prompts().save(id, pd)
 }
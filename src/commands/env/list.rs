@@ -0,0 +1,18 @@
+use crate::core::profiles::list_profiles;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List all environment profiles.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let names = list_profiles(&ctx.base_dir)?;
+    if names.is_empty() {
+        println!("{}", style("No profiles").yellow());
+    } else {
+        println!("{}", style("Profiles:").green().bold());
+        for name in names {
+            println!("  {} {}", style(BULLET).green(), name);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,4 @@
+pub mod list;
+pub mod set;
+pub mod show;
+pub mod unset;
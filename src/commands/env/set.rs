@@ -0,0 +1,23 @@
+use crate::core::profiles::{load_profile, save_profile};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Set one or more variables in an environment profile, creating it if needed.
+pub fn run(ctx: &AppCtx, profile: &str, assignments: &[String]) -> Result<(), String> {
+    let mut vars = load_profile(&ctx.base_dir, &ctx.cipher, profile)?.unwrap_or_default();
+    for assignment in assignments {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid assignment '{}'. Use key=value.", assignment))?;
+        vars.insert(key.trim().to_string(), value.trim().to_string());
+    }
+    save_profile(&ctx.base_dir, &ctx.cipher, profile, &vars)?;
+    println!(
+        "{} profile {} updated ({} variables)",
+        style(BULLET).green().bold(),
+        style(profile).yellow(),
+        vars.len()
+    );
+    Ok(())
+}
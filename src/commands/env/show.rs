@@ -0,0 +1,14 @@
+use crate::core::profiles::load_profile;
+use crate::core::storage::AppCtx;
+use console::style;
+
+/// Display the variables of an environment profile.
+pub fn run(ctx: &AppCtx, profile: &str) -> Result<(), String> {
+    let vars = load_profile(&ctx.base_dir, &ctx.cipher, profile)?
+        .ok_or_else(|| format!("No profile named '{}'", profile))?;
+    println!("{} {}", style("Profile:").green().bold(), profile);
+    for (key, value) in vars {
+        println!("  {} = {}", style(key).cyan(), value);
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::cli::RemoveArgs;
+use crate::core::profiles::{delete_profile, load_profile, save_profile};
+use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Remove variables from an environment profile, or the whole profile if no keys are given.
+pub fn run(ctx: &AppCtx, profile: &str, keys: &[String], args: RemoveArgs) -> Result<(), String> {
+    let mut vars = load_profile(&ctx.base_dir, &ctx.cipher, profile)?
+        .ok_or_else(|| format!("No profile named '{}'", profile))?;
+
+    if keys.is_empty() {
+        println!(
+            "Profile {}: {} variables",
+            style(profile).yellow(),
+            vars.len()
+        );
+        if !confirm_removal(args, &format!("profile {}", profile))? {
+            return Ok(());
+        }
+        delete_profile(&ctx.base_dir, profile)?;
+        println!(
+            "{} profile {} deleted",
+            style(BULLET).green().bold(),
+            profile
+        );
+        return Ok(());
+    }
+
+    let missing: Vec<&str> = keys
+        .iter()
+        .filter(|key| !vars.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Profile '{}' has no variable {}",
+            profile,
+            missing.join(", ")
+        ));
+    }
+    if !confirm_removal(
+        args,
+        &format!("{} from profile {}", keys.join(", "), profile),
+    )? {
+        return Ok(());
+    }
+    for key in keys {
+        vars.remove(key);
+    }
+    save_profile(&ctx.base_dir, &ctx.cipher, profile, &vars)?;
+    println!(
+        "{} profile {} updated",
+        style(BULLET).green().bold(),
+        profile
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use crate::commands::run::{build_llm, complete};
+use crate::core::config::load_input_redaction;
+use crate::core::pii::redact_values;
+use crate::core::policy::load_policies;
+use crate::core::storage::{few_shot, parse_id, AppCtx};
+use crate::core::template::render;
+use crate::ui::symbols::{ERR, OK};
+use console::style;
+use std::collections::HashMap;
+
+/// Run a prompt on each of its examples as baseline cases and report the outputs that
+/// differ from the expected ones. Each case gets the other examples as `{{examples}}`,
+/// never its own.
+pub async fn run(ctx: &AppCtx, id: &str, backend: &str) -> Result<(), String> {
+    let pd = ctx.prompts().load(id)?;
+    if pd.examples.is_empty() {
+        return Err(format!(
+            "Prompt '{}' has no examples. Add some with `prompt-store edit {}`.",
+            id, id
+        ));
+    }
+    let template = ctx.prompts().expand_attachments(id, &pd.content)?;
+    load_policies()?.check_content(id, &template)?;
+    ctx.prompts().check_provider(&parse_id(id).0, backend)?;
+    let redaction = load_input_redaction()?;
+    let llm = build_llm(ctx, backend)?;
+
+    let mut failed = 0;
+    for (i, example) in pd.examples.iter().enumerate() {
+        let mut vars: HashMap<String, String> = example.vars.clone().into_iter().collect();
+        if let Some(policy) = &redaction {
+            redact_values(&mut vars, policy)?;
+        }
+        let others = pd
+            .examples
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| other);
+        vars.entry("examples".to_string())
+            .or_insert_with(|| few_shot(others).unwrap_or_default());
+
+        let output = complete(llm.as_ref(), &render(&template, &vars)).await?;
+        if normalize(&output) == normalize(&example.output) {
+            println!("{} example {}", style(OK).green(), i + 1);
+        } else {
+            failed += 1;
+            println!("{} example {}", style(ERR).red(), i + 1);
+            println!(
+                "  {} {}",
+                style("expected:").yellow(),
+                example.output.trim()
+            );
+            println!("  {} {}", style("got:").yellow(), output.trim());
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} examples did not match the expected output.",
+            failed,
+            pd.examples.len()
+        ));
+    }
+    println!(
+        "{} All {} examples matched",
+        style(OK).green(),
+        pd.examples.len()
+    );
+    Ok(())
+}
+
+/// Ignores differences in surrounding and repeated whitespace.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
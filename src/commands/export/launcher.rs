@@ -0,0 +1,133 @@
+//! Launcher integrations (Raycast, Alfred, ...): each prompt becomes an action calling
+//! `prompt-store` to copy or run it. Only titles are written out; the prompt contents
+//! stay in the encrypted store.
+
+use crate::core::bundle::Bundle;
+use crate::core::utils::slugify;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// A launcher action and the command it runs.
+struct Action {
+    /// File name friendly identifier, unique within the export.
+    name: String,
+    id: String,
+    title: String,
+    kind: &'static str,
+    tags: Vec<String>,
+    copy: Option<String>,
+    run: Option<String>,
+}
+
+/// Lists the actions of a bundle: prompts can be copied (and run when a `backend`
+/// is given), chains can be run.
+fn actions(bundle: &Bundle, exe: &str, backend: Option<&str>) -> Vec<Action> {
+    let command = |args: &[&str]| {
+        let mut words = vec![exe];
+        words.extend_from_slice(args);
+        shell_words::join(words)
+    };
+
+    let mut actions: Vec<Action> = bundle
+        .prompts
+        .iter()
+        .map(|p| Action {
+            name: slugify(&p.id),
+            id: p.id.clone(),
+            title: p.title.clone(),
+            kind: "prompt",
+            tags: p.tags.clone(),
+            copy: Some(command(&["copy", &p.id])),
+            run: backend.map(|b| command(&["run", &p.id, "--backend", b])),
+        })
+        .collect();
+    let chains = bundle
+        .chains
+        .iter()
+        .map(|c| (&c.id, c.title.clone()))
+        .chain(
+            bundle
+                .chain_definitions
+                .iter()
+                .map(|d| (&d.id, d.id.clone())),
+        );
+    for (id, title) in chains {
+        actions.push(Action {
+            name: format!("chain-{}", slugify(id)),
+            id: id.clone(),
+            title,
+            kind: "chain",
+            tags: Vec::new(),
+            copy: None,
+            run: Some(command(&["chain", "run", id])),
+        });
+    }
+    actions
+}
+
+/// Builds the generic quick actions JSON: one entry per prompt or chain with its title
+/// and the commands to copy or run it.
+pub fn quick_actions(bundle: &Bundle, exe: &str, backend: Option<&str>) -> Value {
+    let actions = actions(bundle, exe, backend)
+        .into_iter()
+        .map(|a| {
+            let mut action = json!({
+                "id": a.id,
+                "title": a.title,
+                "kind": a.kind,
+                "tags": a.tags,
+            });
+            if let Some(copy) = a.copy {
+                action["copy"] = json!(copy);
+            }
+            if let Some(run) = a.run {
+                action["run"] = json!(run);
+            }
+            action
+        })
+        .collect();
+    Value::Array(actions)
+}
+
+/// Writes one Raycast script command per action into `dir`. Returns the number of scripts.
+pub fn write_raycast(
+    dir: &Path,
+    bundle: &Bundle,
+    exe: &str,
+    backend: Option<&str>,
+) -> Result<usize, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    let mut written = 0;
+    for action in actions(bundle, exe, backend) {
+        let scripts = [
+            ("copy", "Copy", "silent", action.copy),
+            ("run", "Run", "fullOutput", action.run),
+        ];
+        for (verb, label, mode, command) in scripts {
+            let Some(command) = command else { continue };
+            let script = format!(
+                "#!/bin/bash\n\n\
+                 # @raycast.schemaVersion 1\n\
+                 # @raycast.title {} {}\n\
+                 # @raycast.mode {}\n\
+                 # @raycast.packageName Prompt Store\n\n\
+                 {}\n",
+                label,
+                action.title.replace('\n', " "),
+                mode,
+                command
+            );
+            let path = dir.join(format!("{}-{}.sh", verb, action.name));
+            fs::write(&path, script).map_err(|e| format!("Write error: {}", e))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o700))
+                    .map_err(|e| format!("Unable to make {} executable: {}", path.display(), e))?;
+            }
+            written += 1;
+        }
+    }
+    Ok(written)
+}
@@ -0,0 +1,188 @@
+mod launcher;
+mod plaintext;
+mod vscode;
+
+use crate::cli::ExportFormat;
+use crate::core::bundle::{Bundle, BundledChain, BundledChainDefinition};
+use crate::core::crypto::encrypt_to_base64;
+use crate::core::storage::{modified_at, AppCtx};
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::env;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Options of a plaintext export (`--plaintext`).
+pub struct PlaintextOptions {
+    /// Sign the manifest with the Ed25519 key of the store.
+    pub sign: bool,
+    /// Confirmed on the command line rather than at a prompt.
+    pub yes: bool,
+}
+
+/// Export prompts and chains from the default workspace for personal backup.
+/// The output file is encrypted with the user's local master key, unless it is
+/// written for another tool (e.g., VS Code snippets, launchers) or as a plaintext
+/// dump. With `attachments`, bundles also carry the attachments of the prompts.
+pub fn run(
+    ctx: &AppCtx,
+    ids: Option<&str>,
+    out_path: &str,
+    format: ExportFormat,
+    backend: Option<&str>,
+    attachments: bool,
+    plaintext: Option<PlaintextOptions>,
+) -> Result<(), String> {
+    if attachments && format != ExportFormat::Bundle {
+        return Err("--attachments only applies to the bundle format.".to_string());
+    }
+    if plaintext.is_some() && format != ExportFormat::Bundle {
+        return Err("--plaintext only applies to the bundle format.".to_string());
+    }
+    let prompts = ctx.prompts();
+    let mut bundle = Bundle::default();
+
+    if let Some(id_list_str) = ids {
+        // Export specific prompts or chains by ID
+        let id_list: Vec<&str> = id_list_str.split(',').map(|s| s.trim()).collect();
+        for id in id_list {
+            if prompts.exists(id) {
+                let mut pd = prompts.load(id)?;
+                if attachments {
+                    pd.attachments = prompts.inline_attachments(id)?;
+                }
+                bundle.prompts.push(pd);
+            } else if prompts.chain_dir(id).join("chain.meta").exists() {
+                bundle.chains.push(BundledChain::load(&prompts, id)?);
+            } else if prompts.chain_definition_path(id).exists() {
+                bundle.chain_definitions.push(BundledChainDefinition {
+                    id: id.to_string(),
+                    yaml: prompts.load_chain_definition(id)?,
+                    updated_at: modified_at(&prompts.chain_definition_path(id)),
+                });
+            } else {
+                return Err(format!(
+                    "Prompt or chain with ID '{}' not found in default workspace.",
+                    id
+                ));
+            }
+        }
+    } else {
+        // Export all standalone prompts and chains from the default workspace
+        for p in prompts.list("default")? {
+            if p.is_chain_step() {
+                continue;
+            }
+            let mut pd = p.data;
+            if attachments {
+                pd.attachments = prompts.inline_attachments(&p.full_id)?;
+            }
+            bundle.prompts.push(pd);
+        }
+        for (id, _) in prompts.list_chains("default")? {
+            bundle.chains.push(BundledChain::load(&prompts, &id)?);
+        }
+        for id in prompts.list_chain_definitions("default")? {
+            bundle.chain_definitions.push(BundledChainDefinition {
+                yaml: prompts.load_chain_definition(&id)?,
+                updated_at: modified_at(&prompts.chain_definition_path(&id)),
+                id,
+            });
+        }
+    }
+
+    if bundle.is_empty() {
+        return Err("No prompts found to export.".to_string());
+    }
+
+    match format {
+        ExportFormat::Bundle => {}
+        ExportFormat::VscodeSnippets => return write_snippets(&bundle, out_path),
+        ExportFormat::Raycast | ExportFormat::QuickActions => {
+            return write_launcher(&bundle, out_path, format, backend)
+        }
+    }
+
+    if let Some(opts) = plaintext {
+        return plaintext::write(ctx, &bundle, out_path, opts.sign, opts.yes);
+    }
+
+    let serialized =
+        Zeroizing::new(serde_json::to_vec(&bundle).map_err(|e| format!("Serialize error: {}", e))?);
+    let encoded = encrypt_to_base64(&ctx.cipher, &serialized)?;
+
+    fs::write(out_path, encoded).map_err(|e| format!("Write error: {}", e))?;
+    println!(
+        "{} Successfully exported {} prompts and {} chains to {}",
+        style(BULLET).green().bold(),
+        bundle.prompts.len(),
+        bundle.chains.len() + bundle.chain_definitions.len(),
+        out_path
+    );
+    Ok(())
+}
+
+/// Writes the prompts of the bundle as VS Code snippets. Chains are left out.
+fn write_snippets(bundle: &Bundle, out_path: &str) -> Result<(), String> {
+    if bundle.prompts.is_empty() {
+        return Err("No prompts found to export.".to_string());
+    }
+    let snippets = vscode::snippets(&bundle.prompts);
+    let content =
+        serde_json::to_string_pretty(&snippets).map_err(|e| format!("Serialize error: {}", e))?;
+    fs::write(out_path, content).map_err(|e| format!("Write error: {}", e))?;
+    println!(
+        "{} Exported {} prompts as VS Code snippets to {}",
+        style(BULLET).green().bold(),
+        bundle.prompts.len(),
+        out_path
+    );
+    println!(
+        "{}",
+        style("Snippets are written in plain text, not encrypted.").yellow()
+    );
+    Ok(())
+}
+
+/// Writes launcher actions for the bundle: Raycast script commands in the `out_path`
+/// directory, or the generic quick actions JSON file.
+fn write_launcher(
+    bundle: &Bundle,
+    out_path: &str,
+    format: ExportFormat,
+    backend: Option<&str>,
+) -> Result<(), String> {
+    // Absolute path, as launchers usually do not share the shell's PATH.
+    let exe = env::current_exe().map_err(|e| format!("Unable to locate prompt-store: {}", e))?;
+    let exe = exe.to_string_lossy();
+    if format == ExportFormat::Raycast {
+        let count = launcher::write_raycast(Path::new(out_path), bundle, &exe, backend)?;
+        println!(
+            "{} Wrote {} Raycast script commands to {}",
+            style(BULLET).green().bold(),
+            count,
+            out_path
+        );
+        println!(
+            "Add {} as a script directory in Raycast's extension settings.",
+            style(out_path).yellow()
+        );
+    } else {
+        let actions = launcher::quick_actions(bundle, &exe, backend);
+        let content = serde_json::to_string_pretty(&actions)
+            .map_err(|e| format!("Serialize error: {}", e))?;
+        fs::write(out_path, content).map_err(|e| format!("Write error: {}", e))?;
+        println!(
+            "{} Exported {} quick actions to {}",
+            style(BULLET).green().bold(),
+            bundle.prompts.len() + bundle.chains.len() + bundle.chain_definitions.len(),
+            out_path
+        );
+    }
+    println!(
+        "{}",
+        style("Titles are written in plain text; prompt contents stay encrypted.").yellow()
+    );
+    Ok(())
+}
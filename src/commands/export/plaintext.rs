@@ -0,0 +1,176 @@
+//! Unencrypted, canonical dump of a bundle for audits and e-discovery.
+//!
+//! The output directory holds one file per item, in canonical form (pretty JSON with
+//! sorted keys, or the YAML of chain definitions), and `MANIFEST.json`: the SHA-256 of
+//! every file, sorted by path. The same prompts always produce the same bytes. With
+//! `--sign`, `MANIFEST.sig` holds an Ed25519 signature of the manifest and
+//! `SIGNING_KEY.pub` the public key to verify it with.
+
+use crate::core::audit;
+use crate::core::bundle::Bundle;
+use crate::core::signing::sign;
+use crate::core::storage::AppCtx;
+use crate::core::utils::{ensure_dir, hex};
+use crate::ui::symbols::BULLET;
+use console::style;
+use dialoguer::Confirm;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Version of the layout of the dump, recorded in the manifest.
+const DUMP_FORMAT: u32 = 1;
+
+#[derive(Serialize)]
+struct Manifest {
+    format: u32,
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    path: String,
+    sha256: String,
+    bytes: usize,
+}
+
+/// Writes the bundle unencrypted to the `out` directory, after an explicit confirmation
+/// (or `yes`), and records the export in the audit log.
+pub(super) fn write(
+    ctx: &AppCtx,
+    bundle: &Bundle,
+    out: &str,
+    sign_manifest: bool,
+    yes: bool,
+) -> Result<(), String> {
+    let dir = Path::new(out);
+    if dir.exists()
+        && fs::read_dir(dir)
+            .map_err(|e| format!("Read dir error: {}", e))?
+            .next()
+            .is_some()
+    {
+        return Err(format!("{} is not empty.", out));
+    }
+
+    // Keyed by path, so the files and the manifest are ordered the same way every time.
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    for pd in &bundle.prompts {
+        files.insert(
+            format!("prompts/{}.json", file_name(&pd.id)?),
+            canonical_json(pd)?,
+        );
+    }
+    for chain in &bundle.chains {
+        files.insert(
+            format!("chains/{}.json", file_name(&chain.id)?),
+            canonical_json(chain)?,
+        );
+    }
+    for definition in &bundle.chain_definitions {
+        files.insert(
+            format!("chain-definitions/{}.yaml", file_name(&definition.id)?),
+            definition.yaml.clone().into_bytes(),
+        );
+    }
+
+    println!(
+        "{}",
+        style("The export is written in plain text: anyone with access to the directory can read every prompt.")
+            .yellow()
+    );
+    if !yes
+        && !Confirm::new()
+            .with_prompt(format!(
+                "Write {} files unencrypted to {}?",
+                files.len(),
+                out
+            ))
+            .default(false)
+            .interact()
+            .map_err(|e| format!("{} (use --yes to confirm without a prompt)", e))?
+    {
+        println!("{}", style("Export cancelled.").yellow());
+        return Ok(());
+    }
+
+    let manifest = Manifest {
+        format: DUMP_FORMAT,
+        files: files
+            .iter()
+            .map(|(path, data)| ManifestEntry {
+                path: path.clone(),
+                sha256: hex(&Sha256::digest(data)),
+                bytes: data.len(),
+            })
+            .collect(),
+    };
+    let manifest = canonical_json(&manifest)?;
+
+    for (path, data) in &files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent)?;
+        }
+        fs::write(&path, data).map_err(|e| format!("Write error: {}", e))?;
+    }
+    fs::write(dir.join("MANIFEST.json"), &manifest).map_err(|e| format!("Write error: {}", e))?;
+    let public_key = if sign_manifest {
+        let (signature, public_key) = sign(ctx, &manifest)?;
+        fs::write(dir.join("MANIFEST.sig"), format!("{}\n", signature))
+            .map_err(|e| format!("Write error: {}", e))?;
+        fs::write(dir.join("SIGNING_KEY.pub"), format!("{}\n", public_key))
+            .map_err(|e| format!("Write error: {}", e))?;
+        Some(public_key)
+    } else {
+        None
+    };
+
+    let out_dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    audit::record(
+        &ctx.base_dir,
+        "export.plaintext",
+        serde_json::json!({
+            "out": out_dir.display().to_string(),
+            "prompts": bundle.prompts.len(),
+            "chains": bundle.chains.len() + bundle.chain_definitions.len(),
+            "manifest_sha256": hex(&Sha256::digest(&manifest)),
+            "signing_key": &public_key,
+            "confirmed": if yes { "--yes" } else { "prompt" },
+        }),
+    )?;
+
+    println!(
+        "{} Exported {} prompts and {} chains in plain text to {}",
+        style(BULLET).green().bold(),
+        bundle.prompts.len(),
+        bundle.chains.len() + bundle.chain_definitions.len(),
+        out
+    );
+    if let Some(public_key) = public_key {
+        println!(
+            "  Manifest signed with Ed25519 key {}",
+            style(public_key).cyan()
+        );
+    }
+    Ok(())
+}
+
+/// Pretty JSON with sorted keys and a trailing newline.
+fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    // `Value` keeps object keys in a sorted map.
+    let value = serde_json::to_value(value).map_err(|e| format!("Serialize error: {}", e))?;
+    let mut json =
+        serde_json::to_vec_pretty(&value).map_err(|e| format!("Serialize error: {}", e))?;
+    json.push(b'\n');
+    Ok(json)
+}
+
+fn file_name(id: &str) -> Result<&str, String> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\', ':']) {
+        return Err(format!("'{}' cannot be used as a file name", id));
+    }
+    Ok(id)
+}
@@ -0,0 +1,58 @@
+//! Converts prompts into a VS Code snippets file (`.code-snippets`).
+
+use crate::core::storage::PromptData;
+use crate::core::utils::slugify;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Builds the snippets of the prompts, keyed by title. Each snippet is triggered by the
+/// slug of its title, and `{{var}}` placeholders become tab stops.
+pub fn snippets(prompts: &[PromptData]) -> Value {
+    let mut snippets = Map::new();
+    for pd in prompts {
+        let name = if snippets.contains_key(&pd.title) {
+            format!("{} ({})", pd.title, pd.id)
+        } else {
+            pd.title.clone()
+        };
+        let prefix = match slugify(&pd.title) {
+            slug if slug.is_empty() => pd.id.clone(),
+            slug => slug,
+        };
+        let mut snippet = json!({
+            "prefix": prefix,
+            "body": to_snippet_body(&pd.content).lines().collect::<Vec<_>>(),
+        });
+        if let Some(description) = &pd.description {
+            snippet["description"] = json!(description);
+        }
+        snippets.insert(name, snippet);
+    }
+    Value::Object(snippets)
+}
+
+/// Escapes the snippet syntax in a prompt and turns each distinct `{{var}}` into a
+/// numbered tab stop, e.g. `${1:var}`, reused when the variable appears again.
+fn to_snippet_body(content: &str) -> String {
+    let re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('$', "\\$")
+            .replace('}', "\\}")
+    };
+
+    let mut stops: HashMap<String, usize> = HashMap::new();
+    let mut body = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(content) {
+        let whole = caps.get(0).unwrap();
+        body.push_str(&escape(&content[last..whole.start()]));
+        let next = stops.len() + 1;
+        let stop = *stops.entry(caps[1].to_string()).or_insert(next);
+        body.push_str(&format!("${{{}:{}}}", stop, &caps[1]));
+        last = whole.end();
+    }
+    body.push_str(&escape(&content[last..]));
+    body
+}
@@ -0,0 +1,190 @@
+use crate::cli::RemoveArgs;
+use crate::commands::pack_logic::DeployedInfo;
+use crate::core::backup::encrypted_files;
+use crate::core::crypto::{decrypt_from_base64, encrypt_to_base64};
+use crate::core::storage::{write_atomic, AppCtx};
+use crate::ui::confirm_removal;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prune old prompt backups, orphaned chain steps and attachments, and registry clones of
+/// removed packs, once the list is confirmed (or `--force`). With `recompress`, first
+/// rewrite the encrypted files that shrink in the current format.
+pub fn run(ctx: &AppCtx, keep: usize, args: RemoveArgs, recompress: bool) -> Result<(), String> {
+    if recompress {
+        recompress_files(ctx, args.dry_run)?;
+    }
+
+    let mut stale: Vec<(PathBuf, &str)> = Vec::new();
+
+    for workspace in ctx.prompts().workspaces()? {
+        let dir = ctx.workspaces_dir.join(&workspace);
+        stale.extend(
+            old_backups(&dir, keep)?
+                .into_iter()
+                .map(|p| (p, "old backup")),
+        );
+
+        for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+            if path.is_dir() && path.file_name() != Some("chains".as_ref()) {
+                if path.extension() == Some("attachments".as_ref()) {
+                    if !path.with_extension("prompt").exists() {
+                        stale.push((path, "orphaned attachments"));
+                    }
+                } else if path.join("chain.meta").exists() {
+                    stale.extend(
+                        old_backups(&path, keep)?
+                            .into_iter()
+                            .map(|p| (p, "old backup")),
+                    );
+                } else {
+                    stale.push((path, "orphaned chain steps"));
+                }
+            }
+        }
+    }
+
+    let manifest_path = ctx.base_dir.join("deployed.json");
+    let deployed: HashMap<String, DeployedInfo> = match fs::read_to_string(&manifest_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    };
+    if ctx.registries_dir.is_dir() {
+        for entry in
+            fs::read_dir(&ctx.registries_dir).map_err(|e| format!("Read dir error: {}", e))?
+        {
+            let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+            let alias = entry.file_name().to_string_lossy().into_owned();
+            if !deployed.contains_key(&alias) {
+                stale.push((entry.path(), "unused registry clone"));
+            }
+        }
+    }
+
+    if stale.is_empty() {
+        println!("{}", style("Nothing to clean up.").green());
+        return Ok(());
+    }
+
+    let mut reclaimed = 0;
+    for (path, reason) in &stale {
+        reclaimed += disk_usage(path);
+        let rel = path.strip_prefix(&ctx.base_dir).unwrap_or(path);
+        println!("  {} {} ({})", style(BULLET).green(), rel.display(), reason);
+    }
+    println!(
+        "{} items, {} reclaimable",
+        stale.len(),
+        style(format_size(reclaimed)).yellow()
+    );
+    if !confirm_removal(args, &format!("these {} items", stale.len()))? {
+        return Ok(());
+    }
+
+    for (path, _) in &stale {
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    println!(
+        "{} Removed {} items, {} reclaimed",
+        style(OK).green(),
+        stale.len(),
+        style(format_size(reclaimed)).yellow()
+    );
+    Ok(())
+}
+
+/// Re-encrypts every file encrypted with the master key, keeping the result when it is
+/// smaller: files written before compression, or before they had their own data key.
+fn recompress_files(ctx: &AppCtx, dry_run: bool) -> Result<(), String> {
+    let mut rewritten = 0;
+    let mut saved = 0;
+    for file in encrypted_files(ctx)? {
+        let encoded = std::str::from_utf8(&file.data)
+            .map_err(|_| format!("Corrupted file: {}", file.path))?;
+        let plaintext = decrypt_from_base64(&ctx.cipher, encoded)
+            .map_err(|e| format!("{}: {}", file.path, e))?;
+        let recompressed = encrypt_to_base64(&ctx.cipher, &plaintext)?;
+        if recompressed.len() >= encoded.len() {
+            continue;
+        }
+        rewritten += 1;
+        saved += (encoded.len() - recompressed.len()) as u64;
+        if !dry_run {
+            write_atomic(&ctx.base_dir.join(&file.path), recompressed)?;
+        }
+    }
+
+    println!(
+        "{} {} {} files, {} {}",
+        style(OK).green(),
+        if dry_run {
+            "Would recompress"
+        } else {
+            "Recompressed"
+        },
+        rewritten,
+        style(format_size(saved)).yellow(),
+        if dry_run { "reclaimable" } else { "reclaimed" }
+    );
+    Ok(())
+}
+
+/// Returns the `.bak` files in `dir` beyond the `keep` most recent ones of each prompt.
+fn old_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>, String> {
+    let mut by_prompt: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // Backups are named `<local_id>.<timestamp>.bak`.
+        if let Some((local_id, _ts)) = name
+            .strip_suffix(".bak")
+            .and_then(|stem| stem.rsplit_once('.'))
+        {
+            by_prompt
+                .entry(local_id.to_string())
+                .or_default()
+                .push(path.clone());
+        }
+    }
+
+    let mut old = Vec::new();
+    for mut backups in by_prompt.into_values() {
+        // Timestamps sort lexicographically, newest last.
+        backups.sort();
+        let excess = backups.len().saturating_sub(keep);
+        old.extend(backups.into_iter().take(excess));
+    }
+    Ok(old)
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    if path.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|e| disk_usage(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    } else {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
@@ -1,35 +1,103 @@
-use crate::core::storage::{AppCtx, PromptData};
-use aes_gcm::{aead::Aead, Nonce};
-use base64::{engine::general_purpose, Engine as _};
+use crate::core::storage::{parse_id, AppCtx, DEFAULT_VARIANT};
+use crate::core::usage;
+use crate::ui::symbols::{ARROW, BULLET};
 use console::style;
-use serde_json;
-use std::fs;
 
-/// Display a prompt.
-pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID {}", id));
-    }
-
-    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
-    let decoded = general_purpose::STANDARD
-        .decode(encoded.trim_end())
-        .map_err(|_| "Corrupted data".to_string())?;
-    if decoded.len() < 12 {
-        return Err("Corrupted data".to_string());
-    }
-
-    let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-    let plaintext = ctx
-        .cipher
-        .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-        .map_err(|_| "Decrypt error".to_string())?;
-    let pd: PromptData =
-        serde_json::from_slice(&plaintext).map_err(|_| "Invalid JSON".to_string())?;
+/// Display a prompt, with its examples if `examples` is set, in a language if `lang` is set
+/// or as one of its variants if `variant` is set. Chain steps can be addressed by title.
+pub fn run(
+    ctx: &AppCtx,
+    id: &str,
+    examples: bool,
+    lang: Option<&str>,
+    variant: Option<&str>,
+) -> Result<(), String> {
+    let id = &ctx.prompts().resolve_step(id)?;
+    let pd = ctx.prompts().load(id)?;
 
     println!("{} {}", style("Title:").green().bold(), pd.title);
+    if let Some(description) = &pd.description {
+        println!("{} {}", style("Description:").green().bold(), description);
+    }
+    let attachments = ctx.prompts().list_attachments(id)?;
+    if !attachments.is_empty() {
+        println!(
+            "{} {}",
+            style("Attachments:").green().bold(),
+            attachments.join(", ")
+        );
+    }
+    if !pd.translations.is_empty() {
+        let codes: Vec<&str> = pd.translations.keys().map(String::as_str).collect();
+        println!(
+            "{} {}",
+            style("Translations:").green().bold(),
+            codes.join(", ")
+        );
+    }
+    if !pd.variants.is_empty() {
+        let (workspace, local_id) = parse_id(id);
+        let runs = usage::for_workspace(&ctx.base_dir, &workspace)
+            .remove(&local_id)
+            .map(|u| u.variants)
+            .unwrap_or_default();
+        let names: Vec<String> = std::iter::once(DEFAULT_VARIANT)
+            .chain(pd.variants.keys().map(String::as_str))
+            .map(|name| match runs.get(name) {
+                Some(&count) => {
+                    format!(
+                        "{} ({} run{})",
+                        name,
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    )
+                }
+                None => name.to_string(),
+            })
+            .collect();
+        println!("{} {}", style("Variants:").green().bold(), names.join(", "));
+    }
+    if let Some(canary) = &pd.canary {
+        println!(
+            "{} {}% of library runs since {} (see `prompt-store canary`)",
+            style("Canary:").green().bold(),
+            canary.percent,
+            canary.created_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+    let content = match (variant, lang.map(|lang| (lang, pd.translation(lang)))) {
+        (Some(variant), _) => pd
+            .variant_content(variant)
+            .ok_or_else(|| format!("Prompt '{}' has no variant '{}'", id, variant))?,
+        (None, Some((_, Some(translation)))) => translation,
+        (None, Some((lang, None))) => {
+            println!(
+                "{}",
+                style(format!(
+                    "No '{}' translation, showing the default content.",
+                    lang
+                ))
+                .yellow()
+            );
+            &pd.content
+        }
+        (None, None) => &pd.content,
+    };
     println!("{}", style("Content:").green().bold());
-    print!("{}", pd.content);
+    print!("{}", content);
+
+    if examples {
+        println!();
+        if pd.examples.is_empty() {
+            println!("{}", style("No examples.").yellow());
+        }
+        for (i, example) in pd.examples.iter().enumerate() {
+            println!("{}", style(format!("Example {}:", i + 1)).green().bold());
+            for (key, value) in &example.vars {
+                println!("  {} {} = {}", style(BULLET).green(), key, value);
+            }
+            println!("  {} {}", style(ARROW).green(), example.output);
+        }
+    }
     Ok(())
 }
@@ -1,33 +1,28 @@
-use crate::core::storage::{parse_id, AppCtx};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
 use console::style;
-use std::fs;
 
-/// List backups for a prompt ID.
+/// List the versions of a prompt: its backups, oldest first, then the current content.
 pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
-    let (workspace, local_id) = parse_id(id);
-    let workspace_path = ctx.workspaces_dir.join(workspace);
-    let mut backups = Vec::new();
-
-    if workspace_path.exists() {
-        for entry in fs::read_dir(&workspace_path).map_err(|e| format!("Read dir error: {}", e))? {
-            let ent = entry.map_err(|e| format!("Dir read error: {}", e))?;
-            let fname = ent.file_name();
-            if let Some(name) = fname.to_str() {
-                if name.starts_with(&format!("{}.", local_id)) && name.ends_with(".bak") {
-                    backups.push(name.to_string());
-                }
-            }
-        }
-    }
+    let backups = ctx.prompts().list_backups(id)?;
 
     if backups.is_empty() {
         println!("{}", style("No backups").yellow());
     } else {
-        backups.sort();
-        println!("{}", style("Backups:").green().bold());
-        for b in backups {
-            println!("  {} {}", style("•").green(), b);
+        println!("{}", style("Versions:").green().bold());
+        for (i, ts) in backups.iter().enumerate() {
+            println!(
+                "  {} v{}  {}",
+                style(BULLET).green(),
+                i + 1,
+                style(ts).yellow()
+            );
         }
+        println!(
+            "  {} v{}  current",
+            style(BULLET).green(),
+            backups.len() + 1
+        );
     }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,160 @@
+//! Reads prompts from a CSV file, such as a spreadsheet export.
+//!
+//! Columns named `title`, `content`, `tags` and `description` (any case) are used
+//! directly; other names can be mapped with `<column>=<field>`. Tags are separated by
+//! commas or semicolons.
+
+use crate::core::storage::PromptData;
+use std::collections::{BTreeMap, HashMap};
+
+/// A prompt field filled from a CSV column.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Field {
+    Title,
+    Content,
+    Tags,
+    Description,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "title" => Some(Self::Title),
+            "content" => Some(Self::Content),
+            "tags" => Some(Self::Tags),
+            "description" => Some(Self::Description),
+            _ => None,
+        }
+    }
+}
+
+/// A row that could not be turned into a prompt.
+pub struct Rejected {
+    /// Line of the row in the file.
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Reads the prompts of a CSV file. Prompts are returned without ID; rows missing a
+/// title or content, or repeating an earlier title, are rejected.
+pub fn read(file: &str, mappings: &[String]) -> Result<(Vec<PromptData>, Vec<Rejected>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(file)
+        .map_err(|e| format!("Read error: {}", e))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .clone();
+    let columns = map_columns(&headers, mappings)?;
+
+    let mut prompts: Vec<PromptData> = Vec::new();
+    let mut rejected = Vec::new();
+    let mut titles: HashMap<String, u64> = HashMap::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                rejected.push(Rejected {
+                    line: e.position().map(|p| p.line()).unwrap_or_default(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let value = |field: Field| {
+            columns
+                .get(&field)
+                .and_then(|&i| record.get(i))
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+        if record.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+
+        let title = value(Field::Title);
+        let content = value(Field::Content);
+        let reason = if title.is_empty() {
+            Some("missing title".to_string())
+        } else if content.is_empty() {
+            Some("missing content".to_string())
+        } else {
+            titles
+                .get(&title.to_lowercase())
+                .map(|first| format!("duplicate title '{}' (line {})", title, first))
+        };
+        if let Some(reason) = reason {
+            rejected.push(Rejected { line, reason });
+            continue;
+        }
+        titles.insert(title.to_lowercase(), line);
+
+        let description = value(Field::Description);
+        prompts.push(PromptData {
+            id: String::new(),
+            title: title.to_string(),
+            content: content.to_string(),
+            translations: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            canary: None,
+            tags: value(Field::Tags)
+                .split([',', ';'])
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect(),
+            description: (!description.is_empty()).then(|| description.to_string()),
+            schema: None,
+            examples: Vec::new(),
+            updated_at: None,
+            attachments: Vec::new(),
+        });
+    }
+    Ok((prompts, rejected))
+}
+
+/// Finds the column of each field, applying the `<column>=<field>` mappings first.
+fn map_columns(
+    headers: &csv::StringRecord,
+    mappings: &[String],
+) -> Result<HashMap<Field, usize>, String> {
+    let mut renamed: HashMap<String, Field> = HashMap::new();
+    for mapping in mappings {
+        let (column, field) = mapping
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Invalid mapping '{}'. Use <column>=<field>.", mapping))?;
+        let field = Field::parse(field).ok_or_else(|| {
+            format!(
+                "Unknown field '{}'. Use title, content, tags or description.",
+                field
+            )
+        })?;
+        renamed.insert(column.trim().to_lowercase(), field);
+    }
+
+    let mut columns = HashMap::new();
+    for (i, header) in headers.iter().enumerate() {
+        let header = header.trim().to_lowercase();
+        let field = match renamed.get(&header) {
+            Some(&field) => Some(field),
+            // A column mapped elsewhere no longer fills the field it is named after.
+            None if renamed.values().any(|&f| Some(f) == Field::parse(&header)) => None,
+            None => Field::parse(&header),
+        };
+        if let Some(field) = field {
+            columns.entry(field).or_insert(i);
+        }
+    }
+
+    for (field, name) in [(Field::Title, "title"), (Field::Content, "content")] {
+        if !columns.contains_key(&field) {
+            return Err(format!(
+                "No '{}' column found. Use --map \"<column>={}\" to pick one.",
+                name, name
+            ));
+        }
+    }
+    Ok(columns)
+}
@@ -0,0 +1,179 @@
+//! Reads prompts from a folder of markdown notes, such as an Obsidian vault or a
+//! Notion export.
+//!
+//! A note is a prompt when its frontmatter has `prompt: true` or when it is tagged with
+//! the prompt tag, in its frontmatter `tags` or inline (`#prompt`). The note body becomes
+//! the content; `title` (or the file name), `tags` and `description` are carried over.
+//!
+//! The prompt ID of each note is remembered in `imports.json`, so importing the vault
+//! again updates those prompts instead of duplicating them.
+
+use crate::core::storage::{modified_at, AppCtx, PromptData};
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File of the store remembering the prompt ID of each imported note.
+pub const MAPPING_FILE: &str = "imports.json";
+
+/// A note selected as a prompt, with its path relative to the vault.
+pub struct Note {
+    pub path: String,
+    pub prompt: PromptData,
+}
+
+/// Reads the prompt notes of a vault. IDs are left empty.
+pub fn read(vault: &Path, prompt_tag: &str) -> Result<Vec<Note>, String> {
+    let mut files = Vec::new();
+    collect(vault, &mut files)?;
+    files.sort();
+
+    let inline_tag = Regex::new(r"(?:^|\s)#([\w/-]+)").map_err(|e| e.to_string())?;
+    let mut notes = Vec::new();
+    for file in files {
+        let text = fs::read_to_string(&file).map_err(|e| format!("Read error: {}", e))?;
+        let (frontmatter, body) = split_frontmatter(&text);
+        let meta: Value = match frontmatter {
+            Some(yaml) => serde_yaml::from_str(yaml)
+                .map_err(|e| format!("Invalid frontmatter in {}: {}", file.display(), e))?,
+            None => Value::Null,
+        };
+
+        let mut tags = frontmatter_tags(&meta);
+        for cap in inline_tag.captures_iter(body) {
+            if !tags.contains(&cap[1].to_string()) {
+                tags.push(cap[1].to_string());
+            }
+        }
+        let flagged = meta.get("prompt").and_then(Value::as_bool).unwrap_or(false);
+        if !flagged && !tags.iter().any(|t| t.eq_ignore_ascii_case(prompt_tag)) {
+            continue;
+        }
+
+        let text_field = |key: &str| {
+            meta.get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+        let title = text_field("title").unwrap_or_else(|| {
+            file.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        let path = file
+            .strip_prefix(vault)
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        notes.push(Note {
+            path,
+            prompt: PromptData {
+                id: String::new(),
+                title,
+                content: body.trim().to_string(),
+                translations: BTreeMap::new(),
+                variants: BTreeMap::new(),
+                canary: None,
+                tags,
+                description: text_field("description"),
+                schema: None,
+                examples: Vec::new(),
+                updated_at: modified_at(&file),
+                attachments: Vec::new(),
+            },
+        });
+    }
+    Ok(notes)
+}
+
+/// Returns the prompt IDs of the notes of a vault imported before, by note path.
+pub fn load_mapping(ctx: &AppCtx, vault: &Path) -> Result<HashMap<String, String>, String> {
+    Ok(load_mappings(ctx)?
+        .remove(&vault_key(vault)?)
+        .unwrap_or_default())
+}
+
+/// Records the prompt IDs of the notes of a vault.
+pub fn save_mapping(
+    ctx: &AppCtx,
+    vault: &Path,
+    mapping: HashMap<String, String>,
+) -> Result<(), String> {
+    let mut mappings = load_mappings(ctx)?;
+    mappings.insert(vault_key(vault)?, mapping);
+    let content = serde_json::to_string_pretty(&mappings).map_err(|e| e.to_string())?;
+    fs::write(ctx.base_dir.join(MAPPING_FILE), content).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn load_mappings(ctx: &AppCtx) -> Result<HashMap<String, HashMap<String, String>>, String> {
+    let path = ctx.base_dir.join(MAPPING_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    Ok(serde_json::from_str(&content).unwrap_or_default())
+}
+
+/// Identifies a vault by its absolute path.
+fn vault_key(vault: &Path) -> Result<String, String> {
+    let vault = vault
+        .canonicalize()
+        .map_err(|e| format!("Unable to read {}: {}", vault.display(), e))?;
+    Ok(vault.to_string_lossy().into_owned())
+}
+
+/// Splits the YAML frontmatter (between `---` lines at the top) from the body.
+fn split_frontmatter(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// Reads `tags` as a list or a comma-separated string, without leading `#`.
+fn frontmatter_tags(meta: &Value) -> Vec<String> {
+    let tags: Vec<&str> = match meta.get("tags") {
+        Some(Value::Sequence(items)) => items.iter().filter_map(Value::as_str).collect(),
+        Some(Value::String(list)) => list.split(',').collect(),
+        _ => Vec::new(),
+    };
+    tags.into_iter()
+        .map(|t| t.trim().trim_start_matches('#').to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Recursively lists the markdown files, skipping hidden folders such as `.obsidian`.
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        if path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
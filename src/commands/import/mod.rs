@@ -0,0 +1,485 @@
+mod csv;
+mod markdown;
+
+use crate::cli::{ImportFormat, OnConflict, OnDuplicate};
+use crate::commands::duplicates::should_link;
+use crate::commands::pack_logic::load_manifest;
+use crate::core::bundle::{prompt_matches, same_prompt, Bundle, BundledChain, ItemFilter};
+use crate::core::content_index::{content_hash, ContentIndex, CONTENT_INDEX_FILE};
+use crate::core::crypto::decrypt_from_base64;
+use crate::core::journal::JournalEntry;
+use crate::core::{
+    storage::{modified_at, parse_id, AppCtx, ChainData},
+    utils::new_id,
+};
+use crate::ui::progress::bar;
+use crate::ui::symbols::{BULLET, ERR};
+use chrono::{DateTime, Utc};
+use console::style;
+use indicatif::ProgressBar;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// What happens to one item of the bundle.
+enum Action {
+    Create,
+    Update,
+    NewId,
+    /// Link to the identical prompt with this ID instead of storing a copy.
+    Link(String),
+    Skip(&'static str),
+}
+
+/// Options of the `import` command.
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    /// CSV column mappings, as `<column>=<field>`.
+    pub mappings: Vec<String>,
+    /// Tag marking markdown notes as prompts.
+    pub prompt_tag: String,
+    /// Defaults to `new-id`, or `overwrite` for markdown notes imported before.
+    pub on_conflict: Option<OnConflict>,
+    /// What to do with prompts identical to one stored under another ID.
+    pub on_duplicate: OnDuplicate,
+    pub dry_run: bool,
+    /// Filters selecting the items to import, all of which must match.
+    pub only: Vec<String>,
+    /// List the matching items instead of importing them.
+    pub list: bool,
+}
+
+/// Import prompts and chains from encrypted file, or prompts from a CSV file or a
+/// folder of markdown notes.
+///
+/// Items whose ID is already taken are handled according to `on_conflict`; YAML
+/// chains referring to a prompt imported under a new ID are updated to use it.
+/// CSV rows get new IDs, except those identical to an existing prompt, which are skipped.
+/// Notes imported before update their prompt, whose ID is kept in `imports.json`.
+///
+/// Prompts identical to one stored under another ID, found through the content index,
+/// are linked to it rather than copied (see `on_duplicate`): a link is written under
+/// their ID when it is free, and chains refer to the existing prompt otherwise. Prompts
+/// of deployed packs are never linked to, as they change with pack updates.
+pub fn run(ctx: &AppCtx, file: &str, opts: &ImportOptions) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    let filters = opts
+        .only
+        .iter()
+        .map(|f| f.parse())
+        .collect::<Result<Vec<ItemFilter>, String>>()?;
+    // Vault paths of the imported notes, in the order of `bundle.prompts`.
+    let mut note_paths = Vec::new();
+    let (mut bundle, rejected) = match opts.format {
+        ImportFormat::Bundle => {
+            let encoded = fs::read_to_string(file).map_err(|e| format!("Read error: {}", e))?;
+            let plaintext = decrypt_from_base64(&ctx.cipher, &encoded)?;
+            (Bundle::from_json(&plaintext)?, Vec::new())
+        }
+        ImportFormat::Csv => {
+            let (mut rows, rejected) = csv::read(file, &opts.mappings)?;
+            let existing = prompts.list("default")?;
+            for row in rows.iter_mut() {
+                if let Some(p) = existing.iter().find(|p| same_prompt(&p.data, row)) {
+                    row.id = p.full_id.clone();
+                }
+            }
+            let bundle = Bundle {
+                prompts: rows,
+                ..Default::default()
+            };
+            (bundle, rejected)
+        }
+        ImportFormat::Markdown => {
+            let vault = Path::new(file);
+            let mapping = markdown::load_mapping(ctx, vault)?;
+            let mut notes = markdown::read(vault, &opts.prompt_tag)?;
+            notes.retain(|n| prompt_matches(&filters, &n.prompt));
+            for note in notes.iter_mut() {
+                if let Some(id) = mapping.get(&note.path).filter(|id| prompts.exists(id)) {
+                    note.prompt.id = id.clone();
+                }
+            }
+            note_paths = notes.iter().map(|n| n.path.clone()).collect();
+            let bundle = Bundle {
+                prompts: notes.into_iter().map(|n| n.prompt).collect(),
+                ..Default::default()
+            };
+            (bundle, Vec::new())
+        }
+    };
+    bundle.retain_matching(&filters);
+
+    if opts.list {
+        print_contents(&bundle);
+        print_rejected(&rejected);
+        return Ok(());
+    }
+    let on_conflict = opts.on_conflict.unwrap_or(match opts.format {
+        ImportFormat::Markdown => OnConflict::Overwrite,
+        _ => OnConflict::NewId,
+    });
+    let dry_run = opts.dry_run;
+    let mut target_ids = Vec::new();
+
+    let default_dir = ctx.workspaces_dir.join("default");
+    let mut remapped: HashMap<String, String> = HashMap::new();
+    let mut summary = Summary::default();
+    let mut index = ContentIndex::load(ctx)?;
+    let packs = load_manifest(ctx)?;
+    // A dry run lists every item instead.
+    let progress = if dry_run {
+        ProgressBar::hidden()
+    } else {
+        bar(
+            bundle.prompts.len() + bundle.chains.len() + bundle.chain_definitions.len(),
+            "Importing",
+        )
+    };
+
+    let mut journal = JournalEntry::new(format!("import {}", file));
+
+    for mut pd in bundle.prompts {
+        // Nothing refers to a generated ID, so a duplicate needs no link under it.
+        let generated = pd.id.is_empty();
+        if generated {
+            pd.id = new_id(&default_dir, &pd.title)?;
+        }
+        let local = if prompts.exists(&pd.id) {
+            let local = prompts.load(&pd.id)?;
+            let updated_at = local
+                .updated_at
+                .or_else(|| modified_at(&ctx.prompt_path(&pd.id)));
+            Some((same_prompt(&local, &pd), updated_at))
+        } else {
+            None
+        };
+        let mut action = decide(on_conflict, local, pd.updated_at);
+        let hash = content_hash(&pd)?;
+        // Attachments are not part of the hash, so prompts carrying some are copied.
+        if matches!(action, Action::Create | Action::NewId) && pd.attachments.is_empty() {
+            let existing = index
+                .matches(&hash)
+                .find(|id| !packs.contains_key(&parse_id(id).0));
+            if let Some(existing) = existing {
+                let link = progress
+                    .suspend(|| should_link(opts.on_duplicate, &pd.id, existing, dry_run))?;
+                if link {
+                    action = Action::Link(existing.to_string());
+                }
+            }
+        }
+        let mut target_id = pd.id.clone();
+        match &action {
+            Action::NewId => {
+                while prompts.exists(&target_id) {
+                    target_id = new_id(&default_dir, &pd.title)?;
+                }
+                remapped.insert(pd.id.clone(), target_id.clone());
+            }
+            Action::Link(existing) if generated || prompts.exists(&pd.id) => {
+                target_id = existing.clone();
+                remapped.insert(pd.id.clone(), target_id.clone());
+            }
+            _ => {}
+        }
+        summary.record(&action, "prompt", &pd.id, &target_id, dry_run);
+        if !dry_run && !matches!(action, Action::Skip(_)) {
+            journal.save(ctx, &ctx.prompt_path(&target_id))?;
+            journal.save(ctx, &prompts.attachments_dir(&target_id))?;
+            match &action {
+                Action::Skip(_) => {}
+                Action::Link(existing) => {
+                    if target_id != *existing {
+                        prompts.save_link(&target_id, existing)?;
+                        index.insert(ctx, &target_id, None);
+                    }
+                }
+                _ => {
+                    pd.id = target_id.clone();
+                    prompts.save_with_attachments(&target_id, &pd)?;
+                    index.insert(ctx, &target_id, Some(hash));
+                }
+            }
+        }
+        target_ids.push(target_id);
+        progress.inc(1);
+    }
+    if !dry_run {
+        journal.save(ctx, &ctx.base_dir.join(CONTENT_INDEX_FILE))?;
+        index.save(ctx)?;
+    }
+
+    for chain in &bundle.chains {
+        progress.inc(1);
+        let local = if prompts.chain_dir(&chain.id).join("chain.meta").exists() {
+            let local = BundledChain::load(&prompts, &chain.id)?;
+            Some((local.same_content(chain), local.updated_at))
+        } else {
+            None
+        };
+        let action = decide(on_conflict, local, chain.updated_at);
+        let mut target_id = chain.id.clone();
+        if let Action::NewId = action {
+            while prompts.chain_dir(&target_id).exists() {
+                target_id = new_id(&default_dir, &chain.title)?;
+            }
+        }
+        summary.record(&action, "chain", &chain.id, &target_id, dry_run);
+        if dry_run || matches!(action, Action::Skip(_)) {
+            continue;
+        }
+        journal.save(ctx, &prompts.chain_dir(&target_id))?;
+        if let Action::Update = action {
+            // Replace the previous steps entirely, their number may differ.
+            fs::remove_dir_all(prompts.chain_dir(&target_id))
+                .map_err(|e| format!("Delete error: {}", e))?;
+        }
+        prompts.save_chain(
+            &target_id,
+            &ChainData {
+                id: target_id.clone(),
+                title: chain.title.clone(),
+                tags: chain.tags.clone(),
+            },
+        )?;
+        for step in &chain.steps {
+            let step_no = step.id.rsplit('/').next().unwrap_or(&step.id);
+            let mut pd = step.clone();
+            pd.id = format!("{}/{}", target_id, step_no);
+            prompts.save(&pd.id, &pd)?;
+        }
+    }
+
+    for definition in &bundle.chain_definitions {
+        progress.inc(1);
+        let yaml = remap_prompt_refs(&definition.yaml, &remapped)?;
+        let path = prompts.chain_definition_path(&definition.id);
+        let local = if path.exists() {
+            let local = prompts.load_chain_definition(&definition.id)?;
+            Some((local == yaml, modified_at(&path)))
+        } else {
+            None
+        };
+        let action = decide(on_conflict, local, definition.updated_at);
+        let mut target_id = definition.id.clone();
+        if let Action::NewId = action {
+            while prompts.chain_definition_path(&target_id).exists() {
+                target_id = new_id(&default_dir, &definition.id)?;
+            }
+        }
+        summary.record(&action, "chain", &definition.id, &target_id, dry_run);
+        if !dry_run && !matches!(action, Action::Skip(_)) {
+            journal.save(ctx, &prompts.chain_definition_path(&target_id))?;
+            prompts.save_chain_definition(&target_id, &yaml)?;
+            // Keep the original modification time so later merges compare correctly.
+            if let Some(updated_at) = definition.updated_at {
+                fs::File::options()
+                    .write(true)
+                    .open(prompts.chain_definition_path(&target_id))
+                    .and_then(|f| f.set_modified(SystemTime::from(updated_at)))
+                    .ok();
+            }
+        }
+    }
+
+    if opts.format == ImportFormat::Markdown && !dry_run {
+        let vault = Path::new(file);
+        let mut mapping = markdown::load_mapping(ctx, vault)?;
+        mapping.extend(note_paths.into_iter().zip(target_ids));
+        journal.save(ctx, &ctx.base_dir.join(markdown::MAPPING_FILE))?;
+        markdown::save_mapping(ctx, vault, mapping)?;
+    }
+
+    progress.finish_and_clear();
+    journal.commit(ctx)?;
+
+    print_rejected(&rejected);
+    println!(
+        "{} {}{} created, {} updated, {} linked, {} skipped",
+        style(BULLET).green().bold(),
+        if dry_run { "Dry run: " } else { "" },
+        summary.created,
+        summary.updated,
+        summary.linked,
+        summary.skipped
+    );
+    Ok(())
+}
+
+/// Prints the items of a bundle.
+fn print_contents(bundle: &Bundle) {
+    if bundle.is_empty() {
+        println!("{}", style("No matching prompts or chains.").yellow());
+        return;
+    }
+    let updated = |at: Option<DateTime<Utc>>| {
+        at.map(|at| format!(" ({})", at.format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default()
+    };
+    for p in &bundle.prompts {
+        let tags = if p.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", p.tags.join(", "))
+        };
+        println!(
+            "  {} {} - {}{}{}",
+            style(BULLET).green(),
+            style(if p.id.is_empty() { "(new)" } else { &p.id }).yellow(),
+            p.title,
+            tags,
+            style(updated(p.updated_at)).dim()
+        );
+    }
+    for c in &bundle.chains {
+        println!(
+            "  {} {} (Chain, {} steps) - {}{}",
+            style(BULLET).blue(),
+            style(&c.id).yellow(),
+            c.steps.len(),
+            c.title,
+            style(updated(c.updated_at)).dim()
+        );
+    }
+    for d in &bundle.chain_definitions {
+        println!(
+            "  {} {} (YAML chain){}",
+            style(BULLET).blue(),
+            style(&d.id).yellow(),
+            style(updated(d.updated_at)).dim()
+        );
+    }
+}
+
+/// Prints the CSV rows that could not be imported.
+fn print_rejected(rejected: &[csv::Rejected]) {
+    if rejected.is_empty() {
+        return;
+    }
+    println!(
+        "{} {} rows rejected:",
+        style(ERR).red(),
+        style(rejected.len()).yellow()
+    );
+    for row in rejected {
+        println!("  line {}: {}", row.line, row.reason);
+    }
+}
+
+/// Chooses what to do with an incoming item. `local` is `(identical, updated_at)` of the
+/// item already stored under the same ID, if any.
+fn decide(
+    on_conflict: OnConflict,
+    local: Option<(bool, Option<DateTime<Utc>>)>,
+    incoming_updated_at: Option<DateTime<Utc>>,
+) -> Action {
+    let Some((identical, local_updated_at)) = local else {
+        return Action::Create;
+    };
+    if identical {
+        return Action::Skip("unchanged");
+    }
+    match on_conflict {
+        OnConflict::Skip => Action::Skip("already exists"),
+        OnConflict::Overwrite => Action::Update,
+        OnConflict::NewId => Action::NewId,
+        OnConflict::Merge => match (incoming_updated_at, local_updated_at) {
+            (Some(incoming), Some(local)) if incoming > local => Action::Update,
+            (Some(_), None) => Action::Update,
+            _ => Action::Skip("local copy is newer"),
+        },
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    created: usize,
+    updated: usize,
+    linked: usize,
+    skipped: usize,
+}
+
+impl Summary {
+    /// Counts an action, and describes it when previewing.
+    fn record(&mut self, action: &Action, kind: &str, id: &str, target_id: &str, dry_run: bool) {
+        let line = match action {
+            Action::Create => {
+                self.created += 1;
+                format!("{} {} {} (create)", style("+").green(), kind, id)
+            }
+            Action::NewId => {
+                self.created += 1;
+                format!(
+                    "{} {} {} as {} (new ID)",
+                    style("+").green(),
+                    kind,
+                    id,
+                    target_id
+                )
+            }
+            Action::Update => {
+                self.updated += 1;
+                format!("{} {} {} (update)", style("~").yellow(), kind, id)
+            }
+            Action::Link(existing) => {
+                self.linked += 1;
+                format!(
+                    "{} {} {} (link to identical {})",
+                    style("=").cyan(),
+                    kind,
+                    id,
+                    existing
+                )
+            }
+            Action::Skip(reason) => {
+                self.skipped += 1;
+                format!("{} {} {} (skip: {})", style("-").dim(), kind, id, reason)
+            }
+        };
+        if dry_run {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// Points the `prompt:` fields of a YAML chain at the new IDs of remapped prompts.
+/// The YAML is returned untouched when nothing it uses was remapped.
+fn remap_prompt_refs(yaml: &str, remapped: &HashMap<String, String>) -> Result<String, String> {
+    if remapped.is_empty() {
+        return Ok(yaml.to_string());
+    }
+    let mut doc: Value =
+        serde_yaml::from_str(yaml).map_err(|e| format!("Failed to parse chain file: {}", e))?;
+    if !remap_value(&mut doc, remapped) {
+        return Ok(yaml.to_string());
+    }
+    serde_yaml::to_string(&doc).map_err(|e| format!("Serialize error: {}", e))
+}
+
+fn remap_value(value: &mut Value, remapped: &HashMap<String, String>) -> bool {
+    match value {
+        Value::Mapping(map) => {
+            let mut changed = false;
+            for (key, v) in map.iter_mut() {
+                let new_id = match (key.as_str(), v.as_str()) {
+                    (Some("prompt"), Some(id)) => remapped.get(id),
+                    _ => None,
+                };
+                if let Some(new_id) = new_id {
+                    *v = Value::String(new_id.clone());
+                    changed = true;
+                } else {
+                    changed |= remap_value(v, remapped);
+                }
+            }
+            changed
+        }
+        Value::Sequence(items) => items
+            .iter_mut()
+            .fold(false, |changed, item| remap_value(item, remapped) | changed),
+        _ => false,
+    }
+}
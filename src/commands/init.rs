@@ -0,0 +1,263 @@
+use crate::cli::OnDuplicate;
+use crate::commands::deploy;
+use crate::core::config::{add_local_providers, add_provider, list_providers};
+use crate::core::crypto::create_key;
+use crate::core::discovery::{ollama_host, LocalModel};
+use crate::core::paths::{config_file, store_dir};
+use crate::core::secrets::{load_api_keys, save_api_keys};
+use crate::core::storage::{AppCtx, PromptData};
+use crate::core::utils::new_id;
+use crate::ui::symbols::{BULLET, OK};
+use crate::ui::theme;
+use chrono::Utc;
+use console::style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, Password, Select};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// A backend offered by the wizard.
+struct Backend {
+    label: &'static str,
+    /// Name of the backend in `config.toml`, and the default provider name.
+    name: &'static str,
+    model: &'static str,
+    /// Environment variable read for the API key, `None` for local backends.
+    key_env: Option<&'static str>,
+}
+
+const BACKENDS: &[Backend] = &[
+    Backend {
+        label: "OpenAI",
+        name: "openai",
+        model: "gpt-4o-mini",
+        key_env: Some("OPENAI_API_KEY"),
+    },
+    Backend {
+        label: "Anthropic",
+        name: "anthropic",
+        model: "claude-3-5-haiku-latest",
+        key_env: Some("ANTHROPIC_API_KEY"),
+    },
+    Backend {
+        label: "Google Gemini",
+        name: "google",
+        model: "gemini-1.5-flash",
+        key_env: Some("GOOGLE_API_KEY"),
+    },
+    Backend {
+        label: "Groq",
+        name: "groq",
+        model: "llama-3.1-8b-instant",
+        key_env: Some("GROQ_API_KEY"),
+    },
+    Backend {
+        label: "DeepSeek",
+        name: "deepseek",
+        model: "deepseek-chat",
+        key_env: Some("DEEPSEEK_API_KEY"),
+    },
+    Backend {
+        label: "xAI",
+        name: "xai",
+        model: "grok-2-latest",
+        key_env: Some("XAI_API_KEY"),
+    },
+    Backend {
+        label: "Ollama (local)",
+        name: "ollama",
+        model: "llama3.2",
+        key_env: None,
+    },
+];
+
+/// Sets up the store step by step: creates the key (protected by a password or not),
+/// configures a provider in `config.toml`, optionally deploys a starter pack and
+/// creates a sample prompt. Steps already done on an existing store are skipped.
+pub async fn run() -> Result<(), String> {
+    let theme = theme();
+    let base_dir = store_dir()?;
+    let key_path = base_dir.join("keys").join("key.bin");
+    println!(
+        "{} Setting up prompt-store in {}\n",
+        style(BULLET).green().bold(),
+        style(base_dir.display()).yellow()
+    );
+
+    let ctx = if key_path.exists() {
+        println!("A store already exists there, opening it.");
+        AppCtx::init()?
+    } else {
+        let protect = Confirm::with_theme(&theme)
+            .with_prompt("Protect the store with a password? (asked whenever it is opened)")
+            .default(true)
+            .interact()
+            .map_err(|e| format!("Confirmation error: {}", e))?;
+        let password = if protect {
+            Some(Zeroizing::new(
+                Password::with_theme(&theme)
+                    .with_prompt("Password")
+                    .with_confirmation("Confirm password", "Passwords do not match.")
+                    .interact()
+                    .map_err(|e| format!("Password error: {}", e))?,
+            ))
+        } else {
+            None
+        };
+        let key = create_key(&key_path, password.as_deref().map(String::as_str))?;
+        println!(
+            "{} Store created{}.",
+            style(OK).green().bold(),
+            if protect {
+                ", its key protected by your password"
+            } else {
+                ""
+            }
+        );
+        AppCtx::with_key(&key)?
+    };
+
+    let provider = configure_provider(&ctx, &theme)?;
+
+    let pack_url: String = Input::with_theme(&theme)
+        .with_prompt("Git URL of a starter pack to deploy (empty to skip)")
+        .allow_empty(true)
+        .interact_text()
+        .map_err(|e| format!("Input error: {}", e))?;
+    if !pack_url.trim().is_empty() {
+        deploy::run(
+            &ctx,
+            pack_url.trim(),
+            None,
+            None,
+            None,
+            false,
+            OnDuplicate::Ask,
+        )
+        .await?;
+    }
+
+    let sample = create_sample(&ctx)?;
+
+    println!("\n{} prompt-store is ready.", style(OK).green().bold());
+    if let (Some(sample), Some(provider)) = (sample, provider) {
+        println!(
+            "Try the sample prompt: prompt-store run {} --backend {} --var sentences=2 --var text=\"...\"",
+            sample, provider
+        );
+    }
+    println!("List your prompts with 'prompt-store list', add one with 'prompt-store new'.");
+    Ok(())
+}
+
+/// Adds a provider to `config.toml`, storing its API key encrypted if one is given.
+/// Offered only as an option when providers are already configured. Returns the name of
+/// a configured provider.
+fn configure_provider(ctx: &AppCtx, theme: &ColorfulTheme) -> Result<Option<String>, String> {
+    let existing = list_providers()?;
+    if !existing.is_empty() {
+        let names: Vec<&str> = existing.iter().map(|p| p.name.as_str()).collect();
+        println!("Providers configured: {}", names.join(", "));
+        if !Confirm::with_theme(theme)
+            .with_prompt("Add another provider?")
+            .default(false)
+            .interact()
+            .map_err(|e| format!("Confirmation error: {}", e))?
+        {
+            return Ok(Some(existing[0].name.clone()));
+        }
+    }
+
+    let labels: Vec<&str> = BACKENDS.iter().map(|b| b.label).collect();
+    let choice = Select::with_theme(theme)
+        .with_prompt("LLM provider")
+        .items(&labels)
+        .default(0)
+        .interact()
+        .map_err(|e| format!("Selection error: {}", e))?;
+    let backend = &BACKENDS[choice];
+    let name: String = Input::with_theme(theme)
+        .with_prompt("Provider name")
+        .default(backend.name.to_string())
+        .interact_text()
+        .map_err(|e| format!("Input error: {}", e))?;
+    if existing.iter().any(|p| p.name == name) {
+        return Err(format!("Provider '{}' is already configured.", name));
+    }
+    let model: String = Input::with_theme(theme)
+        .with_prompt("Model")
+        .default(backend.model.to_string())
+        .interact_text()
+        .map_err(|e| format!("Input error: {}", e))?;
+
+    match backend.key_env {
+        None => {
+            add_local_providers(&[LocalModel {
+                name: name.clone(),
+                backend: backend.name.to_string(),
+                model,
+                base_url: ollama_host(),
+            }])?;
+        }
+        Some(key_env) => {
+            let key = Zeroizing::new(
+                Password::with_theme(theme)
+                    .with_prompt(format!(
+                        "API key (stored encrypted; empty to read ${} instead)",
+                        key_env
+                    ))
+                    .allow_empty_password(true)
+                    .interact()
+                    .map_err(|e| format!("Password error: {}", e))?,
+            );
+            add_provider(&name, backend.name, &model, Some(key_env))?;
+            if !key.trim().is_empty() {
+                let mut keys = load_api_keys(&ctx.base_dir, &ctx.cipher)?;
+                keys.insert(name.clone(), key.trim().to_string());
+                save_api_keys(&ctx.base_dir, &ctx.cipher, &keys)?;
+            }
+        }
+    }
+    println!(
+        "{} Provider {} added to {}",
+        style(OK).green().bold(),
+        style(&name).yellow(),
+        config_file()?.display()
+    );
+    Ok(Some(name))
+}
+
+/// Creates a sample prompt in the default workspace if it has no prompts yet. Returns
+/// its ID.
+fn create_sample(ctx: &AppCtx) -> Result<Option<String>, String> {
+    let repo = ctx.prompts();
+    if !repo.list("default")?.is_empty() {
+        return Ok(None);
+    }
+    let id = new_id(&ctx.workspaces_dir.join("default"), "Summarize text")?;
+    let pd = PromptData {
+        id: id.clone(),
+        title: "Summarize text".to_string(),
+        content: "Summarize the following text in {{sentences}} sentences:\n\n{{text}}".to_string(),
+        translations: BTreeMap::new(),
+        variants: BTreeMap::new(),
+        canary: None,
+        tags: vec!["sample".to_string()],
+        description: Some(
+            "Created by `prompt-store init`, to edit or delete once you have your own prompts."
+                .to_string(),
+        ),
+        schema: None,
+        examples: Vec::new(),
+        updated_at: Some(Utc::now()),
+        attachments: Vec::new(),
+    };
+    repo.save(&id, &pd)?;
+    println!(
+        "{} Sample prompt '{}' created with ID {}.",
+        style(OK).green().bold(),
+        pd.title,
+        style(&id).yellow()
+    );
+    Ok(Some(id))
+}
@@ -1,50 +1,401 @@
-use crate::cli::Cli;
+use crate::cli::{ChainCmd, Cli, Cmd, OutputArgs};
 use crate::commands::dispatch;
-use crate::core::storage::AppCtx;
-use clap::Parser;
+use crate::core::config::load_lock_after;
+use crate::core::crypto::{decrypt_from_base64, encrypt_to_base64, is_key_locked};
+use crate::core::storage::{write_atomic, AppCtx};
+use crate::ui::output::configure;
+use crate::ui::symbols::BULLET;
+use clap::{CommandFactory, Parser};
 use console::style;
-use dialoguer::Input;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// History of the REPL, encrypted with the master key, relative to the store directory.
+const HISTORY_FILE: &str = "repl-history.bin";
+/// Commands kept in the history.
+const HISTORY_SIZE: usize = 1000;
+
+/// The store opened by the REPL, dropped (and its key wiped) once it locks.
+struct Session {
+    ctx: Option<AppCtx>,
+    last_used: Instant,
+}
 
 /// Run the CLI in interactive REPL mode.
-pub fn run(ctx: &AppCtx) -> Result<(), String> {
-    let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
-    rt.block_on(async {
-        println!("Entering interactive mode. Type 'exit' or 'quit' to leave.");
-        loop {
-            let input: String = Input::new()
-                .with_prompt(format!("{}", style("ps >").blue().bold()))
-                .interact_text()
-                .map_err(|e| format!("Input error: {}", e))?;
-
-            let input = input.trim();
-            if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
-                break;
-            }
-            if input.is_empty() {
+///
+/// Commands are read with line editing, a history kept encrypted across sessions, and
+/// tab completion of subcommands, flags and prompt IDs. A line ending with `\` or
+/// leaving a quote open continues on the next one. Lines starting with a space are left
+/// out of the history.
+///
+/// `set key=value` sets session variables passed to every `run`, `render` and
+/// `chain run` (`--var` overrides them), `unset key` removes one. `!!` repeats the last
+/// command, and `@id` stands for the last prompt or chain a command selected.
+///
+/// When the master key is protected by a password or a hardware device, the store
+/// locks after `[interactive] lock_after_minutes` without a command, and the next
+/// command unlocks it again.
+///
+/// The output flags given to `interactive` apply to every command of the session.
+pub async fn run(ctx: AppCtx, output: OutputArgs) -> Result<(), String> {
+    let lock_after = if is_key_locked(&ctx.key_path) {
+        load_lock_after()?
+    } else {
+        None
+    };
+    let mut editor = editor(&ctx)?;
+    let session = Arc::new(Mutex::new(Session {
+        ctx: Some(ctx),
+        last_used: Instant::now(),
+    }));
+    let locker = lock_after.map(|idle| tokio::spawn(lock_when_idle(session.clone(), idle)));
+
+    println!("Entering interactive mode. Type 'exit' or 'quit' to leave.");
+    let result = repl(&session, &mut editor, output).await;
+    if let Some(locker) = locker {
+        locker.abort();
+    }
+    result
+}
+
+/// Builds the line editor, with the history of previous sessions and the IDs to complete.
+fn editor(ctx: &AppCtx) -> Result<Editor<ReplHelper, DefaultHistory>, String> {
+    let config = Config::builder()
+        .max_history_size(HISTORY_SIZE)
+        .map_err(|e| e.to_string())?
+        .history_ignore_space(true)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::with_config(config).map_err(|e| format!("Terminal error: {}", e))?;
+    editor.set_helper(Some(ReplHelper {
+        commands: Cli::command(),
+        ids: completion_ids(ctx),
+    }));
+    for line in load_history(ctx) {
+        editor
+            .add_history_entry(line)
+            .map_err(|e| format!("History error: {}", e))?;
+    }
+    Ok(editor)
+}
+
+async fn repl(
+    session: &Mutex<Session>,
+    editor: &mut Editor<ReplHelper, DefaultHistory>,
+    output: OutputArgs,
+) -> Result<(), String> {
+    let prompt = format!("{} ", style("ps >").blue().bold());
+    let mut session_vars = BTreeMap::new();
+    let mut last_command: Option<String> = None;
+    let mut last_id: Option<String> = None;
+    loop {
+        // Reading blocks until a line is entered: let the runtime move its other tasks
+        // (such as the idle lock) to another thread meanwhile, instead of building a
+        // runtime of its own.
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(format!("Input error: {}", e)),
+        };
+        editor
+            .add_history_entry(line.as_str())
+            .map_err(|e| format!("History error: {}", e))?;
+
+        let mut input = line.replace("\\\n", " ").trim().to_string();
+        if let Some(rest) = input.strip_prefix("!!") {
+            let Some(last) = &last_command else {
+                eprintln!("{} No previous command to repeat.", BULLET);
+                continue;
+            };
+            input = format!("{}{}", last, rest);
+            println!("{}", style(&input).dim());
+        }
+        if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
+            break;
+        }
+        if input.is_empty() {
+            continue;
+        }
+        last_command = Some(input.clone());
+
+        let args = match shell_words::split(&input) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{} Error parsing command: {}", BULLET, e);
                 continue;
             }
+        };
+        let args = match expand_last_id(args, last_id.as_deref()) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{} {}", BULLET, e);
+                continue;
+            }
+        };
+        if session_vars_command(&args, &mut session_vars) {
+            continue;
+        }
+
+        let full_args = std::iter::once("prompt-store".to_string()).chain(args);
 
-            let args = match shell_words::split(input) {
-                Ok(args) => args,
-                Err(e) => {
-                    eprintln!("• Error parsing command: {}", e);
-                    continue;
+        match Cli::try_parse_from(full_args.clone()) {
+            Ok(mut cli) => {
+                configure(&OutputArgs {
+                    no_color: output.no_color || cli.output.no_color,
+                    no_pager: output.no_pager || cli.output.no_pager,
+                    full: output.full || cli.output.full,
+                    quiet: output.quiet || cli.output.quiet,
+                });
+                merge_session_vars(&mut cli.command, &session_vars);
+                let mut session = session.lock().await;
+                let ctx = match session.ctx.take() {
+                    Some(ctx) => ctx,
+                    None => {
+                        println!("The store was locked after inactivity.");
+                        match AppCtx::init() {
+                            Ok(ctx) => ctx,
+                            Err(e) => {
+                                eprintln!("{} {}", BULLET, e);
+                                continue;
+                            }
+                        }
+                    }
+                };
+                match dispatch(cli.command, &ctx).await {
+                    Ok(()) => last_id = selected_id(full_args).or(last_id),
+                    Err(e) => eprintln!("{} {}", BULLET, e),
                 }
-            };
+                if let Err(e) = save_history(&ctx, editor.history()) {
+                    eprintln!("{} {}", BULLET, e);
+                }
+                if let Some(helper) = editor.helper_mut() {
+                    helper.ids = completion_ids(&ctx);
+                }
+                session.ctx = Some(ctx);
+                session.last_used = Instant::now();
+            }
+            Err(e) => {
+                e.print().unwrap_or(());
+            }
+        }
+    }
+    Ok(())
+}
 
-            let full_args = std::iter::once("prompt-store".to_string()).chain(args);
+/// Replaces the `@id` arguments with the ID of the last prompt a command selected.
+fn expand_last_id(args: Vec<String>, last_id: Option<&str>) -> Result<Vec<String>, String> {
+    args.into_iter()
+        .map(|arg| match (arg.as_str(), last_id) {
+            ("@id", Some(id)) => Ok(id.to_string()),
+            ("@id", None) => Err("No prompt selected yet for @id.".to_string()),
+            _ => Ok(arg),
+        })
+        .collect()
+}
 
-            match Cli::try_parse_from(full_args) {
-                Ok(cli) => {
-                    if let Err(e) = dispatch(cli.command, ctx).await {
-                        eprintln!("• {}", e);
-                    }
+/// Handles `set key=value...` (or `set` alone, listing the variables) and
+/// `unset key...`. Returns whether `args` was one of them.
+fn session_vars_command(args: &[String], vars: &mut BTreeMap<String, String>) -> bool {
+    match args.split_first() {
+        Some((command, assignments)) if command == "set" => {
+            if assignments.is_empty() && vars.is_empty() {
+                println!("No session variables. Set one with 'set key=value'.");
+            } else if assignments.is_empty() {
+                for (key, value) in vars.iter() {
+                    println!("{} = {}", style(key).cyan(), value);
                 }
-                Err(e) => {
-                    e.print().unwrap_or(());
+            }
+            for assignment in assignments {
+                match assignment.split_once('=') {
+                    Some((key, value)) => {
+                        vars.insert(key.to_string(), value.to_string());
+                    }
+                    None => eprintln!("{} Expected key=value, got '{}'.", BULLET, assignment),
                 }
             }
+            true
+        }
+        Some((command, keys)) if command == "unset" => {
+            for key in keys {
+                vars.remove(key);
+            }
+            true
         }
-        Ok(())
-    })
-}
\ No newline at end of file
+        _ => false,
+    }
+}
+
+/// Adds the session variables to the variables of `run`, `render` and `chain run`,
+/// before those given with `--var`, which take precedence.
+fn merge_session_vars(command: &mut Cmd, session_vars: &BTreeMap<String, String>) {
+    let vars = match command {
+        Cmd::Run { vars, .. }
+        | Cmd::Render { vars, .. }
+        | Cmd::Chain(ChainCmd::Run { vars, .. }) => vars,
+        _ => return,
+    };
+    let explicit = std::mem::take(vars);
+    *vars = session_vars
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .chain(explicit)
+        .collect();
+}
+
+/// The `id` argument of a command, the prompt or chain it selected.
+fn selected_id(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let matches = Cli::command().try_get_matches_from(args).ok()?;
+    let mut matches = &matches;
+    while let Some((_, sub)) = matches.subcommand() {
+        matches = sub;
+    }
+    matches.try_get_one::<String>("id").ok().flatten().cloned()
+}
+
+/// Drops the store once no command ran for `idle`.
+async fn lock_when_idle(session: Arc<Mutex<Session>>, idle: Duration) {
+    loop {
+        let remaining = {
+            let mut session = session.lock().await;
+            let remaining = idle.saturating_sub(session.last_used.elapsed());
+            if remaining.is_zero() {
+                session.ctx = None;
+                idle
+            } else {
+                remaining
+            }
+        };
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+fn history_path(ctx: &AppCtx) -> PathBuf {
+    ctx.base_dir.join(HISTORY_FILE)
+}
+
+/// The commands of previous sessions, oldest first. An unreadable history is ignored.
+fn load_history(ctx: &AppCtx) -> Vec<String> {
+    fs::read_to_string(history_path(ctx))
+        .ok()
+        .and_then(|encoded| decrypt_from_base64(&ctx.cipher, &encoded).ok())
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_history(ctx: &AppCtx, history: &DefaultHistory) -> Result<(), String> {
+    let lines: Vec<&String> = history.iter().collect();
+    let json =
+        Zeroizing::new(serde_json::to_vec(&lines).map_err(|e| format!("Serialize error: {}", e))?);
+    write_atomic(&history_path(ctx), encrypt_to_base64(&ctx.cipher, &json)?)
+}
+
+/// IDs of the prompts and chains of every workspace, sorted, for completion.
+fn completion_ids(ctx: &AppCtx) -> Vec<String> {
+    let repo = ctx.prompts();
+    let mut ids: Vec<String> = repo
+        .files()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    for workspace in repo.workspaces().unwrap_or_default() {
+        ids.extend(repo.list_chain_definitions(&workspace).unwrap_or_default());
+        let chains = repo.list_chains(&workspace).unwrap_or_default();
+        ids.extend(chains.into_iter().map(|(id, _)| id));
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Completion and multi-line input of the REPL.
+struct ReplHelper {
+    commands: clap::Command,
+    ids: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        // The deepest subcommand named before the word.
+        let mut command = &self.commands;
+        for arg in before[..start].split_whitespace() {
+            if let Some(sub) = command.find_subcommand(arg) {
+                command = sub;
+            }
+        }
+
+        let candidates: Vec<String> = if word.starts_with('-') {
+            command
+                .get_arguments()
+                .filter_map(|arg| arg.get_long())
+                .map(|long| format!("--{}", long))
+                .collect()
+        } else if command.has_subcommands() {
+            let mut names: Vec<String> = command
+                .get_subcommands()
+                .map(|sub| sub.get_name().to_string())
+                .collect();
+            if before[..start].trim().is_empty() {
+                names.extend(["set", "unset", "exit", "quit"].map(String::from));
+            }
+            names
+        } else {
+            self.ids.clone()
+        };
+
+        let mut pairs: Vec<Pair> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        pairs.sort_by(|a, b| a.display.cmp(&b.display));
+        Ok((start, pairs))
+    }
+}
+
+impl Validator for ReplHelper {
+    /// Asks for another line after a trailing `\` or an unclosed quote.
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        if input.ends_with('\\') || shell_words::split(&input.replace("\\\n", " ")).is_err() {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,31 @@
+use crate::commands::key::encode_share;
+use crate::core::crypto::{key_check_value, read_master_key};
+use crate::core::shamir::split;
+use crate::core::storage::AppCtx;
+use console::style;
+
+/// Split the master key into `shares` Shamir shares, any `threshold` of which recover it
+/// with `key recover`.
+pub fn run(ctx: &AppCtx, shares: u8, threshold: u8) -> Result<(), String> {
+    let key = read_master_key(ctx)?;
+    let check = key_check_value(&key)?;
+    let key_shares = split(&key, shares, threshold)?;
+
+    for share in &key_shares {
+        println!(
+            "Share {}/{}: {}",
+            share.x,
+            shares,
+            encode_share(threshold, share, &check)
+        );
+    }
+    println!(
+        "\n{}",
+        style(format!(
+            "Keep each share in a separate place: any {} of them recover the master key with 'prompt-store key recover'. They stay valid until 'rotate-key'.",
+            threshold
+        ))
+        .yellow()
+    );
+    Ok(())
+}
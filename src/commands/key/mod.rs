@@ -0,0 +1,44 @@
+//! Escrow of the master key as Shamir shares.
+//!
+//! A share is printed as `pskey-<threshold>-<index>-<values>-<check>`, where `values`
+//! is the hex-encoded share of the key and `check` its [`key_check_value`], which tells
+//! whether the shares recovered the right key.
+//!
+//! [`key_check_value`]: crate::core::crypto::key_check_value
+
+use crate::core::shamir::Share;
+
+pub mod backup;
+pub mod protect;
+pub mod recover;
+
+const SHARE_PREFIX: &str = "pskey";
+
+/// Formats a share of the master key.
+fn encode_share(threshold: u8, share: &Share, check: &str) -> String {
+    let values: String = share.values.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        SHARE_PREFIX, threshold, share.x, values, check
+    )
+}
+
+/// Parses a share of the master key into its threshold, share and key check value.
+fn parse_share(text: &str) -> Result<(u8, Share, String), String> {
+    let invalid = || format!("'{}' is not a key share.", text.trim());
+    let parts: Vec<&str> = text.trim().split('-').collect();
+    let [SHARE_PREFIX, threshold, x, values, check] = parts[..] else {
+        return Err(invalid());
+    };
+    let threshold = threshold.parse().map_err(|_| invalid())?;
+    let x = x.parse().map_err(|_| invalid())?;
+    if values.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let values = (0..values.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&values[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    Ok((threshold, Share { x, values }, check.to_string()))
+}
@@ -0,0 +1,31 @@
+use crate::core::config::load_hardware_key;
+use crate::core::crypto::read_master_key;
+use crate::core::hardware::{unwrap_key, wrap_key};
+use crate::core::storage::{write_atomic, AppCtx};
+use crate::ui::symbols::OK;
+use console::style;
+
+/// Wrap the master key with the hardware device selected by `[key] hardware` in
+/// `config.toml`, so the store only opens while the device is present.
+///
+/// The wrapped key is unwrapped once before replacing `key.bin`, so a device that does
+/// not answer consistently never locks the store.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let (device, slot) = load_hardware_key()?.ok_or_else(|| {
+        "No hardware device configured. Set [key] hardware = \"yubikey\" or \"tpm\" in config.toml."
+            .to_string()
+    })?;
+    let key = read_master_key(ctx)?;
+    let wrapped = wrap_key(&key, device, slot)?;
+    if unwrap_key(&wrapped)? != key {
+        return Err("The device did not unwrap the master key it wrapped.".to_string());
+    }
+    write_atomic(&ctx.key_path, wrapped)?;
+
+    println!(
+        "{} Master key wrapped with the {}. Keep Shamir shares of it ('prompt-store key backup') in case the device is lost.",
+        style(OK).green(),
+        device.name()
+    );
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::commands::key::parse_share;
+use crate::core::crypto::{encode_plain_key, key_check_value};
+use crate::core::shamir::combine;
+use crate::core::storage::{write_atomic, AppCtx};
+use crate::ui::symbols::OK;
+use console::style;
+use dialoguer::{Confirm, Input};
+
+/// Rebuild the master key from Shamir shares and write it to `key.bin`, unprotected.
+///
+/// Shares missing from `shares` are asked for until the threshold is reached. The key
+/// is only written once it decrypts the prompts of the store.
+pub fn run(shares: &[String], yes: bool) -> Result<(), String> {
+    let mut parsed = shares
+        .iter()
+        .map(|share| parse_share(share))
+        .collect::<Result<Vec<_>, _>>()?;
+    loop {
+        if let Some((threshold, _, _)) = parsed.first() {
+            if parsed.len() >= *threshold as usize {
+                break;
+            }
+        }
+        let share: String = Input::new()
+            .with_prompt(format!("Share {}", parsed.len() + 1))
+            .interact_text()
+            .map_err(|e| format!("Input error: {}", e))?;
+        parsed.push(parse_share(&share)?);
+    }
+
+    let (threshold, _, check) = parsed[0].clone();
+    if parsed
+        .iter()
+        .any(|(t, _, c)| *t != threshold || *c != check)
+    {
+        return Err("The shares come from different key backups.".to_string());
+    }
+    let key_shares: Vec<_> = parsed.into_iter().map(|(_, share, _)| share).collect();
+    let key = combine(&key_shares)?;
+    if key_check_value(&key)? != check {
+        return Err("The shares do not recover the key. Is one of them mistyped?".to_string());
+    }
+
+    let ctx = AppCtx::with_key(&key)?;
+    let prompts = ctx.verify_key().map_err(|e| {
+        format!(
+            "The recovered key does not match this store ({}). Were the shares made before a 'rotate-key'?",
+            e
+        )
+    })?;
+
+    if ctx.key_path.exists()
+        && !yes
+        && !Confirm::new()
+            .with_prompt("Replace the current key.bin with the recovered key?")
+            .default(false)
+            .interact()
+            .map_err(|e| format!("{} (use --yes to replace it without confirmation)", e))?
+    {
+        return Ok(());
+    }
+    write_atomic(&ctx.key_path, encode_plain_key(&key))?;
+
+    println!(
+        "{} Master key recovered ({} prompts checked). Protect it with 'prompt-store passwd'.",
+        style(OK).green(),
+        prompts
+    );
+    Ok(())
+}
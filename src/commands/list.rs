@@ -1,135 +1,145 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx, ChainData};
-use aes_gcm::aead::Aead;
-use aes_gcm::Nonce;
-use base64::{engine::general_purpose, Engine as _};
-use console::style;
-use std::collections::{BTreeMap, HashSet};
-use std::fs;
-use std::path::Path;
+use crate::cli::{ListColumn, ListSort};
+use crate::core::storage::{parse_id, AppCtx, StoredPrompt};
+use crate::core::usage;
+use crate::ui::output::{fit, print_paged};
+use crate::ui::symbols::BULLET;
+use console::{measure_text_width, pad_str, style, Alignment};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt::Write as _;
 
-struct WorkspaceContent {
-    standalone_prompts: Vec<(String, String)>,
-    chains: Vec<(String, String)>,
+/// How `list` selects, orders and shows prompts.
+pub struct ListOptions {
+    pub workspace: Option<String>,
+    pub sort: ListSort,
+    pub columns: Vec<ListColumn>,
 }
 
 /// List every saved prompt and chain, with optional tag filtering.
-pub fn run(ctx: &AppCtx, tags: &[String]) -> Result<(), String> {
+pub fn run(ctx: &AppCtx, tags: &[String], opts: &ListOptions) -> Result<(), String> {
     let tag_filter: HashSet<_> = tags.iter().map(|t| t.to_lowercase()).collect();
-    let is_filtering = !tag_filter.is_empty();
+    let repo = ctx.prompts();
+    let usage = usage::load(&ctx.base_dir);
 
-    let mut workspaces: BTreeMap<String, WorkspaceContent> = BTreeMap::new();
+    let workspaces = match &opts.workspace {
+        Some(workspace) if !repo.workspaces()?.contains(workspace) => {
+            return Err(format!("Workspace '{}' not found.", workspace));
+        }
+        Some(workspace) => vec![workspace.clone()],
+        None => repo.workspaces()?,
+    };
+
+    let mut out = String::new();
+    for workspace in workspaces {
+        let (steps, mut standalone_prompts): (Vec<StoredPrompt>, Vec<StoredPrompt>) = repo
+            .list(&workspace)?
+            .into_iter()
+            .partition(StoredPrompt::is_chain_step);
+        let has_tags = |tags: &[String]| {
+            let tags: HashSet<_> = tags.iter().map(|t| t.to_lowercase()).collect();
+            tag_filter.is_subset(&tags)
+        };
+        standalone_prompts.retain(|p| has_tags(&p.data.tags));
+        let mut chains = repo.list_chains(&workspace)?;
+        chains.retain(|(_, chain)| has_tags(&chain.tags));
 
-    for entry in fs::read_dir(&ctx.workspaces_dir).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let path = entry.path();
-        if !path.is_dir() {
+        if standalone_prompts.is_empty() && chains.is_empty() {
             continue;
         }
 
-        let workspace_name = entry.file_name().to_string_lossy().to_string();
-        let mut content = WorkspaceContent {
-            standalone_prompts: Vec::new(),
-            chains: Vec::new(),
+        let runs = |p: &StoredPrompt| {
+            usage
+                .get(&workspace)
+                .and_then(|counts| counts.get(&parse_id(&p.full_id).1))
+                .map_or(0, |u| u.runs)
         };
+        match opts.sort {
+            ListSort::Id => {}
+            ListSort::Title => standalone_prompts
+                .sort_by_cached_key(|p| (p.data.title.to_lowercase(), p.full_id.clone())),
+            ListSort::Updated => standalone_prompts.sort_by_key(|p| Reverse(p.data.updated_at)),
+            ListSort::Runs => standalone_prompts.sort_by_key(|p| Reverse(runs(p))),
+        }
 
-        for item in fs::read_dir(&path).map_err(|e| e.to_string())? {
-            let item_path = item.map_err(|e| e.to_string())?.path();
-            if item_path.is_dir() {
-                // It's a chain
-                if is_filtering {
-                    continue;
-                }
-                if let Ok(chain_data) =
-                    decrypt_chain_meta(&item_path.join("chain.meta"), &ctx.cipher)
-                {
-                    content.chains.push((chain_data.id, chain_data.title));
-                }
-            } else if item_path.extension().and_then(|s| s.to_str()) == Some("prompt") {
-                // Standalone prompt
-                if let Ok(prompt) = decrypt_full_prompt(&item_path, &ctx.cipher) {
-                    if is_filtering {
-                        let prompt_tags: HashSet<_> =
-                            prompt.tags.iter().map(|t| t.to_lowercase()).collect();
-                        if !tag_filter.is_subset(&prompt_tags) {
-                            continue;
+        let mut rows: Vec<Vec<String>> = standalone_prompts
+            .iter()
+            .map(|p| {
+                opts.columns
+                    .iter()
+                    .map(|column| match column {
+                        ListColumn::Id => style(&p.full_id).yellow().to_string(),
+                        ListColumn::Title => p.data.title.clone(),
+                        ListColumn::Tags => p.data.tags.join(", "),
+                        ListColumn::Updated => p
+                            .data
+                            .updated_at
+                            .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                    })
+                    .collect()
+            })
+            .collect();
+        let prompt_rows = rows.len();
+        for (id, chain) in &chains {
+            let prefix = format!("{}/", parse_id(id).1);
+            let step_count = steps
+                .iter()
+                .filter(|s| parse_id(&s.full_id).1.starts_with(&prefix))
+                .count();
+            rows.push(
+                opts.columns
+                    .iter()
+                    .map(|column| match column {
+                        ListColumn::Id => style(id).yellow().to_string(),
+                        ListColumn::Title => {
+                            format!(
+                                "{} (Chain, {} step{})",
+                                chain.title,
+                                step_count,
+                                if step_count == 1 { "" } else { "s" }
+                            )
                         }
-                    }
-                    content
-                        .standalone_prompts
-                        .push((prompt.id, prompt.title));
-                }
-            }
+                        ListColumn::Tags => chain.tags.join(", "),
+                        ListColumn::Updated => String::new(),
+                    })
+                    .collect(),
+            );
         }
 
-        content.standalone_prompts.sort_by(|a, b| a.0.cmp(&b.0));
-        content.chains.sort_by(|a, b| a.0.cmp(&b.0));
-
-        if !content.standalone_prompts.is_empty() || !content.chains.is_empty() {
-            workspaces.insert(workspace_name, content);
+        writeln!(out, "\nWorkspace: {}", style(&workspace).bold().cyan()).unwrap();
+        let widths: Vec<usize> = (0..opts.columns.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| measure_text_width(&row[i]))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for (n, row) in rows.iter().enumerate() {
+            let bullet = if n < prompt_rows {
+                style(BULLET).green()
+            } else {
+                style(BULLET).blue()
+            };
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| pad_str(cell, width, Alignment::Left, None).into_owned())
+                .collect();
+            let line = format!("  {} {}", bullet, cells.join("  ").trim_end());
+            writeln!(out, "{}", fit(&line)).unwrap();
         }
     }
 
-    if workspaces.is_empty() {
+    if out.is_empty() {
         println!(
             "{}",
             style("No matching prompts or chains found.")
                 .yellow()
                 .bold()
         );
-    } else {
-        for (name, content) in workspaces {
-            println!("\nWorkspace: {}", style(name.clone()).bold().cyan());
-            if content.standalone_prompts.is_empty() && content.chains.is_empty() {
-                println!("  (empty)");
-                continue;
-            }
-
-            for (id, title) in content.standalone_prompts {
-                let display_id = if name == "default" {
-                    id.clone()
-                } else {
-                    format!("{}::{}", name, id)
-                };
-                println!(
-                    "  {} {} - {}",
-                    style("•").green(),
-                    style(display_id).yellow(),
-                    title
-                );
-            }
-            for (id, title) in content.chains {
-                let display_id = if name == "default" {
-                    id.clone()
-                } else {
-                    format!("{}::{}", name, id)
-                };
-                println!(
-                    "  {} {} (Chain) - {}",
-                    style("•").blue(),
-                    style(display_id).yellow(),
-                    title
-                );
-            }
-        }
+        return Ok(());
     }
+    print_paged(&out);
     Ok(())
 }
-
-fn decrypt_chain_meta(path: &Path, cipher: &aes_gcm::Aes256Gcm) -> Result<ChainData, String> {
-    let plaintext = decrypt_file(path, cipher)?;
-    serde_json::from_slice(&plaintext).map_err(|_| "Invalid JSON for ChainData".to_string())
-}
-
-fn decrypt_file(path: &Path, cipher: &aes_gcm::Aes256Gcm) -> Result<Vec<u8>, String> {
-    let encoded = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
-    let decoded = general_purpose::STANDARD
-        .decode(encoded.trim_end())
-        .map_err(|_| "Corrupted data".to_string())?;
-    if decoded.len() < 12 {
-        return Err("Corrupted data".to_string());
-    }
-    let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-    cipher
-        .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-        .map_err(|_| "Decrypt error".to_string())
-}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::core::migrate::{apply, plan, MIGRATIONS};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+
+/// Upgrade the store files written in older formats to the current one.
+pub fn run(ctx: &AppCtx, dry_run: bool) -> Result<(), String> {
+    let upgrades = plan(ctx)?;
+    if upgrades.is_empty() {
+        println!("{}", style("The store is up to date.").green());
+        return Ok(());
+    }
+
+    for migration in MIGRATIONS {
+        let count = upgrades
+            .iter()
+            .filter(|u| u.migrations.contains(&migration.name))
+            .count();
+        if count > 0 {
+            println!(
+                "  {} {} ({} files)",
+                style(BULLET).green(),
+                migration.description,
+                count
+            );
+        }
+    }
+    if dry_run {
+        for upgrade in &upgrades {
+            println!("    {}", upgrade.path);
+        }
+        println!(
+            "{} Would migrate {} files",
+            style(OK).green(),
+            upgrades.len()
+        );
+        return Ok(());
+    }
+
+    apply(ctx, &upgrades)?;
+    println!("{} Migrated {} files", style(OK).green(), upgrades.len());
+    Ok(())
+}
@@ -1,39 +1,112 @@
-use crate::cli::{ChainCmd, Cmd, PackCmd};
-use crate::core::storage::AppCtx;
+use crate::cli::{
+    AttachCmd, BatchCmd, ChainCmd, Cmd, DocsCmd, EnvCmd, KeyCmd, PackCmd, ProvidersCmd,
+    ReleaseCmd, RunsCmd, TokensCmd, TransferCmd,
+};
+use crate::core::storage::{AppCtx, WorkspaceInfo};
 
+pub mod attach;
+pub mod backup;
+pub mod batch;
+pub mod canary;
 pub mod chain;
+pub mod check_links;
+pub mod compare_versions;
 pub mod copy;
 pub mod delete;
+pub mod describe;
 pub mod deploy;
+pub mod docs;
+pub mod duplicates;
 pub mod edit;
+pub mod env;
+pub mod eval;
 pub mod export;
+pub mod gc;
 pub mod get;
 pub mod history;
 pub mod import;
+pub mod init;
 pub mod interactive;
+pub mod key;
 pub mod list;
+pub mod migrate;
+pub mod monitor;
 pub mod new;
 pub mod pack;
 pub mod pack_logic;
+pub mod passwd;
+pub mod pipe;
+pub mod play;
+pub mod promote;
+pub mod providers;
+pub mod receive;
+pub mod redact;
+pub mod release;
+#[cfg(feature = "grpc")]
+pub mod remote;
 pub mod rename;
 pub mod render;
+pub mod restore;
 pub mod revert;
 pub mod rotate_key;
 pub mod run;
+pub mod runs;
 pub mod search;
+#[cfg(feature = "grpc")]
+pub mod serve;
+pub mod share;
 pub mod stats;
 pub mod tag;
+pub mod tokens;
+pub mod transfer;
+pub mod translate;
+pub mod undo;
 pub mod update;
+pub mod update_check;
+pub mod uses;
+pub mod worker;
 
 /// Dispatches the parsed command to the appropriate handler.
 pub async fn dispatch(command: Cmd, ctx: &AppCtx) -> Result<(), String> {
     match command {
-        Cmd::List { tag } => list::run(ctx, &tag),
+        Cmd::List {
+            tag,
+            workspace,
+            sort,
+            columns,
+        } => list::run(
+            ctx,
+            &tag,
+            &list::ListOptions {
+                workspace,
+                sort,
+                columns,
+            },
+        ),
         Cmd::New => new::run(ctx),
-        Cmd::Get { id } => get::run(ctx, &id),
-        Cmd::Edit { id } => edit::run(ctx, &id),
-        Cmd::Delete { id } => delete::run(ctx, &id),
-        Cmd::Rename { id, title } => rename::run(ctx, &id, &title),
+        Cmd::Get {
+            id,
+            examples,
+            lang,
+            variant,
+        } => get::run(ctx, &id, examples, lang.as_deref(), variant.as_deref()),
+        Cmd::Edit { id, lang, variant } => edit::run(ctx, &id, lang.as_deref(), variant.as_deref()),
+        Cmd::Delete { id, remove } => delete::run(ctx, &id, remove),
+        Cmd::Undo { yes } => undo::run(ctx, yes),
+        Cmd::Uses { id } => uses::run(ctx, &id),
+        Cmd::Rename {
+            id,
+            title,
+            new_id,
+            update_references,
+        } => rename::run(
+            ctx,
+            &id,
+            title.as_deref(),
+            new_id.as_deref(),
+            update_references,
+        ),
+        Cmd::CheckLinks => check_links::run(ctx),
         Cmd::Search {
             query,
             tag,
@@ -41,31 +114,310 @@ pub async fn dispatch(command: Cmd, ctx: &AppCtx) -> Result<(), String> {
         } => search::run(ctx, &query, tag.as_deref(), content),
         Cmd::Tag { id, changes } => tag::run(ctx, &id, &changes),
         Cmd::Copy { id } => copy::run(ctx, &id),
-        Cmd::Run { id, backend, vars } => run::run(ctx, &id, &backend, &vars).await,
-        Cmd::Render { id, vars } => render::run(ctx, &id, &vars),
-        Cmd::Export { ids, out } => export::run(ctx, ids.as_deref(), &out),
-        Cmd::Import { file } => import::run(ctx, &file),
+        Cmd::Run {
+            id,
+            backend,
+            vars,
+            env_profile,
+            exec,
+            yes,
+            then,
+            lang,
+            variant,
+        } => {
+            let opts = run::RunOptions {
+                env_profile: env_profile.as_deref(),
+                lang: lang.as_deref(),
+                variant: variant.as_deref(),
+            };
+            let exec_opts = exec.then_some(run::ExecOptions { yes, then });
+            run::run(ctx, &id, &backend, &vars, &opts, exec_opts).await
+        }
+        Cmd::Pipe {
+            ids,
+            provider,
+            vars,
+            lang,
+        } => pipe::run(&ids, provider.as_deref(), &vars, lang.as_deref()).await,
+        Cmd::Render { id, vars, lang } => render::run(ctx, &id, &vars, lang.as_deref()),
+        Cmd::Canary { id, percent, abort } => canary::run(ctx, &id, percent, abort),
+        Cmd::Promote { id } => promote::run(ctx, &id),
+        Cmd::Translate { id, to, backend } => translate::run(ctx, &id, &to, &backend).await,
+        Cmd::Eval { id, backend } => eval::run(ctx, &id, &backend).await,
+        Cmd::Batch { job, run } => match (job, run) {
+            (Some(BatchCmd::Submit { id, input, backend }), _) => {
+                batch::submit::run(ctx, &id, &input, &backend).await
+            }
+            (Some(BatchCmd::Status { job }), _) => batch::status::run(ctx, job.as_deref()).await,
+            (Some(BatchCmd::Fetch { job, out }), _) => batch::fetch::run(ctx, &job, &out).await,
+            (None, Some(args)) => {
+                let opts = batch::run::BatchOptions {
+                    backend: &args.backend,
+                    input: &args.input,
+                    out: &args.out,
+                    concurrency: args.concurrency,
+                    rate: args.rate,
+                    retries: args.retries,
+                };
+                batch::run::run(ctx, &args.id, &opts).await
+            }
+            (None, None) => Err("Give a prompt ID, or one of submit, status and fetch.".to_string()),
+        },
+        Cmd::CompareVersions {
+            id,
+            from,
+            to,
+            backend,
+            vars_file,
+            judge,
+            format,
+            out,
+        } => {
+            let opts = compare_versions::CompareOptions {
+                backend: &backend,
+                vars_file: vars_file.as_deref(),
+                judge: judge.as_deref(),
+                format,
+                out: out.as_deref(),
+            };
+            compare_versions::run(ctx, &id, &from, &to, &opts).await
+        }
+        Cmd::Play { id, backend, vars } => play::run(ctx, &id, &backend, &vars).await,
+        Cmd::Redact { file } => redact::run(file.as_deref()),
+        Cmd::Export {
+            ids,
+            out,
+            format,
+            backend,
+            attachments,
+            plaintext,
+            sign,
+            yes,
+        } => export::run(
+            ctx,
+            ids.as_deref(),
+            &out,
+            format,
+            backend.as_deref(),
+            attachments,
+            plaintext.then_some(export::PlaintextOptions { sign, yes }),
+        ),
+        Cmd::Import {
+            file,
+            format,
+            mappings,
+            prompt_tag,
+            on_conflict,
+            on_duplicate,
+            dry_run,
+            only,
+            list,
+        } => {
+            let opts = import::ImportOptions {
+                format,
+                mappings,
+                prompt_tag,
+                on_conflict,
+                on_duplicate,
+                dry_run,
+                only,
+                list,
+            };
+            import::run(ctx, &file, &opts)
+        }
+        Cmd::Share { id, out } => share::run(ctx, &id, out.as_deref()).await,
+        Cmd::Receive { source, passphrase } => {
+            receive::run(ctx, &source, passphrase.as_deref()).await
+        }
         Cmd::History { id } => history::run(ctx, &id),
         Cmd::Revert { id, timestamp } => revert::run(ctx, &id, timestamp.as_deref()),
         Cmd::RotateKey { password } => rotate_key::run(ctx, password),
-        Cmd::Stats => stats::run(ctx),
-        Cmd::Interactive => interactive::run(ctx),
+        Cmd::Passwd {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => passwd::run(ctx, memory_kib, iterations, parallelism),
+        Cmd::Backup { out, with_key } => backup::run(ctx, &out, with_key),
+        Cmd::Restore { file, yes } => restore::run(ctx, &file, yes),
+        Cmd::Describe {
+            name,
+            edit,
+            purpose,
+            owner,
+            conventions,
+            allow_providers,
+            deny_providers,
+        } => describe::run(
+            ctx,
+            &name,
+            describe::DescribeEdit {
+                editor: edit,
+                fields: WorkspaceInfo {
+                    purpose,
+                    owner,
+                    conventions,
+                    ..WorkspaceInfo::default()
+                },
+                allowed_providers: allow_providers,
+                denied_providers: deny_providers,
+            },
+        ),
+        Cmd::Stats { json } => stats::run(ctx, json),
+        Cmd::Gc {
+            keep,
+            remove,
+            recompress,
+        } => gc::run(ctx, keep, remove, recompress),
+        Cmd::Migrate { dry_run } => migrate::run(ctx, dry_run),
+        Cmd::Init => Err("Run 'prompt-store init' outside of interactive mode.".to_string()),
+        Cmd::Interactive => Err("Interactive mode is already running.".to_string()),
+        Cmd::Worker {
+            queue,
+            backend,
+            once,
+            metrics_addr,
+        } => worker::run(ctx, &queue, backend.as_deref(), once, metrics_addr.as_deref()).await,
+        #[cfg(feature = "grpc")]
+        Cmd::Serve { addr, metrics_addr } => serve::run(&addr, metrics_addr.as_deref()).await,
         Cmd::Deploy {
             repo_url,
             alias,
             password,
-        } => deploy::run(ctx, &repo_url, alias.as_deref(), password.as_deref()).await,
-        Cmd::Update { alias } => update::run(ctx, alias.as_deref()).await,
+            rev,
+            yes,
+            on_duplicate,
+        } => {
+            deploy::run(
+                ctx,
+                &repo_url,
+                alias.as_deref(),
+                password.as_deref(),
+                rev.as_deref(),
+                yes,
+                on_duplicate,
+            )
+            .await
+        }
+        Cmd::Update {
+            alias, check: true, ..
+        } => update_check::report(ctx, alias.as_deref()).await,
+        Cmd::Update {
+            alias, latest, yes, ..
+        } => update::run(ctx, alias.as_deref(), latest, yes).await,
+        Cmd::Transfer(transfer_cmd) => match transfer_cmd {
+            TransferCmd::Export { out, password } => {
+                transfer::export::run(ctx, &out, password.as_deref())
+            }
+            TransferCmd::Import {
+                file,
+                password,
+                yes,
+            } => transfer::import::run(ctx, &file, password.as_deref(), yes),
+        },
         Cmd::Chain(chain_cmd) => match chain_cmd {
-            ChainCmd::New => chain::new::run(ctx),
+            ChainCmd::New { template, id } => match template {
+                Some(template) => chain::templates::run(ctx, template, id.as_deref()),
+                None => chain::new::run(ctx),
+            },
             ChainCmd::Import { file, id } => chain::import::run(ctx, &file, &id),
-            ChainCmd::Run { id, vars } => chain::run::run(ctx, &id, &vars).await,
+            ChainCmd::Run {
+                id,
+                vars,
+                env_profile,
+                lang,
+                preset,
+                vars_file,
+                vars_stdin,
+                save_preset,
+            } => {
+                let opts = chain::run::ChainRunOptions {
+                    env_profile: env_profile.as_deref(),
+                    lang: lang.as_deref(),
+                    preset: preset.as_deref(),
+                    vars_file: vars_file.as_deref(),
+                    vars_stdin,
+                    save_preset: save_preset.as_deref(),
+                };
+                chain::run::run(&id, &vars, &opts).await
+            }
+            ChainCmd::Presets { id, delete } => chain::presets::run(ctx, &id, delete.as_deref()),
             ChainCmd::Edit { id } => chain::edit::run(ctx, &id),
             ChainCmd::AddStep { id } => chain::add_step::run(ctx, &id),
-            ChainCmd::RmStep { step_id } => chain::rm_step::run(ctx, &step_id),
+            ChainCmd::RmStep { step_id, remove } => chain::rm_step::run(ctx, &step_id, remove),
+            ChainCmd::Steps { id } => chain::steps::run(ctx, &id),
+            ChainCmd::Tag { id, changes } => chain::tag::run(ctx, &id, &changes),
         },
         Cmd::Pack(pack_cmd) => match pack_cmd {
-            PackCmd::Export { workspace } => pack::export::run(ctx, workspace.as_deref()),
+            PackCmd::Export {
+                workspace,
+                attachments,
+                policy,
+            } => pack::export::run(ctx, workspace.as_deref(), attachments, policy.as_deref()),
+            PackCmd::Remove { alias, remove } => pack::remove::run(ctx, &alias, remove),
+            PackCmd::Rollback { alias } => pack::rollback::run(ctx, &alias),
+            PackCmd::ListRemote { registry } => pack::list_remote::run(&registry).await,
+            PackCmd::Stats { alias, export } => pack::stats::run(ctx, &alias, export.as_deref()),
+        },
+        Cmd::Providers(providers_cmd) => match providers_cmd {
+            ProvidersCmd::List => providers::list::run(ctx),
+            ProvidersCmd::SetKey { provider } => providers::set_key::run(ctx, &provider),
+            ProvidersCmd::RemoveKey { provider, remove } => {
+                providers::remove_key::run(ctx, &provider, remove)
+            }
+            ProvidersCmd::Discover => providers::discover::run().await,
+            ProvidersCmd::Models { name } => providers::models::run(ctx, &name).await,
+        },
+        Cmd::Tokens(tokens_cmd) => match tokens_cmd {
+            TokensCmd::Add {
+                name,
+                scope,
+                workspaces,
+                rate_limit,
+            } => tokens::add::run(ctx, &name, scope, workspaces, rate_limit),
+            TokensCmd::List => tokens::list::run(ctx),
+            TokensCmd::Remove { name, remove } => tokens::remove::run(ctx, &name, remove),
+        },
+        Cmd::Env(env_cmd) => match env_cmd {
+            EnvCmd::Set { profile, vars } => env::set::run(ctx, &profile, &vars),
+            EnvCmd::Unset {
+                profile,
+                keys,
+                remove,
+            } => env::unset::run(ctx, &profile, &keys, remove),
+            EnvCmd::Show { profile } => env::show::run(ctx, &profile),
+            EnvCmd::List => env::list::run(ctx),
+        },
+        Cmd::Runs(runs_cmd) => match runs_cmd {
+            RunsCmd::List => runs::list::run(ctx),
+            RunsCmd::Export {
+                run_id,
+                format,
+                out,
+            } => runs::export::run(ctx, &run_id, format, out.as_deref()),
+        },
+        Cmd::Release(cmd) => match cmd {
+            ReleaseCmd::Create { name, workspace } => release::create::run(ctx, &name, &workspace),
+            ReleaseCmd::List => release::list::run(ctx),
+        },
+        Cmd::Attach(attach_cmd) => match attach_cmd {
+            AttachCmd::Add { id, file, name } => attach::add::run(ctx, &id, &file, name.as_deref()),
+            AttachCmd::List { id } => attach::list::run(ctx, &id),
+            AttachCmd::Get { id, name, out } => attach::get::run(ctx, &id, &name, out.as_deref()),
+            AttachCmd::Remove { id, name, remove } => attach::remove::run(ctx, &id, &name, remove),
+        },
+        Cmd::Docs(docs_cmd) => match docs_cmd {
+            DocsCmd::Add {
+                path,
+                provider,
+                chunk_size,
+            } => docs::add::run(ctx, &path, provider.as_deref(), chunk_size).await,
+            DocsCmd::List => docs::list::run(ctx),
+            DocsCmd::Clear { remove } => docs::clear::run(ctx, remove),
+        },
+        Cmd::Key(key_cmd) => match key_cmd {
+            KeyCmd::Backup { shares, threshold } => key::backup::run(ctx, shares, threshold),
+            KeyCmd::Protect => key::protect::run(ctx),
+            KeyCmd::Recover { shares, yes } => key::recover::run(&shares, yes),
         },
     }
 }
\ No newline at end of file
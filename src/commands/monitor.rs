@@ -0,0 +1,185 @@
+//! HTTP endpoint of `serve` and `worker` for operators:
+//!
+//! - `GET /metrics`: the metrics of [`crate::core::metrics`] in the Prometheus text format
+//! - `GET /healthz`: 200 while the process is up
+//! - `GET /readyz`: 200 when the command's readiness check passes, 503 otherwise and
+//!   once shutting down, so no new work is routed to the process
+//!
+//! It is only started with `--metrics-addr`, and only answers requests made to it. A
+//! connection gets a few seconds to send its request and read the answer, and only a
+//! limited number are handled at once, so idle clients cannot pile up.
+
+use crate::core::metrics;
+use log::{debug, warn};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+
+/// Check of `/readyz`, returning what is not ready.
+pub type ReadyCheck = Arc<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Connections handled at once; the others wait to be accepted.
+const MAX_CONNECTIONS: usize = 32;
+
+/// Time a connection has to send its request, then to read the answer.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Size past which a request whose headers have not ended is dropped.
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// Listens on `addr` and answers its requests in the background. Returns the address
+/// listened on.
+pub async fn spawn(addr: &str, ready: ReadyCheck) -> Result<SocketAddr, String> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    let local = listener
+        .local_addr()
+        .map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    tokio::spawn(async move {
+        loop {
+            let Ok(permit) = Arc::clone(&connections).acquire_owned().await else {
+                return;
+            };
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let ready = Arc::clone(&ready);
+                    tokio::spawn(async move {
+                        respond(stream, ready).await;
+                        drop(permit);
+                    });
+                }
+                Err(e) => warn!("metrics endpoint: {}", e),
+            }
+        }
+    });
+    Ok(local)
+}
+
+/// Waits for Ctrl+C or, on Unix, SIGTERM, then makes `/readyz` fail.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+}
+
+/// Answers a single request, then closes the connection.
+async fn respond(mut stream: TcpStream, ready: ReadyCheck) {
+    let Ok(Some(request)) = timeout(IO_TIMEOUT, read_request(&mut stream)).await else {
+        debug!("metrics endpoint: dropped an incomplete request");
+        return;
+    };
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    debug!("{} {}", method, path);
+
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", metrics::render()),
+        ("GET", "/healthz") => ("200 OK", "ok\n".to_string()),
+        ("GET", "/readyz") => readiness(&ready),
+        ("GET", _) => ("404 Not Found", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method not allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    timeout(IO_TIMEOUT, stream.write_all(response.as_bytes()))
+        .await
+        .ok();
+}
+
+/// Reads a request up to the end of its headers. Returns `None` if the connection
+/// closes before, or the headers are too long.
+async fn read_request(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 || request.len() + n > MAX_REQUEST {
+            return None;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    Some(request)
+}
+
+fn readiness(ready: &ReadyCheck) -> (&'static str, String) {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return ("503 Service Unavailable", "shutting down\n".to_string());
+    }
+    match ready() {
+        Ok(()) => ("200 OK", "ready\n".to_string()),
+        Err(e) => {
+            warn!("not ready: {}", e);
+            ("503 Service Unavailable", format!("{}\n", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(addr: SocketAddr, chunks: &[&str]) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        for chunk in chunks {
+            stream.write_all(chunk.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // A dropped request may reset the connection instead of closing it.
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.ok();
+        response
+    }
+
+    #[tokio::test]
+    async fn answers_requests_sent_in_several_packets() {
+        let addr = spawn("127.0.0.1:0", Arc::new(|| Ok(()))).await.unwrap();
+        let response = get(addr, &["GET /healthz HTTP/1.1\r\n", "Host: x\r\n\r\n"]).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("ok\n"));
+    }
+
+    #[tokio::test]
+    async fn drops_requests_with_oversized_headers() {
+        let addr = spawn("127.0.0.1:0", Arc::new(|| Ok(()))).await.unwrap();
+        let header = format!("X-Pad: {}\r\n", "a".repeat(MAX_REQUEST));
+        let response = get(addr, &["GET /healthz HTTP/1.1\r\n", &header]).await;
+        assert!(response.is_empty());
+    }
+}
@@ -1,16 +1,13 @@
 use crate::core::{
     storage::{AppCtx, PromptData, PromptSchema},
-    utils::new_id,
+    utils::{editor, new_id},
 };
-use aes_gcm::{
-    aead::{Aead, AeadCore, OsRng},
-    Aes256Gcm,
-};
-use base64::{engine::general_purpose, Engine as _};
+use crate::ui::symbols::BULLET;
+use chrono::Utc;
 use console::style;
-use dialoguer::{theme::ColorfulTheme, Confirm, Editor, Input};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
 use serde_json::Value;
-use std::fs;
+use std::collections::BTreeMap;
 
 /// Create a new prompt in the default workspace.
 pub fn run(ctx: &AppCtx) -> Result<(), String> {
@@ -35,7 +32,7 @@ pub fn run(ctx: &AppCtx) -> Result<(), String> {
         .filter(|s| !s.is_empty())
         .collect();
 
-    let content = Editor::new()
+    let content = editor()
         .edit("Enter your prompt content here.")
         .map_err(|e| format!("Editor error: {}", e))?
         .unwrap_or_default();
@@ -67,7 +64,7 @@ pub fn run(ctx: &AppCtx) -> Result<(), String> {
     "required": ["output_field"]
   }
 }"#;
-        let schema_str = Editor::new()
+        let schema_str = editor()
             .edit(schema_template)
             .map_err(|e| format!("Editor error: {}", e))?
             .unwrap_or_default();
@@ -82,38 +79,25 @@ pub fn run(ctx: &AppCtx) -> Result<(), String> {
     }
 
     let default_workspace = ctx.workspaces_dir.join("default");
-    let id = new_id(&default_workspace);
+    let id = new_id(&default_workspace, &title)?;
     let pd = PromptData {
         id: id.clone(),
         title: title.clone(),
         content,
+        translations: BTreeMap::new(),
+        variants: BTreeMap::new(),
+        canary: None,
         tags,
+        description: None,
         schema,
+        examples: Vec::new(),
+        updated_at: Some(Utc::now()),
+        attachments: Vec::new(),
     };
-
-    let json = serde_json::to_vec(&pd).map_err(|e| format!("Serialize error: {}", e))?;
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = ctx
-        .cipher
-        .encrypt(&nonce, json.as_ref())
-        .map_err(|_| "Encrypt error".to_string())?;
-
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded = general_purpose::STANDARD.encode(&out);
-
-    // Use prompt_path with the implicit default workspace
-    let path = ctx.prompt_path(&id);
-    fs::write(&path, encoded).map_err(|e| format!("Write error: {}", e))?;
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).ok();
-    }
+    ctx.prompts().save(&id, &pd)?;
     println!(
         "{} Prompt saved with ID {} and title '{}'",
-        style("•").green().bold(),
+        style(BULLET).green().bold(),
         style(&id).yellow(),
         title
     );
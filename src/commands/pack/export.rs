@@ -1,4 +1,7 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx, PromptData};
+use crate::commands::pack_logic::PackFile;
+use crate::core::policy::check_policy;
+use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::ui::symbols::OK;
 use aes_gcm::aead::{Aead, AeadCore, KeyInit};
 use aes_gcm::{Aes256Gcm, Key};
 use argon2::Argon2;
@@ -7,10 +10,17 @@ use console::style;
 use dialoguer::Password;
 use rand::RngCore;
 use std::fs;
-use std::path::Path;
+use zeroize::Zeroizing;
 
-/// Export all prompts from a specified workspace to a 'prompts.bundle' file.
-pub fn run(ctx: &AppCtx, workspace: Option<&str>) -> Result<(), String> {
+/// Export all prompts from a specified workspace to a 'prompts.bundle' file, with their
+/// attachments if `attachments` is set, the description of the workspace, and the
+/// policy file at `policy` if given.
+pub fn run(
+    ctx: &AppCtx,
+    workspace: Option<&str>,
+    attachments: bool,
+    policy: Option<&str>,
+) -> Result<(), String> {
     let workspace_name = workspace.unwrap_or("default");
     let workspace_path = ctx.workspaces_dir.join(workspace_name);
     let output_file = "prompts.bundle";
@@ -18,9 +28,26 @@ pub fn run(ctx: &AppCtx, workspace: Option<&str>) -> Result<(), String> {
     if !workspace_path.is_dir() {
         return Err(format!("Workspace '{}' not found.", workspace_name));
     }
+    let policy = policy
+        .map(|path| {
+            let content =
+                fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+            check_policy(&content)?;
+            Ok::<_, String>(content)
+        })
+        .transpose()?;
 
-    let mut prompts = Vec::new();
-    find_prompts_recursive(&workspace_path, &ctx.cipher, &mut prompts)?;
+    let repo = ctx.prompts();
+    let mut prompts: Vec<PromptData> = Vec::new();
+    for p in repo.list(workspace_name)? {
+        let mut prompt = p.data;
+        // We strip the workspace from the ID for portability
+        prompt.id = parse_id(&prompt.id).1;
+        if attachments {
+            prompt.attachments = repo.inline_attachments(&p.full_id)?;
+        }
+        prompts.push(prompt);
+    }
 
     if prompts.is_empty() {
         return Err(format!(
@@ -35,17 +62,27 @@ pub fn run(ctx: &AppCtx, workspace: Option<&str>) -> Result<(), String> {
         .interact()
         .map_err(|e| format!("Password input error: {}", e))?;
 
-    let serialized =
-        serde_json::to_vec(&prompts).map_err(|e| format!("Serialization failed: {}", e))?;
+    let count = prompts.len();
+    let pack = match (repo.load_workspace_info(workspace_name)?, policy) {
+        (None, None) => PackFile::Prompts(prompts),
+        (info, policy) => PackFile::Described {
+            info: info.unwrap_or_default(),
+            prompts,
+            policy,
+        },
+    };
+    let serialized = Zeroizing::new(
+        serde_json::to_vec(&pack).map_err(|e| format!("Serialization failed: {}", e))?,
+    );
 
     let mut salt = [0u8; 16];
     rand::thread_rng().fill_bytes(&mut salt);
-    let mut key = [0u8; 32];
+    let mut key = Zeroizing::new([0u8; 32]);
     Argon2::default()
-        .hash_password_into(password.as_bytes(), &salt, &mut key)
+        .hash_password_into(password.as_bytes(), &salt, &mut *key)
         .map_err(|_| "KDF error".to_string())?;
 
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*key));
     let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
     let encrypted_data = cipher
         .encrypt(&nonce, serialized.as_ref())
@@ -61,33 +98,10 @@ pub fn run(ctx: &AppCtx, workspace: Option<&str>) -> Result<(), String> {
 
     println!(
         "{} Successfully exported {} prompts from workspace '{}' to {}",
-        style("✔").green(),
-        prompts.len(),
+        style(OK).green(),
+        count,
         workspace_name,
         style(output_file).yellow()
     );
     Ok(())
 }
-
-fn find_prompts_recursive(
-    dir: &Path,
-    cipher: &Aes256Gcm,
-    prompts: &mut Vec<PromptData>,
-) -> Result<(), String> {
-    if !dir.exists() {
-        return Ok(());
-    }
-    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
-        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
-        if path.is_dir() {
-            find_prompts_recursive(&path, cipher, prompts)?;
-        } else if path.extension().and_then(|s| s.to_str()) == Some("prompt") {
-            if let Ok(mut prompt) = decrypt_full_prompt(&path, cipher) {
-                // We strip the workspace from the ID for portability
-                prompt.id = crate::core::storage::parse_id(&prompt.id).1;
-                prompts.push(prompt);
-            }
-        }
-    }
-    Ok(())
-}
@@ -0,0 +1,26 @@
+use crate::core::registry::list_packs;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the packs available on a pack registry server.
+pub async fn run(registry: &str) -> Result<(), String> {
+    let packs = list_packs(registry).await?;
+    if packs.is_empty() {
+        println!("No packs available on {}.", registry);
+        return Ok(());
+    }
+    let registry = registry.trim_end_matches('/');
+    for pack in packs {
+        println!(
+            "  {} {} ({}){}",
+            style(BULLET).green(),
+            style(&pack.name).yellow(),
+            pack.version,
+            pack.description
+                .map(|d| format!(" - {}", d))
+                .unwrap_or_default()
+        );
+        println!("    prompt-store deploy {}/packs/{}", registry, pack.name);
+    }
+    Ok(())
+}
@@ -1 +1,5 @@
 pub mod export;
+pub mod list_remote;
+pub mod remove;
+pub mod rollback;
+pub mod stats;
@@ -0,0 +1,52 @@
+use crate::cli::RemoveArgs;
+use crate::commands::pack_logic::{load_manifest, save_manifest};
+use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::OK;
+use console::style;
+use std::fs;
+
+/// Remove a deployed pack: its workspace, its git clone and its entry in
+/// `deployed.json`, after showing what it holds and asking for confirmation (or
+/// `--force`). `deploy` installs it again.
+pub fn run(ctx: &AppCtx, alias: &str, args: RemoveArgs) -> Result<(), String> {
+    let mut manifest = load_manifest(ctx)?;
+    let info = manifest
+        .remove(alias)
+        .ok_or_else(|| format!("Pack with alias '{}' not found.", alias))?;
+    let repo = ctx.prompts();
+    let workspace = ctx.workspaces_dir.join(alias);
+    let (prompts, chains) = if workspace.is_dir() {
+        (
+            repo.list(alias)?.len(),
+            repo.list_chains(alias)?.len() + repo.list_chain_definitions(alias)?.len(),
+        )
+    } else {
+        (0, 0)
+    };
+    println!(
+        "Pack {} from {} at {}",
+        style(alias).yellow(),
+        info.url,
+        info.commit_hash.get(..7).unwrap_or(&info.commit_hash)
+    );
+    println!("  {} prompts, {} chains", prompts, chains);
+
+    if !confirm_removal(args, &format!("pack {}", alias))? {
+        return Ok(());
+    }
+    for dir in [workspace, ctx.registries_dir.join(alias)] {
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Delete error: {}", e))?;
+        }
+    }
+    save_manifest(ctx, &manifest)?;
+    println!(
+        "{} Pack '{}' removed. Deploy it again with 'prompt-store deploy {} --alias {}'.",
+        style(OK).green(),
+        style(alias).yellow(),
+        info.url,
+        alias
+    );
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use crate::commands::pack_logic::{
+    checkout_rev, install_pack_from_local_repo, load_manifest, save_manifest, PackSource,
+};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+use std::env;
+
+/// Re-install the commit of a pack recorded before its last update, and pin it there
+/// so the next `update` does not move it forward again.
+pub fn run(ctx: &AppCtx, alias: &str) -> Result<(), String> {
+    let mut manifest = load_manifest(ctx)?;
+    let info = manifest
+        .get_mut(alias)
+        .ok_or_else(|| format!("Pack with alias '{}' not found.", alias))?;
+    if info.source == PackSource::Registry {
+        return Err("Rollback is only available for packs deployed from git.".to_string());
+    }
+    let previous = info
+        .previous
+        .clone()
+        .ok_or_else(|| format!("No previous version recorded for '{}'.", alias))?;
+
+    let repo_path = ctx.registries_dir.join(alias);
+    let commit = checkout_rev(&repo_path, &previous)?;
+    let password = env::var("PROMPT_PACK_PASSWORD").ok();
+    let num_prompts = install_pack_from_local_repo(ctx, &repo_path, alias, password.as_deref())?;
+
+    info.previous = Some(std::mem::replace(&mut info.commit_hash, commit.clone()));
+    info.pin = Some(commit.clone());
+    save_manifest(ctx, &manifest)?;
+
+    println!(
+        "{} Rolled '{}' back to {} ({} prompts), pinned there. Use 'update --latest' to move forward again.",
+        style(OK).green(),
+        style(alias).yellow(),
+        &commit[..7],
+        num_prompts
+    );
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::commands::pack_logic::load_manifest;
+use crate::core::storage::{parse_id, AppCtx};
+use crate::core::usage::{self, PromptUsage};
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use serde_json::json;
+use std::fs;
+
+/// Show how often the prompts of a deployed pack were run or copied, most used first.
+/// With `export`, also writes the counts to a file that can be sent to the pack
+/// maintainers: only prompt IDs and totals, no titles, contents or dates.
+pub fn run(ctx: &AppCtx, alias: &str, export: Option<&str>) -> Result<(), String> {
+    let manifest = load_manifest(ctx)?;
+    let info = manifest
+        .get(alias)
+        .ok_or_else(|| format!("Pack with alias '{}' not found.", alias))?;
+
+    let mut counts = usage::for_workspace(&ctx.base_dir, alias);
+    let mut rows: Vec<(String, String, PromptUsage)> = ctx
+        .prompts()
+        .list(alias)?
+        .into_iter()
+        .filter(|p| !p.is_chain_step())
+        .map(|p| {
+            let id = parse_id(&p.full_id).1;
+            let used = counts.remove(&id).unwrap_or_default();
+            (id, p.data.title, used)
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        (b.2.runs + b.2.copies)
+            .cmp(&(a.2.runs + a.2.copies))
+            .then_with(|| a.1.cmp(&b.1))
+    });
+
+    println!(
+        "{}",
+        style(format!("Usage of '{}'", alias)).bold().underlined()
+    );
+    for (id, title, used) in &rows {
+        let last_used = used
+            .last_used
+            .map(|at| format!(", last used {}", at.format("%Y-%m-%d")))
+            .unwrap_or_default();
+        println!(
+            "  {} {} ({}): {} runs, {} copies{}",
+            style(BULLET).green().bold(),
+            title,
+            style(id).dim(),
+            style(used.runs).yellow(),
+            style(used.copies).yellow(),
+            last_used
+        );
+    }
+    let unused = rows
+        .iter()
+        .filter(|(_, _, u)| u.runs + u.copies == 0)
+        .count();
+    println!("{} of {} prompts never used.", unused, rows.len());
+
+    if let Some(path) = export {
+        let prompts: serde_json::Map<String, serde_json::Value> = rows
+            .iter()
+            .map(|(id, _, used)| {
+                (
+                    id.clone(),
+                    json!({ "runs": used.runs, "copies": used.copies }),
+                )
+            })
+            .collect();
+        let report = json!({
+            "pack": public_url(&info.url),
+            "revision": info.commit_hash,
+            "prompts": prompts,
+        });
+        let content = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| format!("Write error: {}", e))?;
+        println!(
+            "{} Usage counts written to {}",
+            style(OK).green(),
+            style(path).yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Drops credentials from a pack URL before it is shared.
+fn public_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            parsed.set_username("").ok();
+            parsed.set_password(None).ok();
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
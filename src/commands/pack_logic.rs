@@ -1,35 +1,193 @@
 //! Shared logic for deploying and managing prompt packs.
 
-use crate::core::storage::{AppCtx, PromptData};
-use aes_gcm::aead::{Aead, AeadCore, KeyInit};
+use crate::core::content_index::{content_hash, ContentIndex};
+use crate::core::policy::{check_policy, POLICY_FILE};
+use crate::core::scan::{scan, Risk};
+use crate::core::storage::{parse_id, AppCtx, PromptData, WorkspaceInfo};
+use crate::ui::progress::bar;
+use crate::ui::symbols::WARN;
+use aes_gcm::aead::{Aead, KeyInit};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
 use argon2::Argon2;
 use base64::{engine::general_purpose, Engine as _};
-use dialoguer::Password;
+use console::style;
+use dialoguer::{Confirm, Password};
+use git2::{build::CheckoutBuilder, FetchOptions, RemoteCallbacks, Repository};
+use indicatif::ProgressBar;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Where a deployed pack comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PackSource {
+    /// A git repository cloned under `registries/`.
+    #[default]
+    Git,
+    /// A pack registry server (see [`crate::core::registry`]).
+    Registry,
+}
 
 /// Represents the metadata for a deployed pack in `deployed.json`.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeployedInfo {
     pub alias: String,
     pub url: String,
+    /// The deployed commit for git packs, or the version for registry packs.
     pub commit_hash: String,
+    #[serde(default)]
+    pub source: PackSource,
+    /// Tag, branch or commit the pack is pinned to; `update` leaves pinned packs alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    /// The commit installed before the last update, restored by `pack rollback`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+}
+
+/// The prompts of a pack file, with the description and the policy of the pack if it
+/// has them.
+pub struct Pack {
+    pub prompts: Vec<PromptData>,
+    pub info: Option<WorkspaceInfo>,
+    /// Content of the `policy.toml` of the pack.
+    pub policy: Option<String>,
+}
+
+/// What a pack file holds: the prompts alone, or with the description of the workspace
+/// they were exported from and the policy shipped with them. Packs without a
+/// description keep the plain list, readable by older versions.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PackFile {
+    Described {
+        info: WorkspaceInfo,
+        prompts: Vec<PromptData>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        policy: Option<String>,
+    },
+    Prompts(Vec<PromptData>),
+}
+
+impl From<PackFile> for Pack {
+    fn from(file: PackFile) -> Self {
+        match file {
+            PackFile::Described {
+                info,
+                prompts,
+                policy,
+            } => Pack {
+                prompts,
+                info: Some(info),
+                policy,
+            },
+            PackFile::Prompts(prompts) => Pack {
+                prompts,
+                info: None,
+                policy: None,
+            },
+        }
+    }
+}
+
+/// Fetch options showing the git objects received in `progress`.
+pub fn fetch_progress(progress: ProgressBar) -> FetchOptions<'static> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.transfer_progress(move |stats| {
+        progress.set_length(stats.total_objects() as u64);
+        progress.set_position(stats.received_objects() as u64);
+        true
+    });
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    options
+}
+
+/// Checks out `rev` (a tag, branch or commit) in the clone of a pack, detaching HEAD.
+/// Returns the commit checked out.
+pub fn checkout_rev(repo_path: &Path, rev: &str) -> Result<String, String> {
+    let repo = Repository::open(repo_path)
+        .map_err(|e| format!("Failed to open local repository: {}", e))?;
+    let object = repo
+        .revparse_single(rev)
+        .or_else(|_| repo.revparse_single(&format!("origin/{}", rev)))
+        .map_err(|_| format!("Revision '{}' not found in the pack repository.", rev))?;
+    let commit = object.peel_to_commit().map_err(|e| e.to_string())?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))
+        .map_err(|e| format!("Failed to check out '{}': {}", rev, e))?;
+    repo.set_head_detached(commit.id())
+        .map_err(|e| e.to_string())?;
+    Ok(commit.id().to_string())
+}
+
+/// Reads `deployed.json`, empty if no pack was deployed yet.
+pub fn load_manifest(ctx: &AppCtx) -> Result<HashMap<String, DeployedInfo>, String> {
+    let manifest_path = ctx.base_dir.join("deployed.json");
+    if !manifest_path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(&manifest_path).map_err(|e| e.to_string())?;
+    Ok(serde_json::from_str(&content).unwrap_or_default())
+}
+
+/// Writes `deployed.json`.
+pub fn save_manifest(ctx: &AppCtx, manifest: &HashMap<String, DeployedInfo>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(ctx.base_dir.join("deployed.json"), content).map_err(|e| e.to_string())
 }
 
-/// Reads prompts from a local repository path, decrypts if necessary,
-/// and installs them into the local secure cache.
+/// Reads the pack file and the policy of a local repository, decrypting the pack if
+/// necessary, and installs them into the local secure cache.
 pub fn install_pack_from_local_repo(
     ctx: &AppCtx,
     repo_path: &Path,
     alias: &str,
     password: Option<&str>,
 ) -> Result<usize, String> {
+    let content = read_local_pack_file(repo_path)?;
+    let mut pack = read_pack_file(&content, alias, password)?;
+    pack.policy = read_local_policy(repo_path)?.or(pack.policy);
+    let num_prompts = pack.prompts.len();
+    install_prompts_to_workspace(ctx, alias, pack, &HashMap::new())?;
+    Ok(num_prompts)
+}
+
+/// Reads the `policy.toml` at the root of a local repository, if it has one.
+pub fn read_local_policy(repo_path: &Path) -> Result<Option<String>, String> {
+    let path = repo_path.join(POLICY_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {}: {}", POLICY_FILE, e))
+}
+
+/// Reads the `prompts.bundle` or `prompts.json` of a local repository.
+pub fn read_local_pack_file(repo_path: &Path) -> Result<String, String> {
     let bundle_path = repo_path.join("prompts.bundle");
     let json_path = repo_path.join("prompts.json");
 
-    let prompts: Vec<PromptData> = if bundle_path.exists() {
+    if bundle_path.exists() {
+        fs::read_to_string(&bundle_path).map_err(|e| format!("Failed to read bundle: {}", e))
+    } else if json_path.exists() {
+        fs::read_to_string(&json_path).map_err(|e| format!("Failed to read prompts.json: {}", e))
+    } else {
+        Err("No 'prompts.bundle' or 'prompts.json' found in repository.".to_string())
+    }
+}
+
+/// Reads the prompts, description and policy of a pack file, decrypting a `prompts.bundle`
+/// with `password` (prompted if missing).
+pub fn read_pack_file(content: &str, alias: &str, password: Option<&str>) -> Result<Pack, String> {
+    if content.trim_start().starts_with(['[', '{']) {
+        serde_json::from_str::<PackFile>(content)
+            .map(Pack::from)
+            .map_err(|e| format!("Failed to parse prompts.json: {}", e))
+    } else {
         let pass = match password {
             Some(p) => Ok(p.to_string()),
             None => Password::new()
@@ -37,24 +195,11 @@ pub fn install_pack_from_local_repo(
                 .interact()
                 .map_err(|e| e.to_string()),
         }?;
-        decrypt_bundle(&bundle_path, &pass)?
-    } else if json_path.exists() {
-        let content = fs::read_to_string(&json_path)
-            .map_err(|e| format!("Failed to read prompts.json: {}", e))?;
-        serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse prompts.json: {}", e))?
-    } else {
-        return Err("No 'prompts.bundle' or 'prompts.json' found in repository.".to_string());
-    };
-
-    let num_prompts = prompts.len();
-    install_prompts_to_workspace(ctx, alias, prompts)?;
-    Ok(num_prompts)
+        decrypt_bundle(content, &pass)
+    }
 }
 
-fn decrypt_bundle(path: &Path, password: &str) -> Result<Vec<PromptData>, String> {
-    let encoded_string =
-        fs::read_to_string(path).map_err(|e| format!("Failed to read bundle: {}", e))?;
+fn decrypt_bundle(encoded_string: &str, password: &str) -> Result<Pack, String> {
     let decoded = general_purpose::STANDARD
         .decode(encoded_string.trim())
         .map_err(|_| "Invalid Base64 in bundle".to_string())?;
@@ -68,24 +213,126 @@ fn decrypt_bundle(path: &Path, password: &str) -> Result<Vec<PromptData>, String
     let nonce = Nonce::from_slice(&decoded[16..28]);
     let ciphertext = &decoded[28..];
 
-    let mut key = [0u8; 32];
+    let mut key = Zeroizing::new([0u8; 32]);
     Argon2::default()
-        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .hash_password_into(password.as_bytes(), salt, &mut *key)
         .map_err(|_| "Key derivation (Argon2) failed".to_string())?;
 
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*key));
     let plaintext = cipher
         .decrypt(nonce, ciphertext)
+        .map(Zeroizing::new)
         .map_err(|_| "Failed to decrypt bundle. Invalid password?".to_string())?;
 
-    serde_json::from_slice(&plaintext).map_err(|e| format!("Invalid JSON in bundle: {}", e))
+    serde_json::from_slice::<PackFile>(&plaintext)
+        .map(Pack::from)
+        .map_err(|e| format!("Invalid JSON in bundle: {}", e))
+}
+
+/// Scans the content of a pack prompt, its translations, variants and canary, and its
+/// text attachments, which are inlined into it when it runs, for prompt-injection patterns.
+pub fn scan_prompt(prompt: &PromptData) -> Vec<Risk> {
+    let mut risks = scan(&prompt.content);
+    let canary = prompt.canary.iter().map(|canary| &canary.content);
+    for text in prompt
+        .translations
+        .values()
+        .chain(prompt.variants.values())
+        .chain(canary)
+    {
+        risks.extend(scan(text));
+    }
+    for attachment in &prompt.attachments {
+        let text = general_purpose::STANDARD
+            .decode(&attachment.data)
+            .ok()
+            .and_then(|data| String::from_utf8(data).ok());
+        if let Some(text) = text {
+            risks.extend(scan(&text));
+        }
+    }
+    risks
+}
+
+/// Prints a report of the prompts of a pack that look like prompt injections, and asks
+/// whether to install it anyway (unless `yes` is set). Returns whether to install.
+pub fn confirm_pack_risks(alias: &str, prompts: &[PromptData], yes: bool) -> Result<bool, String> {
+    let flagged: Vec<_> = prompts
+        .iter()
+        .map(|prompt| (prompt, scan_prompt(prompt)))
+        .filter(|(_, risks)| !risks.is_empty())
+        .collect();
+    if flagged.is_empty() {
+        return Ok(true);
+    }
+
+    println!(
+        "{} {} prompt(s) of '{}' look like prompt injections:",
+        style(WARN).yellow(),
+        flagged.len(),
+        style(alias).yellow()
+    );
+    for (prompt, risks) in &flagged {
+        println!("  {} ({})", prompt.title, prompt.id);
+        for risk in risks {
+            println!(
+                "    {} {}",
+                style(format!("[{}]", risk.kind)).red(),
+                risk.excerpt
+            );
+        }
+    }
+    if yes {
+        return Ok(true);
+    }
+    Confirm::new()
+        .with_prompt(format!("Install '{}' anyway?", alias))
+        .default(false)
+        .interact()
+        .map_err(|e| format!("{} (use --yes to install without confirmation)", e))
+}
+
+/// Finds the prompts of a pack identical to a prompt stored outside of packs, and
+/// returns those `link` accepts as `(pack prompt ID, existing prompt ID)`, for
+/// [`install_prompts_to_workspace`]. Prompts with attachments are always copied.
+///
+/// Prompts of deployed packs are never linked to, as they change with pack updates.
+pub fn duplicate_links(
+    ctx: &AppCtx,
+    alias: &str,
+    prompts: &[PromptData],
+    mut link: impl FnMut(&str, &str) -> Result<bool, String>,
+) -> Result<HashMap<String, String>, String> {
+    let index = ContentIndex::load(ctx)?;
+    let packs = load_manifest(ctx)?;
+    let mut links = HashMap::new();
+    for prompt in prompts.iter().filter(|p| p.attachments.is_empty()) {
+        let hash = content_hash(prompt)?;
+        let existing = index.matches(&hash).find(|id| {
+            let workspace = parse_id(id).0;
+            workspace != alias && !packs.contains_key(&workspace)
+        });
+        if let Some(existing) = existing {
+            if link(&prompt.id, existing)? {
+                links.insert(prompt.id.clone(), existing.to_string());
+            }
+        }
+    }
+    index.save(ctx)?;
+    Ok(links)
 }
 
-fn install_prompts_to_workspace(
+/// Replaces the cached prompts and description of a pack's workspace. Prompts whose ID
+/// is a key of `links` are stored as links to the prompt it maps to.
+pub fn install_prompts_to_workspace(
     ctx: &AppCtx,
     alias: &str,
-    prompts: Vec<PromptData>,
+    pack: Pack,
+    links: &HashMap<String, String>,
 ) -> Result<(), String> {
+    if let Some(policy) = &pack.policy {
+        check_policy(policy).map_err(|e| format!("Pack '{}': {}", alias, e))?;
+    }
     let workspace_dir = ctx.workspaces_dir.join(alias);
     if workspace_dir.exists() {
         fs::remove_dir_all(&workspace_dir)
@@ -93,25 +340,26 @@ fn install_prompts_to_workspace(
     }
     fs::create_dir_all(&workspace_dir)
         .map_err(|e| format!("Failed to create workspace directory: {}", e))?;
+    if let Some(policy) = &pack.policy {
+        // Enforced by `load_policies` while the workspace exists.
+        fs::write(workspace_dir.join(POLICY_FILE), policy)
+            .map_err(|e| format!("Failed to install the policy of '{}': {}", alias, e))?;
+    }
 
-    for prompt in prompts {
-        let original_id = prompt.id.clone();
-        // The ID inside the file remains the simple one. The namespace is contextual.
-        let json = serde_json::to_vec(&prompt).map_err(|e| e.to_string())?;
-
-        let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
-        let encrypted = ctx
-            .cipher
-            .encrypt(&nonce, json.as_ref())
-            .map_err(|_| "Local encryption failed")?;
-
-        let mut out = Vec::new();
-        out.extend_from_slice(nonce.as_slice());
-        out.extend_from_slice(&encrypted);
-        let encoded = general_purpose::STANDARD.encode(&out);
-
-        let path = workspace_dir.join(format!("{}.prompt", original_id));
-        fs::write(path, encoded).map_err(|e| e.to_string())?;
+    let repo = ctx.prompts();
+    if let Some(info) = &pack.info {
+        repo.save_workspace_info(alias, info)?;
+    }
+    let progress = bar(pack.prompts.len(), &format!("Installing '{}'", alias));
+    for prompt in pack.prompts {
+        let full_id = format!("{}::{}", alias, prompt.id);
+        match links.get(&prompt.id) {
+            Some(target) => repo.save_link(&full_id, target)?,
+            // The ID inside the file remains the simple one. The namespace is contextual.
+            None => repo.save_with_attachments(&full_id, &prompt)?,
+        }
+        progress.inc(1);
     }
+    progress.finish_and_clear();
     Ok(())
 }
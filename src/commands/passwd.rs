@@ -0,0 +1,15 @@
+use crate::core::crypto::{change_password, KdfParams};
+use crate::core::storage::AppCtx;
+
+/// Change the password of the master key with the given Argon2 costs, leaving the
+/// encrypted store untouched.
+pub fn run(ctx: &AppCtx, memory_kib: u32, iterations: u32, parallelism: u32) -> Result<(), String> {
+    change_password(
+        ctx,
+        KdfParams {
+            memory_kib,
+            iterations,
+            parallelism,
+        },
+    )
+}
@@ -0,0 +1,58 @@
+use crate::api::{PromptStore, RunOutput};
+use crate::core::config::{load_input_redaction, resolve_provider_name};
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::collections::HashMap;
+
+/// Run prompts in sequence, feeding each output to the next one as `{{input}}`.
+pub async fn run(
+    ids: &[String],
+    provider: Option<&str>,
+    vars: &[String],
+    lang: Option<&str>,
+) -> Result<(), String> {
+    if ids.is_empty() {
+        return Err("At least one prompt ID is required.".to_string());
+    }
+
+    let mut map: HashMap<String, String> = HashMap::new();
+    for v in vars {
+        if let Some((key, value)) = v.split_once('=') {
+            map.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    let provider_name = resolve_provider_name(provider)?;
+
+    let store = PromptStore::init().map_err(|e| e.to_string())?;
+    let redaction = load_input_redaction()?;
+
+    let mut output = String::new();
+    for (i, id) in ids.iter().enumerate() {
+        println!(
+            "{} [{}/{}] {}",
+            style(BULLET).green(),
+            i + 1,
+            ids.len(),
+            style(id).yellow()
+        );
+        let mut runner = store.prompt(id).vars(map.clone()).provider(&provider_name);
+        if let Some(policy) = &redaction {
+            runner = runner.redact_inputs(policy.clone());
+        }
+        if let Some(lang) = lang {
+            runner = runner.lang(lang);
+        }
+        let result = runner
+            .run()
+            .await
+            .map_err(|e| format!("Step '{}' failed: {}", id, e))?;
+        if let RunOutput::Prompt(text) = result {
+            output = text;
+        }
+        map.insert("input".to_string(), output.clone());
+    }
+
+    println!("\n{}", output);
+    Ok(())
+}
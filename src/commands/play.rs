@@ -0,0 +1,148 @@
+use crate::commands::edit::save_with_backup;
+use crate::commands::run::{build_llm, complete, with_examples};
+use crate::core::config::load_input_redaction;
+use crate::core::pii::redact_values;
+use crate::core::storage::{parse_id, AppCtx};
+use crate::core::template::{render, variables};
+use crate::core::utils::editor;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use std::collections::HashMap;
+
+/// Open a playground for a prompt: set its variables, run it against a backend, tweak
+/// it in the editor and run it again, then save the improved version.
+pub async fn run(ctx: &AppCtx, id: &str, backend: &str, vars: &[String]) -> Result<(), String> {
+    let mut pd = ctx.prompts().load(id)?;
+    let original_content = pd.content.clone();
+    let (workspace, _) = parse_id(id);
+    ctx.prompts().check_provider(&workspace, backend)?;
+    let mut backend = backend.to_string();
+    let mut llm = build_llm(ctx, &backend)?;
+    let redaction = load_input_redaction()?;
+    let theme = ColorfulTheme::default();
+
+    let mut map = HashMap::new();
+    for v in vars {
+        if let Some((key, value)) = v.split_once('=') {
+            map.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    println!(
+        "{} Playground for '{}' with backend {}",
+        style(BULLET).green().bold(),
+        pd.title,
+        style(&backend).cyan()
+    );
+
+    loop {
+        let selections = &[
+            "Run",
+            "Edit Variables",
+            "Edit Prompt",
+            "Change Backend",
+            "Save and Quit",
+            "Quit",
+        ];
+        let selection = Select::with_theme(&theme)
+            .with_prompt("What would you like to do?")
+            .default(0)
+            .items(&selections[..])
+            .interact()
+            .map_err(|e| e.to_string())?;
+
+        match selection {
+            0 => {
+                // Run
+                let mut vars = map.clone();
+                if let Some(policy) = &redaction {
+                    redact_values(&mut vars, policy)?;
+                }
+                let vars = with_examples(&vars, &pd);
+                let template = ctx.prompts().expand_attachments(id, &pd.content)?;
+                match complete(llm.as_ref(), &render(&template, &vars)).await {
+                    Ok(result) => println!("\n{}\n", result),
+                    Err(e) => eprintln!("{} {}", BULLET, e),
+                }
+            }
+            1 => {
+                // Edit Variables
+                // `{{examples}}` is filled from the prompt's examples.
+                let mut names = variables(&pd.content);
+                names.retain(|name| name != "examples");
+                if names.is_empty() {
+                    println!("{}", style("The prompt has no variables.").yellow());
+                }
+                for name in names {
+                    let value: String = Input::with_theme(&theme)
+                        .with_prompt(&name)
+                        .with_initial_text(map.get(&name).cloned().unwrap_or_default())
+                        .allow_empty(true)
+                        .interact_text()
+                        .map_err(|e| e.to_string())?;
+                    map.insert(name, value);
+                }
+            }
+            2 => {
+                // Edit Prompt
+                if let Some(edited) = editor()
+                    .edit(&pd.content)
+                    .map_err(|e| format!("Editor error: {}", e))?
+                {
+                    pd.content = edited;
+                    println!("{}", style("Content updated.").green());
+                }
+            }
+            3 => {
+                // Change Backend
+                let new_backend: String = Input::with_theme(&theme)
+                    .with_prompt("Backend")
+                    .with_initial_text(backend.clone())
+                    .interact_text()
+                    .map_err(|e| e.to_string())?;
+                match ctx
+                    .prompts()
+                    .check_provider(&workspace, &new_backend)
+                    .and_then(|_| build_llm(ctx, &new_backend))
+                {
+                    Ok(new_llm) => {
+                        llm = new_llm;
+                        backend = new_backend;
+                    }
+                    Err(e) => eprintln!("{} {}", BULLET, e),
+                }
+            }
+            4 => {
+                // Save and Quit
+                if pd.content == original_content {
+                    println!(
+                        "{}",
+                        style("No changes detected. Nothing to save.").yellow()
+                    );
+                } else {
+                    save_with_backup(ctx, id, &mut pd)?;
+                    println!(
+                        "{} Prompt '{}' updated successfully.",
+                        style(OK).green().bold(),
+                        id
+                    );
+                }
+                return Ok(());
+            }
+            _ => {
+                // Quit
+                if pd.content != original_content
+                    && !Confirm::with_theme(&theme)
+                        .with_prompt("Discard the changes to the prompt?")
+                        .default(false)
+                        .interact()
+                        .map_err(|e| e.to_string())?
+                {
+                    continue;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
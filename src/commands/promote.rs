@@ -0,0 +1,23 @@
+use crate::commands::edit::save_with_backup;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+
+/// Make the canary of a prompt its content, served to every run.
+pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
+    let mut pd = ctx.prompts().load(id)?;
+    let canary = pd.canary.take().ok_or_else(|| {
+        format!(
+            "Prompt '{}' has no canary. Create one with `prompt-store canary {} --percent <n>`.",
+            id, id
+        )
+    })?;
+    pd.content = canary.content;
+    save_with_backup(ctx, id, &mut pd)?;
+    println!(
+        "{} Promoted the canary of prompt '{}', which now serves every run.",
+        style(OK).green().bold(),
+        id
+    );
+    Ok(())
+}
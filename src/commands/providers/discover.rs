@@ -0,0 +1,66 @@
+use crate::core::config::add_local_providers;
+use crate::core::discovery::{discover_llamacpp, discover_ollama, llamacpp_host, ollama_host};
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+
+/// Detect local model servers and register their models as providers in config.toml.
+pub async fn run() -> Result<(), String> {
+    let ollama = ollama_host();
+    let llamacpp = llamacpp_host();
+    let detected = [
+        ("Ollama", &ollama, discover_ollama(&ollama).await),
+        ("llama.cpp", &llamacpp, discover_llamacpp(&llamacpp).await),
+    ];
+
+    let mut found = Vec::new();
+    for (label, host, models) in detected {
+        match models {
+            Some(models) => {
+                println!(
+                    "{} {} at {} ({} model(s))",
+                    style(BULLET).green().bold(),
+                    label,
+                    style(host).yellow(),
+                    models.len()
+                );
+                found.extend(models);
+            }
+            None => println!("  {} {} not detected at {}", style("-").dim(), label, host),
+        }
+    }
+
+    if found.is_empty() {
+        println!(
+            "{}",
+            style("No local models found. Start Ollama or a llama.cpp server and try again.")
+                .yellow()
+        );
+        return Ok(());
+    }
+
+    let added = add_local_providers(&found)?;
+    for m in &found {
+        let status = if added.contains(&m.name) {
+            style("added").green()
+        } else {
+            style("already configured").dim()
+        };
+        println!(
+            "  {} {} - {}:{} ({})",
+            style(BULLET).green(),
+            style(&m.name).yellow(),
+            m.backend,
+            m.model,
+            status
+        );
+    }
+    if !added.is_empty() {
+        println!(
+            "{} Registered {} provider(s). Run one with 'prompt-store run <id> --backend {}'.",
+            style(OK).green(),
+            added.len(),
+            added[0]
+        );
+    }
+    Ok(())
+}
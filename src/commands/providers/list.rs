@@ -0,0 +1,42 @@
+use crate::core::config::list_providers;
+use crate::core::paths::config_file;
+use crate::core::secrets::load_api_keys;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List configured providers and where their API key comes from.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let providers = list_providers()?;
+    let keys = load_api_keys(&ctx.base_dir, &ctx.cipher)?;
+
+    if providers.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "No providers configured in {}.",
+                config_file()?.display()
+            ))
+            .yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", style("Providers:").green().bold());
+    for p in providers {
+        let key_source = if keys.contains_key(&p.name) {
+            style("stored key").green()
+        } else {
+            style("env").dim()
+        };
+        println!(
+            "  {} {} - {}:{} ({})",
+            style(BULLET).green(),
+            style(&p.name).yellow(),
+            p.backend,
+            p.model,
+            key_source
+        );
+    }
+    Ok(())
+}
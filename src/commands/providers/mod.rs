@@ -0,0 +1,5 @@
+pub mod discover;
+pub mod list;
+pub mod models;
+pub mod remove_key;
+pub mod set_key;
@@ -0,0 +1,30 @@
+use crate::core::config::load_llm_registry;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the models available from a configured provider.
+pub async fn run(ctx: &AppCtx, name: &str) -> Result<(), String> {
+    let registry = load_llm_registry(ctx)?;
+    let provider = registry
+        .get(name)
+        .ok_or_else(|| format!("Provider '{}' not found in config.toml", name))?;
+
+    let response = provider
+        .list_models(None)
+        .await
+        .map_err(|e| format!("Failed to list models for '{}': {}", name, e))?;
+    let mut models = response.get_models();
+    models.sort();
+
+    if models.is_empty() {
+        println!("{}", style("No models reported by this provider.").yellow());
+        return Ok(());
+    }
+
+    println!("{}", style(format!("Models for {}:", name)).green().bold());
+    for m in models {
+        println!("  {} {}", style(BULLET).green(), m);
+    }
+    Ok(())
+}
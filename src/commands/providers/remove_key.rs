@@ -0,0 +1,24 @@
+use crate::cli::RemoveArgs;
+use crate::core::secrets::{load_api_keys, save_api_keys};
+use crate::core::storage::AppCtx;
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Remove a stored provider API key.
+pub fn run(ctx: &AppCtx, provider: &str, args: RemoveArgs) -> Result<(), String> {
+    let mut keys = load_api_keys(&ctx.base_dir, &ctx.cipher)?;
+    if keys.remove(provider).is_none() {
+        return Err(format!("No stored API key for provider '{}'", provider));
+    }
+    if !confirm_removal(args, &format!("the stored API key of {}", provider))? {
+        return Ok(());
+    }
+    save_api_keys(&ctx.base_dir, &ctx.cipher, &keys)?;
+    println!(
+        "{} API key removed for provider {}",
+        style(BULLET).green().bold(),
+        provider
+    );
+    Ok(())
+}
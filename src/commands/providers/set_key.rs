@@ -0,0 +1,41 @@
+use crate::core::config::list_providers;
+use crate::core::paths::config_file;
+use crate::core::secrets::{load_api_keys, save_api_keys};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use console::style;
+use dialoguer::Password;
+
+/// Store a provider API key encrypted with the master key.
+pub fn run(ctx: &AppCtx, provider: &str) -> Result<(), String> {
+    if !list_providers()?.iter().any(|p| p.name == provider) {
+        println!(
+            "{}",
+            style(format!(
+                "Warning: provider '{}' is not defined in {}.",
+                provider,
+                config_file()?.display()
+            ))
+            .yellow()
+        );
+    }
+
+    let key = Password::new()
+        .with_prompt(format!("API key for '{}'", provider))
+        .interact()
+        .map_err(|e| format!("Password error: {}", e))?;
+    if key.trim().is_empty() {
+        return Err("API key cannot be empty".to_string());
+    }
+
+    let mut keys = load_api_keys(&ctx.base_dir, &ctx.cipher)?;
+    keys.insert(provider.to_string(), key.trim().to_string());
+    save_api_keys(&ctx.base_dir, &ctx.cipher, &keys)?;
+
+    println!(
+        "{} API key stored for provider {}",
+        style(BULLET).green().bold(),
+        style(provider).yellow()
+    );
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::core::config::http_client;
+use crate::core::share::open;
+use crate::core::storage::AppCtx;
+use crate::core::utils::new_id;
+use crate::ui::symbols::BULLET;
+use chrono::Utc;
+use console::style;
+use dialoguer::Password;
+use std::fs;
+
+/// Import a prompt shared with `share`, from a `.pshare` file or a paste URL.
+/// It is saved under a new ID in the default workspace.
+pub async fn run(ctx: &AppCtx, source: &str, passphrase: Option<&str>) -> Result<(), String> {
+    let sealed = if source.starts_with("http://") || source.starts_with("https://") {
+        let resp = http_client()?
+            .get(source)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", source, e))?;
+        if !resp.status().is_success() {
+            return Err(format!(
+                "Failed to fetch {}: status {}",
+                source,
+                resp.status()
+            ));
+        }
+        resp.text()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", source, e))?
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("Read error: {}", e))?
+    };
+
+    let passphrase = match passphrase {
+        Some(p) => p.to_string(),
+        None => Password::new()
+            .with_prompt("Passphrase")
+            .interact()
+            .map_err(|e| format!("Password input error: {}", e))?,
+    };
+    let mut pd = open(&sealed, passphrase.trim())?;
+
+    pd.id = new_id(&ctx.workspaces_dir.join("default"), &pd.title)?;
+    pd.updated_at = Some(Utc::now());
+    ctx.prompts().save(&pd.id, &pd)?;
+    println!(
+        "{} Received '{}' as ID {}",
+        style(BULLET).green().bold(),
+        pd.title,
+        style(&pd.id).yellow()
+    );
+    Ok(())
+}
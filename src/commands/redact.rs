@@ -0,0 +1,22 @@
+use crate::core::config::load_redaction_policy;
+use crate::core::pii::redact;
+use std::fs;
+use std::io::{self, Read};
+
+/// Print a file, or stdin, with the personal data selected by `[redaction]` replaced.
+pub fn run(file: Option<&str>) -> Result<(), String> {
+    let text = match file {
+        Some(path) if path != "-" => {
+            fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?
+        }
+        _ => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            text
+        }
+    };
+    print!("{}", redact(&text, &load_redaction_policy()?)?);
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use crate::core::releases::create_release;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+
+/// Snapshot the prompts and chains of a workspace into an immutable release.
+pub fn run(ctx: &AppCtx, name: &str, workspace: &str) -> Result<(), String> {
+    let prompts = ctx.prompts();
+    let prompt_count = prompts
+        .list(workspace)?
+        .iter()
+        .filter(|p| !p.is_chain_step())
+        .count();
+    let chain_count =
+        prompts.list_chains(workspace)?.len() + prompts.list_chain_definitions(workspace)?.len();
+
+    create_release(ctx, name, workspace)?;
+    println!(
+        "{} Released {} prompts and {} chains of workspace '{}' as '{}'.",
+        style(OK).green().bold(),
+        prompt_count,
+        chain_count,
+        workspace,
+        name
+    );
+    println!(
+        "Pin an application to it with `store.at_release(\"{}\")`.",
+        name
+    );
+    Ok(())
+}
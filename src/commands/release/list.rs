@@ -0,0 +1,34 @@
+use crate::core::releases::list_releases;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use chrono::Local;
+use console::style;
+
+/// List the releases with their workspace and creation time.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let releases = list_releases(&ctx.base_dir)?;
+    if releases.is_empty() {
+        println!(
+            "{}",
+            style("No releases yet. Create one with 'prompt-store release create <name>'.")
+                .yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", style("Releases:").green().bold());
+    for release in releases {
+        let created = release
+            .created_at
+            .map(|at| format!(" at {}", at.with_timezone(&Local).format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default();
+        println!(
+            "  {} {}  workspace '{}'{}",
+            style(BULLET).green(),
+            style(&release.name).yellow(),
+            release.workspace,
+            created
+        );
+    }
+    Ok(())
+}
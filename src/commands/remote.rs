@@ -0,0 +1,239 @@
+//! Client of `--remote`: sends `render`, `run` and `chain run` to the gRPC API of a
+//! `prompt-store serve` instance, so the commands run against its store without the
+//! key or the providers being available locally.
+
+use crate::api::grpc::proto::prompt_store_client::PromptStoreClient;
+use crate::api::grpc::proto::{chain_event::Event, RenderRequest, RunChainRequest, RunRequest};
+use crate::cli::{ChainCmd, Cmd};
+use crate::core::vars::VarSources;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::{Request, Status};
+
+/// Connection to a remote store.
+struct Remote {
+    client: PromptStoreClient<Channel>,
+    /// `authorization` header of the requests, when a token is given.
+    auth: Option<MetadataValue<Ascii>>,
+}
+
+/// Run a command on the store served at `url`, authenticated by `token` if given.
+pub async fn run(url: &str, token: Option<&str>, command: Cmd) -> Result<(), String> {
+    let unsupported = |what: &str| Err(format!("{} is not available with --remote.", what));
+    match command {
+        Cmd::Render { id, vars, lang } => {
+            let mut remote = Remote::connect(url, token).await?;
+            let request = remote.request(RenderRequest {
+                id,
+                vars: parse_vars(&vars),
+                lang: lang.unwrap_or_default(),
+                variant: String::new(),
+            });
+            let response = remote
+                .client
+                .render(request)
+                .await
+                .map_err(status_error)?;
+            println!("{}", response.into_inner().text);
+            Ok(())
+        }
+        Cmd::Run {
+            id,
+            backend,
+            vars,
+            env_profile,
+            exec,
+            lang,
+            variant,
+            ..
+        } => {
+            if env_profile.is_some() {
+                return unsupported("--env");
+            }
+            if exec {
+                return unsupported("--exec");
+            }
+            let mut remote = Remote::connect(url, token).await?;
+            let request = remote.request(RunRequest {
+                id,
+                vars: parse_vars(&vars),
+                backend,
+                lang: lang.unwrap_or_default(),
+                variant: variant.unwrap_or_default(),
+            });
+            let mut chunks = remote
+                .client
+                .run(request)
+                .await
+                .map_err(status_error)?
+                .into_inner();
+            println!();
+            while let Some(chunk) = chunks.message().await.map_err(status_error)? {
+                print!("{}", chunk.text);
+                std::io::stdout().flush().ok();
+            }
+            println!();
+            Ok(())
+        }
+        Cmd::Chain(ChainCmd::Run {
+            id,
+            vars,
+            env_profile,
+            lang,
+            preset,
+            vars_file,
+            vars_stdin,
+            save_preset,
+        }) => {
+            if preset.is_some() || save_preset.is_some() {
+                return unsupported("--preset");
+            }
+            // Variables from files, the environment and stdin are read locally, like
+            // `chain run` does, then sent with the others.
+            let mut sources = VarSources::auto();
+            if let Some(path) = vars_file {
+                sources.files = vec![PathBuf::from(path)];
+            }
+            sources.stdin = vars_stdin;
+            let mut all_vars = sources.resolve()?;
+            all_vars.extend(parse_vars(&vars));
+
+            let mut remote = Remote::connect(url, token).await?;
+            let request = remote.request(RunChainRequest {
+                id: id.clone(),
+                vars: all_vars,
+                env: env_profile.unwrap_or_default(),
+                lang: lang.unwrap_or_default(),
+            });
+            let mut events = remote
+                .client
+                .run_chain(request)
+                .await
+                .map_err(status_error)?
+                .into_inner();
+            println!("Executing chain '{}' on {}...", style(&id).yellow(), url);
+            while let Some(event) = events.message().await.map_err(status_error)? {
+                match event.event {
+                    Some(Event::Step(step)) => println!(
+                        "  {} {} ({} ms)",
+                        style(BULLET).green(),
+                        step.key,
+                        step.latency_ms
+                    ),
+                    Some(Event::Completed(completed)) => {
+                        println!(
+                            "{} {}",
+                            style(OK).green(),
+                            style("Chain execution complete.").green()
+                        );
+                        let outputs: BTreeMap<_, _> = completed.outputs.into_iter().collect();
+                        println!("{:#?}", outputs);
+                        if !completed.finished_by.is_empty() {
+                            println!(
+                                "  {} finished early after step {}",
+                                style(BULLET).green(),
+                                style(&completed.finished_by).yellow()
+                            );
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Ok(())
+        }
+        _ => Err(
+            "This command is not available with --remote, which supports render, run and chain run."
+                .to_string(),
+        ),
+    }
+}
+
+impl Remote {
+    async fn connect(url: &str, token: Option<&str>) -> Result<Self, String> {
+        let mut endpoint = Endpoint::from_shared(url.to_string())
+            .map_err(|e| format!("Invalid remote URL '{}': {}", url, e))?;
+        let host = endpoint.uri().host().unwrap_or_default();
+        if token.is_some() && !url.starts_with("https://") && !is_loopback(host) {
+            return Err(format!(
+                "Refusing to send the token to {} without TLS: use an https:// URL",
+                url
+            ));
+        }
+        if url.starts_with("https://") {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().with_native_roots())
+                .map_err(|e| format!("TLS error: {}", e))?;
+        }
+        let channel = endpoint
+            .connect()
+            .await
+            .map_err(|e| format!("Cannot connect to {}: {}", url, e))?;
+        let auth = token
+            .map(|token| {
+                format!("Bearer {}", token)
+                    .parse()
+                    .map_err(|_| "Invalid token".to_string())
+            })
+            .transpose()?;
+        Ok(Self {
+            client: PromptStoreClient::new(channel),
+            auth,
+        })
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(auth) = &self.auth {
+            request.metadata_mut().insert("authorization", auth.clone());
+        }
+        request
+    }
+}
+
+/// Whether `host` is this machine, which a token may reach without TLS.
+fn is_loopback(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn parse_vars(vars: &[String]) -> HashMap<String, String> {
+    vars.iter()
+        .filter_map(|v| v.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn status_error(status: Status) -> String {
+    format!("Remote error ({:?}): {}", status.code(), status.message())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_loopback_hosts_are_reached_without_tls() {
+        assert!(is_loopback("localhost"));
+        assert!(is_loopback("127.0.0.1"));
+        assert!(is_loopback("[::1]"));
+        assert!(!is_loopback("prompts.internal"));
+        assert!(!is_loopback("10.0.0.5"));
+    }
+
+    #[tokio::test]
+    async fn tokens_are_not_sent_over_plain_http() {
+        let err = Remote::connect("http://prompts.internal:50051", Some("pst_x"))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.starts_with("Refusing to send the token"), "{}", err);
+    }
+}
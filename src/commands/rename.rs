@@ -1,51 +1,196 @@
-use crate::core::storage::{AppCtx, PromptData};
-use aes_gcm::{
-    aead::{Aead, AeadCore, OsRng},
-    Aes256Gcm, Nonce,
-};
-use base64::{engine::general_purpose, Engine as _};
+use crate::commands::uses::{references, update_references};
+use crate::core::journal::JournalEntry;
+use crate::core::storage::{full_id, parse_id, AppCtx};
+use crate::ui::symbols::BULLET;
+use chrono::Utc;
 use console::style;
-use serde_json;
+use dialoguer::Confirm;
 use std::fs;
+use std::path::Path;
 
-/// Rename a prompt.
-pub fn run(ctx: &AppCtx, id: &str, title: &str) -> Result<(), String> {
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID {}", id));
-    }
-
-    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
-    let decoded = general_purpose::STANDARD
-        .decode(encoded.trim_end())
-        .map_err(|_| "Corrupted data".to_string())?;
-    if decoded.len() < 12 {
-        return Err("Corrupted data".to_string());
-    }
-
-    let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-    let plaintext = ctx
-        .cipher
-        .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-        .map_err(|_| "Decrypt error".to_string())?;
-    let mut pd: PromptData =
-        serde_json::from_slice(&plaintext).map_err(|_| "Invalid JSON".to_string())?;
-
-    pd.title = title.to_string();
-
-    let json = serde_json::to_vec(&pd).map_err(|e| format!("Serialize error: {}", e))?;
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = ctx
-        .cipher
-        .encrypt(&nonce, json.as_ref())
-        .map_err(|_| "Encrypt error".to_string())?;
-
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded_out = general_purpose::STANDARD.encode(&out);
-
-    fs::write(&path, encoded_out).map_err(|e| format!("Write error: {}", e))?;
-    println!("{} prompt {} renamed", style("•").green().bold(), id);
+/// Rename a prompt: change its title, its ID within its workspace, or both.
+///
+/// Chain references by title break when the title changes, and references by ID when
+/// the ID does. `update_refs` rewrites them; otherwise they are listed and the rename
+/// needs a confirmation. Prompts linking to a prompt whose ID changes are pointed to
+/// the new ID.
+pub fn run(
+    ctx: &AppCtx,
+    id: &str,
+    title: Option<&str>,
+    new_id: Option<&str>,
+    update_refs: bool,
+) -> Result<(), String> {
+    if title.is_none() && new_id.is_none() {
+        return Err("Nothing to rename: pass --title and/or --id.".to_string());
+    }
+    let prompts = ctx.prompts();
+    let mut pd = prompts.load(id)?;
+    let (workspace, local_id) = parse_id(id);
+    let old_id = full_id(&workspace, &local_id);
+    let old_title = pd.title.clone();
+
+    let target = match new_id {
+        Some(_) if local_id.contains('/') => {
+            return Err("Chain steps cannot change ID.".to_string());
+        }
+        Some(new_id)
+            if new_id.is_empty()
+                || new_id.contains(['/', '\\', ':', '.'])
+                || new_id.contains(char::is_whitespace) =>
+        {
+            return Err(format!("'{}' is not a valid prompt ID.", new_id));
+        }
+        Some(new_id) => {
+            let target = full_id(&workspace, new_id);
+            if target != old_id && prompts.exists(&target) {
+                return Err(format!("A prompt with ID '{}' already exists.", target));
+            }
+            target
+        }
+        None => old_id.clone(),
+    };
+    let new_title = title.unwrap_or(&old_title);
+
+    let refs = references(&prompts, id)?;
+    let breaking: Vec<_> = refs
+        .iter()
+        .filter(|(_, by_title)| {
+            if *by_title {
+                !new_title.eq_ignore_ascii_case(&old_title)
+            } else {
+                target != old_id
+            }
+        })
+        .collect();
+    if !breaking.is_empty() && !update_refs {
+        println!(
+            "{}",
+            style(format!(
+                "Warning: these chains reference '{}' and would break:",
+                id
+            ))
+            .yellow()
+        );
+        for (r, _) in &breaking {
+            println!(
+                "  {} {} (step {}: {})",
+                style(BULLET).yellow(),
+                r.chain,
+                r.step,
+                r.step_id
+            );
+        }
+        if !Confirm::new()
+            .with_prompt("Rename anyway? Use --update-references to rewrite them instead.")
+            .default(false)
+            .interact()
+            .map_err(|e| format!("{} (use --update-references)", e))?
+        {
+            println!("Rename cancelled.");
+            return Ok(());
+        }
+    }
+
+    pd.title = new_title.to_string();
+    pd.id = parse_id(&target).1;
+    pd.updated_at = Some(Utc::now());
+    let mut journal = JournalEntry::new(format!("rename {}", id));
+    for prompt_id in [&old_id, &target] {
+        journal.save(ctx, &ctx.prompt_path(prompt_id))?;
+        journal.save(ctx, &prompts.attachments_dir(prompt_id))?;
+    }
+    let dir = ctx.workspaces_dir.join(&workspace);
+    for backup in backups(&dir, &local_id)? {
+        journal.save(ctx, &dir.join(format!("{}.{}", local_id, backup)))?;
+        journal.save(ctx, &dir.join(format!("{}.{}", pd.id, backup)))?;
+    }
+    if update_refs {
+        for (r, _) in &refs {
+            journal.save(ctx, &prompts.chain_definition_path(&r.chain))?;
+        }
+    }
+    let links = if target != old_id {
+        prompts.links_to(&old_id)?
+    } else {
+        Vec::new()
+    };
+    for link in &links {
+        journal.save(ctx, &ctx.prompt_path(link))?;
+    }
+
+    prompts.save(&target, &pd)?;
+    if target != old_id {
+        for link in &links {
+            prompts.save_link(link, &target)?;
+        }
+        prompts.move_attachments(&old_id, &target)?;
+        prompts.delete(&old_id)?;
+        move_backups(ctx, &workspace, &local_id, &pd.id)?;
+    }
+
+    if update_refs {
+        let updated = update_references(&prompts, &old_id, &old_title, &target, new_title)?;
+        if updated > 0 {
+            println!(
+                "{} {} chain reference(s) updated",
+                style(BULLET).green().bold(),
+                updated
+            );
+        }
+    }
+    if !links.is_empty() {
+        println!(
+            "{} {} link(s) updated",
+            style(BULLET).green().bold(),
+            links.len()
+        );
+    }
+    journal.commit(ctx)?;
+
+    if target == old_id {
+        println!("{} prompt {} renamed", style(BULLET).green().bold(), id);
+    } else {
+        println!(
+            "{} prompt {} renamed to {}",
+            style(BULLET).green().bold(),
+            id,
+            style(&target).yellow()
+        );
+    }
     Ok(())
 }
+
+/// Renames the edit backups (`<id>.<timestamp>.bak`) of a prompt whose ID changed, so
+/// `history` and `revert` keep working.
+fn move_backups(ctx: &AppCtx, workspace: &str, old: &str, new: &str) -> Result<(), String> {
+    let dir = ctx.workspaces_dir.join(workspace);
+    for rest in backups(&dir, old)? {
+        fs::rename(
+            dir.join(format!("{}.{}", old, rest)),
+            dir.join(format!("{}.{}", new, rest)),
+        )
+        .map_err(|e| format!("Rename error: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Lists the edit backups of a prompt in `dir`, as their names without `<id>.`.
+fn backups(dir: &Path, id: &str) -> Result<Vec<String>, String> {
+    let prefix = format!("{}.", id);
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let name = entry
+            .map_err(|e| format!("Dir entry error: {}", e))?
+            .file_name()
+            .to_string_lossy()
+            .into_owned();
+        if let Some(rest) = name
+            .strip_prefix(&prefix)
+            .filter(|rest| rest.ends_with(".bak"))
+        {
+            backups.push(rest.to_string());
+        }
+    }
+    Ok(backups)
+}
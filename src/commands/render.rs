@@ -1,28 +1,23 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx};
-use regex::Regex;
+use crate::core::storage::AppCtx;
+use crate::core::template::render;
 use std::collections::HashMap;
 
-/// Render a template prompt with variables and print it to stdout.
-pub fn run(ctx: &AppCtx, id: &str, vars: &[String]) -> Result<(), String> {
+/// Render a template prompt with variables, in a language if `lang` is set, and print it
+/// to stdout.
+pub fn run(ctx: &AppCtx, id: &str, vars: &[String], lang: Option<&str>) -> Result<(), String> {
     let mut map = HashMap::new();
     for v in vars {
         if let Some((key, value)) = v.split_once('=') {
-            map.insert(key.trim(), value.trim());
+            map.insert(key.trim().to_string(), value.trim().to_string());
         }
     }
 
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID '{}'", id));
+    let pd = ctx.prompts().load(id)?;
+    let template = ctx.prompts().expand_attachments(id, pd.content_for(lang))?;
+    if let Some(examples) = pd.few_shot_examples() {
+        map.entry("examples".to_string()).or_insert(examples);
     }
 
-    let pd = decrypt_full_prompt(&path, &ctx.cipher)?;
-
-    let re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
-    let rendered = re.replace_all(&pd.content, |caps: &regex::Captures| {
-        map.get(&caps[1]).copied().unwrap_or("").to_string()
-    });
-
-    println!("{}", rendered);
+    println!("{}", render(&template, &map));
     Ok(())
-}
\ No newline at end of file
+}
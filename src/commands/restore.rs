@@ -0,0 +1,75 @@
+use crate::core::backup::{
+    archive_kind, open_backup, restore_backup, verify_backup, ArchiveKind, KEY_FILE,
+};
+use crate::core::cipher::{StoreCipher, AES_256_GCM};
+use crate::core::crypto::{decrypt_key_with_password, is_password_protected, plain_key};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+use dialoguer::{Confirm, Password};
+use std::fs;
+
+/// Restore the whole store from a backup archive, after verifying its integrity.
+pub fn run(ctx: &AppCtx, file: &str, yes: bool) -> Result<(), String> {
+    let data = fs::read(file).map_err(|e| format!("Read error: {}", e))?;
+    let kind = archive_kind(&data)?;
+    if kind == ArchiveKind::Transfer {
+        return Err(
+            "This is a transfer archive, import it with `prompt-store transfer import`."
+                .to_string(),
+        );
+    }
+    let password = if kind.needs_password() {
+        Some(
+            Password::new()
+                .with_prompt("Backup password")
+                .interact()
+                .map_err(|e| format!("Password input error: {}", e))?,
+        )
+    } else {
+        None
+    };
+    let files = open_backup(ctx, &data, password.as_deref())?;
+
+    // Verify against the key the store will use once the backup is restored.
+    let prompts = match files.iter().find(|f| f.path == KEY_FILE) {
+        Some(key_file) => {
+            let key = if !is_password_protected(&key_file.data) {
+                plain_key(&key_file.data)?
+            } else {
+                let key_password = Password::new()
+                    .with_prompt("Password of the backed-up master key")
+                    .interact()
+                    .map_err(|e| format!("Password input error: {}", e))?;
+                decrypt_key_with_password(&key_file.data, &key_password)?
+            };
+            verify_backup(&files, &StoreCipher::from_key(&key, AES_256_GCM)?)?
+        }
+        None => verify_backup(&files, &ctx.cipher)?,
+    };
+    println!(
+        "{} Backup verified: {} files, {} prompts",
+        style(OK).green(),
+        files.len(),
+        prompts
+    );
+
+    if !yes
+        && !Confirm::new()
+            .with_prompt("Replace the current store with this backup?")
+            .default(false)
+            .interact()
+            .map_err(|e| e.to_string())?
+    {
+        println!("{}", style("Restore cancelled.").yellow());
+        return Ok(());
+    }
+
+    restore_backup(ctx, &files)?;
+    println!(
+        "{} Store restored from {}",
+        style(OK).green(),
+        style(file).yellow()
+    );
+    Ok(())
+}
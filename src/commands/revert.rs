@@ -1,4 +1,5 @@
 use crate::core::storage::{parse_id, AppCtx};
+use crate::ui::symbols::BULLET;
 use chrono::Local;
 use console::style;
 use std::fs;
@@ -48,6 +49,10 @@ pub fn run(ctx: &AppCtx, id: &str, ts: Option<&str>) -> Result<(), String> {
     fs::copy(&main_path, &current_backup).map_err(|e| format!("Backup current error: {}", e))?;
 
     fs::copy(&backup_path, &main_path).map_err(|e| format!("Revert error: {}", e))?;
-    println!("{} reverted to {}", style("•").green().bold(), target_name);
+    println!(
+        "{} reverted to {}",
+        style(BULLET).green().bold(),
+        target_name
+    );
     Ok(())
 }
@@ -1,45 +1,178 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx};
+use crate::core::config::{load_input_redaction, load_llm_registry};
+use crate::core::discovery::ollama_host;
+use crate::core::logging::LoggedProvider;
+use crate::core::pii::redact_values;
+use crate::core::policy::load_policies;
+use crate::core::profiles::load_profile;
+use crate::core::template::render;
+use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::core::usage::{self, Usage};
+use crate::ui::symbols::{ERR, OK};
+use console::style;
+use dialoguer::Confirm;
 use llm::{
     builder::{LLMBackend, LLMBuilder},
     chat::ChatMessage,
+    LLMProvider,
 };
 use regex::Regex;
 use spinners::{Spinner, Spinners};
 use std::collections::HashMap;
 use std::env;
+use std::process::Command;
 use std::str::FromStr;
 
-/// Execute a prompt with an LLM and print the response.
+/// Tag marking a prompt whose output is a shell command that may be executed with `--exec`.
+pub const COMMAND_TAG: &str = "command";
+
+/// Options controlling execution of a generated shell command.
+pub struct ExecOptions {
+    /// Skip the confirmation prompt before running the command.
+    pub yes: bool,
+    /// Optional follow-up prompt receiving the command's exit status and output.
+    pub then: Option<String>,
+}
+
+/// Which variables and text of a prompt `run` uses.
+pub struct RunOptions<'a> {
+    /// Environment profile providing default variables.
+    pub env_profile: Option<&'a str>,
+    /// Language of the translation to run, falling back to the default content.
+    pub lang: Option<&'a str>,
+    /// Variant to run instead of the content, counted in the usage history.
+    pub variant: Option<&'a str>,
+}
+
+/// Execute a prompt with an LLM and print the response. Chain steps can be addressed
+/// by title.
 pub async fn run(
     ctx: &AppCtx,
     id: &str,
     backend: &str,
     vars: &[String],
+    opts: &RunOptions<'_>,
+    exec: Option<ExecOptions>,
 ) -> Result<(), String> {
+    let id = &ctx.prompts().resolve_step(id)?;
+    let lang = opts.lang;
     let mut map = HashMap::new();
+    if let Some(profile) = opts.env_profile {
+        let profile_vars = load_profile(&ctx.base_dir, &ctx.cipher, profile)?
+            .ok_or_else(|| format!("No profile named '{}'", profile))?;
+        map.extend(profile_vars);
+    }
     for v in vars {
         if let Some((key, value)) = v.split_once('=') {
-            map.insert(key.trim(), value.trim());
+            map.insert(key.trim().to_string(), value.trim().to_string());
         }
     }
 
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID '{}'", id));
+    let pd = ctx.prompts().load(id)?;
+    if exec.is_some() && !pd.tags.iter().any(|t| t.eq_ignore_ascii_case(COMMAND_TAG)) {
+        return Err(format!(
+            "Prompt '{}' is not tagged as a command generator. Add the '{}' tag to use --exec.",
+            id, COMMAND_TAG
+        ));
+    }
+
+    let redaction = load_input_redaction()?;
+    if let Some(policy) = &redaction {
+        redact_values(&mut map, policy)?;
+    }
+
+    let content = match opts.variant {
+        Some(variant) => pd
+            .variant_content(variant)
+            .ok_or_else(|| format!("Prompt '{}' has no variant '{}'", id, variant))?,
+        None => pd.content_for(lang),
+    };
+    let template = ctx.prompts().expand_attachments(id, content)?;
+    load_policies()?.check_content(id, &template)?;
+    ctx.prompts().check_provider(&parse_id(id).0, backend)?;
+    let llm = build_llm(ctx, backend)?;
+    let vars = with_examples(&map, &pd);
+    let result = complete(llm.as_ref(), &render(&template, &vars)).await?;
+    usage::record(&ctx.base_dir, id, Usage::Run);
+    if let Some(variant) = opts.variant {
+        usage::record_variant(&ctx.base_dir, id, variant);
+    }
+
+    let Some(opts) = exec else {
+        println!("\n{}", result);
+        return Ok(());
+    };
+
+    let command = extract_command(&result);
+    if command.is_empty() {
+        return Err("The LLM did not return a command to execute.".to_string());
+    }
+
+    println!("\n{}", style("Generated command:").green().bold());
+    println!("  {}", style(&command).yellow());
+
+    if !opts.yes
+        && !Confirm::new()
+            .with_prompt("Run this command?")
+            .default(false)
+            .interact()
+            .unwrap_or(false)
+    {
+        println!("Execution cancelled.");
+        return Ok(());
+    }
+
+    let output = shell_command(&command)
+        .output()
+        .map_err(|e| format!("Failed to run command: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let exit_status = output
+        .status
+        .code()
+        .map_or_else(|| "terminated".to_string(), |c| c.to_string());
+
+    print!("{}", stdout);
+    eprint!("{}", stderr);
+    let status_line = format!("Command exited with status {}", exit_status);
+    if output.status.success() {
+        println!("{} {}", style(OK).green(), status_line);
+    } else {
+        println!("{} {}", style(ERR).red(), status_line);
     }
 
-    let pd = decrypt_full_prompt(&path, &ctx.cipher)?;
+    if let Some(follow_up_id) = opts.then {
+        let follow_up = ctx.prompts().load(&follow_up_id)?;
+        let template = ctx
+            .prompts()
+            .expand_attachments(&follow_up_id, follow_up.content_for(lang))?;
 
-    let re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
-    let rendered = re
-        .replace_all(&pd.content, |caps: &regex::Captures| {
-            map.get(&caps[1]).copied().unwrap_or("").to_string()
-        })
-        .to_string();
+        map.insert("command".to_string(), command);
+        map.insert("exit_status".to_string(), exit_status);
+        map.insert("stdout".to_string(), stdout);
+        map.insert("stderr".to_string(), stderr);
+        if let Some(policy) = &redaction {
+            redact_values(&mut map, policy)?;
+        }
+
+        let vars = with_examples(&map, &follow_up);
+        let result = complete(llm.as_ref(), &render(&template, &vars)).await?;
+        println!("\n{}", result);
+    }
 
-    let (provider_str, model) = backend
-        .split_once(':')
-        .ok_or("Invalid backend format. Use 'provider:model'")?;
+    Ok(())
+}
+
+/// Builds an LLM provider from a `provider:model` backend string,
+/// or looks up a provider configured in `config.toml` when no model is given.
+pub(crate) fn build_llm(ctx: &AppCtx, backend: &str) -> Result<Box<dyn LLMProvider>, String> {
+    let Some((provider_str, model)) = backend.split_once(':') else {
+        return load_llm_registry(ctx)?.backends.remove(backend).ok_or_else(|| {
+            format!(
+                "Invalid backend '{}'. Use 'provider:model' or a provider name from config.toml",
+                backend
+            )
+        });
+    };
     let provider =
         LLMBackend::from_str(provider_str).map_err(|_| format!("Unknown provider: {}", provider_str))?;
 
@@ -56,24 +189,75 @@ pub async fn run(
         _ => return Err("Provider not yet supported for direct CLI execution.".to_string()),
     };
 
-    let api_key = env::var(api_key_env_var)
-        .map_err(|_| format!("API key env var '{}' not found.", api_key_env_var))?;
+    let policies = load_policies()?;
+    let temperature = policies.max_temperature();
+    policies.check_provider(backend, provider_str, temperature)?;
 
-    let llm = LLMBuilder::new()
-        .backend(provider)
-        .api_key(api_key)
-        .model(model)
-        .build()
-        .map_err(|e| e.to_string())?;
+    let is_ollama = matches!(provider, LLMBackend::Ollama);
+    let mut builder = LLMBuilder::new().backend(provider).model(model);
+    if let Some(temperature) = temperature {
+        builder = builder.temperature(temperature);
+    }
+    if is_ollama {
+        // A local Ollama instance needs no key and works offline.
+        builder = builder.base_url(ollama_host());
+        if let Ok(key) = env::var(api_key_env_var) {
+            builder = builder.api_key(key);
+        }
+    } else {
+        let api_key = env::var(api_key_env_var)
+            .map_err(|_| format!("API key env var '{}' not found.", api_key_env_var))?;
+        builder = builder.api_key(api_key);
+    }
+
+    let provider = builder.build().map_err(|e| e.to_string())?;
+    Ok(Box::new(LoggedProvider::new(backend, provider)))
+}
+
+/// Adds the few-shot text of the prompt's examples as `{{examples}}`, unless the variable
+/// is already given.
+pub(crate) fn with_examples(
+    vars: &HashMap<String, String>,
+    pd: &PromptData,
+) -> HashMap<String, String> {
+    let mut vars = vars.clone();
+    if let Some(examples) = pd.few_shot_examples() {
+        vars.entry("examples".to_string()).or_insert(examples);
+    }
+    vars
+}
 
+/// Sends a single user message to the LLM and returns the text response.
+pub(crate) async fn complete(llm: &dyn LLMProvider, prompt: &str) -> Result<String, String> {
     let mut sp = Spinner::new(Spinners::Dots9, "Waiting for LLM response...".into());
 
-    let messages = vec![ChatMessage::user().content(&rendered).build()];
+    let messages = vec![ChatMessage::user().content(prompt).build()];
     let response = llm.chat(&messages).await.map_err(|e| e.to_string())?;
     let result = response.text().unwrap_or_default();
 
-    sp.stop_with_message("✔ Response received.".into());
-    println!("\n{}", result);
+    sp.stop_with_message(format!("{} Response received.", OK));
+    Ok(result)
+}
 
-    Ok(())
-}
\ No newline at end of file
+/// Extracts the command from an LLM response, unwrapping a fenced code block if present.
+fn extract_command(response: &str) -> String {
+    let re = Regex::new(r"(?s)```[\w-]*\s*\n(.*?)```").unwrap();
+    let command = re
+        .captures(response)
+        .and_then(|caps| caps.get(1))
+        .map_or(response, |m| m.as_str());
+    command.trim().trim_matches('`').trim().to_string()
+}
+
+/// Builds a platform shell invocation for the given command line.
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
@@ -0,0 +1,131 @@
+use crate::cli::ReportFormat;
+use crate::core::config::load_redaction_policy;
+use crate::core::pii::redact;
+use crate::core::runs::{load_run, RunRecord};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use chrono::Local;
+use console::style;
+use std::fs;
+
+/// Write a report of a chain run, with the prompts redacted according to `[redaction]`.
+pub fn run(
+    ctx: &AppCtx,
+    run_id: &str,
+    format: ReportFormat,
+    out: Option<&str>,
+) -> Result<(), String> {
+    let mut record = load_run(&ctx.base_dir, &ctx.cipher, run_id)?
+        .ok_or_else(|| format!("No run with ID '{}'. See 'prompt-store runs list'.", run_id))?;
+    let policy = load_redaction_policy()?;
+    for step in &mut record.steps {
+        step.prompt = redact(&step.prompt, &policy)?;
+    }
+
+    let report = match format {
+        ReportFormat::Md => markdown_report(run_id, &record),
+        ReportFormat::Html => html_report(run_id, &record),
+    };
+    match out {
+        Some(path) => {
+            fs::write(path, report).map_err(|e| format!("Write error: {}", e))?;
+            eprintln!(
+                "{} Report of run {} written to {}",
+                style(BULLET).green().bold(),
+                run_id,
+                path
+            );
+        }
+        None => print!("{}", report),
+    }
+    Ok(())
+}
+
+fn markdown_report(run_id: &str, record: &RunRecord) -> String {
+    let mut md = format!("# Chain run `{}`\n\n", record.chain);
+    md.push_str(&format!("- **Run:** {}\n", run_id));
+    md.push_str(&format!("- **Started:** {}\n", started(record)));
+    md.push_str(&format!(
+        "- **Steps:** {} ({} ms in total)\n",
+        record.steps.len(),
+        total_latency(record)
+    ));
+    for (i, step) in record.steps.iter().enumerate() {
+        md.push_str(&format!("\n## {}. `{}`\n\n", i + 1, step.key));
+        if let Some(provider) = &step.provider {
+            md.push_str(&format!("- **Provider:** {}\n", provider));
+        }
+        md.push_str(&format!("- **Latency:** {} ms\n", step.latency_ms));
+        md.push_str(&format!("\n**Prompt**\n\n{}\n", fenced(&step.prompt)));
+        md.push_str(&format!("\n**Output**\n\n{}\n", fenced(&step.output)));
+    }
+    md
+}
+
+fn html_report(run_id: &str, record: &RunRecord) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Chain run {}</title>\n\
+         <style>\nbody {{ font-family: sans-serif; max-width: 60rem; margin: 2rem auto; }}\n\
+         pre {{ background: #f4f4f4; padding: 1rem; white-space: pre-wrap; }}\n</style>\n\
+         </head>\n<body>\n<h1>Chain run <code>{}</code></h1>\n<ul>\n",
+        escape(&record.chain),
+        escape(&record.chain)
+    );
+    html.push_str(&format!("<li><b>Run:</b> {}</li>\n", escape(run_id)));
+    html.push_str(&format!("<li><b>Started:</b> {}</li>\n", started(record)));
+    html.push_str(&format!(
+        "<li><b>Steps:</b> {} ({} ms in total)</li>\n</ul>\n",
+        record.steps.len(),
+        total_latency(record)
+    ));
+    for (i, step) in record.steps.iter().enumerate() {
+        html.push_str(&format!(
+            "<h2>{}. <code>{}</code></h2>\n<ul>\n",
+            i + 1,
+            escape(&step.key)
+        ));
+        if let Some(provider) = &step.provider {
+            html.push_str(&format!("<li><b>Provider:</b> {}</li>\n", escape(provider)));
+        }
+        html.push_str(&format!(
+            "<li><b>Latency:</b> {} ms</li>\n</ul>\n",
+            step.latency_ms
+        ));
+        html.push_str(&format!(
+            "<h3>Prompt</h3>\n<pre>{}</pre>\n",
+            escape(&step.prompt)
+        ));
+        html.push_str(&format!(
+            "<h3>Output</h3>\n<pre>{}</pre>\n",
+            escape(&step.output)
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn started(record: &RunRecord) -> String {
+    record
+        .started_at
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn total_latency(record: &RunRecord) -> u64 {
+    record.steps.iter().map(|s| s.latency_ms).sum()
+}
+
+/// Wraps text in a code fence longer than any run of backticks it contains.
+fn fenced(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}text\n{}\n{}", fence, text.trim_end(), fence)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
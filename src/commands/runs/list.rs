@@ -0,0 +1,42 @@
+use crate::core::runs::{list_runs, load_run};
+use crate::core::storage::AppCtx;
+use crate::ui::output::{fit, print_paged};
+use crate::ui::symbols::BULLET;
+use chrono::Local;
+use console::style;
+use std::fmt::Write as _;
+
+/// List the recorded chain runs, most recent first.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let ids = list_runs(&ctx.base_dir)?;
+    if ids.is_empty() {
+        println!(
+            "{}",
+            style("No runs recorded yet. Runs of 'prompt-store chain run' are kept here.").yellow()
+        );
+        return Ok(());
+    }
+
+    let mut out = format!("{}\n", style("Chain runs:").green().bold());
+    for id in ids {
+        let Some(record) = load_run(&ctx.base_dir, &ctx.cipher, &id)? else {
+            continue;
+        };
+        let latency: u64 = record.steps.iter().map(|s| s.latency_ms).sum();
+        let line = format!(
+            "  {} {}  {} ({} steps, {} ms) at {}",
+            style(BULLET).green(),
+            style(&id).yellow(),
+            record.chain,
+            record.steps.len(),
+            latency,
+            record
+                .started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+        writeln!(out, "{}", fit(&line)).unwrap();
+    }
+    print_paged(&out);
+    Ok(())
+}
@@ -1,9 +1,16 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx};
+use crate::core::storage::AppCtx;
+use crate::ui::output::{fit, print_paged};
+use crate::ui::symbols::BULLET;
 use console::style;
-use std::fs;
-use std::path::Path;
+use serde_yaml::Value;
+use std::fmt::Write as _;
 
-/// Search prompts by title, optional tag, optional full-text content across all workspaces.
+/// Search prompts and chains by title, optional tag, optional full-text content across
+/// all workspaces.
+///
+/// Chain steps are searched like prompts and reported as `chain-id (step 2)`. YAML chain
+/// definitions have no tags; without a tag filter, their steps match on any of their
+/// values (step IDs, prompt IDs, providers, ...), to find which chains use a prompt.
 pub fn run(
     ctx: &AppCtx,
     query: &str,
@@ -12,13 +19,54 @@ pub fn run(
 ) -> Result<(), String> {
     let q = query.to_lowercase();
     let tag = tag_filter.map(|s| s.to_lowercase());
-    let mut hits = Vec::new();
 
-    if ctx.workspaces_dir.exists() {
-        for workspace_entry in fs::read_dir(&ctx.workspaces_dir).map_err(|e| e.to_string())? {
-            let workspace_path = workspace_entry.map_err(|e| e.to_string())?.path();
-            if workspace_path.is_dir() {
-                find_prompts_recursive(&workspace_path, &ctx, &q, &tag, search_content, &mut hits)?;
+    let repo = ctx.prompts();
+    let has_tag = |tags: &[String]| {
+        tag.as_ref()
+            .is_none_or(|t| tags.iter().any(|x| x.to_lowercase() == *t))
+    };
+    let text_matches = |title: &str, content: &str| {
+        title.to_lowercase().contains(&q)
+            || (search_content && content.to_lowercase().contains(&q))
+    };
+
+    let mut hits: Vec<(String, String)> = repo
+        .list_all()?
+        .into_iter()
+        .filter(|p| !p.is_chain_step())
+        .filter(|p| text_matches(&p.data.title, &p.data.content) && has_tag(&p.data.tags))
+        .map(|p| (p.full_id, p.data.title))
+        .collect();
+
+    for workspace in repo.workspaces()? {
+        for (id, chain) in repo.list_chains(&workspace)? {
+            if !has_tag(&chain.tags) {
+                continue;
+            }
+            if chain.title.to_lowercase().contains(&q) {
+                hits.push((id.clone(), format!("{} (Chain)", chain.title)));
+            }
+            for step in repo.chain_steps(&id)? {
+                if text_matches(&step.data.title, &step.data.content) {
+                    let step_no = step.full_id.rsplit('/').next().unwrap_or_default();
+                    hits.push((format!("{} (step {})", id, step_no), step.data.title));
+                }
+            }
+        }
+
+        if tag.is_some() {
+            continue;
+        }
+        for id in repo.list_chain_definitions(&workspace)? {
+            // Definitions that cannot be decrypted are skipped, as prompts are.
+            let Ok(yaml) = repo.load_chain_definition(&id) else {
+                continue;
+            };
+            if id.to_lowercase().contains(&q) {
+                hits.push((id.clone(), "(YAML chain)".to_string()));
+            }
+            for (step_no, label) in matching_steps(&yaml, &q) {
+                hits.push((format!("{} (step {})", id, step_no), label));
             }
         }
     }
@@ -26,41 +74,62 @@ pub fn run(
     if hits.is_empty() {
         println!("{}", style("No match").yellow());
     } else {
-        println!("{}", style("Matches:").green().bold());
+        let mut out = format!("{}\n", style("Matches:").green().bold());
         for (id, title) in hits {
-            println!("  {} {} - {}", style("•").green(), style(id).yellow(), title);
+            let line = format!(
+                "  {} {} - {}",
+                style(BULLET).green(),
+                style(id).yellow(),
+                title
+            );
+            writeln!(out, "{}", fit(&line)).unwrap();
         }
+        print_paged(&out);
     }
     Ok(())
 }
 
-fn find_prompts_recursive(
-    dir: &Path,
-    ctx: &AppCtx,
-    q: &str,
-    tag: &Option<String>,
-    search_content: bool,
-    hits: &mut Vec<(String, String)>,
-) -> Result<(), String> {
-    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
-        let path = entry.map_err(|e| e.to_string())?.path();
-        if path.is_dir() {
-            find_prompts_recursive(&path, ctx, q, tag, search_content, hits)?;
-        } else if path.extension().and_then(|s| s.to_str()) == Some("prompt") {
-            if let Ok(pd) = decrypt_full_prompt(&path, &ctx.cipher) {
-                let mut match_ok = pd.title.to_lowercase().contains(q);
-                if search_content {
-                    match_ok |= pd.content.to_lowercase().contains(q);
-                }
-                if let Some(t) = tag {
-                    match_ok &= pd.tags.iter().any(|x| x.to_lowercase() == *t);
-                }
+/// Returns the steps (numbered from 1) of a YAML chain with a value containing `q`,
+/// labelled with the IDs of the matching (parallel sub-)steps.
+fn matching_steps(yaml: &str, q: &str) -> Vec<(usize, String)> {
+    let Ok(doc) = serde_yaml::from_str::<Value>(yaml) else {
+        return Vec::new();
+    };
+    let Some(steps) = doc.get("steps").and_then(Value::as_sequence) else {
+        return Vec::new();
+    };
+    steps
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| contains(step, q))
+        .map(|(n, step)| {
+            let mut ids = Vec::new();
+            matching_ids(step, q, &mut ids);
+            (n + 1, ids.join(", "))
+        })
+        .collect()
+}
 
-                if match_ok {
-                    hits.push((pd.id, pd.title));
-                }
-            }
-        }
+/// Whether any scalar value of a YAML node (keys excluded) contains `q`.
+fn contains(value: &Value, q: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(q),
+        Value::Sequence(items) => items.iter().any(|v| contains(v, q)),
+        Value::Mapping(map) => map.values().any(|v| contains(v, q)),
+        _ => false,
     }
-    Ok(())
-}
\ No newline at end of file
+}
+
+/// Collects the `id` of the innermost steps containing `q`, descending into
+/// wrappers such as `parallel:` or `fetch:`.
+fn matching_ids<'a>(value: &'a Value, q: &str, out: &mut Vec<&'a str>) {
+    match value {
+        Value::Sequence(items) => items.iter().for_each(|v| matching_ids(v, q, out)),
+        Value::Mapping(map) => match map.get("id").and_then(Value::as_str) {
+            Some(id) if contains(value, q) => out.push(id),
+            Some(_) => {}
+            None => map.values().for_each(|v| matching_ids(v, q, out)),
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,100 @@
+use crate::api::grpc::GrpcService;
+use crate::api::PromptStore;
+use crate::commands::monitor;
+use crate::core::metrics;
+use crate::core::tokens::load_tokens;
+use crate::ui::symbols::{BULLET, OK, WARN};
+use console::style;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a shutdown waits for the runs in progress, within the 30 seconds
+/// Kubernetes gives a pod by default.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Serve the gRPC API of `proto/prompt_store.proto` until interrupted (Ctrl+C or
+/// SIGTERM), and its metrics and health checks on `metrics_addr` if given.
+///
+/// A shutdown stops accepting requests and waits for the runs in progress.
+pub async fn run(addr: &str, metrics_addr: Option<&str>) -> Result<(), String> {
+    let addr: SocketAddr = addr
+        .parse()
+        .map_err(|e| format!("Invalid address '{}': {}", addr, e))?;
+    let store = Arc::new(PromptStore::init().map_err(|e| e.to_string())?);
+    let tokens = load_tokens(&store.ctx.base_dir, &store.ctx.cipher)?;
+
+    println!(
+        "{} Serving gRPC on {} (Ctrl+C to stop)",
+        style(BULLET).green().bold(),
+        addr
+    );
+    if tokens.is_empty() {
+        println!(
+            "{} No API tokens: every request is accepted (see 'prompt-store tokens add')",
+            style(WARN).yellow()
+        );
+    } else {
+        println!(
+            "{} {} API tokens loaded, requests need 'authorization: Bearer <token>'",
+            style(BULLET).green().bold(),
+            tokens.len()
+        );
+    }
+    if let Some(metrics_addr) = metrics_addr {
+        let checked = Arc::clone(&store);
+        let metrics_addr =
+            monitor::spawn(metrics_addr, Arc::new(move || readiness(&checked))).await?;
+        println!(
+            "{} Metrics on http://{}/metrics, health checks on /healthz and /readyz",
+            style(BULLET).green().bold(),
+            metrics_addr
+        );
+    }
+    tonic::transport::Server::builder()
+        .add_service(GrpcService::new(store).with_tokens(tokens).into_server())
+        .serve_with_shutdown(addr, async {
+            monitor::shutdown_signal().await;
+            println!(
+                "{} Shutting down, finishing {} runs in progress...",
+                style(BULLET).yellow().bold(),
+                metrics::in_flight()
+            );
+        })
+        .await
+        .map_err(|e| format!("gRPC server error: {}", e))?;
+
+    // Runs stream from tasks of their own, which may outlive their connection.
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    while metrics::in_flight() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if metrics::in_flight() > 0 {
+        println!(
+            "{} Stopped with {} runs still in progress",
+            style(WARN).yellow(),
+            metrics::in_flight()
+        );
+        return Ok(());
+    }
+    println!("{} Server stopped", style(OK).green().bold());
+    Ok(())
+}
+
+/// Checks of `/readyz`: the key decrypts the store and providers are configured.
+fn readiness(store: &PromptStore) -> Result<(), String> {
+    let repo = store.ctx.prompts();
+    let files = repo
+        .files()
+        .map_err(|e| format!("Store not readable: {}", e))?;
+    // Decrypting a prompt shows the key is unlocked and is the one of the store.
+    if let Some((id, _)) = files.first() {
+        repo.load_file(id)
+            .map_err(|e| format!("Store not readable: {}", e))?;
+    }
+    let registry = store.config_registry().map_err(|e| e.to_string())?;
+    if registry.backends.is_empty() {
+        return Err("No providers configured in config.toml".to_string());
+    }
+    Ok(())
+}
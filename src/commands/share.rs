@@ -0,0 +1,70 @@
+use crate::core::config::{http_client, load_share_endpoint};
+use crate::core::share::{generate_passphrase, seal};
+use crate::core::storage::AppCtx;
+use crate::core::utils::slugify;
+use crate::ui::symbols::BULLET;
+use console::style;
+use std::fs;
+
+/// Seal a prompt with a random passphrase, then upload it to the configured paste
+/// endpoint or write it to a `.pshare` file. The passphrase is printed separately.
+pub async fn run(ctx: &AppCtx, id: &str, out: Option<&str>) -> Result<(), String> {
+    let pd = ctx.prompts().load(id)?;
+    let passphrase = generate_passphrase();
+    let sealed = seal(&pd, &passphrase)?;
+
+    let endpoint = match out {
+        Some(_) => None,
+        None => load_share_endpoint()?,
+    };
+    let location = match endpoint {
+        Some(endpoint) => upload(&endpoint, sealed).await?,
+        None => {
+            let path = match out {
+                Some(path) => path.to_string(),
+                None => match slugify(&pd.title) {
+                    slug if slug.is_empty() => format!("{}.pshare", id),
+                    slug => format!("{}.pshare", slug),
+                },
+            };
+            fs::write(&path, sealed).map_err(|e| format!("Write error: {}", e))?;
+            path
+        }
+    };
+
+    println!(
+        "{} Shared '{}' at {}",
+        style(BULLET).green().bold(),
+        pd.title,
+        style(&location).yellow()
+    );
+    println!(
+        "Passphrase (send it through a different channel): {}",
+        style(&passphrase).bold()
+    );
+    println!("The recipient runs: prompt-store receive {}", location);
+    Ok(())
+}
+
+/// Posts the sealed prompt to a paste endpoint, which answers with its URL.
+async fn upload(endpoint: &str, sealed: String) -> Result<String, String> {
+    let resp = http_client()?
+        .post(endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain")
+        .body(sealed)
+        .send()
+        .await
+        .map_err(|e| format!("Upload to {} failed: {}", endpoint, e))?;
+    if !resp.status().is_success() {
+        return Err(format!(
+            "Upload to {} failed with status {}",
+            endpoint,
+            resp.status()
+        ));
+    }
+    let url = resp
+        .text()
+        .await
+        .map_err(|e| format!("Upload to {} failed: {}", endpoint, e))?;
+    Ok(url.trim().to_string())
+}
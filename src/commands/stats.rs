@@ -1,90 +1,229 @@
-use crate::core::storage::{decrypt_full_prompt, AppCtx};
+use crate::core::storage::{full_id, AppCtx};
+use crate::core::usage;
 use console::style;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-/// Display statistics about the prompt store.
-pub fn run(ctx: &AppCtx) -> Result<(), String> {
-    let mut standalone_prompts = 0;
-    let mut chain_count = 0;
-    let mut prompts_in_chains = 0;
-    let mut tag_counts: HashMap<String, usize> = HashMap::new();
+/// Entries shown in the top tags and most-run lists.
+const TOP: usize = 10;
 
-    if ctx.workspaces_dir.exists() {
-        for workspace_entry in fs::read_dir(&ctx.workspaces_dir).map_err(|e| e.to_string())? {
-            let workspace_path = workspace_entry.map_err(|e| e.to_string())?.path();
-            if !workspace_path.is_dir() {
-                continue;
-            }
-            for entry in fs::read_dir(&workspace_path).map_err(|e| e.to_string())? {
-                let path = entry.map_err(|e| e.to_string())?.path();
-                if path.is_dir() {
-                    chain_count += 1;
-                    prompts_in_chains += process_directory(&path, &ctx.cipher, &mut tag_counts)?;
-                } else if path.extension().and_then(|s| s.to_str()) == Some("prompt") {
-                    standalone_prompts += 1;
-                    if let Ok(prompt) = decrypt_full_prompt(&path, &ctx.cipher) {
-                        for tag in prompt.tags {
-                            *tag_counts.entry(tag).or_insert(0) += 1;
-                        }
-                    }
-                }
-            }
-        }
+#[derive(Serialize)]
+struct StoreStats {
+    chains: usize,
+    standalone_prompts: usize,
+    prompts_in_chains: usize,
+    total_prompts: usize,
+    /// Share of prompts with a variable schema, in percent.
+    schema_coverage: f64,
+    /// Earlier versions kept as backups by `edit`.
+    versions: usize,
+    content_length: LengthStats,
+    workspaces: Vec<WorkspaceStats>,
+    top_tags: Vec<TagCount>,
+    most_run: Vec<RunCount>,
+}
+
+#[derive(Serialize)]
+struct WorkspaceStats {
+    name: String,
+    prompts: usize,
+    chain_steps: usize,
+    chains: usize,
+    with_schema: usize,
+    versions: usize,
+}
+
+/// Prompt content length, in characters.
+#[derive(Serialize, Default)]
+struct LengthStats {
+    average: f64,
+    median: usize,
+    p90: usize,
+    max: usize,
+}
+
+#[derive(Serialize)]
+struct TagCount {
+    tag: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct RunCount {
+    id: String,
+    title: String,
+    runs: u64,
+}
+
+/// Display statistics about the prompt store, as text or as JSON for dashboards.
+pub fn run(ctx: &AppCtx, json: bool) -> Result<(), String> {
+    let stats = collect(ctx)?;
+    if json {
+        let content = serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?;
+        println!("{}", content);
+        return Ok(());
     }
 
     println!("{}", style("Prompt Store Statistics").bold().underlined());
-    println!(
-        "{}: {}",
-        style("Total Chains").cyan(),
-        style(chain_count).yellow()
-    );
-    println!(
-        "{}: {}",
-        style("Total Standalone Prompts").cyan(),
-        style(standalone_prompts).yellow()
-    );
-    println!(
-        "{}: {}",
-        style("Prompts within Chains").cyan(),
-        style(prompts_in_chains).yellow()
-    );
-    println!(
-        "{}: {}",
-        style("Total Prompts").cyan(),
-        style(standalone_prompts + prompts_in_chains).yellow()
+    let line = |label: &str, value: String| {
+        println!("{}: {}", style(label).cyan(), style(value).yellow());
+    };
+    line("Total Chains", stats.chains.to_string());
+    line("Total Standalone Prompts", stats.standalone_prompts.to_string());
+    line("Prompts within Chains", stats.prompts_in_chains.to_string());
+    line("Total Prompts", stats.total_prompts.to_string());
+    line("Schema Coverage", format!("{:.1}%", stats.schema_coverage));
+    line("Saved Versions", stats.versions.to_string());
+    let length = &stats.content_length;
+    line(
+        "Content Length",
+        format!(
+            "avg {:.0}, median {}, p90 {}, max {} chars",
+            length.average, length.median, length.p90, length.max
+        ),
     );
 
-    if !tag_counts.is_empty() {
-        let mut sorted_tags: Vec<_> = tag_counts.into_iter().collect();
-        sorted_tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    println!("\n{}", style("Workspaces:").bold().underlined());
+    for ws in &stats.workspaces {
+        println!(
+            "  - {}: {} prompts, {} chain steps, {} chains, {} with schema, {} versions",
+            style(&ws.name).green(),
+            ws.prompts,
+            ws.chain_steps,
+            ws.chains,
+            ws.with_schema,
+            ws.versions
+        );
+    }
 
+    if !stats.top_tags.is_empty() {
         println!("\n{}", style("Top Tags:").bold().underlined());
-        for (tag, count) in sorted_tags.iter().take(10) {
-            println!("  - {} ({})", style(tag).green(), count);
+        for tag in &stats.top_tags {
+            println!("  - {} ({})", style(&tag.tag).green(), tag.count);
+        }
+    }
+
+    if !stats.most_run.is_empty() {
+        println!("\n{}", style("Most Run:").bold().underlined());
+        for prompt in &stats.most_run {
+            println!(
+                "  - {} ({}): {} runs",
+                style(&prompt.title).green(),
+                prompt.id,
+                prompt.runs
+            );
         }
     }
 
     Ok(())
 }
 
-fn process_directory(
-    dir: &Path,
-    cipher: &aes_gcm::Aes256Gcm,
-    tag_counts: &mut HashMap<String, usize>,
-) -> Result<u32, String> {
+fn collect(ctx: &AppCtx) -> Result<StoreStats, String> {
+    let repo = ctx.prompts();
+    let mut workspaces = Vec::new();
+    let mut lengths = Vec::new();
+    let mut titles = HashMap::new();
+    let mut tag_counts: HashMap<String, usize> = HashMap::new();
+
+    for name in repo.workspaces()? {
+        let mut ws = WorkspaceStats {
+            chains: repo.list_chains(&name)?.len(),
+            versions: count_backups(&ctx.workspaces_dir.join(&name))?,
+            name,
+            prompts: 0,
+            chain_steps: 0,
+            with_schema: 0,
+        };
+        for prompt in repo.list(&ws.name)? {
+            if prompt.is_chain_step() {
+                ws.chain_steps += 1;
+            } else {
+                ws.prompts += 1;
+            }
+            if prompt.data.schema.is_some() {
+                ws.with_schema += 1;
+            }
+            lengths.push(prompt.data.content.chars().count());
+            for tag in prompt.data.tags {
+                *tag_counts.entry(tag).or_insert(0) += 1;
+            }
+            titles.insert(prompt.full_id, prompt.data.title);
+        }
+        workspaces.push(ws);
+    }
+
+    let total_prompts = lengths.len();
+    let with_schema: usize = workspaces.iter().map(|ws| ws.with_schema).sum();
+
+    let mut top_tags: Vec<TagCount> = tag_counts
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect();
+    top_tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    top_tags.truncate(TOP);
+
+    // Run counts of prompts deleted since are left out.
+    let mut most_run: Vec<RunCount> = Vec::new();
+    for (workspace, prompts) in usage::load(&ctx.base_dir) {
+        for (id, used) in prompts {
+            let id = full_id(&workspace, &id);
+            if let (Some(title), true) = (titles.get(&id), used.runs > 0) {
+                most_run.push(RunCount {
+                    title: title.clone(),
+                    id,
+                    runs: used.runs,
+                });
+            }
+        }
+    }
+    most_run.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.id.cmp(&b.id)));
+    most_run.truncate(TOP);
+
+    Ok(StoreStats {
+        chains: workspaces.iter().map(|ws| ws.chains).sum(),
+        standalone_prompts: workspaces.iter().map(|ws| ws.prompts).sum(),
+        prompts_in_chains: workspaces.iter().map(|ws| ws.chain_steps).sum(),
+        total_prompts,
+        schema_coverage: if total_prompts == 0 {
+            0.0
+        } else {
+            with_schema as f64 * 100.0 / total_prompts as f64
+        },
+        versions: workspaces.iter().map(|ws| ws.versions).sum(),
+        content_length: length_stats(lengths),
+        workspaces,
+        top_tags,
+        most_run,
+    })
+}
+
+fn length_stats(mut lengths: Vec<usize>) -> LengthStats {
+    if lengths.is_empty() {
+        return LengthStats::default();
+    }
+    lengths.sort_unstable();
+    // Nearest-rank percentile.
+    let percentile = |p: usize| lengths[(lengths.len() * p).div_ceil(100).max(1) - 1];
+    LengthStats {
+        average: lengths.iter().sum::<usize>() as f64 / lengths.len() as f64,
+        median: percentile(50),
+        p90: percentile(90),
+        max: lengths[lengths.len() - 1],
+    }
+}
+
+/// Counts the `.bak` files kept by `edit` in a workspace, chain folders included.
+fn count_backups(dir: &Path) -> Result<usize, String> {
     let mut count = 0;
     for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
         let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("prompt") {
+        if path.is_dir() {
+            count += count_backups(&path)?;
+        } else if path.extension().is_some_and(|e| e == "bak") {
             count += 1;
-            if let Ok(prompt) = decrypt_full_prompt(&path, cipher) {
-                for tag in prompt.tags {
-                    *tag_counts.entry(tag).or_insert(0) += 1;
-                }
-            }
         }
     }
     Ok(count)
-}
\ No newline at end of file
+}
@@ -1,58 +1,40 @@
-use crate::core::storage::{AppCtx, PromptData};
-use aes_gcm::{
-    aead::{Aead, AeadCore, OsRng},
-    Aes256Gcm, Nonce,
-};
-use base64::{engine::general_purpose, Engine as _};
+use crate::core::journal::JournalEntry;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::BULLET;
+use chrono::Utc;
 use console::style;
-use std::fs;
 
 /// Modify tags (+/-) on a prompt.
 pub fn run(ctx: &AppCtx, id: &str, changes: &[String]) -> Result<(), String> {
-    let path = ctx.prompt_path(id);
-    if !path.exists() {
-        return Err(format!("No prompt with ID {}", id));
-    }
+    let prompts = ctx.prompts();
+    let mut pd = prompts.load(id)?;
+    apply_changes(&mut pd.tags, changes);
 
-    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
-    let decoded = general_purpose::STANDARD
-        .decode(encoded.trim_end())
-        .map_err(|_| "Corrupted data".to_string())?;
-    if decoded.len() < 12 {
-        return Err("Corrupted data".to_string());
+    pd.updated_at = Some(Utc::now());
+    let mut journal = JournalEntry::new(format!("tag {} {}", id, changes.join(" ")));
+    if removes_tags(changes) {
+        journal.save(ctx, &ctx.prompt_path(id))?;
     }
+    prompts.save(id, &pd)?;
+    journal.commit(ctx)?;
+    println!("{} tags updated", style(BULLET).green().bold());
+    Ok(())
+}
 
-    let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-    let plaintext = ctx
-        .cipher
-        .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-        .map_err(|_| "Decrypt error".to_string())?;
-    let mut pd: PromptData =
-        serde_json::from_slice(&plaintext).map_err(|_| "Invalid JSON".to_string())?;
+/// Whether tag changes remove a tag, which `undo` can then revert.
+pub fn removes_tags(changes: &[String]) -> bool {
+    changes.iter().any(|c| c.starts_with('-'))
+}
 
+/// Applies `+tag` / `-tag` changes to a list of tags, ignoring case.
+pub fn apply_changes(tags: &mut Vec<String>, changes: &[String]) {
     for c in changes {
         if let Some(rest) = c.strip_prefix('+') {
-            if !pd.tags.iter().any(|t| t.eq_ignore_ascii_case(rest)) {
-                pd.tags.push(rest.to_string());
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(rest)) {
+                tags.push(rest.to_string());
             }
         } else if let Some(rest) = c.strip_prefix('-') {
-            pd.tags.retain(|t| !t.eq_ignore_ascii_case(rest));
+            tags.retain(|t| !t.eq_ignore_ascii_case(rest));
         }
     }
-
-    let json = serde_json::to_vec(&pd).map_err(|e| format!("Serialize error: {}", e))?;
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher_bytes = ctx
-        .cipher
-        .encrypt(&nonce, json.as_ref())
-        .map_err(|_| "Encrypt error".to_string())?;
-
-    let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&cipher_bytes);
-    let encoded_out = general_purpose::STANDARD.encode(&out);
-
-    fs::write(&path, encoded_out).map_err(|e| format!("Write error: {}", e))?;
-    println!("{} tags updated", style("•").green().bold());
-    Ok(())
 }
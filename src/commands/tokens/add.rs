@@ -0,0 +1,55 @@
+use crate::cli::TokenScope;
+use crate::core::storage::AppCtx;
+use crate::core::tokens::{load_tokens, save_tokens, ApiToken, Scope};
+use crate::ui::symbols::{OK, WARN};
+use console::style;
+
+/// Create an API token of `serve` and print it. Only its hash is stored, so it cannot
+/// be shown again.
+pub fn run(
+    ctx: &AppCtx,
+    name: &str,
+    scope: TokenScope,
+    workspaces: Vec<String>,
+    rate_limit: Option<u32>,
+) -> Result<(), String> {
+    let mut tokens = load_tokens(&ctx.base_dir, &ctx.cipher)?;
+    if tokens.iter().any(|t| t.name == name) {
+        return Err(format!(
+            "A token named '{}' already exists. Remove it first to replace it.",
+            name
+        ));
+    }
+    for workspace in &workspaces {
+        if !ctx.workspaces_dir.join(workspace).is_dir() {
+            return Err(format!("Workspace '{}' not found.", workspace));
+        }
+    }
+    if rate_limit == Some(0) {
+        return Err("The rate limit must be at least 1 request per minute.".to_string());
+    }
+    let scope = match scope {
+        TokenScope::ReadOnly => Scope::ReadOnly,
+        TokenScope::RunOnly => Scope::RunOnly,
+        TokenScope::Admin => Scope::Admin,
+    };
+    let (mut token, secret) = ApiToken::generate(name, scope, workspaces);
+    token.rate_limit = rate_limit;
+    tokens.push(token);
+    save_tokens(&ctx.base_dir, &ctx.cipher, &tokens)?;
+
+    println!(
+        "{} Token {} created:\n\n  {}\n",
+        style(OK).green().bold(),
+        style(name).yellow(),
+        secret
+    );
+    println!(
+        "{} Copy it now, it is not shown again. Clients send it as 'authorization: Bearer <token>'.",
+        style(WARN).yellow()
+    );
+    if tokens.len() == 1 {
+        println!("`serve` now rejects requests without a token (restart it to apply).");
+    }
+    Ok(())
+}
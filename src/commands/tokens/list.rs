@@ -0,0 +1,40 @@
+use crate::core::storage::AppCtx;
+use crate::core::tokens::load_tokens;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// List the API tokens of `serve` with their scope, workspaces and rate limit.
+pub fn run(ctx: &AppCtx) -> Result<(), String> {
+    let tokens = load_tokens(&ctx.base_dir, &ctx.cipher)?;
+    if tokens.is_empty() {
+        println!(
+            "{}",
+            style("No tokens: `serve` accepts every request. Create one with 'prompt-store tokens add'.")
+                .yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", style("Tokens:").green().bold());
+    for token in tokens {
+        let workspaces = if token.workspaces.is_empty() {
+            "all workspaces".to_string()
+        } else {
+            token.workspaces.join(", ")
+        };
+        let rate = match token.rate_limit {
+            Some(limit) => format!(", {} requests/min", limit),
+            None => String::new(),
+        };
+        println!(
+            "  {} {} - {} on {}{} (created {})",
+            style(BULLET).green(),
+            style(&token.name).yellow(),
+            token.scope.as_str(),
+            workspaces,
+            rate,
+            token.created_at.format("%Y-%m-%d")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::cli::RemoveArgs;
+use crate::core::storage::AppCtx;
+use crate::core::tokens::{load_tokens, save_tokens};
+use crate::ui::confirm_removal;
+use crate::ui::symbols::BULLET;
+use console::style;
+
+/// Revoke an API token of `serve`.
+pub fn run(ctx: &AppCtx, name: &str, args: RemoveArgs) -> Result<(), String> {
+    let mut tokens = load_tokens(&ctx.base_dir, &ctx.cipher)?;
+    let Some(index) = tokens.iter().position(|t| t.name == name) else {
+        return Err(format!("No token named '{}'", name));
+    };
+    if !confirm_removal(args, &format!("token {}", name))? {
+        return Ok(());
+    }
+    tokens.remove(index);
+    save_tokens(&ctx.base_dir, &ctx.cipher, &tokens)?;
+    println!(
+        "{} Token {} revoked (restart `serve` to apply)",
+        style(BULLET).green().bold(),
+        name
+    );
+    if tokens.is_empty() {
+        println!("No tokens are left: `serve` accepts every request.");
+    }
+    Ok(())
+}
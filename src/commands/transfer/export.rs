@@ -0,0 +1,33 @@
+use crate::core::backup::create_transfer;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+use dialoguer::Password;
+use std::fs;
+
+/// Export the whole store re-encrypted under a transfer password, without the master key.
+pub fn run(ctx: &AppCtx, out: &str, password: Option<&str>) -> Result<(), String> {
+    let password = match password {
+        Some(p) => p.to_string(),
+        None => Password::new()
+            .with_prompt("Enter a transfer password")
+            .with_confirmation("Confirm password", "Passwords do not match.")
+            .interact()
+            .map_err(|e| format!("Password input error: {}", e))?,
+    };
+
+    let (archive, count) = create_transfer(ctx, &password)?;
+    fs::write(out, archive).map_err(|e| format!("Failed to write transfer file: {}", e))?;
+
+    println!(
+        "{} Exported {} files to {}",
+        style(OK).green(),
+        count,
+        style(out).yellow()
+    );
+    println!(
+        "Run {} on the target machine.",
+        style(format!("prompt-store transfer import {}", out)).yellow()
+    );
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::core::backup::{
+    archive_kind, open_backup, restore_backup, rewrap_transfer, verify_backup, ArchiveKind,
+};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use console::style;
+use dialoguer::{Confirm, Password};
+use std::fs;
+
+/// Import a transfer archive, re-encrypting every file with this machine's master key.
+pub fn run(ctx: &AppCtx, file: &str, password: Option<&str>, yes: bool) -> Result<(), String> {
+    let data = fs::read(file).map_err(|e| format!("Read error: {}", e))?;
+    if archive_kind(&data)? != ArchiveKind::Transfer {
+        return Err(
+            "This is a backup archive, restore it with `prompt-store restore`.".to_string(),
+        );
+    }
+    let password = match password {
+        Some(p) => p.to_string(),
+        None => Password::new()
+            .with_prompt("Transfer password")
+            .interact()
+            .map_err(|e| format!("Password input error: {}", e))?,
+    };
+
+    let mut files = open_backup(ctx, &data, Some(&password))?;
+    rewrap_transfer(ctx, &mut files)?;
+    let prompts = verify_backup(&files, &ctx.cipher)?;
+    println!(
+        "{} Transfer verified: {} files, {} prompts",
+        style(OK).green(),
+        files.len(),
+        prompts
+    );
+
+    if !yes
+        && !Confirm::new()
+            .with_prompt("Replace the current store with the transferred one?")
+            .default(false)
+            .interact()
+            .map_err(|e| e.to_string())?
+    {
+        println!("{}", style("Import cancelled.").yellow());
+        return Ok(());
+    }
+
+    restore_backup(ctx, &files)?;
+    println!(
+        "{} Store imported and re-encrypted with this machine's key",
+        style(OK).green()
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use crate::commands::edit::save_with_backup;
+use crate::commands::run::{build_llm, complete};
+use crate::core::policy::load_policies;
+use crate::core::storage::{normalize_lang, parse_id, AppCtx};
+use crate::core::template::variables;
+use crate::core::utils::editor;
+use crate::ui::symbols::{BULLET, OK};
+use console::style;
+
+/// Draft a translation of a prompt with an LLM, let the user review it in the editor, and
+/// save it as the prompt's translation in `to`.
+pub async fn run(ctx: &AppCtx, id: &str, to: &str, backend: &str) -> Result<(), String> {
+    let lang = normalize_lang(to)?;
+    let mut pd = ctx.prompts().load(id)?;
+    load_policies()?.check_content(id, &pd.content)?;
+    ctx.prompts().check_provider(&parse_id(id).0, backend)?;
+    let llm = build_llm(ctx, backend)?;
+
+    let request = format!(
+        "Translate the following prompt template into the language with code '{}'. \
+         Keep every {{{{...}}}} placeholder exactly as it is, untranslated. \
+         Reply with the translated template only.\n\n{}",
+        lang, pd.content
+    );
+    let draft = complete(llm.as_ref(), &request).await?;
+
+    println!(
+        "{} Review the draft in the editor; an empty file discards it.",
+        style(BULLET).green().bold()
+    );
+    let reviewed = editor()
+        .edit(draft.trim())
+        .map_err(|e| format!("Editor error: {}", e))?
+        .unwrap_or_default();
+    if reviewed.trim().is_empty() {
+        println!("{}", style("Translation discarded.").yellow());
+        return Ok(());
+    }
+
+    let expected = variables(&pd.content);
+    let found = variables(&reviewed);
+    let missing: Vec<&str> = expected
+        .iter()
+        .filter(|name| !found.contains(name))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "Warning: the translation does not use {}.",
+                missing.join(", ")
+            ))
+            .yellow()
+        );
+    }
+
+    let replaced = pd.translations.insert(lang.clone(), reviewed).is_some();
+    save_with_backup(ctx, id, &mut pd)?;
+    println!(
+        "{} {} the '{}' translation of prompt '{}'.",
+        style(OK).green().bold(),
+        if replaced { "Replaced" } else { "Saved" },
+        lang,
+        id
+    );
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::core::journal::{last, undo};
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::OK;
+use chrono::Local;
+use console::style;
+use dialoguer::Confirm;
+
+/// Reverts the last destructive operation (delete, step or tag removal, rename,
+/// import), putting back its files as they were. Asks for confirmation unless `yes`.
+pub fn run(ctx: &AppCtx, yes: bool) -> Result<(), String> {
+    let Some(entry) = last(ctx)? else {
+        println!("{}", style("Nothing to undo.").yellow());
+        return Ok(());
+    };
+    let when = entry.at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+    if !yes
+        && !Confirm::new()
+            .with_prompt(format!("Undo '{}' from {}?", entry.action, when))
+            .default(true)
+            .interact()
+            .map_err(|e| format!("{} (use --yes to undo anyway)", e))?
+    {
+        println!("Undo cancelled.");
+        return Ok(());
+    }
+
+    undo(ctx)?;
+    println!(
+        "{} Undone: {} (from {})",
+        style(OK).green().bold(),
+        entry.action,
+        when
+    );
+    Ok(())
+}
@@ -1,28 +1,45 @@
-use crate::commands::pack_logic::{install_pack_from_local_repo, DeployedInfo};
-use crate::core::storage::AppCtx;
+use crate::commands::pack_logic::{
+    duplicate_links, fetch_progress, install_prompts_to_workspace, load_manifest,
+    read_local_policy, read_pack_file, save_manifest, scan_prompt, DeployedInfo, PackSource,
+};
+use crate::commands::update_check::record_update;
+use crate::core::bundle::same_prompt;
+use crate::core::logging;
+use crate::core::registry::{fetch_bundle, fetch_pack_info};
+use crate::core::storage::{parse_id, AppCtx, PromptData};
+use crate::ui::progress::bar;
+use crate::ui::symbols::{OK, WARN};
 use console::style;
-use git2::{build::CheckoutBuilder, FetchOptions, Repository};
-use serde_json;
-use std::collections::HashMap;
+use dialoguer::Confirm;
+use git2::{build::CheckoutBuilder, Oid, Repository};
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs;
 use std::path::Path;
 
-/// Update deployed prompt pack(s).
-pub async fn run(ctx: &AppCtx, alias_filter: Option<&str>) -> Result<(), String> {
-    let manifest_path = ctx.base_dir.join("deployed.json");
-    if !manifest_path.exists() {
+/// Commits listed in the changelog of an update.
+const MAX_COMMITS: usize = 20;
+
+/// Update deployed prompt pack(s). Pinned packs are skipped unless `latest` is set,
+/// which also removes their pin.
+///
+/// The prompts added, removed or modified by each update are shown with the new
+/// commits, and the update is applied once confirmed (or directly with `yes`).
+pub async fn run(
+    ctx: &AppCtx,
+    alias_filter: Option<&str>,
+    latest: bool,
+    yes: bool,
+) -> Result<(), String> {
+    let mut manifest = load_manifest(ctx)?;
+    if manifest.is_empty() {
         println!("No packs deployed yet. Use 'prompt-store deploy' to add one.");
         return Ok(());
     }
 
-    let content = fs::read_to_string(&manifest_path).map_err(|e| e.to_string())?;
-    let mut manifest: HashMap<String, DeployedInfo> =
-        serde_json::from_str(&content).unwrap_or_default();
-
     let packs_to_update: Vec<DeployedInfo> = manifest
         .values()
-        .filter(|info| alias_filter.map_or(true, |alias| info.alias == alias))
+        .filter(|info| alias_filter.is_none_or(|alias| info.alias == alias))
         .cloned()
         .collect();
 
@@ -35,58 +52,290 @@ pub async fn run(ctx: &AppCtx, alias_filter: Option<&str>) -> Result<(), String>
         };
     }
 
+    let password = env::var("PROMPT_PACK_PASSWORD").ok();
     for pack in packs_to_update {
+        if let (Some(pin), false) = (&pack.pin, latest) {
+            println!(
+                "Pack '{}' is pinned to {}, skipping (use --latest to update it).",
+                style(&pack.alias).yellow(),
+                pin
+            );
+            continue;
+        }
         println!(
             "Checking for updates in '{}'...",
             style(&pack.alias).yellow()
         );
+
+        info!(
+            "checking '{}' ({:?} pack from {}) at {}",
+            pack.alias,
+            pack.source,
+            logging::url(&pack.url),
+            pack.commit_hash
+        );
         let repo_path = ctx.registries_dir.join(&pack.alias);
+        let (new_rev, content, commits) = if pack.source == PackSource::Registry {
+            let info = fetch_pack_info(&pack.url)
+                .await?
+                .ok_or_else(|| format!("{} is no longer a pack registry URL.", pack.url))?;
+            if info.version == pack.commit_hash {
+                println!("Pack '{}' is up to date.", style(&pack.alias).green());
+                continue;
+            }
+            (info.version, fetch_bundle(&pack.url).await?, Vec::new())
+        } else {
+            let new_hash = fetch_main(&repo_path, &pack.alias)?;
+            if new_hash == pack.commit_hash {
+                println!("Pack '{}' is up to date.", style(&pack.alias).green());
+                if let Some(info) = manifest.get_mut(&pack.alias) {
+                    info.pin = None;
+                }
+                continue;
+            }
+            let (content, commits) = read_revision(&repo_path, &pack.commit_hash, &new_hash)?;
+            (new_hash, content, commits)
+        };
+        let mut new_pack = read_pack_file(&content, &pack.alias, password.as_deref())?;
+        debug!(
+            "'{}' {} has {} prompts",
+            pack.alias,
+            new_rev,
+            new_pack.prompts.len()
+        );
 
-        let new_hash = pull_repo(&repo_path, &pack.alias)?;
+        println!(
+            "Updating '{}' from {} to {}:",
+            style(&pack.alias).yellow(),
+            short(&pack, &pack.commit_hash),
+            short(&pack, &new_rev)
+        );
+        let current: Vec<PromptData> = ctx
+            .prompts()
+            .list(&pack.alias)?
+            .into_iter()
+            .map(|p| PromptData {
+                id: parse_id(&p.full_id).1,
+                ..p.data
+            })
+            .collect();
+        print_changes(&current, &new_pack.prompts, &commits);
+        print_risks(&current, &new_pack.prompts);
 
-        if new_hash == pack.commit_hash {
-            println!("Pack '{}' is up to date.", style(&pack.alias).green());
+        if !yes
+            && !Confirm::new()
+                .with_prompt(format!("Apply this update to '{}'?", pack.alias))
+                .default(true)
+                .interact()
+                .map_err(|e| format!("{} (use --yes to update without confirmation)", e))?
+        {
+            println!("{}", style(format!("Skipped '{}'.", pack.alias)).yellow());
             continue;
         }
 
-        println!(
-            "Updating '{}' from {} to {}...",
-            style(&pack.alias).yellow(),
-            &pack.commit_hash[..7],
-            &new_hash[..7]
+        if pack.source == PackSource::Git {
+            checkout_main(&repo_path, &new_rev)?;
+            new_pack.policy = read_local_policy(&repo_path)?.or(new_pack.policy);
+        }
+        // Prompts linked at deployment stay linked while they remain identical.
+        let repo = ctx.prompts();
+        let links = duplicate_links(ctx, &pack.alias, &new_pack.prompts, |id, _| {
+            let full_id = format!("{}::{}", pack.alias, id);
+            Ok(repo.exists(&full_id) && repo.link_target(&full_id)?.is_some())
+        })?;
+        install_prompts_to_workspace(ctx, &pack.alias, new_pack, &links)?;
+
+        // Update the manifest with the new revision, keeping the old one for rollback
+        if let Some(info) = manifest.get_mut(&pack.alias) {
+            info.previous = Some(std::mem::replace(&mut info.commit_hash, new_rev));
+            info.pin = None;
+        }
+        save_manifest(ctx, &manifest)?;
+        record_update(ctx, &pack.alias, &manifest[&pack.alias].commit_hash);
+        info!(
+            "updated '{}' to {}",
+            pack.alias, manifest[&pack.alias].commit_hash
         );
+        println!("{} Updated '{}'.", style(OK).green(), pack.alias);
+    }
 
-        let password = env::var("PROMPT_PACK_PASSWORD").ok();
-        install_pack_from_local_repo(ctx, &repo_path, &pack.alias, password.as_deref())?;
+    save_manifest(ctx, &manifest)
+}
 
-        // Update the manifest with the new hash
-        if let Some(info) = manifest.get_mut(&pack.alias) {
-            info.commit_hash = new_hash;
+/// Prints the prompts added, removed and modified by an update, with line counts,
+/// then the commits it brings.
+fn print_changes(current: &[PromptData], new: &[PromptData], commits: &[String]) {
+    let by_id: HashMap<&str, &PromptData> = current.iter().map(|p| (p.id.as_str(), p)).collect();
+    let new_ids: HashSet<&str> = new.iter().map(|p| p.id.as_str()).collect();
+    let (mut added, mut removed, mut modified) = (0, 0, 0);
+
+    for prompt in new {
+        match by_id.get(prompt.id.as_str()) {
+            None => {
+                added += 1;
+                println!("  {} {} ({})", style("+").green(), prompt.title, prompt.id);
+            }
+            Some(old) if !same_prompt(old, prompt) => {
+                modified += 1;
+                let (plus, minus) = line_changes(&old.content, &prompt.content);
+                let renamed = if old.title != prompt.title {
+                    format!(", was '{}'", old.title)
+                } else {
+                    String::new()
+                };
+                println!(
+                    "  {} {} ({}{}) {} {}",
+                    style("~").yellow(),
+                    prompt.title,
+                    prompt.id,
+                    renamed,
+                    style(format!("+{}", plus)).green(),
+                    style(format!("-{}", minus)).red()
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    for prompt in current {
+        if !new_ids.contains(prompt.id.as_str()) {
+            removed += 1;
+            println!("  {} {} ({})", style("-").red(), prompt.title, prompt.id);
+        }
+    }
+    println!(
+        "  {} added, {} removed, {} modified",
+        added, removed, modified
+    );
+
+    if !commits.is_empty() {
+        println!("  Commits:");
+        for commit in commits.iter().take(MAX_COMMITS) {
+            println!("    {}", commit);
+        }
+        if commits.len() > MAX_COMMITS {
+            println!("    ... and {} more", commits.len() - MAX_COMMITS);
         }
     }
+}
 
-    let updated_content = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
-    fs::write(manifest_path, updated_content).map_err(|e| e.to_string())?;
+/// Prints the added or modified prompts that look like prompt injections.
+fn print_risks(current: &[PromptData], new: &[PromptData]) {
+    let by_id: HashMap<&str, &PromptData> = current.iter().map(|p| (p.id.as_str(), p)).collect();
+    for prompt in new {
+        if by_id
+            .get(prompt.id.as_str())
+            .is_some_and(|old| old.content == prompt.content)
+        {
+            continue;
+        }
+        for risk in scan_prompt(prompt) {
+            println!(
+                "  {} {} ({}) {} {}",
+                style(WARN).yellow(),
+                prompt.title,
+                prompt.id,
+                style(format!("[{}]", risk.kind)).red(),
+                risk.excerpt
+            );
+        }
+    }
+}
 
-    Ok(())
+/// Counts the lines added and removed between two versions of a prompt, ignoring moves.
+fn line_changes(old: &str, new: &str) -> (usize, usize) {
+    let mut remaining: HashMap<&str, isize> = HashMap::new();
+    for line in old.lines() {
+        *remaining.entry(line).or_default() += 1;
+    }
+    for line in new.lines() {
+        *remaining.entry(line).or_default() -= 1;
+    }
+    let added = remaining
+        .values()
+        .filter(|&&n| n < 0)
+        .map(|n| -n as usize)
+        .sum();
+    let removed = remaining
+        .values()
+        .filter(|&&n| n > 0)
+        .map(|&n| n as usize)
+        .sum();
+    (added, removed)
 }
 
-fn pull_repo(repo_path: &Path, alias: &str) -> Result<String, String> {
+/// Shortens git commits for display; registry versions are shown as is.
+fn short<'a>(pack: &DeployedInfo, revision: &'a str) -> &'a str {
+    match pack.source {
+        PackSource::Git => &revision[..revision.len().min(7)],
+        PackSource::Registry => revision,
+    }
+}
+
+/// Reads the pack file at commit `new` and the summaries of the commits since `old`,
+/// newest first, without touching the working tree.
+fn read_revision(repo_path: &Path, old: &str, new: &str) -> Result<(String, Vec<String>), String> {
+    let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+    let new_oid = Oid::from_str(new).map_err(|e| e.to_string())?;
+    let tree = repo
+        .find_commit(new_oid)
+        .and_then(|c| c.tree())
+        .map_err(|e| e.to_string())?;
+    let entry = tree
+        .get_path(Path::new("prompts.bundle"))
+        .or_else(|_| tree.get_path(Path::new("prompts.json")))
+        .map_err(|_| "No 'prompts.bundle' or 'prompts.json' found in repository.".to_string())?;
+    let blob = repo.find_blob(entry.id()).map_err(|e| e.to_string())?;
+    let content = String::from_utf8(blob.content().to_vec())
+        .map_err(|_| "The pack file is not valid UTF-8.".to_string())?;
+
+    let mut walk = repo.revwalk().map_err(|e| e.to_string())?;
+    walk.push(new_oid).map_err(|e| e.to_string())?;
+    if let Ok(old_oid) = Oid::from_str(old) {
+        // The old commit may be unknown, e.g. after a force-push upstream.
+        walk.hide(old_oid).ok();
+    }
+    let commits = walk
+        .filter_map(Result::ok)
+        .filter_map(|oid| repo.find_commit(oid).ok())
+        .map(|c| {
+            format!(
+                "{} {}",
+                &c.id().to_string()[..7],
+                c.summary().unwrap_or_default()
+            )
+        })
+        .collect();
+    Ok((content, commits))
+}
+
+/// Fetches `main` from the pack's origin and returns the fetched commit.
+fn fetch_main(repo_path: &Path, alias: &str) -> Result<String, String> {
     let repo = Repository::open(repo_path)
         .map_err(|e| format!("Failed to open local repository for '{}': {}", alias, e))?;
 
     let mut remote = repo.find_remote("origin").map_err(|e| e.to_string())?;
 
-    // Simple fetch
-    let mut fo = FetchOptions::new();
-    remote
-        .fetch(&["main"], Some(&mut fo), None)
-        .map_err(|e| format!("Failed to fetch updates for '{}': {}", alias, e))?;
+    let progress = bar(0, &format!("Fetching '{}'", alias));
+    let fetched = remote
+        .fetch(&["main"], Some(&mut fetch_progress(progress.clone())), None)
+        .map_err(|e| {
+            warn!("fetching '{}' failed: {:?}", alias, e);
+            format!("Failed to fetch updates for '{}': {}", alias, e)
+        });
+    progress.finish_and_clear();
+    fetched?;
 
     let fetch_head = repo
         .find_reference("FETCH_HEAD")
         .map_err(|e| e.to_string())?;
     let fetch_commit = fetch_head.peel_to_commit().map_err(|e| e.to_string())?;
+    Ok(fetch_commit.id().to_string())
+}
+
+/// Fast-forwards `main` to a fetched commit and checks it out.
+fn checkout_main(repo_path: &Path, commit: &str) -> Result<(), String> {
+    let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+    let oid = Oid::from_str(commit).map_err(|e| e.to_string())?;
 
     // Simple fast-forward merge
     let main_ref_name = "refs/heads/main";
@@ -94,11 +343,10 @@ fn pull_repo(repo_path: &Path, alias: &str) -> Result<String, String> {
         .find_reference(main_ref_name)
         .map_err(|e| e.to_string())?;
     main_ref
-        .set_target(fetch_commit.id(), "Fast-forward update")
+        .set_target(oid, "Fast-forward update")
         .map_err(|e| e.to_string())?;
     repo.set_head(main_ref_name).map_err(|e| e.to_string())?;
     repo.checkout_head(Some(CheckoutBuilder::new().force()))
         .map_err(|e| e.to_string())?;
-
-    Ok(fetch_commit.id().to_string())
+    Ok(())
 }
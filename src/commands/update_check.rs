@@ -0,0 +1,197 @@
+//! Looks for newer versions of deployed packs without installing them.
+//!
+//! Besides `update --check`, the check runs after any command once per configured
+//! interval (`[updates] check_interval_hours`). Its result is cached in
+//! `update-check.json`, so later commands print the hint without going online.
+
+use crate::commands::pack_logic::{DeployedInfo, PackSource};
+use crate::core::config::load_update_check_interval;
+use crate::core::registry::fetch_pack_info;
+use crate::core::storage::AppCtx;
+use crate::ui::symbols::{ARROW, BULLET, ERR, OK};
+use chrono::{DateTime, Utc};
+use console::style;
+use git2::{Direction, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+/// Result of the last automatic check.
+#[derive(Serialize, Deserialize, Default)]
+struct CheckState {
+    checked_at: Option<DateTime<Utc>>,
+    /// Latest upstream revision of each pack.
+    latest: HashMap<String, String>,
+}
+
+/// Reports which deployed packs have a newer upstream version, without updating them.
+pub async fn report(ctx: &AppCtx, alias_filter: Option<&str>) -> Result<(), String> {
+    let manifest = load_manifest(ctx);
+    let mut packs: Vec<&DeployedInfo> = manifest
+        .values()
+        .filter(|info| alias_filter.is_none_or(|alias| info.alias == alias))
+        .collect();
+    packs.sort_by(|a, b| a.alias.cmp(&b.alias));
+    if packs.is_empty() {
+        return match alias_filter {
+            Some(alias) => Err(format!("Pack with alias '{}' not found.", alias)),
+            None => {
+                println!("No packs deployed yet. Use 'prompt-store deploy' to add one.");
+                Ok(())
+            }
+        };
+    }
+
+    let mut state = load_state(ctx);
+    let mut outdated = 0;
+    for pack in packs {
+        let latest = match latest_revision(ctx, pack).await {
+            Ok(latest) => latest,
+            Err(e) => {
+                println!("  {} {}: {}", style(ERR).red(), pack.alias, e);
+                continue;
+            }
+        };
+        if latest == pack.commit_hash {
+            println!("  {} {} is up to date", style(OK).green(), pack.alias);
+        } else if let Some(pin) = &pack.pin {
+            println!(
+                "  {} {} is pinned to {} (latest: {})",
+                style(BULLET).dim(),
+                pack.alias,
+                pin,
+                short(pack, &latest)
+            );
+        } else {
+            outdated += 1;
+            println!(
+                "  {} {}: update available ({} {} {})",
+                style(BULLET).yellow(),
+                style(&pack.alias).yellow(),
+                short(pack, &pack.commit_hash),
+                ARROW,
+                short(pack, &latest)
+            );
+        }
+        state.latest.insert(pack.alias.clone(), latest);
+    }
+    save_state(ctx, &state);
+
+    if outdated > 0 {
+        println!("Run 'prompt-store update' to install the new versions.");
+    }
+    Ok(())
+}
+
+/// Prints a one-line hint when unpinned deployed packs are behind upstream. Checks upstream
+/// once per configured interval; failures are ignored so commands are never blocked.
+pub async fn notify_stale_packs(ctx: &AppCtx) {
+    let Ok(Some(interval)) = load_update_check_interval() else {
+        return;
+    };
+    let manifest = load_manifest(ctx);
+    if manifest.is_empty() {
+        return;
+    }
+
+    let mut state = load_state(ctx);
+    let due = state.checked_at.is_none_or(|at| {
+        Utc::now()
+            .signed_duration_since(at)
+            .to_std()
+            .unwrap_or_default()
+            >= interval
+    });
+    if due {
+        for pack in manifest.values() {
+            if let Ok(latest) = latest_revision(ctx, pack).await {
+                state.latest.insert(pack.alias.clone(), latest);
+            }
+        }
+        state.checked_at = Some(Utc::now());
+        save_state(ctx, &state);
+    }
+
+    let mut stale: Vec<&str> = manifest
+        .values()
+        .filter(|pack| {
+            // Pinned packs are behind on purpose.
+            pack.pin.is_none()
+                && state
+                    .latest
+                    .get(&pack.alias)
+                    .is_some_and(|latest| *latest != pack.commit_hash)
+        })
+        .map(|pack| pack.alias.as_str())
+        .collect();
+    if stale.is_empty() {
+        return;
+    }
+    stale.sort();
+    eprintln!(
+        "{} Newer versions are available for {}. Run 'prompt-store update --check' for details.",
+        style(BULLET).yellow(),
+        stale.join(", ")
+    );
+}
+
+/// Records the revision a pack was just updated to as its latest, so the hint does not
+/// report it until the next check.
+pub fn record_update(ctx: &AppCtx, alias: &str, revision: &str) {
+    let mut state = load_state(ctx);
+    state.latest.insert(alias.to_string(), revision.to_string());
+    save_state(ctx, &state);
+}
+
+/// Returns the upstream revision of a pack: the head of `main` for git packs, the
+/// version for registry packs.
+async fn latest_revision(ctx: &AppCtx, pack: &DeployedInfo) -> Result<String, String> {
+    match pack.source {
+        PackSource::Registry => fetch_pack_info(&pack.url)
+            .await?
+            .map(|info| info.version)
+            .ok_or_else(|| format!("{} is no longer a pack registry URL.", pack.url)),
+        PackSource::Git => {
+            let repo = Repository::open(ctx.registries_dir.join(&pack.alias))
+                .map_err(|e| format!("Failed to open local repository: {}", e))?;
+            let mut remote = repo.find_remote("origin").map_err(|e| e.to_string())?;
+            remote
+                .connect(Direction::Fetch)
+                .map_err(|e| format!("Failed to reach {}: {}", pack.url, e))?;
+            let heads = remote.list().map_err(|e| e.to_string())?;
+            heads
+                .iter()
+                .find(|head| head.name() == "refs/heads/main")
+                .map(|head| head.oid().to_string())
+                .ok_or_else(|| "No 'main' branch upstream.".to_string())
+        }
+    }
+}
+
+/// Shortens git commits for display; registry versions are shown as is.
+fn short<'a>(pack: &DeployedInfo, revision: &'a str) -> &'a str {
+    match pack.source {
+        PackSource::Git => &revision[..revision.len().min(7)],
+        PackSource::Registry => revision,
+    }
+}
+
+fn load_manifest(ctx: &AppCtx) -> HashMap<String, DeployedInfo> {
+    fs::read_to_string(ctx.base_dir.join("deployed.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn load_state(ctx: &AppCtx) -> CheckState {
+    fs::read_to_string(ctx.base_dir.join("update-check.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(ctx: &AppCtx, state: &CheckState) {
+    if let Ok(content) = serde_json::to_string_pretty(state) {
+        fs::write(ctx.base_dir.join("update-check.json"), content).ok();
+    }
+}
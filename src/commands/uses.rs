@@ -0,0 +1,187 @@
+use crate::core::storage::{full_id, parse_id, AppCtx, PromptRepository};
+use crate::ui::symbols::BULLET;
+use console::style;
+use serde_yaml::Value;
+
+/// A `prompt:` (or fallback prompt) of a YAML chain step.
+pub struct Reference {
+    /// Full ID of the chain.
+    pub chain: String,
+    /// Step number, from 1.
+    pub step: usize,
+    /// ID of the (parallel sub-)step, with ` on_error` for a fallback prompt.
+    pub step_id: String,
+    /// The prompt ID or title, as written in the chain.
+    pub prompt: String,
+}
+
+/// List the chains whose steps reference a prompt, by ID or title.
+pub fn run(ctx: &AppCtx, id: &str) -> Result<(), String> {
+    let refs = references(&ctx.prompts(), id)?;
+    if refs.is_empty() {
+        println!("{}", style(format!("No chain uses '{}'.", id)).yellow());
+        return Ok(());
+    }
+    println!("{}", style(format!("'{}' is used by:", id)).green().bold());
+    for (r, by_title) in refs {
+        println!(
+            "  {} {} (step {}: {}){}",
+            style(BULLET).green(),
+            style(&r.chain).yellow(),
+            r.step,
+            r.step_id,
+            if by_title { ", by title" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+/// Finds the steps of YAML chain definitions, in every workspace, referencing a prompt,
+/// and whether they do so by title. Interactive chains hold their own step prompts and
+/// reference nothing; their steps (`chain/1`) can be looked up like any prompt.
+pub fn references(repo: &PromptRepository, id: &str) -> Result<Vec<(Reference, bool)>, String> {
+    let title = repo.load(id)?.title;
+    let id = canonical_id(id);
+    Ok(chain_references(repo)?
+        .into_iter()
+        .filter_map(|r| resolves_to(&r.prompt, &id, &title).map(|by_title| (r, by_title)))
+        .collect())
+}
+
+/// Lists every prompt reference of the YAML chain definitions of the store.
+/// Definitions that cannot be decrypted or parsed are skipped.
+pub fn chain_references(repo: &PromptRepository) -> Result<Vec<Reference>, String> {
+    let mut found = Vec::new();
+    for workspace in repo.workspaces()? {
+        for chain in repo.list_chain_definitions(&workspace)? {
+            let Some(doc) = load_definition(repo, &chain) else {
+                continue;
+            };
+            let Some(steps) = doc.get("steps").and_then(Value::as_sequence) else {
+                continue;
+            };
+            for (n, step) in steps.iter().enumerate() {
+                let mut refs = Vec::new();
+                prompt_refs(step, "", &mut refs);
+                found.extend(refs.into_iter().map(|(step_id, prompt)| Reference {
+                    chain: chain.clone(),
+                    step: n + 1,
+                    step_id,
+                    prompt: prompt.to_string(),
+                }));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Points the references to prompt `old_id` (titled `old_title`) at its new ID and title,
+/// keeping each reference by ID or by title. Returns the number of references updated.
+pub fn update_references(
+    repo: &PromptRepository,
+    old_id: &str,
+    old_title: &str,
+    new_id: &str,
+    new_title: &str,
+) -> Result<usize, String> {
+    let old_id = canonical_id(old_id);
+    let (new_workspace, new_local_id) = parse_id(new_id);
+    let mut updated = 0;
+    for workspace in repo.workspaces()? {
+        for chain in repo.list_chain_definitions(&workspace)? {
+            let Some(mut doc) = load_definition(repo, &chain) else {
+                continue;
+            };
+            let mut count = 0;
+            rewrite_refs(&mut doc, &mut |reference| {
+                let by_title = resolves_to(reference, &old_id, old_title)?;
+                count += 1;
+                let target = if by_title { new_title } else { &new_local_id };
+                Some(match reference.split_once("::") {
+                    Some(_) => format!("{}::{}", new_workspace, target),
+                    None if by_title => target.to_string(),
+                    None => full_id(&new_workspace, &new_local_id),
+                })
+            });
+            if count > 0 {
+                let yaml =
+                    serde_yaml::to_string(&doc).map_err(|e| format!("Serialize error: {}", e))?;
+                repo.save_chain_definition(&chain, &yaml)?;
+                updated += count;
+            }
+        }
+    }
+    Ok(updated)
+}
+
+fn load_definition(repo: &PromptRepository, chain: &str) -> Option<Value> {
+    let yaml = repo.load_chain_definition(chain).ok()?;
+    serde_yaml::from_str(&yaml).ok()
+}
+
+/// Collects the `prompt:` values of a step with the ID of the step holding them.
+fn prompt_refs<'a>(value: &'a Value, label: &str, out: &mut Vec<(String, &'a str)>) {
+    match value {
+        Value::Sequence(items) => items.iter().for_each(|v| prompt_refs(v, label, out)),
+        Value::Mapping(map) => {
+            let label = map
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or(label)
+                .to_string();
+            for (key, v) in map {
+                match (key.as_str(), v.as_str()) {
+                    (Some("prompt"), Some(reference)) => out.push((label.clone(), reference)),
+                    (Some("on_error"), _) => prompt_refs(v, &format!("{} on_error", label), out),
+                    _ => prompt_refs(v, &label, out),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the `prompt:` values for which `replace` returns a new reference.
+fn rewrite_refs(value: &mut Value, replace: &mut impl FnMut(&str) -> Option<String>) {
+    match value {
+        Value::Sequence(items) => items.iter_mut().for_each(|v| rewrite_refs(v, replace)),
+        Value::Mapping(map) => {
+            for (key, v) in map.iter_mut() {
+                let new = match (key.as_str(), v.as_str()) {
+                    (Some("prompt"), Some(reference)) => replace(reference),
+                    _ => None,
+                };
+                match new {
+                    Some(new) => *v = Value::String(new),
+                    None => rewrite_refs(v, replace),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `default::x` is stored and referenced as `x`.
+fn canonical_id(id: &str) -> String {
+    let (workspace, local_id) = parse_id(id);
+    full_id(&workspace, &local_id)
+}
+
+/// Whether a chain's prompt reference resolves to the prompt `id` titled `title`, the way
+/// `chain run` resolves it: an ID (prefixed by its workspace outside `default`) or a
+/// title, optionally scoped with `workspace::`. Returns whether it is by title.
+fn resolves_to(reference: &str, id: &str, title: &str) -> Option<bool> {
+    let (workspace, local_id) = parse_id(id);
+    let (by_id, query) = match reference.split_once("::") {
+        Some((ref_workspace, query)) if ref_workspace == workspace => (query == local_id, query),
+        Some(_) => return None,
+        None => (reference == id, reference),
+    };
+    if by_id {
+        Some(false)
+    } else if query.eq_ignore_ascii_case(title) {
+        Some(true)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,139 @@
+use crate::commands::monitor;
+use crate::commands::run::{build_llm, with_examples};
+use crate::core::config::load_input_redaction;
+use crate::core::metrics;
+use crate::core::pii::{redact_values, RedactionPolicy};
+use crate::core::policy::load_policies;
+use crate::core::queue::{Queue, RunRequest, RunResult};
+use crate::core::storage::{parse_id, AppCtx};
+use crate::core::template::render;
+use crate::core::usage::{self, Usage};
+use crate::ui::symbols::{BULLET, ERR, OK};
+use chrono::Local;
+use console::style;
+use llm::{chat::ChatMessage, LLMProvider};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Consume run requests from a queue and write their results back, until interrupted
+/// (Ctrl+C or SIGTERM, once the request in progress is done) or, with `once`, until the
+/// queue is empty. With `metrics_addr`, metrics and health checks are served there.
+pub async fn run(
+    ctx: &AppCtx,
+    queue: &str,
+    default_backend: Option<&str>,
+    once: bool,
+    metrics_addr: Option<&str>,
+) -> Result<(), String> {
+    let mut source = Queue::open(queue).await?;
+    let redaction = load_input_redaction()?;
+    let mut providers: HashMap<String, Box<dyn LLMProvider>> = HashMap::new();
+    println!(
+        "{} Waiting for run requests on {} (Ctrl+C to stop)",
+        style(BULLET).green().bold(),
+        queue
+    );
+    if let Some(addr) = metrics_addr {
+        let addr = monitor::spawn(addr, Arc::new(|| Ok(()))).await?;
+        println!(
+            "{} Metrics on http://{}/metrics, health checks on /healthz and /readyz",
+            style(BULLET).green().bold(),
+            addr
+        );
+    }
+
+    let (mut done, mut failed) = (0, 0);
+    loop {
+        let job = tokio::select! {
+            job = source.next() => job?,
+            _ = monitor::shutdown_signal() => break,
+        };
+        let Some(job) = job else {
+            if once {
+                break;
+            }
+            continue;
+        };
+
+        let _in_flight = metrics::start_run();
+        let start = Instant::now();
+        let (id, prompt, outcome) = match &job.request {
+            Ok(request) => (
+                request.id.clone(),
+                request.prompt.clone(),
+                execute(ctx, request, default_backend, &redaction, &mut providers).await,
+            ),
+            Err(e) => (None, String::new(), Err(e.clone())),
+        };
+        let (output, error) = match outcome {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(e)),
+        };
+        metrics::record_run("worker", error.is_none());
+        let stamp = Local::now().format("%H:%M:%S");
+        let label = id.as_deref().unwrap_or("-");
+        match &error {
+            Some(e) => {
+                failed += 1;
+                eprintln!("{} {} {} {}: {}", stamp, style(ERR).red(), label, prompt, e);
+            }
+            None => {
+                done += 1;
+                println!("{} {} {} {}", stamp, style(OK).green(), label, prompt);
+            }
+        }
+        let result = RunResult {
+            id,
+            prompt,
+            output,
+            error,
+            latency_ms: start.elapsed().as_millis() as u64,
+        };
+        source.complete(job, &result).await?;
+    }
+
+    println!(
+        "{} Worker stopped: {} run, {} failed",
+        style(BULLET).green().bold(),
+        done,
+        failed
+    );
+    Ok(())
+}
+
+/// Runs the prompt of a request, building its provider on first use.
+async fn execute(
+    ctx: &AppCtx,
+    request: &RunRequest,
+    default_backend: Option<&str>,
+    redaction: &Option<RedactionPolicy>,
+    providers: &mut HashMap<String, Box<dyn LLMProvider>>,
+) -> Result<String, String> {
+    let backend = request
+        .backend
+        .as_deref()
+        .or(default_backend)
+        .ok_or("The request names no backend and the worker has no --backend.")?;
+    let pd = ctx.prompts().load(&request.prompt)?;
+    let template = ctx
+        .prompts()
+        .expand_attachments(&request.prompt, pd.content_for(request.lang.as_deref()))?;
+    load_policies()?.check_content(&request.prompt, &template)?;
+    ctx.prompts()
+        .check_provider(&parse_id(&request.prompt).0, backend)?;
+    let mut vars: HashMap<String, String> = request.vars.clone().into_iter().collect();
+    if let Some(policy) = redaction {
+        redact_values(&mut vars, policy)?;
+    }
+    let prompt = render(&template, &with_examples(&vars, &pd));
+
+    if !providers.contains_key(backend) {
+        providers.insert(backend.to_string(), build_llm(ctx, backend)?);
+    }
+    let llm = &providers[backend];
+    let messages = vec![ChatMessage::user().content(&prompt).build()];
+    let response = llm.chat(&messages).await.map_err(|e| e.to_string())?;
+    usage::record(&ctx.base_dir, &request.prompt, Usage::Run);
+    Ok(response.text().unwrap_or_default())
+}
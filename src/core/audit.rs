@@ -0,0 +1,63 @@
+//! Append-only log of sensitive operations, such as plaintext exports and requests to
+//! `serve`, kept in `audit.log` in the store directory: one JSON object per line.
+
+use super::utils::restrict_permissions;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// An entry of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEvent {
+    pub at: DateTime<Utc>,
+    /// Login of the user who ran the operation, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// What was done, e.g. `export.plaintext`.
+    pub action: String,
+    #[serde(default)]
+    pub details: Value,
+}
+
+/// Returns the path of the audit log.
+pub fn audit_log_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("audit.log")
+}
+
+/// Appends an event to the audit log, done by the user running the command.
+pub fn record(base_dir: &Path, action: &str, details: Value) -> Result<(), String> {
+    let user = env::var("USER").or_else(|_| env::var("USERNAME")).ok();
+    record_as(base_dir, user.as_deref(), action, details)
+}
+
+/// Appends an event to the audit log, done by `user`, e.g. the API token of a request
+/// to the server.
+pub fn record_as(
+    base_dir: &Path,
+    user: Option<&str>,
+    action: &str,
+    details: Value,
+) -> Result<(), String> {
+    let event = AuditEvent {
+        at: Utc::now(),
+        user: user.map(str::to_string),
+        action: action.to_string(),
+        details,
+    };
+    let mut line = serde_json::to_string(&event).map_err(|e| format!("Serialize error: {}", e))?;
+    line.push('\n');
+
+    let path = audit_log_path(base_dir);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Audit log error: {}", e))?;
+    restrict_permissions(&path);
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Audit log error: {}", e))
+}
@@ -0,0 +1,394 @@
+//! Portable archives of the whole store, used to move it to another machine.
+//!
+//! An archive is a tar of the store directory and `config.toml` (without git clones of deployed packs),
+//! encrypted as a whole: `PSBK` + kind + [salt] + sealed tar. With a password, the tar
+//! is sealed with AES-256-GCM (nonce + ciphertext); with the master key, it is sealed by
+//! the store cipher, which records its algorithm in a header.
+//! A backup sealed with the master key cannot carry that key; a backup sealed with a
+//! password includes `key.bin`. A transfer archive holds the files decrypted and is
+//! sealed with a password, so they can be re-encrypted under another master key.
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use std::fs;
+use std::path::{Component, Path};
+
+use super::cipher::{CipherBackend, StoreCipher};
+use super::crypto::{decrypt_from_base64, encrypt_to_base64, password_cipher, ROTATION_DIR};
+use super::journal::JOURNAL_FILE;
+use super::paths::config_file;
+use super::policy::POLICY_FILE;
+use super::storage::AppCtx;
+use super::tokens::TOKENS_FILE;
+use super::utils::{ensure_dir, restrict_permissions};
+
+const MAGIC_BACKUP: &[u8; 4] = b"PSBK";
+
+/// Path of `config.toml` inside an archive.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Path of the master key inside an archive.
+pub const KEY_FILE: &str = "keys/key.bin";
+
+/// How an archive was produced and sealed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Backup sealed with the master key, without the key itself.
+    Backup = 0,
+    /// Backup sealed with a password, including the master key.
+    BackupWithKey = 1,
+    /// Decrypted store files sealed with a transfer password, without the master key.
+    Transfer = 2,
+}
+
+impl ArchiveKind {
+    /// Whether the archive is sealed with a password rather than the master key.
+    pub fn needs_password(self) -> bool {
+        self != ArchiveKind::Backup
+    }
+}
+
+/// A file of the store, with its path relative to the store directory.
+pub struct BackupFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// Archives the store and encrypts it. With a `password`, the master key is included.
+pub fn create_backup(ctx: &AppCtx, password: Option<&str>) -> Result<(Vec<u8>, usize), String> {
+    let mut files = store_files(ctx)?;
+    files.retain(|f| f.path != KEY_FILE || password.is_some());
+    let kind = match password {
+        Some(_) => ArchiveKind::BackupWithKey,
+        None => ArchiveKind::Backup,
+    };
+    Ok((seal(ctx, &files, kind, password)?, files.len()))
+}
+
+/// Archives the store with every file decrypted, sealed with a transfer password.
+/// The master key is left out.
+pub fn create_transfer(ctx: &AppCtx, password: &str) -> Result<(Vec<u8>, usize), String> {
+    let mut files = store_files(ctx)?;
+    files.retain(|f| f.path != KEY_FILE);
+    for file in files.iter_mut().filter(|f| is_encrypted(&f.path)) {
+        file.data = decrypt_file(&ctx.cipher, file)?;
+    }
+    Ok((
+        seal(ctx, &files, ArchiveKind::Transfer, Some(password))?,
+        files.len(),
+    ))
+}
+
+/// Re-encrypts the decrypted files of a transfer archive with the local master key.
+pub fn rewrap_transfer(ctx: &AppCtx, files: &mut [BackupFile]) -> Result<(), String> {
+    for file in files.iter_mut().filter(|f| is_encrypted(&f.path)) {
+        file.data = encrypt_to_base64(&ctx.cipher, &file.data)?.into_bytes();
+    }
+    Ok(())
+}
+
+/// Reads the kind of an archive from its header.
+pub fn archive_kind(data: &[u8]) -> Result<ArchiveKind, String> {
+    if data.len() < 5 || !data.starts_with(MAGIC_BACKUP) {
+        return Err("Not a prompt-store backup".to_string());
+    }
+    match data[4] {
+        0 => Ok(ArchiveKind::Backup),
+        1 => Ok(ArchiveKind::BackupWithKey),
+        2 => Ok(ArchiveKind::Transfer),
+        _ => Err("Unsupported backup format".to_string()),
+    }
+}
+
+/// Decrypts an archive and returns its files. Fails if the archive was tampered with.
+pub fn open_backup(
+    ctx: &AppCtx,
+    data: &[u8],
+    password: Option<&str>,
+) -> Result<Vec<BackupFile>, String> {
+    let tar = if archive_kind(data)?.needs_password() {
+        let password = password.ok_or("This backup is protected by a password")?;
+        if data.len() < 5 + 16 + 12 {
+            return Err("Backup is corrupted or too short".to_string());
+        }
+        password_cipher(password, &data[5..21])?
+            .decrypt(&data[21..])
+            .map_err(|_| {
+                "Failed to decrypt backup. Invalid password or corrupted file.".to_string()
+            })?
+    } else {
+        ctx.cipher.open(&data[5..]).map_err(|_| {
+            "Failed to decrypt backup. It was made with another master key or is corrupted."
+                .to_string()
+        })?
+    };
+
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(tar.as_slice());
+    for entry in archive
+        .entries()
+        .map_err(|e| format!("Archive error: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Archive error: {}", e))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("Archive error: {}", e))?
+            .to_string_lossy()
+            .into_owned();
+        if !Path::new(&path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(format!("Unsafe path in backup: {}", path));
+        }
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut data)
+            .map_err(|e| format!("Archive error: {}", e))?;
+        files.push(BackupFile { path, data });
+    }
+    Ok(files)
+}
+
+/// Checks that every encrypted file of an archive decrypts with `cipher`, the key
+/// the store will use once restored. Returns the number of prompts found.
+pub fn verify_backup(files: &[BackupFile], cipher: &StoreCipher) -> Result<usize, String> {
+    let mut prompts = 0;
+    for file in files.iter().filter(|f| is_encrypted(&f.path)) {
+        decrypt_file(cipher, file)?;
+        if file.path.ends_with(".prompt") {
+            prompts += 1;
+        }
+    }
+    Ok(prompts)
+}
+
+/// Replaces the store contents with the files of an archive.
+///
+/// Files are first written to a staging directory, then moved into place. Directories
+/// present in the archive (e.g., `workspaces`) replace the current ones entirely.
+pub fn restore_backup(ctx: &AppCtx, files: &[BackupFile]) -> Result<(), String> {
+    let staging = tempfile::tempdir_in(&ctx.base_dir)
+        .map_err(|e| format!("Unable to create staging directory: {}", e))?;
+    for file in files {
+        let path = staging.path().join(&file.path);
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent)?;
+        }
+        fs::write(&path, &file.data).map_err(|e| format!("Write error: {}", e))?;
+        restrict_permissions(&path);
+    }
+
+    for entry in fs::read_dir(staging.path()).map_err(|e| format!("Read dir error: {}", e))? {
+        let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+        let target = ctx.base_dir.join(entry.file_name());
+        if entry.file_name() == "keys" {
+            // Keep the current master key when the archive does not carry one.
+            ensure_dir(&target)?;
+            for key in fs::read_dir(entry.path()).map_err(|e| format!("Read dir error: {}", e))? {
+                let key = key.map_err(|e| format!("Dir entry error: {}", e))?;
+                fs::rename(key.path(), target.join(key.file_name()))
+                    .map_err(|e| format!("Restore error: {}", e))?;
+            }
+            continue;
+        }
+        if entry.file_name() == CONFIG_FILE {
+            // The config may live outside the store directory (XDG config home).
+            let config = config_file()?;
+            if let Some(parent) = config.parent() {
+                ensure_dir(parent)?;
+            }
+            fs::copy(entry.path(), &config).map_err(|e| format!("Restore error: {}", e))?;
+            continue;
+        }
+        if target.is_dir() {
+            fs::remove_dir_all(&target).map_err(|e| format!("Restore error: {}", e))?;
+        }
+        fs::rename(entry.path(), &target).map_err(|e| format!("Restore error: {}", e))?;
+    }
+    // Undoing an operation from before the restore would mix the two stores.
+    super::journal::clear(&ctx.base_dir)
+}
+
+/// Tars the files and encrypts the archive with the master key or a password-derived key.
+fn seal(
+    ctx: &AppCtx,
+    files: &[BackupFile],
+    kind: ArchiveKind,
+    password: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for file in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(file.data.len() as u64);
+        header.set_mode(0o600);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &file.path, file.data.as_slice())
+            .map_err(|e| format!("Archive error: {}", e))?;
+    }
+    let tar = builder
+        .into_inner()
+        .map_err(|e| format!("Archive error: {}", e))?;
+
+    let mut out = Vec::from(&MAGIC_BACKUP[..]);
+    out.push(kind as u8);
+    let sealed = match password {
+        Some(password) => {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            out.extend_from_slice(&salt);
+            password_cipher(password, &salt)?.encrypt(&tar)?
+        }
+        None => ctx.cipher.seal(&tar)?,
+    };
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+fn decrypt_file(cipher: &StoreCipher, file: &BackupFile) -> Result<Vec<u8>, String> {
+    std::str::from_utf8(&file.data)
+        .ok()
+        .and_then(|encoded| decrypt_from_base64(cipher, encoded).ok())
+        .map(|data| data.to_vec())
+        .ok_or_else(|| format!("Corrupted file in backup: {}", file.path))
+}
+
+/// Reads the store files encrypted with the master key.
+pub(crate) fn encrypted_files(ctx: &AppCtx) -> Result<Vec<BackupFile>, String> {
+    let mut files = store_files(ctx)?;
+    files.retain(|f| is_encrypted(&f.path));
+    Ok(files)
+}
+
+/// Whether a store file is encrypted with the master key.
+pub(crate) fn is_encrypted(path: &str) -> bool {
+    // The policy of a deployed pack stays plain: policies are read without the master key.
+    (path.starts_with("workspaces/") && Path::new(path).file_name() != Some(POLICY_FILE.as_ref()))
+        || path.starts_with("profiles/")
+        || path.starts_with("runs/")
+        || path.starts_with("releases/")
+        || path.starts_with("batches/")
+        || path == "docs/index.bin"
+        || path == "content-index.bin"
+        || path == "repl-history.bin"
+        || path == "keys/providers.bin"
+        || path == "keys/signing.key"
+        || path == TOKENS_FILE
+}
+
+/// Reads the store files and `config.toml`.
+pub(crate) fn store_files(ctx: &AppCtx) -> Result<Vec<BackupFile>, String> {
+    let mut files = Vec::new();
+    collect(&ctx.base_dir, &ctx.base_dir, &mut files)?;
+    let config = config_file()?;
+    if config.is_file() && !files.iter().any(|f| f.path == CONFIG_FILE) {
+        let data = fs::read(&config).map_err(|e| format!("Read error: {}", e))?;
+        files.push(BackupFile {
+            path: CONFIG_FILE.to_string(),
+            data,
+        });
+    }
+    Ok(files)
+}
+
+/// Recursively reads the store files, skipping pack clones, the undo journal, the logs,
+/// a staged key rotation, temporary files and symlinks.
+fn collect(base: &Path, dir: &Path, files: &mut Vec<BackupFile>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        let rel = path
+            .strip_prefix(base)
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let is_symlink = fs::symlink_metadata(&path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if rel == "registries"
+            || rel == JOURNAL_FILE
+            || rel == "logs"
+            || rel == ROTATION_DIR
+            || rel.ends_with(".tmp")
+            || is_symlink
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect(base, &path, files)?;
+        } else {
+            let data = fs::read(&path).map_err(|e| format!("Read error: {}", e))?;
+            files.push(BackupFile { path: rel, data });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROMPT: &str = "workspaces/default/hello.prompt";
+
+    /// Seals a tar of `entries` with the master key, writing their paths as is.
+    fn archive(ctx: &AppCtx, entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            // `set_path` refuses the paths a crafted archive would carry.
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o600);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        let mut out = Vec::from(&MAGIC_BACKUP[..]);
+        out.push(ArchiveKind::Backup as u8);
+        out.extend_from_slice(&ctx.cipher.seal(&builder.into_inner().unwrap()).unwrap());
+        out
+    }
+
+    #[test]
+    fn backups_restore_into_another_store() {
+        let (_dir, ctx) = AppCtx::temp();
+        let prompt = encrypt_to_base64(&ctx.cipher, b"hello").unwrap();
+        fs::write(ctx.base_dir.join(PROMPT), &prompt).unwrap();
+        let (data, _) = create_backup(&ctx, None).unwrap();
+
+        let mut files = open_backup(&ctx, &data, None).unwrap();
+        assert!(files.iter().all(|f| f.path != KEY_FILE));
+        assert_eq!(verify_backup(&files, &ctx.cipher).unwrap(), 1);
+        // Restoring `config.toml` would overwrite the one of the user running the tests.
+        files.retain(|f| f.path != CONFIG_FILE);
+
+        let (_other_dir, other) = AppCtx::temp();
+        restore_backup(&other, &files).unwrap();
+        assert_eq!(
+            fs::read_to_string(other.base_dir.join(PROMPT)).unwrap(),
+            prompt
+        );
+    }
+
+    #[test]
+    fn paths_leaving_the_store_are_refused() {
+        let (dir, ctx) = AppCtx::temp();
+        for path in ["../evil", "workspaces/../../evil", "/tmp/evil"] {
+            let data = archive(&ctx, &[("workspaces/default/ok.prompt", b""), (path, b"x")]);
+            let err = open_backup(&ctx, &data, None).err().unwrap();
+            assert_eq!(err, format!("Unsafe path in backup: {}", path));
+        }
+        assert!(!dir.path().parent().unwrap().join("evil").exists());
+    }
+
+    #[test]
+    fn pack_policies_are_not_encrypted() {
+        assert!(is_encrypted(PROMPT));
+        assert!(!is_encrypted("workspaces/legal/policy.toml"));
+    }
+
+    #[test]
+    fn backups_of_another_key_are_refused() {
+        let (_dir, ctx) = AppCtx::temp();
+        let (_other_dir, other) = AppCtx::temp();
+        let data = archive(&other, &[(PROMPT, b"")]);
+        assert!(open_backup(&ctx, &data, None).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Provider batch jobs submitted with `batch submit`, and a client of the OpenAI Batch API.
+//!
+//! A job uploads one chat completion request per record of a dataset, which the
+//! provider answers within 24 hours at a lower price. The state of each job, including
+//! the records sent, is kept encrypted under `batches/` so `batch fetch` can pair the
+//! answers with their inputs.
+
+use super::cipher::StoreCipher;
+use super::config::{http_client, OpenAiEndpoint};
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::storage::write_atomic;
+use chrono::{DateTime, Utc};
+use reqwest::{multipart, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Endpoint the requests of a job are sent to.
+const CHAT_ENDPOINT: &str = "/v1/chat/completions";
+
+/// A batch job submitted to a provider.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchJob {
+    /// ID of the job at the provider, e.g. `batch_abc123`.
+    pub id: String,
+    /// ID of the prompt that was run.
+    pub prompt: String,
+    pub backend: String,
+    /// The dataset the records were read from.
+    pub input: String,
+    pub created_at: DateTime<Utc>,
+    /// Records sent, keyed by their line in the input file.
+    pub records: BTreeMap<usize, BTreeMap<String, String>>,
+    /// Last status reported by the provider.
+    #[serde(default)]
+    pub status: String,
+}
+
+/// Progress of a job, as reported by the provider.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteStatus {
+    /// `validating`, `in_progress`, `finalizing`, `completed`, `failed`, `expired`,
+    /// `cancelling` or `cancelled`.
+    pub status: String,
+    #[serde(default)]
+    pub request_counts: RequestCounts,
+    #[serde(default)]
+    pub output_file_id: Option<String>,
+    #[serde(default)]
+    pub error_file_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RequestCounts {
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub completed: u64,
+    #[serde(default)]
+    pub failed: u64,
+}
+
+/// The answer to one record of a job.
+pub struct RecordAnswer {
+    pub line: usize,
+    pub result: Result<String, String>,
+}
+
+/// Returns the directory holding the batch jobs.
+pub fn jobs_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join("batches")
+}
+
+fn job_path(base_dir: &Path, id: &str) -> PathBuf {
+    jobs_dir(base_dir).join(format!("{}.job", id))
+}
+
+/// Encrypts and writes the state of a job.
+pub fn save_job(base_dir: &Path, cipher: &StoreCipher, job: &BatchJob) -> Result<(), String> {
+    let json =
+        Zeroizing::new(serde_json::to_vec(job).map_err(|e| format!("Serialize error: {}", e))?);
+    write_atomic(
+        &job_path(base_dir, &job.id),
+        encrypt_to_base64(cipher, &json)?,
+    )
+}
+
+/// Loads a job, returning `None` if it does not exist.
+pub fn load_job(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    id: &str,
+) -> Result<Option<BatchJob>, String> {
+    if id.is_empty() || id.contains(['/', '\\', '.']) {
+        return Ok(None);
+    }
+    let path = job_path(base_dir, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let plaintext = decrypt_from_base64(cipher, &encoded)?;
+    serde_json::from_slice(&plaintext)
+        .map(Some)
+        .map_err(|_| "Invalid JSON".to_string())
+}
+
+/// Loads every job, most recent first.
+pub fn list_jobs(base_dir: &Path, cipher: &StoreCipher) -> Result<Vec<BatchJob>, String> {
+    let dir = jobs_dir(base_dir);
+    let mut jobs = Vec::new();
+    if !dir.exists() {
+        return Ok(jobs);
+    }
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("job") {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            if let Some(job) = load_job(base_dir, cipher, stem)? {
+                jobs.push(job);
+            }
+        }
+    }
+    jobs.sort_by_key(|j| std::cmp::Reverse(j.created_at));
+    Ok(jobs)
+}
+
+/// Uploads one request per rendered prompt and starts a job answering them.
+/// Returns the ID of the job.
+pub async fn submit(
+    endpoint: &OpenAiEndpoint,
+    prompts: &[(usize, String)],
+) -> Result<String, String> {
+    let mut requests = String::new();
+    for (line, prompt) in prompts {
+        let mut body = serde_json::json!({
+            "model": endpoint.model,
+            "messages": [{ "role": "user", "content": prompt }],
+        });
+        if let Some(temperature) = endpoint.temperature {
+            body["temperature"] = temperature.into();
+        }
+        let request = serde_json::json!({
+            "custom_id": custom_id(*line),
+            "method": "POST",
+            "url": CHAT_ENDPOINT,
+            "body": body,
+        });
+        requests.push_str(&request.to_string());
+        requests.push('\n');
+    }
+
+    let form = multipart::Form::new().text("purpose", "batch").part(
+        "file",
+        multipart::Part::bytes(requests.into_bytes()).file_name("requests.jsonl"),
+    );
+    let url = endpoint_url(endpoint, "files");
+    let file = send(
+        authorize(http_client()?.post(&url), endpoint).multipart(form),
+        &url,
+    )
+    .await?;
+    let file_id = string_field(&file, "id", &url)?;
+
+    let url = endpoint_url(endpoint, "batches");
+    let body = serde_json::json!({
+        "input_file_id": file_id,
+        "endpoint": CHAT_ENDPOINT,
+        "completion_window": "24h",
+    });
+    let request = authorize(http_client()?.post(&url), endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string());
+    let batch = send(request, &url).await?;
+    string_field(&batch, "id", &url)
+}
+
+/// Asks the provider for the progress of a job.
+pub async fn remote_status(endpoint: &OpenAiEndpoint, id: &str) -> Result<RemoteStatus, String> {
+    let url = endpoint_url(endpoint, &format!("batches/{}", id));
+    let body = send(authorize(http_client()?.get(&url), endpoint), &url).await?;
+    serde_json::from_value(body).map_err(|e| format!("Invalid answer from {}: {}", url, e))
+}
+
+/// Downloads the answers of a completed job, from its output and error files.
+pub async fn download_answers(
+    endpoint: &OpenAiEndpoint,
+    status: &RemoteStatus,
+) -> Result<Vec<RecordAnswer>, String> {
+    let mut answers = Vec::new();
+    for file_id in [&status.output_file_id, &status.error_file_id]
+        .into_iter()
+        .flatten()
+    {
+        let url = endpoint_url(endpoint, &format!("files/{}/content", file_id));
+        let resp = authorize(http_client()?.get(&url), endpoint)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+        if !resp.status().is_success() {
+            return Err(format!("{} answered with status {}", url, resp.status()));
+        }
+        let content = resp
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+        for text in content.lines().filter(|t| !t.trim().is_empty()) {
+            let line: Value = serde_json::from_str(text)
+                .map_err(|e| format!("Invalid line in {}: {}", url, e))?;
+            if let Some(answer) = parse_answer(&line) {
+                answers.push(answer);
+            }
+        }
+    }
+    Ok(answers)
+}
+
+/// Reads a line of an output or error file, skipping lines of requests this store
+/// didn't send.
+fn parse_answer(line: &Value) -> Option<RecordAnswer> {
+    let line_number = line["custom_id"]
+        .as_str()?
+        .strip_prefix("line-")?
+        .parse()
+        .ok()?;
+    let response = &line["response"];
+    let result = if let Some(message) = line["error"]["message"].as_str() {
+        Err(message.to_string())
+    } else if response["status_code"].as_u64() != Some(200) {
+        Err(response["body"]["error"]["message"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("HTTP {}", response["status_code"])))
+    } else {
+        Ok(response["body"]["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    };
+    Some(RecordAnswer {
+        line: line_number,
+        result,
+    })
+}
+
+fn custom_id(line: usize) -> String {
+    format!("line-{}", line)
+}
+
+fn endpoint_url(endpoint: &OpenAiEndpoint, path: &str) -> String {
+    format!("{}/{}", endpoint.base_url.trim_end_matches('/'), path)
+}
+
+fn authorize(request: RequestBuilder, endpoint: &OpenAiEndpoint) -> RequestBuilder {
+    if endpoint.api_key.is_empty() {
+        request
+    } else {
+        request.bearer_auth(&endpoint.api_key)
+    }
+}
+
+async fn send(request: RequestBuilder, url: &str) -> Result<Value, String> {
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    let json: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    if !status.is_success() {
+        return Err(match json["error"]["message"].as_str() {
+            Some(message) => format!("{} answered with status {}: {}", url, status, message),
+            None => format!("{} answered with status {}", url, status),
+        });
+    }
+    Ok(json)
+}
+
+fn string_field(json: &Value, field: &str, url: &str) -> Result<String, String> {
+    json[field]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("Invalid answer from {}: no '{}'", url, field))
+}
@@ -0,0 +1,184 @@
+//! The personal backup bundle written by `export` and read by `import`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::storage::{modified_at, PromptData, PromptRepository};
+
+/// Prompts and chains of the default workspace, serialized as JSON then encrypted
+/// with the master key.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Bundle {
+    pub prompts: Vec<PromptData>,
+    /// Interactive chains, with their step prompts.
+    #[serde(default)]
+    pub chains: Vec<BundledChain>,
+    /// YAML chain definitions (`chains/<id>.chain`).
+    #[serde(default)]
+    pub chain_definitions: Vec<BundledChainDefinition>,
+}
+
+/// An interactive chain: its `chain.meta` and the prompts of its steps (`<id>/<n>`).
+#[derive(Serialize, Deserialize)]
+pub struct BundledChain {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub steps: Vec<PromptData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// A YAML chain definition and its ID.
+#[derive(Serialize, Deserialize)]
+pub struct BundledChainDefinition {
+    pub id: String,
+    pub yaml: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Bundles written before chains were supported are a plain array of prompts.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BundleFormat {
+    Full(Bundle),
+    Legacy(Vec<PromptData>),
+}
+
+impl Bundle {
+    /// Parses a decrypted bundle, accepting the older prompts-only format.
+    pub fn from_json(json: &[u8]) -> Result<Self, String> {
+        match serde_json::from_slice(json).map_err(|_| "Invalid JSON".to_string())? {
+            BundleFormat::Full(bundle) => Ok(bundle),
+            BundleFormat::Legacy(prompts) => Ok(Bundle {
+                prompts,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Whether the bundle holds nothing.
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty() && self.chains.is_empty() && self.chain_definitions.is_empty()
+    }
+}
+
+impl BundledChain {
+    /// Reads an interactive chain of the store. Its `updated_at` is the most recent
+    /// change of its steps, or of its files when the steps carry no timestamp.
+    pub fn load(repo: &PromptRepository, id: &str) -> Result<Self, String> {
+        let chain = repo.load_chain(id)?;
+        let steps: Vec<PromptData> = repo.chain_steps(id)?.into_iter().map(|p| p.data).collect();
+        let updated_at = steps
+            .iter()
+            .filter_map(|s| s.updated_at)
+            .max()
+            .or_else(|| modified_at(&repo.chain_dir(id).join("chain.meta")));
+        Ok(Self {
+            id: chain.id,
+            title: chain.title,
+            tags: chain.tags,
+            steps,
+            updated_at,
+        })
+    }
+
+    /// Whether both chains have the same title, tags and steps, ignoring IDs and timestamps.
+    pub fn same_content(&self, other: &BundledChain) -> bool {
+        self.title == other.title
+            && self.tags == other.tags
+            && self.steps.len() == other.steps.len()
+            && self
+                .steps
+                .iter()
+                .zip(&other.steps)
+                .all(|(a, b)| same_prompt(a, b))
+    }
+}
+
+/// Whether two prompts have the same title, content, tags, schema and examples.
+pub fn same_prompt(a: &PromptData, b: &PromptData) -> bool {
+    a.title == b.title
+        && a.content == b.content
+        && a.translations == b.translations
+        && a.variants == b.variants
+        && a.canary == b.canary
+        && a.tags == b.tags
+        && a.description == b.description
+        && a.schema == b.schema
+        && a.examples == b.examples
+}
+
+/// A condition selecting bundle items, parsed from `tag:<tag>`, `id:<id>`,
+/// `title:<title>`, `title~<text>` or `content~<text>`. Matching is case-insensitive.
+pub enum ItemFilter {
+    Tag(String),
+    Id(String),
+    Title(String),
+    TitleContains(String),
+    ContentContains(String),
+}
+
+impl FromStr for ItemFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s
+            .find([':', '~'])
+            .map(|i| (&s[..i], &s[i..i + 1], s[i + 1..].to_lowercase()));
+        match split {
+            Some(("tag", ":", v)) => Ok(Self::Tag(v)),
+            Some(("id", ":", v)) => Ok(Self::Id(v)),
+            Some(("title", ":", v)) => Ok(Self::Title(v)),
+            Some(("title", "~", v)) => Ok(Self::TitleContains(v)),
+            Some(("content", "~", v)) => Ok(Self::ContentContains(v)),
+            _ => Err(format!(
+                "Invalid filter '{}'. Use tag:<tag>, id:<id>, title:<title>, title~<text> or content~<text>.",
+                s
+            )),
+        }
+    }
+}
+
+impl ItemFilter {
+    fn matches(&self, id: &str, title: &str, tags: &[String], content: &str) -> bool {
+        match self {
+            Self::Tag(tag) => tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Self::Id(v) => id.eq_ignore_ascii_case(v),
+            Self::Title(v) => title.eq_ignore_ascii_case(v),
+            Self::TitleContains(v) => title.to_lowercase().contains(v.as_str()),
+            Self::ContentContains(v) => content.to_lowercase().contains(v.as_str()),
+        }
+    }
+}
+
+/// Whether a prompt matches every filter.
+pub fn prompt_matches(filters: &[ItemFilter], p: &PromptData) -> bool {
+    filters
+        .iter()
+        .all(|f| f.matches(&p.id, &p.title, &p.tags, &p.content))
+}
+
+impl Bundle {
+    /// Keeps only the items matching every filter. A chain matches a tag or content
+    /// filter through its own tags or any of its steps; a YAML chain is titled by its ID.
+    pub fn retain_matching(&mut self, filters: &[ItemFilter]) {
+        self.prompts.retain(|p| prompt_matches(filters, p));
+        self.chains.retain(|c| {
+            filters.iter().all(|f| {
+                f.matches(&c.id, &c.title, &c.tags, "")
+                    || c.steps
+                        .iter()
+                        .any(|s| f.matches(&c.id, &c.title, &s.tags, &s.content))
+            })
+        });
+        self.chain_definitions.retain(|d| {
+            filters
+                .iter()
+                .all(|f| f.matches(&d.id, &d.id, &[], &d.yaml))
+        });
+    }
+}
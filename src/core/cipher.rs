@@ -0,0 +1,234 @@
+//! Pluggable encryption of the store files.
+//!
+//! Files encrypted with the master key start with a header holding the format version
+//! and the algorithm, `$ps2$<algorithm>$`, followed by what the [`CipherBackend`]
+//! produced (base64-encoded in text files). Files written before headers existed have
+//! none and are AES-256-GCM.
+//!
+//! Text files use envelope encryption (see `crypto::encrypt_to_base64`): the backend
+//! only wraps a data key of the file, which encrypts the data with [`data_cipher`].
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use chacha20poly1305::ChaCha20Poly1305;
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+/// Identifier of AES-256-GCM, the default algorithm.
+pub const AES_256_GCM: &str = "aes-256-gcm";
+/// Identifier of ChaCha20-Poly1305.
+pub const CHACHA20_POLY1305: &str = "chacha20-poly1305";
+
+/// Length of the data keys of enveloped files.
+pub const DATA_KEY_LEN: usize = 32;
+
+/// Version of the format of the files encrypted with the master key, written in their
+/// header:
+///
+/// - 0: no header, base64 of the AES-256-GCM nonce and ciphertext.
+/// - 1: `$ps1$<algorithm>$` header naming the cipher.
+/// - 2: text files hold a data key wrapped by the cipher, and flag compressed plaintext.
+///
+/// Files of a later version are refused instead of misread; `prompt-store migrate`
+/// upgrades files of an earlier one.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Start of the header of an encrypted file, before the format version.
+const HEADER_PREFIX: &str = "$ps";
+
+/// An authenticated cipher for the files of the store.
+///
+/// The built-in backends are AES-256-GCM and ChaCha20-Poly1305 under the master key.
+/// Implement it to encrypt with a KMS (envelope encryption) or an HSM, and install it
+/// with `PromptStore::with_cipher_backend`.
+pub trait CipherBackend: Send + Sync {
+    /// Identifier written in the header of the files it encrypts, e.g. `aes-256-gcm`:
+    /// lowercase letters, digits and '-'.
+    fn algorithm(&self) -> &str;
+
+    /// Encrypts data, returning everything `decrypt` needs, such as the nonce.
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Decrypts and authenticates data produced by `encrypt`.
+    fn decrypt(&self, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String>;
+}
+
+impl CipherBackend for Aes256Gcm {
+    fn algorithm(&self) -> &str {
+        AES_256_GCM
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let cipher_bytes =
+            Aead::encrypt(self, &nonce, plaintext).map_err(|_| "Encrypt error".to_string())?;
+        Ok([nonce.as_slice(), &cipher_bytes].concat())
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        if data.len() < 12 {
+            return Err("Corrupted data".to_string());
+        }
+        let (nonce, cipher_bytes) = data.split_at(12);
+        Aead::decrypt(self, Nonce::from_slice(nonce), cipher_bytes)
+            .map(Zeroizing::new)
+            .map_err(|_| "Decrypt error".to_string())
+    }
+}
+
+impl CipherBackend for ChaCha20Poly1305 {
+    fn algorithm(&self) -> &str {
+        CHACHA20_POLY1305
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let cipher_bytes =
+            Aead::encrypt(self, &nonce, plaintext).map_err(|_| "Encrypt error".to_string())?;
+        Ok([nonce.as_slice(), &cipher_bytes].concat())
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        if data.len() < 12 {
+            return Err("Corrupted data".to_string());
+        }
+        let (nonce, cipher_bytes) = data.split_at(12);
+        Aead::decrypt(
+            self,
+            chacha20poly1305::Nonce::from_slice(nonce),
+            cipher_bytes,
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| "Decrypt error".to_string())
+    }
+}
+
+/// The ciphers of a store: one encrypting new files, and every one able to read
+/// existing files, picked by the algorithm in their header.
+#[derive(Clone)]
+pub struct StoreCipher {
+    writer: Arc<dyn CipherBackend>,
+    readers: Vec<Arc<dyn CipherBackend>>,
+}
+
+impl StoreCipher {
+    /// The built-in ciphers under a master key, encrypting new files with `algorithm`.
+    pub fn from_key(key: &[u8], algorithm: &str) -> Result<Self, String> {
+        let aes: Arc<dyn CipherBackend> =
+            Arc::new(Aes256Gcm::new_from_slice(key).map_err(|_| "Invalid key length".to_string())?);
+        let chacha: Arc<dyn CipherBackend> = Arc::new(
+            ChaCha20Poly1305::new_from_slice(key).map_err(|_| "Invalid key length".to_string())?,
+        );
+        let writer = match algorithm {
+            AES_256_GCM => Arc::clone(&aes),
+            CHACHA20_POLY1305 => Arc::clone(&chacha),
+            other => {
+                return Err(format!(
+                    "Unknown cipher '{}': use '{}' or '{}'",
+                    other, AES_256_GCM, CHACHA20_POLY1305
+                ))
+            }
+        };
+        Ok(Self {
+            writer,
+            readers: vec![aes, chacha],
+        })
+    }
+
+    /// Encrypts new files with `backend`. Files encrypted with the previous ciphers
+    /// still decrypt, so a store moves to the new backend as its files are rewritten.
+    pub fn with_backend(mut self, backend: Arc<dyn CipherBackend>) -> Self {
+        self.readers
+            .retain(|reader| reader.algorithm() != backend.algorithm());
+        self.readers.push(Arc::clone(&backend));
+        self.writer = backend;
+        self
+    }
+
+    /// The algorithm new files are encrypted with.
+    pub fn algorithm(&self) -> &str {
+        self.writer.algorithm()
+    }
+
+    /// Encrypts data into the binary format: header, then the backend output.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = self.header().into_bytes();
+        out.extend_from_slice(&self.writer.encrypt(plaintext)?);
+        Ok(out)
+    }
+
+    /// Decrypts data produced by [`seal`](Self::seal), or written without a header by
+    /// older versions.
+    pub fn open(&self, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        let (_, algorithm, body) = split_header(data)?;
+        self.decrypt_with(algorithm, body)
+    }
+
+    /// The header of the files encrypted with the writer, e.g. `$ps2$aes-256-gcm$`.
+    pub(crate) fn header(&self) -> String {
+        format!("{}{}${}$", HEADER_PREFIX, FORMAT_VERSION, self.algorithm())
+    }
+
+    /// Encrypts data with the writer, without a header.
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        self.writer.encrypt(plaintext)
+    }
+
+    /// Decrypts data with the backend of an algorithm read from a header.
+    pub(crate) fn decrypt_with(
+        &self,
+        algorithm: &str,
+        data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, String> {
+        self.readers
+            .iter()
+            .find(|reader| reader.algorithm() == algorithm)
+            .ok_or_else(|| format!("No cipher backend for '{}' is installed", algorithm))?
+            .decrypt(data)
+    }
+}
+
+/// Returns the format version of a file encrypted with the master key.
+pub fn format_version(data: &[u8]) -> Result<u32, String> {
+    split_header(data).map(|(version, _, _)| version)
+}
+
+/// Splits the header off encrypted data, returning the format version, the algorithm
+/// and the rest. Data without a header is version 0, encrypted with AES-256-GCM.
+pub(crate) fn split_header(data: &[u8]) -> Result<(u32, &str, &[u8]), String> {
+    let Some((version, algorithm, rest)) = parse_header(data) else {
+        return Ok((0, AES_256_GCM, data));
+    };
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "The file was written by a newer version of prompt-store (format {}, this one reads up to {}). Upgrade prompt-store to read it.",
+            version, FORMAT_VERSION
+        ));
+    }
+    Ok((version, algorithm, rest))
+}
+
+fn parse_header(data: &[u8]) -> Option<(u32, &str, &[u8])> {
+    let mut fields = data
+        .strip_prefix(HEADER_PREFIX.as_bytes())?
+        .splitn(3, |b| *b == b'$');
+    let version = std::str::from_utf8(fields.next().filter(|f| f.len() <= 9)?).ok()?;
+    let version: u32 = version.parse().ok().filter(|v| *v > 0)?;
+    let algorithm = std::str::from_utf8(fields.next().filter(|f| f.len() <= 64)?).ok()?;
+    let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+    if algorithm.is_empty() || !algorithm.chars().all(valid) {
+        return None;
+    }
+    Some((version, algorithm, fields.next()?))
+}
+
+/// The cipher encrypting the data of an enveloped file under its data key: the
+/// built-in algorithm named in the header, or AES-256-GCM when the data key is wrapped
+/// by a custom backend.
+pub fn data_cipher(algorithm: &str, data_key: &[u8]) -> Result<Box<dyn CipherBackend>, String> {
+    let invalid = |_| "Invalid data key".to_string();
+    Ok(match algorithm {
+        CHACHA20_POLY1305 => Box::new(ChaCha20Poly1305::new_from_slice(data_key).map_err(invalid)?),
+        _ => Box::new(Aes256Gcm::new_from_slice(data_key).map_err(invalid)?),
+    })
+}
@@ -1,63 +1,915 @@
 //! Manages the loading of LLM provider configurations.
+//!
+//! `config.toml` (see [`config_file`]) has the following shape (every section is optional):
+//!
+//! ```toml
+//! [defaults]
+//! provider = "openai"        # used when a command doesn't name a provider
+//! temperature = 0.7
+//! max_tokens = 1024
+//! timeout_seconds = 60
+//! proxy = "http://proxy.internal:3128" # registries, batch jobs, fetch steps, sinks, share
+//! retry = { max_attempts = 3, backoff_ms = 500 }
+//!
+//! [providers.openai]
+//! backend = "openai"
+//! model = "gpt-4o-mini"
+//! temperature = 0.2          # per-provider overrides of the defaults above
+//!
+//! [failover.chat]           # use `chat` as a provider name to fail over in order
+//! primary = "openai"
+//! fallback = ["anthropic", "ollama"]
+//!
+//! [pools.bulk]              # weighted round-robin across keys/deployments
+//! members = [{ provider = "openai-a", weight = 3 }, { provider = "openai-b" }]
+//!
+//! [lookup]
+//! resolution = "local-first" # or "packs-first" / "strict" when a title matches several prompts
+//!
+//! [ids]
+//! scheme = "slug"            # "random" (default), "ulid" or "slug"
+//!
+//! [shell]
+//! enabled = true
+//! allowlist = ["jq", "pandoc"] # exact program names or paths, looked up in PATH
+//!
+//! [updates]
+//! check_interval_hours = 24  # how often to look for newer pack versions (0 disables)
+//!
+//! [share]
+//! endpoint = "https://paste.internal/api" # `share` uploads there instead of writing a file
+//!
+//! [redaction]
+//! inputs = true              # redact run variables before they reach providers
+//! phone_numbers = false      # emails, phone_numbers and credit_cards default to true
+//! patterns = ["EMP-\\d{6}"]  # custom regexes, replaced with [REDACTED]
+//!
+//! [key]
+//! hardware = "yubikey"       # or "tpm"; `key protect` wraps the master key with it
+//! yubikey_slot = 2           # challenge-response slot of the YubiKey
+//!
+//! [crypto]
+//! cipher = "chacha20-poly1305" # cipher of newly written files; "aes-256-gcm" by default
+//!
+//! [interactive]
+//! lock_after_minutes = 15    # idle time before the password is asked again (0 disables)
+//!
+//! [logging]
+//! file = true                # also log to logs/prompt-store.log in the store, as with --log-file
+//! ```
+//!
+//! A `policy.toml` (see [`super::policy`]) can ban providers, cap their temperature and
+//! require redaction, whatever this file says.
 
+use super::cipher::AES_256_GCM;
+use super::discovery::LocalModel;
+use super::hardware::HardwareKey;
+use super::logging::LoggedProvider;
+use super::paths::config_file;
+use super::pii::RedactionPolicy;
+use super::policy::{load_policies, BlockedProvider};
+use super::retry::{RetryPolicy, RetryingProvider};
+use super::secrets::load_api_keys;
+use super::storage::AppCtx;
 use llm::builder::{LLMBackend, LLMBuilder};
 use llm::chain::LLMRegistry;
+use llm::LLMProvider;
+use log::warn;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
 struct Config {
+    #[serde(default)]
+    defaults: Defaults,
     #[serde(default)]
     providers: HashMap<String, ProviderConfig>,
+    #[serde(default)]
+    failover: HashMap<String, FailoverConfig>,
+    #[serde(default)]
+    pools: HashMap<String, PoolConfig>,
+    #[serde(default)]
+    ids: IdsConfig,
+    #[serde(default)]
+    lookup: LookupConfig,
+    #[serde(default)]
+    shell: ShellConfig,
+    #[serde(default)]
+    share: ShareConfig,
+    #[serde(default)]
+    updates: UpdatesConfig,
+    #[serde(default)]
+    redaction: RedactionConfig,
+    #[serde(default)]
+    key: KeyConfig,
+    #[serde(default)]
+    crypto: CryptoConfig,
+    #[serde(default)]
+    interactive: InteractiveConfig,
+    #[serde(default)]
+    logging: LoggingConfig,
+}
+
+/// Global settings applied to every provider unless overridden.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Defaults {
+    /// Provider used by commands that don't name one explicitly (e.g., `pipe`).
+    provider: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    timeout_seconds: Option<u64>,
+    /// HTTP(S) proxy of the requests made by `http_client()`. LLM providers take theirs
+    /// from `HTTPS_PROXY`.
+    proxy: Option<String>,
+    retry: Option<RetryConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+struct RetryConfig {
+    max_attempts: u32,
+    #[serde(default = "default_backoff_ms")]
+    backoff_ms: u64,
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+/// An ordered group of providers: the fallbacks are tried in turn when the primary
+/// fails with a transient error (HTTP 429/5xx or timeout).
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct FailoverConfig {
+    primary: String,
+    #[serde(default)]
+    fallback: Vec<String>,
+}
+
+/// A set of weighted providers that chain steps are distributed across.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PoolConfig {
+    members: Vec<PoolMember>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PoolMember {
+    provider: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// How IDs are generated for new prompts and chains.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdScheme {
+    /// 8 random alphanumeric characters.
+    #[default]
+    Random,
+    /// Lexicographically sortable ULID, ordered by creation time.
+    Ulid,
+    /// Human-readable slug derived from the title, suffixed on collision.
+    Slug,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct IdsConfig {
+    #[serde(default)]
+    scheme: IdScheme,
+}
+
+/// Which prompt wins when a title matches prompts in several workspaces.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolutionOrder {
+    /// Prefer the prompt in the `default` workspace over deployed packs.
+    #[default]
+    LocalFirst,
+    /// Prefer the prompt from a deployed pack over the `default` workspace.
+    PacksFirst,
+    /// Never guess; report every candidate.
+    Strict,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct LookupConfig {
+    #[serde(default)]
+    resolution: ResolutionOrder,
+}
+
+/// Opt-in settings for shell steps in chains.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ShellConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    allowlist: Vec<String>,
+}
+
+/// Background check for newer versions of deployed packs.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct UpdatesConfig {
+    #[serde(default = "default_check_interval_hours")]
+    check_interval_hours: u64,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_hours: default_check_interval_hours(),
+        }
+    }
+}
+
+fn default_check_interval_hours() -> u64 {
+    24
+}
+
+/// Where `share` uploads sealed prompts.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ShareConfig {
+    /// Paste endpoint receiving the share as a POST body and answering with its URL.
+    endpoint: Option<String>,
+}
+
+/// Personal data redaction by `redact`, and of run variables when `inputs` is set.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RedactionConfig {
+    #[serde(default)]
+    inputs: bool,
+    emails: Option<bool>,
+    phone_numbers: Option<bool>,
+    credit_cards: Option<bool>,
+    #[serde(default)]
+    patterns: Vec<String>,
+}
+
+impl RedactionConfig {
+    fn policy(&self) -> RedactionPolicy {
+        let defaults = RedactionPolicy::default();
+        RedactionPolicy {
+            emails: self.emails.unwrap_or(defaults.emails),
+            phone_numbers: self.phone_numbers.unwrap_or(defaults.phone_numbers),
+            credit_cards: self.credit_cards.unwrap_or(defaults.credit_cards),
+            patterns: self.patterns.clone(),
+        }
+    }
 }
 
+/// Hardware device protecting the master key.
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct KeyConfig {
+    hardware: Option<HardwareKey>,
+    #[serde(default = "default_yubikey_slot")]
+    yubikey_slot: u8,
+}
+
+fn default_yubikey_slot() -> u8 {
+    2
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        Self {
+            hardware: None,
+            yubikey_slot: default_yubikey_slot(),
+        }
+    }
+}
+
+/// Encryption of the store files.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct CryptoConfig {
+    /// Algorithm files are encrypted with when written. Existing files keep theirs.
+    cipher: Option<String>,
+}
+
+/// Idle auto-lock of the interactive mode.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InteractiveConfig {
+    #[serde(default = "default_lock_after_minutes")]
+    lock_after_minutes: u64,
+}
+
+impl Default for InteractiveConfig {
+    fn default() -> Self {
+        Self {
+            lock_after_minutes: default_lock_after_minutes(),
+        }
+    }
+}
+
+fn default_lock_after_minutes() -> u64 {
+    15
+}
+
+/// Diagnostic logs of the CLI.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct LoggingConfig {
+    /// Always write the log file, as `--log-file` does.
+    #[serde(default)]
+    file: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 struct ProviderConfig {
     backend: String,
     model: String,
     api_key_env: Option<String>,
     base_url: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    timeout_seconds: Option<u64>,
+    retry: Option<RetryConfig>,
 }
 
-/// Loads the LLM provider configurations from `~/.prompt-store/config.toml`
-/// and builds an LLMRegistry.
-pub fn load_llm_registry() -> Result<LLMRegistry, String> {
-    let home = env::var("HOME").map_err(|_| "Unable to determine HOME directory".to_string())?;
-    let config_path = PathBuf::from(home)
-        .join(".prompt-store")
-        .join("config.toml");
+/// Reads, parses and validates `config.toml`, returning an empty config if it is missing.
+fn load_config() -> Result<Config, String> {
+    let config_path = config_file()?;
 
     if !config_path.exists() {
-        // Return an empty registry if no config file is found, commands will warn the user.
-        return Ok(LLMRegistry::new());
+        return Ok(Config::default());
     }
 
     let config_content =
         fs::read_to_string(config_path).map_err(|e| format!("Failed to read config.toml: {}", e))?;
-    let config: Config =
-        toml::from_str(&config_content).map_err(|e| format!("Failed to parse config.toml: {}", e))?;
+    let config: Config = toml::from_str(&config_content)
+        .map_err(|e| format!("Failed to parse config.toml: {}", e))?;
+    config.validate()?;
+    Ok(config)
+}
+
+impl Config {
+    /// Checks value ranges and cross-references, naming the offending key on failure.
+    fn validate(&self) -> Result<(), String> {
+        validate_settings(
+            "[defaults]",
+            self.defaults.temperature,
+            self.defaults.timeout_seconds,
+            self.defaults.retry,
+        )?;
+        if let Some(proxy) = &self.defaults.proxy {
+            reqwest::Proxy::all(proxy).map_err(|e| {
+                format!("Invalid config.toml: [defaults] proxy '{}' is not a valid URL ({})", proxy, e)
+            })?;
+        }
+        if let Some(name) = &self.defaults.provider {
+            if !self.providers.contains_key(name) {
+                return Err(format!(
+                    "Invalid config.toml: [defaults] provider '{}' is not defined under [providers]",
+                    name
+                ));
+            }
+        }
+        for (name, provider) in &self.providers {
+            let section = format!("[providers.{}]", name);
+            LLMBackend::from_str(&provider.backend).map_err(|_| {
+                format!(
+                    "Invalid config.toml: {} backend '{}' is not a known backend",
+                    section, provider.backend
+                )
+            })?;
+            validate_settings(
+                &section,
+                provider.temperature,
+                provider.timeout_seconds,
+                provider.retry,
+            )?;
+        }
+        for (name, group) in &self.failover {
+            if self.providers.contains_key(name) {
+                return Err(format!(
+                    "Invalid config.toml: [failover.{}] has the same name as a provider",
+                    name
+                ));
+            }
+            for member in std::iter::once(&group.primary).chain(&group.fallback) {
+                if !self.providers.contains_key(member) {
+                    return Err(format!(
+                        "Invalid config.toml: [failover.{}] provider '{}' is not defined under [providers]",
+                        name, member
+                    ));
+                }
+            }
+        }
+        for (name, pool) in &self.pools {
+            if pool.members.is_empty() {
+                return Err(format!(
+                    "Invalid config.toml: [pools.{}] members must not be empty",
+                    name
+                ));
+            }
+            for member in &pool.members {
+                if !self.providers.contains_key(&member.provider) {
+                    return Err(format!(
+                        "Invalid config.toml: [pools.{}] provider '{}' is not defined under [providers]",
+                        name, member.provider
+                    ));
+                }
+                if member.weight == 0 {
+                    return Err(format!(
+                        "Invalid config.toml: [pools.{}] weight of '{}' must be greater than 0",
+                        name, member.provider
+                    ));
+                }
+            }
+        }
+        self.redaction
+            .policy()
+            .validate()
+            .map_err(|e| format!("Invalid config.toml: [redaction] {}", e))?;
+        if !(1..=2).contains(&self.key.yubikey_slot) {
+            return Err(format!(
+                "Invalid config.toml: [key] yubikey_slot must be 1 or 2, not {}",
+                self.key.yubikey_slot
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn validate_settings(
+    section: &str,
+    temperature: Option<f32>,
+    timeout_seconds: Option<u64>,
+    retry: Option<RetryConfig>,
+) -> Result<(), String> {
+    if let Some(t) = temperature {
+        if !(0.0..=2.0).contains(&t) {
+            return Err(format!(
+                "Invalid config.toml: {} temperature must be between 0.0 and 2.0 (got {})",
+                section, t
+            ));
+        }
+    }
+    if timeout_seconds == Some(0) {
+        return Err(format!(
+            "Invalid config.toml: {} timeout_seconds must be greater than 0",
+            section
+        ));
+    }
+    if let Some(retry) = retry {
+        if retry.max_attempts == 0 {
+            return Err(format!(
+                "Invalid config.toml: {} retry.max_attempts must be at least 1",
+                section
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of the default provider: the configured `[defaults] provider`,
+/// or the only provider if exactly one is configured.
+pub fn default_provider_name() -> Result<Option<String>, String> {
+    let config = load_config()?;
+    if config.defaults.provider.is_some() {
+        return Ok(config.defaults.provider);
+    }
+    if config.providers.len() == 1 {
+        return Ok(config.providers.into_keys().next());
+    }
+    Ok(None)
+}
+
+/// Returns the explicitly requested provider name, falling back to the default provider.
+pub fn resolve_provider_name(explicit: Option<&str>) -> Result<String, String> {
+    match explicit {
+        Some(p) => Ok(p.to_string()),
+        None => default_provider_name()?.ok_or_else(|| {
+            format!(
+                "No default provider. Set 'provider' under [defaults] in {} or use --provider.",
+                config_file().unwrap_or_default().display()
+            )
+        }),
+    }
+}
+
+/// Returns how ambiguous titles are resolved, as configured in the `[lookup]` section.
+pub fn load_resolution_order() -> Result<ResolutionOrder, String> {
+    Ok(load_config()?.lookup.resolution)
+}
+
+/// Returns the ID scheme configured in the `[ids]` section.
+pub fn load_id_scheme() -> Result<IdScheme, String> {
+    Ok(load_config()?.ids.scheme)
+}
+
+/// Returns the programs shell steps may run, as configured in the `[shell]` section.
+/// The list is empty unless shell steps are explicitly enabled.
+pub fn load_shell_allowlist() -> Result<Vec<String>, String> {
+    let config = load_config()?;
+    if config.shell.enabled {
+        Ok(config.shell.allowlist)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Returns how often deployed packs are checked for updates, as configured in the
+/// `[updates]` section, or `None` when the check is disabled.
+pub fn load_update_check_interval() -> Result<Option<Duration>, String> {
+    Ok(match load_config()?.updates.check_interval_hours {
+        0 => None,
+        hours => Some(Duration::from_secs(hours * 3600)),
+    })
+}
+
+/// Returns the paste endpoint configured in the `[share]` section, if any.
+pub fn load_share_endpoint() -> Result<Option<String>, String> {
+    Ok(load_config()?.share.endpoint)
+}
+
+/// Returns the redaction policy configured in the `[redaction]` section, used by `redact`.
+pub fn load_redaction_policy() -> Result<RedactionPolicy, String> {
+    Ok(load_config()?.redaction.policy())
+}
+
+/// Returns the redaction policy applied to run variables, if the `[redaction]` section
+/// enables `inputs` or a `policy.toml` requires redaction.
+pub fn load_input_redaction() -> Result<Option<RedactionPolicy>, String> {
+    let redaction = load_config()?.redaction;
+    let required = load_policies()?.requires_redaction();
+    Ok((redaction.inputs || required).then(|| redaction.policy()))
+}
+
+/// Returns the redaction policy of the `[redaction]` section if a `policy.toml` requires
+/// redaction, for runners of the library which only redact on request otherwise.
+pub fn load_required_redaction() -> Result<Option<RedactionPolicy>, String> {
+    if !load_policies()?.requires_redaction() {
+        return Ok(None);
+    }
+    Ok(Some(load_config()?.redaction.policy()))
+}
+
+/// Returns the hardware device selected by `[key] hardware` to protect the master key,
+/// with the YubiKey slot to use.
+pub fn load_hardware_key() -> Result<Option<(HardwareKey, u8)>, String> {
+    let key = load_config()?.key;
+    Ok(key.hardware.map(|device| (device, key.yubikey_slot)))
+}
+
+/// Returns the algorithm new files are encrypted with, as configured in the `[crypto]`
+/// section.
+pub fn load_cipher_algorithm() -> Result<String, String> {
+    Ok(load_config()?
+        .crypto
+        .cipher
+        .unwrap_or_else(|| AES_256_GCM.to_string()))
+}
+
+/// Returns how long the interactive mode may stay idle before it locks the store, as
+/// configured in the `[interactive]` section, or `None` when auto-lock is disabled.
+pub fn load_lock_after() -> Result<Option<Duration>, String> {
+    Ok(match load_config()?.interactive.lock_after_minutes {
+        0 => None,
+        minutes => Some(Duration::from_secs(minutes * 60)),
+    })
+}
+
+/// How long a fetch or shell step may take when `[defaults]` sets no `timeout_seconds`.
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Returns how long a fetch or shell step may take: the `[defaults] timeout_seconds`,
+/// or [`DEFAULT_STEP_TIMEOUT`].
+pub fn load_step_timeout() -> Result<Duration, String> {
+    Ok(load_config()?
+        .defaults
+        .timeout_seconds
+        .map_or(DEFAULT_STEP_TIMEOUT, Duration::from_secs))
+}
+
+/// Builds an HTTP client for the requests that don't go through a provider, with the
+/// `[defaults]` timeout and proxy.
+pub fn http_client() -> Result<reqwest::Client, String> {
+    let defaults = load_config()?.defaults;
+    let mut builder = reqwest::Client::builder();
+    if let Some(timeout) = defaults.timeout_seconds {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+    if let Some(proxy) = &defaults.proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy '{}': {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build the HTTP client: {}", e))
+}
+
+/// Returns whether the `[logging]` section enables the log file.
+pub fn load_log_file() -> Result<bool, String> {
+    Ok(load_config()?.logging.file)
+}
+
+/// Returns the failover groups defined in `config.toml`, each as its ordered list of
+/// provider names (primary first).
+pub fn load_failover_groups() -> Result<HashMap<String, Vec<String>>, String> {
+    Ok(load_config()?
+        .failover
+        .into_iter()
+        .map(|(name, group)| {
+            let mut members = vec![group.primary];
+            members.extend(group.fallback);
+            (name, members)
+        })
+        .collect())
+}
+
+/// Returns the provider pools defined in `config.toml`, each as its list of
+/// `(provider, weight)` members.
+pub fn load_provider_pools() -> Result<HashMap<String, Vec<(String, u32)>>, String> {
+    Ok(load_config()?
+        .pools
+        .into_iter()
+        .map(|(name, pool)| {
+            let members = pool
+                .members
+                .into_iter()
+                .map(|m| (m.provider, m.weight))
+                .collect();
+            (name, members)
+        })
+        .collect())
+}
+
+/// Summary of a configured provider.
+pub struct ProviderInfo {
+    pub name: String,
+    pub backend: String,
+    pub model: String,
+}
+
+/// Lists the providers defined in `config.toml`, sorted by name.
+pub fn list_providers() -> Result<Vec<ProviderInfo>, String> {
+    let mut providers: Vec<ProviderInfo> = load_config()?
+        .providers
+        .into_iter()
+        .map(|(name, p)| ProviderInfo {
+            name,
+            backend: p.backend,
+            model: p.model,
+        })
+        .collect();
+    providers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(providers)
+}
+
+/// Returns the backend of a provider: the backend of `backend:model`, or the one
+/// `config.toml` gives the provider of that name, `None` for unknown providers.
+pub fn provider_backend(provider: &str) -> Result<Option<String>, String> {
+    if let Some((backend, _)) = provider.split_once(':') {
+        return Ok(Some(backend.to_string()));
+    }
+    Ok(load_config()?
+        .providers
+        .remove(provider)
+        .map(|conf| conf.backend))
+}
+
+/// Base URL of the OpenAI API, used when a provider doesn't set `base_url`.
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1/";
+
+/// An OpenAI-compatible API, for endpoints the LLM client doesn't cover such as batch jobs.
+pub struct OpenAiEndpoint {
+    pub base_url: String,
+    pub model: String,
+    /// Empty for servers that don't check keys.
+    pub api_key: String,
+    /// Temperature of the requests, capped by the policies in force.
+    pub temperature: Option<f32>,
+}
+
+/// Resolves the OpenAI-compatible API of a backend: `openai:<model>`, or a provider of
+/// `config.toml` whose backend is `openai`. Keys stored with `providers set-key` come first.
+///
+/// Fails if a policy bans the provider, as the providers of [`load_llm_registry`] do.
+pub fn load_openai_endpoint(ctx: &AppCtx, backend: &str) -> Result<OpenAiEndpoint, String> {
+    let policies = load_policies()?;
+    if let Some((provider, model)) = backend.split_once(':') {
+        if provider != "openai" {
+            return Err(format!(
+                "Provider '{}' has no batch API here; use 'openai:<model>' or an OpenAI-compatible provider.",
+                provider
+            ));
+        }
+        let temperature = policies.max_temperature();
+        policies.check_provider(backend, provider, temperature)?;
+        let api_key = env::var("OPENAI_API_KEY")
+            .map_err(|_| "API key env var 'OPENAI_API_KEY' not found.".to_string())?;
+        return Ok(OpenAiEndpoint {
+            base_url: OPENAI_BASE_URL.to_string(),
+            model: model.to_string(),
+            api_key,
+            temperature,
+        });
+    }
 
+    let mut config = load_config()?;
+    let provider = config.providers.remove(backend).ok_or_else(|| {
+        format!(
+            "Invalid backend '{}'. Use 'openai:<model>' or a provider name from config.toml",
+            backend
+        )
+    })?;
+    if provider.backend != "openai" {
+        return Err(format!(
+            "Provider '{}' uses the '{}' backend; batch jobs need an OpenAI-compatible provider.",
+            backend, provider.backend
+        ));
+    }
+    let temperature = provider
+        .temperature
+        .or(config.defaults.temperature)
+        .or(policies.max_temperature());
+    policies.check_provider(backend, &provider.backend, temperature)?;
+    let api_key_env = provider
+        .api_key_env
+        .unwrap_or_else(|| "OPENAI_API_KEY".to_string());
+    let api_key = match load_api_keys(&ctx.base_dir, &ctx.cipher)?.remove(backend) {
+        Some(key) => key,
+        None if api_key_env.is_empty() => String::new(),
+        None => env::var(&api_key_env).map_err(|_| {
+            format!(
+                "Environment variable '{}' not set for provider '{}'. Set it or run 'prompt-store providers set-key {}'.",
+                api_key_env, backend, backend
+            )
+        })?,
+    };
+    Ok(OpenAiEndpoint {
+        base_url: provider
+            .base_url
+            .unwrap_or_else(|| OPENAI_BASE_URL.to_string()),
+        model: provider.model,
+        api_key,
+        temperature,
+    })
+}
+
+/// Appends the given local models to `config.toml` as `[providers.<name>]` sections,
+/// leaving the rest of the file (including comments) untouched.
+///
+/// Models whose provider name is already configured are skipped; the names added are returned.
+pub fn add_local_providers(models: &[LocalModel]) -> Result<Vec<String>, String> {
+    let existing = load_config()?.providers;
+    let path = config_file()?;
+    let mut content = read_config_text(&path)?;
+
+    let mut added = Vec::new();
+    for m in models {
+        if existing.contains_key(&m.name) || added.contains(&m.name) {
+            continue;
+        }
+        start_section(&mut content);
+        content.push_str(&format!(
+            "[providers.{}]\nbackend = {}\nmodel = {}\nbase_url = {}\n",
+            m.name,
+            toml::Value::String(m.backend.clone()),
+            toml::Value::String(m.model.clone()),
+            toml::Value::String(m.base_url.clone()),
+        ));
+        if m.backend == "openai" {
+            // Local OpenAI-compatible servers don't check the key.
+            content.push_str("api_key_env = \"\"\n");
+        }
+        added.push(m.name.clone());
+    }
+
+    if !added.is_empty() {
+        write_config_text(&path, &content)?;
+    }
+    Ok(added)
+}
+
+/// Appends a `[providers.<name>]` section for a hosted backend to `config.toml`, leaving
+/// the rest of the file untouched. `api_key_env` names the environment variable holding
+/// its API key, when the key is not stored with `providers set-key`.
+pub fn add_provider(
+    name: &str,
+    backend: &str,
+    model: &str,
+    api_key_env: Option<&str>,
+) -> Result<(), String> {
+    if load_config()?.providers.contains_key(name) {
+        return Err(format!("Provider '{}' is already configured.", name));
+    }
+    let path = config_file()?;
+    let mut content = read_config_text(&path)?;
+    start_section(&mut content);
+    content.push_str(&format!(
+        "[providers.{}]\nbackend = {}\nmodel = {}\n",
+        name,
+        toml::Value::String(backend.to_string()),
+        toml::Value::String(model.to_string()),
+    ));
+    if let Some(var) = api_key_env {
+        content.push_str(&format!(
+            "api_key_env = {}\n",
+            toml::Value::String(var.to_string())
+        ));
+    }
+    write_config_text(&path, &content)
+}
+
+/// The text of `config.toml`, empty if it is missing.
+fn read_config_text(path: &Path) -> Result<String, String> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    fs::read_to_string(path).map_err(|e| format!("Failed to read config.toml: {}", e))
+}
+
+fn write_config_text(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        super::utils::ensure_dir(parent)?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write config.toml: {}", e))
+}
+
+/// Separates a new section from the existing content with a blank line.
+fn start_section(content: &mut String) {
+    if !content.is_empty() && !content.ends_with("\n\n") {
+        content.push_str(if content.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        });
+    }
+}
+
+/// Loads the LLM provider configurations from `config.toml`
+/// and builds an LLMRegistry.
+///
+/// API keys stored with `providers set-key` take precedence over `api_key_env`.
+pub fn load_llm_registry(ctx: &AppCtx) -> Result<LLMRegistry, String> {
+    // An empty registry is returned if no config file is found, commands will warn the user.
+    let config = load_config()?;
+    let defaults = config.defaults;
+    let mut stored_keys = load_api_keys(&ctx.base_dir, &ctx.cipher)?;
+
+    // The llm crate builds its own HTTP clients, which only take proxies from the
+    // environment; the clients of `http_client()` get the proxy explicitly.
+    let proxied = ["HTTPS_PROXY", "https_proxy"]
+        .iter()
+        .any(|var| env::var_os(var).is_some());
+    if defaults.proxy.is_some() && !proxied {
+        warn!("[defaults] proxy does not apply to LLM providers; set HTTPS_PROXY for them");
+    }
+
+    let policies = load_policies()?;
     let mut registry = LLMRegistry::new();
 
     for (name, provider_conf) in config.providers {
         let backend = LLMBackend::from_str(&provider_conf.backend)
             .map_err(|_| format!("Invalid backend '{}' for provider '{}'", provider_conf.backend, name))?;
 
+        // Providers a policy forbids stay known, so using them reports the violation.
+        let temperature = provider_conf
+            .temperature
+            .or(defaults.temperature)
+            .or(policies.max_temperature());
+        if let Err(violation) = policies.check_provider(&name, &provider_conf.backend, temperature) {
+            registry.insert(&name, Box::new(BlockedProvider::new(violation)));
+            continue;
+        }
+
         let api_key_env_var = provider_conf.api_key_env.unwrap_or_else(|| match backend {
             LLMBackend::OpenAI => "OPENAI_API_KEY".to_string(),
             LLMBackend::Anthropic => "ANTHROPIC_API_KEY".to_string(),
             _ => "".to_string(),
         });
 
-        let api_key = if !api_key_env_var.is_empty() {
+        let api_key = if let Some(key) = stored_keys.remove(&name) {
+            key
+        } else if !api_key_env_var.is_empty() {
             env::var(&api_key_env_var).map_err(|_| {
                 format!(
-                    "Environment variable '{}' not set for provider '{}'",
-                    api_key_env_var, name
+                    "Environment variable '{}' not set for provider '{}'. Set it or run 'prompt-store providers set-key {}'.",
+                    api_key_env_var, name, name
                 )
             })?
         } else {
@@ -67,17 +919,40 @@ pub fn load_llm_registry() -> Result<LLMRegistry, String> {
         let mut builder = LLMBuilder::new()
             .backend(backend)
             .model(&provider_conf.model);
-        
+
         if !api_key.is_empty() {
             builder = builder.api_key(api_key);
+        } else if provider_conf.base_url.is_some() {
+            // Self-hosted OpenAI-compatible servers accept any key, but the client requires one.
+            builder = builder.api_key("local");
         }
         if let Some(base_url) = provider_conf.base_url {
             builder = builder.base_url(base_url);
         }
+        if let Some(temperature) = temperature {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(max_tokens) = provider_conf.max_tokens.or(defaults.max_tokens) {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(timeout) = provider_conf.timeout_seconds.or(defaults.timeout_seconds) {
+            builder = builder.timeout_seconds(timeout);
+        }
 
-        let provider = builder.build().map_err(|e| e.to_string())?;
-        registry.insert(&name, provider);
+        let mut provider: Box<dyn LLMProvider> = builder.build().map_err(|e| e.to_string())?;
+        if let Some(retry) = provider_conf.retry.or(defaults.retry) {
+            if retry.max_attempts > 1 {
+                provider = Box::new(RetryingProvider::new(
+                    provider,
+                    RetryPolicy {
+                        max_attempts: retry.max_attempts,
+                        backoff_ms: retry.backoff_ms,
+                    },
+                ));
+            }
+        }
+        registry.insert(&name, Box::new(LoggedProvider::new(&name, provider)));
     }
 
     Ok(registry)
-}
\ No newline at end of file
+}
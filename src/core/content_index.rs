@@ -0,0 +1,134 @@
+//! Hashes of the content of every prompt, kept encrypted in `content-index.bin` of the
+//! store directory, to find the prompts identical to one being imported or deployed.
+//!
+//! Each entry remembers the size and modification time of the file it was computed
+//! from, so bringing the index up to date only decrypts the prompts changed since.
+
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::storage::{write_atomic, AppCtx, PromptData, PromptFile};
+use super::utils::hex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Path of the content index, relative to the store directory.
+pub const CONTENT_INDEX_FILE: &str = "content-index.bin";
+
+/// Returns the path of the encrypted content index.
+pub fn content_index_path(base_dir: &Path) -> PathBuf {
+    base_dir.join(CONTENT_INDEX_FILE)
+}
+
+/// SHA-256 of everything a prompt is made of except its ID, modification time and
+/// attachments, hex-encoded. Identical prompts have the same hash whatever their ID.
+pub fn content_hash(pd: &PromptData) -> Result<String, String> {
+    let pd = PromptData {
+        id: String::new(),
+        updated_at: None,
+        attachments: Vec::new(),
+        ..pd.clone()
+    };
+    // `Value` keeps object keys in a sorted map, so the JSON is canonical.
+    let value = serde_json::to_value(&pd).map_err(|e| format!("Serialize error: {}", e))?;
+    let json =
+        Zeroizing::new(serde_json::to_vec(&value).map_err(|e| format!("Serialize error: {}", e))?);
+    Ok(hex(&Sha256::digest(&*json)))
+}
+
+/// The content hashes of the prompts of the store.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ContentIndex {
+    /// Keyed by full prompt ID.
+    entries: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    /// `None` for links, which are not copies of their own.
+    sha256: Option<String>,
+    len: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+impl ContentIndex {
+    /// Loads the index and brings it up to date with the prompt files. Prompts that
+    /// cannot be decrypted are left out.
+    pub fn load(ctx: &AppCtx) -> Result<Self, String> {
+        let path = content_index_path(&ctx.base_dir);
+        // A missing or unreadable index is only a cache, rebuilt from the prompts.
+        let mut index: Self = fs::read_to_string(&path)
+            .ok()
+            .and_then(|encoded| decrypt_from_base64(&ctx.cipher, &encoded).ok())
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default();
+
+        let repo = ctx.prompts();
+        let mut entries = BTreeMap::new();
+        for (full_id, path) in repo.files()? {
+            let (len, modified) = file_stamp(&path);
+            let entry = match index.entries.remove(&full_id) {
+                Some(entry) if entry.len == len && entry.modified == modified => entry,
+                _ => {
+                    let sha256 = match repo.load_file(&full_id) {
+                        Ok(PromptFile::Prompt(pd)) => Some(content_hash(&pd)?),
+                        Ok(PromptFile::Link { .. }) => None,
+                        Err(_) => continue,
+                    };
+                    IndexEntry {
+                        sha256,
+                        len,
+                        modified,
+                    }
+                }
+            };
+            entries.insert(full_id, entry);
+        }
+        index.entries = entries;
+        Ok(index)
+    }
+
+    /// Encrypts and writes the index.
+    pub fn save(&self, ctx: &AppCtx) -> Result<(), String> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(self).map_err(|e| format!("Serialize error: {}", e))?,
+        );
+        write_atomic(
+            &content_index_path(&ctx.base_dir),
+            encrypt_to_base64(&ctx.cipher, &json)?,
+        )
+    }
+
+    /// Returns the IDs of the prompts with the given content hash, sorted. Links are
+    /// never returned.
+    pub fn matches<'a>(&'a self, sha256: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| entry.sha256.as_deref() == Some(sha256))
+            .map(|(full_id, _)| full_id.as_str())
+    }
+
+    /// Records a prompt or link (`sha256` of `None`) saved since the index was loaded.
+    pub fn insert(&mut self, ctx: &AppCtx, full_id: &str, sha256: Option<String>) {
+        let (len, modified) = file_stamp(&ctx.prompt_path(full_id));
+        self.entries.insert(
+            full_id.to_string(),
+            IndexEntry {
+                sha256,
+                len,
+                modified,
+            },
+        );
+    }
+}
+
+/// Size and modification time of a file, telling whether an entry is stale.
+fn file_stamp(path: &Path) -> (u64, Option<DateTime<Utc>>) {
+    match fs::metadata(path) {
+        Ok(meta) => (meta.len(), meta.modified().ok().map(DateTime::from)),
+        Err(_) => (0, None),
+    }
+}
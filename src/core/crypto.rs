@@ -2,39 +2,286 @@ use aes_gcm::{
     aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, Params, Version};
 use base64::{engine::general_purpose, Engine as _};
+#[cfg(feature = "cli")]
 use console::style;
+#[cfg(feature = "cli")]
 use dialoguer::Password;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::Path;
+use zeroize::Zeroizing;
 
-use super::storage::AppCtx;
-use super::utils::ensure_dir;
+#[cfg(feature = "cli")]
+use super::backup::BackupFile;
+use super::cipher::{
+    data_cipher, split_header, StoreCipher, AES_256_GCM, CHACHA20_POLY1305, DATA_KEY_LEN,
+};
+use super::hardware::{is_hardware_wrapped, unwrap_key};
+use super::storage::{write_atomic, AppCtx};
+use super::utils::{ensure_dir, restrict_permissions};
+#[cfg(feature = "cli")]
+use crate::ui::progress::bar;
+
+/// Flags compressed plaintext, followed by a zstd frame. JSON, YAML and text never
+/// start with a NUL byte, so plaintext written before compression is told apart.
+const COMPRESSED: &[u8; 4] = b"\0zst";
+/// Plaintext shorter than this is not worth compressing.
+const COMPRESS_MIN_LEN: usize = 512;
+
+/// Unprotected key, after the version of the key file format.
+const MAGIC_PSKY: &[u8; 4] = b"PSKY";
+/// Version of the format of unprotected key files written by this build. Version 0 is
+/// the 32 raw bytes of the key.
+pub const KEY_FORMAT_VERSION: u8 = 1;
 
+/// Directory of the store where a key rotation stages the re-wrapped files and the
+/// new key before installing them, left out of backups.
+pub const ROTATION_DIR: &str = "rotation";
+/// Staged files, under the rotation directory, at their paths in the store.
+const STAGED_FILES: &str = "files";
+/// Staged key, under the rotation directory, written last to mark the staging complete.
+const STAGED_KEY: &str = "key.bin";
+
+/// Password-protected key derived with the default Argon2 parameters.
 const MAGIC_PSWD: &[u8; 4] = b"PSWD";
+/// Password-protected key derived with the Argon2 parameters stored after the magic.
+const MAGIC_PSW2: &[u8; 4] = b"PSW2";
+
+/// Argon2 cost parameters of the password protecting the master key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    fn argon2(&self) -> Result<Argon2<'static>, String> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+/// Encrypts data with the master cipher into the on-disk format, with envelope
+/// encryption: a random data key encrypts the data and the master cipher wraps the data
+/// key. The result is `$ps2$<algorithm>$<base64 wrapped key>$<base64 ciphertext>`, so
+/// changing the master key only re-wraps data keys (see [`rewrap_base64`]). Large
+/// plaintext is compressed with zstd first.
+pub fn encrypt_to_base64(cipher: &StoreCipher, data: &[u8]) -> Result<String, String> {
+    let mut data_key = Zeroizing::new(vec![0u8; DATA_KEY_LEN]);
+    OsRng.fill_bytes(&mut data_key);
+    let sealed = data_cipher(cipher.algorithm(), &data_key)?.encrypt(&compress(data)?)?;
+    envelope(cipher, &data_key, &sealed)
+}
+
+/// Decrypts data produced by [`encrypt_to_base64`], or by versions without envelopes
+/// (`$ps1$<algorithm>$<base64>`) or headers (plain base64, AES-256-GCM). The plaintext
+/// is wiped from memory once dropped.
+pub fn decrypt_from_base64(
+    cipher: &StoreCipher,
+    encoded: &str,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    match parse_encoded(encoded)? {
+        Encoded::Envelope {
+            algorithm,
+            wrapped_key,
+            sealed,
+        } => {
+            let data_key = cipher.decrypt_with(algorithm, &wrapped_key)?;
+            decompress(data_cipher(algorithm, &data_key)?.decrypt(&sealed)?)
+        }
+        Encoded::Direct { algorithm, sealed } => cipher.decrypt_with(algorithm, &sealed),
+    }
+}
+
+/// Re-encrypts a file for another master cipher. The data key of an enveloped file is
+/// re-wrapped and its data copied as is; older files are decrypted and re-encrypted.
+pub fn rewrap_base64(
+    old: &StoreCipher,
+    new: &StoreCipher,
+    encoded: &str,
+) -> Result<String, String> {
+    if let Encoded::Envelope {
+        algorithm,
+        wrapped_key,
+        sealed,
+    } = parse_encoded(encoded)?
+    {
+        if data_cipher_name(algorithm) == data_cipher_name(new.algorithm()) {
+            let data_key = old.decrypt_with(algorithm, &wrapped_key)?;
+            return envelope(new, &data_key, &sealed);
+        }
+    }
+    encrypt_to_base64(new, &decrypt_from_base64(old, encoded)?)
+}
+
+/// Returns the data key of an enveloped file. With it, [`decrypt_with_data_key`]
+/// decrypts that one file, so a single prompt can be handed over without the master key.
+pub fn data_key(cipher: &StoreCipher, encoded: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    match parse_encoded(encoded)? {
+        Encoded::Envelope {
+            algorithm,
+            wrapped_key,
+            ..
+        } => cipher.decrypt_with(algorithm, &wrapped_key),
+        Encoded::Direct { .. } => Err("The file has no data key; rewrite it first".to_string()),
+    }
+}
+
+/// Decrypts an enveloped file with its data key (see [`data_key`]).
+pub fn decrypt_with_data_key(encoded: &str, data_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    match parse_encoded(encoded)? {
+        Encoded::Envelope {
+            algorithm, sealed, ..
+        } => decompress(data_cipher(algorithm, data_key)?.decrypt(&sealed)?),
+        Encoded::Direct { .. } => Err("The file has no data key".to_string()),
+    }
+}
+
+/// The parts of a file encrypted with the master cipher.
+enum Encoded<'a> {
+    /// Data encrypted with a data key wrapped by `algorithm`.
+    Envelope {
+        algorithm: &'a str,
+        wrapped_key: Vec<u8>,
+        sealed: Vec<u8>,
+    },
+    /// Data encrypted by `algorithm` directly.
+    Direct { algorithm: &'a str, sealed: Vec<u8> },
+}
+
+fn parse_encoded(encoded: &str) -> Result<Encoded<'_>, String> {
+    let decode = |part: &[u8]| {
+        general_purpose::STANDARD
+            .decode(part)
+            .map_err(|_| "Corrupted data".to_string())
+    };
+    let (version, algorithm, body) = split_header(encoded.trim_end().as_bytes())?;
+    if version == 0 {
+        return Ok(Encoded::Direct {
+            algorithm,
+            sealed: decode(body)?,
+        });
+    }
+    // Base64 has no '$': another one separates the wrapped key from the data.
+    Ok(match body.iter().position(|b| *b == b'$') {
+        Some(end) => Encoded::Envelope {
+            algorithm,
+            wrapped_key: decode(&body[..end])?,
+            sealed: decode(&body[end + 1..])?,
+        },
+        None => Encoded::Direct {
+            algorithm,
+            sealed: decode(body)?,
+        },
+    })
+}
+
+fn envelope(cipher: &StoreCipher, data_key: &[u8], sealed: &[u8]) -> Result<String, String> {
+    Ok(format!(
+        "{}{}${}",
+        cipher.header(),
+        general_purpose::STANDARD.encode(cipher.encrypt(data_key)?),
+        general_purpose::STANDARD.encode(sealed)
+    ))
+}
+
+/// Compresses plaintext and flags it, unless it is too short or would not shrink.
+fn compress(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    if data.len() >= COMPRESS_MIN_LEN {
+        let frame = Zeroizing::new(
+            zstd::bulk::compress(data, 0).map_err(|e| format!("Compression error: {}", e))?,
+        );
+        if COMPRESSED.len() + frame.len() < data.len() {
+            return Ok(Zeroizing::new([&COMPRESSED[..], &frame].concat()));
+        }
+    }
+    Ok(Zeroizing::new(data.to_vec()))
+}
+
+/// Decompresses plaintext flagged by [`compress`]; other plaintext is returned as is.
+fn decompress(plaintext: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>, String> {
+    match plaintext.strip_prefix(&COMPRESSED[..]) {
+        Some(frame) => zstd::stream::decode_all(frame)
+            .map(Zeroizing::new)
+            .map_err(|e| format!("Decompression error: {}", e)),
+        None => Ok(plaintext),
+    }
+}
+
+/// Name of the cipher [`data_cipher`] picks for a wrapping algorithm.
+fn data_cipher_name(algorithm: &str) -> &str {
+    match algorithm {
+        CHACHA20_POLY1305 => CHACHA20_POLY1305,
+        _ => AES_256_GCM,
+    }
+}
+
+/// Derives an AES-256-GCM cipher from a password and salt with Argon2.
+pub fn password_cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut pwd_key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut *pwd_key)
+        .map_err(|_| "KDF error".to_string())?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*pwd_key)))
+}
+
+/// Whether a key file holds a password-protected master key.
+pub fn is_password_protected(key_data: &[u8]) -> bool {
+    key_data.starts_with(MAGIC_PSWD) || key_data.starts_with(MAGIC_PSW2)
+}
 
 /// Decrypts the master key using a provided password.
-pub fn decrypt_key_with_password(key_data: &[u8], password: &str) -> Result<Vec<u8>, String> {
-    if !key_data.starts_with(MAGIC_PSWD) {
+pub fn decrypt_key_with_password(
+    key_data: &[u8],
+    password: &str,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    let (params, rest) = if let Some(rest) = key_data.strip_prefix(MAGIC_PSWD) {
+        (KdfParams::default(), rest)
+    } else if let Some(rest) = key_data.strip_prefix(MAGIC_PSW2) {
+        if rest.len() < 12 {
+            return Err("Corrupted password key".to_string());
+        }
+        let param = |i: usize| u32::from_le_bytes(rest[i * 4..i * 4 + 4].try_into().unwrap());
+        let params = KdfParams {
+            memory_kib: param(0),
+            iterations: param(1),
+            parallelism: param(2),
+        };
+        (params, &rest[12..])
+    } else {
         return Err("Key is not password protected.".to_string());
-    }
-    if key_data.len() < 4 + 16 + 12 {
+    };
+    if rest.len() < 16 + 12 {
         return Err("Corrupted password key".to_string());
     }
-    let salt = &key_data[4..20];
-    let nonce = Nonce::from_slice(&key_data[20..32]);
-    let cipher_bytes = &key_data[32..];
+    let salt = &rest[..16];
+    let nonce = Nonce::from_slice(&rest[16..28]);
+    let cipher_bytes = &rest[28..];
 
-    let mut pwd_key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(password.as_bytes(), salt, &mut pwd_key)
+    let mut pwd_key = Zeroizing::new([0u8; 32]);
+    params
+        .argon2()?
+        .hash_password_into(password.as_bytes(), salt, &mut *pwd_key)
         .map_err(|_| "KDF error".to_string())?;
 
-    let tmp_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&pwd_key));
+    let tmp_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*pwd_key));
     let raw = tmp_cipher
         .decrypt(nonce, cipher_bytes)
+        .map(Zeroizing::new)
         .map_err(|_| "Invalid password".to_string())?;
 
     if raw.len() != 32 {
@@ -43,27 +290,106 @@ pub fn decrypt_key_with_password(key_data: &[u8], password: &str) -> Result<Vec<
     Ok(raw)
 }
 
+/// Encrypts the master key with a password. Keys derived with the default parameters
+/// keep the original format, readable by older versions.
+pub fn wrap_key(key: &[u8], password: &str, params: KdfParams) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+
+    let mut pwd_key = Zeroizing::new([0u8; 32]);
+    params
+        .argon2()?
+        .hash_password_into(password.as_bytes(), &salt, &mut *pwd_key)
+        .map_err(|_| "KDF error".to_string())?;
+
+    let tmp_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*pwd_key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher_bytes = tmp_cipher
+        .encrypt(&nonce, key)
+        .map_err(|_| "Encrypt error".to_string())?;
+
+    let mut out = Vec::with_capacity(4 + 12 + 16 + 12 + cipher_bytes.len());
+    if params == KdfParams::default() {
+        out.extend_from_slice(MAGIC_PSWD);
+    } else {
+        out.extend_from_slice(MAGIC_PSW2);
+        for param in [params.memory_kib, params.iterations, params.parallelism] {
+            out.extend_from_slice(&param.to_le_bytes());
+        }
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&cipher_bytes);
+    Ok(out)
+}
+
+/// Encodes an unprotected master key for `key.bin`, after a versioned header.
+pub fn encode_plain_key(key: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut out = Zeroizing::new(Vec::with_capacity(5 + key.len()));
+    out.extend_from_slice(MAGIC_PSKY);
+    out.push(KEY_FORMAT_VERSION);
+    out.extend_from_slice(key);
+    out
+}
+
+/// Reads the master key of an unprotected key file, with a versioned header or as the
+/// raw bytes written by older versions.
+pub fn plain_key(key_data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let key = match key_data.strip_prefix(MAGIC_PSKY) {
+        Some([version, ..]) if *version > KEY_FORMAT_VERSION => {
+            return Err(format!(
+                "The key file was written by a newer version of prompt-store (format {}). Upgrade prompt-store to read it.",
+                version
+            ))
+        }
+        Some([_, key @ ..]) => key,
+        Some([]) => return Err("Corrupted key".to_string()),
+        None => key_data,
+    };
+    if key.len() != 32 {
+        return Err("Invalid key length".to_string());
+    }
+    Ok(Zeroizing::new(key.to_vec()))
+}
+
+/// Format version of a key file: protected keys (password or hardware) have their own
+/// formats and count as current; unprotected keys are 0 without a header.
+pub fn key_format_version(key_data: &[u8]) -> u8 {
+    if is_password_protected(key_data) || is_hardware_wrapped(key_data) {
+        return KEY_FORMAT_VERSION;
+    }
+    match key_data.strip_prefix(MAGIC_PSKY) {
+        Some([version, ..]) => *version,
+        _ => 0,
+    }
+}
+
+/// Whether opening the store needs the user, because its key file is protected by a
+/// password or a hardware device.
+pub fn is_key_locked(path: &Path) -> bool {
+    fs::read(path)
+        .map(|data| is_password_protected(&data) || is_hardware_wrapped(&data))
+        .unwrap_or(false)
+}
+
 /// Load or create encryption key.
-pub fn load_or_generate_key(path: &Path) -> Result<(Vec<u8>, bool), String> {
+pub fn load_or_generate_key(path: &Path) -> Result<(Zeroizing<Vec<u8>>, bool), String> {
+    finish_rotation(path)?;
     if path.exists() {
-        let mut buf = Vec::new();
+        let mut buf = Zeroizing::new(Vec::new());
         File::open(path)
             .map_err(|e| format!("Unable to open key: {}", e))?
             .read_to_end(&mut buf)
             .map_err(|e| format!("Unable to read key: {}", e))?;
 
-        if buf.starts_with(MAGIC_PSWD) {
-            let password = Password::new()
-                .with_prompt("Password")
-                .interact()
-                .map_err(|e| format!("Password error: {}", e))?;
+        if is_hardware_wrapped(&buf) {
+            Ok((unwrap_key(&buf)?, true))
+        } else if is_password_protected(&buf) {
+            let password = ask_password("Password")?;
             let raw = decrypt_key_with_password(&buf, &password)?;
             Ok((raw, true))
         } else {
-            if buf.len() != 32 {
-                return Err("Invalid key length".to_string());
-            }
-            Ok((buf, false))
+            Ok((plain_key(&buf)?, false))
         }
     } else {
         if let Some(parent) = path.parent() {
@@ -76,91 +402,307 @@ pub fn load_or_generate_key(path: &Path) -> Result<(Vec<u8>, bool), String> {
             .truncate(true)
             .open(path)
             .map_err(|e| format!("Key write error: {}", e))?;
-        f.write_all(&key)
+        f.write_all(&encode_plain_key(&key))
             .map_err(|e| format!("Key write error: {}", e))?;
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).ok();
-        }
-        Ok((key.to_vec(), false))
+        restrict_permissions(path);
+        Ok((Zeroizing::new(key.to_vec()), false))
     }
 }
 
-/// Rotate encryption key, optional password protection.
-pub fn rotate_key(ctx: &AppCtx, use_password: bool) -> Result<(), String> {
-    let mut plain = Vec::new();
-    if ctx.workspaces_dir.exists() {
-        for entry in
-            fs::read_dir(&ctx.workspaces_dir).map_err(|e| format!("Read dir error: {}", e))?
-        {
-            let ent = entry.map_err(|e| format!("Dir read error: {}", e))?;
-            let encoded =
-                fs::read_to_string(ent.path()).map_err(|e| format!("Read error: {}", e))?;
-            let decoded = general_purpose::STANDARD
-                .decode(encoded.trim_end())
-                .map_err(|_| "Corrupted data".to_string())?;
-            let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-            let plaintext = ctx
-                .cipher
-                .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-                .map_err(|_| "Decrypt error".to_string())?;
-            plain.push((ent.path(), plaintext));
-        }
+/// Creates `key.bin` with a new master key, protected by `password` if one is given.
+/// Returns the master key.
+pub fn create_key(path: &Path, password: Option<&str>) -> Result<Zeroizing<Vec<u8>>, String> {
+    if path.exists() {
+        return Err(format!("{} already exists.", path.display()));
     }
+    let key = Zeroizing::new(Aes256Gcm::generate_key(OsRng).to_vec());
+    let data = match password {
+        Some(password) => Zeroizing::new(wrap_key(&key, password, KdfParams::default())?),
+        None => encode_plain_key(&key),
+    };
+    write_atomic(path, &*data)?;
+    Ok(key)
+}
 
-    let new_key = Aes256Gcm::generate_key(OsRng);
-    let new_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&new_key));
+/// Asks for the password of the key on the terminal.
+#[cfg(feature = "cli")]
+fn ask_password(prompt: &str) -> Result<Zeroizing<String>, String> {
+    Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Password error: {}", e))
+}
+
+/// Builds without the `cli` feature have no terminal prompts: a password-protected store
+/// is opened with `PromptStore::with_password`.
+#[cfg(not(feature = "cli"))]
+fn ask_password(_prompt: &str) -> Result<Zeroizing<String>, String> {
+    Err("The key is password-protected; open the store with PromptStore::with_password".to_string())
+}
 
-    if use_password {
+/// Rotate encryption key, optional password protection. The new key keeps the
+/// protection of the current one: a password (a new one is asked) or a hardware device.
+#[cfg(feature = "cli")]
+pub fn rotate_key(ctx: &AppCtx, use_password: bool) -> Result<(), String> {
+    let current =
+        Zeroizing::new(fs::read(&ctx.key_path).map_err(|e| format!("Unable to read key: {}", e))?);
+    let new_key = Zeroizing::new(Aes256Gcm::generate_key(OsRng).to_vec());
+    let key_data = if use_password || is_password_protected(&current) {
         let password = Password::new()
             .with_prompt("New password")
             .with_confirmation("Confirm password", "Mismatch")
             .interact()
+            .map(Zeroizing::new)
             .map_err(|e| format!("Password error: {}", e))?;
-        let mut salt = [0u8; 16];
-        OsRng.fill_bytes(&mut salt);
+        Zeroizing::new(wrap_key(&new_key, &password, KdfParams::default())?)
+    } else if let Some((device, slot)) = super::hardware::wrapping(&current) {
+        Zeroizing::new(super::hardware::wrap_key(&new_key, device, slot)?)
+    } else {
+        encode_plain_key(&new_key)
+    };
 
-        let mut pwd_key = [0u8; 32];
-        Argon2::default()
-            .hash_password_into(password.as_bytes(), &salt, &mut pwd_key)
-            .map_err(|_| "KDF error".to_string())?;
+    let files = super::backup::encrypted_files(ctx)?;
+    let progress = bar(files.len(), "Re-wrapping");
+    let count = stage_rotation(ctx, &new_key, &key_data, files, || progress.inc(1))?;
+    progress.finish_and_clear();
+    finish_rotation(&ctx.key_path)?;
+    super::journal::clear(&ctx.base_dir)?;
 
-        let tmp_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&pwd_key));
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let cipher_bytes = tmp_cipher
-            .encrypt(&nonce, new_key.as_ref())
-            .map_err(|_| "Encrypt error".to_string())?;
+    println!(
+        "{} ({} files re-wrapped)",
+        style("Key rotated").green().bold(),
+        count
+    );
+    Ok(())
+}
 
-        let mut out = Vec::with_capacity(4 + 16 + 12 + cipher_bytes.len());
-        out.extend_from_slice(MAGIC_PSWD);
-        out.extend_from_slice(&salt);
-        out.extend_from_slice(&nonce);
-        out.extend_from_slice(&cipher_bytes);
-        fs::write(&ctx.key_path, out).map_err(|e| format!("Key write error: {}", e))?;
-    } else {
-        fs::write(&ctx.key_path, &new_key).map_err(|e| format!("Key write error: {}", e))?;
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(&ctx.key_path, fs::Permissions::from_mode(0o600)).ok();
-        }
+/// Re-wraps `files` for `new_key` in the staging directory, then stages `key_data`,
+/// which marks the staging as complete. The store itself is left as is: a failure only
+/// leaves an incomplete staging, which [`finish_rotation`] discards.
+#[cfg(feature = "cli")]
+fn stage_rotation(
+    ctx: &AppCtx,
+    new_key: &[u8],
+    key_data: &[u8],
+    files: Vec<BackupFile>,
+    mut progress: impl FnMut(),
+) -> Result<usize, String> {
+    let staging = ctx.base_dir.join(ROTATION_DIR);
+    if staging.join(STAGED_KEY).exists() {
+        return Err(
+            "An interrupted key rotation is pending: run the command again to finish it."
+                .to_string(),
+        );
+    }
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| format!("Delete error: {}", e))?;
     }
 
-    for (path, plaintext) in plain {
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let cipher_bytes = new_cipher
-            .encrypt(&nonce, plaintext.as_ref())
-            .map_err(|_| "Encrypt error".to_string())?;
+    let new_cipher = StoreCipher::from_key(new_key, ctx.cipher.algorithm())?;
+    for file in &files {
+        let encoded = std::str::from_utf8(&file.data)
+            .map_err(|_| format!("Corrupted file: {}", file.path))?;
+        let encoded = rewrap_base64(&ctx.cipher, &new_cipher, encoded)
+            .map_err(|e| format!("{}: {}", file.path, e))?;
+        write_atomic(&staging.join(STAGED_FILES).join(&file.path), encoded)?;
+        progress();
+    }
+    write_atomic(&staging.join(STAGED_KEY), key_data)?;
+    Ok(files.len())
+}
 
-        let mut out = Vec::with_capacity(12 + cipher_bytes.len());
-        out.extend_from_slice(&nonce);
-        out.extend_from_slice(&cipher_bytes);
-        let encoded = general_purpose::STANDARD.encode(&out);
+/// Completes a key rotation whose staging is complete: moves the staged files over
+/// the store files, then replaces `key.bin` with the staged key. Until then the current
+/// key stays in place, and an interrupted install is resumed by the next call. An
+/// incomplete staging is discarded, as the current key still opens every file.
+///
+/// Called before `key.bin` is read, so a rotation interrupted by a crash is finished
+/// when the store is next opened.
+pub fn finish_rotation(key_path: &Path) -> Result<(), String> {
+    let Some(base_dir) = key_path.parent().and_then(Path::parent) else {
+        return Ok(());
+    };
+    let staging = base_dir.join(ROTATION_DIR);
+    if !staging.exists() {
+        return Ok(());
+    }
+    let staged_key = staging.join(STAGED_KEY);
+    if staged_key.exists() {
+        install(&staging.join(STAGED_FILES), base_dir)?;
+        let key_data =
+            Zeroizing::new(fs::read(&staged_key).map_err(|e| format!("Read error: {}", e))?);
+        write_atomic(key_path, &*key_data)?;
+    }
+    fs::remove_dir_all(&staging).map_err(|e| format!("Delete error: {}", e))
+}
 
-        fs::write(path, encoded).map_err(|e| format!("Write error: {}", e))?;
+/// Moves the files under `from` to the same paths under `to`, one rename each.
+fn install(from: &Path, to: &Path) -> Result<(), String> {
+    if !from.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(from).map_err(|e| format!("Read dir error: {}", e))? {
+        let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+        let (path, target) = (entry.path(), to.join(entry.file_name()));
+        if path.is_dir() {
+            ensure_dir(&target)?;
+            install(&path, &target)?;
+        } else {
+            fs::rename(&path, &target).map_err(|e| format!("Write error: {}", e))?;
+        }
     }
+    Ok(())
+}
 
-    println!("{}", style("Key rotated").green().bold());
+/// Changes the password protecting the master key (or adds one), without re-encrypting
+/// the store. The new key file replaces the old one atomically.
+#[cfg(feature = "cli")]
+pub fn change_password(ctx: &AppCtx, params: KdfParams) -> Result<(), String> {
+    params.argon2()?;
+    let key = read_master_key(ctx)?;
+    let password = Password::new()
+        .with_prompt("New password")
+        .with_confirmation("Confirm password", "Mismatch")
+        .interact()
+        .map_err(|e| format!("Password error: {}", e))?;
+    write_atomic(&ctx.key_path, wrap_key(&key, &password, params)?)?;
+
+    println!("{}", style("Password changed").green().bold());
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Reads the raw master key from `key.bin`, asking for its password if it has one or
+/// its hardware device if it is wrapped by one.
+pub fn read_master_key(ctx: &AppCtx) -> Result<Zeroizing<Vec<u8>>, String> {
+    let key_data =
+        Zeroizing::new(fs::read(&ctx.key_path).map_err(|e| format!("Unable to read key: {}", e))?);
+    if is_hardware_wrapped(&key_data) {
+        unwrap_key(&key_data)
+    } else if is_password_protected(&key_data) {
+        let password = ask_password("Current password")?;
+        decrypt_key_with_password(&key_data, &password)
+    } else {
+        plain_key(&key_data)
+    }
+}
+
+/// A short fingerprint of a key, to check a recovered key without revealing it: the
+/// start of the authentication tag of an empty message under a zero nonce.
+pub fn key_check_value(key: &[u8]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| "Invalid key length".to_string())?;
+    let tag = cipher
+        .encrypt(Nonce::from_slice(&[0u8; 12]), &[][..])
+        .map_err(|_| "Encrypt error".to_string())?;
+    Ok(tag[..4].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use crate::core::backup::encrypted_files;
+    use tempfile::TempDir;
+
+    /// A store in a temporary directory with a plain key and one encrypted prompt.
+    fn store() -> (TempDir, AppCtx) {
+        let (dir, ctx) = AppCtx::temp();
+        let prompt = encrypt_to_base64(&ctx.cipher, b"hello").unwrap();
+        write_atomic(&prompt_path(&ctx), prompt).unwrap();
+        (dir, ctx)
+    }
+
+    fn prompt_path(ctx: &AppCtx) -> std::path::PathBuf {
+        ctx.workspaces_dir.join("default").join("hello.prompt")
+    }
+
+    /// Decrypts the prompt with the key currently in `key.bin`.
+    fn read_prompt(ctx: &AppCtx) -> Result<Vec<u8>, String> {
+        let key = plain_key(&fs::read(&ctx.key_path).unwrap())?;
+        let cipher = StoreCipher::from_key(&key, AES_256_GCM)?;
+        let encoded = fs::read_to_string(prompt_path(ctx)).unwrap();
+        decrypt_from_base64(&cipher, &encoded).map(|data| data.to_vec())
+    }
+
+    fn stage(ctx: &AppCtx, new_key: &[u8]) -> Result<usize, String> {
+        let files = encrypted_files(ctx)?;
+        stage_rotation(ctx, new_key, &encode_plain_key(new_key), files, || {})
+    }
+
+    #[test]
+    fn rotation_rewraps_files_for_the_new_key() {
+        let (_dir, ctx) = store();
+        let new_key = Aes256Gcm::generate_key(OsRng);
+        assert_eq!(stage(&ctx, &new_key).unwrap(), 1);
+        finish_rotation(&ctx.key_path).unwrap();
+
+        assert_eq!(
+            *plain_key(&fs::read(&ctx.key_path).unwrap()).unwrap(),
+            new_key.to_vec()
+        );
+        assert_eq!(read_prompt(&ctx).unwrap(), b"hello");
+        assert!(!ctx.base_dir.join(ROTATION_DIR).exists());
+    }
+
+    #[test]
+    fn staging_leaves_the_store_as_is_until_installed() {
+        let (_dir, ctx) = store();
+        let old_key = fs::read(&ctx.key_path).unwrap();
+        stage(&ctx, &Aes256Gcm::generate_key(OsRng)).unwrap();
+
+        assert_eq!(fs::read(&ctx.key_path).unwrap(), old_key);
+        assert_eq!(read_prompt(&ctx).unwrap(), b"hello");
+        // A second rotation waits for the staged one to be installed.
+        assert!(stage(&ctx, &Aes256Gcm::generate_key(OsRng)).is_err());
+
+        // The next opening of the store installs it.
+        load_or_generate_key(&ctx.key_path).unwrap();
+        assert_ne!(fs::read(&ctx.key_path).unwrap(), old_key);
+        assert_eq!(read_prompt(&ctx).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn interrupted_install_resumes() {
+        let (_dir, ctx) = store();
+        stage(&ctx, &Aes256Gcm::generate_key(OsRng)).unwrap();
+        // The prompt was moved in place before a crash, the key was not.
+        let staged = ctx
+            .base_dir
+            .join(ROTATION_DIR)
+            .join(STAGED_FILES)
+            .join("workspaces/default/hello.prompt");
+        fs::rename(staged, prompt_path(&ctx)).unwrap();
+        assert!(read_prompt(&ctx).is_err());
+
+        finish_rotation(&ctx.key_path).unwrap();
+        assert_eq!(read_prompt(&ctx).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn failed_staging_keeps_the_old_key() {
+        let (_dir, ctx) = store();
+        let old_key = fs::read(&ctx.key_path).unwrap();
+        write_atomic(
+            &ctx.workspaces_dir.join("default/broken.prompt"),
+            "not base64",
+        )
+        .unwrap();
+
+        assert!(stage(&ctx, &Aes256Gcm::generate_key(OsRng)).is_err());
+        finish_rotation(&ctx.key_path).unwrap();
+
+        assert_eq!(fs::read(&ctx.key_path).unwrap(), old_key);
+        assert_eq!(read_prompt(&ctx).unwrap(), b"hello");
+        assert!(!ctx.base_dir.join(ROTATION_DIR).exists());
+    }
+
+    #[test]
+    fn hardware_wrapping_is_read_from_the_key_file() {
+        use crate::core::hardware::{wrapping, HardwareKey};
+        assert_eq!(
+            wrapping(b"HWKY\x01\x02rest"),
+            Some((HardwareKey::YubiKey, 2))
+        );
+        assert_eq!(wrapping(b"HWKY\x02sealed"), Some((HardwareKey::Tpm, 0)));
+        assert_eq!(wrapping(&encode_plain_key(&[0u8; 32])), None);
+    }
+}
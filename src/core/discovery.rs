@@ -0,0 +1,130 @@
+//! Detection of locally served models (Ollama, llama.cpp) that can be used offline.
+
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+
+/// Default address of a local Ollama instance, overridable with `OLLAMA_HOST`.
+pub const DEFAULT_OLLAMA_HOST: &str = "http://localhost:11434";
+/// Default address of a local llama.cpp server serving GGUF models, overridable with `LLAMACPP_HOST`.
+pub const DEFAULT_LLAMACPP_HOST: &str = "http://localhost:8080";
+
+/// A model served locally, ready to be registered as a provider.
+pub struct LocalModel {
+    /// Provider name suggested for `config.toml`.
+    pub name: String,
+    pub backend: String,
+    pub model: String,
+    pub base_url: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTags {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAIModels {
+    #[serde(default)]
+    data: Vec<OpenAIModel>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIModel {
+    id: String,
+}
+
+/// Returns the Ollama host from `OLLAMA_HOST`, falling back to the default address.
+pub fn ollama_host() -> String {
+    host_from_env("OLLAMA_HOST", DEFAULT_OLLAMA_HOST)
+}
+
+/// Returns the llama.cpp server host from `LLAMACPP_HOST`, falling back to the default address.
+pub fn llamacpp_host() -> String {
+    host_from_env("LLAMACPP_HOST", DEFAULT_LLAMACPP_HOST)
+}
+
+fn host_from_env(var: &str, default: &str) -> String {
+    let host = env::var(var).unwrap_or_else(|_| default.to_string());
+    let host = host.trim_end_matches('/');
+    if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    } else {
+        format!("http://{}", host)
+    }
+}
+
+/// Lists the models pulled into a running Ollama instance.
+/// Returns `None` if nothing answers at `host`.
+pub async fn discover_ollama(host: &str) -> Option<Vec<LocalModel>> {
+    let body = get(&format!("{}/api/tags", host)).await?;
+    let tags: OllamaTags = serde_json::from_str(&body).ok()?;
+    Some(
+        tags.models
+            .into_iter()
+            .map(|m| LocalModel {
+                name: format!("ollama-{}", provider_slug(&m.name)),
+                backend: "ollama".to_string(),
+                model: m.name,
+                base_url: host.to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// Lists the GGUF models loaded by a running llama.cpp server through its OpenAI-compatible API.
+/// Returns `None` if nothing answers at `host`.
+pub async fn discover_llamacpp(host: &str) -> Option<Vec<LocalModel>> {
+    let body = get(&format!("{}/v1/models", host)).await?;
+    let models: OpenAIModels = serde_json::from_str(&body).ok()?;
+    Some(
+        models
+            .data
+            .into_iter()
+            .map(|m| {
+                let file_name = m.id.rsplit(['/', '\\']).next().unwrap_or(&m.id);
+                LocalModel {
+                    name: format!(
+                        "llamacpp-{}",
+                        provider_slug(file_name.trim_end_matches(".gguf"))
+                    ),
+                    backend: "openai".to_string(),
+                    model: m.id,
+                    base_url: format!("{}/v1/", host),
+                }
+            })
+            .collect(),
+    )
+}
+
+async fn get(url: &str) -> Option<String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .ok()?;
+    let resp = client.get(url).send().await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    resp.text().await.ok()
+}
+
+/// Turns a model name such as `llama3.1:latest` into a provider-name-friendly slug (`llama3-1`).
+fn provider_slug(model: &str) -> String {
+    let model = model.strip_suffix(":latest").unwrap_or(model);
+    let slug: String = model
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    slug.split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
@@ -0,0 +1,113 @@
+//! Encrypted vector index of local documents used by retrieval steps.
+
+use super::cipher::StoreCipher;
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// A chunk of a source document together with its embedding.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DocChunk {
+    pub source: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// The collection of embedded chunks stored in `docs/index.bin` of the store directory.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct DocIndex {
+    /// Name of the provider used to compute the embeddings.
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub chunks: Vec<DocChunk>,
+}
+
+/// Returns the path of the encrypted document index.
+pub fn index_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("docs").join("index.bin")
+}
+
+impl DocIndex {
+    /// Loads and decrypts the index, returning an empty index if none exists yet.
+    pub fn load(path: &Path, cipher: &StoreCipher) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let encoded = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
+        let plaintext = decrypt_from_base64(cipher, &encoded)?;
+        serde_json::from_slice(&plaintext).map_err(|_| "Invalid document index".to_string())
+    }
+
+    /// Encrypts and writes the index to disk.
+    pub fn save(&self, path: &Path, cipher: &StoreCipher) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            super::utils::ensure_dir(parent)?;
+        }
+        let json = Zeroizing::new(
+            serde_json::to_vec(self).map_err(|e| format!("Serialize error: {}", e))?,
+        );
+        let encoded = encrypt_to_base64(cipher, &json)?;
+        fs::write(path, encoded).map_err(|e| format!("Write error: {}", e))
+    }
+
+    /// Replaces every chunk coming from `source` with the given chunks.
+    pub fn replace_source(&mut self, source: &str, chunks: Vec<DocChunk>) {
+        self.chunks.retain(|c| c.source != source);
+        self.chunks.extend(chunks);
+    }
+
+    /// Returns the `top_k` chunks most similar to the query embedding.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<&DocChunk> {
+        let mut scored: Vec<(f32, &DocChunk)> = self
+            .chunks
+            .iter()
+            .map(|c| (cosine_similarity(query, &c.embedding), c))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(top_k).map(|(_, c)| c).collect()
+    }
+}
+
+/// Formats retrieved chunks into a single block of text suitable for a prompt variable.
+pub fn format_chunks(chunks: &[&DocChunk]) -> String {
+    chunks
+        .iter()
+        .map(|c| format!("[{}]\n{}", c.source, c.text))
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n")
+}
+
+/// Splits text into chunks of roughly `size` characters, breaking on whitespace.
+pub fn chunk_text(text: &str, size: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + word.len() + 1 > size {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
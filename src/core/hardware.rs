@@ -0,0 +1,196 @@
+//! Wrapping of the master key with a hardware device, so that the store only opens
+//! while the device is present.
+//!
+//! The devices are driven through their command-line tools: `ykchalresp` (from
+//! yubikey-personalization) for the HMAC-SHA1 challenge-response slot of a YubiKey, and
+//! `systemd-creds` for credentials sealed by the TPM.
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng},
+    Aes256Gcm, Nonce,
+};
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+use super::crypto::password_cipher;
+
+/// Key file wrapped by a hardware device.
+const MAGIC_HWKY: &[u8; 4] = b"HWKY";
+const KIND_YUBIKEY: u8 = 1;
+const KIND_TPM: u8 = 2;
+/// Name the TPM credential is sealed under, checked when unsealing it.
+const CREDENTIAL_NAME: &str = "prompt-store-key";
+
+/// A hardware device protecting the master key, selected by `[key] hardware` in
+/// `config.toml`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HardwareKey {
+    /// The HMAC-SHA1 challenge-response slot of a YubiKey.
+    YubiKey,
+    /// The TPM of this machine, through `systemd-creds`.
+    Tpm,
+}
+
+impl HardwareKey {
+    /// Name of the device, for messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardwareKey::YubiKey => "YubiKey",
+            HardwareKey::Tpm => "TPM",
+        }
+    }
+}
+
+/// Whether a key file holds a master key wrapped by a hardware device.
+pub fn is_hardware_wrapped(key_data: &[u8]) -> bool {
+    key_data.starts_with(MAGIC_HWKY)
+}
+
+/// The device, and the slot of a YubiKey, wrapping a key file written by [`wrap_key`].
+pub fn wrapping(key_data: &[u8]) -> Option<(HardwareKey, u8)> {
+    match key_data.strip_prefix(MAGIC_HWKY)? {
+        [KIND_YUBIKEY, slot, ..] => Some((HardwareKey::YubiKey, *slot)),
+        [KIND_TPM, ..] => Some((HardwareKey::Tpm, 0)),
+        _ => None,
+    }
+}
+
+/// Wraps the master key with a device. `yubikey_slot` is the challenge-response slot
+/// used by a YubiKey.
+pub fn wrap_key(key: &[u8], device: HardwareKey, yubikey_slot: u8) -> Result<Vec<u8>, String> {
+    let mut out = Vec::from(&MAGIC_HWKY[..]);
+    match device {
+        HardwareKey::YubiKey => {
+            let mut challenge = [0u8; 32];
+            OsRng.fill_bytes(&mut challenge);
+            let cipher = yubikey_cipher(yubikey_slot, &challenge)?;
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let cipher_bytes = cipher
+                .encrypt(&nonce, key)
+                .map_err(|_| "Encrypt error".to_string())?;
+            out.extend_from_slice(&[KIND_YUBIKEY, yubikey_slot]);
+            out.extend_from_slice(&challenge);
+            out.extend_from_slice(&nonce);
+            out.extend_from_slice(&cipher_bytes);
+        }
+        HardwareKey::Tpm => {
+            let sealed = run_tool(
+                device,
+                "systemd-creds",
+                &[
+                    "encrypt",
+                    "--with-key=tpm2",
+                    &format!("--name={}", CREDENTIAL_NAME),
+                    "-",
+                    "-",
+                ],
+                key,
+            )?;
+            out.push(KIND_TPM);
+            out.extend_from_slice(&sealed);
+        }
+    }
+    Ok(out)
+}
+
+/// Unwraps a master key wrapped by [`wrap_key`], which needs the device to be present.
+pub fn unwrap_key(key_data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    unwrap_with_device(key_data).map_err(|e| {
+        format!(
+            "{} Without the device, rebuild the master key from its shares with 'prompt-store key recover'.",
+            e
+        )
+    })
+}
+
+fn unwrap_with_device(key_data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let corrupted = || "Corrupted hardware-wrapped key".to_string();
+    let rest = key_data.strip_prefix(MAGIC_HWKY).ok_or_else(corrupted)?;
+    let key = Zeroizing::new(match rest.split_first() {
+        Some((&KIND_YUBIKEY, rest)) => {
+            if rest.len() < 1 + 32 + 12 {
+                return Err(corrupted());
+            }
+            let (slot, challenge) = (rest[0], &rest[1..33]);
+            let nonce = Nonce::from_slice(&rest[33..45]);
+            yubikey_cipher(slot, challenge)?
+                .decrypt(nonce, &rest[45..])
+                .map_err(|_| {
+                    format!(
+                        "The YubiKey in slot {} is not the one protecting the master key.",
+                        slot
+                    )
+                })?
+        }
+        Some((&KIND_TPM, sealed)) => run_tool(
+            HardwareKey::Tpm,
+            "systemd-creds",
+            &["decrypt", &format!("--name={}", CREDENTIAL_NAME), "-", "-"],
+            sealed,
+        )?,
+        _ => return Err(corrupted()),
+    });
+    if key.len() != 32 {
+        return Err("Corrupted key".to_string());
+    }
+    Ok(key)
+}
+
+/// Derives a cipher from the response of a YubiKey to a challenge.
+fn yubikey_cipher(slot: u8, challenge: &[u8]) -> Result<Aes256Gcm, String> {
+    let challenge_hex: String = challenge.iter().map(|b| format!("{:02x}", b)).collect();
+    let response = run_tool(
+        HardwareKey::YubiKey,
+        "ykchalresp",
+        &[&format!("-{}", slot), "-x", &challenge_hex],
+        &[],
+    )?;
+    let response = String::from_utf8_lossy(&response).trim().to_string();
+    password_cipher(&response, &challenge[..16])
+}
+
+/// Runs a device tool with `input` on stdin and returns its stdout.
+fn run_tool(
+    device: HardwareKey,
+    program: &str,
+    args: &[&str],
+    input: &[u8],
+) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Could not reach the {}: '{}' could not be started ({}).",
+                device.name(),
+                program,
+                e
+            )
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .map_err(|e| format!("Failed to write stdin of '{}': {}", program, e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run '{}': {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => format!("The {} did not respond, is it present?", device.name()),
+            stderr => format!(
+                "The {} did not respond, is it present? ({})",
+                device.name(),
+                stderr
+            ),
+        });
+    }
+    Ok(output.stdout)
+}
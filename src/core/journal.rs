@@ -0,0 +1,165 @@
+//! Journal of the last destructive operations (delete, step removal, tag removal,
+//! rename, import), so `prompt-store undo` can put their files back.
+//!
+//! An entry keeps the store files an operation changes as they were before it, still
+//! encrypted as stored. The journal itself is encrypted in `journal.bin`; it is left out
+//! of backups and cleared by key rotation, as its files are only readable with the
+//! current key.
+
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::storage::{write_atomic, AppCtx};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// File of the journal in the store directory.
+pub const JOURNAL_FILE: &str = "journal.bin";
+
+/// Number of operations kept; `undo` can go back this many times.
+const MAX_ENTRIES: usize = 10;
+
+/// An undoable operation, with the files it changed as they were before it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JournalEntry {
+    /// What the operation did, e.g. `delete my-prompt`.
+    pub action: String,
+    pub at: DateTime<Utc>,
+    saved: Vec<SavedPath>,
+}
+
+/// A file or directory, relative to the store directory, and the files it held.
+/// No files means it did not exist.
+#[derive(Serialize, Deserialize, Debug)]
+struct SavedPath {
+    path: String,
+    /// `(path, base64 content)` of each file under `path`, or of `path` itself.
+    files: Vec<(String, String)>,
+}
+
+impl JournalEntry {
+    /// Starts the entry of an operation, to [`save`](Self::save) the paths it is about
+    /// to change, then [`commit`](Self::commit) once done.
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            action: action.into(),
+            at: Utc::now(),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Saves a file or directory of the store as it is now, present or not. A path
+    /// saved already keeps its first state.
+    pub fn save(&mut self, ctx: &AppCtx, path: &Path) -> Result<(), String> {
+        let rel = relative(ctx, path)?;
+        if self.saved.iter().any(|s| s.path == rel) {
+            return Ok(());
+        }
+        let mut files = Vec::new();
+        collect(ctx, path, &mut files)?;
+        self.saved.push(SavedPath { path: rel, files });
+        Ok(())
+    }
+
+    /// Records the operation as the last one `undo` reverts, dropping the oldest entries
+    /// beyond the limit.
+    pub fn commit(self, ctx: &AppCtx) -> Result<(), String> {
+        if self.saved.is_empty() {
+            return Ok(());
+        }
+        let mut entries = load(ctx)?;
+        entries.push(self);
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..excess);
+        store(ctx, &entries)
+    }
+
+    /// Puts the saved paths back as they were: paths that did not exist are removed.
+    fn restore(&self, ctx: &AppCtx) -> Result<(), String> {
+        // Later saves may be nested in earlier ones, whose state must prevail.
+        for saved in self.saved.iter().rev() {
+            let path = ctx.base_dir.join(&saved.path);
+            if path.is_dir() {
+                fs::remove_dir_all(&path).map_err(|e| format!("Delete error: {}", e))?;
+            } else if path.exists() {
+                fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+            }
+            for (file, data) in &saved.files {
+                let data = general_purpose::STANDARD
+                    .decode(data)
+                    .map_err(|e| format!("Corrupted journal: {}", e))?;
+                write_atomic(&ctx.base_dir.join(file), data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The last recorded operation, if any.
+pub fn last(ctx: &AppCtx) -> Result<Option<JournalEntry>, String> {
+    Ok(load(ctx)?.pop())
+}
+
+/// Reverts the last recorded operation and removes it from the journal. Returns it, or
+/// `None` when there is nothing to undo.
+pub fn undo(ctx: &AppCtx) -> Result<Option<JournalEntry>, String> {
+    let mut entries = load(ctx)?;
+    let Some(entry) = entries.pop() else {
+        return Ok(None);
+    };
+    entry.restore(ctx)?;
+    store(ctx, &entries)?;
+    Ok(Some(entry))
+}
+
+/// Empties the journal, whose files would no longer decrypt after a key change.
+pub fn clear(base_dir: &Path) -> Result<(), String> {
+    let path = base_dir.join(JOURNAL_FILE);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+    }
+    Ok(())
+}
+
+fn load(ctx: &AppCtx) -> Result<Vec<JournalEntry>, String> {
+    let path = ctx.base_dir.join(JOURNAL_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let json = decrypt_from_base64(&ctx.cipher, &encoded)?;
+    serde_json::from_slice(&json).map_err(|e| format!("Corrupted journal: {}", e))
+}
+
+fn store(ctx: &AppCtx, entries: &[JournalEntry]) -> Result<(), String> {
+    if entries.is_empty() {
+        return clear(&ctx.base_dir);
+    }
+    let json =
+        Zeroizing::new(serde_json::to_vec(entries).map_err(|e| format!("Serialize error: {}", e))?);
+    write_atomic(
+        &ctx.base_dir.join(JOURNAL_FILE),
+        encrypt_to_base64(&ctx.cipher, &json)?,
+    )
+}
+
+fn relative(ctx: &AppCtx, path: &Path) -> Result<String, String> {
+    path.strip_prefix(&ctx.base_dir)
+        .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+        .map_err(|_| format!("{} is outside the store", path.display()))
+}
+
+fn collect(ctx: &AppCtx, path: &Path, files: &mut Vec<(String, String)>) -> Result<(), String> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).map_err(|e| format!("Read dir error: {}", e))? {
+            let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+            collect(ctx, &entry.path(), files)?;
+        }
+    } else if path.is_file() {
+        let data = fs::read(path).map_err(|e| format!("Read error: {}", e))?;
+        files.push((relative(ctx, path)?, general_purpose::STANDARD.encode(data)));
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! In-process metrics of the long-running commands (`serve`, `worker`), rendered in the
+//! Prometheus text format by their `/metrics` endpoint. Nothing is sent anywhere: the
+//! counters only live in memory until scraped.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the provider latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    runs: BTreeMap::new(),
+    providers: BTreeMap::new(),
+});
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+struct Metrics {
+    /// `(total, failed)` runs per kind, e.g. `worker` or `chain`.
+    runs: BTreeMap<String, (u64, u64)>,
+    providers: BTreeMap<String, ProviderStats>,
+}
+
+#[derive(Default)]
+struct ProviderStats {
+    requests: u64,
+    failures: u64,
+    /// Requests per latency bucket, the last one counting those above every bound.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum: f64,
+}
+
+/// Counts a run of the given kind (`worker`, `prompt`, `chain`), and its failure.
+pub fn record_run(kind: &str, ok: bool) {
+    let mut metrics = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    let (total, failed) = metrics.runs.entry(kind.to_string()).or_default();
+    *total += 1;
+    if !ok {
+        *failed += 1;
+    }
+}
+
+/// A run in progress, counted by [`in_flight`] until dropped.
+pub struct InFlight(());
+
+/// Counts a run as in progress until the returned guard is dropped.
+pub fn start_run() -> InFlight {
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    InFlight(())
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Number of runs in progress, which a shutdown waits for.
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+/// Counts a request to a provider with its latency, and its failure.
+pub fn record_request(provider: &str, latency: Duration, ok: bool) {
+    let mut metrics = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = metrics.providers.entry(provider.to_string()).or_default();
+    let seconds = latency.as_secs_f64();
+    stats.requests += 1;
+    if !ok {
+        stats.failures += 1;
+    }
+    let bucket = LATENCY_BUCKETS
+        .iter()
+        .position(|bound| seconds <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len());
+    stats.buckets[bucket] += 1;
+    stats.latency_sum += seconds;
+}
+
+/// Renders the metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let metrics = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut out = String::new();
+
+    out.push_str("# HELP prompt_store_runs_total Runs handled, by kind.\n");
+    out.push_str("# TYPE prompt_store_runs_total counter\n");
+    for (kind, (total, _)) in &metrics.runs {
+        let _ = writeln!(
+            out,
+            "prompt_store_runs_total{{kind=\"{}\"}} {}",
+            escape(kind),
+            total
+        );
+    }
+    out.push_str("# HELP prompt_store_run_failures_total Runs that failed, by kind.\n");
+    out.push_str("# TYPE prompt_store_run_failures_total counter\n");
+    for (kind, (_, failed)) in &metrics.runs {
+        let _ = writeln!(
+            out,
+            "prompt_store_run_failures_total{{kind=\"{}\"}} {}",
+            escape(kind),
+            failed
+        );
+    }
+
+    out.push_str("# HELP prompt_store_runs_in_flight Runs in progress.\n");
+    out.push_str("# TYPE prompt_store_runs_in_flight gauge\n");
+    let _ = writeln!(out, "prompt_store_runs_in_flight {}", in_flight());
+
+    out.push_str("# HELP prompt_store_provider_requests_total Requests sent to each provider.\n");
+    out.push_str("# TYPE prompt_store_provider_requests_total counter\n");
+    for (provider, stats) in &metrics.providers {
+        let _ = writeln!(
+            out,
+            "prompt_store_provider_requests_total{{provider=\"{}\"}} {}",
+            escape(provider),
+            stats.requests
+        );
+    }
+    out.push_str("# HELP prompt_store_provider_failures_total Failed requests to each provider.\n");
+    out.push_str("# TYPE prompt_store_provider_failures_total counter\n");
+    for (provider, stats) in &metrics.providers {
+        let _ = writeln!(
+            out,
+            "prompt_store_provider_failures_total{{provider=\"{}\"}} {}",
+            escape(provider),
+            stats.failures
+        );
+    }
+
+    out.push_str(
+        "# HELP prompt_store_provider_latency_seconds Latency of the requests to each provider.\n",
+    );
+    out.push_str("# TYPE prompt_store_provider_latency_seconds histogram\n");
+    for (provider, stats) in &metrics.providers {
+        let provider = escape(provider);
+        let mut count = 0;
+        for (bound, requests) in LATENCY_BUCKETS.iter().zip(&stats.buckets) {
+            count += requests;
+            let _ = writeln!(
+                out,
+                "prompt_store_provider_latency_seconds_bucket{{provider=\"{}\",le=\"{}\"}} {}",
+                provider, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "prompt_store_provider_latency_seconds_bucket{{provider=\"{}\",le=\"+Inf\"}} {}",
+            provider, stats.requests
+        );
+        let _ = writeln!(
+            out,
+            "prompt_store_provider_latency_seconds_sum{{provider=\"{}\"}} {}",
+            provider, stats.latency_sum
+        );
+        let _ = writeln!(
+            out,
+            "prompt_store_provider_latency_seconds_count{{provider=\"{}\"}} {}",
+            provider, stats.requests
+        );
+    }
+    out
+}
+
+/// Escapes a label value: backslashes, double quotes and line feeds.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,192 @@
+//! Upgrades of store files written in older formats, applied by `prompt-store migrate`.
+//!
+//! Each format change adds a [`Migration`] to [`MIGRATIONS`]. Migrations run in order
+//! on every file of the store, each one seeing the output of the previous ones, so a
+//! file written several versions ago is upgraded step by step. Readers keep accepting
+//! older formats; migrating only brings the files to the current one.
+
+use super::backup::{is_encrypted, store_files, KEY_FILE};
+use super::cipher::{format_version, FORMAT_VERSION};
+use super::crypto::{
+    decrypt_from_base64, encode_plain_key, encrypt_to_base64, key_format_version, plain_key,
+    KEY_FORMAT_VERSION,
+};
+use super::storage::{write_atomic, AppCtx};
+use zeroize::Zeroizing;
+
+/// Returns the upgraded content of a file, or `None` if it needs no upgrade.
+type UpgradeFn = fn(&AppCtx, &str, &[u8]) -> Result<Option<Vec<u8>>, String>;
+
+/// An upgrade of the files of one kind to a newer format.
+pub struct Migration {
+    pub name: &'static str,
+    pub description: &'static str,
+    upgrade: UpgradeFn,
+}
+
+/// Every migration, oldest first.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "encrypted-files",
+        description: "Encrypted files get a versioned header, their own data key and compression",
+        upgrade: upgrade_encrypted_file,
+    },
+    Migration {
+        name: "key-file",
+        description: "An unprotected key.bin gets a versioned header",
+        upgrade: upgrade_key_file,
+    },
+];
+
+/// A file of the store to upgrade.
+pub struct Upgrade {
+    /// Path relative to the store directory.
+    pub path: String,
+    /// Names of the migrations applied to it.
+    pub migrations: Vec<&'static str>,
+    data: Zeroizing<Vec<u8>>,
+}
+
+/// Runs every migration on the files of the store, without writing anything. Returns
+/// the files that changed.
+pub fn plan(ctx: &AppCtx) -> Result<Vec<Upgrade>, String> {
+    let mut upgrades = Vec::new();
+    for file in store_files(ctx)? {
+        let mut data = Zeroizing::new(file.data);
+        let mut migrations = Vec::new();
+        for migration in MIGRATIONS {
+            if let Some(upgraded) = (migration.upgrade)(ctx, &file.path, &data)
+                .map_err(|e| format!("{}: {}", file.path, e))?
+            {
+                data = Zeroizing::new(upgraded);
+                migrations.push(migration.name);
+            }
+        }
+        if !migrations.is_empty() {
+            upgrades.push(Upgrade {
+                path: file.path,
+                migrations,
+                data,
+            });
+        }
+    }
+    // The key goes last: if a write fails, the files left behind still open with it.
+    upgrades.sort_by_key(|u| u.path == KEY_FILE);
+    Ok(upgrades)
+}
+
+/// Writes the upgraded files in place.
+pub fn apply(ctx: &AppCtx, upgrades: &[Upgrade]) -> Result<(), String> {
+    for upgrade in upgrades {
+        write_atomic(&ctx.base_dir.join(&upgrade.path), &upgrade.data)?;
+    }
+    Ok(())
+}
+
+fn upgrade_encrypted_file(
+    ctx: &AppCtx,
+    path: &str,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, String> {
+    if !is_encrypted(path) || format_version(data)? >= FORMAT_VERSION {
+        return Ok(None);
+    }
+    let encoded = std::str::from_utf8(data).map_err(|_| "Corrupted data".to_string())?;
+    let plaintext = decrypt_from_base64(&ctx.cipher, encoded)?;
+    Ok(Some(
+        encrypt_to_base64(&ctx.cipher, &plaintext)?.into_bytes(),
+    ))
+}
+
+fn upgrade_key_file(_ctx: &AppCtx, path: &str, data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    if path != KEY_FILE || key_format_version(data) >= KEY_FORMAT_VERSION {
+        return Ok(None);
+    }
+    Ok(Some(encode_plain_key(&plain_key(data)?).to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use std::fs;
+
+    const PROMPT: &str = "workspaces/default/hello.prompt";
+
+    /// Writes the prompt and the key in the formats of the first versions: plain base64
+    /// of the AES-256-GCM ciphertext, and the raw master key.
+    fn legacy_store() -> (tempfile::TempDir, AppCtx, Vec<u8>) {
+        let (dir, ctx) = AppCtx::temp();
+        let key = plain_key(&fs::read(&ctx.key_path).unwrap())
+            .unwrap()
+            .to_vec();
+        let sealed = ctx.cipher.encrypt(b"hello").unwrap();
+        write_atomic(
+            &ctx.base_dir.join(PROMPT),
+            general_purpose::STANDARD.encode(sealed),
+        )
+        .unwrap();
+        write_atomic(&ctx.key_path, &key).unwrap();
+        (dir, ctx, key)
+    }
+
+    #[test]
+    fn legacy_files_are_upgraded_and_still_open() {
+        let (_dir, ctx, key) = legacy_store();
+        let upgrades = plan(&ctx).unwrap();
+        let paths: Vec<&str> = upgrades.iter().map(|u| u.path.as_str()).collect();
+        assert_eq!(paths, [PROMPT, KEY_FILE]);
+        apply(&ctx, &upgrades).unwrap();
+
+        let prompt = fs::read(ctx.base_dir.join(PROMPT)).unwrap();
+        assert_eq!(format_version(&prompt).unwrap(), FORMAT_VERSION);
+        let encoded = std::str::from_utf8(&prompt).unwrap();
+        assert_eq!(
+            *decrypt_from_base64(&ctx.cipher, encoded).unwrap(),
+            b"hello"
+        );
+        let key_file = fs::read(&ctx.key_path).unwrap();
+        assert_eq!(key_format_version(&key_file), KEY_FORMAT_VERSION);
+        assert_eq!(*plain_key(&key_file).unwrap(), key);
+
+        assert!(plan(&ctx).unwrap().is_empty());
+    }
+
+    #[test]
+    fn planning_writes_nothing() {
+        let (_dir, ctx, key) = legacy_store();
+        let prompt = fs::read(ctx.base_dir.join(PROMPT)).unwrap();
+        assert_eq!(plan(&ctx).unwrap().len(), 2);
+        assert_eq!(fs::read(ctx.base_dir.join(PROMPT)).unwrap(), prompt);
+        assert_eq!(fs::read(&ctx.key_path).unwrap(), key);
+    }
+
+    #[test]
+    fn files_of_newer_versions_are_refused() {
+        let (_dir, ctx) = AppCtx::temp();
+        let current = encrypt_to_base64(&ctx.cipher, b"hello").unwrap();
+        let newer = current.replacen(
+            &format!("$ps{}$", FORMAT_VERSION),
+            &format!("$ps{}$", FORMAT_VERSION + 1),
+            1,
+        );
+        assert_ne!(newer, current);
+        write_atomic(&ctx.base_dir.join(PROMPT), &newer).unwrap();
+
+        let err = plan(&ctx).err().unwrap();
+        assert!(err.starts_with(PROMPT), "{}", err);
+        assert!(err.contains("newer version"), "{}", err);
+    }
+
+    #[test]
+    fn corrupted_legacy_files_are_refused() {
+        let (_dir, ctx) = AppCtx::temp();
+        write_atomic(&ctx.base_dir.join(PROMPT), "bm90IGVuY3J5cHRlZA==").unwrap();
+        assert!(plan(&ctx).is_err());
+
+        let (_dir, ctx) = AppCtx::temp();
+        write_atomic(&ctx.key_path, [0u8; 16]).unwrap();
+        let err = plan(&ctx).err().unwrap();
+        assert!(err.contains("Invalid key length"), "{}", err);
+    }
+}
@@ -1,4 +1,43 @@
+pub mod audit;
+#[cfg(feature = "cli")]
+pub mod backup;
+#[cfg(feature = "cli")]
+pub mod batch_jobs;
+pub mod bundle;
+pub mod cipher;
 pub mod config;
+pub mod content_index;
 pub mod crypto;
+pub mod discovery;
+pub mod docs;
+pub mod hardware;
+#[cfg(feature = "cli")]
+pub mod journal;
+pub mod logging;
+pub mod metrics;
+#[cfg(feature = "cli")]
+pub mod migrate;
+pub mod paths;
+pub mod pii;
+pub mod policy;
+pub mod profiles;
+#[cfg(feature = "cli")]
+pub mod queue;
+pub mod registry;
+pub mod releases;
+pub mod retry;
+pub mod runs;
+pub mod scan;
+pub mod secrets;
+pub mod shamir;
+pub mod share;
+pub mod shell;
+#[cfg(feature = "cli")]
+pub mod signing;
 pub mod storage;
-pub mod utils;
\ No newline at end of file
+pub mod template;
+pub mod tokens;
+pub mod usage;
+pub mod utils;
+pub mod vars;
+pub mod web;
\ No newline at end of file
@@ -0,0 +1,147 @@
+//! Platform-specific locations of the store.
+//!
+//! On Linux and other XDG platforms, data (keys, workspaces, packs) lives in
+//! `$XDG_DATA_HOME/prompt-store` and `config.toml` in `$XDG_CONFIG_HOME/prompt-store`.
+//! On macOS and Windows both stay in `~/.prompt-store`.
+//!
+//! A legacy `~/.prompt-store` found on an XDG platform is moved once, and replaced by a
+//! symlink to the new data directory so scripts using the old paths keep working.
+
+use std::path::PathBuf;
+
+const APP_DIR: &str = "prompt-store";
+
+/// Returns the directory holding the keys, workspaces and other store data.
+pub fn store_dir() -> Result<PathBuf, String> {
+    Ok(locations()?.0)
+}
+
+/// Returns the path of `config.toml`.
+pub fn config_file() -> Result<PathBuf, String> {
+    Ok(locations()?.1.join("config.toml"))
+}
+
+/// Returns the path of the `policy.toml` set by an admin, next to `config.toml`.
+pub fn policy_file() -> Result<PathBuf, String> {
+    Ok(locations()?.1.join("policy.toml"))
+}
+
+/// Returns the pre-XDG store directory, `~/.prompt-store`.
+///
+/// The home directory comes from `HOME` on Unix and from the user profile
+/// (`USERPROFILE`) on Windows.
+fn legacy_dir() -> Result<PathBuf, String> {
+    dirs::home_dir()
+        .map(|home| home.join(".prompt-store"))
+        .ok_or_else(|| "Unable to determine the home directory".to_string())
+}
+
+/// Returns the data and config directories.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn locations() -> Result<(PathBuf, PathBuf), String> {
+    let data = dirs::data_dir()
+        .ok_or_else(|| "Unable to determine the data directory".to_string())?
+        .join(APP_DIR);
+    let config = dirs::config_dir()
+        .ok_or_else(|| "Unable to determine the config directory".to_string())?
+        .join(APP_DIR);
+    migrate_legacy(&legacy_dir()?, &data, &config)?;
+    Ok((data, config))
+}
+
+/// Returns the data and config directories.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn locations() -> Result<(PathBuf, PathBuf), String> {
+    let legacy = legacy_dir()?;
+    Ok((legacy.clone(), legacy))
+}
+
+/// Moves a legacy store to the XDG data directory and its `config.toml` to the config
+/// directory, leaving symlinks behind. Does nothing once the data directory exists.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn migrate_legacy(
+    legacy: &std::path::Path,
+    data: &std::path::Path,
+    config: &std::path::Path,
+) -> Result<(), String> {
+    use super::utils::ensure_dir;
+    #[cfg(feature = "cli")]
+    use crate::ui::symbols::BULLET;
+    #[cfg(feature = "cli")]
+    use console::style;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    // After migration the legacy path is a symlink, not a directory.
+    let is_legacy_dir = fs::symlink_metadata(legacy)
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+    if !is_legacy_dir || data.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = data.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Unable to create {}: {}", parent.display(), e))?;
+    }
+    if fs::rename(legacy, data).is_err() {
+        // Different filesystems: copy next to the data directory, then move the copy
+        // into place, so a failed copy never leaves a partial data directory.
+        let mut partial = data.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = std::path::PathBuf::from(partial);
+        if partial.exists() {
+            fs::remove_dir_all(&partial)
+                .map_err(|e| format!("Unable to remove {}: {}", partial.display(), e))?;
+        }
+        if let Err(e) = copy_dir(legacy, &partial) {
+            fs::remove_dir_all(&partial).ok();
+            return Err(e);
+        }
+        fs::rename(&partial, data)
+            .map_err(|e| format!("Unable to move {}: {}", partial.display(), e))?;
+        fs::remove_dir_all(legacy)
+            .map_err(|e| format!("Unable to remove {}: {}", legacy.display(), e))?;
+    }
+
+    let old_config = data.join("config.toml");
+    if old_config.is_file() {
+        ensure_dir(config)?;
+        let new_config = config.join("config.toml");
+        fs::copy(&old_config, &new_config)
+            .and_then(|_| fs::remove_file(&old_config))
+            .map_err(|e| format!("Unable to move config.toml: {}", e))?;
+        symlink(&new_config, &old_config).ok();
+    }
+    symlink(data, legacy).ok();
+
+    #[cfg(feature = "cli")]
+    let bullet = style(BULLET).yellow().to_string();
+    #[cfg(not(feature = "cli"))]
+    let bullet = "-";
+    eprintln!(
+        "{} Moved {} to {} (config in {})",
+        bullet,
+        legacy.display(),
+        data.display(),
+        config.display()
+    );
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn copy_dir(from: &std::path::Path, to: &std::path::Path) -> Result<(), String> {
+    use std::fs;
+
+    fs::create_dir_all(to).map_err(|e| format!("Unable to create {}: {}", to.display(), e))?;
+    for entry in fs::read_dir(from).map_err(|e| format!("Read dir error: {}", e))? {
+        let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target).map_err(|e| format!("Copy error: {}", e))?;
+        }
+    }
+    Ok(())
+}
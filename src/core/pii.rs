@@ -0,0 +1,148 @@
+//! Best-effort detection and redaction of personal data (emails, phone numbers, card
+//! numbers and custom patterns) before text is sent to external providers.
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Which kinds of personal data `redact` replaces.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionPolicy {
+    /// Replace email addresses with `[EMAIL]`.
+    #[serde(default = "enabled")]
+    pub emails: bool,
+    /// Replace phone numbers with `[PHONE]`.
+    #[serde(default = "enabled")]
+    pub phone_numbers: bool,
+    /// Replace payment card numbers (checked with the Luhn algorithm) with `[CARD]`.
+    #[serde(default = "enabled")]
+    pub credit_cards: bool,
+    /// Regular expressions whose matches are replaced with `[REDACTED]`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            emails: true,
+            phone_numbers: true,
+            credit_cards: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// A policy redacting emails, phone numbers and card numbers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also redacts the matches of a regular expression.
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Checks that the custom patterns are valid regular expressions.
+    pub fn validate(&self) -> Result<(), String> {
+        self.custom_patterns().map(|_| ())
+    }
+
+    fn custom_patterns(&self) -> Result<Vec<Regex>, String> {
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| format!("Invalid redaction pattern '{}': {}", pattern, e))
+            })
+            .collect()
+    }
+}
+
+const EMAIL: &str = r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}\b";
+const CARD: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+const PHONE: &str =
+    r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{1,4}\)[\s.-]?)?\d{1,4}(?:[\s.-]\d{2,4}){1,5}|\+\d{8,15}\b";
+const ISO_DATE: &str = r"^\d{4}-\d{2}-\d{2}$";
+
+/// Replaces the personal data selected by the policy. Card numbers are redacted before
+/// phone numbers so that their digit groups are not mistaken for one another.
+pub fn redact(text: &str, policy: &RedactionPolicy) -> Result<String, String> {
+    let mut text = text.to_string();
+    for re in policy.custom_patterns()? {
+        text = re.replace_all(&text, "[REDACTED]").into_owned();
+    }
+    if policy.emails {
+        text = Regex::new(EMAIL)
+            .unwrap()
+            .replace_all(&text, "[EMAIL]")
+            .into_owned();
+    }
+    if policy.credit_cards {
+        text = replace_checked(&text, CARD, "[CARD]", |m| luhn(&digits(m)));
+    }
+    if policy.phone_numbers {
+        let date = Regex::new(ISO_DATE).unwrap();
+        text = replace_checked(&text, PHONE, "[PHONE]", |m| {
+            (8..=15).contains(&digits(m).len()) && !date.is_match(m)
+        });
+    }
+    Ok(text)
+}
+
+/// Redacts every value of a set of variables.
+pub fn redact_values(
+    vars: &mut HashMap<String, String>,
+    policy: &RedactionPolicy,
+) -> Result<(), String> {
+    for value in vars.values_mut() {
+        *value = redact(value, policy)?;
+    }
+    Ok(())
+}
+
+/// Replaces the matches of `pattern` accepted by `check`.
+fn replace_checked(
+    text: &str,
+    pattern: &str,
+    placeholder: &str,
+    check: impl Fn(&str) -> bool,
+) -> String {
+    Regex::new(pattern)
+        .unwrap()
+        .replace_all(text, |caps: &Captures| {
+            if check(&caps[0]) {
+                placeholder.to_string()
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn digits(text: &str) -> String {
+    text.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Whether a number passes the Luhn checksum used by payment cards.
+fn luhn(number: &str) -> bool {
+    let sum: u32 = number
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    !number.is_empty() && sum.is_multiple_of(10)
+}
@@ -0,0 +1,411 @@
+//! Organization policies, enforced by the CLI and the library whatever `config.toml`
+//! says.
+//!
+//! An admin sets one in `policy.toml` next to `config.toml` (see [`policy_file`]), and a
+//! pack can ship one (at the root of a git pack, or in the `policy` of a pack file),
+//! installed in its workspace and in force while the pack is deployed. Every policy
+//! applies: a pack can add restrictions but never lift those of another policy.
+//!
+//! ```toml
+//! banned_providers = ["deepseek"]       # provider names of config.toml, or backends
+//! require_redaction = true              # redact run variables, as [redaction] inputs = true
+//! max_temperature = 0.5                 # providers without a temperature get this one
+//! required_tags = ["owner"]             # tags every saved prompt needs (chain steps excepted)
+//! allowed_url_hosts = ["wiki.internal"] # hosts URLs in prompts may point to, with subdomains
+//! ```
+//!
+//! Saving a prompt is refused when it removes a required tag or adds a URL to another
+//! host, and running one when its content has such a URL, so prompts saved before a
+//! policy keep working until they are edited. Fetch steps only request allowed hosts.
+
+use super::paths::{policy_file, store_dir};
+use super::storage::{parse_id, PromptData};
+use llm::async_trait;
+use llm::chat::{ChatMessage, ChatProvider, ChatResponse, Tool};
+use llm::completion::{CompletionProvider, CompletionRequest, CompletionResponse};
+use llm::embedding::EmbeddingProvider;
+use llm::error::LLMError;
+use llm::models::{ModelListRequest, ModelListResponse, ModelsProvider};
+use llm::stt::SpeechToTextProvider;
+use llm::tts::TextToSpeechProvider;
+use llm::LLMProvider;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Name of the policy file, in the config directory or at the root of a pack.
+pub const POLICY_FILE: &str = "policy.toml";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Policy {
+    #[serde(default)]
+    banned_providers: Vec<String>,
+    #[serde(default)]
+    require_redaction: bool,
+    max_temperature: Option<f32>,
+    #[serde(default)]
+    required_tags: Vec<String>,
+    /// Hosts URLs in prompts may point to; any host when `None`, none when empty.
+    allowed_url_hosts: Option<Vec<String>>,
+}
+
+/// The policies in force, each with where it comes from.
+#[derive(Debug, Default)]
+pub struct Policies {
+    policies: Vec<(String, Policy)>,
+}
+
+/// Loads the admin policy and those of the packs deployed in the store.
+pub fn load_policies() -> Result<Policies, String> {
+    let mut policies = Vec::new();
+    let admin = policy_file()?;
+    if admin.exists() {
+        policies.push((POLICY_FILE.to_string(), read_policy(&admin)?));
+    }
+    // Deploying a pack installs its policy in the pack's workspace, whether it comes
+    // from a git clone or a registry.
+    if let Ok(entries) = fs::read_dir(store_dir()?.join("workspaces")) {
+        let mut packs: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|dir| dir.join(POLICY_FILE).is_file())
+            .collect();
+        packs.sort();
+        for dir in packs {
+            let pack = dir.file_name().unwrap_or_default().to_string_lossy();
+            let origin = format!("{} of pack '{}'", POLICY_FILE, pack);
+            policies.push((origin, read_policy(&dir.join(POLICY_FILE))?));
+        }
+    }
+    Ok(Policies { policies })
+}
+
+impl Policies {
+    /// Whether no policy is in force.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Checks that a provider of `config.toml` (or `backend:model` for `name`) may be
+    /// used with its temperature.
+    pub fn check_provider(
+        &self,
+        name: &str,
+        backend: &str,
+        temperature: Option<f32>,
+    ) -> Result<(), String> {
+        for (origin, policy) in &self.policies {
+            if let Some(banned) = policy.banned_providers.iter().find(|banned| {
+                banned.eq_ignore_ascii_case(name) || banned.eq_ignore_ascii_case(backend)
+            }) {
+                return Err(violation(
+                    origin,
+                    format!("provider '{}' is banned ('{}')", name, banned),
+                ));
+            }
+            if let (Some(max), Some(temperature)) = (policy.max_temperature, temperature) {
+                if temperature > max {
+                    return Err(violation(
+                        origin,
+                        format!(
+                            "provider '{}' has temperature {}, above the maximum of {}",
+                            name, temperature, max
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The lowest maximum temperature of the policies, given to providers without one.
+    pub fn max_temperature(&self) -> Option<f32> {
+        self.policies
+            .iter()
+            .filter_map(|(_, policy)| policy.max_temperature)
+            .reduce(f32::min)
+    }
+
+    /// Whether run variables must be redacted.
+    pub fn requires_redaction(&self) -> bool {
+        self.policies
+            .iter()
+            .any(|(_, policy)| policy.require_redaction)
+    }
+
+    /// Checks a prompt about to be saved over `previous`: it must keep the required tags
+    /// it had (a new prompt needs them all) and add no URL to a host that isn't allowed.
+    pub fn check_save(
+        &self,
+        full_id: &str,
+        pd: &PromptData,
+        previous: Option<&PromptData>,
+    ) -> Result<(), String> {
+        let is_step = parse_id(full_id).1.contains('/');
+        let had_tag = |tag: &str| previous.is_none_or(|previous| has_tag(previous, tag));
+        let known_urls: BTreeSet<&str> = previous.map(urls_of).unwrap_or_default();
+        for (origin, policy) in &self.policies {
+            let missing: Vec<&str> = policy
+                .required_tags
+                .iter()
+                .map(String::as_str)
+                .filter(|tag| !is_step && !has_tag(pd, tag) && had_tag(tag))
+                .collect();
+            if !missing.is_empty() {
+                return Err(violation(
+                    origin,
+                    format!("prompt '{}' must be tagged {}", full_id, missing.join(", ")),
+                ));
+            }
+            if let Some(url) = urls_of(pd)
+                .into_iter()
+                .find(|url| !known_urls.contains(url) && !policy.allows_url(url))
+            {
+                return Err(violation(
+                    origin,
+                    format!("prompt '{}' links to '{}', an external URL", full_id, url),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the content of a prompt about to be run for URLs to hosts that aren't
+    /// allowed.
+    pub fn check_content(&self, full_id: &str, content: &str) -> Result<(), String> {
+        for (origin, policy) in &self.policies {
+            if let Some(url) = urls(content).find(|url| !policy.allows_url(url)) {
+                return Err(violation(
+                    origin,
+                    format!("prompt '{}' links to '{}', an external URL", full_id, url),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a fetch step may request `url`.
+    pub fn check_url(&self, url: &str) -> Result<(), String> {
+        for (origin, policy) in &self.policies {
+            if !policy.allows_url(url) {
+                return Err(violation(
+                    origin,
+                    format!("fetching '{}', an external URL, is not allowed", url),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Policy {
+    fn allows_url(&self, url: &str) -> bool {
+        let Some(allowed) = &self.allowed_url_hosts else {
+            return true;
+        };
+        let Some(host) = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        else {
+            return false;
+        };
+        allowed.iter().any(|allowed| {
+            let allowed = allowed.to_lowercase();
+            host == allowed || host.ends_with(&format!(".{}", allowed))
+        })
+    }
+}
+
+/// Stands for a provider a policy forbids: every request fails with the violation.
+pub struct BlockedProvider {
+    violation: String,
+}
+
+impl BlockedProvider {
+    pub fn new(violation: String) -> Self {
+        Self { violation }
+    }
+
+    fn error(&self) -> LLMError {
+        LLMError::InvalidRequest(self.violation.clone())
+    }
+}
+
+impl LLMProvider for BlockedProvider {
+    fn tools(&self) -> Option<&[Tool]> {
+        None
+    }
+}
+
+#[async_trait]
+impl ChatProvider for BlockedProvider {
+    async fn chat(&self, _messages: &[ChatMessage]) -> Result<Box<dyn ChatResponse>, LLMError> {
+        Err(self.error())
+    }
+
+    async fn chat_with_tools(
+        &self,
+        _messages: &[ChatMessage],
+        _tools: Option<&[Tool]>,
+    ) -> Result<Box<dyn ChatResponse>, LLMError> {
+        Err(self.error())
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for BlockedProvider {
+    async fn complete(&self, _req: &CompletionRequest) -> Result<CompletionResponse, LLMError> {
+        Err(self.error())
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for BlockedProvider {
+    async fn embed(&self, _input: Vec<String>) -> Result<Vec<Vec<f32>>, LLMError> {
+        Err(self.error())
+    }
+}
+
+#[async_trait]
+impl SpeechToTextProvider for BlockedProvider {
+    async fn transcribe(&self, _audio: Vec<u8>) -> Result<String, LLMError> {
+        Err(self.error())
+    }
+}
+
+#[async_trait]
+impl TextToSpeechProvider for BlockedProvider {
+    async fn speech(&self, _text: &str) -> Result<Vec<u8>, LLMError> {
+        Err(self.error())
+    }
+}
+
+#[async_trait]
+impl ModelsProvider for BlockedProvider {
+    async fn list_models(
+        &self,
+        _request: Option<&ModelListRequest>,
+    ) -> Result<Box<dyn ModelListResponse>, LLMError> {
+        Err(self.error())
+    }
+}
+
+/// Checks that the content of a policy file is valid, e.g. one shipped with a pack.
+pub fn check_policy(content: &str) -> Result<(), String> {
+    toml::from_str::<Policy>(content)
+        .map(drop)
+        .map_err(|e| format!("Invalid {}: {}", POLICY_FILE, e))
+}
+
+fn read_policy(path: &Path) -> Result<Policy, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn violation(origin: &str, message: String) -> String {
+    format!("Policy violation ({}): {}", origin, message)
+}
+
+fn has_tag(pd: &PromptData, tag: &str) -> bool {
+    pd.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// URLs of every text of a prompt: content, translations, variants and canary.
+fn urls_of(pd: &PromptData) -> BTreeSet<&str> {
+    std::iter::once(pd.content.as_str())
+        .chain(pd.translations.values().map(String::as_str))
+        .chain(pd.variants.values().map(String::as_str))
+        .chain(pd.canary.iter().map(|canary| canary.content.as_str()))
+        .flat_map(urls)
+        .collect()
+}
+
+fn urls(text: &str) -> impl Iterator<Item = &str> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    URL.get_or_init(|| Regex::new(r#"(?i)\bhttps?://[^\s"'<>()\[\]{}]+"#).unwrap())
+        .find_iter(text)
+        .map(|m| m.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Policies parsed from `(origin, policy.toml)` pairs.
+    fn policies(files: &[(&str, &str)]) -> Policies {
+        Policies {
+            policies: files
+                .iter()
+                .map(|(origin, toml)| (origin.to_string(), toml::from_str(toml).unwrap()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn banned_providers_match_names_and_backends() {
+        let policies = policies(&[(POLICY_FILE, r#"banned_providers = ["DeepSeek"]"#)]);
+        assert!(policies
+            .check_provider("deepseek", "deepseek", None)
+            .is_err());
+        assert!(policies.check_provider("cheap", "deepseek", None).is_err());
+        assert!(policies
+            .check_provider("deepseek:chat", "deepseek", None)
+            .is_err());
+        assert!(policies.check_provider("fast", "openai", None).is_ok());
+    }
+
+    #[test]
+    fn temperatures_above_the_maximum_are_refused() {
+        let policies = policies(&[(POLICY_FILE, "max_temperature = 0.5")]);
+        assert!(policies.check_provider("fast", "openai", Some(0.5)).is_ok());
+        assert!(policies.check_provider("fast", "openai", None).is_ok());
+        let err = policies
+            .check_provider("fast", "openai", Some(0.9))
+            .unwrap_err();
+        assert!(err.starts_with("Policy violation (policy.toml)"), "{}", err);
+    }
+
+    #[test]
+    fn packs_cannot_lift_the_restrictions_of_another_policy() {
+        let policies = policies(&[
+            (POLICY_FILE, r#"banned_providers = ["deepseek"]"#),
+            (
+                "policy.toml of pack 'team'",
+                "banned_providers = []\nmax_temperature = 0.3",
+            ),
+        ]);
+        assert!(policies
+            .check_provider("deepseek", "deepseek", None)
+            .is_err());
+        let err = policies
+            .check_provider("fast", "openai", Some(0.4))
+            .unwrap_err();
+        assert!(err.contains("of pack 'team'"), "{}", err);
+        assert_eq!(policies.max_temperature(), Some(0.3));
+    }
+
+    #[test]
+    fn fetched_urls_must_be_on_allowed_hosts() {
+        let policies = policies(&[(POLICY_FILE, r#"allowed_url_hosts = ["wiki.internal"]"#)]);
+        assert!(policies.check_url("https://wiki.internal/page").is_ok());
+        assert!(policies
+            .check_url("https://docs.wiki.internal/page")
+            .is_ok());
+        assert!(policies
+            .check_url("https://evil-wiki.internal/page")
+            .is_err());
+        assert!(policies.check_url("not a url").is_err());
+        assert!(Policies::default().check_url("https://example.com").is_ok());
+    }
+
+    #[test]
+    fn unknown_settings_are_refused() {
+        assert!(check_policy("banned_providers = [\"deepseek\"]").is_ok());
+        assert!(check_policy("banned_provider = [\"deepseek\"]").is_err());
+    }
+}
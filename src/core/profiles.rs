@@ -0,0 +1,86 @@
+//! Named, encrypted variable profiles (e.g., `dev`, `staging`, `prod`).
+
+use super::cipher::StoreCipher;
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::utils::{ensure_dir, restrict_permissions};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Variables held by a profile, keyed by variable name.
+pub type ProfileVars = BTreeMap<String, String>;
+
+/// Returns the directory holding the encrypted profiles.
+pub fn profiles_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join("profiles")
+}
+
+fn profile_path(base_dir: &Path, name: &str) -> PathBuf {
+    profiles_dir(base_dir).join(format!("{}.profile", name))
+}
+
+/// Loads a profile, returning `None` if it does not exist.
+pub fn load_profile(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    name: &str,
+) -> Result<Option<ProfileVars>, String> {
+    let path = profile_path(base_dir, name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let plaintext = decrypt_from_base64(cipher, &encoded)?;
+    serde_json::from_slice(&plaintext)
+        .map(Some)
+        .map_err(|_| "Invalid JSON".to_string())
+}
+
+/// Encrypts and writes a profile, replacing any previous content.
+pub fn save_profile(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    name: &str,
+    vars: &ProfileVars,
+) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\', '.']) {
+        return Err(format!("Invalid profile name '{}'", name));
+    }
+    ensure_dir(&profiles_dir(base_dir))?;
+    let json =
+        Zeroizing::new(serde_json::to_vec(vars).map_err(|e| format!("Serialize error: {}", e))?);
+    let path = profile_path(base_dir, name);
+    fs::write(&path, encrypt_to_base64(cipher, &json)?)
+        .map_err(|e| format!("Write error: {}", e))?;
+    restrict_permissions(&path);
+    Ok(())
+}
+
+/// Deletes a profile. Returns false if it did not exist.
+pub fn delete_profile(base_dir: &Path, name: &str) -> Result<bool, String> {
+    let path = profile_path(base_dir, name);
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+    Ok(true)
+}
+
+/// Lists the names of all stored profiles, sorted.
+pub fn list_profiles(base_dir: &Path) -> Result<Vec<String>, String> {
+    let dir = profiles_dir(base_dir);
+    let mut names = Vec::new();
+    if dir.exists() {
+        for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("profile") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
@@ -0,0 +1,196 @@
+//! Queues of run requests consumed by `prompt-store worker`.
+//!
+//! Two kinds of queue are supported:
+//!
+//! - A directory: requests are JSON files dropped in `<dir>/pending/`. The worker moves
+//!   a request to `<dir>/processing/` while it runs, then writes the result to
+//!   `<dir>/results/<name>.json`. Writers should create the file under another name
+//!   (e.g. `.tmp`) and rename it to `.json`, so a half-written request is never read.
+//! - A Redis list (`redis://` or `rediss://` URL): requests are pushed to
+//!   `prompt-store:requests`, and the result of each is pushed to
+//!   `prompt-store:results:<id>`, where callers can wait for it with `BLPOP`.
+
+use super::storage::write_atomic;
+use super::utils::ensure_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Redis list the requests are taken from.
+const REDIS_REQUESTS: &str = "prompt-store:requests";
+/// Prefix of the Redis lists the results are pushed to.
+const REDIS_RESULTS: &str = "prompt-store:results";
+/// Seconds a result stays in Redis when nobody collects it.
+const REDIS_RESULT_TTL: u64 = 24 * 60 * 60;
+/// Seconds to wait for a request before checking again.
+const POLL_SECS: u64 = 1;
+
+/// A request to run a prompt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunRequest {
+    /// ID chosen by the caller to match the result. The file name is used for directory
+    /// queues when absent; Redis requests without one get no result.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// ID of the prompt to run.
+    pub prompt: String,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Backend to run it with, instead of the worker's default.
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Language of the translation to run.
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+/// The outcome of a request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/// A request taken from a queue, to be answered with [`Queue::complete`].
+pub struct Job {
+    /// The request, or why it couldn't be read.
+    pub request: Result<RunRequest, String>,
+    /// File of the request in a directory queue.
+    claimed: Option<PathBuf>,
+}
+
+/// A queue of run requests.
+pub enum Queue {
+    Dir(PathBuf),
+    Redis(redis::aio::MultiplexedConnection),
+}
+
+impl Queue {
+    /// Opens a queue from a directory path or a Redis URL.
+    pub async fn open(spec: &str) -> Result<Self, String> {
+        if spec.starts_with("redis://") || spec.starts_with("rediss://") {
+            let client = redis::Client::open(spec)
+                .map_err(|e| format!("Invalid Redis URL '{}': {}", spec, e))?;
+            let connection = client
+                .get_multiplexed_async_connection()
+                .await
+                .map_err(|e| format!("Failed to reach {}: {}", spec, e))?;
+            return Ok(Queue::Redis(connection));
+        }
+        let dir = PathBuf::from(spec);
+        for sub in ["pending", "processing", "results"] {
+            ensure_dir(&dir.join(sub))?;
+        }
+        Ok(Queue::Dir(dir))
+    }
+
+    /// Takes the next request, waiting up to a second for one. Returns `None` when the
+    /// queue is empty.
+    pub async fn next(&mut self) -> Result<Option<Job>, String> {
+        match self {
+            Queue::Dir(dir) => match next_file(dir)? {
+                Some(claimed) => Ok(Some(Job {
+                    request: read_request(&claimed),
+                    claimed: Some(claimed),
+                })),
+                None => {
+                    tokio::time::sleep(Duration::from_secs(POLL_SECS)).await;
+                    Ok(None)
+                }
+            },
+            Queue::Redis(connection) => {
+                let popped: Option<(String, String)> = redis::cmd("BLPOP")
+                    .arg(REDIS_REQUESTS)
+                    .arg(POLL_SECS)
+                    .query_async(connection)
+                    .await
+                    .map_err(|e| format!("Redis error: {}", e))?;
+                Ok(popped.map(|(_, json)| Job {
+                    request: serde_json::from_str(&json)
+                        .map_err(|e| format!("Invalid request: {}", e)),
+                    claimed: None,
+                }))
+            }
+        }
+    }
+
+    /// Writes back the result of a request and releases it.
+    pub async fn complete(&mut self, job: Job, result: &RunResult) -> Result<(), String> {
+        let json = serde_json::to_string(result).map_err(|e| format!("Serialize error: {}", e))?;
+        match self {
+            Queue::Dir(dir) => {
+                let Some(claimed) = job.claimed else {
+                    return Ok(());
+                };
+                let path = dir
+                    .join("results")
+                    .join(claimed.file_name().unwrap_or_default());
+                write_atomic(&path, json)?;
+                fs::remove_file(&claimed).map_err(|e| format!("Delete error: {}", e))
+            }
+            Queue::Redis(connection) => {
+                let Some(id) = &result.id else {
+                    return Ok(());
+                };
+                let key = format!("{}:{}", REDIS_RESULTS, id);
+                let _: () = redis::pipe()
+                    .cmd("RPUSH")
+                    .arg(&key)
+                    .arg(json)
+                    .ignore()
+                    .cmd("EXPIRE")
+                    .arg(&key)
+                    .arg(REDIS_RESULT_TTL)
+                    .ignore()
+                    .query_async(connection)
+                    .await
+                    .map_err(|e| format!("Redis error: {}", e))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Claims the oldest pending request by moving it to `processing/`. A request another
+/// worker claimed first is skipped.
+fn next_file(dir: &Path) -> Result<Option<PathBuf>, String> {
+    let pending = dir.join("pending");
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&pending).map_err(|e| format!("Read dir error: {}", e))? {
+        let entry = entry.map_err(|e| format!("Dir entry error: {}", e))?;
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            files.push((modified, path));
+        }
+    }
+    files.sort();
+    for (_, path) in files {
+        let claimed = dir
+            .join("processing")
+            .join(path.file_name().unwrap_or_default());
+        if fs::rename(&path, &claimed).is_ok() {
+            return Ok(Some(claimed));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads a claimed request, taking its ID from the file name when it has none.
+fn read_request(path: &Path) -> Result<RunRequest, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
+    let mut request: RunRequest =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid request: {}", e))?;
+    if request.id.is_none() {
+        request.id = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+    }
+    Ok(request)
+}
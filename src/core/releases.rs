@@ -0,0 +1,119 @@
+//! Immutable releases: named snapshots of a workspace taken with `release create` and
+//! pinned by applications with `PromptStore::at_release`.
+//!
+//! A release copies the encrypted files of a workspace to `releases/<name>/<workspace>/`,
+//! so a store whose workspaces directory is `releases/<name>` resolves prompts and
+//! chains as they were when the release was created. Nothing writes to it afterwards.
+
+use super::storage::{modified_at, AppCtx};
+use super::utils::{ensure_dir, restrict_permissions};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A release of the store.
+pub struct ReleaseInfo {
+    pub name: String,
+    /// The workspace it was taken from.
+    pub workspace: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Returns the directory holding the releases.
+pub fn releases_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join("releases")
+}
+
+/// Returns the directory of a release, which is the workspaces directory of the stores
+/// pinned to it.
+pub fn release_dir(base_dir: &Path, name: &str) -> PathBuf {
+    releases_dir(base_dir).join(name)
+}
+
+/// Checks a release name: letters, digits, '.', '_' and '-', not starting with '.'.
+pub fn validate_release_name(name: &str) -> Result<(), String> {
+    let valid = |c: char| c.is_alphanumeric() || matches!(c, '.' | '_' | '-');
+    if name.is_empty() || name.starts_with('.') || !name.chars().all(valid) {
+        return Err(format!(
+            "Invalid release name '{}': use letters, digits, '.', '_' and '-'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Snapshots a workspace into a new release. Prompt backups are left out.
+pub fn create_release(ctx: &AppCtx, name: &str, workspace: &str) -> Result<(), String> {
+    validate_release_name(name)?;
+    let source = ctx.workspaces_dir.join(workspace);
+    if !source.is_dir() {
+        return Err(format!("Workspace '{}' not found.", workspace));
+    }
+    let target = release_dir(&ctx.base_dir, name);
+    if target.exists() {
+        return Err(format!(
+            "Release '{}' already exists. Releases cannot be changed; use a new name.",
+            name
+        ));
+    }
+
+    // Copied aside first, so an interrupted copy never leaves a partial release.
+    ensure_dir(&releases_dir(&ctx.base_dir))?;
+    let partial = releases_dir(&ctx.base_dir).join(format!("{}.tmp", name));
+    if partial.exists() {
+        fs::remove_dir_all(&partial).map_err(|e| format!("Delete error: {}", e))?;
+    }
+    copy_workspace(&source, &partial.join(workspace))?;
+    fs::rename(&partial, &target).map_err(|e| format!("Unable to create release: {}", e))
+}
+
+/// Lists the releases, sorted by name.
+pub fn list_releases(base_dir: &Path) -> Result<Vec<ReleaseInfo>, String> {
+    let dir = releases_dir(base_dir);
+    let mut releases = Vec::new();
+    if !dir.is_dir() {
+        return Ok(releases);
+    }
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !path.is_dir() || name.ends_with(".tmp") {
+            continue;
+        }
+        let workspace = fs::read_dir(&path)
+            .map_err(|e| format!("Read dir error: {}", e))?
+            .filter_map(|e| e.ok())
+            .find(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .unwrap_or_default();
+        releases.push(ReleaseInfo {
+            name: name.into_owned(),
+            workspace,
+            created_at: modified_at(&path),
+        });
+    }
+    releases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(releases)
+}
+
+/// Recursively copies a workspace, skipping backups and symlinks.
+fn copy_workspace(from: &Path, to: &Path) -> Result<(), String> {
+    ensure_dir(to)?;
+    for entry in fs::read_dir(from).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        let target = to.join(path.file_name().unwrap_or_default());
+        let is_symlink = fs::symlink_metadata(&path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_symlink || path.extension().is_some_and(|ext| ext == "bak") {
+            continue;
+        }
+        if path.is_dir() {
+            copy_workspace(&path, &target)?;
+        } else {
+            fs::copy(&path, &target).map_err(|e| format!("Copy error: {}", e))?;
+            restrict_permissions(&target);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! An `LLMProvider` wrapper that retries transient failures.
+
+use llm::async_trait;
+use llm::chat::{ChatMessage, ChatProvider, ChatResponse, Tool};
+use llm::completion::{CompletionProvider, CompletionRequest, CompletionResponse};
+use llm::embedding::EmbeddingProvider;
+use llm::error::LLMError;
+use llm::models::{ModelListRequest, ModelListResponse, ModelsProvider};
+use llm::stt::SpeechToTextProvider;
+use llm::tts::TextToSpeechProvider;
+use llm::LLMProvider;
+use std::future::Future;
+use std::time::Duration;
+
+/// How many times a request is attempted and how long to wait between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each failed attempt.
+    pub backoff_ms: u64,
+}
+
+/// Wraps a provider and retries requests that fail with a transient error.
+pub struct RetryingProvider {
+    inner: Box<dyn LLMProvider>,
+    policy: RetryPolicy,
+}
+
+impl RetryingProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn with_retry<T, F, Fut>(&self, mut attempt: F) -> Result<T, LLMError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, LLMError>>,
+    {
+        let mut delay = self.policy.backoff_ms;
+        let mut tries = 1;
+        loop {
+            // The response is dropped before sleeping since it isn't guaranteed to be `Send`.
+            match attempt().await {
+                Err(e) if tries < self.policy.max_attempts && is_transient(&e) => {}
+                result => return result,
+            }
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            delay = delay.saturating_mul(2);
+            tries += 1;
+        }
+    }
+}
+
+/// Network failures (including timeouts), provider-side failures and HTTP 429/5xx
+/// responses are worth retrying; auth and request errors are not.
+pub fn is_transient(err: &LLMError) -> bool {
+    match err {
+        LLMError::HttpError(_) | LLMError::ProviderError(_) => true,
+        LLMError::ResponseFormatError { message, .. } => {
+            http_status(message).is_some_and(|code| code == 429 || code >= 500)
+        }
+        _ => false,
+    }
+}
+
+/// Extracts the status code from backend messages like "OpenAI API returned error status: 503 ...".
+fn http_status(message: &str) -> Option<u16> {
+    let (_, rest) = message.split_once("error status: ")?;
+    rest.get(..3)?.parse().ok()
+}
+
+impl LLMProvider for RetryingProvider {
+    fn tools(&self) -> Option<&[Tool]> {
+        self.inner.tools()
+    }
+}
+
+#[async_trait]
+impl ChatProvider for RetryingProvider {
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<Box<dyn ChatResponse>, LLMError> {
+        self.with_retry(|| self.inner.chat(messages)).await
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        tools: Option<&[Tool]>,
+    ) -> Result<Box<dyn ChatResponse>, LLMError> {
+        self.with_retry(|| self.inner.chat_with_tools(messages, tools))
+            .await
+    }
+
+    async fn memory_contents(&self) -> Option<Vec<ChatMessage>> {
+        self.inner.memory_contents().await
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for RetryingProvider {
+    async fn complete(&self, req: &CompletionRequest) -> Result<CompletionResponse, LLMError> {
+        self.with_retry(|| self.inner.complete(req)).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RetryingProvider {
+    async fn embed(&self, input: Vec<String>) -> Result<Vec<Vec<f32>>, LLMError> {
+        self.with_retry(|| self.inner.embed(input.clone())).await
+    }
+}
+
+#[async_trait]
+impl SpeechToTextProvider for RetryingProvider {
+    async fn transcribe(&self, audio: Vec<u8>) -> Result<String, LLMError> {
+        self.with_retry(|| self.inner.transcribe(audio.clone()))
+            .await
+    }
+}
+
+#[async_trait]
+impl TextToSpeechProvider for RetryingProvider {
+    async fn speech(&self, text: &str) -> Result<Vec<u8>, LLMError> {
+        self.with_retry(|| self.inner.speech(text)).await
+    }
+}
+
+#[async_trait]
+impl ModelsProvider for RetryingProvider {
+    async fn list_models(
+        &self,
+        request: Option<&ModelListRequest>,
+    ) -> Result<Box<dyn ModelListResponse>, LLMError> {
+        self.inner.list_models(request).await
+    }
+}
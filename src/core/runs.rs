@@ -0,0 +1,125 @@
+//! Encrypted transcripts of chain runs, kept to be reviewed or exported as reports.
+
+use super::cipher::StoreCipher;
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::utils::{ensure_dir, restrict_permissions};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Number of transcripts kept; older ones are removed when a run is recorded.
+const MAX_RUNS: usize = 100;
+
+/// A completed chain run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunRecord {
+    /// ID of the chain that ran.
+    pub chain: String,
+    pub started_at: DateTime<Utc>,
+    /// Steps in the order they completed.
+    pub steps: Vec<RunStep>,
+}
+
+/// One step of a recorded run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunStep {
+    /// Output key of the step.
+    pub key: String,
+    /// Rendered prompt, query, URL or program, with secrets and redacted inputs masked.
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    pub latency_ms: u64,
+    pub output: String,
+}
+
+/// Returns the directory holding the run transcripts.
+pub fn runs_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join("runs")
+}
+
+fn run_path(base_dir: &Path, id: &str) -> PathBuf {
+    runs_dir(base_dir).join(format!("{}.run", id))
+}
+
+/// Encrypts and writes a transcript, then removes the oldest ones beyond the limit.
+/// Returns the ID of the run, e.g. `20250101-120000`.
+pub fn save_run(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    record: &RunRecord,
+) -> Result<String, String> {
+    ensure_dir(&runs_dir(base_dir))?;
+    let stamp = record
+        .started_at
+        .with_timezone(&Local)
+        .format("%Y%m%d-%H%M%S")
+        .to_string();
+    let id = (1..)
+        .map(|n| match n {
+            1 => stamp.clone(),
+            n => format!("{}-{}", stamp, n),
+        })
+        .find(|id| !run_path(base_dir, id).exists())
+        .unwrap();
+
+    let json =
+        Zeroizing::new(serde_json::to_vec(record).map_err(|e| format!("Serialize error: {}", e))?);
+    let path = run_path(base_dir, &id);
+    fs::write(&path, encrypt_to_base64(cipher, &json)?)
+        .map_err(|e| format!("Write error: {}", e))?;
+    restrict_permissions(&path);
+
+    for old in list_runs(base_dir)?.iter().skip(MAX_RUNS) {
+        fs::remove_file(run_path(base_dir, old)).map_err(|e| format!("Delete error: {}", e))?;
+    }
+    Ok(id)
+}
+
+/// Loads a transcript, returning `None` if it does not exist.
+pub fn load_run(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    id: &str,
+) -> Result<Option<RunRecord>, String> {
+    if id.is_empty() || id.contains(['/', '\\', '.']) {
+        return Ok(None);
+    }
+    let path = run_path(base_dir, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let plaintext = decrypt_from_base64(cipher, &encoded)?;
+    serde_json::from_slice(&plaintext)
+        .map(Some)
+        .map_err(|_| "Invalid JSON".to_string())
+}
+
+/// Lists the IDs of the recorded runs, most recent first.
+pub fn list_runs(base_dir: &Path) -> Result<Vec<String>, String> {
+    let dir = runs_dir(base_dir);
+    let mut ids = Vec::new();
+    if dir.exists() {
+        for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("run") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    ids.push(stem.to_string());
+                }
+            }
+        }
+    }
+    ids.sort_by(|a, b| run_order(b).cmp(&run_order(a)));
+    Ok(ids)
+}
+
+/// Orders run IDs by time, then by the `-N` suffix of runs started in the same second.
+fn run_order(id: &str) -> (&str, u32) {
+    match id.get(15..).and_then(|rest| rest.strip_prefix('-')) {
+        Some(n) => (&id[..15], n.parse().unwrap_or(0)),
+        None => (id, 1),
+    }
+}
@@ -0,0 +1,103 @@
+//! Heuristic scanning of prompt contents for prompt-injection patterns, used before
+//! installing third-party packs.
+
+use regex::Regex;
+use std::fmt;
+
+/// The kind of suspicious content found in a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskKind {
+    /// Instructions to send secrets, the conversation or the system prompt elsewhere,
+    /// or URLs that embed template variables.
+    Exfiltration,
+    /// Phrases trying to override the system prompt or previous instructions.
+    Override,
+    /// Invisible or bidirectional-control unicode characters that hide text.
+    HiddenUnicode,
+}
+
+impl fmt::Display for RiskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RiskKind::Exfiltration => "exfiltration",
+            RiskKind::Override => "override",
+            RiskKind::HiddenUnicode => "hidden unicode",
+        })
+    }
+}
+
+/// A suspicious passage of a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Risk {
+    pub kind: RiskKind,
+    /// The matched text, shortened, or the code points of hidden characters.
+    pub excerpt: String,
+}
+
+const OVERRIDE: &[&str] = &[
+    r"(?i)\b(ignore|disregard|forget|override)\s+(all\s+|any\s+|the\s+|your\s+)*(previous|prior|above|earlier|system|original)\s+(instructions|prompts?|rules|messages|guidelines)",
+    r"(?i)\byou\s+are\s+no\s+longer\b",
+    r"(?i)\bnew\s+system\s+prompt\b",
+    r"(?i)\b(developer|jailbreak|dan)\s+mode\b",
+    r"(?i)<\|?\s*(im_start|system)\s*\|?>",
+];
+
+const EXFILTRATION: &[&str] = &[
+    r"(?i)\b(send|post|upload|forward|leak|exfiltrate|transmit|email)\b[^.\n]{0,80}\b(api[ _-]?keys?|passwords?|secrets?|tokens?|credentials|system\s+prompt|conversation|chat\s+history)\b",
+    r"(?i)\b(reveal|print|repeat|output|show)\s+(your|the)\s+(system\s+prompt|hidden\s+instructions|api[ _-]?key)",
+    r"https?://[^\s)]*\{\{",
+];
+
+/// Lists the suspicious passages of a prompt. Detection is heuristic: an empty list
+/// does not prove the prompt is safe.
+pub fn scan(content: &str) -> Vec<Risk> {
+    let mut risks = Vec::new();
+    for (kind, patterns) in [
+        (RiskKind::Override, OVERRIDE),
+        (RiskKind::Exfiltration, EXFILTRATION),
+    ] {
+        for pattern in patterns {
+            let re = Regex::new(pattern).unwrap();
+            risks.extend(re.find_iter(content).map(|m| Risk {
+                kind,
+                excerpt: shorten(m.as_str()),
+            }));
+        }
+    }
+
+    let mut hidden: Vec<char> = content.chars().filter(|c| is_hidden(*c)).collect();
+    hidden.sort_unstable();
+    hidden.dedup();
+    if !hidden.is_empty() {
+        let points: Vec<String> = hidden
+            .iter()
+            .map(|c| format!("U+{:04X}", *c as u32))
+            .collect();
+        risks.push(Risk {
+            kind: RiskKind::HiddenUnicode,
+            excerpt: points.join(", "),
+        });
+    }
+    risks
+}
+
+/// Zero-width, bidirectional-control and tag characters.
+fn is_hidden(c: char) -> bool {
+    matches!(c,
+        '\u{200B}'..='\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{2066}'..='\u{2069}'
+        | '\u{FEFF}'
+        | '\u{E0000}'..='\u{E007F}')
+}
+
+fn shorten(text: &str) -> String {
+    const MAX_CHARS: usize = 80;
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > MAX_CHARS {
+        format!("{}...", text.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        text
+    }
+}
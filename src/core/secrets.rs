@@ -0,0 +1,43 @@
+//! Provider API keys stored encrypted with the master key.
+
+use super::cipher::StoreCipher;
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::utils::restrict_permissions;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Returns the path of the encrypted API key file.
+pub fn api_keys_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("keys").join("providers.bin")
+}
+
+/// Loads the stored API keys, keyed by provider name.
+pub fn load_api_keys(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+) -> Result<BTreeMap<String, String>, String> {
+    let path = api_keys_path(base_dir);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let plaintext = decrypt_from_base64(cipher, &encoded)?;
+    serde_json::from_slice(&plaintext).map_err(|_| "Invalid API key file".to_string())
+}
+
+/// Encrypts and writes the API keys.
+pub fn save_api_keys(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    keys: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let path = api_keys_path(base_dir);
+    let json =
+        Zeroizing::new(serde_json::to_vec(keys).map_err(|e| format!("Serialize error: {}", e))?);
+    fs::write(&path, encrypt_to_base64(cipher, &json)?)
+        .map_err(|e| format!("Key write error: {}", e))?;
+    restrict_permissions(&path);
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Shamir secret sharing over GF(256), used to escrow the master key.
+//!
+//! Each byte of the secret is the constant term of a random polynomial of degree
+//! `threshold - 1`; share `x` holds the values of those polynomials at `x`. Any
+//! `threshold` shares recover the secret by Lagrange interpolation at zero, while fewer
+//! reveal nothing about it.
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use zeroize::Zeroizing;
+
+/// A share of a secret: its x coordinate (1 to 255) and one value per secret byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub x: u8,
+    pub values: Vec<u8>,
+}
+
+/// Splits a secret into `shares` shares, any `threshold` of which recover it.
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<Share>, String> {
+    if threshold < 2 || threshold > shares {
+        return Err(format!(
+            "The threshold must be between 2 and the number of shares ({}).",
+            shares
+        ));
+    }
+    let mut result: Vec<Share> = (1..=shares)
+        .map(|x| Share {
+            x,
+            values: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+        for share in &mut result {
+            // Horner's method, from the highest degree down.
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, &c| mul(acc, share.x) ^ c);
+            share.values.push(value);
+        }
+    }
+    Ok(result)
+}
+
+/// Recovers a secret from at least `threshold` distinct shares. With fewer shares, the
+/// result is a wrong secret rather than an error.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let Some(first) = shares.first() else {
+        return Err("No shares given.".to_string());
+    };
+    for (i, share) in shares.iter().enumerate() {
+        if share.x == 0 || share.values.len() != first.values.len() {
+            return Err("The shares do not belong to the same secret.".to_string());
+        }
+        if shares[..i].iter().any(|other| other.x == share.x) {
+            return Err(format!("Share {} was given twice.", share.x));
+        }
+    }
+
+    let mut secret = Zeroizing::new(vec![0u8; first.values.len()]);
+    for (i, share) in shares.iter().enumerate() {
+        // Lagrange basis polynomial of this share, evaluated at zero.
+        let basis = shares
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(1, |acc, (_, other)| {
+                mul(acc, div(other.x, other.x ^ share.x))
+            });
+        for (byte, &value) in secret.iter_mut().zip(&share.values) {
+            *byte ^= mul(value, basis);
+        }
+    }
+    Ok(secret)
+}
+
+/// Multiplication in GF(256) with the AES polynomial.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(256); `b` must not be zero.
+fn div(a: u8, b: u8) -> u8 {
+    // b^254 is the inverse of b, as b^255 = 1.
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = mul(inverse, b);
+    }
+    mul(a, inverse)
+}
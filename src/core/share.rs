@@ -0,0 +1,70 @@
+//! Single prompts sealed with a random passphrase, exchanged with `share`/`receive`.
+//!
+//! A share is text (base64 of `PSSH` + salt + nonce + AES-GCM ciphertext of the prompt
+//! JSON), so it can be pasted anywhere or written to a `.pshare` file.
+
+use aes_gcm::aead::{rand_core::RngCore, Aead, AeadCore, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use rand::seq::SliceRandom;
+
+use super::crypto::password_cipher;
+use super::storage::PromptData;
+
+const MAGIC_SHARE: &[u8; 4] = b"PSSH";
+
+/// Generates a passphrase of five groups of four lowercase letters and digits.
+pub fn generate_passphrase() -> String {
+    // Without look-alike characters (0/o, 1/l), as it is often typed by hand.
+    const CHARS: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+    let mut rng = OsRng;
+    (0..5)
+        .map(|_| {
+            (0..4)
+                .map(|_| *CHARS.choose(&mut rng).unwrap() as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Encrypts a prompt with a passphrase. Its ID is left out.
+pub fn seal(pd: &PromptData, passphrase: &str) -> Result<String, String> {
+    let shared = PromptData {
+        id: String::new(),
+        ..pd.clone()
+    };
+    let json = serde_json::to_vec(&shared).map_err(|e| format!("Serialize error: {}", e))?;
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = password_cipher(passphrase, &salt)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher_bytes = cipher
+        .encrypt(&nonce, json.as_ref())
+        .map_err(|_| "Encrypt error".to_string())?;
+
+    let mut out = Vec::from(&MAGIC_SHARE[..]);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&cipher_bytes);
+    Ok(general_purpose::STANDARD.encode(&out))
+}
+
+/// Decrypts a shared prompt.
+pub fn open(text: &str, passphrase: &str) -> Result<PromptData, String> {
+    let data = general_purpose::STANDARD
+        .decode(text.trim())
+        .map_err(|_| "Not a shared prompt".to_string())?;
+    if !data.starts_with(MAGIC_SHARE) {
+        return Err("Not a shared prompt".to_string());
+    }
+    if data.len() < 4 + 16 + 12 {
+        return Err("Shared prompt is corrupted or too short".to_string());
+    }
+    let cipher = password_cipher(passphrase, &data[4..20])?;
+    let json = cipher
+        .decrypt(Nonce::from_slice(&data[20..32]), &data[32..])
+        .map_err(|_| "Failed to decrypt. Invalid passphrase or corrupted share.".to_string())?;
+    serde_json::from_slice(&json).map_err(|_| "Invalid shared prompt".to_string())
+}
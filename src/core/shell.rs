@@ -0,0 +1,113 @@
+//! Local command execution used by shell steps in chains.
+
+use std::io::ErrorKind;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Runs `program` with `args` (no shell interpretation), optionally piping `stdin`,
+/// and returns its stdout. A non-zero exit status is reported as an error, and a
+/// program still running after `timeout` is killed.
+pub async fn run_command(
+    program: &str,
+    args: &[String],
+    stdin: Option<&str>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the child when the timeout expires kills it.
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start '{}': {}", program, e))?;
+
+    // Stdin is written while the output is drained: writing it all first blocks forever
+    // once the program fills its stdout pipe waiting for us to read.
+    let pipe = child.stdin.take();
+    let write = async move {
+        if let (Some(input), Some(mut pipe)) = (stdin, pipe) {
+            pipe.write_all(input.as_bytes()).await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    let run = async { tokio::join!(write, child.wait_with_output()) };
+    let (written, output) = tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| format!("'{}' was killed after running for {:?}", program, timeout))?;
+    let output = output.map_err(|e| format!("Failed to run '{}': {}", program, e))?;
+    match written {
+        // A program may exit without reading all of its input; its status tells.
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+            return Err(format!("Failed to write stdin of '{}': {}", program, e));
+        }
+        _ => {}
+    }
+    if !output.status.success() {
+        return Err(format!(
+            "'{}' exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns true if `program` is permitted by the allowlist. Entries match the program
+/// exactly: allowing `jq` runs the `jq` found in `PATH`, not `./jq` or `/tmp/jq`.
+pub fn is_allowed(program: &str, allowlist: &[String]) -> bool {
+    allowlist.iter().any(|a| a == program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn allowlist_matches_programs_exactly() {
+        let allowlist = vec!["jq".to_string(), "/usr/bin/wc".to_string()];
+        assert!(is_allowed("jq", &allowlist));
+        assert!(is_allowed("/usr/bin/wc", &allowlist));
+        assert!(!is_allowed("./jq", &allowlist));
+        assert!(!is_allowed("/tmp/x/jq", &allowlist));
+        assert!(!is_allowed("wc", &allowlist));
+        assert!(!is_allowed("jq", &[]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pipes_input_larger_than_the_pipe_buffer() {
+        let input = "x".repeat(1024 * 1024);
+        let output = run_command("cat", &[], Some(&input), TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(output.len(), input.len());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_failing_programs() {
+        let err = run_command("false", &[], None, TIMEOUT).await.unwrap_err();
+        assert!(err.starts_with("'false' exited with"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn kills_programs_running_past_the_timeout() {
+        let args = vec!["10".to_string()];
+        let err = run_command("sleep", &args, None, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("'sleep' was killed"), "{}", err);
+    }
+}
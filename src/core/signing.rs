@@ -0,0 +1,47 @@
+//! Ed25519 key of the store, signing the manifests of plaintext exports so that
+//! auditors can check them with the public key alone.
+//!
+//! The key is created on first use and kept in `keys/signing.key`, encrypted with the
+//! master key.
+
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::storage::{write_atomic, AppCtx};
+use super::utils::hex;
+use ed25519_dalek::{Signer, SigningKey};
+use rand::rngs::OsRng;
+use std::fs;
+use std::path::PathBuf;
+
+/// Path of the signing key, relative to the store directory.
+pub const SIGNING_KEY_FILE: &str = "keys/signing.key";
+
+fn signing_key_path(ctx: &AppCtx) -> PathBuf {
+    ctx.base_dir.join(SIGNING_KEY_FILE)
+}
+
+/// Loads the signing key of the store, creating it if needed.
+pub fn signing_key(ctx: &AppCtx) -> Result<SigningKey, String> {
+    let path = signing_key_path(ctx);
+    if path.exists() {
+        let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+        let seed = decrypt_from_base64(&ctx.cipher, &encoded)?;
+        let seed: [u8; 32] = seed
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Corrupted signing key".to_string())?;
+        return Ok(SigningKey::from_bytes(&seed));
+    }
+    let key = SigningKey::generate(&mut OsRng);
+    write_atomic(&path, encrypt_to_base64(&ctx.cipher, key.as_bytes())?)?;
+    Ok(key)
+}
+
+/// Signs data with the key of the store. Returns the signature and the public key,
+/// both hex-encoded.
+pub fn sign(ctx: &AppCtx, data: &[u8]) -> Result<(String, String), String> {
+    let key = signing_key(ctx)?;
+    Ok((
+        hex(&key.sign(data).to_bytes()),
+        hex(key.verifying_key().as_bytes()),
+    ))
+}
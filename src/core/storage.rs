@@ -1,16 +1,22 @@
-use super::utils::ensure_dir;
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Key, Nonce,
-};
+use super::utils::{ensure_dir, restrict_permissions};
+use chrono::{DateTime, Utc};
 use base64::{engine::general_purpose, Engine as _};
+use log::{debug, trace, warn};
+use rand::Rng;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use regex::Regex;
 use serde_json::Value;
-use std::env;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
-use super::crypto::load_or_generate_key;
+use super::cipher::StoreCipher;
+use super::config::{load_cipher_algorithm, provider_backend};
+use super::crypto::{decrypt_from_base64, encrypt_to_base64, load_or_generate_key};
+use super::paths::store_dir;
+use super::policy::load_policies;
 
 /// Data for a single, storable prompt, including an optional I/O schema.
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -18,13 +24,142 @@ pub struct PromptData {
     pub id: String,
     pub title: String,
     pub content: String,
+    /// Content in other languages, keyed by lowercase language code (e.g., `fr`, `pt-br`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, String>,
+    /// Named alternatives of the content to compare (A/B), keyed by variant name. The
+    /// content itself is the `default` variant.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, String>,
+    /// A new version of the content served to a share of the library runs until it is
+    /// promoted with `prompt-store promote`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary: Option<Canary>,
     pub tags: Vec<String>,
+    /// A short note on what the prompt is for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub schema: Option<PromptSchema>,
+    /// Sample inputs with their expected outputs, used by `eval` and `{{examples}}`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<PromptExample>,
+    /// When the prompt was last created or modified. Absent for prompts saved by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Attachments carried by export bundles and packs. Stored prompts keep theirs in
+    /// separate files (see [`PromptRepository::save_attachment`]), so this is empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// An input of a prompt, as its variables, and the output expected for it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PromptExample {
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    pub output: String,
+}
+
+/// A version of a prompt's content rolled out to a percentage of the runs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Canary {
+    pub content: String,
+    /// Share of the runs served by the canary, from 0 to 100.
+    pub percent: u8,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Canary {
+    /// Draws whether a run is served by the canary.
+    pub fn picked(&self) -> bool {
+        rand::thread_rng().gen_range(0..100) < self.percent
+    }
+}
+
+impl PromptData {
+    /// The content in a language: its translation for that code, else for its primary
+    /// subtag (`fr` for `fr-CA`), else the default content.
+    pub fn content_for(&self, lang: Option<&str>) -> &str {
+        lang.and_then(|lang| self.translation(lang))
+            .unwrap_or(&self.content)
+    }
+
+    /// The translation for a language code, or for its primary subtag.
+    pub fn translation(&self, lang: &str) -> Option<&str> {
+        let lang = lang.to_lowercase().replace('_', "-");
+        let primary = lang.split('-').next().unwrap_or_default();
+        self.translations
+            .get(&lang)
+            .or_else(|| self.translations.get(primary))
+            .map(String::as_str)
+    }
+
+    /// The content of a variant, `default` being the content itself.
+    pub fn variant_content(&self, name: &str) -> Option<&str> {
+        match name {
+            DEFAULT_VARIANT => Some(&self.content),
+            _ => self.variants.get(name).map(String::as_str),
+        }
+    }
+
+    /// Formats the examples as few-shot text for the `{{examples}}` variable, or `None`
+    /// when the prompt has none.
+    pub fn few_shot_examples(&self) -> Option<String> {
+        few_shot(self.examples.iter())
+    }
+}
+
+/// Name of the variant holding the content of a prompt.
+pub const DEFAULT_VARIANT: &str = "default";
+
+/// Checks a variant name: letters, digits, '_' and '-'.
+pub fn validate_variant_name(name: &str) -> Result<(), String> {
+    let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(valid) {
+        return Err(format!(
+            "Invalid variant name '{}': use letters, digits, '_' and '-'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a language code (letters, digits and dashes, e.g., `fr` or `pt-br`) and
+/// returns it in lowercase.
+pub fn normalize_lang(code: &str) -> Result<String, String> {
+    let code = code.trim().to_lowercase().replace('_', "-");
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid language code '{}'", code));
+    }
+    Ok(code)
+}
+
+/// Formats examples as few-shot text: an `Input:` and `Output:` block for each.
+pub fn few_shot<'a>(examples: impl Iterator<Item = &'a PromptExample>) -> Option<String> {
+    let blocks: Vec<String> = examples
+        .map(|example| {
+            let input: Vec<String> = example
+                .vars
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect();
+            format!("Input:\n{}\nOutput:\n{}", input.join("\n"), example.output)
+        })
+        .collect();
+    (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+}
+
+/// A file attached to a prompt, inlined in a bundle or pack.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    /// The file contents, base64-encoded.
+    pub data: String,
 }
 
 /// Defines the expected inputs and output format (as a JSON Schema value) for a prompt.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct PromptSchema {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inputs: Option<Value>,
@@ -37,15 +172,91 @@ pub struct PromptSchema {
 pub struct ChainData {
     pub id: String,
     pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
-/// Runtime context holding paths and encryption keys.
+/// What a workspace or pack is for, shown by `prompt-store describe`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    /// Who maintains the prompts and answers questions about them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Naming, tagging or writing conventions the prompts follow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conventions: Option<String>,
+    /// Providers the prompts may run on, any provider when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_providers: Vec<String>,
+    /// Providers the prompts must not run on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_providers: Vec<String>,
+}
+
+impl WorkspaceInfo {
+    /// Whether no field is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the workspace limits the providers its prompts run on.
+    pub fn restricts_providers(&self) -> bool {
+        !self.allowed_providers.is_empty() || !self.denied_providers.is_empty()
+    }
+
+    /// Checks that the prompts of `workspace` may run on a provider, given as a name of
+    /// `config.toml` or as `backend:model`. Entries match the name or the backend of the
+    /// provider, so denying `openai` also denies every provider whose backend is openai.
+    pub fn check_provider(
+        &self,
+        workspace: &str,
+        provider: &str,
+        backend: Option<&str>,
+    ) -> Result<(), String> {
+        let matches = |entry: &String| {
+            entry.eq_ignore_ascii_case(provider)
+                || backend.is_some_and(|backend| entry.eq_ignore_ascii_case(backend))
+        };
+        if self.denied_providers.iter().any(matches) {
+            return Err(format!(
+                "Workspace '{}' does not allow its prompts to run on provider '{}'.",
+                workspace, provider
+            ));
+        }
+        if !self.allowed_providers.is_empty() && !self.allowed_providers.iter().any(matches) {
+            return Err(format!(
+                "Workspace '{}' only allows its prompts to run on {}, not on '{}'.",
+                workspace,
+                self.allowed_providers.join(", "),
+                provider
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What a prompt file holds: a prompt, or a link standing for another prompt with the
+/// same content, written by `import` and `deploy` instead of a copy.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PromptFile {
+    Link {
+        /// Full ID of the linked prompt.
+        link: String,
+    },
+    Prompt(Box<PromptData>),
+}
+
+/// Runtime context holding paths and encryption keys. The key schedule of the cipher is
+/// zeroized when the context is dropped.
 pub struct AppCtx {
     pub base_dir: PathBuf,
     pub workspaces_dir: PathBuf,
     pub registries_dir: PathBuf,
     pub key_path: PathBuf,
-    pub cipher: Aes256Gcm,
+    pub cipher: StoreCipher,
 }
 
 /// Parses a prompt identifier into its workspace and local ID components.
@@ -60,9 +271,15 @@ pub fn parse_id(id: &str) -> (String, String) {
 impl AppCtx {
     /// Initializes the application context, creating necessary directories and loading the encryption key.
     pub fn init() -> Result<Self, String> {
-        let home =
-            env::var("HOME").map_err(|_| "Unable to determine HOME directory".to_string())?;
-        let base_dir = PathBuf::from(home).join(".prompt-store");
+        let key_path = store_dir()?.join("keys").join("key.bin");
+        let (key_bytes, _) = load_or_generate_key(&key_path)?;
+        Self::with_key(&key_bytes)
+    }
+
+    /// Initializes the application context with a given master key, without reading or
+    /// creating `key.bin`.
+    pub fn with_key(key: &[u8]) -> Result<Self, String> {
+        let base_dir = store_dir()?;
         let key_dir = base_dir.join("keys");
         let key_path = key_dir.join("key.bin");
         let workspaces_dir = base_dir.join("workspaces");
@@ -74,8 +291,7 @@ impl AppCtx {
         ensure_dir(&workspaces_dir.join("default"))?; // Ensure default workspace exists
         ensure_dir(&registries_dir)?;
 
-        let (key_bytes, _) = load_or_generate_key(&key_path)?;
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes));
+        let cipher = StoreCipher::from_key(key, &load_cipher_algorithm()?)?;
 
         Ok(Self {
             base_dir,
@@ -86,6 +302,38 @@ impl AppCtx {
         })
     }
 
+    /// Creates a store with a new plain key in a temporary directory, removed when the
+    /// directory is dropped.
+    #[cfg(all(test, any(feature = "cli", feature = "grpc")))]
+    pub(crate) fn temp() -> (tempfile::TempDir, Self) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base_dir = dir.path().to_path_buf();
+        let key: [u8; 32] = rand::thread_rng().gen();
+        let ctx = Self {
+            workspaces_dir: base_dir.join("workspaces"),
+            registries_dir: base_dir.join("registries"),
+            key_path: base_dir.join("keys").join("key.bin"),
+            cipher: StoreCipher::from_key(&key, super::cipher::AES_256_GCM).unwrap(),
+            base_dir,
+        };
+        ensure_dir(&ctx.workspaces_dir.join("default")).unwrap();
+        write_atomic(&ctx.key_path, &*super::crypto::encode_plain_key(&key)).unwrap();
+        (dir, ctx)
+    }
+
+    /// Checks that every prompt of every workspace decrypts with the cipher of the
+    /// context. Returns the number of prompts checked.
+    pub fn verify_key(&self) -> Result<usize, String> {
+        let mut paths = Vec::new();
+        collect_files(&self.workspaces_dir, "prompt", &mut paths)?;
+        for path in &paths {
+            let encoded = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
+            decrypt_from_base64(&self.cipher, &encoded)
+                .map_err(|_| format!("{} does not decrypt with this key", path.display()))?;
+        }
+        Ok(paths.len())
+    }
+
     /// Constructs the full path for a prompt file from its full ID.
     pub fn prompt_path(&self, full_id: &str) -> PathBuf {
         let (workspace, local_id) = parse_id(full_id);
@@ -101,18 +349,750 @@ impl AppCtx {
     }
 }
 
-/// Decrypts a prompt file to read its full data.
-pub fn decrypt_full_prompt(path: &Path, cipher: &Aes256Gcm) -> Result<PromptData, String> {
-    let encoded = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
-    let decoded = general_purpose::STANDARD
-        .decode(encoded.trim_end())
-        .map_err(|_| "Corrupted data".to_string())?;
-    if decoded.len() < 12 {
-        return Err("Corrupted data".to_string());
+/// A prompt loaded from the store together with its full ID
+/// (e.g., `abc123`, `chain/1` or `pack::abc123`).
+pub struct StoredPrompt {
+    pub full_id: String,
+    pub data: PromptData,
+}
+
+impl StoredPrompt {
+    /// Whether the prompt is a step of an interactive chain rather than a standalone prompt.
+    pub fn is_chain_step(&self) -> bool {
+        parse_id(&self.full_id).1.contains('/')
+    }
+}
+
+/// Typed access to the encrypted prompts and chains stored under `workspaces/`.
+///
+/// Files hold base64(nonce + AES-GCM ciphertext) of their JSON or YAML content.
+/// Commands and the library API go through this type rather than touching files directly.
+pub struct PromptRepository<'a> {
+    ctx: &'a AppCtx,
+}
+
+impl AppCtx {
+    /// Returns the repository of prompts and chains backed by this context.
+    pub fn prompts(&self) -> PromptRepository<'_> {
+        PromptRepository { ctx: self }
+    }
+}
+
+impl PromptRepository<'_> {
+    /// Whether a prompt with the given full ID exists.
+    pub fn exists(&self, full_id: &str) -> bool {
+        self.ctx.prompt_path(full_id).exists()
+    }
+
+    /// Loads and decrypts a prompt by its full ID. A link loads the prompt it points to.
+    pub fn load(&self, full_id: &str) -> Result<PromptData, String> {
+        let path = self.ctx.prompt_path(full_id);
+        if !path.exists() {
+            return Err(format!("No prompt with ID '{}'", full_id));
+        }
+        self.read_prompt(full_id, &path)
+    }
+
+    /// Loads and decrypts a prompt file without following links.
+    pub fn load_file(&self, full_id: &str) -> Result<PromptFile, String> {
+        let path = self.ctx.prompt_path(full_id);
+        if !path.exists() {
+            return Err(format!("No prompt with ID '{}'", full_id));
+        }
+        read_encrypted(&path, &self.ctx.cipher)
+    }
+
+    /// Returns the full ID of the prompt a link points to, or `None` for a prompt.
+    pub fn link_target(&self, full_id: &str) -> Result<Option<String>, String> {
+        Ok(match self.load_file(full_id)? {
+            PromptFile::Link { link } => Some(link),
+            PromptFile::Prompt(_) => None,
+        })
+    }
+
+    /// Lists the full IDs of the links pointing to the prompt `target`, in every
+    /// workspace.
+    pub fn links_to(&self, target: &str) -> Result<Vec<String>, String> {
+        let target = parse_id(target);
+        let mut links = Vec::new();
+        for workspace in self.workspaces()? {
+            let dir = self.ctx.workspaces_dir.join(&workspace);
+            let mut paths = Vec::new();
+            collect_files(&dir, "prompt", &mut paths)?;
+            for path in paths {
+                let Ok(PromptFile::Link { link }) = read_encrypted(&path, &self.ctx.cipher) else {
+                    continue;
+                };
+                if parse_id(&link) == target {
+                    let local_id = path
+                        .strip_prefix(&dir)
+                        .map_err(|e| e.to_string())?
+                        .with_extension("")
+                        .to_string_lossy()
+                        .replace('\\', "/");
+                    links.push(full_id(&workspace, &local_id));
+                }
+            }
+        }
+        links.sort();
+        Ok(links)
+    }
+
+    /// Turns a link into a copy of the prompt it points to, e.g. before that prompt is
+    /// deleted.
+    pub fn unlink(&self, full_id: &str) -> Result<(), String> {
+        let mut pd = self.load(full_id)?;
+        pd.id = parse_id(full_id).1;
+        self.save(full_id, &pd)
+    }
+
+    /// Encrypts and writes a prompt under its full ID, replacing any existing version.
+    /// Saving over a link turns it into a copy of its own.
+    ///
+    /// Fails if the change breaks a policy (see [`super::policy`]).
+    pub fn save(&self, full_id: &str, pd: &PromptData) -> Result<(), String> {
+        let policies = load_policies()?;
+        if !policies.is_empty() {
+            let previous = self.load(full_id).ok();
+            policies.check_save(full_id, pd, previous.as_ref())?;
+        }
+        write_encrypted(&self.ctx.prompt_path(full_id), &self.ctx.cipher, pd)
+    }
+
+    /// Writes a link under `full_id` to the prompt `target`, which must not be a link.
+    /// Loading the link returns the current version of the target.
+    pub fn save_link(&self, full_id: &str, target: &str) -> Result<(), String> {
+        if self.link_target(target)?.is_some() {
+            return Err(format!("'{}' is itself a link", target));
+        }
+        write_encrypted(
+            &self.ctx.prompt_path(full_id),
+            &self.ctx.cipher,
+            &PromptFile::Link {
+                link: target.to_string(),
+            },
+        )
+    }
+
+    /// Deletes a prompt by its full ID, with its attachments.
+    pub fn delete(&self, full_id: &str) -> Result<(), String> {
+        let path = self.ctx.prompt_path(full_id);
+        if !path.exists() {
+            return Err(format!("No prompt with ID '{}'", full_id));
+        }
+        fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+        let attachments = self.attachments_dir(full_id);
+        if attachments.is_dir() {
+            fs::remove_dir_all(&attachments).map_err(|e| format!("Delete error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Lists the workspace names, sorted.
+    pub fn workspaces(&self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.ctx.workspaces_dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if entry.path().is_dir() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Lists every prompt of a workspace, including chain steps, sorted by full ID.
+    /// Files that cannot be decrypted are skipped.
+    pub fn list(&self, workspace: &str) -> Result<Vec<StoredPrompt>, String> {
+        let dir = self.ctx.workspaces_dir.join(workspace);
+        let mut paths = Vec::new();
+        if dir.is_dir() {
+            collect_files(&dir, "prompt", &mut paths)?;
+        }
+
+        let mut prompts: Vec<StoredPrompt> = paths
+            .into_iter()
+            .filter_map(|path| {
+                let local_id = path
+                    .strip_prefix(&dir)
+                    .ok()?
+                    .with_extension("")
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let full_id = full_id(workspace, &local_id);
+                let data = self.read_prompt(&full_id, &path).ok()?;
+                Some(StoredPrompt { full_id, data })
+            })
+            .collect();
+        prompts.sort_by(|a, b| a.full_id.cmp(&b.full_id));
+        Ok(prompts)
+    }
+
+    /// Lists every prompt across all workspaces.
+    pub fn list_all(&self) -> Result<Vec<StoredPrompt>, String> {
+        let mut prompts = Vec::new();
+        for workspace in self.workspaces()? {
+            prompts.extend(self.list(&workspace)?);
+        }
+        Ok(prompts)
+    }
+
+    /// Lists the full ID and path of every prompt file across all workspaces, without
+    /// decrypting them.
+    pub fn files(&self) -> Result<Vec<(String, PathBuf)>, String> {
+        let mut files = Vec::new();
+        for workspace in self.workspaces()? {
+            let dir = self.ctx.workspaces_dir.join(&workspace);
+            let mut paths = Vec::new();
+            collect_files(&dir, "prompt", &mut paths)?;
+            for path in paths {
+                let Ok(local_id) = path.strip_prefix(&dir) else {
+                    continue;
+                };
+                let local_id = local_id
+                    .with_extension("")
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                files.push((full_id(&workspace, &local_id), path));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Loads the metadata of an interactive chain by its full ID.
+    pub fn load_chain(&self, full_id: &str) -> Result<ChainData, String> {
+        let path = self.chain_dir(full_id).join("chain.meta");
+        if !path.exists() {
+            return Err(format!("Chain with ID '{}' not found.", full_id));
+        }
+        read_encrypted(&path, &self.ctx.cipher)
+    }
+
+    /// Encrypts and writes the metadata of an interactive chain.
+    pub fn save_chain(&self, full_id: &str, chain: &ChainData) -> Result<(), String> {
+        write_encrypted(
+            &self.chain_dir(full_id).join("chain.meta"),
+            &self.ctx.cipher,
+            chain,
+        )
+    }
+
+    /// Lists the interactive chains of a workspace as `(full ID, metadata)`, sorted by ID.
+    pub fn list_chains(&self, workspace: &str) -> Result<Vec<(String, ChainData)>, String> {
+        let dir = self.ctx.workspaces_dir.join(workspace);
+        let mut chains = Vec::new();
+        if !dir.is_dir() {
+            return Ok(chains);
+        }
+        for entry in fs::read_dir(&dir).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let meta_path = path.join("chain.meta");
+            if !meta_path.is_file() {
+                continue;
+            }
+            if let Ok(chain) = read_encrypted::<ChainData>(&meta_path, &self.ctx.cipher) {
+                let local_id = path.file_name().unwrap_or_default().to_string_lossy();
+                chains.push((full_id(workspace, &local_id), chain));
+            }
+        }
+        chains.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(chains)
+    }
+
+    /// Loads the step prompts of an interactive chain, sorted by full ID.
+    pub fn chain_steps(&self, full_id: &str) -> Result<Vec<StoredPrompt>, String> {
+        let (workspace, _) = parse_id(full_id);
+        let prefix = format!("{}/", full_id);
+        Ok(self
+            .list(&workspace)?
+            .into_iter()
+            .filter(|p| p.full_id.starts_with(&prefix))
+            .collect())
+    }
+
+    /// Resolves a step of an interactive chain addressed by its title, such as
+    /// `mychain/"Extract Topic"` or `pack::mychain/extract topic` (case-insensitive),
+    /// to its full ID (`mychain/3`). Any other ID is returned unchanged.
+    pub fn resolve_step(&self, id: &str) -> Result<String, String> {
+        let (workspace, local_id) = parse_id(id);
+        let Some((chain_id, step)) = local_id.split_once('/') else {
+            return Ok(id.to_string());
+        };
+        let chain = full_id(&workspace, chain_id);
+        if self.exists(id) || !self.chain_dir(&chain).join("chain.meta").exists() {
+            return Ok(id.to_string());
+        }
+        let title = step.trim().trim_matches('"').trim();
+        let matches: Vec<String> = self
+            .chain_steps(&chain)?
+            .into_iter()
+            .filter(|s| s.data.title.eq_ignore_ascii_case(title))
+            .map(|s| s.full_id)
+            .collect();
+        match matches.as_slice() {
+            [] => Err(format!(
+                "Chain '{}' has no step '{}'. See 'prompt-store chain steps {}'.",
+                chain, title, chain
+            )),
+            [step_id] => Ok(step_id.clone()),
+            _ => Err(format!(
+                "Several steps of chain '{}' are titled '{}': {}. Use the step number.",
+                chain,
+                title,
+                matches.join(", ")
+            )),
+        }
+    }
+
+    /// Returns the directory holding an interactive chain's metadata and step prompts.
+    pub fn chain_dir(&self, full_id: &str) -> PathBuf {
+        let (workspace, local_id) = parse_id(full_id);
+        self.ctx.workspaces_dir.join(workspace).join(local_id)
+    }
+
+    /// Returns the path of a YAML chain definition from its full ID.
+    pub fn chain_definition_path(&self, full_id: &str) -> PathBuf {
+        let (workspace, local_id) = parse_id(full_id);
+        self.ctx
+            .workspaces_dir
+            .join(workspace)
+            .join("chains")
+            .join(format!("{}.chain", local_id))
+    }
+
+    /// Lists the IDs of the YAML chain definitions of a workspace, sorted.
+    pub fn list_chain_definitions(&self, workspace: &str) -> Result<Vec<String>, String> {
+        let dir = self.ctx.workspaces_dir.join(workspace).join("chains");
+        let mut ids = Vec::new();
+        if !dir.is_dir() {
+            return Ok(ids);
+        }
+        for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("chain") {
+                let local_id = path.file_stem().unwrap_or_default().to_string_lossy();
+                ids.push(full_id(workspace, &local_id));
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Loads and decrypts a YAML chain definition.
+    pub fn load_chain_definition(&self, full_id: &str) -> Result<String, String> {
+        let path = self.chain_definition_path(full_id);
+        if !path.exists() {
+            return Err(format!("Chain with ID '{}' not found.", full_id));
+        }
+        let yaml = decrypt_file(&path, &self.ctx.cipher)
+            .map_err(|_| "Failed to decrypt chain file. Check master password.".to_string())?;
+        std::str::from_utf8(&yaml)
+            .map(str::to_string)
+            .map_err(|_| "Chain file is not valid UTF-8".to_string())
+    }
+
+    /// Encrypts and writes a YAML chain definition.
+    pub fn save_chain_definition(&self, full_id: &str, yaml: &str) -> Result<(), String> {
+        let encoded = encrypt_to_base64(&self.ctx.cipher, yaml.as_bytes())?;
+        write_atomic(&self.chain_definition_path(full_id), &encoded)
+    }
+
+    /// Returns the path of the variable presets of a YAML chain, next to its definition.
+    pub fn chain_presets_path(&self, full_id: &str) -> PathBuf {
+        self.chain_definition_path(full_id)
+            .with_extension("presets")
+    }
+
+    /// Loads the named variable sets saved for a YAML chain, keyed by preset name.
+    pub fn load_chain_presets(
+        &self,
+        full_id: &str,
+    ) -> Result<BTreeMap<String, BTreeMap<String, String>>, String> {
+        let path = self.chain_presets_path(full_id);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        read_encrypted(&path, &self.ctx.cipher)
+    }
+
+    /// Encrypts and writes the variable presets of a YAML chain, removing the file when
+    /// none are left.
+    pub fn save_chain_presets(
+        &self,
+        full_id: &str,
+        presets: &BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Result<(), String> {
+        let path = self.chain_presets_path(full_id);
+        if presets.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+            }
+            return Ok(());
+        }
+        write_encrypted(&path, &self.ctx.cipher, presets)
+    }
+
+    /// Returns the path of the description of a workspace.
+    pub fn workspace_info_path(&self, workspace: &str) -> PathBuf {
+        self.ctx
+            .workspaces_dir
+            .join(workspace)
+            .join("workspace.meta")
+    }
+
+    /// Loads the description of a workspace, if it has one.
+    pub fn load_workspace_info(&self, workspace: &str) -> Result<Option<WorkspaceInfo>, String> {
+        let path = self.workspace_info_path(workspace);
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_encrypted(&path, &self.ctx.cipher).map(Some)
+    }
+
+    /// Checks that the prompts of a workspace may run on a provider, as the description
+    /// of the workspace allows (see [`WorkspaceInfo::check_provider`]). The backend of
+    /// the provider is looked up in `config.toml`.
+    pub fn check_provider(&self, workspace: &str, provider: &str) -> Result<(), String> {
+        match self.load_workspace_info(workspace)? {
+            Some(info) if info.restricts_providers() => {
+                let backend = provider_backend(provider)?;
+                info.check_provider(workspace, provider, backend.as_deref())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Encrypts and writes the description of a workspace, removing the file when it is
+    /// empty.
+    pub fn save_workspace_info(&self, workspace: &str, info: &WorkspaceInfo) -> Result<(), String> {
+        let path = self.workspace_info_path(workspace);
+        if info.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+            }
+            return Ok(());
+        }
+        write_encrypted(&path, &self.ctx.cipher, info)
+    }
+
+    /// Lists the timestamps of the backups of a prompt (`<id>.<timestamp>.bak`), oldest
+    /// first.
+    pub fn list_backups(&self, full_id: &str) -> Result<Vec<String>, String> {
+        let path = self.ctx.prompt_path(full_id);
+        let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}.", stem.to_string_lossy());
+        let mut timestamps = Vec::new();
+        if !dir.is_dir() {
+            return Ok(timestamps);
+        }
+        for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let name = entry
+                .map_err(|e| format!("Dir entry error: {}", e))?
+                .file_name()
+                .to_string_lossy()
+                .into_owned();
+            if let Some(ts) = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".bak"))
+            {
+                timestamps.push(ts.to_string());
+            }
+        }
+        timestamps.sort();
+        Ok(timestamps)
+    }
+
+    /// Loads and decrypts a backup of a prompt by its timestamp.
+    pub fn load_backup(&self, full_id: &str, timestamp: &str) -> Result<PromptData, String> {
+        let path = self.ctx.prompt_path(full_id);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let backup = path.with_file_name(format!("{}.{}.bak", stem, timestamp));
+        if !backup.is_file() {
+            return Err(format!(
+                "No backup '{}' of prompt '{}'. See 'prompt-store history {}'.",
+                timestamp, full_id, full_id
+            ));
+        }
+        self.read_prompt(full_id, &backup)
+    }
+
+    /// Returns the directory holding the attachments of a prompt, next to its file
+    /// (`<id>.attachments/<name>.att`).
+    pub fn attachments_dir(&self, full_id: &str) -> PathBuf {
+        self.ctx.prompt_path(full_id).with_extension("attachments")
+    }
+
+    /// Lists the attachment names of a prompt, sorted.
+    pub fn list_attachments(&self, full_id: &str) -> Result<Vec<String>, String> {
+        let dir = self.attachments_dir(full_id);
+        let mut names = Vec::new();
+        if !dir.is_dir() {
+            return Ok(names);
+        }
+        for entry in fs::read_dir(&dir).map_err(|e| format!("Read dir error: {}", e))? {
+            let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("att") {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                names.push(name.into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Loads and decrypts an attachment of a prompt.
+    pub fn load_attachment(&self, full_id: &str, name: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+        let path = self.attachment_path(full_id, name)?;
+        if !path.exists() {
+            return Err(format!("Prompt '{}' has no attachment '{}'", full_id, name));
+        }
+        decrypt_file(&path, &self.ctx.cipher)
+    }
+
+    /// Encrypts and writes an attachment of a prompt, replacing one of the same name.
+    pub fn save_attachment(&self, full_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        if !self.exists(full_id) {
+            return Err(format!("No prompt with ID '{}'", full_id));
+        }
+        let path = self.attachment_path(full_id, name)?;
+        ensure_dir(&self.attachments_dir(full_id))?;
+        write_atomic(&path, encrypt_to_base64(&self.ctx.cipher, data)?)
+    }
+
+    /// Deletes an attachment of a prompt.
+    pub fn delete_attachment(&self, full_id: &str, name: &str) -> Result<(), String> {
+        let path = self.attachment_path(full_id, name)?;
+        if !path.exists() {
+            return Err(format!("Prompt '{}' has no attachment '{}'", full_id, name));
+        }
+        fs::remove_file(&path).map_err(|e| format!("Delete error: {}", e))?;
+        // Leave no empty folder behind; it fails harmlessly while attachments remain.
+        let _ = fs::remove_dir(self.attachments_dir(full_id));
+        Ok(())
+    }
+
+    /// Moves the attachments of a prompt to a new ID, when the prompt is renamed.
+    pub fn move_attachments(&self, from: &str, to: &str) -> Result<(), String> {
+        let dir = self.attachments_dir(from);
+        if dir.is_dir() {
+            fs::rename(&dir, self.attachments_dir(to))
+                .map_err(|e| format!("Rename error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Loads the attachments of a prompt in the inline form used by bundles and packs.
+    pub fn inline_attachments(&self, full_id: &str) -> Result<Vec<Attachment>, String> {
+        self.list_attachments(full_id)?
+            .into_iter()
+            .map(|name| {
+                let data = general_purpose::STANDARD.encode(self.load_attachment(full_id, &name)?);
+                Ok(Attachment { name, data })
+            })
+            .collect()
+    }
+
+    /// Saves a prompt received in a bundle or pack, writing its inline attachments to
+    /// their own files.
+    pub fn save_with_attachments(&self, full_id: &str, pd: &PromptData) -> Result<(), String> {
+        let attachments = &pd.attachments;
+        let pd = PromptData {
+            attachments: Vec::new(),
+            ..pd.clone()
+        };
+        self.save(full_id, &pd)?;
+        for attachment in attachments {
+            let data = general_purpose::STANDARD
+                .decode(&attachment.data)
+                .map(Zeroizing::new)
+                .map_err(|_| format!("Attachment '{}' is not valid base64", attachment.name))?;
+            self.save_attachment(full_id, &attachment.name, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the `{{attachment:<name>}}` placeholders of a prompt's content with the
+    /// text of its attachments.
+    pub fn expand_attachments(&self, full_id: &str, content: &str) -> Result<String, String> {
+        let re = Regex::new(r"\{\{\s*attachment:([\w-]+)\s*\}\}").unwrap();
+        let mut error = None;
+        let expanded = re.replace_all(content, |caps: &regex::Captures| {
+            let text = self.load_attachment(full_id, &caps[1]).and_then(|data| {
+                String::from_utf8(data.to_vec())
+                    .map_err(|_| format!("Attachment '{}' is not text", &caps[1]))
+            });
+            text.unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                String::new()
+            })
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(expanded.into_owned()),
+        }
+    }
+
+    /// Reads a prompt file, following it if it is a link.
+    fn read_prompt(&self, full_id: &str, path: &Path) -> Result<PromptData, String> {
+        match read_encrypted(path, &self.ctx.cipher)? {
+            PromptFile::Prompt(pd) => Ok(*pd),
+            PromptFile::Link { link } => {
+                let target = self.ctx.prompt_path(&link);
+                if !target.exists() {
+                    return Err(format!(
+                        "Prompt '{}' links to '{}', which no longer exists",
+                        full_id, link
+                    ));
+                }
+                match read_encrypted(&target, &self.ctx.cipher)? {
+                    PromptFile::Prompt(pd) => Ok(*pd),
+                    PromptFile::Link { .. } => Err(format!(
+                        "Prompt '{}' links to '{}', which is itself a link",
+                        full_id, link
+                    )),
+                }
+            }
+        }
+    }
+
+    fn attachment_path(&self, full_id: &str, name: &str) -> Result<PathBuf, String> {
+        validate_attachment_name(name)?;
+        Ok(self.attachments_dir(full_id).join(format!("{}.att", name)))
+    }
+}
+
+/// Checks that an attachment name only uses letters, digits, `_` and `-`, so it can be
+/// referenced as `{{attachment:<name>}}`.
+pub fn validate_attachment_name(name: &str) -> Result<(), String> {
+    let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(valid) {
+        return Err(format!(
+            "Invalid attachment name '{}': use letters, digits, '_' and '-'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the last modification time of a file.
+pub fn modified_at(path: &Path) -> Option<DateTime<Utc>> {
+    fs::metadata(path).and_then(|m| m.modified()).ok().map(DateTime::from)
+}
+
+/// Builds a full ID from a workspace and local ID, omitting the `default` workspace.
+pub fn full_id(workspace: &str, local_id: &str) -> String {
+    if workspace == "default" {
+        local_id.to_string()
+    } else {
+        format!("{}::{}", workspace, local_id)
     }
-    let (nonce_bytes, cipher_bytes) = decoded.split_at(12);
-    let plaintext = cipher
-        .decrypt(Nonce::from_slice(nonce_bytes), cipher_bytes)
-        .map_err(|_| "Decrypt error".to_string())?;
-    serde_json::from_slice(&plaintext).map_err(|_| "Invalid JSON".to_string())
-}
\ No newline at end of file
+}
+
+fn read_encrypted<T: DeserializeOwned>(path: &Path, cipher: &StoreCipher) -> Result<T, String> {
+    let plaintext = decrypt_file(path, cipher)?;
+    serde_json::from_slice(&plaintext).map_err(|_| {
+        warn!("{} decrypted to invalid JSON", path.display());
+        "Invalid JSON".to_string()
+    })
+}
+
+/// Reads and decrypts a store file, logging the failures with its path.
+fn decrypt_file(path: &Path, cipher: &StoreCipher) -> Result<Zeroizing<Vec<u8>>, String> {
+    debug!("reading {}", path.display());
+    let encoded = fs::read_to_string(path).map_err(|e| {
+        warn!("cannot read {}: {}", path.display(), e);
+        format!("Read error: {}", e)
+    })?;
+    decrypt_from_base64(cipher, &encoded).inspect_err(|e| {
+        warn!("cannot decrypt {}: {}", path.display(), e);
+    })
+}
+
+fn write_encrypted<T: Serialize>(path: &Path, cipher: &StoreCipher, value: &T) -> Result<(), String> {
+    let json =
+        Zeroizing::new(serde_json::to_vec(value).map_err(|e| format!("Serialize error: {}", e))?);
+    let encoded = encrypt_to_base64(cipher, &json)?;
+    write_atomic(path, &encoded)
+}
+
+/// Writes through a temporary file and renames it into place, so a crash never
+/// leaves a half-written file behind. The file is only readable by its owner.
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    trace!("writing {}", path.display());
+    fs::write(&tmp_path, contents).map_err(|e| {
+        warn!("cannot write {}: {}", tmp_path.display(), e);
+        format!("Write error: {}", e)
+    })?;
+    restrict_permissions(&tmp_path);
+    fs::rename(&tmp_path, path).map_err(|e| format!("Write error: {}", e))
+}
+
+/// Recursively collects the files with the given extension under `dir`.
+fn collect_files(dir: &Path, extension: &str, paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Read dir error: {}", e))? {
+        let path = entry.map_err(|e| format!("Dir entry error: {}", e))?.path();
+        if path.is_dir() {
+            collect_files(&path, extension, paths)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some(extension) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn providers(allowed: &[&str], denied: &[&str]) -> WorkspaceInfo {
+        WorkspaceInfo {
+            allowed_providers: allowed.iter().map(|p| p.to_string()).collect(),
+            denied_providers: denied.iter().map(|p| p.to_string()).collect(),
+            ..WorkspaceInfo::default()
+        }
+    }
+
+    fn allows(info: &WorkspaceInfo, provider: &str, backend: Option<&str>) -> bool {
+        info.check_provider("ws", provider, backend).is_ok()
+    }
+
+    #[test]
+    fn unrestricted_workspaces_allow_every_provider() {
+        let info = WorkspaceInfo::default();
+        assert!(!info.restricts_providers());
+        assert!(allows(&info, "openai", Some("openai")));
+    }
+
+    #[test]
+    fn denied_backends_cover_every_alias() {
+        let info = providers(&[], &["openai"]);
+        assert!(!allows(&info, "openai:gpt-4o", Some("openai")));
+        assert!(!allows(&info, "cheap-gpt", Some("OpenAI")));
+        assert!(allows(&info, "local", Some("ollama")));
+    }
+
+    #[test]
+    fn allowed_providers_match_names_or_backends() {
+        let info = providers(&["local-ollama", "anthropic"], &[]);
+        assert!(allows(&info, "local-ollama", Some("ollama")));
+        assert!(allows(&info, "claude", Some("anthropic")));
+        assert!(!allows(&info, "other-ollama", Some("ollama")));
+        assert!(!allows(&info, "unknown", None));
+    }
+
+    #[test]
+    fn denials_win_over_allowances() {
+        let info = providers(&["ollama"], &["big-ollama"]);
+        let err = info
+            .check_provider("ws", "big-ollama", Some("ollama"))
+            .unwrap_err();
+        assert!(err.contains("does not allow"));
+    }
+}
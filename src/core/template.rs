@@ -0,0 +1,235 @@
+//! Rendering of prompt templates: `{{var}}` placeholders and built-in helper functions
+//! such as `{{now "%Y-%m-%d"}}`, `{{uuid}}`, `{{upper name}}` or `{{truncate text 500}}`.
+//!
+//! Helper arguments are variable names, numbers or double-quoted strings. A variable
+//! named like a helper (e.g., `now`) takes precedence over the helper when it is set.
+//! Placeholders calling an unknown helper, or a helper with invalid arguments, are left
+//! as they are.
+
+use chrono::Local;
+use rand::Rng;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Helpers that can be called in a template.
+const FUNCTIONS: &[&str] = &[
+    "now", "uuid", "random", "upper", "lower", "title", "snake", "kebab", "camel", "truncate",
+];
+
+fn placeholder_re() -> Regex {
+    Regex::new(r#"\{\{\s*(\w+)((?:\s+(?:"(?:[^"\\]|\\.)*"|[\w.-]+))*)\s*\}\}"#).unwrap()
+}
+
+fn arg_re() -> Regex {
+    Regex::new(r#""((?:[^"\\]|\\.)*)"|([\w.-]+)"#).unwrap()
+}
+
+/// An argument of a helper call.
+enum Arg<'a> {
+    /// A double-quoted string, with its escapes.
+    Literal(String),
+    /// A variable name or a number.
+    Word(&'a str),
+}
+
+/// Renders a template, substituting variables (missing ones become empty) and calling
+/// helpers.
+pub fn render(template: &str, vars: &HashMap<String, String>) -> String {
+    let arg_re = arg_re();
+    placeholder_re()
+        .replace_all(template, |caps: &Captures| {
+            let name = &caps[1];
+            let args: Vec<Arg> = arg_re
+                .captures_iter(&caps[2])
+                .map(|arg| match (arg.get(1), arg.get(2)) {
+                    (Some(literal), _) => Arg::Literal(unescape(literal.as_str())),
+                    (_, Some(word)) => Arg::Word(word.as_str()),
+                    _ => unreachable!(),
+                })
+                .collect();
+            if args.is_empty() {
+                if let Some(value) = vars.get(name) {
+                    return value.clone();
+                }
+                if !FUNCTIONS.contains(&name) {
+                    return String::new();
+                }
+            }
+            call(name, &args, vars).unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// The distinct variables a template uses, directly or as helper arguments, in order of
+/// appearance.
+pub fn variables(template: &str) -> Vec<String> {
+    let arg_re = arg_re();
+    let mut names: Vec<String> = Vec::new();
+    for caps in placeholder_re().captures_iter(template) {
+        let words: Vec<&str> = arg_re
+            .captures_iter(caps.get(2).map_or("", |m| m.as_str()))
+            .filter_map(|arg| arg.get(2))
+            .map(|word| word.as_str())
+            .filter(|word| word.parse::<f64>().is_err())
+            .collect();
+        let found = match caps.get(1).map(|m| m.as_str()) {
+            Some(name) if FUNCTIONS.contains(&name) => words,
+            Some(name) if words.is_empty() => vec![name],
+            // An unknown helper is not rendered, its arguments are not used.
+            _ => Vec::new(),
+        };
+        for name in found {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Calls a helper, returning `None` for an unknown helper or invalid arguments.
+fn call(name: &str, args: &[Arg], vars: &HashMap<String, String>) -> Option<String> {
+    let text = |i: usize| -> Option<String> {
+        match args.get(i)? {
+            Arg::Literal(value) => Some(value.clone()),
+            Arg::Word(word) => Some(vars.get(*word).cloned().unwrap_or_default()),
+        }
+    };
+    let number = |i: usize| -> Option<i64> {
+        match args.get(i)? {
+            Arg::Word(word) => word.parse().ok(),
+            Arg::Literal(_) => None,
+        }
+    };
+    let arity = |n: usize| (args.len() == n).then_some(());
+
+    match name {
+        "now" => {
+            let format = match args {
+                [] => "%Y-%m-%d %H:%M:%S".to_string(),
+                [_] => text(0)?,
+                _ => return None,
+            };
+            let mut out = String::new();
+            // An invalid format string fails here instead of panicking.
+            write!(out, "{}", Local::now().format(&format)).ok()?;
+            Some(out)
+        }
+        "uuid" => {
+            arity(0)?;
+            Some(uuid_v4())
+        }
+        "random" => {
+            let (min, max) = match args.len() {
+                0 => (0, i64::from(u32::MAX)),
+                1 => (0, number(0)?),
+                2 => (number(0)?, number(1)?),
+                _ => return None,
+            };
+            (min <= max).then(|| rand::thread_rng().gen_range(min..=max).to_string())
+        }
+        "upper" | "lower" | "title" | "snake" | "kebab" | "camel" => {
+            arity(1)?;
+            let value = text(0)?;
+            Some(match name {
+                "upper" => value.to_uppercase(),
+                "lower" => value.to_lowercase(),
+                "title" => words(&value)
+                    .iter()
+                    .map(|w| capitalize(w))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                "snake" => words(&value).join("_").to_lowercase(),
+                "kebab" => words(&value).join("-").to_lowercase(),
+                _ => words(&value)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| match i {
+                        0 => w.to_lowercase(),
+                        _ => capitalize(w),
+                    })
+                    .collect(),
+            })
+        }
+        "truncate" => {
+            arity(2)?;
+            let max = usize::try_from(number(1)?).ok()?;
+            Some(text(0)?.chars().take(max).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Splits text into words at non-alphanumeric characters and lowercase-to-uppercase
+/// boundaries, so `fooBar baz-qux` gives `foo`, `Bar`, `baz`, `qux`.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Resolves `\"`, `\\` and `\n` in a quoted argument.
+fn unescape(literal: &str) -> String {
+    let mut out = String::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                out.push('\n');
+            }
+            ('\\', Some(escaped)) => {
+                chars.next();
+                out.push(escaped);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A random (version 4) UUID.
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,137 @@
+//! API tokens of `prompt-store serve`, each with a scope and optionally restricted to
+//! some workspaces, stored encrypted in `tokens.json`.
+//!
+//! Only the SHA-256 of a token is kept: the token itself is shown once, when created.
+//! While no token exists, the server accepts every request.
+
+use super::cipher::StoreCipher;
+use super::crypto::{decrypt_from_base64, encrypt_to_base64};
+use super::storage::write_atomic;
+use super::utils::hex;
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// File of the tokens in the store directory.
+pub const TOKENS_FILE: &str = "tokens.json";
+
+/// What a token gives access to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// Rendering prompts, e.g. for CI checks, without running them.
+    ReadOnly,
+    /// Running prompts and chains, e.g. for a frontend, without reading the prompts.
+    RunOnly,
+    /// Every operation.
+    Admin,
+}
+
+/// Kind of server operation, checked against the scope of a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Returns prompt content.
+    Read,
+    /// Sends prompts to providers and returns their answers.
+    Run,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::ReadOnly => "read-only",
+            Scope::RunOnly => "run-only",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn allows(self, operation: Operation) -> bool {
+        matches!(
+            (self, operation),
+            (Scope::Admin, _)
+                | (Scope::ReadOnly, Operation::Read)
+                | (Scope::RunOnly, Operation::Run)
+        )
+    }
+}
+
+/// A token of the server.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    /// Hex SHA-256 of the token.
+    hash: String,
+    pub scope: Scope,
+    /// Workspaces the token can use, every workspace when empty.
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    /// Requests allowed per minute, unlimited when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u32>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiToken {
+    /// Creates a token. Returns it with its secret value, to show once.
+    pub fn generate(name: &str, scope: Scope, workspaces: Vec<String>) -> (Self, String) {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = format!("pst_{}", general_purpose::URL_SAFE_NO_PAD.encode(bytes));
+        let token = Self {
+            name: name.to_string(),
+            hash: hash(&secret),
+            scope,
+            workspaces,
+            rate_limit: None,
+            created_at: Utc::now(),
+        };
+        (token, secret)
+    }
+
+    /// Whether the token can use a workspace.
+    pub fn allows_workspace(&self, workspace: &str) -> bool {
+        self.workspaces.is_empty() || self.workspaces.iter().any(|w| w == workspace)
+    }
+}
+
+/// Returns the path of the encrypted token file.
+pub fn tokens_path(base_dir: &Path) -> PathBuf {
+    base_dir.join(TOKENS_FILE)
+}
+
+/// Loads the tokens, none if the file does not exist.
+pub fn load_tokens(base_dir: &Path, cipher: &StoreCipher) -> Result<Vec<ApiToken>, String> {
+    let path = tokens_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let encoded = fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let plaintext = decrypt_from_base64(cipher, &encoded)?;
+    serde_json::from_slice(&plaintext).map_err(|_| "Invalid token file".to_string())
+}
+
+/// Encrypts and writes the tokens.
+pub fn save_tokens(
+    base_dir: &Path,
+    cipher: &StoreCipher,
+    tokens: &[ApiToken],
+) -> Result<(), String> {
+    let json =
+        Zeroizing::new(serde_json::to_vec(tokens).map_err(|e| format!("Serialize error: {}", e))?);
+    write_atomic(&tokens_path(base_dir), encrypt_to_base64(cipher, &json)?)
+}
+
+/// Finds the token of a secret value.
+pub fn find_token<'a>(tokens: &'a [ApiToken], secret: &str) -> Option<&'a ApiToken> {
+    let hash = hash(secret);
+    tokens.iter().find(|token| token.hash == hash)
+}
+
+fn hash(secret: &str) -> String {
+    hex(&Sha256::digest(secret.as_bytes()))
+}
@@ -0,0 +1,76 @@
+//! Local counts of how often prompts are run or copied, kept in `usage.json`.
+//!
+//! They feed `stats` and `pack stats`, and stay on this machine unless the counts of a
+//! pack are exported with `pack stats --export`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use super::storage::parse_id;
+
+/// How a prompt was used.
+#[derive(Clone, Copy)]
+pub enum Usage {
+    Run,
+    Copy,
+}
+
+/// Usage counts of one prompt.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct PromptUsage {
+    #[serde(default)]
+    pub runs: u64,
+    #[serde(default)]
+    pub copies: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Utc>>,
+    /// Runs served by each variant of the prompt, by variant name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, u64>,
+}
+
+/// Counts by workspace, then by prompt ID.
+pub type UsageLog = HashMap<String, HashMap<String, PromptUsage>>;
+
+/// Counts one use of a prompt. Failures are ignored so the command itself never fails.
+pub fn record(base_dir: &Path, full_id: &str, usage: Usage) {
+    update(base_dir, full_id, |entry| match usage {
+        Usage::Run => entry.runs += 1,
+        Usage::Copy => entry.copies += 1,
+    });
+}
+
+/// Counts one run of a prompt served by one of its variants, to compare the variants of
+/// an experiment later.
+pub fn record_variant(base_dir: &Path, full_id: &str, variant: &str) {
+    update(base_dir, full_id, |entry| {
+        *entry.variants.entry(variant.to_string()).or_default() += 1
+    });
+}
+
+fn update(base_dir: &Path, full_id: &str, apply: impl FnOnce(&mut PromptUsage)) {
+    let (workspace, id) = parse_id(full_id);
+    let mut log = load(base_dir);
+    let entry = log.entry(workspace).or_default().entry(id).or_default();
+    apply(entry);
+    entry.last_used = Some(Utc::now());
+    if let Ok(content) = serde_json::to_string_pretty(&log) {
+        fs::write(base_dir.join("usage.json"), content).ok();
+    }
+}
+
+/// Returns the usage counts of the prompts of a workspace.
+pub fn for_workspace(base_dir: &Path, workspace: &str) -> HashMap<String, PromptUsage> {
+    load(base_dir).remove(workspace).unwrap_or_default()
+}
+
+/// Returns the usage counts of every workspace.
+pub fn load(base_dir: &Path) -> UsageLog {
+    fs::read_to_string(base_dir.join("usage.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
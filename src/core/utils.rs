@@ -1,9 +1,10 @@
+use super::config::{load_id_scheme, IdScheme};
+#[cfg(feature = "cli")]
+use dialoguer::Editor;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
 use std::path::Path;
-
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Ensure directory exists.
 pub fn ensure_dir(path: &Path) -> Result<(), String> {
@@ -11,15 +12,81 @@ pub fn ensure_dir(path: &Path) -> Result<(), String> {
         fs::create_dir_all(path)
             .map_err(|e| format!("Unable to create directory {}: {}", path.display(), e))?;
     }
+    restrict_permissions(path);
+    Ok(())
+}
+
+/// Restricts a file or directory to its owner: mode 0600/0700 on Unix, an ACL granting
+/// only the current user access on Windows. Failures are ignored.
+pub fn restrict_permissions(path: &Path) {
     #[cfg(unix)]
     {
-        fs::set_permissions(path, fs::Permissions::from_mode(0o700)).ok();
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if path.is_dir() { 0o700 } else { 0o600 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).ok();
     }
-    Ok(())
+    #[cfg(windows)]
+    {
+        // Files inherit the ACL of their directory, so only directories are updated.
+        if let (true, Ok(user)) = (path.is_dir(), std::env::var("USERNAME")) {
+            // Drop inherited entries (e.g., Users/Everyone) and keep the owner only.
+            std::process::Command::new("icacls")
+                .arg(path)
+                .args(["/inheritance:r", "/grant:r", &format!("{}:(OI)(CI)F", user)])
+                .output()
+                .ok();
+        }
+    }
+}
+
+/// Returns a text editor prompt.
+///
+/// dialoguer picks `VISUAL`, then `EDITOR`, then `notepad.exe` on Windows or `vi` elsewhere.
+/// On Windows an editor given as a plain path is quoted, so its backslashes and spaces
+/// survive dialoguer's shell-style argument splitting.
+#[cfg(feature = "cli")]
+pub fn editor() -> Editor {
+    #[allow(unused_mut)]
+    let mut editor = Editor::new();
+    #[cfg(windows)]
+    if let Some(path) = std::env::var_os("VISUAL").or_else(|| std::env::var_os("EDITOR")) {
+        if Path::new(&path).is_file() {
+            editor.executable(format!("\"{}\"", path.to_string_lossy()));
+        }
+    }
+    editor
 }
 
-/// Generate a new unique alphanumeric ID.
-pub fn new_id(dir: &Path) -> String {
+/// Generate a new unique ID using the scheme configured in `config.toml`.
+///
+/// `title` is only used by the slug scheme; an ID is unique if no prompt or chain in `dir` uses it.
+pub fn new_id(dir: &Path, title: &str) -> Result<String, String> {
+    let id = match load_id_scheme()? {
+        IdScheme::Random => random_id(dir),
+        IdScheme::Ulid => loop {
+            let id = ulid();
+            if !id_taken(dir, &id) {
+                break id;
+            }
+        },
+        IdScheme::Slug => match slugify(title) {
+            slug if slug.is_empty() => random_id(dir),
+            slug => (1..)
+                .map(|n| {
+                    if n == 1 {
+                        slug.clone()
+                    } else {
+                        format!("{}-{}", slug, n)
+                    }
+                })
+                .find(|id| !id_taken(dir, id))
+                .unwrap(),
+        },
+    };
+    Ok(id)
+}
+
+fn random_id(dir: &Path) -> String {
     loop {
         let id: String = rand::thread_rng()
             .sample_iter(&Alphanumeric)
@@ -28,9 +95,51 @@ pub fn new_id(dir: &Path) -> String {
             .collect::<String>()
             .to_lowercase();
 
-        let path = dir.join(format!("{}.prompt", &id));
-        if !path.exists() {
+        if !id_taken(dir, &id) {
             return id;
         }
     }
 }
+
+fn id_taken(dir: &Path, id: &str) -> bool {
+    dir.join(format!("{}.prompt", id)).exists()
+        || dir.join(id).exists()
+        || dir.join("chains").join(format!("{}.chain", id)).exists()
+}
+
+/// Builds a lowercase ULID: 48 bits of milliseconds since the epoch followed by
+/// 80 random bits, encoded as 26 Crockford base32 characters.
+fn ulid() -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let random: u128 = rand::thread_rng().gen::<u128>() & ((1 << 80) - 1);
+    let value = ((millis & ((1 << 48) - 1)) << 80) | random;
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Turns a title into a slug of lowercase alphanumerics separated by single dashes,
+/// e.g. "Summarize Meeting Notes!" becomes `summarize-meeting-notes`.
+pub fn slugify(title: &str) -> String {
+    const MAX_LEN: usize = 48;
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_LEN);
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Lowercase hex encoding.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,101 @@
+//! Variables of chain runs read from files, the environment and stdin, resolved the same
+//! way by `prompt-store chain run` and `ChainRunner::vars_auto()`.
+//!
+//! The sources are merged in this order, each one overriding the previous ones:
+//!
+//! 1. The vars files (`--vars-file`, or the path in `PS_VARS_FILE`): a JSON or YAML
+//!    object.
+//! 2. The environment: `PS_VAR_TOPIC=sales` sets `topic`. Names are lowercased.
+//! 3. A JSON object read from stdin (`--vars-stdin`).
+//!
+//! Variables given explicitly (`--var`, `vars()`) override them all, and they all
+//! override the preset, the environment profile and the defaults of the chain. Values
+//! that aren't strings are kept as JSON text.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Prefix of the environment variables holding chain variables.
+pub const ENV_PREFIX: &str = "PS_VAR_";
+/// Environment variable naming a vars file read when none is given.
+pub const VARS_FILE_ENV: &str = "PS_VARS_FILE";
+
+/// Where chain variables are read from besides explicit assignments.
+#[derive(Clone, Debug, Default)]
+pub struct VarSources {
+    /// JSON or YAML files holding an object of variables, later ones taking precedence.
+    pub files: Vec<PathBuf>,
+    /// Whether to read a JSON object of variables from stdin.
+    pub stdin: bool,
+}
+
+impl VarSources {
+    /// The sources used when nothing else is specified: the file named by
+    /// `PS_VARS_FILE`, if set, and the `PS_VAR_*` environment variables.
+    pub fn auto() -> Self {
+        Self {
+            files: env::var_os(VARS_FILE_ENV)
+                .map(PathBuf::from)
+                .into_iter()
+                .collect(),
+            stdin: false,
+        }
+    }
+
+    /// Reads and merges the variables of every source.
+    pub fn resolve(&self) -> Result<HashMap<String, String>, String> {
+        let mut vars = HashMap::new();
+        for path in &self.files {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            // YAML is a superset of JSON, so one parser reads both.
+            let value: Value = serde_yaml::from_str(&content)
+                .map_err(|e| format!("Invalid vars file {}: {}", path.display(), e))?;
+            vars.extend(object_vars(value, &path.display().to_string())?);
+        }
+        vars.extend(env_vars());
+        if self.stdin {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Unable to read stdin: {}", e))?;
+            let value: Value = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid JSON on stdin: {}", e))?;
+            vars.extend(object_vars(value, "stdin")?);
+        }
+        Ok(vars)
+    }
+}
+
+/// The `PS_VAR_*` environment variables, by lowercased name.
+fn env_vars() -> HashMap<String, String> {
+    // `vars_os`, as `vars` panics on a variable that isn't valid unicode.
+    env::vars_os()
+        .filter_map(|(key, value)| {
+            let name = key.to_str()?.strip_prefix(ENV_PREFIX)?;
+            if name.is_empty() {
+                return None;
+            }
+            Some((name.to_lowercase(), value.into_string().ok()?))
+        })
+        .collect()
+}
+
+fn object_vars(value: Value, source: &str) -> Result<HashMap<String, String>, String> {
+    let object = match value {
+        Value::Object(object) => object,
+        Value::Null => return Ok(HashMap::new()),
+        _ => return Err(format!("{}: expected an object of variables", source)),
+    };
+    Ok(object
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(text) => (key, text),
+            other => (key, other.to_string()),
+        })
+        .collect())
+}
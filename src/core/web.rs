@@ -0,0 +1,88 @@
+//! HTTP fetching used by fetch steps in chains.
+
+use super::config::{http_client, load_step_timeout};
+use super::policy::{load_policies, Policies};
+use regex::Regex;
+use tokio::time::timeout;
+
+/// Default maximum number of bytes read from a fetched URL.
+pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+/// Fetches a URL and returns its body as text, truncated to `max_bytes`.
+/// When `readable` is set, HTML markup is stripped down to the visible text.
+///
+/// The request goes through the `[defaults]` proxy of `config.toml` and fails after its
+/// step timeout. A policy's `allowed_url_hosts` apply to the URL and to where it redirects.
+pub async fn fetch_text(url: &str, max_bytes: usize, readable: bool) -> Result<String, String> {
+    let policies = load_policies()?;
+    policies.check_url(url)?;
+    let client = http_client()?;
+    let fetch = read_body(&client, url, max_bytes, &policies);
+    let body = timeout(load_step_timeout()?, fetch)
+        .await
+        .map_err(|_| format!("Failed to fetch '{}': timed out", url))??;
+
+    let text = String::from_utf8_lossy(&body).into_owned();
+    Ok(if readable {
+        extract_readable(&text)
+    } else {
+        text
+    })
+}
+
+/// Reads up to `max_bytes` of the body of a URL.
+async fn read_body(
+    client: &reqwest::Client,
+    url: &str,
+    max_bytes: usize,
+    policies: &Policies,
+) -> Result<Vec<u8>, String> {
+    let mut resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch '{}': {}", url, e))?;
+    policies.check_url(resp.url().as_str())?;
+    if !resp.status().is_success() {
+        return Err(format!("Failed to fetch '{}': HTTP {}", url, resp.status()));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read '{}': {}", url, e))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() >= max_bytes {
+            body.truncate(max_bytes);
+            break;
+        }
+    }
+    Ok(body)
+}
+
+/// Reduces an HTML document to its readable text content.
+pub fn extract_readable(html: &str) -> String {
+    let hidden = Regex::new(r"(?is)<(script|style|noscript|head|nav|footer)\b.*?</(script|style|noscript|head|nav|footer)>").unwrap();
+    let breaks = Regex::new(r"(?i)<(br|/p|/div|/li|/h[1-6]|/tr)\b[^>]*>").unwrap();
+    let tags = Regex::new(r"(?s)<[^>]*>").unwrap();
+    let blank_lines = Regex::new(r"\n\s*\n+").unwrap();
+
+    let text = hidden.replace_all(html, "");
+    let text = breaks.replace_all(&text, "\n");
+    let text = tags.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    blank_lines
+        .replace_all(&lines.join("\n"), "\n\n")
+        .trim()
+        .to_string()
+}
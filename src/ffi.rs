@@ -0,0 +1,238 @@
+//! A small C ABI over the library, so programs in other languages can use the same
+//! encrypted store. Available with the `ffi` feature; the declarations are in
+//! `include/prompt_store.h`.
+//!
+//! Strings passed in are NUL-terminated UTF-8. Strings returned are owned by the caller
+//! and freed with `ps_string_free`. A function that fails returns NULL, and
+//! `ps_last_error` tells why.
+
+use crate::api::{PromptStore, RunOutput};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use tokio::runtime::{Builder, Runtime};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An unlocked store with the runtime its runs are driven by.
+pub struct StoreHandle {
+    store: PromptStore,
+    runtime: Runtime,
+}
+
+impl StoreHandle {
+    /// Unlocks the store with its password, or with `None` when the key isn't protected
+    /// by one (a protected key is then asked for on the terminal).
+    pub fn open(password: Option<&str>) -> Result<Self, String> {
+        let store = match password {
+            Some(password) => PromptStore::with_password(password),
+            None => PromptStore::init(),
+        }
+        .map_err(|e| e.to_string())?;
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to start the runtime: {}", e))?;
+        Ok(Self { store, runtime })
+    }
+
+    /// Finds a prompt by ID or title and returns it as JSON.
+    pub fn find(&self, id: &str) -> Result<String, String> {
+        let pd = self
+            .store
+            .find_prompt(None, id)
+            .map_err(|e| e.to_string())?;
+        serde_json::to_string(&pd).map_err(|e| format!("Serialize error: {}", e))
+    }
+
+    /// Renders a prompt with its variables.
+    pub fn render(&self, id: &str, vars: HashMap<String, String>) -> Result<String, String> {
+        let output = self
+            .runtime
+            .block_on(self.store.prompt(id).vars(vars).run())
+            .map_err(|e| e.to_string())?;
+        Ok(prompt_text(output))
+    }
+
+    /// Runs a prompt on a provider of `config.toml`.
+    pub fn run(
+        &self,
+        id: &str,
+        vars: HashMap<String, String>,
+        backend: &str,
+    ) -> Result<String, String> {
+        let registry = self.store.config_registry().map_err(|e| e.to_string())?;
+        let llm = registry.backends.get(backend).ok_or_else(|| {
+            format!(
+                "Unknown backend '{}': use a provider name from config.toml",
+                backend
+            )
+        })?;
+        let output = self
+            .runtime
+            .block_on(self.store.prompt(id).vars(vars).backend(llm.as_ref()).run())
+            .map_err(|e| e.to_string())?;
+        Ok(prompt_text(output))
+    }
+}
+
+fn prompt_text(output: RunOutput) -> String {
+    match output {
+        RunOutput::Prompt(text) => text,
+        RunOutput::Chain(_) => String::new(),
+    }
+}
+
+/// Unlocks the store. `password` may be NULL when the key isn't protected by one.
+/// Returns NULL on failure; free the store with `ps_store_free`.
+///
+/// # Safety
+///
+/// `password` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ps_store_open(password: *const c_char) -> *mut StoreHandle {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let password = if password.is_null() {
+            None
+        } else {
+            Some(read_str(password)?)
+        };
+        StoreHandle::open(password)
+    }));
+    match flatten(result) {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Locks and frees a store opened with `ps_store_open`.
+///
+/// # Safety
+///
+/// `store` must be NULL or a store returned by `ps_store_open`, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ps_store_free(store: *mut StoreHandle) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// Returns a prompt, by ID or title, as a JSON object.
+///
+/// # Safety
+///
+/// `store` must come from `ps_store_open`, and `id` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ps_prompt_find(
+    store: *const StoreHandle,
+    id: *const c_char,
+) -> *mut c_char {
+    call(store, |store| store.find(read_str(id)?))
+}
+
+/// Renders a prompt with the variables of `vars_json`, a JSON object of strings, or
+/// NULL for none.
+///
+/// # Safety
+///
+/// `store` must come from `ps_store_open`; `id` and `vars_json`, when not NULL, must be
+/// valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn ps_prompt_render(
+    store: *const StoreHandle,
+    id: *const c_char,
+    vars_json: *const c_char,
+) -> *mut c_char {
+    call(store, |store| {
+        store.render(read_str(id)?, read_vars(vars_json)?)
+    })
+}
+
+/// Runs a prompt with the variables of `vars_json` on `backend`, a provider name from
+/// `config.toml`, and returns the answer.
+///
+/// # Safety
+///
+/// Same as `ps_prompt_render`; `backend` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ps_prompt_run(
+    store: *const StoreHandle,
+    id: *const c_char,
+    vars_json: *const c_char,
+    backend: *const c_char,
+) -> *mut c_char {
+    call(store, |store| {
+        store.run(read_str(id)?, read_vars(vars_json)?, read_str(backend)?)
+    })
+}
+
+/// Returns why the last call failed on this thread, or NULL. The string stays valid
+/// until the next failing call on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn ps_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by a `ps_prompt_*` function, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ps_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Runs `f` on a store and returns its result as an owned C string, recording errors
+/// and panics instead of letting them cross the ABI.
+unsafe fn call(
+    store: *const StoreHandle,
+    f: impl FnOnce(&StoreHandle) -> Result<String, String>,
+) -> *mut c_char {
+    let result = match store.as_ref() {
+        Some(store) => flatten(catch_unwind(AssertUnwindSafe(|| f(store)))),
+        None => Err("The store is NULL".to_string()),
+    };
+    match result.and_then(|text| CString::new(text).map_err(|e| e.to_string())) {
+        Ok(text) => text.into_raw(),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn flatten<T>(result: std::thread::Result<Result<T, String>>) -> Result<T, String> {
+    result.unwrap_or_else(|_| Err("Internal error (panic)".to_string()))
+}
+
+unsafe fn read_str<'a>(s: *const c_char) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err("Unexpected NULL string".to_string());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| "String is not valid UTF-8".to_string())
+}
+
+unsafe fn read_vars(vars_json: *const c_char) -> Result<HashMap<String, String>, String> {
+    if vars_json.is_null() {
+        return Ok(HashMap::new());
+    }
+    serde_json::from_str(read_str(vars_json)?)
+        .map_err(|e| format!("Variables must be a JSON object of strings: {}", e))
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
@@ -57,10 +57,23 @@
 //! ```
 
 pub mod api;
+#[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(feature = "cli")]
 pub mod commands;
 pub mod core;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "cli")]
 pub mod ui;
 
 // Main library entry points
-pub use api::{PromptStore, RunError, RunOutput, StoreError};
+pub use api::{
+    redact, AggregateStrategy, ChainRunner, ContextBudget, Guard, GuardAction, PromptStore,
+    RedactionPolicy, RefineIteration, Risk, RiskKind, RunError, RunMetadata, RunOutput, Sink,
+    StoreError, VarSources,
+};
+pub use core::cipher::{CipherBackend, StoreCipher};
+pub use core::storage::PromptData;
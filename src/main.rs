@@ -1,7 +1,11 @@
 use clap::Parser;
-use prompt_store::cli::Cli;
-use prompt_store::commands::dispatch;
+use prompt_store::cli::{Cli, Cmd, KeyCmd};
+use prompt_store::commands::{dispatch, init, interactive, key, update_check};
+#[cfg(feature = "grpc")]
+use prompt_store::commands::remote;
 use prompt_store::core::storage::AppCtx;
+use prompt_store::ui::{logging, output};
+use prompt_store::ui::symbols::BULLET;
 
 pub mod cli;
 
@@ -9,7 +13,7 @@ pub mod cli;
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
-        eprintln!("• {}", e);
+        eprintln!("{} {}", BULLET, e);
         std::process::exit(1);
     }
 }
@@ -17,6 +21,29 @@ async fn main() {
 /// Initializes context and runs the CLI application.
 async fn run() -> Result<(), String> {
     let cli = Cli::parse();
+    output::configure(&cli.output);
+    logging::init(&cli.log);
+    // A remote store is unlocked by its server, so the local key is not needed.
+    #[cfg(feature = "grpc")]
+    if let Some(url) = &cli.remote.remote {
+        return remote::run(url, cli.remote.remote_token.as_deref(), cli.command).await;
+    }
+    // Recovery must not need the key it replaces, which may be lost or locked.
+    if let Cmd::Key(KeyCmd::Recover { shares, yes }) = &cli.command {
+        return key::recover::run(shares, *yes);
+    }
+    // The wizard creates the key that opening the store would otherwise generate.
+    if let Cmd::Init = cli.command {
+        return init::run().await;
+    }
     let ctx = AppCtx::init()?;
-    dispatch(cli.command, &ctx).await
-}
\ No newline at end of file
+    if let Cmd::Interactive = cli.command {
+        return interactive::run(ctx, cli.output).await;
+    }
+    let check_packs = !matches!(cli.command, Cmd::Update { .. });
+    dispatch(cli.command, &ctx).await?;
+    if check_packs {
+        update_check::notify_stale_packs(&ctx).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Python module `prompt_store`, built with maturin and the `python` feature.
+//!
+//! ```python
+//! import prompt_store
+//!
+//! store = prompt_store.PromptStore(password="my-secret")
+//! print(store.render("greeting", {"name": "Alice"}))
+//! print(store.run("summarize", {"text": "..."}, backend="openai"))
+//! ```
+
+// The wrappers that `#[pymethods]` generates outside the impl convert `PyErr` into
+// itself (pyo3 0.22), so an allow on the impl doesn't reach them.
+#![allow(clippy::useless_conversion)]
+
+use crate::ffi::StoreHandle;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+/// An unlocked prompt store.
+#[pyclass(name = "PromptStore")]
+struct PyPromptStore {
+    handle: StoreHandle,
+}
+
+#[pymethods]
+impl PyPromptStore {
+    /// Unlocks the store; `password` may be omitted when the key isn't protected by one.
+    #[new]
+    #[pyo3(signature = (password=None))]
+    fn new(password: Option<&str>) -> PyResult<Self> {
+        let handle = StoreHandle::open(password).map_err(PyRuntimeError::new_err)?;
+        Ok(Self { handle })
+    }
+
+    /// Returns a prompt, by ID or title, as a dict.
+    fn find(&self, py: Python<'_>, id: &str) -> PyResult<PyObject> {
+        let json = self.handle.find(id).map_err(PyRuntimeError::new_err)?;
+        Ok(py
+            .import_bound("json")?
+            .call_method1("loads", (json,))?
+            .unbind())
+    }
+
+    /// Renders a prompt with its variables.
+    #[pyo3(signature = (id, vars=None))]
+    fn render(
+        &self,
+        py: Python<'_>,
+        id: &str,
+        vars: Option<HashMap<String, String>>,
+    ) -> PyResult<String> {
+        py.allow_threads(|| self.handle.render(id, vars.unwrap_or_default()))
+            .map_err(PyRuntimeError::new_err)
+    }
+
+    /// Runs a prompt on `backend`, a provider name from config.toml, and returns the answer.
+    #[pyo3(signature = (id, vars=None, *, backend))]
+    fn run(
+        &self,
+        py: Python<'_>,
+        id: &str,
+        vars: Option<HashMap<String, String>>,
+        backend: &str,
+    ) -> PyResult<String> {
+        py.allow_threads(|| self.handle.run(id, vars.unwrap_or_default(), backend))
+            .map_err(PyRuntimeError::new_err)
+    }
+}
+
+#[pymodule]
+fn prompt_store(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyPromptStore>()
+}
@@ -0,0 +1,163 @@
+//! Logger of the CLI: records selected by `-v` on stderr, and with `--log-file` (or
+//! `[logging] file = true`) JSON lines in `logs/prompt-store.log` in the store, rotated
+//! past 1 MiB.
+
+use crate::cli::LogArgs;
+use crate::core::config::load_log_file;
+use crate::core::logging::set_log_content;
+use crate::core::paths::store_dir;
+use crate::core::utils::{ensure_dir, restrict_permissions};
+use chrono::Utc;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Name of the log file in the `logs` directory of the store.
+const LOG_FILE: &str = "prompt-store.log";
+
+/// Size past which the log file is rotated.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Rotated files kept, as `prompt-store.log.1` (the newest) to `.3`.
+const KEPT_FILES: usize = 3;
+
+struct Logger {
+    stderr: LevelFilter,
+    file: Option<Mutex<LogFile>>,
+    file_level: LevelFilter,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+/// Installs the logger for the flags of the command. Content logging is enabled only by
+/// `-vvv --unsafe-log`.
+pub fn init(args: &LogArgs) {
+    let stderr = match args.verbose {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    set_log_content(args.verbose >= 3 && args.unsafe_log);
+
+    // A broken config.toml is reported by the command itself.
+    let file = if args.log_file || load_log_file().unwrap_or(false) {
+        match LogFile::open() {
+            Ok(file) => Some(Mutex::new(file)),
+            Err(e) => {
+                eprintln!("Log file disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // The file keeps what is needed to diagnose a failure even without -v.
+    let file_level = match file {
+        Some(_) => stderr.max(LevelFilter::Debug),
+        None => LevelFilter::Off,
+    };
+
+    let logger = Logger {
+        stderr,
+        file,
+        file_level,
+    };
+    let max_level = logger.stderr.max(logger.file_level);
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Records of dependencies are only wanted when something goes wrong.
+        let ours = metadata.target().starts_with("prompt_store");
+        (ours || metadata.level() <= Level::Warn)
+            && metadata.level() <= self.stderr.max(self.file_level)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() <= self.stderr {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+        if let (Some(file), true) = (&self.file, record.level() <= self.file_level) {
+            let line = serde_json::json!({
+                "at": Utc::now().to_rfc3339(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let Ok(mut file) = file.lock() {
+                file.write_line(&line.to_string());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            file.file.flush().ok();
+        }
+    }
+}
+
+impl LogFile {
+    fn open() -> Result<Self, String> {
+        let dir = store_dir()?.join("logs");
+        ensure_dir(&dir)?;
+        let path = dir.join(LOG_FILE);
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let mut log = Self {
+            file: append(&path)?,
+            path,
+            size,
+        };
+        if log.size >= MAX_SIZE {
+            log.rotate()?;
+        }
+        Ok(log)
+    }
+
+    /// Writes a line, rotating the file once it is too large. Write failures are
+    /// ignored: logging must never make a command fail.
+    fn write_line(&mut self, line: &str) {
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.size += line.len() as u64 + 1;
+        }
+        if self.size >= MAX_SIZE {
+            self.rotate().ok();
+        }
+    }
+
+    /// Shifts `prompt-store.log.N` to `.N+1`, dropping the oldest, and starts a new file.
+    fn rotate(&mut self) -> Result<(), String> {
+        let rotated = |n: usize| self.path.with_extension(format!("log.{}", n));
+        fs::remove_file(rotated(KEPT_FILES)).ok();
+        for n in (1..KEPT_FILES).rev() {
+            fs::rename(rotated(n), rotated(n + 1)).ok();
+        }
+        fs::rename(&self.path, rotated(1)).map_err(|e| format!("Write error: {}", e))?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> Result<File, String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    restrict_permissions(path);
+    Ok(file)
+}
@@ -1,6 +1,35 @@
+pub mod logging;
+pub mod output;
+pub mod progress;
+pub mod symbols;
+
+use crate::cli::RemoveArgs;
+use console::style;
 use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
 
 /// Return default theme.
 pub fn theme() -> ColorfulTheme {
     ColorfulTheme::default()
 }
+
+/// Asks to confirm removing `what`, once the command has listed it, unless `--force` is
+/// set. Returns false for a dry run or when declined, saying so.
+pub fn confirm_removal(args: RemoveArgs, what: &str) -> Result<bool, String> {
+    if args.dry_run {
+        println!("{}", style("Dry run: nothing was removed.").yellow());
+        return Ok(false);
+    }
+    if args.force {
+        return Ok(true);
+    }
+    let confirmed = Confirm::new()
+        .with_prompt(format!("Remove {}?", what))
+        .default(false)
+        .interact()
+        .map_err(|e| format!("{} (use --force to remove without confirmation)", e))?;
+    if !confirmed {
+        println!("Removal cancelled.");
+    }
+    Ok(confirmed)
+}
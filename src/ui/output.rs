@@ -0,0 +1,82 @@
+//! How the CLI output is shown: colors, paging of long listings and truncation of long
+//! lines to the terminal width, set once from the global flags and the environment.
+
+use super::symbols::ELLIPSIS;
+use crate::cli::OutputArgs;
+use console::{truncate_str, Term};
+use std::borrow::Cow;
+use std::env;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static PAGER: AtomicBool = AtomicBool::new(true);
+static FULL: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Applies the output flags of a command. Colors are also disabled by a non-empty
+/// `NO_COLOR`, and when the output is not a terminal.
+pub fn configure(args: &OutputArgs) {
+    // What the terminal supports, before any flag changed it.
+    static COLORS: OnceLock<(bool, bool)> = OnceLock::new();
+    let (stdout, stderr) =
+        *COLORS.get_or_init(|| (console::colors_enabled(), console::colors_enabled_stderr()));
+    let color = !args.no_color && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    console::set_colors_enabled(stdout && color);
+    console::set_colors_enabled_stderr(stderr && color);
+    PAGER.store(!args.no_pager, Ordering::Relaxed);
+    FULL.store(args.full, Ordering::Relaxed);
+    QUIET.store(args.quiet, Ordering::Relaxed);
+}
+
+/// Whether `--quiet` is set.
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Truncates a line to the width of the terminal, unless `--full` is set or the output
+/// is not a terminal.
+pub fn fit(line: &str) -> Cow<'_, str> {
+    let term = Term::stdout();
+    if FULL.load(Ordering::Relaxed) || !term.is_term() {
+        return Cow::Borrowed(line);
+    }
+    truncate_str(line, term.size().1 as usize, ELLIPSIS.as_str())
+}
+
+/// Prints a listing, through `$PAGER` (or `less -R`) when it does not fit in the
+/// terminal, unless `--no-pager` is set.
+pub fn print_paged(text: &str) {
+    if !PAGER.load(Ordering::Relaxed) || !page(text) {
+        print!("{}", text);
+    }
+}
+
+/// Shows `text` through the pager when it does not fit in the terminal. Returns false
+/// when it should be printed directly instead.
+fn page(text: &str) -> bool {
+    let term = Term::stdout();
+    if !term.is_term() || text.lines().count() < term.size().0 as usize {
+        return false;
+    }
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    let Some((program, args)) = shell_words::split(&pager)
+        .ok()
+        .and_then(|words| words.split_first().map(|(p, a)| (p.clone(), a.to_vec())))
+    else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may be closed before reading everything.
+        stdin.write_all(text.as_bytes()).ok();
+    }
+    child.wait().is_ok()
+}
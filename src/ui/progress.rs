@@ -0,0 +1,21 @@
+//! Progress bars of bulk operations, drawn on stderr while it is a terminal and hidden
+//! by `--quiet`.
+
+use super::output::quiet;
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Starts a progress bar over `len` items, showing their count and the time left.
+/// Messages printed while it runs go through [`ProgressBar::suspend`] or
+/// [`ProgressBar::println`], so they are not mixed with it.
+pub fn bar(len: usize, message: &str) -> ProgressBar {
+    if quiet() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64).with_message(message.to_string());
+    if let Ok(style) =
+        ProgressStyle::with_template("{msg} [{bar:30.green}] {pos}/{len} (ETA {eta})")
+    {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+}
@@ -0,0 +1,71 @@
+//! Status symbols of the CLI output, with ASCII fallbacks for terminals that cannot
+//! show them (which would otherwise print mojibake such as `âœ”`).
+
+use console::Term;
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A symbol printed as unicode when the terminal supports it, as ASCII otherwise.
+pub struct Symbol {
+    unicode: &'static str,
+    ascii: &'static str,
+}
+
+impl Symbol {
+    const fn new(unicode: &'static str, ascii: &'static str) -> Self {
+        Self { unicode, ascii }
+    }
+
+    /// The variant of the symbol the terminal can show.
+    pub fn as_str(&self) -> &'static str {
+        if unicode() {
+            self.unicode
+        } else {
+            self.ascii
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Success.
+pub const OK: Symbol = Symbol::new("✔", "+");
+/// Failure.
+pub const ERR: Symbol = Symbol::new("✘", "x");
+/// Information, or an item of a list.
+pub const BULLET: Symbol = Symbol::new("•", "*");
+/// Something to check.
+pub const WARN: Symbol = Symbol::new("⚠", "!");
+/// An item nested under the previous line.
+pub const BRANCH: Symbol = Symbol::new("└─", "`-");
+/// From one value to another.
+pub const ARROW: Symbol = Symbol::new("→", "->");
+/// The end of a truncated text.
+pub const ELLIPSIS: Symbol = Symbol::new("…", "...");
+
+/// Whether the terminal shows unicode symbols, decided once.
+fn unicode() -> bool {
+    static UNICODE: OnceLock<bool> = OnceLock::new();
+    *UNICODE.get_or_init(|| {
+        if cfg!(windows) {
+            return Term::stdout().features().wants_emoji();
+        }
+        // The locale tells how the terminal decodes the output; none set is a modern
+        // default, UTF-8.
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()));
+        match locale {
+            Some(locale) => {
+                let locale = locale.to_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            }
+            None => true,
+        }
+    })
+}